@@ -69,17 +69,26 @@ export function ConversationScreen() {
       try {
         const { listen } = await import('@tauri-apps/api/event');
 
+        // Only add messages that belong to this thread
+        const addIncoming = (incoming: MessageType[]) => {
+          const mine = incoming.filter(m => m.thread_id === threadId);
+          if (mine.length === 0) return;
+          setMessages((prev) => {
+            // Avoid duplicates
+            const fresh = mine.filter(m => !prev.some(p => p.id === m.id));
+            return fresh.length ? [...prev, ...fresh] : prev;
+          });
+        };
+
         const unlistenNewMsg = await listen<MessageType>('new_message', (event) => {
           console.log('New message received:', event.payload);
+          addIncoming([event.payload]);
+        });
 
-          // Only add if it belongs to this thread
-          if (event.payload.thread_id === threadId) {
-            setMessages((prev) => {
-              // Avoid duplicates
-              if (prev.some(m => m.id === event.payload.id)) return prev;
-              return [...prev, event.payload];
-            });
-          }
+        // Several messages that arrived together
+        const unlistenBatch = await listen<MessageType[]>('messages_batch', (event) => {
+          console.log('Message batch received:', event.payload.length);
+          addIncoming(event.payload);
         });
 
         const unlistenSynced = await listen<any>('message_synced', (event) => {
@@ -113,6 +122,7 @@ export function ConversationScreen() {
 
         unlisten = () => {
           unlistenNewMsg();
+          unlistenBatch();
           unlistenSynced();
         };
 
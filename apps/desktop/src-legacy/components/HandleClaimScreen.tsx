@@ -5,7 +5,7 @@
 import { useState } from 'react';
 import { useNavigate } from 'react-router-dom';
 import { ArrowLeft, Check, X, Sparkles, Loader2 } from 'lucide-react';
-import { checkHandleAvailable, claimHandle, useBreadcrumbStatus } from '@gns/api-tauri';
+import { checkHandleAvailable, claimHandle, useBreadcrumbStatus, commandErrorMessage } from '@gns/api-tauri';
 import { useDebounce } from '../hooks/useDebounce';
 import { useEffect } from 'react';
 import clsx from 'clsx';
@@ -44,7 +44,7 @@ export function HandleClaimScreen() {
         reason: result.reason,
       });
     } catch (e) {
-      setError(e instanceof Error ? e.message : 'Failed to check availability');
+      setError(commandErrorMessage(e));
     } finally {
       setChecking(false);
     }
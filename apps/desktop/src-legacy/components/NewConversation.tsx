@@ -6,7 +6,7 @@ import { useState, useEffect } from 'react';
 import { useNavigate, useSearchParams } from 'react-router-dom';
 import { ArrowLeft, Search, Loader2, User } from 'lucide-react';
 import { invoke } from '@tauri-apps/api/core';
-import { getPublicKey } from '@gns/api-tauri';
+import { getThreadId, commandErrorMessage } from '@gns/api-tauri';
 
 interface HandleInfo {
   public_key: string;
@@ -52,7 +52,7 @@ export function NewConversation() {
         setError(`@${cleanHandle} not found`);
       }
     } catch (e) {
-      setError(e instanceof Error ? e.message : 'Search failed');
+      setError(commandErrorMessage(e));
     } finally {
       setSearching(false);
     }
@@ -61,20 +61,15 @@ export function NewConversation() {
   const startConversation = async () => {
     if (!result) return;
 
-    // Get local identity to form canonical thread ID
-    const myPk = await getPublicKey();
-    if (!myPk) {
-      setError("Could not get local public key");
+    // Same id the backend files this conversation's messages under
+    let threadId: string;
+    try {
+      threadId = await getThreadId(result.public_key);
+    } catch (e) {
+      setError(e instanceof Error ? e.message : 'Could not open conversation');
       return;
     }
 
-    // Create a thread ID based on sorted public keys (deterministic)
-    // Must match Rust backend format: direct_{sorted_keys}[0..32]
-    // Ensure lowercase to match Rust hex output and sort order
-    const keys = [myPk.toLowerCase(), result.public_key.toLowerCase()].sort();
-    const joined = keys.join('_');
-    const threadId = `direct_${joined.substring(0, 32)}`;
-
     navigate(`/messages/${threadId}`, {
       state: {
         recipientPublicKey: result.public_key,
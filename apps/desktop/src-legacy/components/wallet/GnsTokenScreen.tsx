@@ -3,6 +3,7 @@
 
 import { useState, useEffect, useCallback } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { commandErrorMessage } from '@gns/api-tauri';
 import {
   ArrowLeft,
   RefreshCw,
@@ -64,7 +65,7 @@ export function GnsTokenScreen({ onBack }: GnsTokenScreenProps) {
       const data = await invoke<StellarBalances>('get_stellar_balances');
       setBalances(data);
     } catch (e) {
-      setError(e instanceof Error ? e.message : String(e));
+      setError(commandErrorMessage(e));
     } finally {
       setLoading(false);
     }
@@ -102,7 +103,7 @@ export function GnsTokenScreen({ onBack }: GnsTokenScreenProps) {
       setLastClaimResult({
         success: false,
         hash: null,
-        error: e instanceof Error ? e.message : String(e),
+        error: commandErrorMessage(e),
         message: null,
       });
     } finally {
@@ -124,7 +125,7 @@ export function GnsTokenScreen({ onBack }: GnsTokenScreenProps) {
         setError(result.error || 'Failed to fund account');
       }
     } catch (e) {
-      setError(e instanceof Error ? e.message : String(e));
+      setError(commandErrorMessage(e));
     } finally {
       setFunding(false);
     }
@@ -143,7 +144,7 @@ export function GnsTokenScreen({ onBack }: GnsTokenScreenProps) {
         setError(result.error || 'Failed to create trustline');
       }
     } catch (e) {
-      setError(e instanceof Error ? e.message : String(e));
+      setError(commandErrorMessage(e));
     } finally {
       setCreatingTrustline(false);
     }
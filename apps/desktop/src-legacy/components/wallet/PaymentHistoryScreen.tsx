@@ -3,6 +3,7 @@
 
 import { useState, useEffect, useCallback } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { commandErrorMessage } from '@gns/api-tauri';
 import {
   ArrowLeft,
   RefreshCw,
@@ -58,7 +59,7 @@ export function PaymentHistoryScreen({ onBack }: PaymentHistoryScreenProps) {
       const balances = await invoke<{ use_testnet: boolean }>('get_stellar_balances');
       setUseTestnet(balances.use_testnet);
     } catch (e) {
-      setError(e instanceof Error ? e.message : String(e));
+      setError(commandErrorMessage(e));
     } finally {
       setLoading(false);
     }
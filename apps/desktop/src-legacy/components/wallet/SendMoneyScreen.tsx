@@ -3,6 +3,7 @@
 
 import { useState, useEffect, useCallback } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { estimateSendFee, FeeEstimate, commandErrorMessage } from '@gns/api-tauri';
 import {
   ArrowLeft,
   User,
@@ -162,7 +163,7 @@ export function SendMoneyScreen({ onBack, prefillRecipient, prefillAmount }: Sen
       setSendResult({
         success: false,
         hash: null,
-        error: e instanceof Error ? e.message : String(e),
+        error: commandErrorMessage(e),
         message: null,
       });
       setStep('result');
@@ -430,6 +431,16 @@ function ConfirmStep({
     : `${resolvedIdentity.public_key.substring(0, 16)}...`;
   const initial = resolvedIdentity.handle?.[0].toUpperCase() || '?';
 
+  // A plain send is one payment operation
+  const [fee, setFee] = useState<FeeEstimate | null>(null);
+  useEffect(() => {
+    let cancelled = false;
+    estimateSendFee(1)
+      .then((estimate) => { if (!cancelled) setFee(estimate); })
+      .catch((e) => console.warn('Fee estimate failed:', e));
+    return () => { cancelled = true; };
+  }, []);
+
   return (
     <div className="p-5 flex flex-col items-center">
       {/* Amount Display */}
@@ -472,6 +483,14 @@ function ConfirmStep({
             <div className="text-xs text-slate-400">
               {currency === 'GNS' ? 'GNS Token Transfer' : 'Native XLM Transfer'}
             </div>
+            {fee && (
+              <div className="text-xs text-slate-400 mt-1">
+                Network fee: ~{fee.fee_xlm} XLM
+                {fee.from_network && fee.recommended_fee_stroops > fee.fee_stroops && (
+                  <> (recommended for current congestion: {fee.recommended_fee_xlm} XLM)</>
+                )}
+              </div>
+            )}
           </div>
         </div>
       </div>
@@ -10,6 +10,7 @@
 
 import { useState, useCallback, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { commandErrorMessage } from '@gns/api-tauri';
 
 // ============================================================================
 // TYPES
@@ -73,7 +74,7 @@ export function useStellar() {
       setBalance(result);
     } catch (e) {
       console.error('Failed to fetch balance:', e);
-      setError(String(e));
+      setError(commandErrorMessage(e));
     } finally {
       setLoading(false);
     }
@@ -139,7 +140,7 @@ export function useSendGns() {
       setResult(res);
       return res;
     } catch (e: any) {
-      const errRes = { success: false, hash: null, error: String(e), message: null };
+      const errRes = { success: false, hash: null, error: commandErrorMessage(e), message: null };
       setResult(errRes);
       return errRes;
     } finally {
@@ -159,7 +160,7 @@ export function useCreateGnsTrustline() {
       const res = await invoke<TransactionResponse>('create_gns_trustline');
       return res;
     } catch (e) {
-      return { success: false, hash: null, error: String(e), message: null };
+      return { success: false, hash: null, error: commandErrorMessage(e), message: null };
     } finally {
       setCreating(false);
     }
@@ -177,7 +178,7 @@ export function useClaimBalance() {
       const res = await invoke<TransactionResponse>('claim_gns_tokens');
       return res;
     } catch (e) {
-      return { success: false, hash: null, error: String(e), message: null };
+      return { success: false, hash: null, error: commandErrorMessage(e), message: null };
     } finally {
       setClaiming(false);
     }
@@ -1,5 +1,6 @@
 import { invoke } from '@tauri-apps/api/core';
-import { DixPost, DixMedia, DixPostData, DixUserData } from '../types/dix';
+import { listen, UnlistenFn } from '@tauri-apps/api/event';
+import { DixPost, DixMedia, DixPostData, DixTagPage, DixUserData, EngagementUpdate, TrendingTag } from '../types/dix';
 import { isTauriApp } from '@gns/api-tauri';
 
 export const DixApi = {
@@ -19,6 +20,14 @@ export const DixApi = {
         });
     },
 
+    /** Posts newer than a post id or unix-ms timestamp, newest first */
+    getNewerPosts: async (since: string | number, limit: number = 50): Promise<DixPost[]> => {
+        return invoke<DixPost[]>('get_timeline', {
+            limit,
+            since
+        });
+    },
+
     getPost: async (id: string): Promise<DixPostData> => {
         return invoke<DixPostData>('get_post', { id });
     },
@@ -27,6 +36,16 @@ export const DixApi = {
         return invoke<DixUserData>('get_posts_by_user', { publicKey });
     },
 
+    /** Most used tags over the last `windowHours` (24 by default) */
+    getTrendingTags: async (windowHours?: number): Promise<TrendingTag[]> => {
+        return invoke<TrendingTag[]>('get_trending_tags', { windowHours });
+    },
+
+    /** Posts carrying a tag; pass the previous page's nextCursor to continue */
+    getPostsByTag: async (tag: string, cursor?: string): Promise<DixTagPage> => {
+        return invoke<DixTagPage>('get_posts_by_tag', { tag, cursor });
+    },
+
     likePost: async (id: string): Promise<void> => {
         if (isTauriApp()) {
             return invoke('like_post', { id });
@@ -41,5 +60,21 @@ export const DixApi = {
         }
         // TODO: Web implementation
         console.warn('repostPost not implemented on web yet');
+    },
+
+    /** Keep counts of these posts live; changes arrive via onEngagementUpdated */
+    watchEngagement: async (postIds: string[]): Promise<void> => {
+        if (!isTauriApp()) return;
+        return invoke('watch_post_engagement', { postIds });
+    },
+
+    /** Stop watching these posts, or all posts when omitted */
+    stopWatchingEngagement: async (postIds?: string[]): Promise<void> => {
+        if (!isTauriApp()) return;
+        return invoke('stop_watching_engagement', { postIds });
+    },
+
+    onEngagementUpdated: (handler: (update: EngagementUpdate) => void): Promise<UnlistenFn> => {
+        return listen<EngagementUpdate>('engagement_updated', (event) => handler(event.payload));
     }
 };
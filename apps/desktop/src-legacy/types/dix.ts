@@ -35,6 +35,15 @@ export interface DixPostEngagement {
     views: number;
 }
 
+/** Payload of the `engagement_updated` event */
+export interface EngagementUpdate {
+    post_id: string;
+    likes: number;
+    reposts: number;
+    replies: number;
+    quotes: number;
+}
+
 export interface DixPostMeta {
     signature: string;
     trustScoreAtPost: number;
@@ -67,3 +76,12 @@ export interface DixUserData {
     user: DixPostAuthor;
     posts: DixPost[];
 }
+export interface TrendingTag {
+    /** Lowercase, without '#' */
+    tag: string;
+    count: number;
+}
+export interface DixTagPage {
+    posts: DixPost[];
+    nextCursor?: string | null;
+}
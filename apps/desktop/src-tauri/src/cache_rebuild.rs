@@ -0,0 +1,203 @@
+//! Decrypted Cache Rebuild
+//!
+//! Opens every stored envelope addressed to the identity again and puts
+//! what it decrypts to back in the message's cached payload (see
+//! `storage::decrypted_cache`). Runs after the encryption key changes, when
+//! a cached payload may no longer match what the envelope holds.
+//!
+//! Envelopes are worked through in batches, taking the identity and
+//! database locks for one batch at a time, so sending and reading go on
+//! while a long history is rebuilt.
+
+use crate::crypto::IdentityManager;
+use crate::message_handler::decode_payload;
+use crate::storage::{CacheRepair, Database, StoredEnvelope};
+use gns_crypto_core::{open_envelope_with_cache, GnsEnvelope, GnsIdentity, MessageKeyCache, CRYPTO_VERSION_2};
+use serde::Serialize;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Mutex;
+
+/// Tauri event carrying a [`CacheRebuildReport`] after each batch
+pub const CACHE_REBUILD_PROGRESS_EVENT: &str = "decrypted_cache_progress";
+
+/// Envelopes opened per batch
+const BATCH_SIZE: u32 = 200;
+
+/// Counts of a rebuild, so far or in total
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CacheRebuildReport {
+    /// Received messages with a stored envelope
+    pub total: u64,
+    pub processed: u64,
+    /// Cached payloads that were wrong and have been replaced
+    pub rebuilt: u64,
+    /// Envelopes that no longer open; their messages are marked
+    pub failed: u64,
+    /// Envelopes addressed to another identity, or sent in a forward
+    /// secrecy session whose keys are gone by design; left alone
+    pub skipped: u64,
+}
+
+/// Rebuild the decrypted cache of `identity_pk`'s messages, calling
+/// `progress` after each batch. Stops with an error if `identity_pk`
+/// isn't, or stops being, the active identity.
+pub async fn rebuild(
+    identity: &Mutex<IdentityManager>,
+    database: &Mutex<Database>,
+    identity_pk: &str,
+    mut progress: impl FnMut(&CacheRebuildReport),
+) -> Result<CacheRebuildReport, String> {
+    let mut report = CacheRebuildReport {
+        total: database.lock().await.count_stored_envelopes().map_err(|e| e.to_string())?,
+        ..Default::default()
+    };
+
+    let mut after_rowid = 0;
+    loop {
+        let batch = database
+            .lock()
+            .await
+            .stored_envelopes(after_rowid, BATCH_SIZE)
+            .map_err(|e| e.to_string())?;
+        let Some(last) = batch.last() else { break };
+        after_rowid = last.rowid;
+
+        let repairs = {
+            let identity_mgr = identity.lock().await;
+            let active = identity_mgr
+                .get_identity()
+                .filter(|active| active.public_key_hex().eq_ignore_ascii_case(identity_pk))
+                .ok_or_else(|| format!("{} is not the active identity", identity_pk))?;
+            reopen(active, &identity_mgr.message_keys(), &batch)
+        };
+
+        report.processed += batch.len() as u64;
+        report.skipped += (batch.len() - repairs.len()) as u64;
+        report.failed += repairs.iter().filter(|(_, repair)| matches!(repair, CacheRepair::Failed)).count() as u64;
+        report.rebuilt += database
+            .lock()
+            .await
+            .repair_decrypted_cache(&repairs)
+            .map_err(|e| e.to_string())? as u64;
+        progress(&report);
+    }
+    Ok(report)
+}
+
+/// Rebuild in the background, reporting progress to the UI. Used after
+/// the encryption key changes.
+pub fn rebuild_in_background(
+    app: AppHandle,
+    identity: Arc<Mutex<IdentityManager>>,
+    database: Arc<Mutex<Database>>,
+    identity_pk: String,
+) {
+    tauri::async_runtime::spawn(async move {
+        let emit = |report: &CacheRebuildReport| emit_progress(&app, report);
+        match rebuild(&identity, &database, &identity_pk, emit).await {
+            Ok(report) => tracing::info!(
+                "🔐 Decrypted cache rebuilt: {} replaced, {} undecryptable of {}",
+                report.rebuilt,
+                report.failed,
+                report.total
+            ),
+            Err(e) => tracing::warn!("⚠️ Decrypted cache rebuild stopped: {}", e),
+        }
+    });
+}
+
+/// Send `report` to the UI as a progress event
+pub fn emit_progress(app: &AppHandle, report: &CacheRebuildReport) {
+    if let Err(e) = app.emit(CACHE_REBUILD_PROGRESS_EVENT, report) {
+        tracing::error!("Failed to emit {} event: {}", CACHE_REBUILD_PROGRESS_EVENT, e);
+    }
+}
+
+/// Open each envelope addressed to `identity`. Envelopes for anyone else,
+/// and ratcheted ones, are left out.
+fn reopen(identity: &GnsIdentity, message_keys: &MessageKeyCache, batch: &[StoredEnvelope]) -> Vec<(String, CacheRepair)> {
+    let my_pk = identity.public_key_hex();
+    batch
+        .iter()
+        .filter_map(|stored| {
+            let envelope = match GnsEnvelope::from_json(&stored.envelope_json) {
+                Ok(envelope) => envelope,
+                Err(e) => {
+                    tracing::warn!("⚠️ Stored envelope for {} is unreadable: {}", stored.message_id, e);
+                    return Some((stored.message_id.clone(), CacheRepair::Failed));
+                }
+            };
+            if envelope.crypto_version == CRYPTO_VERSION_2
+                || !envelope.to_public_keys.iter().any(|to| to.eq_ignore_ascii_case(&my_pk))
+            {
+                return None;
+            }
+            let repair = match open_envelope_with_cache(identity, &envelope, message_keys) {
+                Ok(opened) => CacheRepair::Decrypted {
+                    payload: decode_payload(&opened.payload),
+                    signature_valid: opened.signature_valid,
+                },
+                Err(e) => {
+                    tracing::debug!("Envelope for {} no longer opens: {}", stored.message_id, e);
+                    CacheRepair::Failed
+                }
+            };
+            Some((stored.message_id.clone(), repair))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gns_crypto_core::{compute_thread_id, create_envelope_with_cache, Compression};
+
+    #[tokio::test]
+    async fn test_corrupted_cache_entry_is_rebuilt() {
+        let me = GnsIdentity::generate();
+        let peer = GnsIdentity::generate();
+        let (my_pk, peer_pk) = (me.public_key_hex(), peer.public_key_hex());
+        let thread_id = compute_thread_id(&my_pk, &peer_pk);
+        let keys = MessageKeyCache::default();
+        let seal = |text: &str, to: &GnsIdentity| {
+            let payload = serde_json::to_vec(&serde_json::json!({ "text": text })).unwrap();
+            create_envelope_with_cache(&peer, None, &to.public_key_hex(), &to.encryption_key_hex(), "text/plain", &payload, None, None, Compression::Off, &keys)
+                .unwrap()
+        };
+
+        let mut db = Database::open_in_memory().unwrap();
+        let mut store = |id: &str, cached: &str, envelope: &GnsEnvelope, at: i64| {
+            let payload = serde_json::json!({ "text": cached });
+            db.save_received_message(id, &thread_id, &peer_pk, None, "text/plain", &payload, at, true, None).unwrap();
+            db.save_message_envelope(id, envelope).unwrap();
+        };
+        store("m1", "see you at 6", &seal("see you at 6", &me), 1_000);
+        // Cached from a garbled decryption
+        store("m2", "\u{fffd}\u{fffd}x", &seal("running late", &me), 2_000);
+        // Sealed to a key we don't hold, though addressed to us
+        let mut foreign = seal("not for us", &GnsIdentity::generate());
+        foreign.to_public_keys = vec![my_pk.clone()];
+        store("m3", "kept as is", &foreign, 3_000);
+        let database = Mutex::new(db);
+        let identity = Mutex::new(IdentityManager::from_identity(me));
+
+        let mut batches = 0;
+        let report = rebuild(&identity, &database, &my_pk, |_| batches += 1).await.unwrap();
+        assert_eq!(report, CacheRebuildReport { total: 3, processed: 3, rebuilt: 1, failed: 1, skipped: 0 });
+        assert_eq!(batches, 1);
+
+        let db = database.lock().await;
+        let text = |id: &str| db.get_message(id).unwrap().unwrap().payload["text"].as_str().unwrap().to_string();
+        assert_eq!(text("m1"), "see you at 6");
+        assert_eq!(text("m2"), "running late");
+        assert_eq!(text("m3"), "kept as is");
+        drop(db);
+
+        // Nothing left to repair
+        let again = rebuild(&identity, &database, &my_pk, |_| {}).await.unwrap();
+        assert_eq!(again.rebuilt, 0);
+
+        assert!(rebuild(&identity, &database, &peer_pk, |_| {}).await.is_err());
+    }
+}
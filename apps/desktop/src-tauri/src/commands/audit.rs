@@ -0,0 +1,84 @@
+//! Audit Log Commands
+//!
+//! Lets the user review, export and verify the security audit log kept
+//! in `storage::audit`.
+
+use crate::storage::{AuditAction, AuditEntry, Database, SignedAuditLog};
+use crate::AppState;
+use tauri::State;
+use tokio::sync::Mutex;
+
+/// Result of checking an exported audit log
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AuditLogVerification {
+    pub valid: bool,
+    pub public_key: String,
+    pub entries: usize,
+    pub error: Option<String>,
+}
+
+/// Append an audit entry for an action that succeeded (`Ok`) or failed.
+/// A failure to record is logged but never fails the action itself.
+pub(crate) async fn record<T, E: std::fmt::Display>(
+    database: &Mutex<Database>,
+    action: AuditAction,
+    target: Option<&str>,
+    outcome: &Result<T, E>,
+) {
+    let result = match outcome {
+        Ok(_) => "ok".to_string(),
+        Err(e) => e.to_string(),
+    };
+
+    let mut db = database.lock().await;
+    if let Err(e) = db.record_audit(action, target, &result) {
+        tracing::warn!("⚠️ Failed to record {} in audit log: {}", action.as_str(), e);
+    }
+}
+
+/// Append an audit entry for an action that can only succeed
+pub(crate) async fn record_ok(database: &Mutex<Database>, action: AuditAction, target: Option<&str>) {
+    record(database, action, target, &Ok::<(), String>(())).await;
+}
+
+/// Get audit entries, newest first, optionally only those at or after
+/// `since` (unix seconds)
+#[tauri::command]
+pub async fn get_audit_log(
+    limit: Option<u32>,
+    since: Option<i64>,
+    state: State<'_, AppState>,
+) -> Result<Vec<AuditEntry>, String> {
+    let db = state.database.lock().await;
+    db.get_audit_log(limit.unwrap_or(100), since)
+        .map_err(|e| e.to_string())
+}
+
+/// Export the whole audit log, hash-chained and signed with the identity key
+#[tauri::command]
+pub async fn export_audit_log(state: State<'_, AppState>) -> Result<SignedAuditLog, String> {
+    let entries = {
+        let db = state.database.lock().await;
+        db.get_audit_chain().map_err(|e| e.to_string())?
+    };
+
+    let identity = state.identity.lock().await;
+    let gns_identity = identity.get_identity().ok_or("No identity found")?;
+
+    // Refuses to sign a chain that is already broken locally
+    SignedAuditLog::sign(gns_identity, entries, chrono::Utc::now().timestamp())
+        .map_err(|e| format!("Local audit log is corrupted: {}", e))
+}
+
+/// Verify an exported audit log: chain intact, nothing added or removed,
+/// and signed by the key it names
+#[tauri::command]
+pub fn verify_audit_log(log: SignedAuditLog) -> AuditLogVerification {
+    let error = log.verify().err().map(|e| e.to_string());
+    AuditLogVerification {
+        valid: error.is_none(),
+        public_key: log.public_key,
+        entries: log.entries.len(),
+        error,
+    }
+}
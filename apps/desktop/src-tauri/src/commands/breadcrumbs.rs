@@ -1,3 +1,4 @@
+use crate::storage::breadcrumb_chain_hash;
 use crate::AppState;
 use tauri::State;
 use gns_crypto_core::Breadcrumb;
@@ -33,10 +34,14 @@ pub async fn get_breadcrumb_status(state: State<'_, AppState>) -> Result<Breadcr
     #[cfg(any(target_os = "ios", target_os = "android"))]
     let (strategy, collection_enabled) = {
         let collector = state.breadcrumb_collector.lock().await;
-        (
-            collector.current_strategy().to_string(),
-            collector.is_enabled(),
-        )
+        match collector.as_ref() {
+            Some(collector) => (
+                collector.current_strategy().to_string(),
+                collector.is_enabled(),
+            ),
+            // Services haven't started yet
+            None => ("inactive".to_string(), false),
+        }
     };
 
     #[cfg(not(any(target_os = "ios", target_os = "android")))]
@@ -98,7 +103,8 @@ pub async fn set_collection_enabled(
         drop(db); // Release lock before accessing collector
         
         // Update collector
-        let mut collector: tokio::sync::MutexGuard<'_, crate::location::BreadcrumbCollector> = state.breadcrumb_collector.lock().await;
+        let mut collector = state.breadcrumb_collector.lock().await;
+        let collector = collector.get_or_insert_with(crate::location::BreadcrumbCollector::new);
         if enabled {
             collector.start().map_err(|e| e.to_string())?;
         } else {
@@ -133,13 +139,7 @@ pub async fn drop_breadcrumb(
     // Get last breadcrumb hash for chain
     let mut db = state.database.lock().await;
     let recent = db.get_recent_breadcrumbs(1).map_err(|e| e.to_string())?;
-    let prev_hash = recent.first().map(|b| {
-        // Hash the previous breadcrumb
-        use sha2::{Sha256, Digest};
-        let mut hasher = Sha256::new();
-        hasher.update(format!("{}:{}:{}", b.h3_index, b.timestamp, b.signature));
-        hex::encode(hasher.finalize())
-    });
+    let prev_hash = recent.first().map(breadcrumb_chain_hash);
     
     // Create breadcrumb
     let breadcrumb = create_breadcrumb(
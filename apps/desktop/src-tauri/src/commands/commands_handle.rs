@@ -7,12 +7,18 @@ use tauri::State;
 use serde::Serialize;
 
 use crate::AppState;
-use crate::commands::handles::{validate_handle, HandleStatus, ClaimRequirements, canonical_json};
-use crate::network::{ApiClient, ClaimProof, HandleCheckResult, HandleReservationResult, HandleClaimResult};
-
-// ==================== Constants ====================
-
-const GNS_API_URL: &str = "https://gns-browser-production.up.railway.app";
+use crate::crypto::{GnsIdentity, SignatureDomain};
+use crate::devices::{self, DeviceList};
+use crate::commands::audit;
+use crate::commands::timeout::{with_timeout, CommandError};
+use crate::config::CommandCategory;
+use crate::storage::AuditAction;
+use crate::commands::handles::{
+    validate_handle, validate_record, HandleValidation, record_timestamp, HandleStatus, ClaimRequirements, RecordError, canonical_json,
+    check_release, release_message, HandleError,
+};
+use crate::record_sync;
+use crate::network::{NetworkError, ClaimProof, HandleCheckResult, HandleReservationResult, HandleClaimResult, HandleReleaseResult};
 
 // ==================== Response Types ====================
 
@@ -53,33 +59,31 @@ pub struct CreateIdentityResult {
 
 // ==================== Tauri Commands ====================
 
-/// Validate a handle format (client-side only, no network)
+/// Validate a handle format (client-side only, no network), saying which
+/// rule it breaks if any
 #[tauri::command]
-pub fn validate_handle_format(handle: String) -> CommandResult<String> {
-    match validate_handle(&handle) {
-        Ok(clean) => CommandResult::ok(clean),
-        Err(e) => CommandResult::err(e),
-    }
+pub fn validate_handle_format(handle: String) -> HandleValidation {
+    HandleValidation::check(&handle)
 }
 
 /// Check if a handle is available on the network
 #[tauri::command]
-pub async fn check_handle_available(handle: String) -> CommandResult<HandleCheckResult> {
+pub async fn check_handle_available(
+    handle: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResult<HandleCheckResult>, CommandError> {
     // First validate locally
     let clean_handle = match validate_handle(&handle) {
         Ok(h) => h,
-        Err(e) => return CommandResult::err(e),
+        Err(e) => return Ok(CommandResult::err(e)),
     };
     
     // Then check network
-    let api = match ApiClient::new(GNS_API_URL) {
-        Ok(a) => a,
-        Err(e) => return CommandResult::err(e),
-    };
-    
-    match api.check_handle_available(&clean_handle).await {
-        Ok(result) => CommandResult::ok(result),
-        Err(e) => CommandResult::err(e),
+    let api = &state.api;
+    let limit = state.config.command_timeout(CommandCategory::Lookup);
+    match with_timeout("check_handle_available", limit, api.check_handle_available(&clean_handle)).await? {
+        Ok(result) => Ok(CommandResult::ok(result)),
+        Err(e) => Ok(CommandResult::err(e)),
     }
 }
 
@@ -104,11 +108,8 @@ pub async fn create_identity_with_handle(
         }
     }
     
-    // 3. Create API client and check handle availability
-    let api = match ApiClient::new(GNS_API_URL) {
-        Ok(a) => a,
-        Err(e) => return Ok(CommandResult::err(e)),
-    };
+    // 3. Check handle availability
+    let api = &state.api;
     
     let check_result = match api.check_handle_available(&clean_handle).await {
         Ok(r) => r,
@@ -132,6 +133,7 @@ pub async fn create_identity_with_handle(
     let public_key = identity.public_key_hex().unwrap_or_default();
     let encryption_key = identity.encryption_key_hex().unwrap_or_default();
     let gns_id = format!("gns_{}", &public_key[..16]);
+    crate::commands::identity::cache_derived_keys(&state.database, &public_key).await;
     
     tracing::info!("🔑 New identity generated: {}", gns_id);
     tracing::info!("   Ed25519: {}...", &public_key[..16]);
@@ -141,8 +143,8 @@ pub async fn create_identity_with_handle(
     let timestamp = chrono::Utc::now().to_rfc3339();
     let message = format!("reserve:{}:{}", clean_handle, timestamp);
     
-    let signature = match identity.get_identity() {
-        Some(id) => hex::encode(id.sign_bytes(message.as_bytes())),
+    let signature = match identity.sign_for_backend(SignatureDomain::Reserve, &message) {
+        Some(signature) => signature,
         None => return Ok(CommandResult::err("Identity not found after generation")),
     };
     
@@ -165,7 +167,7 @@ pub async fn create_identity_with_handle(
     
     // 8. Publish initial record to network (so others can find our encryption key)
     if network_reserved {
-        let now = chrono::Utc::now().to_rfc3339();
+        let now = record_timestamp(chrono::Utc::now());
         
         let mut record_json = serde_json::json!({
             "identity": public_key,
@@ -183,16 +185,18 @@ pub async fn create_identity_with_handle(
         record_json["handle"] = serde_json::Value::String(clean_handle.clone());
         
         let record_signature = {
-            let id = identity.get_identity().unwrap();
             let data_to_sign = canonical_json(&record_json);
-            hex::encode(id.sign_bytes(data_to_sign.as_bytes()))
+            identity.sign_for_backend(SignatureDomain::Record, &data_to_sign).unwrap()
         };
         
-        if let Err(e) = api.publish_signed_record(
-            &public_key,
-            &record_json,
-            &record_signature,
-        ).await {
+        let published = match validate_record(&record_json, &public_key) {
+            Ok(()) => api
+                .publish_signed_record(&public_key, &record_json, &record_signature)
+                .await
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = published {
             tracing::warn!("Failed to publish initial record: {}", e);
         } else {
             tracing::info!("✅ Initial record published with encryption_key");
@@ -278,20 +282,24 @@ pub async fn reserve_handle(
     let timestamp = chrono::Utc::now().to_rfc3339();
     let message = format!("reserve:{}:{}", clean_handle, timestamp);
     
-    let signature = match identity.get_identity() {
-        Some(id) => hex::encode(id.sign_bytes(message.as_bytes())),
+    let signature = match identity.sign_for_backend(SignatureDomain::Reserve, &message) {
+        Some(signature) => signature,
         None => return Ok(CommandResult::err("Identity not found")),
     };
     
     drop(identity); // Release lock before network call
     
     // Call API
-    let api = match ApiClient::new(GNS_API_URL) {
-        Ok(a) => a,
-        Err(e) => return Ok(CommandResult::err(e)),
+    let api = &state.api;
+    let reserved = api.reserve_handle(&clean_handle, &public_key, &encryption_key, &signature, &timestamp).await;
+    let outcome = match &reserved {
+        Ok(result) if result.success => Ok(()),
+        Ok(result) => Err(result.error.clone().unwrap_or_else(|| "Reservation rejected".to_string())),
+        Err(e) => Err(e.to_string()),
     };
-    
-    match api.reserve_handle(&clean_handle, &public_key, &encryption_key, &signature, &timestamp).await {
+    audit::record(&state.database, AuditAction::ReserveHandle, Some(clean_handle.as_str()), &outcome).await;
+
+    match reserved {
         Ok(result) => {
             // Store handle if successful
             if result.success {
@@ -381,63 +389,105 @@ pub async fn claim_handle(
     let data_to_sign = canonical_json(&claim_data);
     
     let identity = state.identity.lock().await;
-    let signature = match identity.get_identity() {
-        Some(id) => hex::encode(id.sign_bytes(data_to_sign.as_bytes())),
+    let signature = match identity.sign_for_backend(SignatureDomain::Claim, &data_to_sign) {
+        Some(signature) => signature,
         None => return Ok(CommandResult::err("Identity not found")),
     };
     drop(identity); // Release lock before network call
     
     // 6. Call API
-    let api = match ApiClient::new(GNS_API_URL) {
-        Ok(a) => a,
-        Err(e) => return Ok(CommandResult::err(e)),
-    };
+    let api = &state.api;
     
-    match api.claim_handle_with_proof(&cached_handle, &public_key, &proof, &signature).await {
+    let claimed = api.claim_handle_with_proof(&cached_handle, &public_key, &proof, &signature).await;
+    let outcome = match &claimed {
+        Ok(result) if result.success => Ok(()),
+        Ok(result) => Err(result.error.clone().unwrap_or_else(|| "Claim rejected".to_string())),
+        Err(e) => Err(e.to_string()),
+    };
+    audit::record(&state.database, AuditAction::ClaimHandle, Some(cached_handle.as_str()), &outcome).await;
+
+    match claimed {
         Ok(result) => {
             // Update cached handle status if successful
             if result.success {
                 // TODO: Update storage to mark handle as claimed
                 tracing::info!("🎉 Handle @{} claimed successfully!", cached_handle);
 
-                // Re-acquire lock to sign the record
-                let identity = state.identity.lock().await;
-                let encryption_key = identity.encryption_key_hex().unwrap_or_default();
-                let now = chrono::Utc::now().to_rfc3339();
-                
-                let mut record_json = serde_json::json!({
-                    "identity": public_key,
-                    "encryption_key": encryption_key,
-                    "trust_score": trust_score,
-                    "breadcrumb_count": breadcrumb_count,
-                    "version": 1,
-                    "created_at": now,
-                    "updated_at": now,
-                    "modules": [],
-                    "endpoints": [],
-                    "epoch_roots": [],
-                });
-                
-                record_json["handle"] = serde_json::Value::String(cached_handle.clone());
-                
-                let record_signature = match identity.get_identity() {
-                    Some(id) => {
-                        let data_to_sign = canonical_json(&record_json);
-                        hex::encode(id.sign_bytes(data_to_sign.as_bytes()))
-                    },
-                    None => String::new(),
-                };
-                drop(identity); // Release lock again
-
-                if !record_signature.is_empty() {
-                    if let Err(e) = api.publish_signed_record(
-                        &public_key,
-                        &record_json,
-                        &record_signature,
-                    ).await {
-                        tracing::warn!("Failed to publish record after claim: {}", e);
-                    } else {
-                        tracing::info!("✅ Identity record published with encryption key");
+                // Publish the record with the claimed handle, keeping the
+                // profile and device list
+                if let Err(e) = publish_identity_record(&state).await {
+                    tracing::warn!("Failed to publish record after claim: {}", e);
+                } else {
+                    tracing::info!("✅ Identity record published with encryption key");
+                }
+            }
+            Ok(CommandResult::ok(result))
+        }
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Release the reserved handle so a different one can be reserved. If it
+/// has already been claimed, `confirm_claimed` must be set.
+#[tauri::command]
+pub async fn release_reserved_handle(
+    handle: String,
+    confirm_claimed: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<CommandResult<HandleReleaseResult>, String> {
+    let identity = state.identity.lock().await;
+    if !identity.has_identity() {
+        return Ok(CommandResult::err("No identity found"));
+    }
+
+    let cached_handle = match identity.cached_handle() {
+        Some(h) => h.trim_start_matches('@').to_lowercase(),
+        None => return Ok(CommandResult::err(HandleError::NoReservation)),
+    };
+    if handle.trim_start_matches('@').to_lowercase() != cached_handle {
+        return Ok(CommandResult::err("Handle does not match reserved handle"));
+    }
+
+    let public_key = identity.public_key_hex().unwrap_or_default();
+    drop(identity);
+
+    let api = &state.api;
+
+    // Nothing local records the claim yet; a handle that resolves to us is claimed
+    let claimed = match api.resolve_handle(&cached_handle).await {
+        Ok(info) => info.is_some_and(|i| i.public_key.eq_ignore_ascii_case(&public_key)),
+        Err(e) => return Ok(CommandResult::err(format!("Could not check whether @{} is claimed: {}", cached_handle, e))),
+    };
+    if let Err(e) = check_release(&cached_handle, claimed, confirm_claimed.unwrap_or(false)) {
+        return Ok(CommandResult::err(e));
+    }
+
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let message = release_message(&cached_handle, &timestamp);
+    let identity = state.identity.lock().await;
+    let signature = match identity.sign_for_backend(SignatureDomain::Release, &message) {
+        Some(signature) => signature,
+        None => return Ok(CommandResult::err("Identity not found")),
+    };
+    drop(identity);
+
+    let released = api.release_handle(&cached_handle, &public_key, &signature, &timestamp).await;
+    let outcome = match &released {
+        Ok(result) if result.success => Ok(()),
+        Ok(result) => Err(result.error.clone().unwrap_or_else(|| "Release rejected".to_string())),
+        Err(e) => Err(e.to_string()),
+    };
+    audit::record(&state.database, AuditAction::ReleaseHandle, Some(cached_handle.as_str()), &outcome).await;
+
+    match released {
+        Ok(result) => {
+            if result.success {
+                state.identity.lock().await.set_cached_handle(None);
+
+                // Take the handle out of the published record too
+                if claimed {
+                    if let Err(e) = record_sync::update_handle(&api, &state.identity, None).await {
+                        tracing::warn!("Failed to publish record after release: {}", e);
                     }
                 }
             }
@@ -452,10 +502,116 @@ pub async fn claim_handle(
 pub async fn publish_identity(
     state: State<'_, AppState>,
 ) -> Result<CommandResult<bool>, String> {
+    match publish_identity_record(&state).await {
+        Ok(()) => {
+            tracing::info!("✅ Identity record published manually");
+            Ok(CommandResult::ok(true))
+        }
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// What publishing our identity record would sign
+#[derive(Debug, Clone, Serialize)]
+pub struct IdentityRecordPreview {
+    pub record_json: serde_json::Value,
+    pub canonical_json: String,
+    /// The exact message signed: `canonical_json`, after the record domain
+    /// tag if tagged signatures are on
+    pub signed_message: String,
+    /// Why the record would be rejected; empty if it is valid
+    pub errors: Vec<RecordError>,
+}
+
+/// Build our identity record and show what would be signed, without
+/// signing or publishing it
+#[tauri::command]
+pub async fn preview_identity_record(
+    state: State<'_, AppState>,
+) -> Result<IdentityRecordPreview, String> {
+    let prepared = prepare_identity_record(&state, |_, _| Ok(())).await?;
+    let errors = validate_record(&prepared.record_json, &prepared.public_key)
+        .err()
+        .map(|e| e.0)
+        .unwrap_or_default();
+
+    let canonical = canonical_json(&prepared.record_json);
+    let signed_bytes = state.identity.lock().await.backend_signed_bytes(SignatureDomain::Record, &canonical);
+    let signed_message = String::from_utf8_lossy(&signed_bytes).into_owned();
+
+    Ok(IdentityRecordPreview {
+        record_json: prepared.record_json,
+        canonical_json: canonical,
+        signed_message,
+        errors,
+    })
+}
+
+/// Build, sign and publish our identity record, including the profile
+pub(crate) async fn publish_identity_record(state: &AppState) -> Result<(), String> {
+    publish_identity_record_with(state, |_, _| Ok(())).await.map(|_| ())
+}
+
+/// Publish our identity record after applying `edit` to its device list.
+///
+/// The device list is merged from the published record first, so other
+/// devices' entries and revocations carry over, and this device checks in
+/// with a fresh `last_seen`. A device that was revoked refuses to publish.
+/// The record is only published over the version the device list came
+/// from; if another publish got in first, it is rebuilt from that one.
+pub(crate) async fn publish_identity_record_with<F>(state: &AppState, edit: F) -> Result<DeviceList, String>
+where
+    F: Fn(&mut DeviceList, &GnsIdentity) -> Result<(), String>,
+{
+    let api = &state.api;
+
+    for attempt in 1..=record_sync::MAX_ATTEMPTS {
+        let prepared = prepare_identity_record(state, &edit).await?;
+        validate_record(&prepared.record_json, &prepared.public_key).map_err(|e| e.to_string())?;
+
+        // Sign Canonical JSON
+        let data_to_sign = canonical_json(&prepared.record_json);
+
+        let identity = state.identity.lock().await;
+        let signature = match identity.sign_for_backend(SignatureDomain::Record, &data_to_sign) {
+            Some(signature) => signature,
+            None => return Err("Identity not found".to_string()),
+        };
+        drop(identity);
+
+        let published = api
+            .publish_signed_record_if_match(&prepared.public_key, &prepared.record_json, &signature, prepared.version.as_deref())
+            .await;
+        match published {
+            Ok(()) => return Ok(prepared.devices),
+            Err(NetworkError::Conflict(e)) => {
+                tracing::info!("🔁 Record changed while publishing (attempt {}): {}", attempt, e);
+            }
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+
+    Err(format!("Record kept changing; gave up after {} attempts", record_sync::MAX_ATTEMPTS))
+}
+
+/// An identity record built but not yet signed
+struct PreparedRecord {
+    public_key: String,
+    record_json: serde_json::Value,
+    devices: DeviceList,
+    /// Version of the published record it was built over, if there is one
+    version: Option<String>,
+}
+
+/// Build our identity record as `publish_identity_record_with` would sign it
+async fn prepare_identity_record<F>(state: &AppState, edit: F) -> Result<PreparedRecord, String>
+where
+    F: FnOnce(&mut DeviceList, &GnsIdentity) -> Result<(), String>,
+{
     // 1. Get identity
     let identity = state.identity.lock().await;
     if !identity.has_identity() {
-        return Ok(CommandResult::err("No identity found"));
+        return Err("No identity found".to_string());
     }
     
     let public_key = identity.public_key_hex().unwrap_or_default();
@@ -464,16 +620,47 @@ pub async fn publish_identity(
     
     drop(identity); // Release lock
 
-    // 2. Get stats from DB
-    let db = state.database.lock().await;
+    // 2. Get stats and profile from DB
+    let mut db = state.database.lock().await;
     let breadcrumb_count = db.count_breadcrumbs().unwrap_or(0);
     // TODO: Implement trust score
     let trust_score = 0.0;
+    let profile = crate::profile::record_value(&db, &public_key);
+    let device_id = devices::local_device_id(&mut db)?;
+    let device_name = devices::local_device_name(&db);
+    // Lets resolvers holding an earlier key of ours find this one
+    let key_rotations = db.rotation_certificates(&public_key).unwrap_or_else(|e| {
+        tracing::warn!("⚠️ Publishing without rotation certificates: {}", e);
+        Vec::new()
+    });
     drop(db);
 
-    // 3. Construct record JSON (must match server schema)
-    // Use strict RFC3339 with milliseconds and Z suffix for Zod compatibility
-    let now = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+    // 3. Carry over the published device list and check in
+    let (mut device_list, version) = match state.api.get_record_versioned(&public_key).await {
+        Ok(Some(current)) => {
+            let devices = current.record.devices(&public_key).unwrap_or_else(|e| {
+                tracing::warn!("⚠️ Ignoring unverified device list: {}", e);
+                DeviceList::default()
+            });
+            (devices, Some(current.version))
+        }
+        Ok(None) => (DeviceList::default(), None),
+        // Publishing without it would drop every other device
+        Err(e) => return Err(format!("Could not load device list: {}", e)),
+    };
+    if let Some(revoked_at) = device_list.revoked_at(&device_id) {
+        return Err(format!("This device was revoked at {} and can no longer publish for this identity", revoked_at));
+    }
+
+    let identity = state.identity.lock().await;
+    let id = identity.get_identity().ok_or("Identity not found")?;
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    device_list.touch(id, &device_id, &device_name, now_ms);
+    edit(&mut device_list, id)?;
+    drop(identity);
+
+    // 4. Construct record JSON (must match server schema, see `validate_record`)
+    let now = record_timestamp(chrono::Utc::now());
     
     let mut record_json = serde_json::json!({
         "identity": public_key,
@@ -486,38 +673,21 @@ pub async fn publish_identity(
         "modules": [],
         "endpoints": [],
         "epoch_roots": [],
+        "devices": device_list.to_value(),
     });
     
     if let Some(h) = handle {
         record_json["handle"] = serde_json::Value::String(h);
     }
 
-    // 4. Sign Canonical JSON
-    let data_to_sign = canonical_json(&record_json);
-    
-    let identity = state.identity.lock().await;
-    let signature = match identity.get_identity() {
-        Some(id) => hex::encode(id.sign_bytes(data_to_sign.as_bytes())),
-        None => return Ok(CommandResult::err("Identity not found")),
-    };
-    drop(identity);
-
-    // 5. Publish
-    let api = match ApiClient::new(GNS_API_URL) {
-        Ok(a) => a,
-        Err(e) => return Ok(CommandResult::err(e)),
-    };
+    if let Some(p) = profile {
+        record_json["profile"] = p;
+    }
 
-    match api.publish_signed_record(
-        &public_key,
-        &record_json,
-        &signature,
-    ).await {
-        Ok(_) => {
-            tracing::info!("✅ Identity record published manually");
-            Ok(CommandResult::ok(true))
-        }
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+    if !key_rotations.is_empty() {
+        record_json["key_rotations"] = serde_json::to_value(&key_rotations).map_err(|e| e.to_string())?;
     }
+
+    Ok(PreparedRecord { public_key, record_json, devices: device_list, version })
 }
 
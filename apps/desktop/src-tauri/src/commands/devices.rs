@@ -0,0 +1,78 @@
+//! Device Commands
+//!
+//! Commands for listing, renaming and revoking the devices an identity is
+//! active on. The list lives in the signed identity record (see
+//! `crate::devices`), so every change is a record publish.
+
+use crate::commands::audit;
+use crate::commands::commands_handle::publish_identity_record_with;
+use crate::devices::{self, DeviceList, DeviceStatus, DEVICE_NAME_SETTING};
+use crate::storage::AuditAction;
+use crate::AppState;
+use tauri::State;
+
+/// List the devices registered for our identity, revoked ones included
+#[tauri::command]
+pub async fn list_devices(state: State<'_, AppState>) -> Result<Vec<DeviceStatus>, String> {
+    let identity = state.identity.lock().await;
+    let public_key = identity.public_key_hex().ok_or("No identity found")?;
+    drop(identity);
+
+    let device_id = devices::local_device_id(&mut *state.database.lock().await)?;
+
+    let devices = match state.api.get_record(&public_key).await.map_err(|e| e.to_string())? {
+        Some(record) => record.devices(&public_key)?,
+        None => DeviceList::default(),
+    };
+    Ok(devices.statuses(&device_id))
+}
+
+/// Rename one of our devices
+#[tauri::command]
+pub async fn rename_device(
+    device_id: String,
+    name: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<DeviceStatus>, String> {
+    let name = devices::clean_device_name(&name)?;
+
+    let mut db = state.database.lock().await;
+    let current = devices::local_device_id(&mut db)?;
+    if device_id == current {
+        db.set_setting(DEVICE_NAME_SETTING, &name).map_err(|e| e.to_string())?;
+    }
+    drop(db);
+
+    let now = chrono::Utc::now().timestamp_millis();
+    let updated = publish_identity_record_with(&state, |list, identity| {
+        list.rename(identity, &device_id, &name, now)
+    })
+    .await?;
+
+    tracing::info!("📱 Renamed device {}", device_id);
+    Ok(updated.statuses(&current))
+}
+
+/// Revoke another of our devices. Other clients treat it as inactive once
+/// the record is published, and the device itself stops publishing.
+#[tauri::command]
+pub async fn revoke_device(
+    device_id: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<DeviceStatus>, String> {
+    let current = devices::local_device_id(&mut *state.database.lock().await)?;
+    if device_id == current {
+        return Err("Can't revoke the device you are using".to_string());
+    }
+
+    let now = chrono::Utc::now().timestamp_millis();
+    let revoked = publish_identity_record_with(&state, |list, identity| {
+        list.revoke(identity, &device_id, now)
+    })
+    .await;
+    audit::record(&state.database, AuditAction::RevokeDevice, Some(device_id.as_str()), &revoked).await;
+    let updated = revoked?;
+
+    tracing::info!("🚫 Revoked device {}", device_id);
+    Ok(updated.statuses(&current))
+}
@@ -1,24 +1,52 @@
 use crate::AppState;
-use crate::dix::{DixPost, DixPostData, DixUserData, DixMedia};
-use tauri::State;
+use crate::crypto::SignatureDomain;
+use crate::commands::notifications::notify_mentions;
+use crate::dix::{
+    DixLink, DixPost, DixPostData, DixRepost, DixTagPage, DixUserData, DixMedia, FollowAction, RepostVerification,
+    TimelineSince, TrendingTag,
+};
+use crate::storage::Bookmark;
+use serde::Serialize;
+use std::future::Future;
+use tauri::{AppHandle, Emitter, State};
+
+/// A bookmarked post, hydrated from the network when still available
+#[derive(Debug, Clone, Serialize)]
+pub struct BookmarkedPost {
+    pub post_id: String,
+    pub saved_at: i64,
+    pub available: bool,
+    pub post: Option<DixPost>,
+}
 
 #[tauri::command]
 pub async fn create_post(
     state: State<'_, AppState>,
     text: String,
     media: Vec<DixMedia>,
+    links: Option<Vec<DixLink>>,
     reply_to_id: Option<String>,
 ) -> Result<DixPost, String> {
-    state.dix.create_post(text, media, reply_to_id).await
+    state.dix.create_post(text, media, links.unwrap_or_default(), reply_to_id).await
+}
+
+/// Fetch OpenGraph preview metadata for a link in the composer
+#[tauri::command]
+pub async fn fetch_link_preview(url: String) -> Result<DixLink, String> {
+    crate::dix::fetch_link_preview(&url).await
 }
 
 #[tauri::command]
 pub async fn get_timeline(
+    app: AppHandle,
     state: State<'_, AppState>,
     limit: Option<u32>,
     offset: Option<u32>,
+    since: Option<TimelineSince>,
 ) -> Result<Vec<DixPost>, String> {
-    state.dix.get_timeline(limit.unwrap_or(20), offset.unwrap_or(0)).await
+    let posts = state.dix.get_timeline(limit.unwrap_or(20), offset.unwrap_or(0), since).await?;
+    notify_mentions(&app, &state, &posts).await;
+    Ok(posts)
 }
 
 #[tauri::command]
@@ -30,7 +58,7 @@ pub async fn like_post(
         let identity = state.identity.lock().await;
         // Using public_key_hex() as established in file reading
         let pk = identity.public_key_hex().ok_or("No identity")?;
-        let sig = identity.sign_string(&id).ok_or("Failed to sign")?;
+        let sig = identity.sign_for_backend(SignatureDomain::Dix, &id).ok_or("Failed to sign")?;
         (pk, sig)
     };
     state.dix.like_post(&id, &pk, &sig).await
@@ -45,18 +73,33 @@ pub async fn repost_post(
         let identity = state.identity.lock().await;
         // Using public_key_hex() as established in file reading
         let pk = identity.public_key_hex().ok_or("No identity")?;
-        let sig = identity.sign_string(&id).ok_or("Failed to sign")?;
+        let sig = identity.sign_for_backend(SignatureDomain::Dix, &id).ok_or("Failed to sign")?;
         (pk, sig)
     };
     state.dix.repost_post(&id, &pk, &sig).await
 }
 
+/// Check a repost: signed by the reposter over the original's id, and the
+/// original still signed by its author
+#[tauri::command]
+pub fn verify_repost(repost: DixRepost) -> RepostVerification {
+    crate::dix::verify_repost(&repost)
+}
+
 #[tauri::command]
 pub async fn get_post(
+    app: AppHandle,
     state: State<'_, AppState>,
     id: String,
 ) -> Result<DixPostData, String> {
-    state.dix.get_post(&id).await
+    let data = state.dix.get_post(&id).await?;
+
+    let mut posts = Vec::with_capacity(data.replies.len() + 1);
+    posts.push(data.post.clone());
+    posts.extend(data.replies.iter().cloned());
+    notify_mentions(&app, &state, &posts).await;
+
+    Ok(data)
 }
 
 #[tauri::command]
@@ -66,3 +109,317 @@ pub async fn get_posts_by_user(
 ) -> Result<DixUserData, String> {
     state.dix.get_posts_by_user(&public_key).await
 }
+
+/// Most used tags over the last `window_hours` (default 24)
+#[tauri::command]
+pub async fn get_trending_tags(
+    state: State<'_, AppState>,
+    window_hours: Option<u32>,
+) -> Result<Vec<TrendingTag>, String> {
+    state.dix.get_trending_tags(window_hours.unwrap_or(24)).await
+}
+
+/// A page of posts carrying `tag` (with or without '#', any case)
+#[tauri::command]
+pub async fn get_posts_by_tag(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    tag: String,
+    cursor: Option<String>,
+) -> Result<DixTagPage, String> {
+    let page = state.dix.get_posts_by_tag(&tag, cursor.as_deref()).await?;
+    notify_mentions(&app, &state, &page.posts).await;
+    Ok(page)
+}
+
+// ==================== Engagement ====================
+// Live counts for the posts on screen, delivered as `engagement_updated`
+// events by the task in `dix::engagement`.
+
+/// Keep the engagement counts of `post_ids` live, alongside any already
+/// watched
+#[tauri::command]
+pub async fn watch_post_engagement(
+    state: State<'_, AppState>,
+    post_ids: Vec<String>,
+) -> Result<(), String> {
+    state.dix.watch_engagement(&post_ids).await
+}
+
+/// Stop watching `post_ids`, or every post when none are given
+#[tauri::command]
+pub async fn stop_watching_engagement(
+    state: State<'_, AppState>,
+    post_ids: Option<Vec<String>>,
+) -> Result<(), String> {
+    state.dix.engagement.unwatch(post_ids.as_deref()).await;
+    Ok(())
+}
+
+// ==================== Bookmarks ====================
+// Bookmarks are local only and never hit the network, unlike likes.
+
+#[tauri::command]
+pub async fn bookmark_post(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<(), String> {
+    let owner_pk = {
+        let identity = state.identity.lock().await;
+        identity.public_key_hex().ok_or("No identity")?
+    };
+
+    let mut db = state.database.lock().await;
+    db.add_bookmark(&owner_pk, &id).map_err(|e| e.to_string())?;
+    drop(db);
+
+    let _ = app.emit("bookmark_changed", serde_json::json!({ "post_id": id, "bookmarked": true }));
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn unbookmark_post(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<(), String> {
+    let owner_pk = {
+        let identity = state.identity.lock().await;
+        identity.public_key_hex().ok_or("No identity")?
+    };
+
+    let mut db = state.database.lock().await;
+    db.remove_bookmark(&owner_pk, &id).map_err(|e| e.to_string())?;
+    drop(db);
+
+    let _ = app.emit("bookmark_changed", serde_json::json!({ "post_id": id, "bookmarked": false }));
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_bookmarks(state: State<'_, AppState>) -> Result<Vec<BookmarkedPost>, String> {
+    let owner_pk = {
+        let identity = state.identity.lock().await;
+        identity.public_key_hex().ok_or("No identity")?
+    };
+
+    let bookmarks = {
+        let db = state.database.lock().await;
+        db.get_bookmarks(&owner_pk).map_err(|e| e.to_string())?
+    };
+
+    let dix = &state.dix;
+    Ok(hydrate_bookmarks(bookmarks, |id| async move { dix.get_post(&id).await.map(|data| data.post) }).await)
+}
+
+/// Pair each bookmark with its post as `fetch` returns it
+async fn hydrate_bookmarks<F, Fut>(bookmarks: Vec<Bookmark>, fetch: F) -> Vec<BookmarkedPost>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<DixPost, String>>,
+{
+    let mut result = Vec::with_capacity(bookmarks.len());
+    for bookmark in bookmarks {
+        // A deleted or unreachable post shouldn't fail the whole list
+        let post = match fetch(bookmark.post_id.clone()).await {
+            Ok(post) => Some(post),
+            Err(e) => {
+                tracing::warn!("Bookmarked post {} unavailable: {}", bookmark.post_id, e);
+                None
+            }
+        };
+
+        result.push(BookmarkedPost {
+            post_id: bookmark.post_id,
+            saved_at: bookmark.saved_at,
+            available: post.is_some(),
+            post,
+        });
+    }
+    result
+}
+
+// ==================== Follows ====================
+// Follow records are signed and published; the local table keeps the
+// latest record per pair so the UI works offline.
+
+#[tauri::command]
+pub async fn follow(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    public_key: String,
+) -> Result<(), String> {
+    set_following(&app, &state, &public_key, FollowAction::Follow).await
+}
+
+#[tauri::command]
+pub async fn unfollow(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    public_key: String,
+) -> Result<(), String> {
+    set_following(&app, &state, &public_key, FollowAction::Unfollow).await
+}
+
+/// Keys the current identity follows
+#[tauri::command]
+pub async fn get_following(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    let owner_pk = {
+        let identity = state.identity.lock().await;
+        identity.public_key_hex().ok_or("No identity")?
+    };
+
+    let db = state.database.lock().await;
+    db.get_following(&owner_pk).map_err(|e| e.to_string())
+}
+
+/// Followers of `public_key` (default: ourselves), refreshed from the
+/// network when reachable
+#[tauri::command]
+pub async fn get_followers(
+    state: State<'_, AppState>,
+    public_key: Option<String>,
+) -> Result<Vec<String>, String> {
+    let target = match public_key {
+        Some(pk) => pk.to_lowercase(),
+        None => {
+            let identity = state.identity.lock().await;
+            identity.public_key_hex().ok_or("No identity")?
+        }
+    };
+
+    match state.dix.get_follower_records(&target).await {
+        Ok(records) => {
+            let mut db = state.database.lock().await;
+            for record in &records {
+                if let Err(e) = db.apply_follow_record(record) {
+                    tracing::warn!("Failed to store follow record: {}", e);
+                }
+            }
+        }
+        Err(e) => tracing::warn!("⚠️ Follower fetch failed, using local records: {}", e),
+    }
+
+    let db = state.database.lock().await;
+    db.get_followers(&target).map_err(|e| e.to_string())
+}
+
+/// Timeline limited to authors we follow
+#[tauri::command]
+pub async fn get_following_timeline(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> Result<Vec<DixPost>, String> {
+    let following = {
+        let owner_pk = {
+            let identity = state.identity.lock().await;
+            identity.public_key_hex().ok_or("No identity")?
+        };
+        let db = state.database.lock().await;
+        db.get_following(&owner_pk).map_err(|e| e.to_string())?
+    };
+
+    let posts = state
+        .dix
+        .get_following_timeline(&following, limit.unwrap_or(20), offset.unwrap_or(0))
+        .await?;
+    notify_mentions(&app, &state, &posts).await;
+    Ok(posts)
+}
+
+async fn set_following(
+    app: &AppHandle,
+    state: &AppState,
+    public_key: &str,
+    action: FollowAction,
+) -> Result<(), String> {
+    let target = public_key.trim().to_lowercase();
+    if target.len() != 64 || !target.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("Invalid public key".to_string());
+    }
+
+    let own_pk = {
+        let identity = state.identity.lock().await;
+        identity.public_key_hex().ok_or("No identity")?
+    };
+    if own_pk.eq_ignore_ascii_case(&target) {
+        return Err("You can't follow yourself".to_string());
+    }
+
+    let record = state.dix.publish_follow(&target, action).await?;
+
+    let mut db = state.database.lock().await;
+    db.apply_follow_record(&record).map_err(|e| e.to_string())?;
+    drop(db);
+
+    let _ = app.emit(
+        "follow_changed",
+        serde_json::json!({ "public_key": target, "following": action == FollowAction::Follow }),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(id: &str) -> DixPost {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "author": {
+                "publicKey": "ab".repeat(32),
+                "handle": null,
+                "displayName": null,
+                "avatarUrl": null,
+                "trustScore": 0,
+                "breadcrumbCount": 0,
+                "isVerified": false
+            },
+            "facet": "dix",
+            "content": { "text": "saved", "mentions": [], "location": null },
+            "engagement": { "likes": 0, "replies": 0, "reposts": 0, "quotes": 0, "views": 0 },
+            "meta": {
+                "signature": "",
+                "trustScoreAtPost": 0,
+                "breadcrumbsAtPost": 0,
+                "createdAt": "2025-01-01T00:00:00Z"
+            },
+            "thread": null
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_deleted_bookmarked_post_is_marked_unavailable() {
+        let bookmarks = ["kept", "deleted", "also-kept"]
+            .iter()
+            .enumerate()
+            .map(|(i, id)| Bookmark { post_id: id.to_string(), saved_at: 3 - i as i64 })
+            .collect();
+
+        let hydrated = hydrate_bookmarks(bookmarks, |id| async move {
+            if id == "deleted" {
+                Err("Post not found".to_string())
+            } else {
+                Ok(post(&id))
+            }
+        })
+        .await;
+
+        let summary: Vec<_> = hydrated
+            .iter()
+            .map(|b| (b.post_id.as_str(), b.saved_at, b.available, b.post.as_ref().map(|p| p.id.as_str())))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("kept", 3, true, Some("kept")),
+                ("deleted", 2, false, None),
+                ("also-kept", 1, true, Some("also-kept")),
+            ]
+        );
+    }
+}
@@ -5,6 +5,7 @@
 //! 2. Check availability
 //! 3. Reserve handle (before breadcrumbs)
 //! 4. Claim handle (after 100 breadcrumbs with PoT)
+//! 5. Release it, if the user changes their mind
 
 use serde::{Deserialize, Serialize};
 use regex::Regex;
@@ -22,41 +23,77 @@ pub struct CommandResult<T> {
 
 // ==================== Validation ====================
 
-static HANDLE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new(r"^[a-z0-9_]{3,20}$").unwrap()
-});
+const MIN_HANDLE_LEN: usize = 3;
+const MAX_HANDLE_LEN: usize = 20;
 
 const RESERVED_HANDLES: &[&str] = &[
     "admin", "root", "system", "gns", "layer", "browser", 
     "support", "help", "official", "verified", "echo", "bot",
     "api", "www", "app", "mail", "ftp", "ssh", "localhost",
+    "moderator", "security", "staff",
 ];
 
-/// Validate a handle format
-pub fn validate_handle(handle: &str) -> Result<String, HandleError> {
-    let clean = handle.trim().to_lowercase().replace('@', "");
-    
+/// Result of checking a handle's format, detailed enough to guide the
+/// user while they type
+#[derive(Debug, Clone, Serialize)]
+pub struct HandleValidation {
+    pub valid: bool,
+    /// Lowercased, with `@` and surrounding whitespace removed
+    pub normalized: String,
+    /// The first rule the handle breaks
+    pub reason: Option<HandleError>,
+    /// `reason` as a sentence
+    pub message: Option<String>,
+}
+
+impl HandleValidation {
+    pub fn check(handle: &str) -> Self {
+        let normalized = handle.trim().to_lowercase().replace('@', "");
+        let reason = handle_format_error(&normalized);
+        Self {
+            valid: reason.is_none(),
+            message: reason.as_ref().map(|e| e.to_string()),
+            normalized,
+            reason,
+        }
+    }
+}
+
+fn handle_format_error(clean: &str) -> Option<HandleError> {
     if clean.is_empty() {
-        return Err(HandleError::Empty);
+        return Some(HandleError::Empty);
     }
-    
-    if clean.len() < 3 {
-        return Err(HandleError::TooShort { min: 3, got: clean.len() });
+
+    if clean.chars().count() < MIN_HANDLE_LEN {
+        return Some(HandleError::TooShort { min: MIN_HANDLE_LEN, got: clean.chars().count() });
     }
-    
-    if clean.len() > 20 {
-        return Err(HandleError::TooLong { max: 20, got: clean.len() });
+
+    if clean.chars().count() > MAX_HANDLE_LEN {
+        return Some(HandleError::TooLong { max: MAX_HANDLE_LEN, got: clean.chars().count() });
     }
-    
-    if !HANDLE_REGEX.is_match(&clean) {
-        return Err(HandleError::InvalidCharacters);
+
+    if let Some(character) = clean.chars().find(|c| !matches!(c, 'a'..='z' | '0'..='9' | '_')) {
+        return Some(HandleError::InvalidCharacter { character });
     }
-    
-    if RESERVED_HANDLES.contains(&clean.as_str()) {
-        return Err(HandleError::Reserved);
+
+    if clean.starts_with(|c: char| c.is_ascii_digit()) {
+        return Some(HandleError::StartsWithNumber);
+    }
+
+    if RESERVED_HANDLES.contains(&clean) {
+        return Some(HandleError::Reserved);
+    }
+
+    None
+}
+
+/// Validate a handle format, returning it normalized
+pub fn validate_handle(handle: &str) -> Result<String, HandleError> {
+    let validation = HandleValidation::check(handle);
+    match validation.reason {
+        Some(e) => Err(e),
+        None => Ok(validation.normalized),
     }
-    
-    Ok(clean)
 }
 
 // ==================== Handle Status ====================
@@ -159,7 +196,10 @@ impl ClaimRequirements {
 
 // ==================== Errors ====================
 
-#[derive(Debug, Clone, Serialize, thiserror::Error)]
+/// Serialized as `{ kind, detail }`, e.g.
+/// `{ "kind": "too_short", "detail": { "min": 3, "got": 2 } }`
+#[derive(Debug, Clone, PartialEq, Serialize, thiserror::Error)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
 pub enum HandleError {
     #[error("Handle cannot be empty")]
     Empty,
@@ -170,8 +210,11 @@ pub enum HandleError {
     #[error("Handle cannot exceed {max} characters (got {got})")]
     TooLong { max: usize, got: usize },
     
-    #[error("Handle can only contain lowercase letters, numbers, and underscores")]
-    InvalidCharacters,
+    #[error("Handle can't contain {character:?} - only lowercase letters, numbers, and underscores")]
+    InvalidCharacter { character: char },
+
+    #[error("Handle can't start with a number")]
+    StartsWithNumber,
     
     #[error("This handle is reserved")]
     Reserved,
@@ -190,11 +233,31 @@ pub enum HandleError {
     
     #[error("No handle reserved")]
     NoReservation,
+
+    #[error("@{handle} is claimed; releasing it needs confirmation")]
+    ClaimedReleaseUnconfirmed { handle: String },
     
     #[error("Signature error: {0}")]
     SignatureError(String),
 }
 
+// ==================== Release ====================
+
+/// The message signed, in the `Release` domain, to give `handle` up
+pub fn release_message(handle: &str, timestamp: &str) -> String {
+    format!("release:{}:{}", handle, timestamp)
+}
+
+/// Check that `handle` may be released. A reservation can be dropped at
+/// any time, but a claimed handle took a trajectory proof to get and goes
+/// back to the pool for anyone, so it needs `confirmed`.
+pub fn check_release(handle: &str, claimed: bool, confirmed: bool) -> Result<(), HandleError> {
+    if claimed && !confirmed {
+        return Err(HandleError::ClaimedReleaseUnconfirmed { handle: handle.to_string() });
+    }
+    Ok(())
+}
+
 // ==================== Canonical JSON for Signing ====================
 
 /// Create canonical JSON for signing (sorted keys, no null values)
@@ -239,6 +302,156 @@ pub fn canonical_json(value: &serde_json::Value) -> String {
 }
 
 
+// ==================== Record Validation ====================
+
+static RECORD_TIMESTAMP_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}\.\d{3}Z$").unwrap()
+});
+
+/// What a record field has to hold
+#[derive(Clone, Copy)]
+enum RecordField {
+    Key,
+    Number,
+    Count,
+    Timestamp,
+    Array,
+}
+
+/// Fields every identity record carries (the server's schema)
+const RECORD_FIELDS: &[(&str, RecordField)] = &[
+    ("identity", RecordField::Key),
+    ("encryption_key", RecordField::Key),
+    ("trust_score", RecordField::Number),
+    ("breadcrumb_count", RecordField::Count),
+    ("version", RecordField::Count),
+    ("created_at", RecordField::Timestamp),
+    ("updated_at", RecordField::Timestamp),
+    ("modules", RecordField::Array),
+    ("endpoints", RecordField::Array),
+    ("epoch_roots", RecordField::Array),
+];
+
+/// Timestamp in the form record schemas accept: RFC 3339, UTC,
+/// milliseconds, `Z` suffix
+pub fn record_timestamp(at: chrono::DateTime<chrono::Utc>) -> String {
+    at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+/// Why an identity record would be rejected
+#[derive(Debug, Clone, PartialEq, Serialize, thiserror::Error)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RecordError {
+    #[error("Record must be a JSON object")]
+    NotAnObject,
+
+    #[error("Missing field `{field}`")]
+    MissingField { field: String },
+
+    #[error("Field `{field}` must be {expected}")]
+    WrongType { field: String, expected: String },
+
+    #[error("Field `{field}` must look like 2025-01-31T12:00:00.000Z, got {value:?}")]
+    InvalidTimestamp { field: String, value: String },
+
+    #[error("Invalid handle {handle:?}: {reason}")]
+    InvalidHandle { handle: String, reason: String },
+
+    #[error("Record identity {identity} does not match signing key {signer}")]
+    IdentityMismatch { identity: String, signer: String },
+}
+
+/// Every problem found in a record
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RecordErrors(pub Vec<RecordError>);
+
+impl std::fmt::Display for RecordErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let errors: Vec<String> = self.0.iter().map(|e| e.to_string()).collect();
+        write!(f, "Invalid identity record: {}", errors.join("; "))
+    }
+}
+
+/// Check a record against the server's schema before it is signed, so a
+/// mistake shows up as a named field instead of an opaque rejection.
+/// `signer_public_key` is the key that will sign it.
+pub fn validate_record(record_json: &serde_json::Value, signer_public_key: &str) -> Result<(), RecordErrors> {
+    let Some(record) = record_json.as_object() else {
+        return Err(RecordErrors(vec![RecordError::NotAnObject]));
+    };
+    let mut errors = Vec::new();
+
+    for &(field, kind) in RECORD_FIELDS {
+        let Some(value) = record.get(field).filter(|v| !v.is_null()) else {
+            errors.push(RecordError::MissingField { field: field.to_string() });
+            continue;
+        };
+
+        let wrong_type = |expected: &str| RecordError::WrongType {
+            field: field.to_string(),
+            expected: expected.to_string(),
+        };
+        match kind {
+            RecordField::Key => {
+                let is_key = value
+                    .as_str()
+                    .is_some_and(|k| k.len() == 64 && k.chars().all(|c| c.is_ascii_hexdigit()));
+                if !is_key {
+                    errors.push(wrong_type("a 64-character hex key"));
+                }
+            }
+            RecordField::Number if !value.is_number() => errors.push(wrong_type("a number")),
+            RecordField::Count if !value.is_u64() => errors.push(wrong_type("a non-negative integer")),
+            RecordField::Array if !value.is_array() => errors.push(wrong_type("an array")),
+            RecordField::Timestamp => match value.as_str() {
+                Some(ts)
+                    if RECORD_TIMESTAMP_REGEX.is_match(ts)
+                        && chrono::DateTime::parse_from_rfc3339(ts).is_ok() => {}
+                Some(ts) => errors.push(RecordError::InvalidTimestamp {
+                    field: field.to_string(),
+                    value: ts.to_string(),
+                }),
+                None => errors.push(wrong_type("a string")),
+            },
+            _ => {}
+        }
+    }
+
+    match record.get("handle").filter(|v| !v.is_null()) {
+        None => {}
+        Some(serde_json::Value::String(handle)) => match validate_handle(handle) {
+            Ok(clean) if &clean == handle => {}
+            Ok(clean) => errors.push(RecordError::InvalidHandle {
+                handle: handle.clone(),
+                reason: format!("must be written as {:?}", clean),
+            }),
+            Err(e) => errors.push(RecordError::InvalidHandle {
+                handle: handle.clone(),
+                reason: e.to_string(),
+            }),
+        },
+        Some(_) => errors.push(RecordError::WrongType {
+            field: "handle".to_string(),
+            expected: "a string".to_string(),
+        }),
+    }
+
+    if let Some(identity) = record.get("identity").and_then(|v| v.as_str()) {
+        if !identity.eq_ignore_ascii_case(signer_public_key) {
+            errors.push(RecordError::IdentityMismatch {
+                identity: identity.to_string(),
+                signer: signer_public_key.to_string(),
+            });
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(RecordErrors(errors))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -254,6 +467,36 @@ mod tests {
         assert!(validate_handle("has space").is_err()); // Invalid chars
         assert!(validate_handle("HAS_CAPS").is_ok()); // Converted to lowercase
     }
+
+    #[test]
+    fn test_handle_validation_reasons() {
+        let reason = |handle: &str| HandleValidation::check(handle).reason;
+
+        assert_eq!(reason("  @ "), Some(HandleError::Empty));
+        assert_eq!(reason("ab"), Some(HandleError::TooShort { min: 3, got: 2 }));
+        assert_eq!(reason(&"a".repeat(21)), Some(HandleError::TooLong { max: 20, got: 21 }));
+        assert_eq!(reason("has space"), Some(HandleError::InvalidCharacter { character: ' ' }));
+        assert_eq!(reason("bob-smith"), Some(HandleError::InvalidCharacter { character: '-' }));
+        assert_eq!(reason("1alice"), Some(HandleError::StartsWithNumber));
+        assert_eq!(reason("@Support"), Some(HandleError::Reserved));
+
+        let rejected = HandleValidation::check("1alice");
+        assert!(!rejected.valid);
+        assert_eq!(rejected.message.as_deref(), Some("Handle can't start with a number"));
+        assert_eq!(
+            serde_json::to_value(&rejected.reason).unwrap(),
+            serde_json::json!({ "kind": "starts_with_number" })
+        );
+    }
+
+    #[test]
+    fn test_valid_handle_is_normalized() {
+        let validation = HandleValidation::check("  @Alice_99 ");
+        assert!(validation.valid);
+        assert_eq!(validation.normalized, "alice_99");
+        assert!(validation.reason.is_none() && validation.message.is_none());
+        assert_eq!(validate_handle("@Alice_99").unwrap(), "alice_99");
+    }
     
     #[test]
     fn test_canonical_json() {
@@ -271,4 +514,142 @@ mod tests {
         assert!(canonical.contains("\"number\":100"));
         assert!(!canonical.contains("null_value"));
     }
+
+    #[test]
+    fn test_release_signature() {
+        use gns_crypto_core::{verify_in_domain_hex, DomainPolicy, GnsIdentity, SignatureDomain};
+
+        let identity = GnsIdentity::generate();
+        let message = release_message("alice", "2024-01-01T00:00:00Z");
+        assert_eq!(message, "release:alice:2024-01-01T00:00:00Z");
+
+        let signature = hex::encode(identity.sign_in_domain(SignatureDomain::Release, message.as_bytes()));
+        let verify = |domain| {
+            verify_in_domain_hex(&identity.public_key_hex(), domain, message.as_bytes(), &signature, DomainPolicy::Strict)
+        };
+        assert!(verify(SignatureDomain::Release).unwrap());
+        // A release signature can't be replayed as a reservation
+        assert!(!verify(SignatureDomain::Reserve).unwrap());
+    }
+
+    #[test]
+    fn test_claimed_handle_release_needs_confirmation() {
+        assert_eq!(check_release("alice", false, false), Ok(()));
+        assert_eq!(
+            check_release("alice", true, false),
+            Err(HandleError::ClaimedReleaseUnconfirmed { handle: "alice".to_string() })
+        );
+        assert_eq!(check_release("alice", true, true), Ok(()));
+    }
+
+    const SIGNER: &str = "a1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7e8f90";
+
+    fn valid_record() -> serde_json::Value {
+        serde_json::json!({
+            "identity": SIGNER,
+            "encryption_key": "0f".repeat(32),
+            "trust_score": 0.0,
+            "breadcrumb_count": 120,
+            "version": 1,
+            "created_at": "2025-01-31T12:00:00.000Z",
+            "updated_at": record_timestamp(chrono::Utc::now()),
+            "modules": [],
+            "endpoints": [],
+            "epoch_roots": [],
+            "handle": "alice",
+        })
+    }
+
+    fn errors_for(record: &serde_json::Value) -> Vec<RecordError> {
+        validate_record(record, SIGNER).err().map(|e| e.0).unwrap_or_default()
+    }
+
+    #[test]
+    fn test_valid_record_passes() {
+        assert_eq!(validate_record(&valid_record(), SIGNER), Ok(()));
+
+        let mut without_handle = valid_record();
+        without_handle.as_object_mut().unwrap().remove("handle");
+        assert_eq!(validate_record(&without_handle, SIGNER), Ok(()));
+
+        assert_eq!(
+            errors_for(&serde_json::json!([])),
+            vec![RecordError::NotAnObject]
+        );
+    }
+
+    #[test]
+    fn test_record_missing_field() {
+        let mut record = valid_record();
+        record.as_object_mut().unwrap().remove("epoch_roots");
+        record["modules"] = serde_json::Value::Null;
+
+        assert_eq!(
+            errors_for(&record),
+            vec![
+                RecordError::MissingField { field: "modules".to_string() },
+                RecordError::MissingField { field: "epoch_roots".to_string() },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_record_wrong_types() {
+        let mut record = valid_record();
+        record["encryption_key"] = serde_json::json!("not-hex");
+        record["trust_score"] = serde_json::json!("high");
+        record["breadcrumb_count"] = serde_json::json!(-1);
+        record["endpoints"] = serde_json::json!({});
+
+        let fields: Vec<String> = errors_for(&record)
+            .into_iter()
+            .map(|e| match e {
+                RecordError::WrongType { field, .. } => field,
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+        assert_eq!(fields, vec!["encryption_key", "trust_score", "breadcrumb_count", "endpoints"]);
+    }
+
+    #[test]
+    fn test_record_timestamp_format() {
+        // What `to_rfc3339()` produces: no millis, offset instead of Z
+        for bad in ["2025-01-31T12:00:00+00:00", "2025-01-31T12:00:00Z", "2025-01-31T12:00:00.123456Z", "2025-13-31T12:00:00.000Z"] {
+            let mut record = valid_record();
+            record["created_at"] = serde_json::json!(bad);
+            assert_eq!(
+                errors_for(&record),
+                vec![RecordError::InvalidTimestamp { field: "created_at".to_string(), value: bad.to_string() }],
+                "{}",
+                bad
+            );
+        }
+
+        let at = chrono::DateTime::parse_from_rfc3339("2025-01-31T12:00:00.5+02:00").unwrap().to_utc();
+        assert_eq!(record_timestamp(at), "2025-01-31T10:00:00.500Z");
+    }
+
+    #[test]
+    fn test_record_handle_format() {
+        for (handle, reason) in [("@Alice", "must be written as \"alice\""), ("a!", "Handle must be at least 3 characters (got 2)")] {
+            let mut record = valid_record();
+            record["handle"] = serde_json::json!(handle);
+            assert_eq!(
+                errors_for(&record),
+                vec![RecordError::InvalidHandle { handle: handle.to_string(), reason: reason.to_string() }]
+            );
+        }
+    }
+
+    #[test]
+    fn test_record_identity_must_match_signer() {
+        let other = "ff".repeat(32);
+        assert_eq!(
+            validate_record(&valid_record(), &other).unwrap_err().0,
+            vec![RecordError::IdentityMismatch { identity: SIGNER.to_string(), signer: other }]
+        );
+
+        // Case doesn't matter
+        assert!(validate_record(&valid_record(), &SIGNER.to_uppercase()).is_ok());
+    }
 }
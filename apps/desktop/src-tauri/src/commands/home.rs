@@ -1,13 +1,35 @@
 use crate::AppState;
+use crate::commands::timeout::{with_timeout, CommandError};
+use crate::config::CommandCategory;
 use crate::home::{HubInfo, HomeDevice, CommandResult};
+use std::time::Duration;
 use tauri::State;
 
+/// Browse for hubs for `timeout_ms`. Probing what was found may run past
+/// that by up to the discovery time limit.
 #[tauri::command]
 pub async fn discover_hubs(
     state: State<'_, AppState>,
-    timeout_ms: u64
-) -> Result<Vec<HubInfo>, String> {
-    state.home.discover_hubs(timeout_ms).await
+    timeout_ms: u64,
+    max_hubs: Option<usize>
+) -> Result<Vec<HubInfo>, CommandError> {
+    let limit = Duration::from_millis(timeout_ms) + state.config.command_timeout(CommandCategory::Discovery);
+    with_timeout("discover_hubs", limit, state.home.discover_hubs(timeout_ms, max_hubs))
+        .await?
+        .map_err(CommandError::from)
+}
+
+#[tauri::command]
+pub fn stop_discovery(state: State<'_, AppState>) {
+    state.home.stop_discovery();
+}
+
+#[tauri::command]
+pub async fn forget_hub(
+    state: State<'_, AppState>,
+    hub_url: String
+) -> Result<bool, String> {
+    state.home.forget_hub(&hub_url).await
 }
 
 #[tauri::command]
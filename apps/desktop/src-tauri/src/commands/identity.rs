@@ -2,9 +2,19 @@
 //!
 //! Commands for managing the user's cryptographic identity.
 
+use crate::cache_rebuild;
+use crate::commands::audit;
+use crate::crypto::identity_card::IdentityCard;
+use crate::crypto::migration::{MigrationError, MigrationToken};
+use crate::crypto::{IdentityKeys, SignatureDomain, StoredEncryptionKeys};
+use crate::migration_handoff;
+use crate::stellar::StellarService;
+use crate::storage::{AuditAction, Contact, Database};
 use crate::AppState;
 use gns_crypto_core::GnsIdentity;
-use tauri::State;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, State};
+use tokio::sync::Mutex;
 
 /// Get the user's Ed25519 public key (hex)
 #[tauri::command]
@@ -13,18 +23,92 @@ pub async fn get_public_key(state: State<'_, AppState>) -> Result<Option<String>
     Ok(identity.public_key_hex())
 }
 
-/// Sign a string message with the user's private key
+/// Sign a string message with the user's private key. Messages starting
+/// with a signature domain tag are refused: their raw signature would pass
+/// as one the app made for that domain.
 #[tauri::command]
 pub async fn sign_string(
     message: String,
     state: State<'_, AppState>,
 ) -> Result<Option<String>, String> {
-    let identity = state.identity.lock().await; // Do not hold lock across await if possible, but signing is fast
-    // Actually, signing doesn't yield, so it's fine.
-    // However, identity.sign_string might handle the error internally, let's check identity implementation
-    // But wait, the grep showed sign_string returns Option<String> in crypto/mod.rs
-    
-    Ok(identity.sign_string(&message))
+    let signature = match check_raw_message(&message) {
+        Ok(()) => Ok(state.identity.lock().await.sign_string(&message)),
+        Err(e) => Err(e),
+    };
+
+    // Log a digest, not the message itself
+    let digest = hex::encode(Sha256::digest(message.as_bytes()));
+    let outcome = match &signature {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err("No identity found"),
+        Err(e) => Err(e.as_str()),
+    };
+    audit::record(&state.database, AuditAction::SignMessage, Some(&digest[..16]), &outcome).await;
+
+    signature
+}
+
+/// Refuse raw signing of a message carrying a signature domain tag
+fn check_raw_message(message: &str) -> Result<(), String> {
+    match SignatureDomain::tagged_in(message.as_bytes()) {
+        Some(domain) => Err(format!("Refusing to sign a message tagged \"{}\"", domain.prefix())),
+        None => Ok(()),
+    }
+}
+
+/// Longest intent description kept in the audit log
+const MAX_INTENT_LEN: usize = 256;
+
+/// A signature over structured data in the `Typed` domain
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TypedDataSignature {
+    pub public_key: String,
+    pub signature: String,
+    /// Hex of the exact bytes signed, domain tag included, so a verifier
+    /// can check them without re-canonicalizing the payload
+    pub signed_bytes: String,
+}
+
+/// Sign a JSON payload on behalf of `domain` (e.g. "app.example/login").
+/// The payload is canonicalized and prefixed with the domain before
+/// signing, so the same data signed for two apps gives two unrelated
+/// signatures. `intent_description` says what the user agreed to and is
+/// recorded in the audit log.
+#[tauri::command]
+pub async fn sign_typed_data(
+    domain: String,
+    intent_description: String,
+    payload_json: String,
+    state: State<'_, AppState>,
+) -> Result<TypedDataSignature, String> {
+    let intent = intent_description.trim();
+    if intent.is_empty() {
+        return Err("An intent description is required".to_string());
+    }
+    if intent.chars().count() > MAX_INTENT_LEN {
+        return Err(format!("Intent description is longer than {} characters", MAX_INTENT_LEN));
+    }
+
+    let payload: serde_json::Value =
+        serde_json::from_str(&payload_json).map_err(|e| format!("Invalid payload JSON: {}", e))?;
+    let message = gns_crypto_core::typed_data_message(&domain, &payload).map_err(|e| e.to_string())?;
+
+    let signed = {
+        let identity = state.identity.lock().await;
+        identity
+            .get_identity()
+            .map(|i| TypedDataSignature {
+                public_key: i.public_key_hex(),
+                signature: hex::encode(i.sign_in_domain(SignatureDomain::Typed, &message)),
+                signed_bytes: hex::encode(SignatureDomain::Typed.tag(&message)),
+            })
+            .ok_or_else(|| "No identity found".to_string())
+    };
+
+    let target = format!("{}: {}", domain, intent);
+    audit::record(&state.database, AuditAction::SignTypedData, Some(&target), &signed).await;
+
+    signed
 }
 
 /// Get the user's X25519 encryption key (hex)
@@ -34,6 +118,144 @@ pub async fn get_encryption_key(state: State<'_, AppState>) -> Result<Option<Str
     Ok(identity.encryption_key_hex())
 }
 
+/// X25519 encryption key (hex) an Ed25519 public key converts to. Only a
+/// fallback for when the peer's record can't be fetched: it's wrong for
+/// anyone who has rotated their encryption key, see
+/// `gns_crypto_core::ed25519_public_to_x25519_public`.
+#[tauri::command]
+pub fn ed25519_public_to_x25519_public(public_key: String) -> Result<String, String> {
+    gns_crypto_core::ed25519_public_to_x25519_public(public_key.trim()).map_err(|e| e.to_string())
+}
+
+/// Result of rotating the encryption key
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EncryptionKeyRotation {
+    pub encryption_key: String,
+    /// Older keys still kept for decrypting messages sent to them
+    pub retired_keys: usize,
+    /// Whether the signed record carrying the new key reached the network.
+    /// Until it does, senders keep encrypting to the old key.
+    pub published: bool,
+    pub error: Option<String>,
+}
+
+/// Replace the X25519 encryption key with a fresh one, independent of the
+/// identity key, and publish it in the signed record. Messages already
+/// sent to the old key still decrypt; see `gns_crypto_core::identity` for
+/// what rotation does and doesn't protect.
+#[tauri::command]
+pub async fn rotate_encryption_key(app: AppHandle, state: State<'_, AppState>) -> Result<EncryptionKeyRotation, String> {
+    let rotated = {
+        let mut identity = state.identity.lock().await;
+        identity.rotate_encryption_key().map(|key| {
+            let retired = identity
+                .get_identity()
+                .map_or(0, |i| i.retired_encryption_secrets().len());
+            (key, retired)
+        })
+    };
+    let target = rotated.as_ref().ok().map(|(key, _)| key[..16].to_string());
+    audit::record(&state.database, AuditAction::RotateEncryptionKey, target.as_deref(), &rotated).await;
+    let (encryption_key, retired_keys) = rotated.map_err(|e| e.to_string())?;
+    tracing::info!("🔑 Encryption key rotated ({} retired)", retired_keys);
+    if let Some(public_key) = state.identity.lock().await.public_key_hex() {
+        cache_rebuild::rebuild_in_background(app, state.identity.clone(), state.database.clone(), public_key);
+    }
+
+    let published = crate::commands::commands_handle::publish_identity_record(&state).await;
+    if let Err(e) = &published {
+        tracing::warn!("⚠️ New encryption key not published yet: {}", e);
+    }
+
+    Ok(EncryptionKeyRotation {
+        encryption_key,
+        retired_keys,
+        published: published.is_ok(),
+        error: published.err(),
+    })
+}
+
+/// Result of rotating the identity's signing key
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SigningKeyRotation {
+    pub previous_key: String,
+    pub public_key: String,
+    pub encryption_key: String,
+    /// Whether the record under the new key, carrying the rotation
+    /// certificates, reached the network
+    pub published: bool,
+    pub error: Option<String>,
+}
+
+/// Replace the Ed25519 identity key with a fresh one, signed over to by
+/// the old key. The handle stays; threads are re-filed under the new key,
+/// the relay is re-subscribed as it, and the record is published with
+/// every rotation certificate so far, so resolvers can follow the chain
+/// from any earlier key. Messages the old key signed still verify
+/// against it. The Stellar wallet stays with the first key, which is
+/// kept to sign for it.
+#[tauri::command]
+pub async fn rotate_signing_key(state: State<'_, AppState>) -> Result<SigningKeyRotation, String> {
+    let rotated = {
+        let mut identity = state.identity.lock().await;
+        let mut db = state.database.lock().await;
+        identity
+            .rotate_key(|certificate| db.record_key_rotation(certificate))
+            .map(|certificate| (certificate, identity.encryption_key_hex().unwrap_or_default()))
+    };
+    let target = rotated.as_ref().ok().map(|(certificate, _)| certificate.previous_key[..16].to_string());
+    audit::record(&state.database, AuditAction::RotateSigningKey, target.as_deref(), &rotated).await;
+    let (certificate, encryption_key) = rotated.map_err(|e| e.to_string())?;
+    tracing::info!("🔑 Signing key rotated: {}… → {}…", &certificate.previous_key[..16], &certificate.new_key[..16]);
+
+    {
+        let mut db = state.database.lock().await;
+        // Direct thread ids are derived from our key
+        if let Err(e) = db.migrate_thread_ids(&certificate.new_key) {
+            tracing::warn!("⚠️ Could not re-file threads under the new key: {}", e);
+        }
+    }
+    {
+        // The relay delivers by key, so subscribe again as the new one
+        let relay = state.relay.lock().await;
+        let _ = relay.disconnect().await;
+        if let Err(e) = relay.connect(&certificate.new_key).await {
+            tracing::warn!("⚠️ Relay reconnect under the new key failed: {}", e);
+        }
+    }
+
+    let published = crate::commands::commands_handle::publish_identity_record(&state).await;
+    if let Err(e) = &published {
+        tracing::warn!("⚠️ Record under the new key not published yet: {}", e);
+    }
+
+    Ok(SigningKeyRotation {
+        previous_key: certificate.previous_key,
+        public_key: certificate.new_key,
+        encryption_key,
+        published: published.is_ok(),
+        error: published.err(),
+    })
+}
+
+/// Every signing key this identity has used, oldest first, ending with
+/// the current one
+#[tauri::command]
+pub async fn get_key_history(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    let public_key = state.identity.lock().await.public_key_hex().ok_or("No identity found")?;
+    let history = state.database.lock().await.key_history(&public_key);
+    history.map_err(|e| e.to_string())
+}
+
+/// Forget the cached per-peer message keys. Call when the app locks; the
+/// next message to or from each peer derives its key afresh.
+#[tauri::command]
+pub async fn lock_message_keys(state: State<'_, AppState>) -> Result<(), String> {
+    state.identity.lock().await.clear_message_keys();
+    tracing::info!("🔒 Message key cache cleared");
+    Ok(())
+}
+
 /// Get the user's current claimed @handle (if any)
 #[tauri::command]
 pub async fn get_current_handle(state: State<'_, AppState>) -> Result<Option<String>, String> {
@@ -79,31 +301,90 @@ pub async fn generate_identity(state: State<'_, AppState>) -> Result<IdentityInf
 
     identity.generate_new().map_err(|e| e.to_string())?;
 
+    let public_key = identity.public_key_hex().unwrap_or_default();
+    cache_derived_keys(&state.database, &public_key).await;
+
     Ok(IdentityInfo {
-        public_key: identity.public_key_hex().unwrap_or_default(),
+        public_key,
         encryption_key: identity.encryption_key_hex().unwrap_or_default(),
     })
 }
 
 /// Import an identity from private key hex
+///
+/// If `expected_public_key` is given (e.g. from a backup), the import is
+/// refused unless the private key actually belongs to it.
 #[tauri::command]
 pub async fn import_identity(
     private_key_hex: String,
+    expected_public_key: Option<String>,
+    app: AppHandle,
     state: State<'_, AppState>,
 ) -> Result<IdentityInfo, String> {
-    let mut identity = state.identity.lock().await;
-
     // Validate the private key first
-    let test_identity = GnsIdentity::from_hex(&private_key_hex)
-        .map_err(|e| format!("Invalid private key: {}", e))?;
+    let test_identity = check_import_key(&private_key_hex, expected_public_key.as_deref())?;
+    store_imported_identity(app, &state, &test_identity).await
+}
+
+/// Import an identity from a Stellar secret key (S...)
+///
+/// The secret's seed is the GNS private key, so the wallet's G... address
+/// stays the same. If `expected_address` is given, the import is refused
+/// unless the secret belongs to it.
+#[tauri::command]
+pub async fn import_stellar_secret(
+    secret_key: String,
+    expected_address: Option<String>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<StellarIdentityInfo, String> {
+    let (test_identity, stellar_address) = check_stellar_secret(&secret_key, expected_address.as_deref())?;
+    let info = store_imported_identity(app, &state, &test_identity).await?;
+
+    Ok(StellarIdentityInfo {
+        public_key: info.public_key,
+        encryption_key: info.encryption_key,
+        stellar_address,
+    })
+}
+
+/// Put a validated identity in the keychain, recording the import. Its
+/// encryption key may differ from the one messages on this device were
+/// cached under, so the decrypted cache is rebuilt.
+async fn store_imported_identity(
+    app: AppHandle,
+    state: &AppState,
+    test_identity: &GnsIdentity,
+) -> Result<IdentityInfo, String> {
+    let keys = IdentityKeys {
+        private_key: test_identity.private_key_hex(),
+        encryption_keys: None,
+        wallet_key: None,
+    };
+    store_imported_keys(app, state, &keys, test_identity).await
+}
+
+/// [`store_imported_identity`] with rotated encryption and wallet keys
+async fn store_imported_keys(
+    app: AppHandle,
+    state: &AppState,
+    keys: &IdentityKeys,
+    test_identity: &GnsIdentity,
+) -> Result<IdentityInfo, String> {
+    let mut identity = state.identity.lock().await;
 
     // Import into keychain
-    identity
-        .import_from_hex(&private_key_hex)
-        .map_err(|e| e.to_string())?;
+    let imported = identity.import_keys(keys).map_err(|e| e.to_string());
+    drop(identity);
+
+    let public_key = test_identity.public_key_hex();
+    audit::record(&state.database, AuditAction::ImportIdentity, Some(&public_key[..16]), &imported).await;
+    imported?;
+    cache_derived_keys(&state.database, &public_key).await;
+    cache_rebuild::rebuild_in_background(app, state.identity.clone(), state.database.clone(), public_key.clone());
 
     Ok(IdentityInfo {
-        public_key: test_identity.public_key_hex(),
+        public_key,
         encryption_key: test_identity.encryption_key_hex(),
     })
 }
@@ -114,7 +395,7 @@ pub async fn import_identity(
 pub async fn export_identity_backup(state: State<'_, AppState>) -> Result<IdentityBackup, String> {
     let identity = state.identity.lock().await;
 
-    let private_key = identity.private_key_hex().ok_or("No identity to export")?;
+    let keys = identity.export_keys().ok_or("No identity to export")?;
 
     let public_key = identity.public_key_hex().ok_or("No identity to export")?;
 
@@ -123,19 +404,34 @@ pub async fn export_identity_backup(state: State<'_, AppState>) -> Result<Identi
         .ok_or("No identity to export")?;
 
     // Get breadcrumb count
-    let db = state.database.lock().await;
-    let breadcrumb_count = db.count_breadcrumbs().unwrap_or(0);
+    let breadcrumb_count = state.database.lock().await.count_breadcrumbs().unwrap_or(0);
+
+    audit::record_ok(&state.database, AuditAction::ExportIdentity, Some(&public_key[..16])).await;
 
     Ok(IdentityBackup {
-        version: 1,
-        private_key,
+        version: 2,
+        private_key: keys.private_key,
         public_key,
         encryption_key,
+        encryption_keys: keys.encryption_keys,
+        wallet_key: keys.wallet_key,
         breadcrumb_count,
         created_at: chrono::Utc::now().timestamp(),
     })
 }
 
+/// Restore an identity from a backup made by `export_identity_backup`,
+/// including its rotated encryption keys and wallet key
+#[tauri::command]
+pub async fn import_identity_backup(
+    backup: IdentityBackup,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<IdentityInfo, String> {
+    let (keys, test_identity) = check_backup(backup)?;
+    store_imported_keys(app, &state, &keys, &test_identity).await
+}
+
 /// Delete identity from Keychain and clear all local data
 /// ⚠️ This is destructive and cannot be undone!
 #[tauri::command]
@@ -145,7 +441,14 @@ pub async fn delete_identity(state: State<'_, AppState>) -> Result<(), String> {
     // 1. Clear the identity from IdentityManager (clears Keychain)
     {
         let mut identity = state.identity.lock().await;
-        identity.clear().map_err(|e| format!("Failed to clear identity: {}", e))?;
+        let public_key = identity.public_key_hex();
+        let cleared = identity.clear().map_err(|e| format!("Failed to clear identity: {}", e));
+        drop(identity);
+
+        // The audit log survives `clear_all`, so this entry outlives the key
+        let target = public_key.as_deref().map(|pk| &pk[..16]);
+        audit::record(&state.database, AuditAction::DeleteIdentity, target, &cleared).await;
+        cleared?;
     }
     
     // 2. Clear the database
@@ -159,11 +462,278 @@ pub async fn delete_identity(state: State<'_, AppState>) -> Result<(), String> {
         let relay = state.relay.lock().await;
         let _ = relay.disconnect().await;
     }
+    // The next identity starts them again via `initialize_services`
+    state.services.reset();
     
     tracing::info!("✅ Identity deleted successfully");
     Ok(())
 }
 
+/// Create a short-lived, single-use token that moves this identity to
+/// another device. Show it as a QR code; it expires after five minutes.
+/// The new device needs this device online to redeem it, and this device
+/// hands the identity out once unless the token is revoked first.
+/// ⚠️ The token carries the sealed private key - whoever redeems it first gets the identity.
+#[tauri::command]
+pub async fn create_migration_token(state: State<'_, AppState>) -> Result<MigrationTokenInfo, String> {
+    let keys = state.identity.lock().await.export_keys().ok_or("No identity to migrate")?;
+    let (token, transport_key) = MigrationToken::create(&keys, chrono::Utc::now().timestamp())
+        .map_err(|e| e.to_string())?;
+
+    let mut db = state.database.lock().await;
+    db.record_migration_token(&token.id, &token.public_key, token.expires_at, &transport_key)
+        .map_err(|e| e.to_string())?;
+    drop(db);
+
+    tracing::info!("Created migration token {}", token.id);
+    audit::record_ok(&state.database, AuditAction::CreateMigrationToken, Some(token.id.as_str())).await;
+
+    Ok(MigrationTokenInfo {
+        token_id: token.id.clone(),
+        token: token.encode(),
+        expires_at: token.expires_at,
+    })
+}
+
+/// Revoke a migration token this device created before it is used. No new
+/// device can redeem it afterwards.
+#[tauri::command]
+pub async fn revoke_migration_token(
+    token_id: String,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let mut db = state.database.lock().await;
+    db.revoke_migration_token(&token_id).map_err(|e| e.to_string())
+}
+
+/// Import an identity from a migration token scanned from the old device.
+/// The old device has to be online: it is asked over the relay to release
+/// the token, which it does once and only while the token is unrevoked.
+#[tauri::command]
+pub async fn consume_migration_token(
+    token: String,
+    state: State<'_, AppState>,
+) -> Result<IdentityInfo, String> {
+    if state.identity.lock().await.has_identity() {
+        return Err(MigrationError::IdentityExists.to_string());
+    }
+
+    let relay_url = state.relay.lock().await.url().to_string();
+    let redeemed = redeem_migration_token(&relay_url, &token, chrono::Utc::now().timestamp()).await;
+    if redeemed.is_err() {
+        audit::record(&state.database, AuditAction::ConsumeMigrationToken, None, &redeemed).await;
+    }
+    let keys = redeemed.map_err(|e| e.to_string())?;
+    let migrated = keys.identity().map_err(|e| e.to_string())?;
+
+    // Another identity may have been set up while the old device was answering
+    let mut identity = state.identity.lock().await;
+    if identity.has_identity() {
+        return Err(MigrationError::IdentityExists.to_string());
+    }
+    let imported = identity.import_keys(&keys).map_err(|e| e.to_string());
+    let public_key = migrated.public_key_hex();
+    audit::record(&state.database, AuditAction::ConsumeMigrationToken, Some(&public_key[..16]), &imported).await;
+    imported?;
+    cache_derived_keys(&state.database, &public_key).await;
+
+    tracing::info!("✅ Identity migrated: {}", &migrated.public_key_hex()[..16]);
+
+    Ok(IdentityInfo {
+        public_key: migrated.public_key_hex(),
+        encryption_key: migrated.encryption_key_hex(),
+    })
+}
+
+/// This identity's signed public card, to show as a QR code or share as
+/// a `gns-card:` link. It holds no secrets.
+#[tauri::command]
+pub async fn export_identity_card(state: State<'_, AppState>) -> Result<IdentityCardInfo, String> {
+    let public_key = state.identity.lock().await.public_key_hex().ok_or("No identity configured")?;
+    let display_name = state
+        .database
+        .lock()
+        .await
+        .get_profile(&public_key)
+        .map_err(|e| e.to_string())?
+        .and_then(|profile| profile.display_name);
+
+    let identity = state.identity.lock().await;
+    let gns_identity = identity.get_identity().ok_or("No identity configured")?;
+    let card = IdentityCard::create(gns_identity, identity.cached_handle(), display_name)
+        .map_err(|e| e.to_string())?;
+    drop(identity);
+
+    Ok(IdentityCardInfo {
+        link: card.encode(),
+        card,
+    })
+}
+
+/// Add the owner of a scanned `gns-card:` link as a contact. The card must
+/// be signed by its own key and name that key's Stellar address; its
+/// encryption key is kept so the first message goes out without a lookup.
+/// Its handle is only the signer's claim, so it is kept only if the handle
+/// resolves to the card's key.
+#[tauri::command]
+pub async fn import_identity_card(card: String, state: State<'_, AppState>) -> Result<Contact, String> {
+    let owner_pk = state.identity.lock().await.public_key_hex().ok_or("No identity configured")?;
+    let card = verified_card(&card, &owner_pk)?;
+
+    let handle_owner = match &card.handle {
+        Some(handle) => match state.api.resolve_handle(handle).await {
+            Ok(info) => info.map(|info| info.public_key),
+            Err(e) => {
+                tracing::warn!("⚠️ Could not check the card's handle @{}: {}", handle, e);
+                None
+            }
+        },
+        None => None,
+    };
+
+    let mut db = state.database.lock().await;
+    let contact = add_contact_from_card(&mut db, &owner_pk, card, handle_owner.as_deref(), chrono::Utc::now().timestamp_millis())?;
+    drop(db);
+
+    tracing::info!(
+        "📇 Added contact {} from identity card",
+        contact.handle.as_deref().unwrap_or(&contact.public_key[..16])
+    );
+    Ok(contact)
+}
+
+/// Decode a card and check it is signed by its own key and isn't ours
+fn verified_card(card: &str, owner_pk: &str) -> Result<IdentityCard, String> {
+    let card = IdentityCard::decode(card).map_err(|e| e.to_string())?;
+    card.verify().map_err(|e| e.to_string())?;
+    if card.public_key.eq_ignore_ascii_case(owner_pk) {
+        return Err("That's your own identity card".to_string());
+    }
+    Ok(card)
+}
+
+/// Store a verified card's owner as one of `owner_pk`'s contacts.
+/// `handle_owner` is the key the card's handle resolves to, if known; the
+/// handle is dropped unless that is the card's key, since handle lookups
+/// would otherwise send to whoever signed the card.
+fn add_contact_from_card(
+    db: &mut Database,
+    owner_pk: &str,
+    card: IdentityCard,
+    handle_owner: Option<&str>,
+    now: i64,
+) -> Result<Contact, String> {
+    let handle_verified = handle_owner.is_some_and(|pk| pk.eq_ignore_ascii_case(&card.public_key));
+    if card.handle.is_some() && !handle_verified {
+        tracing::warn!("⚠️ Dropping unverified handle from identity card");
+    }
+
+    let contact = Contact {
+        public_key: card.public_key.to_lowercase(),
+        handle: card.handle.filter(|_| handle_verified),
+        display_name: card.display_name,
+        encryption_key: card.encryption_key,
+        stellar_address: card.stellar_address,
+        added_at: now,
+    };
+    db.save_contact(owner_pk, &contact).map_err(|e| e.to_string())?;
+    if let Err(e) = db.stellar_address(&contact.public_key) {
+        tracing::warn!("⚠️ Could not cache Stellar address: {}", e);
+    }
+    Ok(contact)
+}
+
+/// Derive and store what follows from a new identity's public key, so the
+/// wallet address can be shown before any network call
+pub(crate) async fn cache_derived_keys(database: &Mutex<Database>, public_key: &str) {
+    if let Err(e) = database.lock().await.stellar_address(public_key) {
+        tracing::warn!("⚠️ Could not cache Stellar address: {}", e);
+    }
+}
+
+/// Parse an imported private key and check it derives the expected public
+/// key. The encryption key is derived from the same seed, so a key that
+/// passes here can't be stored alongside someone else's encryption key.
+fn check_import_key(private_key_hex: &str, expected_public_key: Option<&str>) -> Result<GnsIdentity, String> {
+    let identity = GnsIdentity::from_hex(private_key_hex)
+        .map_err(|e| format!("Invalid private key: {}", e))?;
+
+    if let Some(expected) = expected_public_key {
+        if !identity.public_key_hex().eq_ignore_ascii_case(expected.trim()) {
+            return Err(format!(
+                "Private key does not match public key {}",
+                expected
+            ));
+        }
+    }
+
+    Ok(identity)
+}
+
+/// Check a backup's keys make up the identity it claims to be
+fn check_backup(backup: IdentityBackup) -> Result<(IdentityKeys, GnsIdentity), String> {
+    let keys = IdentityKeys {
+        private_key: backup.private_key,
+        encryption_keys: backup.encryption_keys,
+        wallet_key: backup.wallet_key,
+    };
+    let identity = keys.identity().map_err(|e| format!("Invalid backup: {}", e))?;
+    if !identity.public_key_hex().eq_ignore_ascii_case(&backup.public_key)
+        || !identity.encryption_key_hex().eq_ignore_ascii_case(&backup.encryption_key)
+    {
+        return Err("Backup keys do not match its public keys".to_string());
+    }
+    Ok((keys, identity))
+}
+
+/// Decode a Stellar secret into the identity it holds, along with the
+/// G... address derived from it
+fn check_stellar_secret(
+    secret_key: &str,
+    expected_address: Option<&str>,
+) -> Result<(GnsIdentity, String), String> {
+    let seed = StellarService::stellar_secret_to_seed(secret_key.trim()).map_err(|e| e.to_string())?;
+    let identity = GnsIdentity::from_bytes(&seed).map_err(|e| format!("Invalid private key: {}", e))?;
+    let address = StellarService::gns_key_to_stellar(&identity.public_key_hex()).map_err(|e| e.to_string())?;
+
+    if let Some(expected) = expected_address {
+        if address != expected.trim() {
+            return Err(format!("Secret key does not match address {}", expected));
+        }
+    }
+
+    Ok((identity, address))
+}
+
+/// Claim a token from the old device over the relay and open it with the
+/// transport key it releases
+async fn redeem_migration_token(relay_url: &str, token: &str, now: i64) -> Result<IdentityKeys, MigrationError> {
+    let token = MigrationToken::decode(token)?;
+    if now >= token.expires_at {
+        return Err(MigrationError::Expired);
+    }
+
+    let transport_key = migration_handoff::claim(relay_url, &token).await?;
+    token.open(&transport_key, now)
+}
+
+/// A migration token as shown on the old device
+#[derive(serde::Serialize)]
+pub struct MigrationTokenInfo {
+    pub token_id: String,
+    /// `gns-migrate:` deep link to render as a QR code
+    pub token: String,
+    pub expires_at: i64,
+}
+
+/// A card ready to share
+#[derive(serde::Serialize)]
+pub struct IdentityCardInfo {
+    pub card: IdentityCard,
+    /// `gns-card:` deep link to render as a QR code
+    pub link: String,
+}
+
 /// Identity information (safe to expose)
 #[derive(serde::Serialize)]
 pub struct IdentityInfo {
@@ -171,13 +741,262 @@ pub struct IdentityInfo {
     pub encryption_key: String,
 }
 
-/// Identity backup (contains private key!)
+/// Identity imported from a Stellar secret, with its wallet address
 #[derive(serde::Serialize)]
+pub struct StellarIdentityInfo {
+    pub public_key: String,
+    pub encryption_key: String,
+    pub stellar_address: String,
+}
+
+/// Identity backup (contains private key!)
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct IdentityBackup {
     pub version: u32,
     pub private_key: String,
     pub public_key: String,
     pub encryption_key: String,
+    /// Explicit X25519 keys, once the encryption key was rotated
+    #[serde(default)]
+    pub encryption_keys: Option<StoredEncryptionKeys>,
+    /// Seed of the Stellar wallet, once the identity key was rotated away from it
+    #[serde(default)]
+    pub wallet_key: Option<String>,
     pub breadcrumb_count: u32,
     pub created_at: i64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::migration::MIGRATION_TOKEN_TTL_SECS;
+    use crate::crypto::IdentityManager;
+    use crate::message_handler::decrypt_envelope;
+    use gns_crypto_core::MessageKeyCache;
+
+    #[test]
+    fn test_import_key_must_match_expected_public_key() {
+        let identity = GnsIdentity::generate();
+        let private_key = identity.private_key_hex();
+        let public_key = identity.public_key_hex();
+
+        let imported = check_import_key(&private_key, Some(&public_key.to_uppercase())).unwrap();
+        assert_eq!(imported.public_key_hex(), public_key);
+        assert_eq!(imported.encryption_key_hex(), identity.encryption_key_hex());
+        assert!(check_import_key(&private_key, None).is_ok());
+
+        let other = GnsIdentity::generate().public_key_hex();
+        let err = check_import_key(&private_key, Some(&other)).err().unwrap();
+        assert!(err.contains("does not match"), "{}", err);
+    }
+
+    #[test]
+    fn test_raw_signing_refuses_domain_tagged_messages() {
+        for domain in SignatureDomain::ALL {
+            let message = String::from_utf8(domain.tag(b"reserve:alice:2025-01-01T00:00:00Z")).unwrap();
+            let err = check_raw_message(&message).err().unwrap();
+            assert!(err.contains(domain.prefix()), "{}", err);
+        }
+        assert!(check_raw_message("Sign in to example.com").is_ok());
+    }
+
+    #[test]
+    fn test_backend_signatures_are_tagged_only_when_turned_on() {
+        use gns_crypto_core::{verify_in_domain_hex, DomainPolicy};
+
+        let message = "reserve:alice:2025-01-01T00:00:00Z";
+        let verify = |identity: &IdentityManager, policy| {
+            let signature = identity.sign_for_backend(SignatureDomain::Reserve, message).unwrap();
+            let public_key = identity.public_key_hex().unwrap();
+            verify_in_domain_hex(&public_key, SignatureDomain::Reserve, message.as_bytes(), &signature, policy).unwrap()
+        };
+
+        // Bare, so backends verifying untagged signatures keep working
+        let bare = IdentityManager::from_identity(GnsIdentity::generate());
+        assert_eq!(bare.backend_signed_bytes(SignatureDomain::Reserve, message), message.as_bytes());
+        assert!(verify(&bare, DomainPolicy::AllowUntagged));
+        assert!(!verify(&bare, DomainPolicy::Strict));
+
+        let tagged = IdentityManager::from_identity(GnsIdentity::generate()).with_tagged_signatures(true);
+        assert_eq!(
+            tagged.backend_signed_bytes(SignatureDomain::Reserve, message),
+            SignatureDomain::Reserve.tag(message.as_bytes())
+        );
+        assert!(verify(&tagged, DomainPolicy::Strict));
+    }
+
+    #[test]
+    fn test_import_stellar_secret() {
+        let secret = "SAV76USXIJOBMEQXPANUOQM6F5LIOTLPDIDVRJBFFE2MDJXG24TAPUU7";
+        let address = "GCFXHS4GXL6BVUCXBWXGTITROWLVYXQKQLF4YH5O5JT3YZXCYPAFBJZB";
+
+        let (identity, derived) = check_stellar_secret(secret, Some(address)).unwrap();
+        assert_eq!(derived, address);
+        assert_eq!(identity.private_key_hex(), "2bff5257425c161217781b47419e2f56874d6f1a0758a4252934c1a6e6d72607");
+        assert_eq!(identity.encryption_key_hex().len(), 64);
+
+        let other = StellarService::gns_key_to_stellar(&GnsIdentity::generate().public_key_hex()).unwrap();
+        let err = check_stellar_secret(secret, Some(&other)).err().unwrap();
+        assert!(err.contains("does not match"), "{}", err);
+    }
+
+    #[test]
+    fn test_import_corrupted_stellar_secret() {
+        // One character off
+        let corrupted = "SAV76USXIJOBMEQXPANUAQM6F5LIOTLPDIDVRJBFFE2MDJXG24TAPUU7";
+        let err = check_stellar_secret(corrupted, None).err().unwrap();
+        assert!(err.contains("checksum mismatch"), "{}", err);
+
+        // An address is not a secret
+        let err = check_stellar_secret("GCFXHS4GXL6BVUCXBWXGTITROWLVYXQKQLF4YH5O5JT3YZXCYPAFBJZB", None).err().unwrap();
+        assert!(err.contains("Invalid Stellar secret key"), "{}", err);
+    }
+
+    #[test]
+    fn test_backup_restores_rotated_encryption_and_wallet_keys() {
+        keyring::set_default_credential_builder(keyring::mock::default_credential_builder());
+        let wallet = GnsIdentity::generate();
+        let mut original = GnsIdentity::generate();
+        original.rotate_encryption_key();
+        let keys = IdentityKeys::of(&original, Some(&wallet));
+        let backup = IdentityBackup {
+            version: 2,
+            private_key: keys.private_key.clone(),
+            public_key: original.public_key_hex(),
+            encryption_key: original.encryption_key_hex(),
+            encryption_keys: keys.encryption_keys.clone(),
+            wallet_key: keys.wallet_key.clone(),
+            breadcrumb_count: 0,
+            created_at: 0,
+        };
+        let json = serde_json::to_string(&backup).unwrap();
+
+        let (restored, _) = check_backup(serde_json::from_str(&json).unwrap()).unwrap();
+        let mut manager = IdentityManager::from_identity(GnsIdentity::generate());
+        manager.import_keys(&restored).unwrap();
+        let identity = manager.get_identity().unwrap();
+        assert_eq!(identity.encryption_key_hex(), original.encryption_key_hex());
+        assert_eq!(identity.retired_encryption_secrets(), original.retired_encryption_secrets());
+        assert_eq!(manager.wallet_public_key(), Some(wallet.public_key_hex()));
+
+        // Without its encryption keys the backup no longer matches itself
+        let mut stripped: IdentityBackup = serde_json::from_str(&json).unwrap();
+        stripped.encryption_keys = None;
+        assert!(check_backup(stripped).is_err());
+    }
+
+    #[test]
+    fn test_version_1_backup_still_imports() {
+        let identity = GnsIdentity::generate();
+        let json = serde_json::json!({
+            "version": 1,
+            "private_key": identity.private_key_hex(),
+            "public_key": identity.public_key_hex(),
+            "encryption_key": identity.encryption_key_hex(),
+            "breadcrumb_count": 3,
+            "created_at": 0,
+        });
+        let (keys, restored) = check_backup(serde_json::from_value(json).unwrap()).unwrap();
+        assert_eq!(keys, IdentityKeys::of(&identity, None));
+        assert_eq!(restored.public_key_hex(), identity.public_key_hex());
+    }
+
+    /// Run a claim through the old device's ledger the way the relay would
+    fn claim_through(
+        old_device: &mut Database,
+        identity: &GnsIdentity,
+        token: &MigrationToken,
+        now: i64,
+    ) -> Option<Result<String, MigrationError>> {
+        let new_device = GnsIdentity::generate();
+        let claim = migration_handoff::claim_envelope(&new_device, token).unwrap();
+        let event = decrypt_envelope(identity, &MessageKeyCache::default(), None, &claim).unwrap();
+        let release = migration_handoff::answer_claim(old_device, identity, &event, now).unwrap()?;
+        migration_handoff::read_release(&new_device, token, &release)
+    }
+
+    #[test]
+    fn test_migration_token_redeems_once() {
+        let mut old_device = Database::open_in_memory().unwrap();
+        let identity = GnsIdentity::generate();
+        let (token, transport_key) = MigrationToken::create(&IdentityKeys::of(&identity, None), 1_000).unwrap();
+        old_device.record_migration_token(&token.id, &token.public_key, token.expires_at, &transport_key).unwrap();
+
+        let released = claim_through(&mut old_device, &identity, &token, 1_010).unwrap().unwrap();
+        let migrated = token.open(&released, 1_010).unwrap().identity().unwrap();
+        assert_eq!(migrated.public_key_hex(), identity.public_key_hex());
+
+        // A second device scanning the same QR code is turned away
+        assert!(matches!(
+            claim_through(&mut old_device, &identity, &token, 1_020),
+            Some(Err(MigrationError::AlreadyUsed))
+        ));
+    }
+
+    #[test]
+    fn test_migration_token_expired_or_revoked() {
+        let identity = GnsIdentity::generate();
+        let mut old_device = Database::open_in_memory().unwrap();
+        // Another device already signed in as the same identity
+        let mut other_device = Database::open_in_memory().unwrap();
+
+        let (expired, key) = MigrationToken::create(&IdentityKeys::of(&identity, None), 1_000).unwrap();
+        old_device.record_migration_token(&expired.id, &expired.public_key, expired.expires_at, &key).unwrap();
+        assert!(matches!(
+            claim_through(&mut old_device, &identity, &expired, 1_000 + MIGRATION_TOKEN_TTL_SECS),
+            Some(Err(MigrationError::Expired))
+        ));
+
+        let (revoked, key) = MigrationToken::create(&IdentityKeys::of(&identity, None), 1_000).unwrap();
+        old_device.record_migration_token(&revoked.id, &revoked.public_key, revoked.expires_at, &key).unwrap();
+        assert!(old_device.revoke_migration_token(&revoked.id).unwrap());
+        assert!(matches!(
+            claim_through(&mut old_device, &identity, &revoked, 1_010),
+            Some(Err(MigrationError::Revoked))
+        ));
+
+        // Devices that didn't issue the token don't answer for it
+        assert!(claim_through(&mut other_device, &identity, &revoked, 1_010).is_none());
+        assert_eq!(other_device.migration_token_status(&revoked.id).unwrap(), None);
+    }
+
+    #[test]
+    fn test_card_import_adds_contact_with_key() {
+        let mut db = Database::open_in_memory().unwrap();
+        let (me, bob) = (GnsIdentity::generate(), GnsIdentity::generate());
+        let card = IdentityCard::create(&bob, Some("bob".into()), Some("Bob".into())).unwrap().encode();
+        let import = |db: &mut Database, card: &str, handle_owner: Option<&str>| {
+            let card = verified_card(card, &me.public_key_hex())?;
+            add_contact_from_card(db, &me.public_key_hex(), card, handle_owner, 1_000)
+        };
+
+        let contact = import(&mut db, &card, Some(&bob.public_key_hex())).unwrap();
+        assert_eq!(contact.encryption_key, bob.encryption_key_hex());
+        let stored = db.find_contact_by_handle(&me.public_key_hex(), "@bob").unwrap();
+        assert_eq!(stored, Some(contact));
+
+        // A forged card stores nothing
+        let mut forged = IdentityCard::create(&bob, Some("carol".into()), None).unwrap();
+        forged.encryption_key = GnsIdentity::generate().encryption_key_hex();
+        assert!(import(&mut db, &forged.encode(), None).is_err());
+        assert!(db.find_contact_by_handle(&me.public_key_hex(), "carol").unwrap().is_none());
+
+        let own = IdentityCard::create(&me, None, None).unwrap().encode();
+        assert!(import(&mut db, &own, None).is_err());
+    }
+
+    #[test]
+    fn test_card_handle_is_kept_only_if_it_resolves_to_the_card() {
+        let mut db = Database::open_in_memory().unwrap();
+        let (me, alice, mallory) = (GnsIdentity::generate(), GnsIdentity::generate(), GnsIdentity::generate());
+        let card = IdentityCard::create(&mallory, Some("alice".into()), None).unwrap().encode();
+
+        for handle_owner in [Some(alice.public_key_hex()), None] {
+            let verified = verified_card(&card, &me.public_key_hex()).unwrap();
+            let contact =
+                add_contact_from_card(&mut db, &me.public_key_hex(), verified, handle_owner.as_deref(), 1_000).unwrap();
+            assert_eq!(contact.handle, None);
+            assert!(db.find_contact_by_handle(&me.public_key_hex(), "alice").unwrap().is_none());
+        }
+    }
+}
@@ -3,10 +3,19 @@
 //! Commands for sending and receiving encrypted messages.
 
 use crate::AppState;
+use crate::cache_rebuild::{self, CacheRebuildReport};
+use crate::commands::audit;
+use crate::commands::timeout::{with_timeout, CommandError};
+use crate::config::CommandCategory;
+use crate::storage::{self, AuditAction, MessageSearchHit, ScheduledMessage, SearchOrder, ThreadMeta, ThreadTranscript};
+use crate::thread_meta;
 // TODO: Add envelope function when implemented
 // use gns_crypto_core::GnsIdentity;
-use tauri::State;
-use gns_crypto_core::create_envelope_with_metadata;
+use tauri::{AppHandle, State};
+use gns_crypto_core::{
+    compute_thread_id, create_envelope_with_cache, create_envelope_with_metadata, create_envelope_with_ratchet,
+    ThreadKey,
+};
 use sha2::Digest;
 
 /// Send an encrypted message
@@ -19,6 +28,28 @@ pub async fn send_message(
     thread_id: Option<String>,
     reply_to_id: Option<String>,
     state: State<'_, AppState>,
+) -> Result<SendResult, String> {
+    send(
+        &state,
+        recipient_handle,
+        recipient_public_key,
+        payload_type,
+        payload,
+        thread_id,
+        reply_to_id,
+    )
+    .await
+}
+
+/// The send path shared by `send_message` and the send-later scheduler
+pub(crate) async fn send(
+    state: &AppState,
+    recipient_handle: Option<String>,
+    recipient_public_key: Option<String>,
+    payload_type: String,
+    payload: serde_json::Value,
+    thread_id: Option<String>,
+    reply_to_id: Option<String>,
 ) -> Result<SendResult, String> {
     // Get our identity
     let identity_mgr = state.identity.lock().await;
@@ -28,8 +59,25 @@ pub async fn send_message(
 
     let my_handle = identity_mgr.cached_handle();
 
+    // A contact added from their identity card needs no lookup
+    let contact = {
+        let owner_pk = identity.public_key_hex();
+        let db = state.database.lock().await;
+        let found = match (&recipient_handle, &recipient_public_key) {
+            (Some(handle), _) => db.find_contact_by_handle(&owner_pk, handle),
+            (None, Some(pk)) => db.get_contact(&owner_pk, pk),
+            (None, None) => Ok(None),
+        };
+        found.unwrap_or_else(|e| {
+            tracing::warn!("⚠️ Contact lookup failed: {}", e);
+            None
+        })
+    };
+
     // Resolve recipient
-    let (recipient_pk, recipient_enc_key) = if let Some(handle) = &recipient_handle {
+    let (recipient_pk, recipient_enc_key) = if let Some(contact) = contact {
+        (contact.public_key, contact.encryption_key)
+    } else if let Some(handle) = &recipient_handle {
         // Resolve handle to keys
         let info = state
             .api
@@ -57,17 +105,39 @@ pub async fn send_message(
     let payload_bytes =
         serde_json::to_vec(&payload).map_err(|e| format!("Failed to serialize payload: {}", e))?;
 
-    // Create envelope
-    let envelope = create_envelope_with_metadata(
-        &identity,
-        my_handle.as_deref(),
-        &recipient_pk,
-        &recipient_enc_key,
-        &payload_type,
-        &payload_bytes,
-        thread_id.as_deref(),
-        reply_to_id.as_deref(),
-    )
+    // Carry the canonical id so the recipient files it in the same thread
+    let thread_id =
+        thread_id.unwrap_or_else(|| compute_thread_id(&identity.public_key_hex(), &recipient_pk));
+
+    // Create envelope, in the ratchet session with the recipient if forward secrecy is on
+    let ratchet = identity_mgr.ratchet_sessions();
+    let envelope = match &ratchet {
+        Some(ratchet) => create_envelope_with_ratchet(
+            &identity,
+            my_handle.as_deref(),
+            &recipient_pk,
+            &recipient_enc_key,
+            &payload_type,
+            &payload_bytes,
+            Some(&thread_id),
+            reply_to_id.as_deref(),
+            identity_mgr.compression_for(&recipient_pk),
+            &identity_mgr.message_keys(),
+            ratchet,
+        ),
+        None => create_envelope_with_cache(
+            &identity,
+            my_handle.as_deref(),
+            &recipient_pk,
+            &recipient_enc_key,
+            &payload_type,
+            &payload_bytes,
+            Some(&thread_id),
+            reply_to_id.as_deref(),
+            identity_mgr.compression_for(&recipient_pk),
+            &identity_mgr.message_keys(),
+        ),
+    }
     .map_err(|e| format!("Failed to create envelope: {}", e))?;
 
     // Send via relay
@@ -94,7 +164,7 @@ pub async fn send_message(
         
         if let Err(e) = relay.send_raw(&sync_event.to_string()).await {
              // Non-fatal, just log
-             println!("Failed to sync sent message to browser: {}", e);
+             tracing::debug!(error = %e, "Failed to sync sent message to browser");
         }
     }
 
@@ -105,6 +175,11 @@ pub async fn send_message(
     
     db.save_sent_message(&envelope, &payload_bytes, clean_handle, reply_to_id)
         .map_err(|e| format!("Failed to save locally: {}", e))?;
+    if let Some(ratchet) = &ratchet {
+        if let Err(e) = db.save_ratchet_sessions(&identity.public_key_hex(), ratchet) {
+            tracing::error!("Failed to save ratchet sessions: {}", e);
+        }
+    }
 
     Ok(SendResult {
         message_id: envelope.id.clone(),
@@ -112,16 +187,98 @@ pub async fn send_message(
     })
 }
 
-/// Get all conversation threads
+/// Schedule a message to be sent at `send_at` (unix millis). It goes
+/// through the same path as `send_message` when due; a time in the past
+/// sends it right away.
+#[tauri::command]
+pub async fn schedule_message(
+    request: ScheduleMessageRequest,
+    state: State<'_, AppState>,
+) -> Result<ScheduledMessage, String> {
+    if request.recipient_handle.is_none() && request.recipient_public_key.is_none() {
+        return Err("Must provide either recipient_handle or recipient_public_key".to_string());
+    }
+    if state.identity.lock().await.public_key().is_none() {
+        return Err("No identity configured".to_string());
+    }
+
+    let message = ScheduledMessage {
+        id: uuid::Uuid::new_v4().to_string(),
+        recipient_handle: request.recipient_handle,
+        recipient_public_key: request.recipient_public_key,
+        payload_type: request.payload_type,
+        payload: request.payload,
+        thread_id: request.thread_id,
+        reply_to_id: request.reply_to_id,
+        send_at: request.send_at,
+        created_at: chrono::Utc::now().timestamp_millis(),
+        last_error: None,
+        attempts: 0,
+    };
+    state
+        .database
+        .lock()
+        .await
+        .save_scheduled_message(&message)
+        .map_err(|e| e.to_string())?;
+    state.send_later.wake();
+
+    Ok(message)
+}
+
+/// Messages waiting to be sent, in the order they will go out
+#[tauri::command]
+pub async fn list_scheduled(state: State<'_, AppState>) -> Result<Vec<ScheduledMessage>, String> {
+    let db = state.database.lock().await;
+    db.list_scheduled_messages().map_err(|e| e.to_string())
+}
+
+/// Cancel a scheduled message. Returns false if it was already sent.
+#[tauri::command]
+pub async fn cancel_scheduled(id: String, state: State<'_, AppState>) -> Result<bool, String> {
+    let cancelled = state
+        .database
+        .lock()
+        .await
+        .delete_scheduled_message(&id)
+        .map_err(|e| e.to_string())?;
+    state.send_later.wake();
+    Ok(cancelled)
+}
+
+/// Move a scheduled message to a new send time (unix millis). A message
+/// that was given up on after failing is tried again.
+#[tauri::command]
+pub async fn reschedule(
+    id: String,
+    send_at: i64,
+    state: State<'_, AppState>,
+) -> Result<ScheduledMessage, String> {
+    let mut db = state.database.lock().await;
+    if !db.reschedule_message(&id, send_at).map_err(|e| e.to_string())? {
+        return Err("Scheduled message not found - it may already have been sent".to_string());
+    }
+    let message = db
+        .get_scheduled_message(&id)
+        .map_err(|e| e.to_string())?
+        .ok_or("Scheduled message not found")?;
+    drop(db);
+    state.send_later.wake();
+
+    Ok(message)
+}
+
+/// Get conversation threads, most recent first, a page at a time
 #[tauri::command]
 pub async fn get_threads(
     include_archived: Option<bool>,
     limit: Option<u32>,
+    offset: Option<u32>,
     state: State<'_, AppState>,
 ) -> Result<Vec<ThreadPreview>, String> {
     let db = state.database.lock().await;
     let threads = db
-        .get_threads(include_archived.unwrap_or(false), limit.unwrap_or(50))
+        .get_threads(include_archived.unwrap_or(false), limit.unwrap_or(50), offset.unwrap_or(0))
         .map_err(|e| e.to_string())?;
 
     Ok(threads)
@@ -137,34 +294,250 @@ pub async fn get_thread(
     db.get_thread(&thread_id).map_err(|e| e.to_string())
 }
 
-/// Get messages in a thread
+/// Get the id of the one-to-one thread with `peer` (a public key or
+/// @handle), whether or not any message has been exchanged yet
+#[tauri::command]
+pub async fn get_thread_id(peer: String, state: State<'_, AppState>) -> Result<String, String> {
+    let my_pk = state
+        .identity
+        .lock()
+        .await
+        .public_key_hex()
+        .ok_or("No identity configured")?;
+
+    let is_public_key = peer.len() == 64 && peer.chars().all(|c| c.is_ascii_hexdigit());
+    let peer_pk = if is_public_key {
+        peer
+    } else {
+        state
+            .api
+            .resolve_handle(&peer)
+            .await
+            .map_err(|e| format!("Failed to resolve handle: {}", e))?
+            .ok_or("Handle not found")?
+            .public_key
+    };
+
+    Ok(compute_thread_id(&my_pk, &peer_pk))
+}
+
+/// Get messages in a thread. `payload_types` picks which kinds to return;
+/// without it control messages (reactions, receipts, typing) are left out.
 #[tauri::command]
 pub async fn get_messages(
     thread_id: String,
     limit: Option<u32>,
     _before_id: Option<String>,
+    payload_types: Option<Vec<String>>,
     state: State<'_, AppState>,
 ) -> Result<Vec<Message>, String> {
     let db = state.database.lock().await;
     let messages = db
-        .get_messages(&thread_id, limit.unwrap_or(50))
+        .get_messages(&thread_id, limit.unwrap_or(state.config.message_limit), payload_types.as_deref())
         .map_err(|e| e.to_string())?;
 
     Ok(messages)
 }
 
+/// Search messages in one thread.
+///
+/// Newest matches come first unless `order` is `"relevance"`.
+#[tauri::command]
+pub async fn search_thread(
+    thread_id: String,
+    query: String,
+    order: Option<SearchOrder>,
+    limit: Option<u32>,
+    state: State<'_, AppState>,
+) -> Result<Vec<MessageSearchHit>, String> {
+    let db = state.database.lock().await;
+    db.search_thread(&thread_id, &query, order.unwrap_or_default(), limit.unwrap_or(50).min(200))
+        .map_err(|e| e.to_string())
+}
+
+/// Result of checking an exported transcript
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TranscriptVerification {
+    pub valid: bool,
+    pub public_key: String,
+    pub messages: usize,
+    /// Messages whose sender signature was checked; the rest are vouched
+    /// for by the exporter only
+    pub sender_verified: usize,
+    pub error: Option<String>,
+}
+
+/// Export a thread with each message's sender signature, signed as a
+/// whole by our identity
+#[tauri::command]
+pub async fn export_thread_transcript(
+    thread_id: String,
+    state: State<'_, AppState>,
+) -> Result<ThreadTranscript, String> {
+    let messages = {
+        let db = state.database.lock().await;
+        db.get_transcript_messages(&thread_id).map_err(|e| e.to_string())?
+    };
+    if messages.is_empty() {
+        return Err("Thread has no messages".to_string());
+    }
+
+    let transcript = {
+        let identity = state.identity.lock().await;
+        let gns_identity = identity.get_identity().ok_or("No identity found")?;
+        ThreadTranscript::sign(gns_identity, &thread_id, messages, chrono::Utc::now().timestamp())
+    };
+
+    audit::record_ok(&state.database, AuditAction::ExportTranscript, Some(thread_id.as_str())).await;
+    Ok(transcript)
+}
+
+/// Verify an exported transcript: every sender signature it carries and
+/// the exporter's signature over the whole
+#[tauri::command]
+pub fn verify_transcript(transcript: ThreadTranscript) -> TranscriptVerification {
+    let (sender_verified, error) = match transcript.verify() {
+        Ok(count) => (count, None),
+        Err(e) => (0, Some(e.to_string())),
+    };
+    TranscriptVerification {
+        valid: error.is_none(),
+        public_key: transcript.public_key,
+        messages: transcript.messages.len(),
+        sender_verified,
+        error,
+    }
+}
+
 /// Mark a thread as read
 #[tauri::command]
 pub async fn mark_thread_read(thread_id: String, state: State<'_, AppState>) -> Result<(), String> {
     let mut db = state.database.lock().await;
-    db.mark_thread_read(&thread_id).map_err(|e| e.to_string())
+    db.mark_thread_read(&thread_id).map_err(|e| e.to_string())?;
+    drop(db);
+
+    // Let our other devices know
+    state.read_sync.wake();
+    Ok(())
+}
+
+/// Name a group thread, encrypting the name for its members only.
+///
+/// `members` (public keys) is only needed for a thread without metadata
+/// yet, which this then creates with us as a member. The change is signed
+/// by us and sent to every member; it's stored locally even if some of
+/// them can't be reached.
+#[tauri::command]
+pub async fn set_thread_name(
+    thread_id: String,
+    name: String,
+    members: Option<Vec<String>>,
+    state: State<'_, AppState>,
+) -> Result<ThreadMeta, String> {
+    let name = thread_meta::normalize_name(&name)?;
+    let my_pk = state
+        .identity
+        .lock()
+        .await
+        .public_key_hex()
+        .ok_or("No identity configured")?;
+
+    let (meta, key) = {
+        let mut db = state.database.lock().await;
+        let existing = db.get_thread_meta(&thread_id).map_err(|e| e.to_string())?;
+        let stored_key = db.get_thread_key(&thread_id).map_err(|e| e.to_string())?;
+        let now = chrono::Utc::now().timestamp_millis();
+
+        let (meta, key) = match (existing, stored_key) {
+            (Some(existing), Some(key)) => {
+                if !existing.is_member(&my_pk) {
+                    return Err("Not a member of this thread".to_string());
+                }
+                let key = ThreadKey::from_hex(&key).map_err(|e| e.to_string())?;
+                let meta = ThreadMeta {
+                    name,
+                    author_public_key: my_pk.clone(),
+                    updated_at: now.max(existing.updated_at + 1),
+                    ..existing
+                };
+                (meta, key)
+            }
+            _ => {
+                let mut members: Vec<String> = members
+                    .ok_or("A new thread needs its members")?
+                    .iter()
+                    .map(|m| m.trim().to_lowercase())
+                    .collect();
+                members.push(my_pk.to_lowercase());
+                members.sort();
+                members.dedup();
+                let meta = ThreadMeta {
+                    thread_id: thread_id.clone(),
+                    name,
+                    avatar_ref: None,
+                    members,
+                    author_public_key: my_pk.clone(),
+                    updated_at: now,
+                };
+                (meta, ThreadKey::generate())
+            }
+        };
+        db.save_thread_meta(&meta, &key.to_hex()).map_err(|e| e.to_string())?;
+        (meta, key)
+    };
+
+    if let Err(e) = thread_meta::send(&state, &meta, &key).await {
+        tracing::warn!("⚠️ {}", e);
+    }
+    Ok(meta)
+}
+
+/// Get a group thread's decrypted metadata, if it has any
+#[tauri::command]
+pub async fn get_thread_meta(thread_id: String, state: State<'_, AppState>) -> Result<Option<ThreadMeta>, String> {
+    let db = state.database.lock().await;
+    db.get_thread_meta(&thread_id).map_err(|e| e.to_string())
+}
+
+/// Decrypt the stored envelopes of `identity_pk`'s messages again and
+/// replace cached payloads that don't match, e.g. after restoring a
+/// backup. Reports progress as `decrypted_cache_progress` events.
+#[tauri::command]
+pub async fn rebuild_decrypted_cache(
+    identity_pk: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<CacheRebuildReport, String> {
+    let report = cache_rebuild::rebuild(&state.identity, &state.database, &identity_pk, |report| {
+        cache_rebuild::emit_progress(&app, report)
+    })
+    .await?;
+    tracing::info!(
+        "🔐 Decrypted cache rebuilt: {} replaced, {} undecryptable of {}",
+        report.rebuilt,
+        report.failed,
+        report.total
+    );
+    Ok(report)
 }
 
 /// Delete a thread
 #[tauri::command]
 pub async fn delete_thread(thread_id: String, state: State<'_, AppState>) -> Result<(), String> {
     let mut db = state.database.lock().await;
-    db.delete_thread(&thread_id).map_err(|e| e.to_string())
+    db.delete_thread(&thread_id).map_err(|e| e.to_string())?;
+
+    if db.compaction_due() {
+        drop(db);
+        let database = state.database.clone();
+        tauri::async_runtime::spawn(async move {
+            match storage::compact_in_background(database).await {
+                Ok(report) => tracing::info!("🧹 Auto-compacted database, reclaimed {} bytes", report.bytes_reclaimed),
+                Err(e) => tracing::warn!("⚠️ Auto-compaction failed: {}", e),
+            }
+        });
+    }
+    Ok(())
 }
 
 /// Delete a message
@@ -207,7 +580,7 @@ pub async fn add_reaction(
     let payload_bytes = serde_json::to_vec(&payload).map_err(|e| e.to_string())?;
 
     // Create envelope
-    let envelope = create_envelope_with_metadata(
+    let envelope = create_envelope_with_cache(
         &identity,
         my_handle.as_deref(),
         &recipient_public_key,
@@ -216,6 +589,8 @@ pub async fn add_reaction(
         &payload_bytes,
         None,
         None,
+        identity_mgr.compression_for(&recipient_public_key),
+        &identity_mgr.message_keys(),
     )
     .map_err(|e| format!("Failed to create envelope: {}", e))?;
 
@@ -331,7 +706,7 @@ pub async fn save_sent_email_message(
     let relay = state.relay.lock().await;
     if let Err(e) = relay.send_raw(&sync_event.to_string()).await {
             // Non-fatal, just log
-            println!("Failed to sync sent email to devices: {}", e);
+            tracing::debug!(error = %e, "Failed to sync sent email to devices");
     }
 
     Ok(SendResult {
@@ -359,11 +734,10 @@ pub async fn request_message_decryption(
 pub async fn resolve_handle(
     handle: String,
     state: State<'_, AppState>,
-) -> Result<Option<HandleInfo>, String> {
-    let info = state
-        .api
-        .resolve_handle(&handle)
-        .await
+) -> Result<Option<HandleInfo>, CommandError> {
+    let limit = state.config.command_timeout(CommandCategory::Lookup);
+    let info = with_timeout("resolve_handle", limit, state.api.resolve_handle(&handle))
+        .await?
         .map_err(|e| format!("Failed to resolve handle: {}", e))?;
 
     Ok(info.map(|i| HandleInfo {
@@ -379,6 +753,18 @@ pub async fn resolve_handle(
 
 // ==================== Types ====================
 
+#[derive(serde::Deserialize)]
+pub struct ScheduleMessageRequest {
+    pub recipient_handle: Option<String>,
+    pub recipient_public_key: Option<String>,
+    pub payload_type: String,
+    pub payload: serde_json::Value,
+    /// Unix millis
+    pub send_at: i64,
+    pub thread_id: Option<String>,
+    pub reply_to_id: Option<String>,
+}
+
 #[derive(serde::Serialize)]
 pub struct SendResult {
     pub message_id: String,
@@ -396,6 +782,7 @@ pub struct ThreadPreview {
     pub is_pinned: bool,
     pub is_muted: bool,
     pub subject: Option<String>,
+    pub last_message_id: Option<String>,
 }
 
 #[derive(serde::Serialize, Clone)]
@@ -418,6 +805,8 @@ pub struct Message {
     pub reply_to_id: Option<String>,
     pub is_starred: bool,
     pub forwarded_from_id: Option<String>,
+    /// Incoming messages only: read here or on another of our devices
+    pub is_read: bool,
     pub reactions: Vec<Reaction>,
 }
 
@@ -9,6 +9,11 @@
 //! - network: Connection management
 //! - stellar: Stellar/GNS token operations
 //! - utils: Miscellaneous utilities
+//! - settings: Runtime endpoint configuration
+//! - notifications: Mention and message notifications, notification preferences
+//! - audit: Security audit log
+//! - devices: Devices the identity is active on
+//! - timeout: Time limits of network-bound commands
 
 pub mod identity;
 pub mod commands_handle;
@@ -21,3 +26,8 @@ pub mod utils;
 pub mod dix;
 pub mod home;
 pub mod profile;
+pub mod settings;
+pub mod notifications;
+pub mod audit;
+pub mod devices;
+pub mod timeout;
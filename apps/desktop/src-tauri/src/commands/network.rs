@@ -2,6 +2,7 @@
 //!
 //! Commands for managing network connectivity.
 
+use crate::services::{self, ServicesStatus};
 use crate::AppState;
 use tauri::State;
 
@@ -15,9 +16,30 @@ pub async fn get_connection_status(state: State<'_, AppState>) -> Result<Connect
         relay_url: relay.url().to_string(),
         last_message_at: relay.last_message_time().await,
         reconnect_attempts: relay.reconnect_attempts().await,
+        relay_subscribed: relay.is_ready(),
     })
 }
 
+/// Connect to the relay and wait until it confirms the subscription.
+///
+/// Starts services first if they aren't running yet. Once this returns,
+/// incoming messages reach the handler; `relay_ready` is emitted here and
+/// again after every reconnect.
+#[tauri::command]
+pub async fn connect_relay(state: State<'_, AppState>) -> Result<ConnectionStatus, String> {
+    services::ensure_relay_ready(&state).await?;
+    get_connection_status(state).await
+}
+
+/// Start the relay connection and other network-bound services.
+///
+/// Call once onboarding has created or imported an identity; before that
+/// it returns `no_identity` and does nothing. Safe to call repeatedly.
+#[tauri::command]
+pub async fn initialize_services(state: State<'_, AppState>) -> Result<ServicesStatus, String> {
+    Ok(services::initialize(&state).await)
+}
+
 /// Force reconnect to relay
 #[tauri::command]
 pub async fn reconnect(state: State<'_, AppState>) -> Result<(), String> {
@@ -35,4 +57,6 @@ pub struct ConnectionStatus {
     pub relay_url: String,
     pub last_message_at: Option<i64>,
     pub reconnect_attempts: u32,
+    /// The relay has confirmed the subscription; messages are flowing
+    pub relay_subscribed: bool,
 }
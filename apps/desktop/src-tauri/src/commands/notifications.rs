@@ -0,0 +1,205 @@
+//! Notification Commands
+//!
+//! Local notifications: being mentioned in a fetched DIX post (each post
+//! notifies at most once) and incoming messages and reactions. Whether
+//! they alert is up to the identity's preferences (see `notifications`).
+
+use crate::dix::{mentions_handle, DixPost};
+use crate::message_handler::IncomingMessageEvent;
+use crate::notifications::{
+    delivery_for, record, Delivery, NotificationCategory, NotificationEvent, NotificationPrefs,
+};
+use crate::settings::{load_notifications_enabled, save_notifications_enabled};
+use crate::storage::{Database, Notification};
+use crate::AppState;
+use tauri::{AppHandle, Emitter, State};
+use tauri_plugin_notification::NotificationExt;
+use tokio::sync::Mutex;
+
+const REACTION_PAYLOAD_TYPE: &str = "reaction";
+const PREVIEW_CHARS: usize = 140;
+
+#[tauri::command]
+pub async fn get_notifications(
+    state: State<'_, AppState>,
+    unread_only: Option<bool>,
+    limit: Option<u32>,
+) -> Result<Vec<Notification>, String> {
+    let owner_pk = {
+        let identity = state.identity.lock().await;
+        identity.public_key_hex().ok_or("No identity")?
+    };
+
+    let db = state.database.lock().await;
+    db.get_notifications(&owner_pk, unread_only.unwrap_or(false), limit.unwrap_or(50))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn mark_notification_read(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: i64,
+) -> Result<(), String> {
+    let owner_pk = {
+        let identity = state.identity.lock().await;
+        identity.public_key_hex().ok_or("No identity")?
+    };
+
+    let mut db = state.database.lock().await;
+    db.mark_notification_read(&owner_pk, id).map_err(|e| e.to_string())?;
+    drop(db);
+
+    let _ = app.emit("notification_read", serde_json::json!({ "id": id }));
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_notifications_enabled(state: State<'_, AppState>) -> Result<bool, String> {
+    let db = state.database.lock().await;
+    Ok(load_notifications_enabled(&db))
+}
+
+#[tauri::command]
+pub async fn set_notifications_enabled(
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    let mut db = state.database.lock().await;
+    save_notifications_enabled(&mut db, enabled).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_notification_prefs(state: State<'_, AppState>) -> Result<NotificationPrefs, String> {
+    let owner_pk = {
+        let identity = state.identity.lock().await;
+        identity.public_key_hex().ok_or("No identity")?
+    };
+
+    let db = state.database.lock().await;
+    db.load_notification_prefs(&owner_pk).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_notification_prefs(
+    state: State<'_, AppState>,
+    prefs: NotificationPrefs,
+) -> Result<(), String> {
+    prefs.validate()?;
+    let owner_pk = {
+        let identity = state.identity.lock().await;
+        identity.public_key_hex().ok_or("No identity")?
+    };
+
+    let db = state.database.lock().await;
+    db.save_notification_prefs(&owner_pk, &prefs).map_err(|e| e.to_string())
+}
+
+/// Record a notification for each post that mentions our current handle,
+/// showing it too unless mentions are muted or it's quiet hours.
+/// Already-seen posts are skipped.
+pub(crate) async fn notify_mentions(app: &AppHandle, state: &AppState, posts: &[DixPost]) {
+    let (owner_pk, handle) = {
+        let identity = state.identity.lock().await;
+        match (identity.public_key_hex(), identity.cached_handle()) {
+            (Some(pk), Some(handle)) => (pk, handle),
+            _ => return,
+        }
+    };
+
+    let mut db = state.database.lock().await;
+    let now = chrono::Local::now().time();
+
+    for post in posts {
+        if post.author.public_key.eq_ignore_ascii_case(&owner_pk) || !mentions_handle(post, &handle) {
+            continue;
+        }
+
+        let preview: String = post.content.text.chars().take(PREVIEW_CHARS).collect();
+        let event = NotificationEvent {
+            category: NotificationCategory::Mentions,
+            item_id: &post.id,
+            actor_public_key: &post.author.public_key,
+            actor_handle: post.author.handle.as_deref(),
+            preview: &preview,
+        };
+        let delivery = match record(&mut db, &owner_pk, &event, now) {
+            Ok(Some(delivery)) => delivery,
+            Ok(None) => continue,
+            Err(e) => {
+                tracing::warn!("Failed to record mention notification: {}", e);
+                continue;
+            }
+        };
+
+        tracing::info!("🔔 Mentioned in post {}", post.id);
+        let _ = app.emit("mention_received", serde_json::json!({ "post_id": post.id }));
+
+        if delivery == Delivery::Alert {
+            let author = sender_name(post.author.handle.as_deref(), &post.author.public_key);
+            show(app, format!("{} mentioned you", author), &preview);
+        }
+    }
+}
+
+/// Alert for an incoming message or reaction, unless its category or its
+/// thread is muted or it's quiet hours. The message is already stored and
+/// counted as unread either way.
+pub(crate) async fn notify_message(
+    app: &AppHandle,
+    database: &Mutex<Database>,
+    my_pk: &str,
+    event: &IncomingMessageEvent,
+) {
+    if event.from_public_key.eq_ignore_ascii_case(my_pk) {
+        return;
+    }
+    let category = if event.payload_type == REACTION_PAYLOAD_TYPE {
+        NotificationCategory::Reactions
+    } else {
+        NotificationCategory::DirectMessages
+    };
+
+    let delivery = {
+        let db = database.lock().await;
+        let thread_muted = event
+            .thread_id
+            .as_deref()
+            .and_then(|id| db.get_thread(id).ok().flatten())
+            .is_some_and(|thread| thread.is_muted);
+        if thread_muted {
+            Delivery::Muted
+        } else {
+            delivery_for(&db, my_pk, category, chrono::Local::now().time())
+        }
+    };
+    if delivery != Delivery::Alert {
+        tracing::debug!(?delivery, "Holding back alert for message {}", event.id);
+        return;
+    }
+
+    let sender = sender_name(event.from_handle.as_deref(), &event.from_public_key);
+    let text = |key: &str| event.payload.get(key).and_then(|v| v.as_str()).map(|s| s.to_string());
+    let (title, body) = match category {
+        NotificationCategory::Reactions => (format!("{} reacted", sender), text("emoji").unwrap_or_default()),
+        _ => (
+            format!("Message from {}", sender),
+            text("text").or_else(|| text("subject")).unwrap_or_else(|| "New message".to_string()),
+        ),
+    };
+    let body: String = body.chars().take(PREVIEW_CHARS).collect();
+    show(app, title, &body);
+}
+
+/// `@handle`, or the start of the public key
+fn sender_name(handle: Option<&str>, public_key: &str) -> String {
+    handle
+        .map(|h| format!("@{}", h))
+        .unwrap_or_else(|| format!("{}…", public_key.get(..8).unwrap_or(public_key)))
+}
+
+fn show(app: &AppHandle, title: String, body: &str) {
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+        tracing::warn!("Failed to show notification: {}", e);
+    }
+}
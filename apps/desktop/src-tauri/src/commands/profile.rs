@@ -1,40 +1,34 @@
 //! Profile Commands
 //!
 //! Commands for managing the user's profile data (name, bio, avatar, etc.)
+//! and reading other identities' signed profiles.
 
 use crate::AppState;
-use crate::storage::Profile;
+use crate::commands::commands_handle::publish_identity_record;
+use crate::profile::identicon::{identicon_data_uri, DEFAULT_IDENTICON_SIZE};
+use crate::profile::{avatar_blob_ref, ProfileRecord, MAX_AVATAR_BYTES};
+use crate::storage::{Profile, StoredBlob};
+use base64::Engine;
+use gns_crypto_core::EncryptedPayload;
 use tauri::State;
 
+pub use crate::profile::ProfileLink;
+
 /// Profile data structure for IPC
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct ProfileData {
     pub display_name: Option<String>,
     pub bio: Option<String>,
     pub avatar_url: Option<String>,
+    #[serde(default)]
+    pub avatar_blob_ref: Option<String>,
     pub links: Vec<ProfileLink>,
     pub location_public: bool,
     pub location_resolution: i32,
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
-pub struct ProfileLink {
-    pub type_: String,
-    pub url: String,
-    pub icon: String,
-}
-
-/// Get the current user's profile
-#[tauri::command]
-pub async fn get_profile(state: State<'_, AppState>) -> Result<Option<ProfileData>, String> {
-    let identity = state.identity.lock().await;
-    let public_key = identity.public_key_hex().ok_or("No identity found")?;
-    drop(identity); // Release lock
-
-    let db = state.database.lock().await;
-    let valid_profile = db.get_profile(&public_key).map_err(|e| e.to_string())?;
-
-    if let Some(p) = valid_profile {
+impl From<Profile> for ProfileData {
+    fn from(p: Profile) -> Self {
         // Parse links JSON
         let links: Vec<ProfileLink> = if let Some(json) = p.links {
             serde_json::from_str(&json).unwrap_or_default()
@@ -42,20 +36,86 @@ pub async fn get_profile(state: State<'_, AppState>) -> Result<Option<ProfileDat
             Vec::new()
         };
 
-        Ok(Some(ProfileData {
+        Self {
             display_name: p.display_name,
             bio: p.bio,
             avatar_url: p.avatar_url,
+            avatar_blob_ref: p.avatar_blob_ref,
             links,
             location_public: p.location_public,
             location_resolution: p.location_resolution,
-        }))
-    } else {
-        Ok(None)
+        }
     }
 }
 
-/// Update the current user's profile
+/// Avatar bytes returned to the WebView
+#[derive(serde::Serialize)]
+pub struct AvatarData {
+    pub blob_ref: String,
+    pub mime_type: String,
+    pub data_base64: String,
+}
+
+/// Get a profile.
+///
+/// Without a public key (or with our own) this returns the local profile.
+/// For anyone else the signed record is fetched and only used if its
+/// signature verifies; the last verified copy is served when offline.
+#[tauri::command]
+pub async fn get_profile(
+    public_key: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Option<ProfileData>, String> {
+    let identity = state.identity.lock().await;
+    let own_key = identity.public_key_hex();
+    drop(identity); // Release lock
+
+    let target = match public_key.map(|k| k.to_lowercase()) {
+        Some(k) if Some(&k) != own_key.as_ref() => k,
+        _ => {
+            let public_key = own_key.ok_or("No identity found")?;
+            let db = state.database.lock().await;
+            let profile = db.get_profile(&public_key).map_err(|e| e.to_string())?;
+            return Ok(profile.map(ProfileData::from));
+        }
+    };
+
+    match state.api.get_record(&target).await {
+        Ok(Some(record)) => {
+            let Some(verified) = record.profile(&target)? else {
+                return Ok(None);
+            };
+
+            let profile = Profile {
+                public_key: target.clone(),
+                display_name: verified.display_name,
+                bio: verified.bio,
+                avatar_url: None,
+                avatar_blob_ref: verified.avatar_blob_ref,
+                links: Some(serde_json::to_string(&verified.links).map_err(|e| e.to_string())?),
+                location_public: false,
+                location_resolution: 7,
+                updated_at: chrono::Utc::now().timestamp(),
+            };
+
+            let mut db = state.database.lock().await;
+            if let Err(e) = db.upsert_profile(&profile) {
+                tracing::warn!("Failed to cache profile for {}: {}", target, e);
+            }
+
+            Ok(Some(profile.into()))
+        }
+        Ok(None) => Ok(None),
+        Err(e) => {
+            tracing::warn!("⚠️ Profile fetch failed for {}, using cache: {}", target, e);
+            let db = state.database.lock().await;
+            let cached = db.get_profile(&target).map_err(|e| e.to_string())?;
+            Ok(cached.map(ProfileData::from))
+        }
+    }
+}
+
+/// Update the current user's profile and publish it in the signed record
 #[tauri::command]
 pub async fn update_profile(
     profile_data: ProfileData,
@@ -65,14 +125,23 @@ pub async fn update_profile(
     let public_key = identity.public_key_hex().ok_or("No identity found")?;
     drop(identity);
 
+    let record = ProfileRecord {
+        display_name: profile_data.display_name,
+        bio: profile_data.bio,
+        avatar_blob_ref: profile_data.avatar_blob_ref,
+        links: profile_data.links,
+    }
+    .sanitized()?;
+
     // Serialize links
-    let links_json = serde_json::to_string(&profile_data.links).map_err(|e| e.to_string())?;
+    let links_json = serde_json::to_string(&record.links).map_err(|e| e.to_string())?;
 
     let profile = Profile {
         public_key: public_key.clone(),
-        display_name: profile_data.display_name,
-        bio: profile_data.bio,
+        display_name: record.display_name,
+        bio: record.bio,
         avatar_url: profile_data.avatar_url,
+        avatar_blob_ref: record.avatar_blob_ref,
         links: Some(links_json),
         location_public: profile_data.location_public,
         location_resolution: profile_data.location_resolution,
@@ -81,6 +150,102 @@ pub async fn update_profile(
 
     let mut db = state.database.lock().await;
     db.upsert_profile(&profile).map_err(|e| e.to_string())?;
+    drop(db);
 
+    publish_identity_record(&state)
+        .await
+        .map_err(|e| format!("Profile saved locally but not published: {}", e))?;
+
+    tracing::info!("✅ Profile published");
     Ok(())
 }
+
+/// Store an avatar image and return its content reference.
+///
+/// The reference is the hash of the plain image, so it stays stable
+/// whether or not the local copy is encrypted at rest.
+#[tauri::command]
+pub async fn set_avatar(
+    data_base64: String,
+    mime_type: String,
+    encrypt_at_rest: bool,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    if !mime_type.starts_with("image/") {
+        return Err(format!("Avatar must be an image, got {}", mime_type));
+    }
+
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data_base64.trim())
+        .map_err(|e| format!("Invalid avatar data: {}", e))?;
+
+    if bytes.is_empty() || bytes.len() > MAX_AVATAR_BYTES {
+        return Err(format!("Avatar must be between 1 byte and {} bytes", MAX_AVATAR_BYTES));
+    }
+
+    let blob_ref = avatar_blob_ref(&bytes);
+
+    let data = if encrypt_at_rest {
+        let identity = state.identity.lock().await;
+        let id = identity.get_identity().ok_or("No identity found")?;
+        let sealed = id
+            .encrypt_for(&bytes, &id.encryption_public_key_bytes())
+            .map_err(|e| e.to_string())?;
+        serde_json::to_vec(&sealed).map_err(|e| e.to_string())?
+    } else {
+        bytes
+    };
+
+    let mut db = state.database.lock().await;
+    db.put_blob(&StoredBlob {
+        blob_ref: blob_ref.clone(),
+        mime_type,
+        data,
+        encrypted: encrypt_at_rest,
+        created_at: chrono::Utc::now().timestamp_millis(),
+    })
+    .map_err(|e| e.to_string())?;
+
+    Ok(blob_ref)
+}
+
+/// Default avatar for `public_key`: an identicon as an SVG data URI, the
+/// same for the same key everywhere. `size` is in pixels (default 64).
+#[tauri::command]
+pub async fn generate_identicon(public_key: String, size: Option<u32>) -> Result<String, String> {
+    identicon_data_uri(&public_key, size.unwrap_or(DEFAULT_IDENTICON_SIZE))
+}
+
+/// Load an avatar by content reference, decrypting it if needed
+#[tauri::command]
+pub async fn get_avatar(
+    blob_ref: String,
+    state: State<'_, AppState>,
+) -> Result<Option<AvatarData>, String> {
+    let db = state.database.lock().await;
+    let blob = db.get_blob(&blob_ref).map_err(|e| e.to_string())?;
+    drop(db);
+
+    let Some(blob) = blob else {
+        return Ok(None);
+    };
+
+    let bytes = if blob.encrypted {
+        let sealed: EncryptedPayload = serde_json::from_slice(&blob.data).map_err(|e| e.to_string())?;
+        let identity = state.identity.lock().await;
+        let id = identity.get_identity().ok_or("No identity found")?;
+        id.decrypt(&sealed).map_err(|e| e.to_string())?
+    } else {
+        blob.data
+    };
+
+    if avatar_blob_ref(&bytes) != blob.blob_ref {
+        return Err("Avatar data does not match its reference".to_string());
+    }
+
+    Ok(Some(AvatarData {
+        blob_ref: blob.blob_ref,
+        mime_type: blob.mime_type,
+        data_base64: base64::engine::general_purpose::STANDARD.encode(bytes),
+    }))
+}
@@ -0,0 +1,102 @@
+//! Settings Commands
+//!
+//! Commands for pointing the app at different API, relay and Horizon
+//! endpoints at runtime.
+
+use crate::settings::{Endpoint, Endpoints};
+use crate::stellar::StellarService;
+use crate::AppState;
+use std::sync::Arc;
+use tauri::State;
+
+/// Get the endpoints currently in use
+#[tauri::command]
+pub async fn get_endpoints(state: State<'_, AppState>) -> Result<Endpoints, String> {
+    let db = state.database.lock().await;
+    Ok(Endpoints::load(&db, &state.config))
+}
+
+/// Set the GNS API base URL
+#[tauri::command]
+pub async fn set_api_url(state: State<'_, AppState>, url: String) -> Result<Endpoints, String> {
+    let mut db = state.database.lock().await;
+    let url = Endpoints::save(&mut db, Endpoint::Api, &url)?;
+    let endpoints = Endpoints::load(&db, &state.config);
+    drop(db);
+
+    state.api.set_base_url(&url);
+    tracing::info!("🔧 API URL set to {}", url);
+
+    Ok(endpoints)
+}
+
+/// Set the relay URL and reconnect if the relay was connected
+#[tauri::command]
+pub async fn set_relay_url(state: State<'_, AppState>, url: String) -> Result<Endpoints, String> {
+    let mut db = state.database.lock().await;
+    let url = Endpoints::save(&mut db, Endpoint::Relay, &url)?;
+    let endpoints = Endpoints::load(&db, &state.config);
+    drop(db);
+
+    apply_relay_url(&state, &url).await?;
+
+    Ok(endpoints)
+}
+
+/// Set the Stellar Horizon URL
+#[tauri::command]
+pub async fn set_horizon_url(state: State<'_, AppState>, url: String) -> Result<Endpoints, String> {
+    let mut db = state.database.lock().await;
+    let url = Endpoints::save(&mut db, Endpoint::Horizon, &url)?;
+    let endpoints = Endpoints::load(&db, &state.config);
+    drop(db);
+
+    apply_horizon_url(&state, &url).await;
+
+    Ok(endpoints)
+}
+
+/// Restore all endpoints to their configured defaults
+#[tauri::command]
+pub async fn reset_endpoints(state: State<'_, AppState>) -> Result<Endpoints, String> {
+    let mut db = state.database.lock().await;
+    let endpoints = Endpoints::reset(&mut db, &state.config).map_err(|e| e.to_string())?;
+    drop(db);
+
+    state.api.set_base_url(&endpoints.api_url);
+    apply_horizon_url(&state, &endpoints.horizon_url).await;
+    apply_relay_url(&state, &endpoints.relay_url).await?;
+
+    tracing::info!("🔧 Endpoints reset to defaults");
+    Ok(endpoints)
+}
+
+// ==================== Helpers ====================
+
+async fn apply_relay_url(state: &AppState, url: &str) -> Result<(), String> {
+    let mut relay = state.relay.lock().await;
+    let was_connected = relay.is_connected().await;
+
+    relay.disconnect().await.map_err(|e| e.to_string())?;
+    relay.set_url(url);
+    tracing::info!("🔧 Relay URL set to {}", relay.url());
+
+    if !was_connected {
+        return Ok(());
+    }
+
+    let public_key = state.identity.lock().await.public_key_hex();
+    if let Some(pk) = public_key {
+        relay.connect(&pk).await.map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+async fn apply_horizon_url(state: &AppState, url: &str) {
+    let mut stellar = state.stellar.lock().await;
+    let mut config = stellar.config().clone();
+    config.horizon_url = url.to_string();
+    *stellar = Arc::new(StellarService::new(config).with_client(state.api.client().clone()));
+    tracing::info!("🔧 Horizon URL set to {}", url);
+}
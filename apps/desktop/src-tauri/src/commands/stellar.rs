@@ -2,10 +2,22 @@
 //!
 //! Exposes Stellar/GNS token functionality to the React frontend
 
-use tauri::State;
+use tauri::{AppHandle, Emitter, State};
 use serde::{Deserialize, Serialize};
 use crate::AppState;
-use crate::stellar::{StellarService, PaymentHistoryItem, StellarError};
+use crate::commands::audit;
+use crate::commands::timeout::{cancel_after, with_timeout, CommandError};
+use crate::config::CommandCategory;
+use crate::storage::AuditAction;
+use crate::settings::{self, Endpoints};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use crate::stellar::{Asset, AssetVerification, BalanceClaimResult, FeeEstimate, GnsAssetInfo, GnsDelivery, RecipientStatus, Remediation, ReserveHeadroom, SendFailure, SendWarning, StellarControlProof, StellarService, StellarNetwork, PaymentHistoryItem, StellarError, TransactionResult};
+use crate::stellar::claim_history::ClaimableBalanceHistory;
+use crate::stellar::outgoing_claims::OutgoingClaimableBalance;
+use crate::stellar::onboarding::{OnboardingError, OnboardingResult, ONBOARDING_EVENT};
+use crate::network::{IdentityInfo, NetworkError};
+use std::future::Future;
 
 // ==================== RESPONSE TYPES ====================
 
@@ -34,6 +46,16 @@ pub struct TransactionResponse {
     pub hash: Option<String>,
     pub error: Option<String>,
     pub message: Option<String>,
+    /// Set when the destination looked wrong. A blocked send has
+    /// `success: false` and goes through when repeated with `confirmed`.
+    #[serde(default)]
+    pub warning: Option<SendWarning>,
+    /// Set when the send failed in a way the user can fix, with the
+    /// action to offer
+    #[serde(default)]
+    pub failure: Option<SendFailure>,
+    #[serde(default)]
+    pub remediation: Option<Remediation>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,6 +64,165 @@ pub struct SendGnsRequest {
     pub recipient_public_key: Option<String>,
     pub amount: f64,
     pub memo: Option<String>,
+    /// Send even if the destination looks wrong (e.g. the GNS issuer)
+    #[serde(default)]
+    pub confirmed: bool,
+}
+
+/// Confirmation of a send to an @handle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SendToHandleResponse {
+    pub handle: String,
+    pub recipient_public_key: String,
+    pub recipient_stellar_address: String,
+    /// How the transfer arrived as Horizon shows it; `None` if Horizon
+    /// couldn't confirm it
+    pub delivery: Option<GnsDelivery>,
+    pub hash: Option<String>,
+    pub message: String,
+    /// Set when sending to our own address
+    pub warning: Option<SendWarning>,
+}
+
+/// Why `send_gns_to_handle` failed, sent to the UI as `{ kind, message }`,
+/// plus `failure` and `remediation` when a failed send names a
+/// [`SendFailure`]
+#[derive(Debug, thiserror::Error)]
+pub enum SendToHandleError {
+    #[error("No identity found")]
+    NoIdentity,
+
+    #[error("Handle @{0} not found")]
+    HandleNotResolved(String),
+
+    #[error("Failed to resolve handle: {0}")]
+    LookupFailed(String),
+
+    #[error("Send failed: {0}")]
+    SendFailed(String),
+
+    #[error("Could not check the recipient on Stellar: {0}")]
+    StellarLookupFailed(String),
+
+    #[error("{}", .0.message())]
+    NeedsConfirmation(SendWarning),
+}
+
+impl SendToHandleError {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::NoIdentity => "no_identity",
+            Self::HandleNotResolved(_) => "handle_not_resolved",
+            Self::LookupFailed(_) => "lookup_failed",
+            Self::SendFailed(_) => "send_failed",
+            Self::StellarLookupFailed(_) => "stellar_lookup_failed",
+            Self::NeedsConfirmation(_) => "needs_confirmation",
+        }
+    }
+
+    fn failure(&self) -> Option<SendFailure> {
+        match self {
+            Self::SendFailed(error) => SendFailure::from_error(error),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for SendToHandleError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let failure = self.failure();
+        let mut error = serializer.serialize_struct("SendToHandleError", 4)?;
+        error.serialize_field("kind", self.kind())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.serialize_field("failure", &failure)?;
+        error.serialize_field("remediation", &failure.map(SendFailure::remediation))?;
+        error.end()
+    }
+}
+
+/// The key and Stellar address an @handle pays out to
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ResolvedRecipient {
+    handle: String,
+    public_key: String,
+    stellar_address: String,
+}
+
+/// Resolve `handle` with `lookup` and derive the recipient's Stellar address
+async fn resolve_recipient<F, Fut>(handle: &str, lookup: F) -> Result<ResolvedRecipient, SendToHandleError>
+where
+    F: FnOnce(String) -> Fut,
+    Fut: Future<Output = Result<Option<IdentityInfo>, NetworkError>>,
+{
+    let handle = handle.trim().trim_start_matches('@').to_lowercase();
+    if handle.is_empty() {
+        return Err(SendToHandleError::HandleNotResolved(handle));
+    }
+
+    let identity = lookup(handle.clone())
+        .await
+        .map_err(|e| SendToHandleError::LookupFailed(e.to_string()))?
+        .ok_or_else(|| SendToHandleError::HandleNotResolved(handle.clone()))?;
+
+    // A record without a usable key is as good as no record
+    let stellar_address = StellarService::gns_key_to_stellar(&identity.public_key)
+        .map_err(|_| SendToHandleError::HandleNotResolved(handle.clone()))?;
+
+    Ok(ResolvedRecipient {
+        handle,
+        public_key: identity.public_key.to_lowercase(),
+        stellar_address,
+    })
+}
+
+/// Stellar address of an @handle or a hex public key
+async fn resolve_handle_or_key<F, Fut>(recipient: &str, lookup: F) -> Result<String, SendToHandleError>
+where
+    F: FnOnce(String) -> Fut,
+    Fut: Future<Output = Result<Option<IdentityInfo>, NetworkError>>,
+{
+    let recipient = recipient.trim();
+    let is_key = recipient.len() == 64 && recipient.chars().all(|c| c.is_ascii_hexdigit());
+    if is_key {
+        return StellarService::gns_key_to_stellar(recipient)
+            .map_err(|_| SendToHandleError::HandleNotResolved(recipient.to_string()));
+    }
+
+    resolve_recipient(recipient, lookup).await.map(|r| r.stellar_address)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StellarNetworkResponse {
+    pub network: StellarNetwork,
+    pub is_testnet: bool,
+    pub horizon_url: String,
+    /// Shown prominently by the UI whenever we are off mainnet
+    pub warning: Option<String>,
+}
+
+impl StellarNetworkResponse {
+    fn from_service(stellar: &StellarService) -> Self {
+        let config = stellar.config();
+        Self {
+            network: config.network(),
+            is_testnet: config.use_testnet,
+            horizon_url: config.horizon_url.clone(),
+            warning: config.use_testnet.then(|| {
+                "TESTNET: balances and payments are not real funds".to_string()
+            }),
+        }
+    }
+}
+
+/// The wallet's Stellar address, from the derived-keys table so
+/// it is only computed once per key
+async fn wallet_address(state: &AppState) -> Result<String, String> {
+    let public_key = state.identity.lock().await.wallet_public_key()
+        .ok_or("No identity found")?;
+    state.database.lock().await
+        .stellar_address(&public_key)
+        .map_err(|e| e.to_string())
 }
 
 // ==================== COMMANDS ====================
@@ -51,14 +232,29 @@ pub struct SendGnsRequest {
 pub async fn get_stellar_address(
     state: State<'_, AppState>,
 ) -> Result<String, String> {
+    wallet_address(&state).await
+}
+
+/// Sign a challenge proving the current identity controls its wallet address
+#[tauri::command]
+pub async fn prove_stellar_control(
+    challenge: String,
+    state: State<'_, AppState>,
+) -> Result<StellarControlProof, String> {
     let identity = state.identity.lock().await;
-    
-    let public_key = identity.public_key()
-        .ok_or("No identity found")?;
-    
-    // Convert GNS key to Stellar address
-    StellarService::gns_key_to_stellar(&public_key)
-        .map_err(|e| e.to_string())
+    let gns_identity = identity.wallet_identity().ok_or("No identity found")?;
+
+    StellarControlProof::create(gns_identity, &challenge).map_err(|e| e.to_string())
+}
+
+/// Verify a Stellar control proof, returning the address the key controls
+#[tauri::command]
+pub fn verify_stellar_control(
+    public_key: String,
+    challenge: String,
+    signature: String,
+) -> Result<String, String> {
+    crate::stellar::verify_stellar_control(&public_key, &challenge, &signature).map_err(|e| e.to_string())
 }
 
 /// Get Stellar Explorer URL for account
@@ -66,10 +262,7 @@ pub async fn get_stellar_address(
 pub async fn get_stellar_explorer_url(
     state: State<'_, AppState>,
 ) -> Result<String, String> {
-    let identity = state.identity.lock().await;
-    let public_key = identity.public_key().ok_or("No identity found")?;
-    let stellar_address = StellarService::gns_key_to_stellar(&public_key)
-        .map_err(|e| e.to_string())?;
+    let stellar_address = wallet_address(&state).await?;
     
     let stellar = state.stellar.lock().await;
     let base_url = if stellar.config().use_testnet {
@@ -85,17 +278,18 @@ pub async fn get_stellar_explorer_url(
 #[tauri::command]
 pub async fn get_stellar_balances(
     state: State<'_, AppState>,
-) -> Result<StellarBalancesResponse, String> {
-    let identity = state.identity.lock().await;
-    
-    let public_key = identity.public_key()
-        .ok_or("No identity found")?;
-    
-    // Get Stellar service
-    let stellar = state.stellar.lock().await;
+) -> Result<StellarBalancesResponse, CommandError> {
+    let stellar_address = wallet_address(&state).await?;
     
-    let balances = stellar.get_stellar_balances(&public_key).await
-        .map_err(|e| e.to_string())?;
+    let limit = state.config.command_timeout(CommandCategory::Payment);
+    let (stellar, balances) = with_timeout("get_stellar_balances", limit, async {
+        // Get Stellar service
+        let stellar = state.stellar.lock().await;
+        let balances = stellar.get_stellar_balances_at(stellar_address).await;
+        (stellar, balances)
+    })
+    .await?;
+    let balances = balances.map_err(|e| e.to_string())?;
     
     Ok(StellarBalancesResponse {
         stellar_address: balances.stellar_address,
@@ -118,21 +312,31 @@ pub async fn get_stellar_balances(
 /// Claim all GNS tokens (creates trustline if needed)
 #[tauri::command]
 pub async fn claim_gns_tokens(
+    operation_id: Option<String>,
     state: State<'_, AppState>,
-) -> Result<TransactionResponse, String> {
+) -> Result<TransactionResponse, CommandError> {
+    let (cancel, _operation) = state.operations.start(operation_id.as_deref())?;
     let identity = state.identity.lock().await;
     
-    let public_key = identity.public_key()
+    let public_key = identity.wallet_public_key()
         .ok_or("No identity found")?;
     
-    let private_key = identity.private_key_bytes()
+    let private_key = identity.wallet_private_key_bytes()
         .ok_or("No private key available")?;
     
-    // Get Stellar service
-    let stellar = state.stellar.lock().await;
+    // Past the limit the claim is cancelled, which stops it unless it was
+    // already submitted
+    let limit = state.config.command_timeout(CommandCategory::Payment);
+    let (claimed, timed_out) = cancel_after(limit, &cancel, async {
+        // Get Stellar service
+        let stellar = cancel.guard(state.stellar.lock()).await?;
 
-    // Claim all GNS tokens
-    match stellar.claim_all_gns(&public_key, &private_key).await {
+        // Claim all GNS tokens
+        stellar.claim_all_gns(&public_key, &private_key, &cancel).await
+    }).await;
+
+    match claimed {
+        Err(StellarError::Cancelled) if timed_out => Err(CommandError::timeout("claim_gns_tokens", limit)),
         Ok(result) => Ok(TransactionResponse {
             success: result.success,
             hash: result.hash.clone(),
@@ -142,101 +346,312 @@ pub async fn claim_gns_tokens(
             } else {
                 None
             },
+            warning: None,
+            failure: None,
+            remediation: None,
         }),
         Err(e) => Ok(TransactionResponse {
             success: false,
             hash: None,
             error: Some(e.to_string()),
             message: None,
+            warning: None,
+            failure: None,
+            remediation: None,
         }),
     }
 }
 
-/// Create GNS trustline
+/// Claim every outstanding GNS claimable balance directly on Horizon,
+/// adding the trustline first if needed. Unlike `claim_gns_tokens` this
+/// doesn't go through the backend. Returns one result per balance.
+/// Past the time limit it is cancelled: nothing more is submitted, and
+/// balances it didn't get to are reported as cancelled. With an
+/// `operation_id`, `cancel_operation` can stop it the same way.
+#[tauri::command]
+pub async fn claim_all_balances(
+    operation_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<BalanceClaimResult>, CommandError> {
+    let (cancel, _operation) = state.operations.start(operation_id.as_deref())?;
+    let (public_key, private_key) = {
+        let identity = state.identity.lock().await;
+        let public_key = identity.wallet_public_key().ok_or("No identity found")?;
+        let private_key = identity.wallet_private_key_bytes().ok_or("No private key available")?;
+        (public_key, private_key)
+    };
+
+    let limit = state.config.command_timeout(CommandCategory::Payment);
+    let (claimed, timed_out) = cancel_after(limit, &cancel, async {
+        let stellar = cancel.guard(state.stellar.lock()).await?;
+        stellar.claim_all_balances(&public_key, &private_key, &cancel).await
+    })
+    .await;
+
+    match claimed {
+        Err(StellarError::Cancelled) if timed_out => Err(CommandError::timeout("claim_all_balances", limit)),
+        claimed => claimed.map_err(|e| CommandError::from(e.to_string())),
+    }
+}
+
+/// Get a new wallet holding GNS: add the trustline if missing, wait for
+/// it to confirm, then claim the welcome airdrop and any other pending
+/// GNS. Steps already done are skipped, so this is safe to call again
+/// after a failure. Progress is emitted as `wallet_onboarding_progress`.
+/// With an `operation_id`, `cancel_operation` can stop it until the first
+/// transaction is submitted.
+#[tauri::command]
+pub async fn onboard_wallet(
+    operation_id: Option<String>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<OnboardingResult, OnboardingError> {
+    let (cancel, _operation) = state
+        .operations
+        .start(operation_id.as_deref())
+        .map_err(|e| OnboardingError::Stellar(StellarError::Validation(e)))?;
+    let (public_key, private_key) = {
+        let identity = state.identity.lock().await;
+        identity
+            .public_key()
+            .zip(identity.wallet_private_key_bytes())
+            .ok_or(OnboardingError::NoIdentity)?
+    };
+
+    // Onboarding polls for up to half a minute, so it runs on its own
+    // handle to the service rather than holding the lock
+    let stellar = state.stellar.lock().await.clone();
+
+    // Past the limit onboarding is cancelled, which stops it before the
+    // next submission
+    let limit = state.config.command_timeout(CommandCategory::Payment);
+    let (result, timed_out) = cancel_after(limit, &cancel, async {
+        stellar
+            .onboard_wallet(&public_key, &private_key, &cancel, |progress| {
+                let _ = app.emit(ONBOARDING_EVENT, &progress);
+            })
+            .await
+    })
+    .await;
+    let result = match result {
+        Err(OnboardingError::Stellar(StellarError::Cancelled)) if timed_out => {
+            Err(OnboardingError::Timeout { seconds: limit.as_secs() })
+        }
+        result => result,
+    };
+
+    match &result {
+        Ok(r) => tracing::info!(
+            "🪙 Wallet onboarded (trustline created: {}, {} claim(s))",
+            r.trustline_created,
+            r.claims.len()
+        ),
+        Err(e) => tracing::warn!("⚠️ Wallet onboarding stopped: {}", e),
+    }
+    result
+}
+
+/// Create GNS trustline, optionally with a limit (unlimited when omitted).
+/// Calling again with a limit adjusts an existing trustline. Past the
+/// time limit it is cancelled, which stops it unless the change was
+/// already submitted. With an `operation_id`, `cancel_operation` can stop
+/// it the same way.
 #[tauri::command]
 pub async fn create_gns_trustline(
+    limit: Option<String>,
+    operation_id: Option<String>,
     state: State<'_, AppState>,
-) -> Result<TransactionResponse, String> {
-    let identity = state.identity.lock().await;
-    
-    let public_key = identity.public_key()
-        .ok_or("No identity found")?;
-    
-    let private_key = identity.private_key_bytes()
-        .ok_or("No private key available")?;
-    
-    // Get Stellar service
-    let stellar = state.stellar.lock().await;
+) -> Result<TransactionResponse, CommandError> {
+    let (cancel, _operation) = state.operations.start(operation_id.as_deref())?;
+    let (public_key, private_key) = {
+        let identity = state.identity.lock().await;
+        let public_key = identity.wallet_public_key().ok_or("No identity found")?;
+        let private_key = identity.wallet_private_key_bytes().ok_or("No private key available")?;
+        (public_key, private_key)
+    };
+
+    // A new trustline is a new ledger entry; adjusting a limit isn't
+    let stellar_address = StellarService::gns_key_to_stellar(&public_key).map_err(|e| e.to_string())?;
+
+    let time_limit = state.config.command_timeout(CommandCategory::Payment);
+    let (created, timed_out) = cancel_after(time_limit, &cancel, async {
+        // Get Stellar service
+        let stellar = cancel.guard(state.stellar.lock()).await?;
+
+        if !cancel.guard(stellar.has_gns_trustline(&stellar_address)).await?.unwrap_or(false) {
+            if let Some(warning) = cancel.guard(reserve_warning(&stellar, &stellar_address, 1)).await? {
+                return Ok(TransactionResult::err(warning));
+            }
+        }
+
+        // Create trustline
+        stellar.create_gns_trustline(&public_key, &private_key, limit.as_deref(), &cancel).await
+    })
+    .await;
+
+    match created {
+        Err(StellarError::Cancelled) if timed_out => Err(CommandError::timeout("create_gns_trustline", time_limit)),
+        Ok(result) => Ok(TransactionResponse {
+            success: result.success,
+            hash: result.hash,
+            error: result.error,
+            message: if result.success {
+                Some(match &limit {
+                    Some(l) => format!("Trustline limit set to {} GNS", l),
+                    None => "Trustline created!".to_string(),
+                })
+            } else {
+                None
+            },
+            warning: None,
+            failure: None,
+            remediation: None,
+        }),
+        Err(e) => Ok(TransactionResponse {
+            success: false,
+            hash: None,
+            error: Some(e.to_string()),
+            message: None,
+            warning: None,
+            failure: None,
+            remediation: None,
+        }),
+    }
+}
+
+/// Remove the GNS trustline. Fails if any GNS is still held. Past the
+/// time limit it is cancelled, which stops it unless the removal was
+/// already submitted. With an `operation_id`, `cancel_operation` can stop
+/// it the same way.
+#[tauri::command]
+pub async fn remove_gns_trustline(
+    operation_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<TransactionResponse, CommandError> {
+    let (cancel, _operation) = state.operations.start(operation_id.as_deref())?;
+    let (public_key, private_key) = {
+        let identity = state.identity.lock().await;
+        let public_key = identity.wallet_public_key().ok_or("No identity found")?;
+        let private_key = identity.wallet_private_key_bytes().ok_or("No private key available")?;
+        (public_key, private_key)
+    };
+
+    let limit = state.config.command_timeout(CommandCategory::Payment);
+    let (removed, timed_out) = cancel_after(limit, &cancel, async {
+        let stellar = cancel.guard(state.stellar.lock()).await?;
+        stellar.remove_gns_trustline(&public_key, &private_key, &cancel).await
+    })
+    .await;
 
-    // Create trustline
-    match stellar.create_gns_trustline(&public_key, &private_key).await {
+    match removed {
+        Err(StellarError::Cancelled) if timed_out => Err(CommandError::timeout("remove_gns_trustline", limit)),
         Ok(result) => Ok(TransactionResponse {
             success: result.success,
             hash: result.hash,
             error: result.error,
             message: if result.success {
-                Some("Trustline created!".to_string())
+                Some("Trustline removed".to_string())
             } else {
                 None
             },
+            warning: None,
+            failure: None,
+            remediation: None,
         }),
         Err(e) => Ok(TransactionResponse {
             success: false,
             hash: None,
             error: Some(e.to_string()),
             message: None,
+            warning: None,
+            failure: None,
+            remediation: None,
         }),
     }
 }
 
-/// Send GNS tokens
+/// Send GNS tokens. With an `operation_id`, `cancel_operation` can stop
+/// the send until it is submitted.
 #[tauri::command]
 pub async fn send_gns(
     request: SendGnsRequest,
+    operation_id: Option<String>,
     state: State<'_, AppState>,
-) -> Result<TransactionResponse, String> {
+) -> Result<TransactionResponse, CommandError> {
+    let (cancel, _operation) = state.operations.start(operation_id.as_deref())?;
     let identity = state.identity.lock().await;
     
-    let sender_pk = identity.public_key()
+    let sender_pk = identity.wallet_public_key()
         .ok_or("No identity found")?;
     
-    let sender_private_key = identity.private_key_bytes()
+    let sender_private_key = identity.wallet_private_key_bytes()
         .ok_or("No private key available")?;
     
     // Convert sender to Stellar address
     let _sender_stellar = StellarService::gns_key_to_stellar(&sender_pk)
         .map_err(|e| e.to_string())?;
     
-    // Resolve recipient
-    let recipient_pk = if let Some(handle) = &request.recipient_handle {
-        // Look up handle via API
-        let api = &state.api;
-        let resolved = api.resolve_handle(handle).await
-            .map_err(|e| format!("Failed to resolve handle: {}", e))?
-            .ok_or_else(|| format!("Handle @{} not found", handle))?;
-        resolved.public_key
-    } else if let Some(pk) = &request.recipient_public_key {
-        pk.clone()
-    } else {
-        return Err("No recipient specified".to_string());
+    // Past the limit the send is cancelled, which stops it unless it was
+    // already submitted
+    let limit = state.config.command_timeout(CommandCategory::Payment);
+    let (attempt, timed_out) = cancel_after(limit, &cancel, async {
+        // Resolve recipient
+        let recipient_pk = if let Some(handle) = &request.recipient_handle {
+            // Look up handle via API
+            let api = &state.api;
+            let resolved = cancel.guard(api.resolve_handle(handle)).await
+                .map_err(|e| e.to_string())?
+                .map_err(|e| format!("Failed to resolve handle: {}", e))?
+                .ok_or_else(|| format!("Handle @{} not found", handle))?;
+            resolved.public_key
+        } else if let Some(pk) = &request.recipient_public_key {
+            pk.clone()
+        } else {
+            return Err("No recipient specified".to_string());
+        };
+        
+        // Get Stellar service
+        let stellar = cancel.guard(state.stellar.lock()).await.map_err(|e| e.to_string())?;
+
+        // Send GNS
+        let sent = stellar.send_gns(
+            &sender_pk,
+            &sender_private_key,
+            None, 
+            None, 
+            &recipient_pk, // We already resolved this to a hex string
+            request.amount,
+            request.confirmed,
+            &cancel,
+        ).await;
+        Ok::<_, String>((recipient_pk, sent))
+    }).await;
+
+    let (recipient_pk, sent) = match attempt {
+        Ok((recipient_pk, Err(StellarError::Cancelled))) if timed_out => {
+            let error = CommandError::timeout("send_gns", limit);
+            audit::record(&state.database, AuditAction::SendGns, Some(recipient_pk.as_str()), &Err::<(), _>(&error)).await;
+            return Err(error);
+        }
+        Err(_) if timed_out => return Err(CommandError::timeout("send_gns", limit)),
+        attempt => attempt?,
     };
-    
-    // Get Stellar service
-    let stellar = state.stellar.lock().await;
 
-    // Send GNS
-    match stellar.send_gns(
-        &sender_pk,
-        &sender_private_key,
-        None, 
-        None, 
-        &recipient_pk, // We already resolved this to a hex string
-        request.amount,
-    ).await {
+    let outcome = match &sent {
+        Ok(result) if result.success => Ok(()),
+        Ok(result) => Err(result.error.clone().unwrap_or_else(|| "Unknown error".to_string())),
+        Err(e) => Err(e.to_string()),
+    };
+    audit::record(&state.database, AuditAction::SendGns, Some(recipient_pk.as_str()), &outcome).await;
+
+    match sent {
         Ok(result) => Ok(TransactionResponse {
             success: result.success,
             hash: result.hash.clone(),
             error: result.error,
+            warning: result.warning,
+            failure: result.failure,
+            remediation: result.remediation,
             message: if result.success {
                 let msg = if let Some(handle) = request.recipient_handle {
                     format!("Sent {:.2} GNS to @{}", request.amount, handle)
@@ -253,10 +668,263 @@ pub async fn send_gns(
             hash: None,
             error: Some(e.to_string()),
             message: None,
+            warning: None,
+            failure: None,
+            remediation: None,
         }),
     }
 }
 
+/// Cancel a `send_gns`, `claim_gns_tokens` or `onboard_wallet` started
+/// with `operation_id`. It stops before submitting; once submitted, it
+/// finishes and reports its real outcome. Returns false if no such
+/// operation is running.
+#[tauri::command]
+pub async fn cancel_operation(
+    operation_id: String,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let cancelled = state.operations.cancel(&operation_id);
+    if cancelled {
+        tracing::info!("🛑 Cancelling operation {}", operation_id);
+    }
+    Ok(cancelled)
+}
+
+/// Send GNS to an @handle, resolving it to a key and Stellar address first.
+///
+/// Recipients without a GNS trustline get a claimable balance instead of
+/// a payment; `delivery` in the response says which, once Horizon shows
+/// the transaction. A handle that pays
+/// out to the GNS issuer needs `confirmed`.
+#[tauri::command]
+pub async fn send_gns_to_handle(
+    handle: String,
+    amount: f64,
+    confirmed: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<SendToHandleResponse, SendToHandleError> {
+    let confirmed = confirmed.unwrap_or(false);
+    let (sender_pk, sender_private_key) = {
+        let identity = state.identity.lock().await;
+        identity
+            .public_key()
+            .zip(identity.wallet_private_key_bytes())
+            .ok_or(SendToHandleError::NoIdentity)?
+    };
+
+    if !amount.is_finite() || amount <= 0.0 {
+        return Err(SendToHandleError::SendFailed("Amount must be greater than zero".to_string()));
+    }
+
+    let api = &state.api;
+    let recipient = resolve_recipient(&handle, |h| async move { api.resolve_handle(&h).await }).await?;
+
+    let stellar = state.stellar.lock().await;
+    let sent = match stellar.gns_delivery(&recipient.stellar_address).await {
+        // A claimable balance is an entry on our account until it's claimed
+        Ok(GnsDelivery::ClaimableBalance) => {
+            let sender_address = StellarService::gns_key_to_stellar(&sender_pk)
+                .map_err(|e| SendToHandleError::SendFailed(e.to_string()))?;
+            if let Some(warning) = reserve_warning(&stellar, &sender_address, 1).await {
+                return Err(SendToHandleError::SendFailed(warning));
+            }
+            stellar
+                .send_gns_to_address(
+                    &sender_pk,
+                    &sender_private_key,
+                    &recipient.stellar_address,
+                    amount,
+                    GnsDelivery::ClaimableBalance,
+                    confirmed,
+                )
+                .await
+        }
+        Ok(delivery) => stellar
+            .send_gns_to_address(
+                &sender_pk,
+                &sender_private_key,
+                &recipient.stellar_address,
+                amount,
+                delivery,
+                confirmed,
+            )
+            .await,
+        Err(e) => Err(e),
+    };
+    drop(stellar);
+
+    let outcome = match sent {
+        Ok(result) if result.success => Ok(result),
+        Ok(result) if !confirmed && result.warning.as_ref().is_some_and(SendWarning::needs_confirmation) => {
+            Err(SendToHandleError::NeedsConfirmation(result.warning.unwrap()))
+        }
+        Ok(result) => Err(SendToHandleError::SendFailed(
+            result.error.unwrap_or_else(|| "Unknown error".to_string()),
+        )),
+        Err(e) => Err(SendToHandleError::SendFailed(e.to_string())),
+    };
+    let target = format!("@{}", recipient.handle);
+    audit::record(&state.database, AuditAction::SendGns, Some(target.as_str()), &outcome).await;
+    let result = outcome?;
+
+    // What we asked the backend for is only a request; report what the
+    // transaction did
+    let delivery = match result.hash.as_deref() {
+        Some(hash) => {
+            let stellar = state.stellar.lock().await.clone();
+            stellar.submitted_gns_delivery(hash).await.unwrap_or_else(|e| {
+                tracing::warn!("⚠️ Could not confirm how the send to @{} arrived: {}", recipient.handle, e);
+                None
+            })
+        }
+        None => None,
+    };
+
+    let message = match delivery {
+        Some(GnsDelivery::ClaimableBalance) => format!(
+            "Sent {:.2} GNS to @{} as a claimable balance (they have no GNS trustline yet)",
+            amount, recipient.handle
+        ),
+        _ => format!("Sent {:.2} GNS to @{}", amount, recipient.handle),
+    };
+
+    Ok(SendToHandleResponse {
+        handle: recipient.handle,
+        recipient_public_key: recipient.public_key,
+        recipient_stellar_address: recipient.stellar_address,
+        delivery,
+        hash: result.hash,
+        message,
+        warning: result.warning,
+    })
+}
+
+/// Send a configured asset (see `list_known_assets`) to a Stellar address.
+/// A send to the asset's issuer is refused unless `confirmed`.
+#[tauri::command]
+pub async fn send_asset(
+    asset_code: String,
+    asset_issuer: String,
+    recipient_address: String,
+    amount: f64,
+    confirmed: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<TransactionResponse, String> {
+    let (sender_pk, sender_private_key) = {
+        let identity = state.identity.lock().await;
+        identity
+            .public_key()
+            .zip(identity.wallet_private_key_bytes())
+            .ok_or("No identity found")?
+    };
+
+    let stellar = state.stellar.lock().await;
+    let sent = stellar
+        .send_asset(&asset_code, &asset_issuer, &sender_pk, &sender_private_key, &recipient_address, amount, confirmed.unwrap_or(false))
+        .await;
+    drop(stellar);
+
+    let outcome = match &sent {
+        Ok(result) if result.success => Ok(()),
+        Ok(result) => Err(result.error.clone().unwrap_or_else(|| "Unknown error".to_string())),
+        Err(e) => Err(e.to_string()),
+    };
+    audit::record(&state.database, AuditAction::SendAsset, Some(recipient_address.as_str()), &outcome).await;
+
+    Ok(match sent {
+        Ok(result) => TransactionResponse {
+            success: result.success,
+            message: result.success.then(|| format!("Sent {:.2} {}", amount, asset_code)),
+            hash: result.hash,
+            error: result.error,
+            warning: result.warning,
+            failure: result.failure,
+            remediation: result.remediation,
+        },
+        Err(e) => TransactionResponse {
+            success: false,
+            hash: None,
+            error: Some(e.to_string()),
+            message: None,
+            warning: None,
+            failure: None,
+            remediation: None,
+        },
+    })
+}
+
+/// Check before sending whether a recipient (@handle or public key) can
+/// take GNS directly or will get a claimable balance
+#[tauri::command]
+pub async fn can_receive_gns(
+    recipient: String,
+    state: State<'_, AppState>,
+) -> Result<RecipientStatus, SendToHandleError> {
+    let api = &state.api;
+    let stellar_address = resolve_handle_or_key(&recipient, |h| async move { api.resolve_handle(&h).await }).await?;
+
+    let stellar = state.stellar.lock().await;
+    stellar
+        .recipient_status(&stellar_address)
+        .await
+        .map_err(|e| SendToHandleError::StellarLookupFailed(e.to_string()))
+}
+
+/// Check whether an account (default: ours) has the XLM to add
+/// `added_subentries` ledger entries, such as a trustline or a claimable
+/// balance, and how much to add if not
+#[tauri::command]
+pub async fn check_reserve_headroom(
+    gns_key: Option<String>,
+    added_subentries: u32,
+    state: State<'_, AppState>,
+) -> Result<ReserveHeadroom, String> {
+    let gns_key = match gns_key {
+        Some(key) => key,
+        None => state.identity.lock().await.wallet_public_key().ok_or("No identity found")?,
+    };
+    let stellar_address = StellarService::gns_key_to_stellar(&gns_key).map_err(|e| e.to_string())?;
+
+    let stellar = state.stellar.lock().await;
+    stellar
+        .reserve_headroom(&stellar_address, added_subentries)
+        .await
+        .map_err(|e| match e {
+            StellarError::AccountNotFound => {
+                format!("Account {} doesn't exist yet - send it some XLM to activate it", stellar_address)
+            }
+            e => e.to_string(),
+        })
+}
+
+/// The warning to show instead of adding `added_subentries` entries to
+/// `stellar_address`, if it can't afford them. A failed lookup doesn't
+/// block: the operation will report its own error.
+async fn reserve_warning(stellar: &StellarService, stellar_address: &str, added_subentries: u32) -> Option<String> {
+    match stellar.reserve_headroom(stellar_address, added_subentries).await {
+        Ok(headroom) => headroom.warning(),
+        Err(e) => {
+            tracing::warn!("⚠️ Reserve check failed, trying anyway: {}", e);
+            None
+        }
+    }
+}
+
+/// Estimate the network fee of a transaction with `operation_count`
+/// operations (default 1, a plain send), for the send confirmation
+#[tauri::command]
+pub async fn estimate_send_fee(
+    operation_count: Option<u32>,
+    state: State<'_, AppState>,
+) -> Result<FeeEstimate, String> {
+    let stellar = state.stellar.lock().await;
+    stellar
+        .estimate_fee(operation_count.unwrap_or(1))
+        .await
+        .map_err(|e| e.to_string())
+}
+
 /// Fund account on testnet (development only)
 #[tauri::command]
 pub async fn fund_testnet_account(
@@ -264,7 +932,7 @@ pub async fn fund_testnet_account(
 ) -> Result<TransactionResponse, String> {
     let identity = state.identity.lock().await;
     
-    let public_key = identity.public_key()
+    let public_key = identity.wallet_public_key()
         .ok_or("No identity found")?;
     
     // Convert to Stellar address
@@ -285,12 +953,18 @@ pub async fn fund_testnet_account(
             } else {
                 None
             },
+            warning: None,
+            failure: None,
+            remediation: None,
         }),
         Err(e) => Ok(TransactionResponse {
             success: false,
             hash: None,
             error: Some(e.to_string()),
             message: None,
+            warning: None,
+            failure: None,
+            remediation: None,
         }),
     }
 }
@@ -301,14 +975,7 @@ pub async fn get_payment_history(
     limit: Option<u32>,
     state: State<'_, AppState>,
 ) -> Result<Vec<PaymentHistoryItem>, String> {
-    let identity = state.identity.lock().await;
-    
-    let public_key = identity.public_key()
-        .ok_or("No identity found")?;
-    
-    // Convert to Stellar address
-    let stellar_address = StellarService::gns_key_to_stellar(&public_key)
-        .map_err(|e| e.to_string())?;
+    let stellar_address = wallet_address(&state).await?;
     
     let stellar = state.stellar.lock().await;
     
@@ -316,3 +983,230 @@ pub async fn get_payment_history(
     stellar.get_payment_history(&stellar_address, limit.unwrap_or(20)).await
         .map_err(|e: StellarError| e.to_string())
 }
+
+/// Page through every claimable balance ever offered to this wallet,
+/// pending, claimed or expired, newest first
+#[tauri::command]
+pub async fn get_claimable_balance_history(
+    cursor: Option<String>,
+    limit: Option<u32>,
+    state: State<'_, AppState>,
+) -> Result<ClaimableBalanceHistory, String> {
+    let stellar_address = wallet_address(&state).await?;
+
+    let stellar = state.stellar.lock().await;
+    stellar
+        .get_claimable_balance_history(&stellar_address, cursor.as_deref(), limit.unwrap_or(20))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Claimable balances an account (default: ours) created for others and
+/// that are still unclaimed, each saying whether it can be reclaimed now
+#[tauri::command]
+pub async fn get_outgoing_claimable_balances(
+    gns_key: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<OutgoingClaimableBalance>, String> {
+    let gns_key = match gns_key {
+        Some(key) => key,
+        None => state.identity.lock().await.wallet_public_key().ok_or("No identity found")?,
+    };
+    let stellar_address = StellarService::gns_key_to_stellar(&gns_key).map_err(|e| e.to_string())?;
+
+    let stellar = state.stellar.lock().await;
+    stellar
+        .get_outgoing_claimable_balances(&stellar_address)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Take back a balance this wallet sponsors whose predicate now lets the
+/// sponsor claim it
+#[tauri::command]
+pub async fn reclaim_expired_balance(
+    balance_id: String,
+    state: State<'_, AppState>,
+) -> Result<TransactionResponse, String> {
+    let (public_key, private_key) = {
+        let identity = state.identity.lock().await;
+        let public_key = identity.wallet_public_key().ok_or("No identity found")?;
+        let private_key = identity.wallet_private_key_bytes().ok_or("No private key available")?;
+        (public_key, private_key)
+    };
+
+    let stellar = state.stellar.lock().await;
+    let result = stellar
+        .reclaim_expired_balance(&public_key, &private_key, &balance_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(TransactionResponse {
+        success: result.success,
+        hash: result.hash,
+        error: result.error,
+        message: result.success.then(|| "Balance reclaimed".to_string()),
+        warning: None,
+        failure: result.failure,
+        remediation: result.remediation,
+    })
+}
+
+/// Get the active Stellar network
+#[tauri::command]
+pub async fn get_stellar_network(
+    state: State<'_, AppState>,
+) -> Result<StellarNetworkResponse, String> {
+    let stellar = state.stellar.lock().await;
+    Ok(StellarNetworkResponse::from_service(&stellar))
+}
+
+/// Switch between mainnet and testnet, rebuilding the Stellar service
+#[tauri::command]
+pub async fn set_stellar_network(
+    network: String,
+    state: State<'_, AppState>,
+) -> Result<StellarNetworkResponse, String> {
+    let network = StellarNetwork::parse(&network)
+        .ok_or_else(|| format!("Unknown Stellar network: {}", network))?;
+
+    let mut db = state.database.lock().await;
+    settings::save_stellar_network(&mut db, &state.config, network).map_err(|e| e.to_string())?;
+    let endpoints = Endpoints::load(&db, &state.config);
+    drop(db);
+
+    let mut config = network.config();
+    config.horizon_url = endpoints.horizon_url;
+
+    let mut stellar = state.stellar.lock().await;
+    *stellar = Arc::new(StellarService::new(config).with_client(state.api.client().clone()));
+    spawn_gns_asset_check(state.stellar.clone());
+
+    if network == StellarNetwork::Testnet {
+        tracing::warn!("⚠️ Switched Stellar to TESTNET - balances are not real funds");
+    } else {
+        tracing::info!("🌐 Switched Stellar to mainnet");
+    }
+
+    Ok(StellarNetworkResponse::from_service(&stellar))
+}
+
+/// Get the GNS asset's issuer and trust flags on the active network
+#[tauri::command]
+pub async fn get_gns_asset_info(
+    state: State<'_, AppState>,
+) -> Result<GnsAssetInfo, String> {
+    let stellar = state.stellar.lock().await.clone();
+    stellar.gns_asset_info().await.map_err(|e| e.to_string())
+}
+
+/// Check the GNS asset against the stellar.toml at its issuer's home
+/// domain, for the "verified asset" badge
+#[tauri::command]
+pub async fn verify_gns_asset_toml(
+    state: State<'_, AppState>,
+) -> Result<AssetVerification, String> {
+    let stellar = state.stellar.lock().await;
+    stellar.verify_gns_asset_toml().await.map_err(|e| e.to_string())
+}
+
+/// Assets the wallet works with on the active network, GNS first
+#[tauri::command]
+pub async fn list_known_assets(
+    state: State<'_, AppState>,
+) -> Result<Vec<Asset>, String> {
+    let stellar = state.stellar.lock().await;
+    Ok(stellar.config().known_assets())
+}
+
+/// Verify the configured GNS issuer in the background and warm the asset cache
+pub(crate) fn spawn_gns_asset_check(stellar: Arc<Mutex<Arc<StellarService>>>) {
+    tauri::async_runtime::spawn(async move {
+        let stellar = stellar.lock().await.clone();
+        match stellar.gns_asset_info().await {
+            Ok(info) => tracing::info!(
+                issuer = %info.issuer,
+                network = info.network.as_str(),
+                clawback = info.clawback_enabled,
+                "🪙 GNS asset verified"
+            ),
+            Err(StellarError::InvalidIssuer(e)) => tracing::error!("❌ GNS issuer is invalid: {}", e),
+            Err(e) => tracing::warn!("⚠️ Could not verify GNS asset: {}", e),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE_PK: &str = "4a4ba1c1a7b6c1c5bf9d5c1d3a4e6f2b8c0d1e2f3a4b5c6d7e8f9a0b1c2d3e4f";
+
+    fn identity(public_key: &str) -> IdentityInfo {
+        IdentityInfo {
+            public_key: public_key.to_string(),
+            encryption_key: String::new(),
+            handle: Some("alice".to_string()),
+            avatar_url: None,
+            display_name: None,
+            is_verified: true,
+            profile: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_resolves_to_stellar_address() {
+        let recipient = resolve_recipient("  @Alice ", |handle| async move {
+            assert_eq!(handle, "alice");
+            Ok(Some(identity(&ALICE_PK.to_uppercase())))
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(recipient.handle, "alice");
+        assert_eq!(recipient.public_key, ALICE_PK);
+        assert_eq!(recipient.stellar_address, StellarService::gns_key_to_stellar(ALICE_PK).unwrap());
+        assert!(recipient.stellar_address.starts_with('G'));
+    }
+
+    #[tokio::test]
+    async fn test_unresolved_handle_is_distinct_from_lookup_failure() {
+        let missing = resolve_recipient("@nobody", |_| async { Ok(None) }).await;
+        assert!(matches!(missing, Err(SendToHandleError::HandleNotResolved(h)) if h == "nobody"));
+
+        // The API answered, but with no usable key
+        let keyless = resolve_recipient("alice", |_| async { Ok(Some(identity(""))) }).await;
+        assert!(matches!(keyless, Err(SendToHandleError::HandleNotResolved(_))));
+
+        let offline = resolve_recipient("alice", |_| async {
+            Err(NetworkError::RequestError("timed out".to_string()))
+        })
+        .await;
+        assert!(matches!(offline, Err(SendToHandleError::LookupFailed(_))));
+
+        let error = serde_json::to_value(SendToHandleError::HandleNotResolved("nobody".to_string())).unwrap();
+        assert_eq!(error["kind"], "handle_not_resolved");
+        assert_eq!(error["message"], "Handle @nobody not found");
+        assert!(error["remediation"].is_null());
+
+        let error = serde_json::to_value(SendToHandleError::SendFailed("tx_failed (op_no_trust)".to_string())).unwrap();
+        assert_eq!(error["kind"], "send_failed");
+        assert_eq!(error["failure"], "no_trust");
+        assert_eq!(error["remediation"], "create_trustline");
+    }
+
+    #[tokio::test]
+    async fn test_recipient_key_skips_handle_lookup() {
+        let address = resolve_handle_or_key(&ALICE_PK.to_uppercase(), |_| async {
+            panic!("a public key needs no lookup")
+        })
+        .await
+        .unwrap();
+        assert_eq!(address, StellarService::gns_key_to_stellar(ALICE_PK).unwrap());
+
+        let by_handle = resolve_handle_or_key("@alice", |_| async { Ok(Some(identity(ALICE_PK))) }).await;
+        assert_eq!(by_handle.unwrap(), address);
+
+        let missing = resolve_handle_or_key("@nobody", |_| async { Ok(None) }).await;
+        assert!(matches!(missing, Err(SendToHandleError::HandleNotResolved(_))));
+    }
+}
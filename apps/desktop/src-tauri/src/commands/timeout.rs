@@ -0,0 +1,114 @@
+//! Command Time Limits
+//!
+//! Network-bound commands run under the limit `DesktopConfig` gives their
+//! category, so every call from the UI settles even when the backend
+//! stalls. A command past its limit fails with [`CommandError::Timeout`].
+//! Lookups are simply dropped, which aborts the request in flight. Sends
+//! are cancelled instead (see `crate::stellar::cancel`): they stop at the
+//! next step before submission, and one already submitted runs to
+//! completion and reports its real outcome.
+
+use crate::stellar::CancelToken;
+use serde::Serialize;
+use std::future::Future;
+use std::time::Duration;
+
+/// Error of a command with a time limit
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CommandError {
+    #[error("{command} timed out after {seconds}s")]
+    Timeout { command: String, seconds: u64 },
+
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl CommandError {
+    pub fn timeout(command: &str, limit: Duration) -> Self {
+        Self::Timeout { command: command.to_string(), seconds: limit.as_secs() }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Timeout { .. } => "timeout",
+            Self::Failed(_) => "failed",
+        }
+    }
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        Self::Failed(message)
+    }
+}
+
+impl From<&str> for CommandError {
+    fn from(message: &str) -> Self {
+        Self::Failed(message.to_string())
+    }
+}
+
+impl Serialize for CommandError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut error = serializer.serialize_struct("CommandError", 2)?;
+        error.serialize_field("kind", self.kind())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
+    }
+}
+
+/// Await `fut` for at most `limit`, dropping it if it takes longer
+pub async fn with_timeout<T>(command: &str, limit: Duration, fut: impl Future<Output = T>) -> Result<T, CommandError> {
+    tokio::time::timeout(limit, fut).await.map_err(|_| {
+        tracing::warn!("⏱️ {} gave up after {:?}", command, limit);
+        CommandError::timeout(command, limit)
+    })
+}
+
+/// Await `fut`, cancelling `cancel` once `limit` passes, and say whether
+/// it did. Unlike [`with_timeout`] this waits for `fut` to finish, which
+/// it does promptly unless it is past the point cancellation can stop.
+pub async fn cancel_after<F: Future>(limit: Duration, cancel: &CancelToken, fut: F) -> (F::Output, bool) {
+    tokio::pin!(fut);
+    tokio::select! {
+        output = &mut fut => (output, false),
+        _ = tokio::time::sleep(limit) => {
+            cancel.cancel();
+            (fut.await, true)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stellar::StellarError;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_never_resolving_command_times_out() {
+        let limit = Duration::from_secs(30);
+        let result = with_timeout("resolve_handle", limit, std::future::pending::<()>()).await;
+        assert_eq!(result, Err(CommandError::timeout("resolve_handle", limit)));
+
+        let json = serde_json::to_value(result.unwrap_err()).unwrap();
+        assert_eq!(json["kind"], "timeout");
+        assert_eq!(json["message"], "resolve_handle timed out after 30s");
+
+        assert_eq!(with_timeout("resolve_handle", limit, async { 7 }).await, Ok(7));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancel_after_stops_a_cancellable_operation() {
+        let cancel = CancelToken::new();
+        let stalled = cancel.guard(std::future::pending::<()>());
+        let (result, timed_out) = cancel_after(Duration::from_secs(5), &cancel, stalled).await;
+        assert!(timed_out);
+        assert!(matches!(result, Err(StellarError::Cancelled)));
+
+        let cancel = CancelToken::new();
+        let (result, timed_out) = cancel_after(Duration::from_secs(5), &cancel, async { 1 }).await;
+        assert_eq!((result, timed_out), (1, false));
+        assert!(!cancel.is_cancelled());
+    }
+}
@@ -2,6 +2,9 @@
 //!
 //! Miscellaneous utility commands.
 
+use crate::debug_snapshot::{ConnectivitySummary, DebugSnapshot, IdentitySummary, SnapshotLoadReport, StoredState};
+use crate::diagnostics::{self, timed, DiagnosticsReport};
+use crate::storage::{self, CompactionReport, IntegrityReport, RepairReport, ThreadIdMigration};
 use crate::AppState;
 use tauri::State;
 
@@ -27,16 +30,18 @@ pub async fn open_external_url(url: String) -> Result<(), String> {
     open::that(&url).map_err(|e| format!("Failed to open URL: {}", e))
 }
 
-/// Get offline status for the offline UI page
+/// Get offline status for the offline UI page.
+///
+/// `is_online` is the cached connectivity state; listen for
+/// `connectivity_changed` events to hear about changes without polling.
 #[tauri::command]
 pub async fn get_offline_status(state: State<'_, AppState>) -> Result<OfflineStatus, String> {
     let db = state.database.lock().await;
-    let relay = state.relay.lock().await;
 
     let breadcrumb_count = db.count_breadcrumbs().unwrap_or(0);
     let pending_messages = db.count_pending_messages().unwrap_or(0);
     let last_sync = db.get_last_sync_time();
-    let is_online = relay.is_connected().await;
+    let is_online = state.connectivity.is_online();
 
     Ok(OfflineStatus {
         is_online,
@@ -50,6 +55,161 @@ pub async fn get_offline_status(state: State<'_, AppState>) -> Result<OfflineSta
     })
 }
 
+/// Run the self-test suite and return a report safe to paste into a bug
+/// report
+#[tauri::command]
+pub async fn run_diagnostics(state: State<'_, AppState>) -> Result<DiagnosticsReport, String> {
+    let api_health = format!("{}/health", state.api.base_url());
+    let horizon = state.stellar.lock().await.config().horizon_url.clone();
+    let client = state.api.client();
+
+    let identity_check = {
+        let identity = state.identity.lock().await;
+        timed("identity", async { diagnostics::check_identity(identity.get_identity()) }).await
+    };
+
+    let checks = vec![
+        timed("crypto", async { diagnostics::check_crypto() }).await,
+        identity_check,
+        timed("database", diagnostics::check_database(&state.database)).await,
+        timed("relay", diagnostics::check_relay(&state.relay)).await,
+        timed("api", diagnostics::check_http(client, &api_health)).await,
+        timed("horizon", diagnostics::check_http(client, &horizon)).await,
+    ];
+
+    let report = DiagnosticsReport::new(checks);
+    tracing::info!(
+        "🩺 Diagnostics: {}/{} checks passed",
+        report.checks.iter().filter(|c| c.passed).count(),
+        report.checks.len()
+    );
+    Ok(report)
+}
+
+/// Capture a redacted snapshot of the app's state for a support ticket.
+/// Holds public keys and counts only: no private keys, message bodies or
+/// locations.
+#[tauri::command]
+pub async fn capture_debug_snapshot(state: State<'_, AppState>) -> Result<DebugSnapshot, String> {
+    let identity = {
+        let identity = state.identity.lock().await;
+        identity.get_identity().map(|id| IdentitySummary {
+            public_key: id.public_key_hex(),
+            encryption_key: id.encryption_key_hex(),
+            handle: identity.cached_handle(),
+        })
+    };
+
+    let connectivity = {
+        // Not borrowed across the awaits below
+        let relay_ready = state.relay_ready.borrow().is_some();
+        let relay = state.relay.lock().await;
+        ConnectivitySummary {
+            online: state.connectivity.is_online(),
+            relay_url: relay.url().to_string(),
+            relay_connected: relay.is_connected().await,
+            relay_ready,
+            reconnect_attempts: relay.reconnect_attempts().await,
+            last_relay_message_at: relay.last_message_time().await,
+        }
+    };
+
+    let stored = {
+        let db = state.database.lock().await;
+        StoredState::read(&db)?
+    };
+
+    let snapshot = DebugSnapshot::new(state.config.clone(), identity, stored, connectivity, state.errors.recent());
+    tracing::info!("🧪 Captured debug snapshot ({} threads)", snapshot.threads.len());
+    Ok(snapshot)
+}
+
+/// Recreate a snapshot's threads with placeholder messages, to reproduce
+/// a report. Dev builds only, and only into a profile without
+/// conversations.
+#[tauri::command]
+pub async fn load_debug_snapshot(
+    snapshot: DebugSnapshot,
+    state: State<'_, AppState>,
+) -> Result<SnapshotLoadReport, String> {
+    if !cfg!(debug_assertions) {
+        return Err("Debug snapshots can only be loaded in a dev build".to_string());
+    }
+
+    let mut db = state.database.lock().await;
+    snapshot.load_into(&mut db)
+}
+
+/// Reclaim the space left by deleted data (`VACUUM`). Runs off the UI
+/// thread; other database work waits until it's done.
+#[tauri::command]
+pub async fn compact_storage(state: State<'_, AppState>) -> Result<CompactionReport, String> {
+    let report = storage::compact_in_background(state.database.clone())
+        .await
+        .map_err(|e| format!("Compaction failed: {}", e))?;
+    tracing::info!("🧹 Compacted database, reclaimed {} bytes", report.bytes_reclaimed);
+    Ok(report)
+}
+
+/// Check the database for corruption and inconsistent rows
+#[tauri::command]
+pub async fn verify_database_integrity(state: State<'_, AppState>) -> Result<IntegrityReport, String> {
+    let own_public_key = state.identity.lock().await.public_key_hex();
+    let db = state.database.lock().await;
+    let report = db
+        .verify_integrity(own_public_key.as_deref())
+        .map_err(|e| format!("Integrity check failed: {}", e))?;
+
+    if !report.ok {
+        tracing::warn!("🩺 Database integrity check found {} issues", report.issues.len());
+    }
+    Ok(report)
+}
+
+/// Quarantine rows that can't be made consistent and rebuild the indexes.
+/// Returns what is still wrong afterwards.
+#[tauri::command]
+pub async fn repair_database(state: State<'_, AppState>) -> Result<RepairReport, String> {
+    let own_public_key = state.identity.lock().await.public_key_hex();
+    let mut db = state.database.lock().await;
+    let report = db
+        .repair(own_public_key.as_deref())
+        .map_err(|e| format!("Repair failed: {}", e))?;
+
+    tracing::info!(
+        "🩹 Database repaired: {} rows quarantined, {} issues left",
+        report.quarantined,
+        report.after.issues.len()
+    );
+    Ok(report)
+}
+
+/// File every direct message under its canonical thread id, merging
+/// threads split between id schemes. Runs by itself once after upgrading;
+/// running it again only picks up stragglers.
+#[tauri::command]
+pub async fn migrate_thread_ids(state: State<'_, AppState>) -> Result<ThreadIdMigration, String> {
+    let own_public_key = state
+        .identity
+        .lock()
+        .await
+        .public_key_hex()
+        .ok_or("No identity found")?;
+    let migration = state
+        .database
+        .lock()
+        .await
+        .migrate_thread_ids(&own_public_key)
+        .map_err(|e| format!("Thread id migration failed: {}", e))?;
+
+    tracing::info!(
+        "🧵 Thread ids migrated: {} messages moved, {} threads merged",
+        migration.messages_moved,
+        migration.threads_merged
+    );
+    Ok(migration)
+}
+
 #[derive(serde::Serialize)]
 pub struct AppVersion {
     pub version: String,
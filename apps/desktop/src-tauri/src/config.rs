@@ -0,0 +1,400 @@
+//! Desktop App Configuration
+//!
+//! Build-time defaults for the app, loadable from `tauri.conf.json` the
+//! same way the GNS plugin loads its `GnsConfig`. Endpoint overrides the
+//! user saves at runtime (see `settings`) still take precedence; these are
+//! what the app falls back to without them.
+
+use crate::settings::{self, DEFAULT_API_URL, DEFAULT_RELAY_URL};
+use crate::stellar::StellarNetwork;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Key of the app's section under `plugins` in `tauri.conf.json`
+pub const CONFIG_KEY: &str = "gnsDesktop";
+
+/// Desktop App Configuration
+///
+/// # Configuration in tauri.conf.json
+///
+/// ```json
+/// {
+///   "plugins": {
+///     "gnsDesktop": {
+///       "apiUrl": "https://staging.gns.earth",
+///       "relayUrl": "wss://staging.gns.earth",
+///       "stellarNetwork": "testnet",
+///       "messageLimit": 100,
+///       "reconnect": { "maxDelayMs": 60000 }
+///     }
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DesktopConfig {
+    /// GNS API base URL.
+    ///
+    /// Default: [`DEFAULT_API_URL`]
+    #[serde(default = "default_api_url")]
+    pub api_url: String,
+
+    /// Relay WebSocket URL.
+    ///
+    /// Default: [`DEFAULT_RELAY_URL`]
+    #[serde(default = "default_relay_url")]
+    pub relay_url: String,
+
+    /// Stellar network used until the user picks one.
+    ///
+    /// Default: `"mainnet"`
+    #[serde(default = "default_stellar_network")]
+    pub stellar_network: StellarNetwork,
+
+    /// Horizon URL for `stellar_network`. The other network keeps its
+    /// public Horizon.
+    ///
+    /// Default: the public Horizon of `stellar_network`
+    #[serde(default)]
+    pub horizon_url: Option<String>,
+
+    /// Messages returned per page when a request doesn't say.
+    ///
+    /// Default: `50`
+    #[serde(default = "default_message_limit")]
+    pub message_limit: u32,
+
+    /// API request timeout in seconds.
+    ///
+    /// Default: `30`
+    #[serde(default = "default_network_timeout")]
+    pub network_timeout_seconds: u64,
+
+    /// Time limits of network-bound commands by category, so a stalled
+    /// backend can't leave the UI waiting forever.
+    #[serde(default)]
+    pub command_timeouts: CommandTimeouts,
+
+    /// How long a derived per-peer message key is reused, in seconds.
+    /// `0` derives a new key for every message.
+    ///
+    /// Default: `120`
+    #[serde(default = "default_message_key_cache")]
+    pub message_key_cache_seconds: u64,
+
+    /// Negotiate a ratchet session with each peer and encrypt one-to-one
+    /// messages in it once the peer answers, so a key captured later
+    /// doesn't open earlier messages. Peers that don't answer keep getting
+    /// static-key encryption. A session lives on one device, so leave this
+    /// off for identities used on several.
+    ///
+    /// Default: `false`
+    #[serde(default)]
+    pub forward_secrecy: bool,
+
+    /// Sign handle reservations, claims and releases, identity records and
+    /// DIX posts, likes, reposts and follows within their signature domain.
+    /// Turn on once the backend verifies domain-tagged signatures; until
+    /// then these are signed over the bare message.
+    ///
+    /// Default: `false`
+    #[serde(default)]
+    pub tagged_signatures: bool,
+
+    /// Tell peers we open compressed envelopes, and deflate large payloads
+    /// for peers that told us the same. Readers that predate this see the
+    /// signed announcement as a bad signature, so turn it on once peers
+    /// have updated.
+    ///
+    /// Default: `false`
+    #[serde(default)]
+    pub compression: bool,
+
+    /// Backoff between relay reconnect attempts.
+    #[serde(default)]
+    pub reconnect: ReconnectPolicy,
+
+    /// Which incoming envelopes are turned away as replays.
+    #[serde(default)]
+    pub replay: ReplayPolicy,
+
+    /// How bursts of UI events are batched and throttled.
+    #[serde(default)]
+    pub events: EventPacing,
+}
+
+/// Exponential backoff for relay reconnects: the first retry waits
+/// `initial_delay_ms`, each later one twice as long, up to `max_delay_ms`.
+///
+/// Fields left out of the JSON keep their default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReconnectPolicy {
+    /// Default: `2000`
+    pub initial_delay_ms: u64,
+    /// Default: `30000`
+    pub max_delay_ms: u64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay_ms: 2000,
+            max_delay_ms: 30_000,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Wait before reconnect attempt `attempt` (counting from 1)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+        Duration::from_millis(self.initial_delay_ms.saturating_mul(factor).min(self.max_delay_ms))
+    }
+}
+
+/// Per-category time limits for network-bound commands, in seconds. A
+/// category left unset uses `networkTimeoutSeconds`.
+///
+/// Fields left out of the JSON keep their default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CommandTimeouts {
+    /// Handle lookups and availability checks.
+    pub lookup_seconds: Option<u64>,
+    /// Balance queries and sends. A send past its limit is only stopped
+    /// before submission.
+    pub payment_seconds: Option<u64>,
+    /// Home hub discovery, on top of the browse time the caller asks for.
+    pub discovery_seconds: Option<u64>,
+}
+
+/// What kind of work a command does, for picking its time limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandCategory {
+    Lookup,
+    Payment,
+    Discovery,
+}
+
+/// Limits on incoming envelopes, against a relay (or anyone on the path)
+/// delivering a captured envelope again under a new id. An envelope is
+/// turned away if its timestamp is too far in the future or too old, or
+/// if the same content from the same sender is among the last
+/// `window_size` seen from them.
+///
+/// Fields left out of the JSON keep their default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReplayPolicy {
+    /// How far ahead of our clock a timestamp may be.
+    ///
+    /// Default: `300000` (5 minutes)
+    pub max_future_skew_ms: u64,
+    /// How old an envelope may be. The relay holds envelopes for offline
+    /// recipients, so this has to cover how long we might be away.
+    ///
+    /// Default: `604800000` (7 days)
+    pub max_age_ms: u64,
+    /// Envelopes remembered per sender.
+    ///
+    /// Default: `512`
+    pub window_size: usize,
+}
+
+impl Default for ReplayPolicy {
+    fn default() -> Self {
+        Self {
+            max_future_skew_ms: 5 * 60 * 1000,
+            max_age_ms: 7 * 24 * 60 * 60 * 1000,
+            window_size: 512,
+        }
+    }
+}
+
+/// Pacing of high-frequency UI events (see `crate::events`).
+///
+/// Fields left out of the JSON keep their default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EventPacing {
+    /// How long incoming messages are collected into one batch. `0`
+    /// emits each message on its own.
+    ///
+    /// Default: `100`
+    pub batch_window_ms: u64,
+    /// Least time between two throttled events with the same key.
+    ///
+    /// Default: `250`
+    pub throttle_ms: u64,
+}
+
+impl Default for EventPacing {
+    fn default() -> Self {
+        Self {
+            batch_window_ms: 100,
+            throttle_ms: 250,
+        }
+    }
+}
+
+fn default_api_url() -> String {
+    DEFAULT_API_URL.to_string()
+}
+
+fn default_relay_url() -> String {
+    DEFAULT_RELAY_URL.to_string()
+}
+
+fn default_stellar_network() -> StellarNetwork {
+    StellarNetwork::Mainnet
+}
+
+fn default_message_limit() -> u32 {
+    50
+}
+
+fn default_network_timeout() -> u64 {
+    30
+}
+
+fn default_message_key_cache() -> u64 {
+    gns_crypto_core::DEFAULT_KEY_CACHE_TTL.as_secs()
+}
+
+impl Default for DesktopConfig {
+    fn default() -> Self {
+        Self {
+            api_url: default_api_url(),
+            relay_url: default_relay_url(),
+            stellar_network: default_stellar_network(),
+            horizon_url: None,
+            message_limit: default_message_limit(),
+            network_timeout_seconds: default_network_timeout(),
+            command_timeouts: CommandTimeouts::default(),
+            message_key_cache_seconds: default_message_key_cache(),
+            forward_secrecy: false,
+            tagged_signatures: false,
+            compression: false,
+            reconnect: ReconnectPolicy::default(),
+            replay: ReplayPolicy::default(),
+            events: EventPacing::default(),
+        }
+    }
+}
+
+impl DesktopConfig {
+    /// Build the configuration from the app's `tauri.conf.json` section.
+    ///
+    /// A missing section gives the defaults. So does an invalid one, with
+    /// a warning, rather than keeping the app from starting.
+    pub fn from_value(value: Option<&serde_json::Value>) -> Self {
+        let Some(value) = value else { return Self::default() };
+
+        let parsed = serde_json::from_value::<Self>(value.clone())
+            .map_err(|e| e.to_string())
+            .and_then(Self::validated);
+        match parsed {
+            Ok(config) => config,
+            Err(e) => {
+                tracing::warn!("⚠️ Invalid {} config, using defaults: {}", CONFIG_KEY, e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Check and normalise the URLs and limits
+    fn validated(mut self) -> Result<Self, String> {
+        let http = &["https", "http"];
+        self.api_url = settings::validate_url(&self.api_url, http)?;
+        self.relay_url = settings::validate_url(&self.relay_url, &["wss", "ws", "https", "http"])?;
+        self.horizon_url = self.horizon_url.map(|url| settings::validate_url(&url, http)).transpose()?;
+
+        if self.message_limit == 0 {
+            return Err("messageLimit must be at least 1".to_string());
+        }
+        if self.network_timeout_seconds == 0 {
+            return Err("networkTimeoutSeconds must be at least 1".to_string());
+        }
+        let timeouts = &self.command_timeouts;
+        if [timeouts.lookup_seconds, timeouts.payment_seconds, timeouts.discovery_seconds].contains(&Some(0)) {
+            return Err("commandTimeouts must be at least 1 second".to_string());
+        }
+        if self.replay.window_size == 0 {
+            return Err("replay.windowSize must be at least 1".to_string());
+        }
+        Ok(self)
+    }
+
+    pub fn network_timeout(&self) -> Duration {
+        Duration::from_secs(self.network_timeout_seconds)
+    }
+
+    /// Time limit of a command in `category`
+    pub fn command_timeout(&self, category: CommandCategory) -> Duration {
+        let timeouts = &self.command_timeouts;
+        let seconds = match category {
+            CommandCategory::Lookup => timeouts.lookup_seconds,
+            CommandCategory::Payment => timeouts.payment_seconds,
+            CommandCategory::Discovery => timeouts.discovery_seconds,
+        };
+        Duration::from_secs(seconds.unwrap_or(self.network_timeout_seconds))
+    }
+
+    pub fn message_key_ttl(&self) -> Duration {
+        Duration::from_secs(self.message_key_cache_seconds)
+    }
+
+    /// Horizon to use on `network` unless the user overrode it
+    pub fn horizon_url_for(&self, network: StellarNetwork) -> String {
+        match &self.horizon_url {
+            Some(url) if network == self.stellar_network => url.clone(),
+            _ => network.config().horizon_url,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provided_config_overrides_defaults() {
+        let value = serde_json::json!({
+            "apiUrl": "http://localhost:3000/",
+            "relayUrl": "ws://localhost:3000",
+            "stellarNetwork": "testnet",
+            "horizonUrl": "http://localhost:8000",
+            "messageLimit": 10,
+            "reconnect": { "maxDelayMs": 5000 },
+            "commandTimeouts": { "paymentSeconds": 90 },
+            "events": { "batchWindowMs": 0 },
+        });
+        let config = DesktopConfig::from_value(Some(&value));
+
+        assert_eq!(config.api_url, "http://localhost:3000");
+        assert_eq!(config.relay_url, "ws://localhost:3000");
+        assert_eq!(config.stellar_network, StellarNetwork::Testnet);
+        assert_eq!(config.message_limit, 10);
+        assert_eq!(config.horizon_url_for(StellarNetwork::Testnet), "http://localhost:8000");
+        assert_eq!(config.horizon_url_for(StellarNetwork::Mainnet), StellarNetwork::Mainnet.config().horizon_url);
+
+        // Fields left out keep their defaults, nested ones included
+        assert_eq!(config.network_timeout_seconds, 30);
+        assert_eq!(config.message_key_ttl(), Duration::from_secs(120));
+        assert!(!config.forward_secrecy);
+        assert!(!config.tagged_signatures);
+        assert!(!config.compression);
+        assert_eq!(config.reconnect.initial_delay_ms, 2000);
+        assert_eq!(config.reconnect.delay(1), Duration::from_secs(2));
+        assert_eq!(config.reconnect.delay(10), Duration::from_secs(5));
+        assert_eq!(config.replay, ReplayPolicy::default());
+        assert_eq!(config.events, EventPacing { batch_window_ms: 0, throttle_ms: 250 });
+        assert_eq!(config.command_timeout(CommandCategory::Payment), Duration::from_secs(90));
+        assert_eq!(config.command_timeout(CommandCategory::Lookup), Duration::from_secs(30));
+
+        assert_eq!(DesktopConfig::from_value(None), DesktopConfig::default());
+        let invalid = serde_json::json!({ "apiUrl": "ftp://example.com" });
+        assert_eq!(DesktopConfig::from_value(Some(&invalid)), DesktopConfig::default());
+    }
+}
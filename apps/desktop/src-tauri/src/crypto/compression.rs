@@ -0,0 +1,86 @@
+//! Envelope Compression
+//!
+//! Readers that predate compressed envelopes can't open them, so a payload
+//! is only deflated for peers whose own validly signed envelopes said they
+//! accept compression. Everyone else gets it as is. Which peers said so is
+//! kept in memory only: after a restart a peer gets plain payloads again
+//! until the next envelope from them.
+
+use gns_crypto_core::{Compression, GnsEnvelope, DEFAULT_COMPRESSION_THRESHOLD};
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// Peers known to open compressed envelopes
+#[derive(Debug, Default)]
+pub struct CompressionPeers {
+    peers: Mutex<HashSet<String>>,
+}
+
+impl CompressionPeers {
+    /// Remember whether the sender of `envelope` accepts compression.
+    /// Envelopes with an invalid signature don't count either way.
+    pub fn note(&self, envelope: &GnsEnvelope, signature_valid: bool) {
+        if !signature_valid {
+            return;
+        }
+        let sender = envelope.from_public_key.to_lowercase();
+        let mut peers = self.peers.lock().unwrap();
+        if envelope.accepts_compression {
+            peers.insert(sender);
+        } else {
+            peers.remove(&sender);
+        }
+    }
+
+    /// Compression to seal envelopes for `public_key` with: deflate if the
+    /// peer accepts it, else just say that we do
+    pub fn compression_for(&self, public_key: &str) -> Compression {
+        if self.peers.lock().unwrap().contains(&public_key.to_lowercase()) {
+            Compression::Deflate {
+                threshold: DEFAULT_COMPRESSION_THRESHOLD,
+            }
+        } else {
+            Compression::Accept
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gns_crypto_core::{create_envelope_with_compression, GnsIdentity};
+
+    fn envelope_from(sender: &GnsIdentity, compression: Compression) -> GnsEnvelope {
+        let recipient = GnsIdentity::generate();
+        create_envelope_with_compression(
+            sender,
+            &recipient.public_key_hex(),
+            &recipient.encryption_key_hex(),
+            "text/plain",
+            b"hi",
+            compression,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_only_peers_that_accept_get_compressed_envelopes() {
+        let peers = CompressionPeers::default();
+        let (old, new) = (GnsIdentity::generate(), GnsIdentity::generate());
+
+        peers.note(&envelope_from(&old, Compression::Off), true);
+        peers.note(&envelope_from(&new, Compression::Accept), true);
+        assert_eq!(peers.compression_for(&old.public_key_hex()), Compression::Accept);
+        assert!(matches!(
+            peers.compression_for(&new.public_key_hex().to_uppercase()),
+            Compression::Deflate { .. }
+        ));
+
+        // A badly signed envelope doesn't count, a later plain one does
+        let forged = GnsIdentity::generate();
+        peers.note(&envelope_from(&forged, Compression::Accept), false);
+        assert_eq!(peers.compression_for(&forged.public_key_hex()), Compression::Accept);
+        peers.note(&envelope_from(&new, Compression::Off), true);
+        assert_eq!(peers.compression_for(&new.public_key_hex()), Compression::Accept);
+    }
+}
@@ -0,0 +1,190 @@
+//! Identity Cards
+//!
+//! A card is the public half of an identity, signed by it, for handing
+//! to someone in person as a QR code / `gns-card:` deep link. It carries
+//! the encryption key, so whoever imports it can message the owner
+//! without resolving them first. Nothing secret goes in a card.
+
+use crate::stellar::StellarService;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use gns_crypto_core::{verify_in_domain_hex, DomainPolicy, GnsIdentity, SignatureDomain};
+use serde::{Deserialize, Serialize};
+
+/// Deep link / QR prefix for identity cards
+pub const IDENTITY_CARD_PREFIX: &str = "gns-card:";
+
+const IDENTITY_CARD_VERSION: u32 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum IdentityCardError {
+    #[error("Not an identity card: {0}")]
+    Malformed(String),
+
+    #[error("Identity card signature is invalid")]
+    BadSignature,
+
+    #[error("Identity card Stellar address {0} doesn't belong to its key")]
+    AddressMismatch(String),
+}
+
+/// A shareable, self-signed contact card
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdentityCard {
+    pub version: u32,
+    pub public_key: String,
+    /// X25519 key messages to this identity are sealed to
+    pub encryption_key: String,
+    pub handle: Option<String>,
+    pub display_name: Option<String>,
+    pub stellar_address: String,
+    /// Signature in the `Card` domain over every other field
+    pub signature: String,
+}
+
+/// The signed fields, in a fixed order
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SignedCard<'a> {
+    version: u32,
+    public_key: &'a str,
+    encryption_key: &'a str,
+    handle: Option<&'a str>,
+    display_name: Option<&'a str>,
+    stellar_address: &'a str,
+}
+
+impl IdentityCard {
+    /// Sign a card for `identity`
+    pub fn create(
+        identity: &GnsIdentity,
+        handle: Option<String>,
+        display_name: Option<String>,
+    ) -> Result<Self, IdentityCardError> {
+        let public_key = identity.public_key_hex();
+        let stellar_address = StellarService::gns_key_to_stellar(&public_key)
+            .map_err(|e| IdentityCardError::Malformed(e.to_string()))?;
+
+        let mut card = Self {
+            version: IDENTITY_CARD_VERSION,
+            public_key,
+            encryption_key: identity.encryption_key_hex(),
+            handle: handle.map(|h| h.trim_start_matches('@').to_lowercase()),
+            display_name,
+            stellar_address,
+            signature: String::new(),
+        };
+        card.signature = hex::encode(identity.sign_in_domain(SignatureDomain::Card, &card.signed_bytes()));
+        Ok(card)
+    }
+
+    fn signed_bytes(&self) -> Vec<u8> {
+        let signed = SignedCard {
+            version: self.version,
+            public_key: &self.public_key,
+            encryption_key: &self.encryption_key,
+            handle: self.handle.as_deref(),
+            display_name: self.display_name.as_deref(),
+            stellar_address: &self.stellar_address,
+        };
+        serde_json::to_vec(&signed).unwrap_or_default()
+    }
+
+    /// Check the card is signed by its own key and names that key's
+    /// Stellar address, so neither can be swapped for someone else's
+    pub fn verify(&self) -> Result<(), IdentityCardError> {
+        let signed = verify_in_domain_hex(
+            &self.public_key,
+            SignatureDomain::Card,
+            &self.signed_bytes(),
+            &self.signature,
+            DomainPolicy::Strict,
+        )
+        .unwrap_or(false);
+        if !signed {
+            return Err(IdentityCardError::BadSignature);
+        }
+
+        let derived = StellarService::gns_key_to_stellar(&self.public_key)
+            .map_err(|e| IdentityCardError::Malformed(e.to_string()))?;
+        if derived != self.stellar_address {
+            return Err(IdentityCardError::AddressMismatch(self.stellar_address.clone()));
+        }
+        Ok(())
+    }
+
+    /// Encode as a `gns-card:` deep link
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        format!("{}{}", IDENTITY_CARD_PREFIX, URL_SAFE_NO_PAD.encode(json))
+    }
+
+    /// Parse a scanned deep link. Does not verify it.
+    pub fn decode(card: &str) -> Result<Self, IdentityCardError> {
+        let body = card
+            .trim()
+            .strip_prefix(IDENTITY_CARD_PREFIX)
+            .ok_or_else(|| IdentityCardError::Malformed("missing gns-card: prefix".to_string()))?;
+        let json = URL_SAFE_NO_PAD
+            .decode(body)
+            .map_err(|e| IdentityCardError::Malformed(e.to_string()))?;
+        let card: Self =
+            serde_json::from_slice(&json).map_err(|e| IdentityCardError::Malformed(e.to_string()))?;
+
+        if card.version != IDENTITY_CARD_VERSION {
+            return Err(IdentityCardError::Malformed(format!(
+                "unsupported card version {}",
+                card.version
+            )));
+        }
+        Ok(card)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_card_round_trip() {
+        let identity = GnsIdentity::generate();
+        let card = IdentityCard::create(&identity, Some("@Alice".into()), Some("Alice".into())).unwrap();
+
+        let encoded = card.encode();
+        assert!(encoded.starts_with(IDENTITY_CARD_PREFIX));
+        assert!(!encoded.contains(&identity.private_key_hex()));
+
+        let scanned = IdentityCard::decode(&encoded).unwrap();
+        scanned.verify().unwrap();
+        assert_eq!(scanned, card);
+        assert_eq!(scanned.handle.as_deref(), Some("alice"));
+        assert_eq!(scanned.encryption_key, identity.encryption_key_hex());
+    }
+
+    #[test]
+    fn test_forged_cards_are_rejected() {
+        let alice = GnsIdentity::generate();
+        let card = IdentityCard::create(&alice, Some("alice".into()), None).unwrap();
+
+        // Mallory's encryption key on Alice's card
+        let mut swapped_key = card.clone();
+        swapped_key.encryption_key = GnsIdentity::generate().encryption_key_hex();
+        assert!(matches!(swapped_key.verify(), Err(IdentityCardError::BadSignature)));
+
+        // Mallory's own card relabelled as @alice
+        let mallory = GnsIdentity::generate();
+        let mut relabelled = IdentityCard::create(&mallory, None, None).unwrap();
+        relabelled.handle = Some("alice".into());
+        assert!(matches!(relabelled.verify(), Err(IdentityCardError::BadSignature)));
+
+        // Signed by the key it names, but paying out to Mallory
+        let mallory_address = StellarService::gns_key_to_stellar(&mallory.public_key_hex()).unwrap();
+        let mut resigned = card.clone();
+        resigned.stellar_address = mallory_address;
+        resigned.signature = hex::encode(alice.sign_in_domain(SignatureDomain::Card, &resigned.signed_bytes()));
+        assert!(matches!(resigned.verify(), Err(IdentityCardError::AddressMismatch(_))));
+
+        assert!(matches!(IdentityCard::decode("gns-migrate:abc"), Err(IdentityCardError::Malformed(_))));
+    }
+}
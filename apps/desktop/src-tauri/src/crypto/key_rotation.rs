@@ -0,0 +1,137 @@
+//! Signing Key Rotation
+//!
+//! Rotating the Ed25519 identity key replaces who we are on the network,
+//! so the old key vouches for the new one: it signs a rotation
+//! certificate naming its successor. The certificates go out with our
+//! signed record, and a resolver holding any earlier key can follow them
+//! to the current one. Messages signed by a retired key keep verifying
+//! against that key; nothing is re-signed.
+
+use gns_crypto_core::{verify_in_domain_hex, DomainPolicy, GnsIdentity, SignatureDomain};
+use serde::{Deserialize, Serialize};
+
+const ROTATION_CERTIFICATE_VERSION: u32 = 1;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum KeyRotationError {
+    #[error("Rotation certificate for {0} has an invalid signature")]
+    BadSignature(String),
+
+    #[error("Rotation certificate from {found} doesn't continue the chain at {expected}")]
+    BrokenChain { expected: String, found: String },
+}
+
+/// `previous_key` handing over to `new_key`, signed by `previous_key`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotationCertificate {
+    pub version: u32,
+    pub previous_key: String,
+    pub new_key: String,
+    /// Unix ms
+    pub rotated_at: i64,
+    /// Signature by `previous_key` in the `KeyRotation` domain
+    pub signature: String,
+}
+
+impl RotationCertificate {
+    /// Have `previous` sign over to `new_key`
+    pub fn create(previous: &GnsIdentity, new_key: &str, rotated_at: i64) -> Self {
+        let mut certificate = Self {
+            version: ROTATION_CERTIFICATE_VERSION,
+            previous_key: previous.public_key_hex(),
+            new_key: new_key.to_lowercase(),
+            rotated_at,
+            signature: String::new(),
+        };
+        certificate.signature =
+            hex::encode(previous.sign_in_domain(SignatureDomain::KeyRotation, &certificate.signed_bytes()));
+        certificate
+    }
+
+    fn signed_bytes(&self) -> Vec<u8> {
+        format!("{}:{}:{}", self.previous_key, self.new_key, self.rotated_at).into_bytes()
+    }
+
+    /// Check the certificate is signed by the key it retires
+    pub fn verify(&self) -> Result<(), KeyRotationError> {
+        let signed = verify_in_domain_hex(
+            &self.previous_key,
+            SignatureDomain::KeyRotation,
+            &self.signed_bytes(),
+            &self.signature,
+            DomainPolicy::Strict,
+        )
+        .unwrap_or(false);
+        if signed {
+            Ok(())
+        } else {
+            Err(KeyRotationError::BadSignature(self.previous_key.clone()))
+        }
+    }
+}
+
+/// Follow `certificates`, oldest first, and return every key they pass
+/// through, the original first and the current last. Each certificate
+/// must verify and start where the one before it ended.
+pub fn follow_chain(certificates: &[RotationCertificate]) -> Result<Vec<String>, KeyRotationError> {
+    let mut keys: Vec<String> = Vec::with_capacity(certificates.len() + 1);
+    for certificate in certificates {
+        certificate.verify()?;
+        if let Some(expected) = keys.last() {
+            if !expected.eq_ignore_ascii_case(&certificate.previous_key) {
+                return Err(KeyRotationError::BrokenChain {
+                    expected: expected.clone(),
+                    found: certificate.previous_key.clone(),
+                });
+            }
+        } else {
+            keys.push(certificate.previous_key.clone());
+        }
+        keys.push(certificate.new_key.clone());
+    }
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_follows_rotations_and_old_signatures_verify() {
+        let first = GnsIdentity::generate();
+        let second = GnsIdentity::generate();
+        let third = GnsIdentity::generate();
+        let message = b"signed before rotating";
+        let old_signature = hex::encode(first.sign_in_domain(SignatureDomain::Dix, message));
+
+        let chain = [
+            RotationCertificate::create(&first, &second.public_key_hex(), 1_000),
+            RotationCertificate::create(&second, &third.public_key_hex(), 2_000),
+        ];
+        assert_eq!(
+            follow_chain(&chain).unwrap(),
+            [first.public_key_hex(), second.public_key_hex(), third.public_key_hex()]
+        );
+
+        // Rotating doesn't touch what the old key already signed
+        assert!(verify_in_domain_hex(
+            &first.public_key_hex(),
+            SignatureDomain::Dix,
+            message,
+            &old_signature,
+            DomainPolicy::Strict
+        )
+        .unwrap());
+
+        // Out of order, or signed by the wrong key, the chain doesn't hold
+        let reversed = [chain[1].clone(), chain[0].clone()];
+        assert!(matches!(follow_chain(&reversed), Err(KeyRotationError::BrokenChain { .. })));
+        let mut forged = RotationCertificate::create(&third, &GnsIdentity::generate().public_key_hex(), 3_000);
+        forged.previous_key = second.public_key_hex();
+        assert_eq!(
+            follow_chain(&[chain[0].clone(), forged]),
+            Err(KeyRotationError::BadSignature(second.public_key_hex()))
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! Identity Migration Tokens
+//!
+//! A migration token moves an identity to a new device: the old device
+//! shows it as a QR code / `gns-migrate:` deep link, the new device scans
+//! it and imports the identity. The seed is sealed to a throwaway X25519
+//! key that stays on the old device; the new device has to ask for it over
+//! the relay (see `migration_handoff`), and the old device hands it out
+//! once, only while the token is unexpired and unrevoked. A leaked
+//! screenshot of the QR code is therefore useless once the token was used
+//! or revoked.
+
+use super::IdentityKeys;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use gns_crypto_core::{EncryptedPayload, GnsIdentity};
+use serde::{Deserialize, Serialize};
+
+/// Deep link / QR prefix for migration tokens
+pub const MIGRATION_TOKEN_PREFIX: &str = "gns-migrate:";
+
+/// How long a token stays valid after it is created
+pub const MIGRATION_TOKEN_TTL_SECS: i64 = 5 * 60;
+
+const MIGRATION_TOKEN_VERSION: u32 = 2;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MigrationError {
+    #[error("Not a migration token: {0}")]
+    Malformed(String),
+
+    #[error("Migration token has expired")]
+    Expired,
+
+    #[error("Migration token was revoked on the old device")]
+    Revoked,
+
+    #[error("Migration token has already been used")]
+    AlreadyUsed,
+
+    #[error("The old device did not answer; keep it unlocked and online, then try again")]
+    NoAnswer,
+
+    #[error("Network error: {0}")]
+    Network(String),
+
+    #[error("Migration token is corrupted: {0}")]
+    Corrupted(String),
+
+    #[error("An identity already exists on this device")]
+    IdentityExists,
+
+    #[error("Storage error: {0}")]
+    Storage(String),
+}
+
+/// A sealed identity ready to hand to another device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationToken {
+    pub version: u32,
+    pub id: String,
+    /// Public key of the identity being moved, shown before importing
+    pub public_key: String,
+    /// Encryption key of the identity, to reach the old device on the relay
+    pub encryption_key: String,
+    pub expires_at: i64,
+    sealed: EncryptedPayload,
+}
+
+/// What is actually sealed. `id` and `expires_at` are repeated inside so
+/// editing the outer copies can't extend or re-badge a token.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SealedSeed {
+    id: String,
+    expires_at: i64,
+    keys: IdentityKeys,
+}
+
+impl MigrationToken {
+    /// Seal `keys` into a new token valid for `MIGRATION_TOKEN_TTL_SECS`.
+    /// Also returns the private transport key the seed is sealed to, which
+    /// the old device keeps until the token is redeemed.
+    pub fn create(keys: &IdentityKeys, now: i64) -> Result<(Self, String), MigrationError> {
+        let identity = keys.identity().map_err(|e| MigrationError::Corrupted(e.to_string()))?;
+        let id = uuid::Uuid::new_v4().to_string();
+        let expires_at = now + MIGRATION_TOKEN_TTL_SECS;
+
+        let seed = SealedSeed {
+            id: id.clone(),
+            expires_at,
+            keys: keys.clone(),
+        };
+        let plaintext =
+            serde_json::to_vec(&seed).map_err(|e| MigrationError::Corrupted(e.to_string()))?;
+
+        let transport = GnsIdentity::generate();
+        let sealed = identity
+            .encrypt_for(&plaintext, &transport.encryption_public_key_bytes())
+            .map_err(|e| MigrationError::Corrupted(e.to_string()))?;
+
+        let token = Self {
+            version: MIGRATION_TOKEN_VERSION,
+            id,
+            public_key: identity.public_key_hex(),
+            encryption_key: identity.encryption_key_hex(),
+            expires_at,
+            sealed,
+        };
+        Ok((token, transport.private_key_hex()))
+    }
+
+    /// Encode as a `gns-migrate:` deep link
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        format!("{}{}", MIGRATION_TOKEN_PREFIX, URL_SAFE_NO_PAD.encode(json))
+    }
+
+    /// Parse a scanned deep link. Does not check expiry or decrypt.
+    pub fn decode(token: &str) -> Result<Self, MigrationError> {
+        let body = token
+            .trim()
+            .strip_prefix(MIGRATION_TOKEN_PREFIX)
+            .ok_or_else(|| MigrationError::Malformed("missing gns-migrate: prefix".to_string()))?;
+        let json = URL_SAFE_NO_PAD
+            .decode(body)
+            .map_err(|e| MigrationError::Malformed(e.to_string()))?;
+        let token: Self =
+            serde_json::from_slice(&json).map_err(|e| MigrationError::Malformed(e.to_string()))?;
+
+        if token.version != MIGRATION_TOKEN_VERSION {
+            return Err(MigrationError::Malformed(format!(
+                "unsupported token version {}",
+                token.version
+            )));
+        }
+        Ok(token)
+    }
+
+    /// Check the token is still valid at `now` and recover the identity's
+    /// keys with the transport key released by the old device
+    pub fn open(&self, transport_key: &str, now: i64) -> Result<IdentityKeys, MigrationError> {
+        if now >= self.expires_at {
+            return Err(MigrationError::Expired);
+        }
+
+        let transport = GnsIdentity::from_hex(transport_key)
+            .map_err(|e| MigrationError::Corrupted(e.to_string()))?;
+        let plaintext = transport
+            .decrypt(&self.sealed)
+            .map_err(|e| MigrationError::Corrupted(e.to_string()))?;
+        let seed: SealedSeed =
+            serde_json::from_slice(&plaintext).map_err(|e| MigrationError::Corrupted(e.to_string()))?;
+
+        if seed.id != self.id || seed.expires_at != self.expires_at {
+            return Err(MigrationError::Corrupted("token header was altered".to_string()));
+        }
+
+        let identity = seed.keys.identity().map_err(|e| MigrationError::Corrupted(e.to_string()))?;
+        if identity.public_key_hex() != self.public_key {
+            return Err(MigrationError::Corrupted("identity does not match token".to_string()));
+        }
+
+        Ok(seed.keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_round_trip() {
+        let identity = GnsIdentity::generate();
+        let (token, transport_key) = MigrationToken::create(&IdentityKeys::of(&identity, None), 1_000).unwrap();
+
+        let encoded = token.encode();
+        assert!(encoded.starts_with(MIGRATION_TOKEN_PREFIX));
+        let json = String::from_utf8(URL_SAFE_NO_PAD.decode(&encoded[MIGRATION_TOKEN_PREFIX.len()..]).unwrap()).unwrap();
+        assert!(!json.contains(&identity.private_key_hex()));
+        assert!(!json.contains(&transport_key));
+
+        let decoded = MigrationToken::decode(&encoded).unwrap();
+        let opened = decoded.open(&transport_key, 1_000).unwrap();
+        assert_eq!(opened.identity().unwrap().public_key_hex(), identity.public_key_hex());
+
+        // The token alone doesn't open
+        let guessed = GnsIdentity::generate().private_key_hex();
+        assert!(matches!(decoded.open(&guessed, 1_000), Err(MigrationError::Corrupted(_))));
+    }
+
+    #[test]
+    fn test_token_expiry() {
+        let (token, key) = MigrationToken::create(&IdentityKeys::of(&GnsIdentity::generate(), None), 1_000).unwrap();
+
+        assert!(token.open(&key, 1_000 + MIGRATION_TOKEN_TTL_SECS - 1).is_ok());
+        assert!(matches!(
+            token.open(&key, 1_000 + MIGRATION_TOKEN_TTL_SECS),
+            Err(MigrationError::Expired)
+        ));
+
+        // Pushing the visible expiry out doesn't revive it
+        let mut extended = token.clone();
+        extended.expires_at += 3600;
+        assert!(matches!(
+            extended.open(&key, 1_000 + MIGRATION_TOKEN_TTL_SECS),
+            Err(MigrationError::Corrupted(_))
+        ));
+    }
+
+    #[test]
+    fn test_token_carries_rotated_encryption_and_wallet_keys() {
+        let wallet = GnsIdentity::generate();
+        let mut identity = GnsIdentity::generate();
+        identity.rotate_encryption_key();
+        let keys = IdentityKeys::of(&identity, Some(&wallet));
+        assert!(keys.encryption_keys.is_some());
+
+        let (token, transport_key) = MigrationToken::create(&keys, 1_000).unwrap();
+        assert_eq!(token.encryption_key, identity.encryption_key_hex());
+
+        let opened = token.open(&transport_key, 1_000).unwrap();
+        assert_eq!(opened, keys);
+        let restored = opened.identity().unwrap();
+        assert_eq!(restored.encryption_key_hex(), identity.encryption_key_hex());
+        assert_eq!(restored.retired_encryption_secrets(), identity.retired_encryption_secrets());
+        assert_eq!(opened.wallet_key, Some(wallet.private_key_hex()));
+    }
+
+    #[test]
+    fn test_decode_rejects_garbage() {
+        assert!(matches!(MigrationToken::decode("gns-pay:abc"), Err(MigrationError::Malformed(_))));
+        assert!(matches!(MigrationToken::decode("gns-migrate:!!!"), Err(MigrationError::Malformed(_))));
+    }
+}
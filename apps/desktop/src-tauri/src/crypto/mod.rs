@@ -2,21 +2,131 @@
 //!
 //! Wraps the gns-crypto-core crate and provides keychain integration.
 
+pub mod compression;
+pub mod identity_card;
+pub mod key_rotation;
+pub mod migration;
 
-pub use gns_crypto_core::GnsIdentity;
+pub use gns_crypto_core::{Compression, GnsIdentity, MessageKeyCache, RatchetSessions, SignatureDomain};
+use compression::CompressionPeers;
+use key_rotation::RotationCertificate;
 use keyring::Entry;
+use std::sync::Arc;
+use std::time::Duration;
 
 const SERVICE_NAME: &str = "com.gcrumbs.browser";
 const IDENTITY_KEY: &str = "identity_private_key";
 const HANDLE_KEY: &str = "cached_handle";
+/// Explicit X25519 keys, present only once the encryption key was rotated
+const ENCRYPTION_KEYS_KEY: &str = "encryption_keys";
+/// Seed of the key the Stellar wallet is derived from, present only once
+/// the identity key was rotated away from it
+const WALLET_KEY: &str = "wallet_private_key";
+
+/// Keychain and backup form of a rotated encryption key and the ones it
+/// replaced, as hex X25519 secrets
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct StoredEncryptionKeys {
+    pub current: String,
+    /// Most recent first
+    pub retired: Vec<String>,
+}
+
+impl StoredEncryptionKeys {
+    fn from_identity(identity: &GnsIdentity) -> Self {
+        Self {
+            current: hex::encode(identity.encryption_secret_bytes()),
+            retired: identity.retired_encryption_secrets().iter().map(hex::encode).collect(),
+        }
+    }
+
+    /// Decode into (current, retired) secrets
+    fn secrets(&self) -> Result<([u8; 32], Vec<[u8; 32]>), IdentityError> {
+        let decode = |key: &str| -> Result<[u8; 32], IdentityError> {
+            hex::decode(key)
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(|| IdentityError::InvalidKey("Stored encryption key is malformed".to_string()))
+        };
+        let retired = self.retired.iter().map(|k| decode(k)).collect::<Result<Vec<_>, _>>()?;
+        Ok((decode(&self.current)?, retired))
+    }
+}
+
+/// Every secret needed to recreate an identity on another device, as
+/// carried by backups and migration tokens. The encryption and wallet keys
+/// are there only once they no longer follow from the signing seed.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct IdentityKeys {
+    pub private_key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption_keys: Option<StoredEncryptionKeys>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wallet_key: Option<String>,
+}
+
+impl IdentityKeys {
+    /// Keys of `identity`, with `wallet` if the wallet moved off it
+    pub fn of(identity: &GnsIdentity, wallet: Option<&GnsIdentity>) -> Self {
+        let derived = GnsIdentity::from_hex(&identity.private_key_hex())
+            .map(|seed| seed.encryption_secret_bytes())
+            .ok();
+        let explicit = !identity.retired_encryption_secrets().is_empty()
+            || derived != Some(identity.encryption_secret_bytes());
+        Self {
+            private_key: identity.private_key_hex(),
+            encryption_keys: explicit.then(|| StoredEncryptionKeys::from_identity(identity)),
+            wallet_key: wallet.map(|w| w.private_key_hex()),
+        }
+    }
+
+    /// The identity these keys make up, with its encryption keys applied
+    pub fn identity(&self) -> Result<GnsIdentity, IdentityError> {
+        let identity =
+            GnsIdentity::from_hex(&self.private_key).map_err(|e| IdentityError::InvalidKey(e.to_string()))?;
+        match &self.encryption_keys {
+            Some(keys) => {
+                let (current, retired) = keys.secrets()?;
+                Ok(identity.with_encryption_secret(current, retired))
+            }
+            None => Ok(identity),
+        }
+    }
+
+    fn wallet(&self) -> Result<Option<GnsIdentity>, IdentityError> {
+        self.wallet_key
+            .as_deref()
+            .map(|key| GnsIdentity::from_hex(key).map_err(|e| IdentityError::InvalidKey(e.to_string())))
+            .transpose()
+    }
+}
 
 /// Identity manager with keychain integration
 pub struct IdentityManager {
     /// Cached identity (loaded from keychain)
     identity: Option<GnsIdentity>,
+
+    /// The first identity key, once rotated away from; it still controls
+    /// the Stellar account derived from it
+    wallet: Option<GnsIdentity>,
     
     /// Cached handle
     cached_handle: Option<String>,
+
+    /// Derived per-peer message keys; memory only, emptied whenever the
+    /// identity or its encryption key changes
+    message_keys: Arc<MessageKeyCache>,
+
+    /// Forward secrecy sessions with peers, if turned on; emptied when
+    /// the identity changes
+    ratchet: Option<Arc<RatchetSessions>>,
+
+    /// Sign backend requests within their signature domain rather than
+    /// over the bare message
+    tagged_signatures: bool,
+
+    /// Peers that accept compressed envelopes, if compression is turned on
+    compression: Option<Arc<CompressionPeers>>,
 }
 
 impl IdentityManager {
@@ -24,15 +134,21 @@ impl IdentityManager {
     pub fn new() -> Result<Self, IdentityError> {
         let mut manager = Self {
             identity: None,
+            wallet: None,
             cached_handle: None,
+            message_keys: Arc::new(MessageKeyCache::default()),
+            ratchet: None,
+            tagged_signatures: false,
+            compression: None,
         };
         
         // Try to load existing identity from keychain
         if let Ok(private_key) = manager.load_from_keychain() {
             if let Ok(identity) = GnsIdentity::from_hex(&private_key) {
-                manager.identity = Some(identity);
+                manager.identity = Some(manager.apply_stored_encryption_keys(identity));
             }
         }
+        manager.wallet = load_wallet_key();
         
         // Load cached handle
         manager.cached_handle = manager.load_cached_handle().ok();
@@ -40,6 +156,80 @@ impl IdentityManager {
         Ok(manager)
     }
     
+    /// Create a manager around an in-memory identity, bypassing the keychain
+    #[cfg(test)]
+    pub fn from_identity(identity: GnsIdentity) -> Self {
+        Self {
+            identity: Some(identity),
+            wallet: None,
+            cached_handle: None,
+            message_keys: Arc::new(MessageKeyCache::default()),
+            ratchet: None,
+            tagged_signatures: false,
+            compression: None,
+        }
+    }
+
+    /// Reuse derived message keys for `ttl`; zero turns the cache off
+    pub fn with_message_key_ttl(mut self, ttl: Duration) -> Self {
+        self.message_keys = Arc::new(MessageKeyCache::new(ttl));
+        self
+    }
+
+    /// Cache of derived message keys for sending and decrypting
+    pub fn message_keys(&self) -> Arc<MessageKeyCache> {
+        self.message_keys.clone()
+    }
+
+    /// Turn forward secrecy on, starting from `sessions` saved for the
+    /// current identity
+    pub fn with_forward_secrecy(mut self, sessions: RatchetSessions) -> Self {
+        self.ratchet = Some(Arc::new(sessions));
+        self
+    }
+
+    /// Sign reservations, claims, releases, records and DIX content within
+    /// their signature domain once the backend verifies tagged signatures
+    pub fn with_tagged_signatures(mut self, tagged: bool) -> Self {
+        self.tagged_signatures = tagged;
+        self
+    }
+
+    /// Deflate large payloads for peers that accept compressed envelopes,
+    /// and tell every peer that we do
+    pub fn with_compression(mut self, enabled: bool) -> Self {
+        self.compression = enabled.then(|| Arc::new(CompressionPeers::default()));
+        self
+    }
+
+    /// Peers that accept compressed envelopes, if compression is on
+    pub fn compression_peers(&self) -> Option<Arc<CompressionPeers>> {
+        self.compression.clone()
+    }
+
+    /// Compression to seal envelopes for `public_key` with
+    pub fn compression_for(&self, public_key: &str) -> Compression {
+        self.compression
+            .as_ref()
+            .map_or(Compression::Off, |peers| peers.compression_for(public_key))
+    }
+
+    /// Ratchet sessions to send and decrypt with, if forward secrecy is on
+    pub fn ratchet_sessions(&self) -> Option<Arc<RatchetSessions>> {
+        self.ratchet.clone()
+    }
+
+    /// Forget every derived message key, e.g. when the app locks
+    pub fn clear_message_keys(&self) {
+        self.message_keys.clear();
+    }
+    
+    fn clear_ratchet_sessions(&self) {
+        if let Some(sessions) = &self.ratchet {
+            sessions.clear();
+        }
+    }
+
     /// Check if an identity exists
     pub fn has_identity(&self) -> bool {
         self.identity.is_some()
@@ -60,11 +250,104 @@ impl IdentityManager {
         self.public_key_hex()
     }
     
+    /// Key the Stellar wallet is derived from. Rotating the identity key
+    /// doesn't move the wallet, so this stays the first identity key.
+    pub fn wallet_identity(&self) -> Option<&GnsIdentity> {
+        self.wallet.as_ref().or(self.identity.as_ref())
+    }
+
+    /// Public key the Stellar wallet is derived from
+    pub fn wallet_public_key(&self) -> Option<String> {
+        self.wallet_identity().map(|i| i.public_key_hex())
+    }
+
+    /// Seed signing for the Stellar wallet (USE WITH CAUTION!)
+    pub fn wallet_private_key_bytes(&self) -> Option<Vec<u8>> {
+        self.wallet_identity().and_then(|i| hex::decode(i.private_key_hex()).ok())
+    }
+
     /// Get encryption key hex
     pub fn encryption_key_hex(&self) -> Option<String> {
         self.identity.as_ref().map(|i| i.encryption_key_hex())
     }
     
+    /// Replace the encryption key with a fresh random one, keeping the
+    /// old key for decrypting messages already sent to it. Returns the new
+    /// public encryption key; publish the record so senders pick it up.
+    pub fn rotate_encryption_key(&mut self) -> Result<String, IdentityError> {
+        let identity = self.identity.as_mut().ok_or(IdentityError::NoIdentity)?;
+        let previous = (
+            identity.encryption_secret_bytes(),
+            identity.retired_encryption_secrets().to_vec(),
+        );
+
+        identity.rotate_encryption_key();
+        self.message_keys.clear();
+        if let Err(e) = save_encryption_keys(&StoredEncryptionKeys::from_identity(identity)) {
+            // Not persisted, so don't use it: the next launch would lose it
+            let (current, retired) = previous;
+            self.identity = self.identity.take().map(|i| i.with_encryption_secret(current, retired));
+            return Err(e);
+        }
+
+        Ok(identity.encryption_key_hex())
+    }
+
+    /// Replace the Ed25519 identity key with a fresh one, keeping the
+    /// handle. The old key signs a certificate naming the new one, which
+    /// is returned for storing and publishing with the record. The
+    /// encryption key is replaced too; the old ones stay for decrypting
+    /// messages already sent to them.
+    ///
+    /// `record` must persist the certificate. It runs before the keychain
+    /// is touched, and if it fails nothing changes: once the old key is
+    /// gone, the certificate is the only thing linking it to the new one.
+    ///
+    /// The first key rotated away from is kept as the wallet key, since
+    /// the Stellar account and everything in it belong to that key.
+    pub fn rotate_key<E: std::fmt::Display>(
+        &mut self,
+        record: impl FnOnce(&RotationCertificate) -> Result<(), E>,
+    ) -> Result<RotationCertificate, IdentityError> {
+        let previous = self.identity.as_ref().ok_or(IdentityError::NoIdentity)?;
+        let mut retired = vec![previous.encryption_secret_bytes()];
+        retired.extend_from_slice(previous.retired_encryption_secrets());
+        let fresh = GnsIdentity::generate();
+        let encryption_secret = fresh.encryption_secret_bytes();
+        let next = fresh.with_encryption_secret(encryption_secret, retired);
+
+        let certificate =
+            RotationCertificate::create(previous, &next.public_key_hex(), chrono::Utc::now().timestamp_millis());
+        record(&certificate).map_err(|e| IdentityError::RotationNotRecorded(e.to_string()))?;
+
+        let wallet = match &self.wallet {
+            Some(_) => None,
+            None => {
+                save_wallet_key(&previous.private_key_hex())?;
+                GnsIdentity::from_hex(&previous.private_key_hex()).ok()
+            }
+        };
+
+        // Failing from here leaves a recorded successor that never took
+        // over; the next rotation's certificate replaces it
+        self.save_to_keychain(&next.private_key_hex())?;
+        if let Err(e) = save_encryption_keys(&StoredEncryptionKeys::from_identity(&next)) {
+            // The new key alone would lose the old encryption keys
+            if let Err(restore) = self.save_to_keychain(&previous.private_key_hex()) {
+                tracing::error!("Failed to restore the previous identity key: {}", restore);
+            }
+            return Err(e);
+        }
+
+        self.identity = Some(next);
+        if wallet.is_some() {
+            self.wallet = wallet;
+        }
+        self.message_keys.clear();
+        self.clear_ratchet_sessions();
+        Ok(certificate)
+    }
+
     /// Get private key hex (USE WITH CAUTION!)
     pub fn private_key_hex(&self) -> Option<String> {
         self.identity.as_ref().map(|i| i.private_key_hex())
@@ -87,6 +370,23 @@ impl IdentityManager {
         })
     }
     
+    /// Sign a message for the backend and return hex signature. It is
+    /// signed within `domain` if tagged signatures are on, bare otherwise.
+    pub fn sign_for_backend(&self, domain: SignatureDomain, message: &str) -> Option<String> {
+        self.identity
+            .as_ref()
+            .map(|i| hex::encode(i.sign_bytes(&self.backend_signed_bytes(domain, message))))
+    }
+
+    /// The exact bytes `sign_for_backend` signs for `message`
+    pub fn backend_signed_bytes(&self, domain: SignatureDomain, message: &str) -> Vec<u8> {
+        if self.tagged_signatures {
+            domain.tag(message.as_bytes())
+        } else {
+            message.as_bytes().to_vec()
+        }
+    }
+    
     /// Get cached handle
     pub fn cached_handle(&self) -> Option<String> {
         self.cached_handle.clone()
@@ -110,27 +410,65 @@ impl IdentityManager {
         
         // Save to keychain
         self.save_to_keychain(&private_key_hex)?;
+        let _ = delete_encryption_keys();
+        let _ = delete_wallet_key();
         
         self.identity = Some(identity);
+        self.wallet = None;
         self.cached_handle = None;
+        self.message_keys.clear();
+        self.clear_ratchet_sessions();
         
         Ok(())
     }
     
     /// Import identity from hex private key
     pub fn import_from_hex(&mut self, private_key_hex: &str) -> Result<(), IdentityError> {
-        let identity = GnsIdentity::from_hex(private_key_hex)
-            .map_err(|e| IdentityError::InvalidKey(e.to_string()))?;
-        
+        self.import_keys(&IdentityKeys {
+            private_key: private_key_hex.to_string(),
+            encryption_keys: None,
+            wallet_key: None,
+        })
+    }
+
+    /// Import an identity along with its rotated encryption keys and
+    /// wallet key, if it has any
+    pub fn import_keys(&mut self, keys: &IdentityKeys) -> Result<(), IdentityError> {
+        let identity = keys.identity()?;
+        let wallet = keys.wallet()?;
+
         // Save to keychain
-        self.save_to_keychain(private_key_hex)?;
-        
+        self.save_to_keychain(&keys.private_key)?;
+        match &keys.encryption_keys {
+            Some(encryption_keys) => save_encryption_keys(encryption_keys)?,
+            None => {
+                let _ = delete_encryption_keys();
+            }
+        }
+        match &keys.wallet_key {
+            Some(wallet_key) => save_wallet_key(wallet_key)?,
+            None => {
+                let _ = delete_wallet_key();
+            }
+        }
+
         self.identity = Some(identity);
+        self.wallet = wallet;
         self.cached_handle = None;
-        
+        self.message_keys.clear();
+        self.clear_ratchet_sessions();
+
         Ok(())
     }
-    
+
+    /// Every secret of the identity, for a backup or migration token
+    /// (USE WITH CAUTION!)
+    pub fn export_keys(&self) -> Option<IdentityKeys> {
+        self.identity
+            .as_ref()
+            .map(|identity| IdentityKeys::of(identity, self.wallet.as_ref()))
+    }
+
     // ==================== Keychain Operations ====================
     
     fn load_from_keychain(&self) -> Result<String, IdentityError> {
@@ -149,6 +487,25 @@ impl IdentityManager {
             .map_err(|e| IdentityError::KeychainError(e.to_string()))
     }
     
+    /// Apply a rotated encryption key from the keychain, if there is one
+    fn apply_stored_encryption_keys(&self, identity: GnsIdentity) -> GnsIdentity {
+        let stored = Entry::new(SERVICE_NAME, ENCRYPTION_KEYS_KEY)
+            .and_then(|entry| entry.get_password())
+            .ok()
+            .and_then(|json| serde_json::from_str::<StoredEncryptionKeys>(&json).ok());
+        let Some(stored) = stored else {
+            return identity;
+        };
+
+        match stored.secrets() {
+            Ok((current, retired)) => identity.with_encryption_secret(current, retired),
+            Err(e) => {
+                tracing::error!("Ignoring stored encryption keys: {}", e);
+                identity
+            }
+        }
+    }
+    
     fn load_cached_handle(&self) -> Result<String, IdentityError> {
         let entry = Entry::new(SERVICE_NAME, HANDLE_KEY)
             .map_err(|e| IdentityError::KeychainError(e.to_string()))?;
@@ -180,15 +537,56 @@ impl IdentityManager {
         
         // Best effort deletion
         let _ = entry.delete_password();
+        let _ = delete_encryption_keys();
+        let _ = delete_wallet_key();
         let _ = self.clear_cached_handle();
         
         self.identity = None;
+        self.wallet = None;
         self.cached_handle = None;
+        self.message_keys.clear();
+        self.clear_ratchet_sessions();
         
         Ok(())
     }
 }
 
+fn save_encryption_keys(keys: &StoredEncryptionKeys) -> Result<(), IdentityError> {
+    let json = serde_json::to_string(keys).map_err(|e| IdentityError::KeychainError(e.to_string()))?;
+    Entry::new(SERVICE_NAME, ENCRYPTION_KEYS_KEY)
+        .and_then(|entry| entry.set_password(&json))
+        .map_err(|e| IdentityError::KeychainError(e.to_string()))
+}
+
+fn delete_encryption_keys() -> Result<(), IdentityError> {
+    Entry::new(SERVICE_NAME, ENCRYPTION_KEYS_KEY)
+        .and_then(|entry| entry.delete_password())
+        .map_err(|e| IdentityError::KeychainError(e.to_string()))
+}
+
+fn load_wallet_key() -> Option<GnsIdentity> {
+    let seed = Entry::new(SERVICE_NAME, WALLET_KEY).and_then(|entry| entry.get_password()).ok()?;
+    match GnsIdentity::from_hex(&seed) {
+        Ok(wallet) => Some(wallet),
+        Err(e) => {
+            tracing::error!("Ignoring stored wallet key: {}", e);
+            None
+        }
+    }
+}
+
+fn save_wallet_key(private_key_hex: &str) -> Result<(), IdentityError> {
+    Entry::new(SERVICE_NAME, WALLET_KEY)
+        .and_then(|entry| entry.set_password(private_key_hex))
+        .map_err(|e| IdentityError::KeychainError(e.to_string()))
+}
+
+fn delete_wallet_key() -> Result<(), IdentityError> {
+    Entry::new(SERVICE_NAME, WALLET_KEY)
+        .and_then(|entry| entry.delete_password())
+        .map_err(|e| IdentityError::KeychainError(e.to_string()))
+}
+
 /// Identity manager errors
 #[derive(Debug, thiserror::Error)]
 pub enum IdentityError {
@@ -200,4 +598,7 @@ pub enum IdentityError {
     
     #[error("No identity configured")]
     NoIdentity,
+
+    #[error("Rotation certificate could not be saved: {0}")]
+    RotationNotRecorded(String),
 }
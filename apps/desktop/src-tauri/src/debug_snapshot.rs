@@ -0,0 +1,365 @@
+//! Debug Snapshots
+//!
+//! A JSON picture of the app's state for reproducing a user's bug: config,
+//! schema version, public keys, message counts, relay status, the latest
+//! audit entries and recent warnings and errors. It is built so it can be
+//! attached to a support ticket as is. Private keys, message bodies,
+//! thread ids and locations are never read into it, and free text (audit
+//! results, log messages) goes through [`redact`].
+//!
+//! A dev build can load a snapshot into an empty database, which gets
+//! threads of the same sizes filled with placeholder messages from
+//! made-up peers.
+
+use crate::config::DesktopConfig;
+use crate::diagnostics::redact;
+use crate::storage::{Database, ThreadCounts};
+use gns_crypto_core::GnsIdentity;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Format of [`DebugSnapshot`]; bump when a field changes meaning
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Audit entries a snapshot carries
+pub const SNAPSHOT_AUDIT_ENTRIES: u32 = 50;
+
+/// Warnings and errors [`ErrorLog`] keeps
+const RECENT_ERRORS: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DebugSnapshot {
+    pub snapshot_version: u32,
+    pub generated_at: i64,
+    pub app_version: String,
+    pub platform: String,
+    pub schema_version: u32,
+    pub config: DesktopConfig,
+    pub identity: Option<IdentitySummary>,
+    /// Per thread, most recently active first
+    pub threads: Vec<ThreadCounts>,
+    pub message_count: u32,
+    pub pending_messages: u32,
+    pub breadcrumb_count: u32,
+    pub connectivity: ConnectivitySummary,
+    /// Newest first
+    pub audit: Vec<AuditSummary>,
+    /// Oldest first
+    pub recent_errors: Vec<ErrorEvent>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdentitySummary {
+    pub public_key: String,
+    pub encryption_key: String,
+    pub handle: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectivitySummary {
+    pub online: bool,
+    pub relay_url: String,
+    pub relay_connected: bool,
+    pub relay_ready: bool,
+    pub reconnect_attempts: u32,
+    pub last_relay_message_at: Option<i64>,
+}
+
+/// An audit entry without its chain hashes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditSummary {
+    pub seq: i64,
+    pub timestamp: i64,
+    pub action: String,
+    pub target: Option<String>,
+    pub result: String,
+}
+
+/// A warning or error that was logged
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorEvent {
+    pub timestamp: i64,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+}
+
+/// What loading a snapshot created
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotLoadReport {
+    pub threads: usize,
+    pub messages: u32,
+}
+
+/// Keeps the latest warnings and errors logged through `tracing`, already
+/// redacted. Installed as a layer at startup and shared with `AppState`.
+#[derive(Clone, Default)]
+pub struct ErrorLog {
+    events: Arc<Mutex<VecDeque<ErrorEvent>>>,
+}
+
+impl ErrorLog {
+    pub fn recent(&self) -> Vec<ErrorEvent> {
+        self.events.lock().unwrap().iter().cloned().collect()
+    }
+
+    fn push(&self, event: ErrorEvent) {
+        let mut events = self.events.lock().unwrap();
+        if events.len() == RECENT_ERRORS {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+}
+
+impl<S: Subscriber> Layer<S> for ErrorLog {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if *metadata.level() > Level::WARN {
+            return;
+        }
+
+        let mut message = MessageVisitor(String::new());
+        event.record(&mut message);
+        self.push(ErrorEvent {
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            level: metadata.level().to_string(),
+            target: metadata.target().to_string(),
+            message: redact(&message.0),
+        });
+    }
+}
+
+/// Writes an event's message followed by its other fields
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// The parts of a snapshot that come from the database
+pub struct StoredState {
+    pub schema_version: u32,
+    pub threads: Vec<ThreadCounts>,
+    pub pending_messages: u32,
+    pub breadcrumb_count: u32,
+    pub audit: Vec<AuditSummary>,
+}
+
+impl StoredState {
+    pub fn read(db: &Database) -> Result<Self, String> {
+        let audit = db
+            .get_audit_log(SNAPSHOT_AUDIT_ENTRIES, None)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|entry| AuditSummary {
+                seq: entry.seq,
+                timestamp: entry.timestamp,
+                action: entry.action,
+                target: entry.target.as_deref().map(redact),
+                result: redact(&entry.result),
+            })
+            .collect();
+
+        Ok(Self {
+            schema_version: db.schema_version().map_err(|e| e.to_string())?,
+            threads: db.thread_counts().map_err(|e| e.to_string())?,
+            pending_messages: db.count_pending_messages().map_err(|e| e.to_string())?,
+            breadcrumb_count: db.count_breadcrumbs().map_err(|e| e.to_string())?,
+            audit,
+        })
+    }
+}
+
+impl DebugSnapshot {
+    pub fn new(
+        config: DesktopConfig,
+        identity: Option<IdentitySummary>,
+        stored: StoredState,
+        connectivity: ConnectivitySummary,
+        recent_errors: Vec<ErrorEvent>,
+    ) -> Self {
+        Self {
+            snapshot_version: SNAPSHOT_VERSION,
+            generated_at: chrono::Utc::now().timestamp_millis(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            platform: format!("{}/{}", std::env::consts::OS, std::env::consts::ARCH),
+            schema_version: stored.schema_version,
+            config,
+            identity,
+            message_count: stored.threads.iter().map(|t| t.messages).sum(),
+            threads: stored.threads,
+            pending_messages: stored.pending_messages,
+            breadcrumb_count: stored.breadcrumb_count,
+            connectivity,
+            audit: stored.audit,
+            recent_errors,
+        }
+    }
+
+    /// Seed `db` with threads shaped like the snapshot's, with placeholder
+    /// messages from freshly generated peers. `db` must have no threads, so
+    /// reproduction data never mixes with real conversations.
+    pub fn load_into(&self, db: &mut Database) -> Result<SnapshotLoadReport, String> {
+        if self.snapshot_version > SNAPSHOT_VERSION {
+            return Err(format!("Snapshot version {} is newer than this build", self.snapshot_version));
+        }
+        if !db.thread_counts().map_err(|e| e.to_string())?.is_empty() {
+            return Err("Load snapshots into a profile without conversations".to_string());
+        }
+
+        let own_public_key = GnsIdentity::generate().public_key_hex();
+        let now = chrono::Utc::now().timestamp_millis();
+        for (i, counts) in self.threads.iter().enumerate() {
+            // Keep the snapshot's order: most recently active first
+            let at = now - i as i64 * 60_000;
+            let thread_id = format!("snapshot-{}", uuid::Uuid::new_v4());
+            let peer = GnsIdentity::generate().public_key_hex();
+            db.seed_thread(&thread_id, &peer, &own_public_key, *counts, at)
+                .map_err(|e| e.to_string())?;
+        }
+
+        tracing::info!("🧪 Loaded snapshot: {} threads, {} messages", self.threads.len(), self.message_count);
+        Ok(SnapshotLoadReport {
+            threads: self.threads.len(),
+            messages: self.threads.iter().map(|t| t.messages).sum(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::AuditAction;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Runs of 32+ hex digits in `text`
+    fn hex_runs(text: &str) -> Vec<String> {
+        text.split(|c: char| !c.is_ascii_hexdigit())
+            .filter(|run| run.len() >= 32)
+            .map(String::from)
+            .collect()
+    }
+
+    fn populated_database() -> Database {
+        let mut db = Database::open_in_memory().unwrap();
+        let peer = GnsIdentity::generate();
+        for i in 0..3 {
+            db.save_received_message(
+                &format!("in-{}", i),
+                "thread-with-peer",
+                &peer.public_key_hex(),
+                None,
+                "text",
+                &serde_json::json!({ "text": "meet at the usual place" }),
+                1_700_000_000_000 + i,
+                true,
+                None,
+            )
+            .unwrap();
+        }
+        db
+    }
+
+    #[test]
+    fn test_snapshot_contains_no_secret_keys() {
+        let identity = GnsIdentity::generate();
+        let secrets = [identity.private_key_hex(), hex::encode(identity.encryption_secret_bytes())];
+        let mut db = populated_database();
+        db.record_audit(
+            AuditAction::ExportIdentity,
+            Some(&identity.public_key_hex()),
+            &format!("refused to export {}", secrets[0]),
+        )
+        .unwrap();
+
+        // A warning that quotes a secret key is captured, redacted
+        let errors = ErrorLog::default();
+        let subscriber = tracing_subscriber::registry().with(errors.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("not kept");
+            tracing::warn!(key = %secrets[1], "Decryption failed with {}", secrets[1]);
+        });
+        assert_eq!(errors.recent().len(), 1);
+
+        let identity_summary = IdentitySummary {
+            public_key: identity.public_key_hex(),
+            encryption_key: identity.encryption_key_hex(),
+            handle: Some("alice".to_string()),
+        };
+        let connectivity = ConnectivitySummary {
+            online: true,
+            relay_url: "wss://relay.example".to_string(),
+            relay_connected: true,
+            relay_ready: true,
+            reconnect_attempts: 0,
+            last_relay_message_at: None,
+        };
+        let snapshot = DebugSnapshot::new(
+            DesktopConfig::default(),
+            Some(identity_summary),
+            StoredState::read(&db).unwrap(),
+            connectivity,
+            errors.recent(),
+        );
+        assert_eq!(snapshot.schema_version, crate::storage::SCHEMA_VERSION);
+        assert_eq!(snapshot.message_count, 3);
+        assert_eq!(snapshot.threads, [ThreadCounts { messages: 3, outgoing: 0, unread: 3 }]);
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        for secret in &secrets {
+            assert!(!json.contains(secret.as_str()));
+            assert!(!json.contains(&secret[..32]));
+        }
+        assert!(!json.contains("usual place"));
+        assert!(!json.contains("thread-with-peer"));
+        // The only full keys are the public ones
+        let public = [identity.public_key_hex(), identity.encryption_key_hex()];
+        for run in hex_runs(&json) {
+            assert!(public.contains(&run), "unexpected key-shaped string {}", run);
+        }
+    }
+
+    #[test]
+    fn test_load_reconstructs_counts() {
+        let source = populated_database();
+        let connectivity = ConnectivitySummary {
+            online: false,
+            relay_url: String::new(),
+            relay_connected: false,
+            relay_ready: false,
+            reconnect_attempts: 4,
+            last_relay_message_at: None,
+        };
+        let mut snapshot = DebugSnapshot::new(
+            DesktopConfig::default(),
+            None,
+            StoredState::read(&source).unwrap(),
+            connectivity,
+            Vec::new(),
+        );
+        snapshot.threads.push(ThreadCounts { messages: 5, outgoing: 2, unread: 1 });
+        snapshot.threads.push(ThreadCounts { messages: 0, outgoing: 0, unread: 0 });
+
+        // Through JSON, as support would pass it around
+        let snapshot: DebugSnapshot = serde_json::from_str(&serde_json::to_string(&snapshot).unwrap()).unwrap();
+        let mut db = Database::open_in_memory().unwrap();
+        let report = snapshot.load_into(&mut db).unwrap();
+        assert_eq!((report.threads, report.messages), (3, 8));
+        assert_eq!(db.thread_counts().unwrap(), snapshot.threads);
+
+        // Never on top of real conversations
+        assert!(snapshot.load_into(&mut db).is_err());
+    }
+}
@@ -0,0 +1,425 @@
+//! Devices - where an identity is active
+//!
+//! Every install that holds the identity key registers itself in the
+//! `devices` section of the signed identity record: a signed entry with
+//! its name and when it was first and last seen. Revoking a device adds a
+//! signed revocation next to the entries. Revocations are never dropped
+//! when records are merged, so once one is published every client treats
+//! that device as inactive, and the revoked install refuses to publish
+//! for the identity again.
+//!
+//! All devices share the identity key, so a revocation can't stop a
+//! determined holder of the key - it marks the install as one the owner
+//! no longer stands behind. Moving off a compromised device still means
+//! moving to a new identity.
+
+use crate::commands::handles::canonical_json;
+use crate::crypto::{GnsIdentity, SignatureDomain};
+use crate::profile::verify_record;
+use crate::storage::Database;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+
+/// Setting holding this install's device id
+pub const DEVICE_ID_SETTING: &str = "device_id";
+
+/// Setting holding the name this install registers with
+pub const DEVICE_NAME_SETTING: &str = "device_name";
+
+pub const MAX_DEVICE_NAME_CHARS: usize = 64;
+
+/// A device registered for an identity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceEntry {
+    pub identity: String,
+    pub device_id: String,
+    pub name: String,
+    /// Milliseconds since the epoch
+    pub created_at: i64,
+    /// Milliseconds since the epoch
+    pub last_seen: i64,
+    pub signature: String,
+}
+
+impl DeviceEntry {
+    /// Create and sign an entry as `identity`
+    pub fn sign(
+        identity: &GnsIdentity,
+        device_id: &str,
+        name: &str,
+        created_at: i64,
+        last_seen: i64,
+    ) -> Self {
+        let mut entry = Self {
+            identity: identity.public_key_hex(),
+            device_id: device_id.to_string(),
+            name: name.to_string(),
+            created_at,
+            last_seen,
+            signature: String::new(),
+        };
+        entry.signature = sign_message(identity, &entry.canonical_message());
+        entry
+    }
+
+    /// The exact string that is signed (keys sorted, signature excluded)
+    pub fn canonical_message(&self) -> String {
+        canonical_json(&json!({
+            "created_at": self.created_at,
+            "device_id": self.device_id,
+            "identity": self.identity,
+            "last_seen": self.last_seen,
+            "name": self.name,
+            "type": "device",
+        }))
+    }
+
+    pub fn verify(&self) -> bool {
+        verify_message(&self.identity, &self.canonical_message(), &self.signature)
+    }
+}
+
+/// A signed statement that a device no longer acts for the identity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceRevocation {
+    pub identity: String,
+    pub device_id: String,
+    /// Milliseconds since the epoch
+    pub revoked_at: i64,
+    pub signature: String,
+}
+
+impl DeviceRevocation {
+    /// Create and sign a revocation as `identity`
+    pub fn sign(identity: &GnsIdentity, device_id: &str, revoked_at: i64) -> Self {
+        let mut revocation = Self {
+            identity: identity.public_key_hex(),
+            device_id: device_id.to_string(),
+            revoked_at,
+            signature: String::new(),
+        };
+        revocation.signature = sign_message(identity, &revocation.canonical_message());
+        revocation
+    }
+
+    /// The exact string that is signed (keys sorted, signature excluded)
+    pub fn canonical_message(&self) -> String {
+        canonical_json(&json!({
+            "device_id": self.device_id,
+            "identity": self.identity,
+            "revoked_at": self.revoked_at,
+            "type": "device_revocation",
+        }))
+    }
+
+    pub fn verify(&self) -> bool {
+        verify_message(&self.identity, &self.canonical_message(), &self.signature)
+    }
+}
+
+/// The `devices` section of a signed identity record
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeviceList {
+    #[serde(default)]
+    pub devices: Vec<DeviceEntry>,
+    #[serde(default)]
+    pub revocations: Vec<DeviceRevocation>,
+}
+
+/// A device as shown to the user
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceStatus {
+    pub device_id: String,
+    pub name: String,
+    pub created_at: i64,
+    pub last_seen: i64,
+    pub active: bool,
+    pub revoked_at: Option<i64>,
+    /// Whether this is the install asking
+    pub is_current: bool,
+}
+
+impl DeviceList {
+    /// Extract the device list from a signed identity record.
+    ///
+    /// The record signature must verify first. Entries and revocations
+    /// are then checked one by one and any that aren't signed by
+    /// `public_key` are dropped, so a record without a `devices` section
+    /// (or with a forged one) yields an empty list.
+    pub fn from_signed_record(
+        public_key: &str,
+        record_json: &serde_json::Value,
+        signature: &str,
+    ) -> Result<Self, String> {
+        verify_record(public_key, record_json, signature)?;
+
+        let list: DeviceList = match record_json.get("devices") {
+            None | Some(serde_json::Value::Null) => return Ok(Self::default()),
+            Some(value) => serde_json::from_value(value.clone())
+                .map_err(|e| format!("Malformed device list: {}", e))?,
+        };
+
+        let owned = |identity: &str| identity.eq_ignore_ascii_case(public_key);
+        let mut verified = Self::default();
+        verified.merge(Self {
+            devices: list.devices.into_iter().filter(|d| owned(&d.identity) && d.verify()).collect(),
+            revocations: list
+                .revocations
+                .into_iter()
+                .filter(|r| owned(&r.identity) && r.verify())
+                .collect(),
+        });
+        Ok(verified)
+    }
+
+    /// Fold `other` in: the most recently seen entry per device wins and
+    /// every revocation is kept
+    pub fn merge(&mut self, other: DeviceList) {
+        for entry in other.devices {
+            match self.devices.iter_mut().find(|d| d.device_id == entry.device_id) {
+                Some(existing) if existing.last_seen >= entry.last_seen => {}
+                Some(existing) => *existing = entry,
+                None => self.devices.push(entry),
+            }
+        }
+        for revocation in other.revocations {
+            match self.revocations.iter_mut().find(|r| r.device_id == revocation.device_id) {
+                // The earliest revocation stands
+                Some(existing) if existing.revoked_at <= revocation.revoked_at => {}
+                Some(existing) => *existing = revocation,
+                None => self.revocations.push(revocation),
+            }
+        }
+    }
+
+    pub fn entry(&self, device_id: &str) -> Option<&DeviceEntry> {
+        self.devices.iter().find(|d| d.device_id == device_id)
+    }
+
+    /// When `device_id` was revoked, if it was
+    pub fn revoked_at(&self, device_id: &str) -> Option<i64> {
+        self.revocations
+            .iter()
+            .find(|r| r.device_id == device_id)
+            .map(|r| r.revoked_at)
+    }
+
+    pub fn is_active(&self, device_id: &str) -> bool {
+        self.entry(device_id).is_some() && self.revoked_at(device_id).is_none()
+    }
+
+    /// Register or refresh `device_id` as seen at `now`, keeping the name
+    /// and creation time it already has
+    pub fn touch(&mut self, identity: &GnsIdentity, device_id: &str, default_name: &str, now: i64) {
+        let (name, created_at) = match self.entry(device_id) {
+            Some(existing) => (existing.name.clone(), existing.created_at),
+            None => (default_name.to_string(), now),
+        };
+        self.upsert(DeviceEntry::sign(identity, device_id, &name, created_at, now));
+    }
+
+    /// Give a registered device a new name
+    pub fn rename(&mut self, identity: &GnsIdentity, device_id: &str, name: &str, now: i64) -> Result<(), String> {
+        let name = clean_device_name(name)?;
+        let existing = self.entry(device_id).ok_or_else(|| format!("Unknown device {}", device_id))?;
+        let entry = DeviceEntry::sign(identity, device_id, &name, existing.created_at, existing.last_seen.max(now));
+        self.upsert(entry);
+        Ok(())
+    }
+
+    /// Revoke a registered device. Revoking it again keeps the first
+    /// revocation.
+    pub fn revoke(&mut self, identity: &GnsIdentity, device_id: &str, now: i64) -> Result<(), String> {
+        if self.entry(device_id).is_none() {
+            return Err(format!("Unknown device {}", device_id));
+        }
+        self.merge(Self {
+            devices: vec![],
+            revocations: vec![DeviceRevocation::sign(identity, device_id, now)],
+        });
+        Ok(())
+    }
+
+    /// Devices for display, most recently seen first
+    pub fn statuses(&self, current_device_id: &str) -> Vec<DeviceStatus> {
+        let revoked: HashMap<&str, i64> = self
+            .revocations
+            .iter()
+            .map(|r| (r.device_id.as_str(), r.revoked_at))
+            .collect();
+
+        let mut statuses: Vec<DeviceStatus> = self
+            .devices
+            .iter()
+            .map(|d| DeviceStatus {
+                device_id: d.device_id.clone(),
+                name: d.name.clone(),
+                created_at: d.created_at,
+                last_seen: d.last_seen,
+                active: !revoked.contains_key(d.device_id.as_str()),
+                revoked_at: revoked.get(d.device_id.as_str()).copied(),
+                is_current: d.device_id == current_device_id,
+            })
+            .collect();
+        statuses.sort_by_key(|s| std::cmp::Reverse(s.last_seen));
+        statuses
+    }
+
+    pub fn to_value(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+
+    fn upsert(&mut self, entry: DeviceEntry) {
+        match self.devices.iter_mut().find(|d| d.device_id == entry.device_id) {
+            Some(existing) => *existing = entry,
+            None => self.devices.push(entry),
+        }
+    }
+}
+
+/// This install's device id, created on first use
+pub fn local_device_id(db: &mut Database) -> Result<String, String> {
+    if let Some(id) = db.get_setting(DEVICE_ID_SETTING) {
+        return Ok(id);
+    }
+    let id = uuid::Uuid::new_v4().to_string();
+    db.set_setting(DEVICE_ID_SETTING, &id).map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+/// The name this install registers with until it is renamed
+pub fn local_device_name(db: &Database) -> String {
+    db.get_setting(DEVICE_NAME_SETTING)
+        .unwrap_or_else(|| format!("GNS Desktop ({})", std::env::consts::OS))
+}
+
+/// Strip control characters and enforce the length limit
+pub fn clean_device_name(name: &str) -> Result<String, String> {
+    let cleaned: String = name.chars().filter(|c| !c.is_control()).collect();
+    let cleaned = cleaned.trim().to_string();
+    if cleaned.is_empty() {
+        return Err("Device name can't be empty".to_string());
+    }
+    if cleaned.chars().count() > MAX_DEVICE_NAME_CHARS {
+        return Err(format!("Device name exceeds {} characters", MAX_DEVICE_NAME_CHARS));
+    }
+    Ok(cleaned)
+}
+
+fn sign_message(identity: &GnsIdentity, message: &str) -> String {
+    hex::encode(identity.sign_in_domain(SignatureDomain::Record, message.as_bytes()))
+}
+
+fn verify_message(public_key: &str, message: &str, signature: &str) -> bool {
+    gns_crypto_core::verify_in_domain_hex(
+        public_key,
+        SignatureDomain::Record,
+        message.as_bytes(),
+        signature,
+        gns_crypto_core::DomainPolicy::Strict,
+    )
+    .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000_000;
+
+    /// A record carrying `devices`, signed the way `publish_identity_record` does
+    fn signed_record(identity: &GnsIdentity, devices: &DeviceList) -> (serde_json::Value, String) {
+        let record = json!({
+            "identity": identity.public_key_hex(),
+            "version": 1,
+            "devices": devices.to_value(),
+        });
+        let signature = sign_message(identity, &canonical_json(&record));
+        (record, signature)
+    }
+
+    #[test]
+    fn test_revocation_canonical_signing() {
+        let identity = GnsIdentity::generate();
+        let revocation = DeviceRevocation::sign(&identity, "laptop", NOW);
+
+        assert_eq!(
+            revocation.canonical_message(),
+            format!(
+                r#"{{"device_id":"laptop","identity":"{}","revoked_at":{},"type":"device_revocation"}}"#,
+                identity.public_key_hex(),
+                NOW
+            )
+        );
+        assert!(revocation.verify());
+
+        // Backdating or retargeting breaks the signature
+        let mut backdated = revocation.clone();
+        backdated.revoked_at -= 1;
+        assert!(!backdated.verify());
+        let mut retargeted = revocation.clone();
+        retargeted.device_id = "phone".to_string();
+        assert!(!retargeted.verify());
+    }
+
+    #[test]
+    fn test_revoked_device_is_inactive_on_resolve() {
+        let identity = GnsIdentity::generate();
+        let public_key = identity.public_key_hex();
+
+        let mut devices = DeviceList::default();
+        devices.touch(&identity, "laptop", "Laptop", NOW);
+        devices.touch(&identity, "phone", "Phone", NOW + 1);
+        devices.revoke(&identity, "phone", NOW + 2).unwrap();
+
+        let (record, signature) = signed_record(&identity, &devices);
+        let resolved = DeviceList::from_signed_record(&public_key, &record, &signature).unwrap();
+
+        assert!(resolved.is_active("laptop"));
+        assert!(!resolved.is_active("phone"));
+        let statuses = resolved.statuses("laptop");
+        assert_eq!(statuses.len(), 2);
+        assert!(!statuses[0].active && statuses[0].revoked_at == Some(NOW + 2));
+        assert!(statuses[1].active && statuses[1].is_current);
+
+        // A later check-in from the revoked device doesn't reactivate it
+        let mut stale = DeviceList::default();
+        stale.touch(&identity, "phone", "Phone", NOW + 10);
+        let mut merged = resolved.clone();
+        merged.merge(stale);
+        assert!(!merged.is_active("phone"));
+        assert_eq!(merged.entry("phone").unwrap().last_seen, NOW + 10);
+
+        // A revocation signed by someone else is dropped on resolve
+        let mallory = GnsIdentity::generate();
+        let mut forged = DeviceList::default();
+        forged.touch(&identity, "laptop", "Laptop", NOW);
+        forged.revocations.push(DeviceRevocation {
+            identity: public_key.clone(),
+            ..DeviceRevocation::sign(&mallory, "laptop", NOW)
+        });
+        let (record, signature) = signed_record(&identity, &forged);
+        let resolved = DeviceList::from_signed_record(&public_key, &record, &signature).unwrap();
+        assert!(resolved.is_active("laptop"));
+        assert!(resolved.revocations.is_empty());
+    }
+
+    #[test]
+    fn test_rename_keeps_registration() {
+        let identity = GnsIdentity::generate();
+        let mut devices = DeviceList::default();
+        devices.touch(&identity, "laptop", "Laptop", NOW);
+        devices.touch(&identity, "laptop", "Ignored", NOW + 5);
+
+        devices.rename(&identity, "laptop", "  Work\u{0007} laptop ", NOW + 6).unwrap();
+        let entry = devices.entry("laptop").unwrap();
+        assert_eq!(entry.name, "Work laptop");
+        assert_eq!((entry.created_at, entry.last_seen), (NOW, NOW + 6));
+        assert!(entry.verify());
+
+        assert!(devices.rename(&identity, "laptop", " ", NOW).is_err());
+        assert!(devices.rename(&identity, "tablet", "Tablet", NOW).is_err());
+        assert!(devices.revoke(&identity, "tablet", NOW).is_err());
+    }
+}
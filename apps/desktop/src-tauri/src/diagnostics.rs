@@ -0,0 +1,283 @@
+//! Self-Test Diagnostics
+//!
+//! Runs a fixed set of checks, each timed and reported pass/fail, so an
+//! "it's not working" report can say which part isn't. The report is meant
+//! to be pasted into bug reports: no check puts key material in its
+//! detail, and anything shaped like a key is shortened by [`redact`] before
+//! it leaves this module.
+
+use crate::network::RelayConnection;
+use crate::storage::Database;
+use gns_crypto_core::signing::verify_signature_hex;
+use gns_crypto_core::GnsIdentity;
+use reqwest::Client;
+use serde::Serialize;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// How long a reachability check may take
+pub const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Settings key the database check writes and removes again
+const PROBE_KEY: &str = "diagnostics_probe";
+
+/// Outcome of one check
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticCheck {
+    pub name: String,
+    pub passed: bool,
+    pub duration_ms: u64,
+    /// What was seen, or why it failed
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticsReport {
+    pub generated_at: i64,
+    pub app_version: String,
+    pub platform: String,
+    /// True when every check passed
+    pub passed: bool,
+    pub checks: Vec<DiagnosticCheck>,
+}
+
+impl DiagnosticsReport {
+    pub fn new(checks: Vec<DiagnosticCheck>) -> Self {
+        Self {
+            generated_at: chrono::Utc::now().timestamp_millis(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            platform: format!("{}/{}", std::env::consts::OS, std::env::consts::ARCH),
+            passed: checks.iter().all(|c| c.passed),
+            checks,
+        }
+    }
+}
+
+/// Run `check` and record how it went and how long it took
+pub async fn timed<F>(name: &str, check: F) -> DiagnosticCheck
+where
+    F: Future<Output = Result<String, String>>,
+{
+    let started = Instant::now();
+    let result = check.await;
+    let duration_ms = started.elapsed().as_millis() as u64;
+
+    let (passed, detail) = match result {
+        Ok(detail) => (true, detail),
+        Err(detail) => (false, detail),
+    };
+    if !passed {
+        tracing::warn!("🩺 Diagnostic '{}' failed: {}", name, detail);
+    }
+
+    DiagnosticCheck {
+        name: name.to_string(),
+        passed,
+        duration_ms,
+        detail: redact(&detail),
+    }
+}
+
+/// Sign/verify and encrypt/decrypt with a throwaway identity
+pub fn check_crypto() -> Result<String, String> {
+    let alice = GnsIdentity::generate();
+    let bob = GnsIdentity::generate();
+    let message = b"gns diagnostics";
+
+    let signature = hex::encode(alice.sign_bytes(message));
+    if !verify_signature_hex(&alice.public_key_hex(), message, &signature).map_err(|e| e.to_string())? {
+        return Err("Signature did not verify".to_string());
+    }
+
+    let encrypted = alice
+        .encrypt_for(message, &bob.encryption_public_key_bytes())
+        .map_err(|e| format!("Encryption failed: {}", e))?;
+    let decrypted = bob.decrypt(&encrypted).map_err(|e| format!("Decryption failed: {}", e))?;
+    if decrypted != message {
+        return Err("Decrypted text differs from the original".to_string());
+    }
+
+    Ok("Ed25519 sign/verify and X25519 encrypt/decrypt round-trip".to_string())
+}
+
+/// The stored private key re-derives the identity's public key and signs
+/// for it
+pub fn check_identity(identity: Option<&GnsIdentity>) -> Result<String, String> {
+    let identity = identity.ok_or("No identity loaded")?;
+    let public_key = identity.public_key_hex();
+
+    let rederived = GnsIdentity::from_hex(&identity.private_key_hex())
+        .map_err(|e| format!("Stored private key is unreadable: {}", e))?;
+    if rederived.public_key_hex() != public_key {
+        return Err(format!("Private key does not match public key {}", public_key));
+    }
+
+    let message = b"gns diagnostics identity";
+    let signature = hex::encode(identity.sign_bytes(message));
+    if !verify_signature_hex(&public_key, message, &signature).unwrap_or(false) {
+        return Err(format!("Signature by {} did not verify", public_key));
+    }
+
+    Ok(format!("Keys consistent for {}", public_key))
+}
+
+/// Write, read back and remove a setting
+pub async fn check_database(database: &Mutex<Database>) -> Result<String, String> {
+    let mut db = database.lock().await;
+    let value = uuid::Uuid::new_v4().to_string();
+
+    db.set_setting(PROBE_KEY, &value).map_err(|e| format!("Write failed: {}", e))?;
+    let read = db.get_setting(PROBE_KEY);
+    db.delete_setting(PROBE_KEY).map_err(|e| format!("Delete failed: {}", e))?;
+
+    if read.as_deref() != Some(value.as_str()) {
+        return Err("Read back a different value than was written".to_string());
+    }
+    Ok("Read/write OK".to_string())
+}
+
+/// Whether the relay WebSocket is up
+pub async fn check_relay(relay: &Mutex<RelayConnection>) -> Result<String, String> {
+    let relay = relay.lock().await;
+
+    if !relay.is_connected().await {
+        return Err(format!(
+            "Not connected to {} ({} reconnect attempts)",
+            relay.url(),
+            relay.reconnect_attempts().await
+        ));
+    }
+
+    match relay.last_message_time().await {
+        Some(at) => {
+            let age = (chrono::Utc::now().timestamp_millis() - at).max(0) / 1000;
+            Ok(format!("Connected to {}, last message {}s ago", relay.url(), age))
+        }
+        None => Ok(format!("Connected to {}", relay.url())),
+    }
+}
+
+/// GET `url` and expect a 2xx answer
+pub async fn check_http(client: &Client, url: &str) -> Result<String, String> {
+    let response = client
+        .get(url)
+        .timeout(HTTP_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| format!("{} unreachable: {}", url, e))?;
+
+    let status = response.status();
+    if status.is_success() {
+        Ok(format!("HTTP {} from {}", status.as_u16(), url))
+    } else {
+        Err(format!("HTTP {} from {}", status.as_u16(), url))
+    }
+}
+
+/// Shorten anything that looks like a key, so reports can be shared
+pub fn redact(text: &str) -> String {
+    const MIN_SECRET_LEN: usize = 32;
+    const KEEP: usize = 8;
+
+    let mut out = String::with_capacity(text.len());
+    let mut run = String::new();
+    let flush = |run: &mut String, out: &mut String| {
+        if run.len() >= MIN_SECRET_LEN {
+            out.push_str(&run[..KEEP]);
+            out.push('…');
+        } else {
+            out.push_str(run);
+        }
+        run.clear();
+    };
+
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() {
+            run.push(c);
+        } else {
+            flush(&mut run, &mut out);
+            out.push(c);
+        }
+    }
+    flush(&mut run, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_checks_report_success() {
+        let database = Mutex::new(Database::open_in_memory().unwrap());
+        let identity = GnsIdentity::generate();
+
+        let checks = vec![
+            timed("crypto", async { check_crypto() }).await,
+            timed("identity", async { check_identity(Some(&identity)) }).await,
+            timed("database", check_database(&database)).await,
+        ];
+        assert!(checks.iter().all(|c| c.passed), "{:?}", checks);
+        assert!(DiagnosticsReport::new(checks).passed);
+
+        // The probe leaves nothing behind
+        assert_eq!(database.lock().await.get_setting(PROBE_KEY), None);
+    }
+
+    #[tokio::test]
+    async fn test_checks_fail_without_their_dependency() {
+        // Nothing listens on a port we just released
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/health", listener.local_addr().unwrap());
+        drop(listener);
+
+        let relay = Mutex::new(RelayConnection::new("http://127.0.0.1:9").unwrap());
+        let database = Mutex::new(Database::open_in_memory().unwrap());
+        database.lock().await.make_read_only().unwrap();
+
+        let checks = vec![
+            timed("identity", async { check_identity(None) }).await,
+            timed("database", check_database(&database)).await,
+            timed("relay", check_relay(&relay)).await,
+            timed("api", check_http(&Client::new(), &url)).await,
+        ];
+        for check in &checks {
+            assert!(!check.passed, "{} should fail", check.name);
+            assert!(!check.detail.is_empty());
+        }
+        assert!(checks[3].detail.contains("unreachable"), "{}", checks[3].detail);
+        assert!(!DiagnosticsReport::new(checks).passed);
+    }
+
+    #[tokio::test]
+    async fn test_http_error_status_fails() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/health", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = socket.read(&mut buf).await;
+            let _ = socket
+                .write_all(b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .await;
+        });
+
+        let check = timed("api", check_http(&Client::new(), &url)).await;
+        assert!(!check.passed);
+        assert!(check.detail.starts_with("HTTP 503"), "{}", check.detail);
+    }
+
+    #[test]
+    fn test_redact_shortens_keys() {
+        let identity = GnsIdentity::generate();
+        let private_key = identity.private_key_hex();
+        let text = format!("Private key {} rejected, code 42", private_key);
+
+        let redacted = redact(&text);
+        assert!(!redacted.contains(&private_key));
+        assert_eq!(redacted, format!("Private key {}… rejected, code 42", &private_key[..8]));
+    }
+}
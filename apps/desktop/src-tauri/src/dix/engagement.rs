@@ -0,0 +1,399 @@
+//! Engagement Watch - live like/repost/reply/quote counts
+//!
+//! The UI registers the posts it is showing and one background task keeps
+//! their counts fresh for all of them. It prefers the API's server-sent
+//! event stream and falls back to polling `get_post` when the server
+//! doesn't offer one, retrying the stream now and then.
+//!
+//! Counts from either source pass through an [`EngagementTracker`], which
+//! holds them back for `COALESCE_WINDOW` and only lets a post through when
+//! its counts differ from what the UI last saw. A post that gets ten likes
+//! in a second produces one event, not ten.
+
+use super::{DixPost, DixPostEngagement, DixService};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, Mutex};
+use tokio::time::Instant;
+
+/// Tauri event carrying an [`EngagementUpdate`]
+pub const ENGAGEMENT_UPDATED_EVENT: &str = "engagement_updated";
+
+/// Most posts watched at once, bounding both the stream URL and a poll round
+pub const MAX_WATCHED_POSTS: usize = 100;
+
+/// How long updates are held so bursts collapse into one event per post
+const COALESCE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Time between poll rounds when there is no stream
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Wait before asking again for a stream the server doesn't offer
+const STREAM_UNAVAILABLE_RETRY: Duration = Duration::from_secs(600);
+
+/// Wait before reconnecting a stream that dropped
+const STREAM_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// Counts for one post, the payload of the `engagement_updated` event
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EngagementUpdate {
+    #[serde(alias = "postId")]
+    pub post_id: String,
+    pub likes: i32,
+    pub reposts: i32,
+    pub replies: i32,
+    pub quotes: i32,
+}
+
+impl EngagementUpdate {
+    pub fn from_post(post: &DixPost) -> Self {
+        Self::new(&post.id, &post.engagement)
+    }
+
+    fn new(post_id: &str, engagement: &DixPostEngagement) -> Self {
+        Self {
+            post_id: post_id.to_string(),
+            likes: engagement.likes,
+            reposts: engagement.reposts,
+            replies: engagement.replies,
+            quotes: engagement.quotes,
+        }
+    }
+}
+
+/// Diffs and coalesces observed counts against what the UI has seen
+#[derive(Debug, Default)]
+pub struct EngagementTracker {
+    /// Counts the UI last saw per watched post
+    shown: HashMap<String, EngagementUpdate>,
+    /// Latest counts observed since the last flush
+    pending: BTreeMap<String, EngagementUpdate>,
+}
+
+impl EngagementTracker {
+    /// Record counts the UI already has, e.g. from the post it rendered.
+    /// Counts recorded earlier win: they're what the UI actually shows.
+    pub fn seed(&mut self, update: EngagementUpdate) {
+        self.shown.entry(update.post_id.clone()).or_insert(update);
+    }
+
+    /// Note the latest counts for a post; nothing is emitted until `flush`
+    pub fn observe(&mut self, update: EngagementUpdate) {
+        self.pending.insert(update.post_id.clone(), update);
+    }
+
+    /// Updates for posts whose counts changed since the UI last saw them.
+    ///
+    /// A post observed without a seed only sets the baseline: there is
+    /// nothing on screen to correct yet.
+    pub fn flush(&mut self) -> Vec<EngagementUpdate> {
+        let pending = std::mem::take(&mut self.pending);
+        pending
+            .into_values()
+            .filter(|update| match self.shown.insert(update.post_id.clone(), update.clone()) {
+                Some(previous) => previous != *update,
+                None => false,
+            })
+            .collect()
+    }
+
+    /// Forget every post not in `watched`
+    pub fn retain(&mut self, watched: &BTreeSet<String>) {
+        self.shown.retain(|id, _| watched.contains(id));
+        self.pending.retain(|id, _| watched.contains(id));
+    }
+}
+
+/// The set of watched posts, shared by the commands and the watch task
+pub struct EngagementWatch {
+    watched: watch::Sender<BTreeSet<String>>,
+    tracker: Mutex<EngagementTracker>,
+}
+
+impl Default for EngagementWatch {
+    fn default() -> Self {
+        Self {
+            watched: watch::channel(BTreeSet::new()).0,
+            tracker: Mutex::new(EngagementTracker::default()),
+        }
+    }
+}
+
+impl EngagementWatch {
+    /// Add posts to the watch set, seeding their counts from `cached`
+    pub async fn watch(&self, post_ids: &[String], cached: &[DixPost]) -> Result<(), String> {
+        let mut tracker = self.tracker.lock().await;
+        for post in cached.iter().filter(|p| post_ids.contains(&p.id)) {
+            tracker.seed(EngagementUpdate::from_post(post));
+        }
+        drop(tracker);
+
+        let mut result = Ok(());
+        self.watched.send_if_modified(|watched| {
+            let before = watched.len();
+            for id in post_ids {
+                if watched.len() >= MAX_WATCHED_POSTS && !watched.contains(id) {
+                    result = Err(format!("At most {} posts can be watched", MAX_WATCHED_POSTS));
+                    break;
+                }
+                watched.insert(id.clone());
+            }
+            watched.len() != before
+        });
+        result
+    }
+
+    /// Stop watching `post_ids`, or every post when `None`
+    pub async fn unwatch(&self, post_ids: Option<&[String]>) {
+        self.watched.send_if_modified(|watched| {
+            let before = watched.len();
+            match post_ids {
+                Some(ids) => ids.iter().for_each(|id| {
+                    watched.remove(id);
+                }),
+                None => watched.clear(),
+            }
+            watched.len() != before
+        });
+        let watched = self.watched.borrow().clone();
+        self.tracker.lock().await.retain(&watched);
+    }
+
+    async fn observe(&self, update: EngagementUpdate) {
+        self.tracker.lock().await.observe(update);
+    }
+
+    async fn flush(&self) -> Vec<EngagementUpdate> {
+        self.tracker.lock().await.flush()
+    }
+}
+
+impl DixService {
+    /// Keep `post_ids` live, starting from the counts in the timeline cache
+    pub async fn watch_engagement(&self, post_ids: &[String]) -> Result<(), String> {
+        let cache = self.timeline.lock().await;
+        self.engagement.watch(post_ids, &cache).await
+    }
+}
+
+/// Why a stream ended
+enum StreamEnd {
+    /// The server doesn't offer engagement streams
+    Unavailable,
+    Dropped(String),
+}
+
+/// Keep watched posts' counts fresh until the app exits, passing each
+/// change to `emit`
+pub async fn run<E>(dix: Arc<DixService>, mut emit: E)
+where
+    E: FnMut(EngagementUpdate),
+{
+    let mut watched = dix.engagement.watched.subscribe();
+    let mut stream_retry_at: Option<Instant> = None;
+
+    loop {
+        let ids: Vec<String> = watched.borrow_and_update().iter().cloned().collect();
+        if ids.is_empty() {
+            if watched.changed().await.is_err() {
+                return;
+            }
+            continue;
+        }
+
+        // Whichever source is used, a change to the watch set restarts it
+        // so the subscription always covers exactly the watched posts
+        tokio::select! {
+            _ = follow(&dix, &ids, &mut stream_retry_at, &mut emit) => {}
+            changed = watched.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Follow `ids` over the stream when it may be tried, polling otherwise.
+/// Returns when the stream is worth another attempt.
+async fn follow<E>(dix: &DixService, ids: &[String], stream_retry_at: &mut Option<Instant>, emit: &mut E)
+where
+    E: FnMut(EngagementUpdate),
+{
+    if !matches!(stream_retry_at, Some(at) if Instant::now() < *at) {
+        let delay = match stream_engagement(dix, ids, emit).await {
+            StreamEnd::Unavailable => {
+                tracing::debug!("Engagement stream unavailable, polling instead");
+                STREAM_UNAVAILABLE_RETRY
+            }
+            StreamEnd::Dropped(e) => {
+                tracing::warn!("⚠️ Engagement stream dropped: {}", e);
+                STREAM_RECONNECT_DELAY
+            }
+        };
+        *stream_retry_at = Some(Instant::now() + delay);
+    }
+
+    let retry_at = stream_retry_at.unwrap_or_else(Instant::now);
+    loop {
+        poll_engagement(dix, ids).await;
+        dix.engagement.flush().await.into_iter().for_each(&mut *emit);
+
+        if Instant::now() + POLL_INTERVAL > retry_at {
+            tokio::time::sleep_until(retry_at).await;
+            return;
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Read the server's engagement event stream for `ids` until it ends
+async fn stream_engagement<E>(dix: &DixService, ids: &[String], emit: &mut E) -> StreamEnd
+where
+    E: FnMut(EngagementUpdate),
+{
+    let url = format!("{}/web/dix/engagement/stream", dix.api.base_url());
+    let client = reqwest::Client::new();
+    let response = client
+        .get(&url)
+        .query(&[("ids", ids.join(","))])
+        .header(reqwest::header::ACCEPT, "text/event-stream")
+        .send()
+        .await;
+
+    let mut response = match response {
+        Ok(response) => response,
+        Err(e) => return StreamEnd::Dropped(e.to_string()),
+    };
+    let is_event_stream = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/event-stream"));
+    if !response.status().is_success() || !is_event_stream {
+        return StreamEnd::Unavailable;
+    }
+
+    tracing::info!("📡 Streaming engagement for {} posts", ids.len());
+    let mut events = SseBuffer::default();
+    let mut flush = tokio::time::interval(COALESCE_WINDOW);
+    loop {
+        tokio::select! {
+            chunk = response.chunk() => match chunk {
+                Ok(Some(bytes)) => {
+                    for data in events.push(&bytes) {
+                        match serde_json::from_str::<EngagementUpdate>(&data) {
+                            Ok(update) if ids.contains(&update.post_id) => dix.engagement.observe(update).await,
+                            Ok(_) => {}
+                            Err(e) => tracing::debug!("Skipping engagement event: {}", e),
+                        }
+                    }
+                }
+                Ok(None) => return StreamEnd::Dropped("stream closed".to_string()),
+                Err(e) => return StreamEnd::Dropped(e.to_string()),
+            },
+            _ = flush.tick() => dix.engagement.flush().await.into_iter().for_each(&mut *emit),
+        }
+    }
+}
+
+/// Fetch current counts for `ids`, all requests in flight at once
+async fn poll_engagement(dix: &DixService, ids: &[String]) {
+    let fetches = ids.iter().map(|id| async move { (id, dix.get_post(id).await) });
+    for (id, result) in futures::future::join_all(fetches).await {
+        match result {
+            Ok(data) => dix.engagement.observe(EngagementUpdate::from_post(&data.post)).await,
+            Err(e) => tracing::debug!("Engagement poll for {} failed: {}", id, e),
+        }
+    }
+}
+
+/// Splits a server-sent event stream into the data of each event
+#[derive(Debug, Default)]
+struct SseBuffer {
+    buffer: String,
+    data: Vec<String>,
+}
+
+impl SseBuffer {
+    /// Feed bytes from the stream; returns the data of each event completed
+    fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.buffer.push_str(&String::from_utf8_lossy(bytes));
+
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.find('\n') {
+            let line: String = self.buffer.drain(..=end).collect();
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    events.push(std::mem::take(&mut self.data).join("\n"));
+                }
+            } else if let Some(data) = line.strip_prefix("data:") {
+                self.data.push(data.strip_prefix(' ').unwrap_or(data).to_string());
+            }
+            // Comments (keep-alives), ids and event names carry nothing we use
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(post_id: &str, likes: i32, replies: i32) -> EngagementUpdate {
+        EngagementUpdate { post_id: post_id.to_string(), likes, reposts: 0, replies, quotes: 0 }
+    }
+
+    #[test]
+    fn test_polled_counts_are_diffed_and_coalesced() {
+        let mut tracker = EngagementTracker::default();
+        tracker.seed(counts("a", 1, 0));
+        tracker.seed(counts("b", 5, 2));
+
+        // Unchanged counts emit nothing
+        tracker.observe(counts("a", 1, 0));
+        tracker.observe(counts("b", 5, 2));
+        assert!(tracker.flush().is_empty());
+
+        // Several rounds inside one window collapse to the latest
+        tracker.observe(counts("a", 2, 0));
+        tracker.observe(counts("a", 3, 1));
+        assert_eq!(tracker.flush(), vec![counts("a", 3, 1)]);
+        assert!(tracker.flush().is_empty());
+
+        // Changing and changing back before a flush is no change
+        tracker.observe(counts("b", 6, 2));
+        tracker.observe(counts("b", 5, 2));
+        assert!(tracker.flush().is_empty());
+
+        // A post first seen by the poller only sets the baseline
+        tracker.observe(counts("c", 9, 9));
+        assert!(tracker.flush().is_empty());
+        tracker.observe(counts("c", 10, 9));
+        assert_eq!(tracker.flush(), vec![counts("c", 10, 9)]);
+
+        // Unwatched posts are forgotten, pending counts included
+        tracker.observe(counts("a", 4, 1));
+        tracker.retain(&BTreeSet::from(["b".to_string()]));
+        assert!(tracker.flush().is_empty());
+        tracker.observe(counts("a", 5, 1));
+        assert!(tracker.flush().is_empty());
+    }
+
+    #[test]
+    fn test_sse_events_split_across_chunks() {
+        let mut buffer = SseBuffer::default();
+        assert!(buffer.push(b": keep-alive\n\nevent: engagement\ndata: {\"postId\":").is_empty());
+
+        let events = buffer.push(b"\"a\",\"likes\":1,\"reposts\":0,\"replies\":0,\"quotes\":0}\r\n\r\ndata: x\n");
+        assert_eq!(events.len(), 1);
+        let update: EngagementUpdate = serde_json::from_str(&events[0]).unwrap();
+        assert_eq!(update, counts("a", 1, 0));
+
+        assert_eq!(buffer.push(b"\n"), vec!["x".to_string()]);
+    }
+}
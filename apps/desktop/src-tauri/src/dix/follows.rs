@@ -0,0 +1,258 @@
+//! Follows - signed follow/unfollow records
+//!
+//! A follow is a small signed statement `{action, followee, follower,
+//! timestamp}`. The latest record per (follower, followee) pair wins, so
+//! records can arrive in any order and still reconcile to the same state.
+
+use super::{generate_canonical_json, DixPost, DixService};
+use crate::crypto::{IdentityManager, SignatureDomain};
+use crate::network::{idempotency_operation, ApiClient};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashSet;
+
+/// Global timeline posts fetched per request when filtering it locally
+const FALLBACK_PAGE_SIZE: u32 = 100;
+
+/// Most global timeline posts scanned for one page of the following timeline
+const FALLBACK_SCAN_LIMIT: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FollowAction {
+    Follow,
+    Unfollow,
+}
+
+impl FollowAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FollowAction::Follow => "follow",
+            FollowAction::Unfollow => "unfollow",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FollowRecord {
+    pub follower: String,
+    pub followee: String,
+    pub action: FollowAction,
+    /// Milliseconds since the epoch, used to order records for the same pair
+    pub timestamp: i64,
+    pub signature: String,
+}
+
+impl FollowRecord {
+    /// Create and sign a record as `identity`'s current key
+    pub fn sign(identity: &IdentityManager, followee: &str, action: FollowAction, timestamp: i64) -> Option<Self> {
+        let mut record = Self {
+            follower: identity.public_key_hex()?,
+            followee: followee.to_lowercase(),
+            action,
+            timestamp,
+            signature: String::new(),
+        };
+        record.signature = identity.sign_for_backend(SignatureDomain::Dix, &record.canonical_message())?;
+        Some(record)
+    }
+
+    /// The exact string that is signed (keys sorted, signature excluded)
+    pub fn canonical_message(&self) -> String {
+        generate_canonical_json(&json!({
+            "action": self.action.as_str(),
+            "followee": self.followee,
+            "follower": self.follower,
+            "timestamp": self.timestamp,
+        }))
+    }
+
+    /// Check the signature against the follower's key
+    pub fn verify(&self) -> bool {
+        gns_crypto_core::verify_in_domain_hex(
+            &self.follower,
+            SignatureDomain::Dix,
+            self.canonical_message().as_bytes(),
+            &self.signature,
+            gns_crypto_core::TRANSITION_POLICY,
+        )
+        .unwrap_or(false)
+    }
+}
+
+#[derive(Deserialize)]
+struct FollowersData {
+    #[serde(default)]
+    records: Vec<FollowRecord>,
+}
+
+#[derive(Deserialize)]
+struct FollowingTimelineData {
+    #[serde(default)]
+    posts: Vec<DixPost>,
+}
+
+impl DixService {
+    /// Sign and publish a follow or unfollow of `followee`
+    pub async fn publish_follow(&self, followee: &str, action: FollowAction) -> Result<FollowRecord, String> {
+        let record = FollowRecord::sign(&*self.identity.lock().await, followee, action, chrono::Utc::now().timestamp_millis())
+            .ok_or("No identity")?;
+
+        let url = format!("{}/web/dix/follow", self.api.base_url());
+        let timestamp = record.timestamp.to_string();
+        let operation = idempotency_operation(&[
+            "dix_follow",
+            &record.follower,
+            &record.followee,
+            record.action.as_str(),
+            &timestamp,
+        ]);
+
+        let response = self.api
+            .send_idempotent(&operation, |client| client.post(&url).json(&record))
+            .await
+            .map_err(|e| format!("Network error: {}", e))?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(format!("Server returned error: {}", error_text));
+        }
+
+        Ok(record)
+    }
+
+    /// Follow records targeting `public_key`, keeping only those whose
+    /// signature checks out
+    pub async fn get_follower_records(&self, public_key: &str) -> Result<Vec<FollowRecord>, String> {
+        let url = format!("{}/web/dix/followers/{}", self.api.base_url(), public_key);
+
+        let res = reqwest::Client::new()
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let records = ApiClient::read_data::<FollowersData>(res).await.map_err(|e| e.to_string())?.records;
+        Ok(records
+            .into_iter()
+            .filter(|r| r.followee.eq_ignore_ascii_case(public_key) && r.verify())
+            .collect())
+    }
+
+    /// Posts from the given authors only.
+    ///
+    /// Older API deployments lack the following endpoint; there we filter
+    /// the global timeline instead, see `filter_global_timeline`.
+    pub async fn get_following_timeline(
+        &self,
+        authors: &[String],
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<DixPost>, String> {
+        if authors.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let url = format!("{}/web/dix/timeline/following", self.api.base_url());
+        let res = reqwest::Client::new()
+            .post(&url)
+            .json(&json!({ "public_keys": authors, "limit": limit, "offset": offset }))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if res.status() == reqwest::StatusCode::NOT_FOUND {
+            return self.filter_global_timeline(authors, limit, offset).await;
+        }
+
+        let data: FollowingTimelineData = ApiClient::read_data(res).await.map_err(|e| e.to_string())?;
+        Ok(data.posts)
+    }
+
+    /// Page through the global timeline until `offset + limit` posts by
+    /// `authors` turned up, then cut the requested page from those. The
+    /// offset counts posts by `authors`, not posts of the global timeline.
+    async fn filter_global_timeline(
+        &self,
+        authors: &[String],
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<DixPost>, String> {
+        let wanted = offset.saturating_add(limit) as usize;
+        let mut seen = HashSet::new();
+        let mut matching = Vec::new();
+        let mut scanned = 0;
+
+        while matching.len() < wanted && scanned < FALLBACK_SCAN_LIMIT {
+            let page = self.get_timeline(FALLBACK_PAGE_SIZE, scanned, None).await?;
+            let fetched = page.len() as u32;
+            // Posts published meanwhile shift the pages, so one can come twice
+            matching.extend(
+                filter_by_authors(page, authors)
+                    .into_iter()
+                    .filter(|p| seen.insert(p.id.clone())),
+            );
+            if fetched < FALLBACK_PAGE_SIZE {
+                break;
+            }
+            scanned += fetched;
+        }
+
+        Ok(matching
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect())
+    }
+}
+
+fn filter_by_authors(posts: Vec<DixPost>, authors: &[String]) -> Vec<DixPost> {
+    posts
+        .into_iter()
+        .filter(|p| authors.iter().any(|a| a.eq_ignore_ascii_case(&p.author.public_key)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gns_crypto_core::GnsIdentity;
+
+    #[test]
+    fn test_follow_record_canonical_signing() {
+        let identity = IdentityManager::from_identity(GnsIdentity::generate());
+        let followee = "AB".repeat(32);
+        let record = FollowRecord::sign(&identity, &followee, FollowAction::Follow, 1_700_000_000_000).unwrap();
+
+        assert_eq!(
+            record.canonical_message(),
+            format!(
+                r#"{{"action":"follow","followee":"{}","follower":"{}","timestamp":1700000000000}}"#,
+                "ab".repeat(32),
+                identity.public_key_hex().unwrap()
+            )
+        );
+        assert!(record.verify());
+
+        // Flipping the action or moving the timestamp invalidates it
+        let mut tampered = record.clone();
+        tampered.action = FollowAction::Unfollow;
+        assert!(!tampered.verify());
+
+        let mut replayed = record;
+        replayed.timestamp += 1;
+        assert!(!replayed.verify());
+    }
+
+    #[test]
+    fn test_follow_record_wire_format() {
+        let identity = IdentityManager::from_identity(GnsIdentity::generate()).with_tagged_signatures(true);
+        let record = FollowRecord::sign(&identity, &"cd".repeat(32), FollowAction::Unfollow, 5).unwrap();
+
+        let value = serde_json::to_value(&record).unwrap();
+        assert_eq!(value["action"], "unfollow");
+
+        let parsed: FollowRecord = serde_json::from_value(value).unwrap();
+        assert!(parsed.verify());
+    }
+}
@@ -0,0 +1,268 @@
+//! Link Preview - OpenGraph / Twitter-card extraction
+//!
+//! Fetches a link on behalf of the composer and pulls out the title,
+//! description and image so the post can carry a `DixLink` instead of a
+//! bare URL. Only public http(s) hosts are fetched, with a timeout,
+//! a redirect cap and a download limit.
+
+use super::DixLink;
+use regex::Regex;
+use reqwest::header::{ACCEPT, CONTENT_TYPE, LOCATION};
+use reqwest::Url;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+const PREVIEW_TIMEOUT: Duration = Duration::from_secs(8);
+const MAX_REDIRECTS: usize = 3;
+const MAX_PREVIEW_BYTES: usize = 512 * 1024;
+const MAX_TITLE_CHARS: usize = 300;
+const MAX_DESCRIPTION_CHARS: usize = 1000;
+
+static META_TAG_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?is)<meta\s[^>]*>").unwrap()
+});
+
+static ATTR_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?is)([a-z:_-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap()
+});
+
+static TITLE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap()
+});
+
+/// Fetch a URL and build a `DixLink` from its preview metadata
+pub async fn fetch_link_preview(url: &str) -> Result<DixLink, String> {
+    let mut current = Url::parse(url.trim()).map_err(|e| format!("Invalid URL: {}", e))?;
+    let mut redirects = 0;
+
+    let mut response = loop {
+        let addr = resolve_public_addr(&current).await?;
+        let host = current.host_str().unwrap_or_default().to_string();
+
+        // Pin the connection to the address we just vetted so a second
+        // DNS lookup can't swap in a private one
+        let client = reqwest::Client::builder()
+            .timeout(PREVIEW_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .resolve(&host, addr)
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        let res = client
+            .get(current.clone())
+            .header(ACCEPT, "text/html,application/xhtml+xml")
+            .send()
+            .await
+            .map_err(|e| format!("Network error: {}", e))?;
+
+        if !res.status().is_redirection() {
+            break res;
+        }
+
+        redirects += 1;
+        if redirects > MAX_REDIRECTS {
+            return Err("Too many redirects".into());
+        }
+
+        let location = res
+            .headers()
+            .get(LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .ok_or("Redirect without a location")?;
+        current = current.join(location).map_err(|e| format!("Invalid redirect: {}", e))?;
+    };
+
+    if !response.status().is_success() {
+        return Err(format!("Server returned {}", response.status()));
+    }
+
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("text/html")
+        .to_ascii_lowercase();
+
+    // Direct image links preview as themselves
+    if content_type.starts_with("image/") {
+        return Ok(DixLink {
+            url: current.to_string(),
+            title: None,
+            description: None,
+            image: Some(current.to_string()),
+        });
+    }
+
+    if !content_type.contains("html") {
+        return Ok(DixLink {
+            url: current.to_string(),
+            title: None,
+            description: None,
+            image: None,
+        });
+    }
+
+    // Meta tags live in <head>, so a truncated body is still useful
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| format!("Network error: {}", e))? {
+        let room = MAX_PREVIEW_BYTES - body.len();
+        body.extend_from_slice(&chunk[..chunk.len().min(room)]);
+        if body.len() >= MAX_PREVIEW_BYTES {
+            break;
+        }
+    }
+
+    Ok(parse_preview(&current, &String::from_utf8_lossy(&body)))
+}
+
+/// Resolve the URL's host and refuse anything that isn't a public address
+async fn resolve_public_addr(url: &Url) -> Result<SocketAddr, String> {
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err("Only http and https links can be previewed".into());
+    }
+
+    let host = url.host_str().ok_or("URL has no host")?;
+    let port = url.port_or_known_default().unwrap_or(443);
+
+    // IPv6 literals come back bracketed from host_str()
+    let literal = host.trim_start_matches('[').trim_end_matches(']');
+    let addrs: Vec<SocketAddr> = match literal.parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|e| format!("Could not resolve {}: {}", host, e))?
+            .collect(),
+    };
+
+    if let Some(blocked) = addrs.iter().find(|a| !is_public_ip(a.ip())) {
+        return Err(format!("Refusing to fetch non-public address {}", blocked.ip()));
+    }
+
+    addrs.into_iter().next().ok_or_else(|| "Host did not resolve".to_string())
+}
+
+/// Whether an address is routable on the public internet
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let octets = v4.octets();
+            let shared = octets[0] == 100 && (octets[1] & 0xc0) == 64; // 100.64.0.0/10
+            !(v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_broadcast()
+                || v4.is_documentation()
+                || v4.is_unspecified()
+                || octets[0] == 0
+                || shared)
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(v4));
+            }
+            let first = v6.segments()[0];
+            let unique_local = (first & 0xfe00) == 0xfc00;
+            let link_local = (first & 0xffc0) == 0xfe80;
+            !(v6.is_loopback() || v6.is_unspecified() || unique_local || link_local)
+        }
+    }
+}
+
+/// Extract preview fields from an HTML document
+fn parse_preview(page_url: &Url, html: &str) -> DixLink {
+    let mut meta: HashMap<String, String> = HashMap::new();
+
+    for tag in META_TAG_REGEX.find_iter(html) {
+        let mut key = None;
+        let mut content = None;
+
+        for cap in ATTR_REGEX.captures_iter(tag.as_str()) {
+            let value = cap.get(2).or_else(|| cap.get(3)).map(|m| m.as_str()).unwrap_or("");
+            match cap[1].to_ascii_lowercase().as_str() {
+                "property" | "name" => key = Some(value.to_ascii_lowercase()),
+                "content" => content = Some(value),
+                _ => {}
+            }
+        }
+
+        if let (Some(k), Some(c)) = (key, content) {
+            meta.entry(k).or_insert_with(|| decode_entities(c.trim()));
+        }
+    }
+
+    let pick = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|k| meta.get(*k).filter(|v| !v.is_empty()).cloned())
+    };
+
+    let title = pick(&["og:title", "twitter:title"]).or_else(|| {
+        TITLE_REGEX
+            .captures(html)
+            .map(|c| decode_entities(c[1].trim()))
+            .filter(|t| !t.is_empty())
+    });
+
+    let description = pick(&["og:description", "twitter:description", "description"]);
+
+    let image = pick(&["og:image", "og:image:url", "twitter:image", "twitter:image:src"])
+        .and_then(|img| page_url.join(&img).ok())
+        .filter(|u| u.scheme() == "http" || u.scheme() == "https")
+        .map(|u| u.to_string());
+
+    DixLink {
+        url: page_url.to_string(),
+        title: title.map(|t| clip(&t, MAX_TITLE_CHARS)),
+        description: description.map(|d| clip(&d, MAX_DESCRIPTION_CHARS)),
+        image,
+    }
+}
+
+fn decode_entities(s: &str) -> String {
+    s.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+fn clip(s: &str, max_chars: usize) -> String {
+    s.chars().take(max_chars).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_preview_prefers_open_graph() {
+        let html = r#"<html><head>
+            <title>Fallback</title>
+            <meta property="og:title" content="Tom &amp; Jerry">
+            <meta name="twitter:description" content='A classic'>
+            <meta content="/img/cover.png" property="og:image" />
+        </head></html>"#;
+
+        let url = Url::parse("https://example.com/post/1").unwrap();
+        let link = parse_preview(&url, html);
+
+        assert_eq!(link.title.as_deref(), Some("Tom & Jerry"));
+        assert_eq!(link.description.as_deref(), Some("A classic"));
+        assert_eq!(link.image.as_deref(), Some("https://example.com/img/cover.png"));
+    }
+
+    #[test]
+    fn test_private_addresses_are_rejected() {
+        assert!(!is_public_ip("127.0.0.1".parse().unwrap()));
+        assert!(!is_public_ip("10.1.2.3".parse().unwrap()));
+        assert!(!is_public_ip("169.254.169.254".parse().unwrap()));
+        assert!(!is_public_ip("::1".parse().unwrap()));
+        assert!(!is_public_ip("::ffff:192.168.0.1".parse().unwrap()));
+        assert!(!is_public_ip("fd00::1".parse().unwrap()));
+        assert!(is_public_ip("93.184.216.34".parse().unwrap()));
+    }
+}
@@ -2,13 +2,24 @@
 //!
 //! Handles creating, signing, and publishing posts to DIX via Supabase.
 
-use crate::crypto::{IdentityManager, GnsIdentity};
-use crate::network::ApiClient;
+use crate::crypto::{IdentityManager, GnsIdentity, SignatureDomain};
+use crate::network::{idempotency_operation, ApiClient};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+pub mod engagement;
+mod follows;
+mod link_preview;
+mod tags;
+mod verify;
+pub use engagement::{EngagementUpdate, EngagementWatch, ENGAGEMENT_UPDATED_EVENT, MAX_WATCHED_POSTS};
+pub use follows::{FollowAction, FollowRecord};
+pub use link_preview::fetch_link_preview;
+pub use tags::{normalize_tag, TrendingTag};
+pub use verify::{verify_post, verify_posts, verify_repost, DixRepost, RepostVerification};
+use verify::post_signing_message;
+
 // ===========================================
 // MODELS
 // ===========================================
@@ -17,8 +28,10 @@ use tokio::sync::Mutex;
 pub struct DixPost {
     pub id: String,
     pub author: DixPostAuthor,
+    #[serde(default)]
     pub facet: String,
     pub content: DixPostContent,
+    #[serde(default)]
     pub engagement: DixPostEngagement,
     pub meta: DixPostMeta,
     pub thread: Option<DixPostThread>,
@@ -33,11 +46,11 @@ pub struct DixPostAuthor {
     pub display_name: Option<String>,
     #[serde(rename = "avatarUrl")]
     pub avatar_url: Option<String>,
-    #[serde(rename = "trustScore")]
+    #[serde(rename = "trustScore", default)]
     pub trust_score: i32,
-    #[serde(rename = "breadcrumbCount")]
+    #[serde(rename = "breadcrumbCount", default)]
     pub breadcrumb_count: i32,
-    #[serde(rename = "isVerified")]
+    #[serde(rename = "isVerified", default)]
     pub is_verified: bool,
 }
 
@@ -67,10 +80,13 @@ pub struct DixMedia {
 pub struct DixLink {
     pub url: String,
     pub title: Option<String>,
+    pub description: Option<String>,
     pub image: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Counts the server leaves out are zero
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct DixPostEngagement {
     pub likes: i32,
     pub replies: i32,
@@ -82,9 +98,9 @@ pub struct DixPostEngagement {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DixPostMeta {
     pub signature: String,
-    #[serde(rename = "trustScoreAtPost")]
+    #[serde(rename = "trustScoreAtPost", default)]
     pub trust_score_at_post: i32,
-    #[serde(rename = "breadcrumbsAtPost")]
+    #[serde(rename = "breadcrumbsAtPost", default)]
     pub breadcrumbs_at_post: i32,
     #[serde(rename = "createdAt")]
     pub created_at: String,
@@ -98,6 +114,25 @@ pub struct DixPostThread {
     pub quote_of_id: Option<String>,
 }
 
+/// Cursor for fetching only posts newer than the client already has:
+/// the newest post it holds, or a unix time in milliseconds
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum TimelineSince {
+    Timestamp(i64),
+    PostId(String),
+}
+
+/// Most posts kept in the local timeline cache
+const TIMELINE_CACHE_LIMIT: usize = 500;
+
+/// Timeline posts fetched to count tags over when the cache is empty and
+/// the server has no trending endpoint
+const TRENDING_FALLBACK_POSTS: u32 = 100;
+
+/// Most trending tags returned
+const TRENDING_TAG_LIMIT: usize = 20;
+
 // ===========================================
 // SERVICE
 // ===========================================
@@ -109,18 +144,33 @@ pub struct DixService {
     // However, ApiClient is struct-based on one base_url.
     // Dix likely uses the same base_url.
     api: Arc<ApiClient>,
+    /// Timeline posts seen so far, newest first
+    timeline: Mutex<Vec<DixPost>>,
+    /// Posts whose engagement counts are kept live
+    pub engagement: EngagementWatch,
 }
 
 impl DixService {
     pub fn new(identity: Arc<Mutex<IdentityManager>>, api: Arc<ApiClient>) -> Self {
-        Self { identity, api }
+        Self { identity, api, timeline: Mutex::new(Vec::new()), engagement: EngagementWatch::default() }
     }
 
     /// Create and publish a new DIX post
+    #[tracing::instrument(
+        name = "dix_create_post",
+        skip_all,
+        fields(
+            correlation_id = %uuid::Uuid::new_v4(),
+            post_id = tracing::field::Empty,
+            reply_to = reply_to_id.as_deref(),
+            media = media.len(),
+        )
+    )]
     pub async fn create_post(
         &self,
         text: String,
         media: Vec<DixMedia>,
+        links: Vec<DixLink>,
         reply_to_id: Option<String>,
     ) -> Result<DixPost, String> {
         let identity = self.identity.lock().await;
@@ -135,28 +185,23 @@ impl DixService {
         
         // 3. Prepare data
         let post_id = uuid::Uuid::new_v4().to_string();
+        tracing::Span::current().record("post_id", post_id.as_str());
         let created_at = chrono::Utc::now().to_rfc3339();
         
         // 4. Create canonical JSON for signing (CRITICAL: must match server/flutter)
         // Fields: id, facet_id, author_public_key, content, created_at, reply_to_id (if present)
-        let mut signed_map = serde_json::Map::new();
-        signed_map.insert("id".to_string(), json!(post_id));
-        signed_map.insert("facet_id".to_string(), json!("dix"));
-        signed_map.insert("author_public_key".to_string(), json!(public_key));
-        signed_map.insert("content".to_string(), json!(text));
-        signed_map.insert("created_at".to_string(), json!(created_at));
-        
-        if let Some(rid) = &reply_to_id {
-            signed_map.insert("reply_to_id".to_string(), json!(rid));
-        }
-        
-        let signed_data = serde_json::Value::Object(signed_map);
-        
-        let canonical_message = generate_canonical_json(&signed_data);
-        println!("📝 [DIX] Signing Canonical Message: {}", canonical_message);
+        let canonical_message = post_signing_message(
+            &post_id,
+            "dix",
+            &public_key,
+            &text,
+            &created_at,
+            reply_to_id.as_deref(),
+        );
+        tracing::debug!(canonical_len = canonical_message.len(), "Signing canonical post message");
         
         // 5. Sign
-        let signature = identity.sign_string(&canonical_message)
+        let signature = identity.sign_for_backend(SignatureDomain::Dix, &canonical_message)
             .ok_or("Failed to sign post")?;
             
         drop(identity); // Release lock
@@ -171,6 +216,7 @@ impl DixService {
             "author_handle": handle,
             "content": text,
             "media": media,
+            "links": links,
             "created_at": created_at,
             "tags": tags,
             "mentions": vec![] as Vec<String>, // TODO: Extract from text
@@ -178,19 +224,32 @@ impl DixService {
             "reply_to_id": reply_to_id
         });
 
-        let response = self.api.client().post(&url)
-            .json(&payload)
-            .send()
+        // Retries of the same post (same author, text, reply target, media
+        // and links) reuse one idempotency key so the server can drop duplicates
+        let media_json = serde_json::to_string(&media).unwrap_or_default();
+        let links_json = serde_json::to_string(&links).unwrap_or_default();
+        let operation = idempotency_operation(&[
+            "dix_publish",
+            &public_key,
+            &text,
+            reply_to_id.as_deref().unwrap_or(""),
+            &media_json,
+            &links_json,
+        ]);
+
+        let response = self.api
+            .send_idempotent(&operation, |client| client.post(&url).json(&payload))
             .await
             .map_err(|e| format!("Network error: {}", e))?;
 
         if !response.status().is_success() {
+            let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
+            tracing::warn!(status = status.as_u16(), error = %error_text, "DIX publish rejected");
             return Err(format!("Server returned error: {}", error_text));
         }
-        
-        // Log success
-        println!("✅ Dix Post published: {}", post_id);
+
+        tracing::info!("✅ DIX post published");
         
         // Return the post object
         Ok(DixPost {
@@ -210,7 +269,7 @@ impl DixService {
                 tags,
                 mentions,
                 media,
-                links: vec![],
+                links,
                 location: None,
             },
             engagement: DixPostEngagement {
@@ -233,21 +292,72 @@ impl DixService {
         })
     }
     
-    pub async fn get_timeline(&self, limit: u32, offset: u32) -> Result<Vec<DixPost>, String> {
+    /// Fetch a page of the global timeline.
+    ///
+    /// Without `since` this is the usual older-page load by `offset`. With
+    /// it, only posts newer than the cursor come back, newest first, e.g.
+    /// for pull-to-refresh. Either way the posts are merged into the local
+    /// timeline cache.
+    pub async fn get_timeline(
+        &self,
+        limit: u32,
+        offset: u32,
+        since: Option<TimelineSince>,
+    ) -> Result<Vec<DixPost>, String> {
         let base_url = self.api.base_url();
-        let url = format!("{}/web/dix/timeline?limit={}&offset={}", base_url, limit, offset);
+        let url = format!("{}/web/dix/timeline", base_url);
+        let mut query = vec![("limit", limit.to_string())];
+        match &since {
+            None => query.push(("offset", offset.to_string())),
+            Some(TimelineSince::Timestamp(ms)) => query.push(("since", ms.to_string())),
+            Some(TimelineSince::PostId(id)) => query.push(("since_id", id.clone())),
+        }
         
         let client = reqwest::Client::new();
         let res = client.get(&url)
+            .query(&query)
             .send()
             .await
             .map_err(|e| e.to_string())?;
             
-        let wrapper: DixResponse = res.json().await.map_err(|e| e.to_string())?;
-        if !wrapper.success {
-             return Err(wrapper.error.unwrap_or("Unknown error".into()));
+        let mut posts = ApiClient::read_data::<DixData>(res).await.map_err(|e| e.to_string())?.posts;
+        let since = match since {
+            Some(since) => Some(self.place_cursor(since, &posts).await?),
+            None => None,
+        };
+
+        let mut cache = self.timeline.lock().await;
+        if let Some(since) = &since {
+            // Servers that don't know the cursor send the latest page;
+            // trim it here so nothing already shown comes back
+            posts = newer_than(posts, since, &cache);
+        }
+        merge_into_timeline(&mut cache, &posts);
+        Ok(posts)
+    }
+
+    /// A post id cursor that is neither in `page` nor in the cache, e.g.
+    /// after a restart, turned into the time of that post, so a server
+    /// that ignored it can't hand back posts already shown. Errors if the
+    /// post can't be looked up either; reload without the cursor then.
+    async fn place_cursor(&self, since: TimelineSince, page: &[DixPost]) -> Result<TimelineSince, String> {
+        let TimelineSince::PostId(id) = &since else {
+            return Ok(since);
+        };
+        let known = page.iter().any(|p| &p.id == id)
+            || self.timeline.lock().await.iter().any(|p| &p.id == id && created_at_millis(p).is_some());
+        if known {
+            return Ok(since);
         }
-        Ok(wrapper.data.map(|d| d.posts).ok_or("No data returned")?)
+
+        let post = self
+            .get_post(id)
+            .await
+            .map_err(|e| format!("Timeline cursor {} is lost, reload without it: {}", id, e))?
+            .post;
+        created_at_millis(&post)
+            .map(TimelineSince::Timestamp)
+            .ok_or_else(|| format!("Timeline cursor {} is lost, reload without it", id))
     }
 
     pub async fn get_post(&self, post_id: &str) -> Result<DixPostData, String> {
@@ -260,13 +370,7 @@ impl DixService {
             .await
             .map_err(|e| e.to_string())?;
 
-        let wrapper: DixPostResponse = res.json().await.map_err(|e| e.to_string())?;
-        
-        if !wrapper.success {
-             return Err(wrapper.error.unwrap_or("Unknown error".into()));
-        }
-
-        Ok(wrapper.data.ok_or("No data returned")?)
+        ApiClient::read_data(res).await.map_err(|e| e.to_string())
     }
 
     pub async fn like_post(&self, post_id: &str, public_key: &str, signature: &str) -> Result<(), String> {
@@ -286,7 +390,7 @@ impl DixService {
 
         if !response.status().is_success() {
              let error_text = response.text().await.unwrap_or_default();
-             println!("❌ [DIX] Like Error: {}", error_text);
+             tracing::warn!(post_id, error = %error_text, "❌ DIX like failed");
              if error_text.contains("Already liked") {
                  return Ok(());
              }
@@ -313,7 +417,7 @@ impl DixService {
 
         if !response.status().is_success() {
               let error_text = response.text().await.unwrap_or_default();
-              println!("❌ [DIX] Repost Error: {}", error_text);
+              tracing::warn!(post_id, error = %error_text, "❌ DIX repost failed");
               if error_text.contains("Already reposted") {
                   return Ok(());
               }
@@ -323,77 +427,176 @@ impl DixService {
         Ok(())
     }
 
-    pub async fn get_posts_by_user(&self, public_key: &str) -> Result<DixUserData, String> {
-        let base_url = self.api.base_url();
-        let url = format!("{}/web/dix/pk/{}", base_url, public_key);
+    /// Most used tags over the last `window_hours`, with how many posts
+    /// carry each. Servers without `/web/dix/trending` get them counted
+    /// over the cached timeline instead.
+    pub async fn get_trending_tags(&self, window_hours: u32) -> Result<Vec<TrendingTag>, String> {
+        let url = format!("{}/web/dix/trending", self.api.base_url());
 
         let client = reqwest::Client::new();
         let res = client.get(&url)
+            .query(&[("window_hours", window_hours.to_string()), ("limit", TRENDING_TAG_LIMIT.to_string())])
             .send()
             .await
             .map_err(|e| e.to_string())?;
 
-        let wrapper: DixUserResponse = res.json().await.map_err(|e| e.to_string())?;
+        if !matches!(res.status(), reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::NOT_IMPLEMENTED) {
+            let data: DixTrendingData = ApiClient::read_data(res).await.map_err(|e| e.to_string())?;
+            // Whatever case the server counted in, merge into ours
+            let mut tags: Vec<TrendingTag> = Vec::new();
+            for trending in data.tags {
+                let Some(tag) = normalize_tag(&trending.tag) else { continue };
+                match tags.iter_mut().find(|t| t.tag == tag) {
+                    Some(existing) => existing.count += trending.count,
+                    None => tags.push(TrendingTag { tag, count: trending.count }),
+                }
+            }
+            tags.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+            return Ok(tags);
+        }
+
+        tracing::debug!("No trending endpoint, counting tags over the cached timeline");
+        if self.timeline.lock().await.is_empty() {
+            self.get_timeline(TRENDING_FALLBACK_POSTS, 0, None).await?;
+        }
+        let since = chrono::Utc::now().timestamp_millis() - i64::from(window_hours) * 3_600_000;
+        let cache = self.timeline.lock().await;
+        Ok(tags::count_tags(&cache, since, TRENDING_TAG_LIMIT))
+    }
+
+    /// A page of posts carrying `tag`, newest first. Pass the returned
+    /// `next_cursor` to get the page after.
+    pub async fn get_posts_by_tag(&self, tag: &str, cursor: Option<&str>) -> Result<DixTagPage, String> {
+        let tag = normalize_tag(tag).ok_or_else(|| format!("Invalid tag: {}", tag))?;
+        let url = format!("{}/web/dix/tag/{}", self.api.base_url(), tag);
 
-        if !wrapper.success {
-             return Err(wrapper.error.unwrap_or("Unknown error".into()));
+        let client = reqwest::Client::new();
+        let mut request = client.get(&url);
+        if let Some(cursor) = cursor {
+            request = request.query(&[("cursor", cursor)]);
         }
+        let res = request.send().await.map_err(|e| e.to_string())?;
 
-        Ok(wrapper.data.ok_or("No data returned")?)
+        ApiClient::read_data(res).await.map_err(|e| e.to_string())
     }
-}
 
-// Helpers
-#[derive(Deserialize)]
-struct DixResponse {
-    success: bool,
-    data: Option<DixData>,
-    error: Option<String>,
+    pub async fn get_posts_by_user(&self, public_key: &str) -> Result<DixUserData, String> {
+        let base_url = self.api.base_url();
+        let url = format!("{}/web/dix/pk/{}", base_url, public_key);
+
+        let client = reqwest::Client::new();
+        let res = client.get(&url)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        ApiClient::read_data(res).await.map_err(|e| e.to_string())
+    }
 }
 
+// Helpers
 #[derive(Deserialize)]
 struct DixData {
+    #[serde(default)]
     posts: Vec<DixPost>,
 }
 
-#[derive(Deserialize)]
-struct DixPostResponse {
-    success: bool,
-    data: Option<DixPostData>,
-    error: Option<String>,
-}
-
 #[derive(Deserialize, Serialize, Clone)]
 pub struct DixPostData {
     pub post: DixPost,
+    #[serde(default)]
     pub replies: Vec<DixPost>,
-    #[serde(rename = "replyCount")]
+    #[serde(rename = "replyCount", default)]
     pub reply_count: u32,
 }
 
 #[derive(Deserialize)]
-struct DixUserResponse {
-    success: bool,
-    data: Option<DixUserData>,
-    error: Option<String>,
+struct DixTrendingData {
+    #[serde(default)]
+    tags: Vec<TrendingTag>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct DixTagPage {
+    #[serde(default)]
+    pub posts: Vec<DixPost>,
+    /// Absent on the last page
+    #[serde(rename = "nextCursor", default)]
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
 pub struct DixUserData {
     pub user: DixPostAuthor,
+    #[serde(default)]
     pub posts: Vec<DixPost>,
 }
 
+fn created_at_millis(post: &DixPost) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc3339(&post.meta.created_at)
+        .ok()
+        .map(|t| t.timestamp_millis())
+}
+
+/// Posts strictly newer than `since`, newest first. A post id cursor is
+/// placed in time by the cached copy of that post, if there is one, else
+/// only by where it shows up in `posts`.
+fn newer_than(posts: Vec<DixPost>, since: &TimelineSince, cache: &[DixPost]) -> Vec<DixPost> {
+    let (cursor_id, cursor_ms) = match since {
+        TimelineSince::Timestamp(ms) => (None, Some(*ms)),
+        TimelineSince::PostId(id) => (
+            Some(id.as_str()),
+            cache.iter().find(|p| &p.id == id).and_then(created_at_millis),
+        ),
+    };
+
+    let mut newer: Vec<DixPost> = posts
+        .into_iter()
+        .take_while(|p| Some(p.id.as_str()) != cursor_id)
+        .filter(|p| match (cursor_ms, created_at_millis(p)) {
+            (Some(cursor), Some(created)) => created > cursor,
+            (Some(_), None) => false,
+            (None, _) => true,
+        })
+        .collect();
+    newer.sort_by_key(|p| std::cmp::Reverse(created_at_millis(p)));
+    newer
+}
+
+/// Merge `posts` into the cached timeline: newer copies replace cached
+/// ones with the same id, and the cache stays newest first and bounded
+fn merge_into_timeline(cache: &mut Vec<DixPost>, posts: &[DixPost]) {
+    for post in posts {
+        match cache.iter_mut().find(|p| p.id == post.id) {
+            Some(cached) => *cached = post.clone(),
+            None => cache.push(post.clone()),
+        }
+    }
+    cache.sort_by_key(|p| std::cmp::Reverse(created_at_millis(p)));
+    cache.truncate(TIMELINE_CACHE_LIMIT);
+}
+
 fn extract_tags(text: &str) -> Vec<String> {
     // Simple regex replacement
     // In Rust we might need the regex crate, which is in Cargo.toml
     use regex::Regex;
     let re = Regex::new(r"#([a-zA-Z][a-zA-Z0-9_]*)").unwrap();
     re.captures_iter(text)
-        .map(|cap| cap[1].to_string().to_lowercase())
+        .filter_map(|cap| normalize_tag(&cap[1]))
         .collect()
 }
 
+/// Whether a post mentions `handle` (case-insensitive, with or without '@')
+pub fn mentions_handle(post: &DixPost, handle: &str) -> bool {
+    let handle = handle.trim_start_matches('@').to_lowercase();
+    !handle.is_empty()
+        && post
+            .content
+            .mentions
+            .iter()
+            .any(|m| m.trim_start_matches('@').to_lowercase() == handle)
+}
+
 fn extract_mentions(text: &str) -> Vec<String> {
     use regex::Regex;
     let re = Regex::new(r"@([a-zA-Z][a-zA-Z0-9_]*)").unwrap();
@@ -435,3 +638,270 @@ fn generate_canonical_json(value: &serde_json::Value) -> String {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::IDEMPOTENCY_HEADER;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answer one request per status, returning the idempotency key of each
+    async fn serve(listener: TcpListener, statuses: Vec<u16>) -> Vec<Option<String>> {
+        serve_body(listener, statuses, r#"{"success":true}"#.to_string()).await
+    }
+
+    async fn serve_body(listener: TcpListener, statuses: Vec<u16>, body: String) -> Vec<Option<String>> {
+        let mut keys = Vec::new();
+        for status in statuses {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = Vec::new();
+            let mut chunk = [0u8; 4096];
+            let header_end = loop {
+                let n = socket.read(&mut chunk).await.unwrap();
+                buf.extend_from_slice(&chunk[..n]);
+                if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break pos + 4;
+                }
+            };
+
+            let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+            let header = |name: &str| {
+                head.lines()
+                    .filter_map(|l| l.split_once(':'))
+                    .find(|(k, _)| k.trim().eq_ignore_ascii_case(name))
+                    .map(|(_, v)| v.trim().to_string())
+            };
+            let content_length: usize = header("content-length")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0);
+            while buf.len() < header_end + content_length {
+                let n = socket.read(&mut chunk).await.unwrap();
+                buf.extend_from_slice(&chunk[..n]);
+            }
+            keys.push(header(IDEMPOTENCY_HEADER));
+
+            let response = format!(
+                "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+        keys
+    }
+
+    #[tokio::test]
+    async fn test_retried_create_post_reuses_idempotency_key() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(serve(listener, vec![503, 200, 503, 503, 503, 200]));
+
+        let identity = IdentityManager::from_identity(GnsIdentity::generate());
+        let api = Arc::new(ApiClient::new(&base_url).unwrap());
+        let dix = DixService::new(Arc::new(Mutex::new(identity)), api);
+
+        dix.create_post("retry me #test".into(), vec![], vec![], None).await.unwrap();
+
+        // A post that never got through keeps its key pending, but the same
+        // text without its link is another post and gets its own
+        let link = DixLink { url: "https://example.com".into(), title: None, description: None, image: None };
+        assert!(dix.create_post("with a link".into(), vec![], vec![link], None).await.is_err());
+        dix.create_post("with a link".into(), vec![], vec![], None).await.unwrap();
+
+        let keys = server.await.unwrap();
+        assert_eq!(keys.len(), 6);
+        assert!(keys[0].is_some());
+        assert_eq!(keys[0], keys[1]);
+        assert_eq!(keys[2], keys[4]);
+        assert_ne!(keys[4], keys[5]);
+    }
+
+    /// A minimal post as the timeline endpoint returns it
+    fn sample_post(id: &str, author_pk: &str, text: &str, mentions: &[&str]) -> DixPost {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "author": {
+                "publicKey": author_pk,
+                "handle": null,
+                "displayName": null,
+                "avatarUrl": null,
+                "trustScore": 0,
+                "breadcrumbCount": 0,
+                "isVerified": false
+            },
+            "facet": "dix",
+            "content": { "text": text, "mentions": mentions, "location": null },
+            "engagement": { "likes": 0, "replies": 0, "reposts": 0, "quotes": 0, "views": 0 },
+            "meta": {
+                "signature": "",
+                "trustScoreAtPost": 0,
+                "breadcrumbsAtPost": 0,
+                "createdAt": "2025-01-01T00:00:00Z"
+            },
+            "thread": null
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_mentions_handle_is_case_insensitive() {
+        let post = sample_post("p1", "ab", "hey @Alice", &["Alice"]);
+
+        assert!(mentions_handle(&post, "alice"));
+        assert!(mentions_handle(&post, "@ALICE"));
+        assert!(!mentions_handle(&post, "alic"));
+        assert!(!mentions_handle(&post, ""));
+    }
+
+    fn post_at(id: &str, created_at: &str) -> DixPost {
+        let mut post = sample_post(id, "ab", id, &[]);
+        post.meta.created_at = created_at.to_string();
+        post
+    }
+
+    #[test]
+    fn test_newer_than_merges_without_duplicates() {
+        let mut cache = Vec::new();
+        merge_into_timeline(
+            &mut cache,
+            &[post_at("p2", "2025-01-02T00:00:00Z"), post_at("p1", "2025-01-01T00:00:00Z")],
+        );
+
+        // A server that ignores the cursor resends what we already have
+        let page = vec![
+            post_at("p3", "2025-01-03T00:00:00Z"),
+            post_at("p4", "2025-01-04T00:00:00Z"),
+            post_at("p2", "2025-01-02T00:00:00Z"),
+            post_at("p1", "2025-01-01T00:00:00Z"),
+        ];
+        let newer = newer_than(page, &TimelineSince::PostId("p2".into()), &cache);
+        let ids: Vec<_> = newer.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["p4", "p3"]);
+
+        merge_into_timeline(&mut cache, &newer);
+        merge_into_timeline(&mut cache, &newer);
+        let ids: Vec<_> = cache.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["p4", "p3", "p2", "p1"]);
+    }
+
+    #[tokio::test]
+    async fn test_since_with_nothing_newer_is_empty() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let body = serde_json::json!({
+            "success": true,
+            "data": { "posts": [
+                post_at("p2", "2025-01-02T00:00:00Z"),
+                post_at("p1", "2025-01-01T00:00:00Z"),
+            ] }
+        })
+        .to_string();
+        let server = tokio::spawn(serve_body(listener, vec![200, 200, 200], body));
+
+        let identity = IdentityManager::from_identity(GnsIdentity::generate());
+        let api = Arc::new(ApiClient::new(&base_url).unwrap());
+        let dix = DixService::new(Arc::new(Mutex::new(identity)), api);
+
+        assert_eq!(dix.get_timeline(20, 0, None).await.unwrap().len(), 2);
+        let by_id = dix.get_timeline(20, 0, Some(TimelineSince::PostId("p2".into()))).await.unwrap();
+        assert!(by_id.is_empty());
+        let newest_ms = chrono::DateTime::parse_from_rfc3339("2025-01-02T00:00:00Z")
+            .unwrap()
+            .timestamp_millis();
+        let by_time = dix.get_timeline(20, 0, Some(TimelineSince::Timestamp(newest_ms))).await.unwrap();
+        assert!(by_time.is_empty());
+
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unknown_cursor_is_placed_by_its_post() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        // Answers both the timeline and the cursor post lookup; the
+        // timeline ignores the cursor and isn't in our cache
+        let body = serde_json::json!({
+            "success": true,
+            "data": {
+                "posts": [
+                    post_at("p4", "2025-01-04T00:00:00Z"),
+                    post_at("p3", "2025-01-03T00:00:00Z"),
+                    post_at("p1", "2025-01-01T00:00:00Z"),
+                ],
+                "post": post_at("p2", "2025-01-02T00:00:00Z"),
+            }
+        })
+        .to_string();
+        let server = tokio::spawn(serve_body(listener, vec![200, 200, 200, 404], body));
+
+        let identity = IdentityManager::from_identity(GnsIdentity::generate());
+        let api = Arc::new(ApiClient::new(&base_url).unwrap());
+        let dix = DixService::new(Arc::new(Mutex::new(identity)), api);
+
+        let newer = dix.get_timeline(20, 0, Some(TimelineSince::PostId("p2".into()))).await.unwrap();
+        let ids: Vec<_> = newer.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["p4", "p3"]);
+
+        // A cursor that can't be looked up is an error, not the whole page
+        assert!(dix.get_timeline(20, 0, Some(TimelineSince::PostId("gone".into()))).await.is_err());
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_trending_falls_back_to_timeline_without_endpoint() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut posts = vec![
+            sample_post("p1", "ab", "#Rust is #fun", &[]),
+            sample_post("p2", "cd", "more #rust", &[]),
+        ];
+        for post in &mut posts {
+            post.meta.created_at = now.clone();
+        }
+        let body = serde_json::json!({ "success": true, "data": { "posts": posts } }).to_string();
+        // The trending request gets a 404, the timeline request the posts
+        let server = tokio::spawn(serve_body(listener, vec![404, 200], body));
+
+        let identity = IdentityManager::from_identity(GnsIdentity::generate());
+        let api = Arc::new(ApiClient::new(&base_url).unwrap());
+        let dix = DixService::new(Arc::new(Mutex::new(identity)), api);
+
+        let trending = dix.get_trending_tags(24).await.unwrap();
+        assert_eq!(
+            trending,
+            [TrendingTag { tag: "rust".into(), count: 2 }, TrendingTag { tag: "fun".into(), count: 1 }]
+        );
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_following_fallback_paginates_the_filtered_posts() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let (followed, other) = ("ab".repeat(32), "cd".repeat(32));
+        let posts = vec![
+            sample_post("a1", &followed, "one", &[]),
+            sample_post("c1", &other, "not followed", &[]),
+            sample_post("a2", &followed, "two", &[]),
+            sample_post("a3", &followed, "three", &[]),
+        ];
+        let body = serde_json::json!({ "success": true, "data": { "posts": posts } }).to_string();
+        // Each call: the following request gets a 404, the timeline the posts
+        let server = tokio::spawn(serve_body(listener, vec![404, 200, 404, 200], body));
+
+        let identity = IdentityManager::from_identity(GnsIdentity::generate());
+        let api = Arc::new(ApiClient::new(&base_url).unwrap());
+        let dix = DixService::new(Arc::new(Mutex::new(identity)), api);
+        let ids = |posts: Vec<DixPost>| posts.into_iter().map(|p| p.id).collect::<Vec<_>>();
+
+        let following = [followed.clone()];
+        let page = dix.get_following_timeline(&following, 2, 0).await.unwrap();
+        assert_eq!(ids(page), ["a1", "a2"]);
+        let page = dix.get_following_timeline(&following, 2, 2).await.unwrap();
+        assert_eq!(ids(page), ["a3"]);
+        server.await.unwrap();
+    }
+}
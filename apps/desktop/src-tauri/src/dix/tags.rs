@@ -0,0 +1,107 @@
+//! Tags
+//!
+//! Hashtags are stored and queried in one form: lowercase, without the
+//! `#`. Trending tags come from the server; when it has no trending
+//! endpoint they are counted here over the cached timeline.
+
+use super::{created_at_millis, DixPost};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A tag and how many posts in the window carry it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrendingTag {
+    pub tag: String,
+    pub count: u32,
+}
+
+/// `tag` in the form posts carry it: no leading `#`, lowercase, a letter
+/// followed by letters, digits or `_`. `None` if it isn't a valid tag.
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let tag = tag.trim().trim_start_matches('#');
+    let mut chars = tag.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    valid.then(|| tag.to_ascii_lowercase())
+}
+
+/// The tags of `post`, each once. Posts from older clients may list them
+/// in another case, or not at all.
+fn post_tags(post: &DixPost) -> Vec<String> {
+    let mut tags: Vec<String> = if post.content.tags.is_empty() {
+        super::extract_tags(&post.content.text)
+    } else {
+        post.content.tags.iter().filter_map(|t| normalize_tag(t)).collect()
+    };
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// Count tags over the posts created at or after `since_ms`, most used
+/// first (ties alphabetically), at most `limit` of them
+pub fn count_tags(posts: &[DixPost], since_ms: i64, limit: usize) -> Vec<TrendingTag> {
+    let mut counts: HashMap<String, u32> = HashMap::new();
+    for post in posts {
+        if created_at_millis(post).map_or(true, |at| at < since_ms) {
+            continue;
+        }
+        for tag in post_tags(post) {
+            *counts.entry(tag).or_default() += 1;
+        }
+    }
+
+    let mut trending: Vec<TrendingTag> = counts.into_iter().map(|(tag, count)| TrendingTag { tag, count }).collect();
+    trending.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+    trending.truncate(limit);
+    trending
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::extract_tags;
+    use super::*;
+
+    fn post(id: &str, text: &str, tags: &[&str], created_at: &str) -> DixPost {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "author": { "publicKey": "ab", "handle": null, "displayName": null, "avatarUrl": null },
+            "content": { "text": text, "tags": tags, "location": null },
+            "meta": { "signature": "", "createdAt": created_at },
+            "thread": null
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_posting_and_querying_normalize_alike() {
+        assert_eq!(extract_tags("Loving #Rust and #GNS_v2 today"), ["rust", "gns_v2"]);
+        for query in ["rust", "#Rust", " #RUST ", "RuSt"] {
+            assert_eq!(normalize_tag(query).as_deref(), Some("rust"), "{:?}", query);
+        }
+        assert_eq!(normalize_tag("#gns_V2").as_deref(), Some("gns_v2"));
+        for invalid in ["", "#", "2fast", "two words", "#émoji"] {
+            assert_eq!(normalize_tag(invalid), None, "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_fallback_counts_each_post_once_within_window() {
+        let posts = [
+            post("p1", "#rust #Rust #gns", &[], "2025-01-03T00:00:00Z"),
+            // Tags listed by the server win over the text, in any case
+            post("p2", "no hashtags here", &["GNS", "#dix"], "2025-01-02T12:00:00Z"),
+            post("p3", "#dix", &[], "2025-01-02T06:00:00Z"),
+            // Outside the window
+            post("p4", "#rust #old", &[], "2024-12-01T00:00:00Z"),
+            post("p5", "#rust", &[], "not a date"),
+        ];
+        let since = chrono::DateTime::parse_from_rfc3339("2025-01-02T00:00:00Z").unwrap().timestamp_millis();
+
+        let trending = count_tags(&posts, since, 10);
+        let counted: Vec<_> = trending.iter().map(|t| (t.tag.as_str(), t.count)).collect();
+        assert_eq!(counted, [("dix", 2), ("gns", 2), ("rust", 1)]);
+
+        assert_eq!(count_tags(&posts, since, 1).len(), 1);
+    }
+}
@@ -0,0 +1,250 @@
+//! Event Pacing
+//!
+//! A sync storm can produce hundreds of UI events a second, more than the
+//! webview can render without stuttering. High-frequency events go through
+//! an [`EventPacer`] instead of straight to `emit`:
+//!
+//! - `new_message` is held for the batch window. A message that arrives
+//!   alone still goes out as `new_message`; several within the window go
+//!   out together as one `messages_batch` carrying all of them, in order.
+//! - Throttled events, like `engagement_updated`, go out at most once
+//!   per throttle interval for each key. The first one is emitted
+//!   straight away; later ones within the interval replace each other
+//!   and the last is emitted when it runs out.
+//!
+//! Rare events that the UI must see right away, like connectivity
+//! changes, don't go through the pacer at all.
+
+use crate::config::EventPacing;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// Tauri event for a single incoming message
+pub const NEW_MESSAGE_EVENT: &str = "new_message";
+
+/// Tauri event carrying an array of `new_message` payloads
+pub const MESSAGES_BATCH_EVENT: &str = "messages_batch";
+
+/// Most messages in one `messages_batch`; a full batch goes out early
+pub const MAX_BATCH: usize = 256;
+
+enum Paced {
+    Message(serde_json::Value),
+    Throttled {
+        event: &'static str,
+        key: String,
+        payload: serde_json::Value,
+    },
+}
+
+/// Queues events for pacing. Cheap to clone.
+#[derive(Clone)]
+pub struct EventPacer {
+    events: mpsc::UnboundedSender<Paced>,
+}
+
+/// Emits what the pacer queues; see [`EventPacerTask::run`]
+pub struct EventPacerTask {
+    events: mpsc::UnboundedReceiver<Paced>,
+    batch_window: Duration,
+    throttle: Duration,
+}
+
+/// Throttle state of one event and key
+struct Throttled {
+    last_emitted: Instant,
+    /// Newest payload held back since then
+    pending: Option<serde_json::Value>,
+}
+
+impl EventPacer {
+    /// Nothing is emitted until the task is spawned
+    pub fn new(pacing: EventPacing) -> (Self, EventPacerTask) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (
+            Self { events: tx },
+            EventPacerTask {
+                events: rx,
+                batch_window: Duration::from_millis(pacing.batch_window_ms),
+                throttle: Duration::from_millis(pacing.throttle_ms),
+            },
+        )
+    }
+
+    /// Queue a `new_message` payload for the next batch
+    pub fn message<T: Serialize>(&self, payload: &T) {
+        self.queue(payload, Paced::Message);
+    }
+
+    /// Queue `event`, keeping only the newest payload per `key` within a
+    /// throttle interval. Use one key for state that replaces itself (a
+    /// balance) and one per item for per-item updates (a post's counts).
+    pub fn throttled<T: Serialize>(&self, event: &'static str, key: impl Into<String>, payload: &T) {
+        let key = key.into();
+        self.queue(payload, |payload| Paced::Throttled { event, key, payload });
+    }
+
+    fn queue<T: Serialize>(&self, payload: &T, paced: impl FnOnce(serde_json::Value) -> Paced) {
+        match serde_json::to_value(payload) {
+            Ok(payload) => {
+                let _ = self.events.send(paced(payload));
+            }
+            Err(e) => tracing::error!("Failed to serialize event payload: {}", e),
+        }
+    }
+}
+
+impl EventPacerTask {
+    /// Emit queued events until every `EventPacer` is dropped, then flush
+    /// whatever is still held back.
+    pub async fn run<F>(mut self, mut emit: F)
+    where
+        F: FnMut(&'static str, serde_json::Value),
+    {
+        let mut batch: Vec<serde_json::Value> = Vec::new();
+        let mut batch_deadline: Option<Instant> = None;
+        let mut throttled: HashMap<(&'static str, String), Throttled> = HashMap::new();
+
+        loop {
+            let throttle_deadline = throttled
+                .values()
+                .filter(|t| t.pending.is_some())
+                .map(|t| t.last_emitted + self.throttle)
+                .min();
+            let deadline = match (batch_deadline, throttle_deadline) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+
+            let paced = tokio::select! {
+                paced = self.events.recv() => match paced {
+                    Some(paced) => Some(paced),
+                    None => break,
+                },
+                _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => None,
+            };
+            let now = Instant::now();
+
+            match paced {
+                Some(Paced::Message(payload)) => {
+                    batch.push(payload);
+                    batch_deadline.get_or_insert(now + self.batch_window);
+                }
+                Some(Paced::Throttled { event, key, payload }) => match throttled.get_mut(&(event, key.clone())) {
+                    Some(state) if now < state.last_emitted + self.throttle => state.pending = Some(payload),
+                    _ => {
+                        emit(event, payload);
+                        throttled.insert((event, key), Throttled { last_emitted: now, pending: None });
+                    }
+                },
+                None => {}
+            }
+
+            if batch_deadline.is_some_and(|at| at <= now) || batch.len() >= MAX_BATCH {
+                emit_batch(std::mem::take(&mut batch), &mut emit);
+                batch_deadline = None;
+            }
+
+            let throttle = self.throttle;
+            throttled.retain(|(event, _), state| {
+                if now < state.last_emitted + throttle {
+                    return true;
+                }
+                match state.pending.take() {
+                    Some(payload) => {
+                        emit(event, payload);
+                        state.last_emitted = now;
+                        true
+                    }
+                    // Quiet for a whole interval; the next one goes out at once
+                    None => false,
+                }
+            });
+        }
+
+        emit_batch(batch, &mut emit);
+        for ((event, _), state) in throttled {
+            if let Some(payload) = state.pending {
+                emit(event, payload);
+            }
+        }
+    }
+}
+
+fn emit_batch<F>(mut batch: Vec<serde_json::Value>, emit: &mut F)
+where
+    F: FnMut(&'static str, serde_json::Value),
+{
+    match batch.len() {
+        0 => {}
+        1 => emit(NEW_MESSAGE_EVENT, batch.remove(0)),
+        _ => emit(MESSAGES_BATCH_EVENT, serde_json::Value::Array(batch)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rapid_messages_collapse_into_one_batch() {
+        let pacing = EventPacing { batch_window_ms: 100, throttle_ms: 500 };
+        let (pacer, task) = EventPacer::new(pacing);
+        let emitted = Arc::new(Mutex::new(Vec::new()));
+        let sink = emitted.clone();
+        tokio::spawn(task.run(move |event, payload| sink.lock().unwrap().push((event, payload))));
+
+        let step = Duration::from_millis(10);
+        for i in 0..5 {
+            pacer.message(&json!({ "id": i }));
+            tokio::time::sleep(step).await;
+        }
+        assert!(emitted.lock().unwrap().is_empty());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // A lone message later on keeps the plain event
+        pacer.message(&json!({ "id": 5 }));
+        tokio::time::sleep(Duration::from_millis(150)).await;
+
+        assert_eq!(
+            *emitted.lock().unwrap(),
+            vec![
+                (MESSAGES_BATCH_EVENT, json!([{ "id": 0 }, { "id": 1 }, { "id": 2 }, { "id": 3 }, { "id": 4 }])),
+                (NEW_MESSAGE_EVENT, json!({ "id": 5 })),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_throttled_events_keep_first_and_latest_per_key() {
+        let pacing = EventPacing { batch_window_ms: 100, throttle_ms: 500 };
+        let (pacer, task) = EventPacer::new(pacing);
+        let emitted = Arc::new(Mutex::new(Vec::new()));
+        let sink = emitted.clone();
+        tokio::spawn(task.run(move |event, payload| sink.lock().unwrap().push((event, payload))));
+
+        for likes in 1..=10 {
+            pacer.throttled("engagement_updated", "p1", &json!({ "postId": "p1", "likes": likes }));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        // Other keys are throttled separately
+        pacer.throttled("engagement_updated", "p2", &json!({ "postId": "p2", "likes": 1 }));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(emitted.lock().unwrap().len(), 2);
+
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert_eq!(
+            *emitted.lock().unwrap(),
+            vec![
+                ("engagement_updated", json!({ "postId": "p1", "likes": 1 })),
+                ("engagement_updated", json!({ "postId": "p2", "likes": 1 })),
+                ("engagement_updated", json!({ "postId": "p1", "likes": 10 })),
+            ]
+        );
+    }
+}
@@ -1,13 +1,33 @@
 //! Home Service - GNS Home Hub Integration
 //! 
 //! Handles discovery and communication with GNS Home Hubs (IoT Gateways).
+//!
+//! Hubs sign every response body with their key in the `Hub` signature
+//! domain. The key a hub advertises the first time we use it is pinned
+//! (see `storage::hubs`), and responses not signed with the pinned key are
+//! rejected.
 
 use crate::crypto::{IdentityManager};
+use crate::network::{ApiClient, ApiEnvelope};
+use crate::storage::Database;
+use crate::stellar::CancelToken;
+use futures::stream::{FuturesUnordered, Stream, StreamExt};
+use gns_crypto_core::{verify_in_domain_hex, DomainPolicy, SignatureDomain};
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::future::Future;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
 use mdns_sd::{ServiceDaemon, ServiceEvent};
 
+/// mDNS service type Home Hubs advertise
+const HUB_SERVICE_TYPE: &str = "_gns-home._tcp.local.";
+
+/// Response header carrying the hub's hex signature over the body
+pub const HUB_SIGNATURE_HEADER: &str = "X-GNS-Hub-Signature";
+
 // ===========================================
 // MODELS
 // ===========================================
@@ -18,7 +38,7 @@ pub struct HubInfo {
     #[serde(rename = "publicKey")]
     pub public_key: String,
     pub owner: Option<String>,
-    #[serde(rename = "deviceCount")]
+    #[serde(rename = "deviceCount", default)]
     pub device_count: usize,
     pub version: String,
     // Added for discovery context
@@ -34,6 +54,7 @@ pub struct HomeDevice {
     pub device_type: String,
     pub brand: String,
     pub protocol: String,
+    #[serde(default)]
     pub capabilities: Vec<String>,
     pub status: DeviceStatus,
 }
@@ -59,60 +80,82 @@ pub struct CommandResult {
 
 pub struct HomeService {
     identity: Arc<Mutex<IdentityManager>>,
+    database: Arc<Mutex<Database>>,
+    /// Stops the discovery in progress, if any
+    discovery: std::sync::Mutex<Option<CancelToken>>,
+}
+
+/// Shuts the mDNS daemon down however discovery ends, including the
+/// discovering future being dropped
+struct DaemonGuard(ServiceDaemon);
+
+impl Drop for DaemonGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.0.shutdown() {
+            tracing::debug!("mDNS daemon did not shut down cleanly: {}", e);
+        }
+    }
 }
 
 impl HomeService {
-    pub fn new(identity: Arc<Mutex<IdentityManager>>) -> Self {
-        Self { identity }
-    }
-
-    /// Discover GNS Home Hubs on the local network via mDNS
-    pub async fn discover_hubs(&self, timeout_ms: u64) -> Result<Vec<HubInfo>, String> {
-        let mdns = ServiceDaemon::new().map_err(|e| format!("Failed to create mDNS daemon: {}", e))?;
-        let service_type = "_gns-home._tcp.local.";
-        let receiver = mdns.browse(service_type).map_err(|e| format!("Failed to browse: {}", e))?;
-
-        let mut hubs = Vec::new();
-        let end_time = std::time::Instant::now() + std::time::Duration::from_millis(timeout_ms);
-
-        while std::time::Instant::now() < end_time {
-            // Non-blocking try_recv or similar? 
-            // The receiver is blocking but we can use recv_timeout if implemented, 
-            // or just simple loop with sleep and check.
-            // mdns-sd receiver is a channel.
-            
-            // For now, we'll collect for `timeout_ms` duration.
-            match receiver.recv_timeout(std::time::Duration::from_millis(100)) {
-                Ok(event) => {
-                    match event {
-                        ServiceEvent::ServiceResolved(info) => {
-                            let ip = info.get_addresses().iter().next();
-                            let port = info.get_port();
-                            
-                            if let Some(ip) = ip {
-                                let url = format!("http://{}:{}", ip, port);
-                                
-                                // Try to fetch hub info from the discovered URL
-                                if let Ok(hub_info) = self.fetch_hub_info(&url).await {
-                                     let mut final_info = hub_info;
-                                     final_info.url = Some(url);
-                                     hubs.push(final_info);
-                                }
-                            }
-                        },
-                        _ => {}
-                    }
-                },
-                Err(_) => {
-                    // Timeout on recv, continue loop
-                }
-            }
+    pub fn new(identity: Arc<Mutex<IdentityManager>>, database: Arc<Mutex<Database>>) -> Self {
+        Self {
+            identity,
+            database,
+            discovery: std::sync::Mutex::new(None),
         }
-        
+    }
+
+    /// Discover GNS Home Hubs on the local network via mDNS.
+    ///
+    /// Browses for up to `timeout_ms`, returning early once `max_hubs`
+    /// hubs have answered or `stop_discovery` is called. Starting a new
+    /// discovery stops the previous one.
+    pub async fn discover_hubs(&self, timeout_ms: u64, max_hubs: Option<usize>) -> Result<Vec<HubInfo>, String> {
+        let cancel = CancelToken::new();
+        if let Some(previous) = self.discovery.lock().unwrap().replace(cancel.clone()) {
+            previous.cancel();
+        }
+
+        let mdns = DaemonGuard(
+            ServiceDaemon::new().map_err(|e| format!("Failed to create mDNS daemon: {}", e))?,
+        );
+        let receiver = mdns.0.browse(HUB_SERVICE_TYPE).map_err(|e| format!("Failed to browse: {}", e))?;
+
+        let urls = receiver.into_stream().filter_map(|event| async move {
+            match event {
+                ServiceEvent::ServiceResolved(info) => info
+                    .get_addresses()
+                    .iter()
+                    .next()
+                    .map(|ip| format!("http://{}:{}", ip, info.get_port())),
+                _ => None,
+            }
+        });
+
+        let hubs = collect_hubs(
+            Box::pin(urls),
+            |url| async move { self.fetch_hub_info(&url).await },
+            Duration::from_millis(timeout_ms),
+            max_hubs,
+            &cancel,
+        )
+        .await;
+        drop(mdns);
+
+        tracing::info!("🏠 Discovered {} hub(s)", hubs.len());
         Ok(hubs)
     }
 
-    /// Fetch Info from a Hub URL
+    /// Stop the discovery in progress; it returns the hubs found so far
+    pub fn stop_discovery(&self) {
+        if let Some(cancel) = self.discovery.lock().unwrap().take() {
+            cancel.cancel();
+        }
+    }
+
+    /// Fetch Info from a Hub URL. The response must be signed by the key
+    /// it advertises, and by the pinned key if the hub is paired.
     pub async fn fetch_hub_info(&self, base_url: &str) -> Result<HubInfo, String> {
         let url = format!("{}/api/hub", base_url);
         let client = reqwest::Client::new();
@@ -123,13 +166,33 @@ impl HomeService {
             .await
             .map_err(|e| e.to_string())?;
 
-        let wrapper: ApiResponse<HubInfo> = res.json().await.map_err(|e| e.to_string())?;
-        
-        if !wrapper.success {
-            return Err("Failed to get hub info".into());
+        let response = SignedResponse::read(res).await?;
+        let pinned = self.database.lock().await.paired_hub_key(base_url).map_err(|e| e.to_string())?;
+        verified_hub_info(&response, pinned.as_deref())
+    }
+
+    /// Key pinned for the hub at `base_url`, pairing with it first if we
+    /// haven't yet
+    async fn hub_key(&self, base_url: &str) -> Result<String, String> {
+        if let Some(key) = self.database.lock().await.paired_hub_key(base_url).map_err(|e| e.to_string())? {
+            return Ok(key);
         }
-        
-        Ok(wrapper.data.ok_or("No data returned")?)
+
+        let hub = self.fetch_hub_info(base_url).await?;
+        let key = self
+            .database
+            .lock()
+            .await
+            .pair_hub(base_url, &hub.public_key, &hub.name)
+            .map_err(|e| e.to_string())?;
+        tracing::info!("🏠 Paired with hub \"{}\" at {}", hub.name, base_url);
+        Ok(key)
+    }
+
+    /// Unpin the hub at `base_url`, e.g. after it was reset and has a new
+    /// key. Returns false if it wasn't paired.
+    pub async fn forget_hub(&self, base_url: &str) -> Result<bool, String> {
+        self.database.lock().await.forget_hub(base_url).map_err(|e| e.to_string())
     }
     
     /// Get Devices
@@ -137,6 +200,7 @@ impl HomeService {
         let identity = self.identity.lock().await;
         let public_key = identity.public_key_hex().ok_or("No identity")?;
         drop(identity);
+        let hub_key = self.hub_key(base_url).await?;
 
         let url = format!("{}/api/devices", base_url);
         let client = reqwest::Client::new();
@@ -147,13 +211,10 @@ impl HomeService {
             .await
             .map_err(|e| e.to_string())?;
 
-        let wrapper: ApiResponse<Vec<HomeDevice>> = res.json().await.map_err(|e| e.to_string())?;
-        
-        if !wrapper.success {
-             return Err("Failed to get devices".into());
-        }
-        
-        Ok(wrapper.data.ok_or("No data returned")?)
+        let response = SignedResponse::read(res).await?;
+        let body = response.verified_body(&hub_key)?;
+        ApiClient::data_from_body(response.status, response.content_type.as_deref(), body)
+            .map_err(|e| format!("Failed to get devices: {}", e))
     }
 
     /// Execute Command
@@ -162,6 +223,7 @@ impl HomeService {
         let public_key = identity.public_key_hex().ok_or("No identity")?;
         // In real impl, we should sign the command here too
         drop(identity);
+        let hub_key = self.hub_key(base_url).await?;
 
         let url = format!("{}/api/command", base_url);
         let client = reqwest::Client::new();
@@ -179,7 +241,11 @@ impl HomeService {
             .await
             .map_err(|e| e.to_string())?;
 
-        let wrapper: ApiResponse<serde_json::Value> = res.json().await.map_err(|e| e.to_string())?;
+        let response = SignedResponse::read(res).await?;
+        let body = response.verified_body(&hub_key)?;
+        let wrapper: ApiEnvelope<serde_json::Value> =
+            ApiClient::envelope_from_body(response.status, response.content_type.as_deref(), body)
+                .map_err(|e| e.to_string())?;
         
         // The endpoint returns success: true/false in the wrapper wrapper?
         // Wait, HomeService.dart says:
@@ -197,10 +263,236 @@ impl HomeService {
     }
 }
 
-// Helper wrapper for standardize API responses
-#[derive(Deserialize)]
-struct ApiResponse<T> {
-    success: bool,
-    data: Option<T>,
-    error: Option<String>,
+/// A hub response read in full, with its signature header
+struct SignedResponse {
+    status: StatusCode,
+    content_type: Option<String>,
+    body: String,
+    signature: Option<String>,
+}
+
+impl SignedResponse {
+    async fn read(res: reqwest::Response) -> Result<Self, String> {
+        let header = |name| res.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+        let (status, content_type, signature) =
+            (res.status(), header(reqwest::header::CONTENT_TYPE.as_str()), header(HUB_SIGNATURE_HEADER));
+        let body = res.text().await.map_err(|e| e.to_string())?;
+        Ok(Self { status, content_type, body, signature })
+    }
+
+    /// The body, if it is signed by `hub_key`
+    fn verified_body(&self, hub_key: &str) -> Result<&str, String> {
+        let signature = self.signature.as_deref().ok_or("Hub response is not signed")?;
+        match verify_in_domain_hex(hub_key, SignatureDomain::Hub, self.body.as_bytes(), signature, DomainPolicy::Strict) {
+            Ok(true) => Ok(&self.body),
+            _ => Err("Hub response is not signed by the paired hub".to_string()),
+        }
+    }
+}
+
+/// Read `/api/hub`, checking it is signed by the key it advertises and
+/// that this is the key `pinned`, if any
+fn verified_hub_info(response: &SignedResponse, pinned: Option<&str>) -> Result<HubInfo, String> {
+    let hub: HubInfo = ApiClient::data_from_body(response.status, response.content_type.as_deref(), &response.body)
+        .map_err(|e| format!("Failed to get hub info: {}", e))?;
+    if let Some(pinned) = pinned {
+        if !hub.public_key.eq_ignore_ascii_case(pinned) {
+            return Err(format!(
+                "Hub \"{}\" advertises a different key than the one paired; forget the hub to pair again",
+                hub.name
+            ));
+        }
+    }
+    response.verified_body(&hub.public_key)?;
+    Ok(hub)
+}
+
+/// Ask each hub URL for its info as it resolves, all at once, until
+/// `timeout`, `max_hubs` answers, cancellation, or the URLs run out and
+/// every fetch has finished
+async fn collect_hubs<S, F, Fut>(
+    mut urls: S,
+    fetch: F,
+    timeout: Duration,
+    max_hubs: Option<usize>,
+    cancel: &CancelToken,
+) -> Vec<HubInfo>
+where
+    S: Stream<Item = String> + Unpin,
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<HubInfo, String>>,
+{
+    let deadline = tokio::time::sleep(timeout);
+    tokio::pin!(deadline);
+
+    let mut hubs = Vec::new();
+    let mut seen = HashSet::new();
+    let mut fetches = FuturesUnordered::new();
+    let mut browsing = true;
+
+    while !matches!(max_hubs, Some(max) if hubs.len() >= max) && (browsing || !fetches.is_empty()) {
+        tokio::select! {
+            _ = &mut deadline => break,
+            _ = cancel.cancelled() => break,
+            url = urls.next(), if browsing => match url {
+                // A hub is resolved again for each of its addresses
+                Some(url) if seen.insert(url.clone()) => {
+                    let fetched = fetch(url.clone());
+                    fetches.push(async move { (url, fetched.await) });
+                }
+                Some(_) => {}
+                None => browsing = false,
+            },
+            Some((url, fetched)) = fetches.next(), if !fetches.is_empty() => match fetched {
+                Ok(mut hub) => {
+                    hub.url = Some(url);
+                    hubs.push(hub);
+                }
+                Err(e) => tracing::debug!("Ignoring hub at {}: {}", url, e),
+            },
+        }
+    }
+
+    hubs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+    use gns_crypto_core::GnsIdentity;
+
+    fn signed(signer: &GnsIdentity, body: &str) -> SignedResponse {
+        SignedResponse {
+            status: StatusCode::OK,
+            content_type: Some("application/json".to_string()),
+            body: body.to_string(),
+            signature: Some(hex::encode(signer.sign_in_domain(SignatureDomain::Hub, body.as_bytes()))),
+        }
+    }
+
+    fn hub_info_body(hub: &GnsIdentity) -> String {
+        serde_json::json!({
+            "success": true,
+            "data": { "name": "Living room", "publicKey": hub.public_key_hex(), "version": "1.2.0", "deviceCount": 3 }
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_signed_hub_response() {
+        let hub = GnsIdentity::generate();
+        let body = r#"{"success":true,"data":{"result":"on"}}"#;
+        let response = signed(&hub, body);
+        assert_eq!(response.verified_body(&hub.public_key_hex()).unwrap(), body);
+
+        let info = verified_hub_info(&signed(&hub, &hub_info_body(&hub)), None).unwrap();
+        assert_eq!((info.name.as_str(), info.device_count), ("Living room", 3));
+        // Still fine once paired, whatever the hex case
+        let pinned = hub.public_key_hex().to_uppercase();
+        assert!(verified_hub_info(&signed(&hub, &hub_info_body(&hub)), Some(&pinned)).is_ok());
+    }
+
+    #[test]
+    fn test_forged_hub_response() {
+        let hub = GnsIdentity::generate();
+        let rogue = GnsIdentity::generate();
+        let body = r#"{"success":true,"data":{"result":"unlocked"}}"#;
+
+        // Signed by someone else, unsigned, tampered with, or signed
+        // outside the hub domain
+        assert!(signed(&rogue, body).verified_body(&hub.public_key_hex()).is_err());
+        let unsigned = SignedResponse { signature: None, ..signed(&hub, body) };
+        assert!(unsigned.verified_body(&hub.public_key_hex()).unwrap_err().contains("not signed"));
+        let tampered = SignedResponse { body: body.replace("unlocked", "locked"), ..signed(&hub, body) };
+        assert!(tampered.verified_body(&hub.public_key_hex()).is_err());
+        let undomained = SignedResponse {
+            signature: Some(hex::encode(hub.sign_bytes(body.as_bytes()))),
+            ..signed(&hub, body)
+        };
+        assert!(undomained.verified_body(&hub.public_key_hex()).is_err());
+
+        // A rogue device advertising its own key is consistent, but not
+        // the hub we paired with
+        let impostor = signed(&rogue, &hub_info_body(&rogue));
+        assert!(verified_hub_info(&impostor, None).is_ok());
+        let err = verified_hub_info(&impostor, Some(&hub.public_key_hex())).unwrap_err();
+        assert!(err.contains("different key"), "{}", err);
+
+        // Advertising the hub's key without being able to sign with it
+        let claimed = signed(&rogue, &hub_info_body(&hub));
+        assert!(verified_hub_info(&claimed, Some(&hub.public_key_hex())).is_err());
+        assert!(verified_hub_info(&claimed, None).is_err());
+    }
+
+    fn hub(name: &str) -> HubInfo {
+        HubInfo {
+            name: name.to_string(),
+            public_key: String::new(),
+            owner: None,
+            device_count: 0,
+            version: "1.0.0".to_string(),
+            url: None,
+        }
+    }
+
+    /// URLs that resolve at once, followed by a browse that never ends
+    fn urls(count: usize) -> impl Stream<Item = String> + Unpin {
+        stream::iter((0..count).map(|i| format!("http://10.0.0.{}:8080", i))).chain(stream::pending())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_returns_when_max_hubs_reached() {
+        let started = tokio::time::Instant::now();
+        let hubs = collect_hubs(
+            urls(5),
+            |url| async move {
+                // Slower hubs answer later; fetched together, the first two
+                // answer after a second, not after one second each
+                let delay = if url.ends_with(".3:8080") { 10 } else { 1 };
+                tokio::time::sleep(Duration::from_secs(delay)).await;
+                Ok(hub(&url))
+            },
+            Duration::from_secs(60),
+            Some(2),
+            &CancelToken::new(),
+        )
+        .await;
+
+        assert_eq!(hubs.len(), 2);
+        assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+        assert!(hubs.iter().all(|h| h.url.as_deref() == Some(h.name.as_str())));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stops_on_timeout_or_cancel() {
+        // A hub that never answers doesn't hold up the timeout
+        let started = tokio::time::Instant::now();
+        let hubs = collect_hubs(
+            urls(3),
+            |url| async move {
+                if url.ends_with(".0:8080") {
+                    std::future::pending::<()>().await;
+                }
+                Ok(hub(&url))
+            },
+            Duration::from_secs(5),
+            None,
+            &CancelToken::new(),
+        )
+        .await;
+        assert_eq!(hubs.len(), 2);
+        assert!(started.elapsed() >= Duration::from_secs(5) && started.elapsed() < Duration::from_secs(6));
+
+        let cancel = CancelToken::new();
+        let stop = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            stop.cancel();
+        });
+        let started = tokio::time::Instant::now();
+        let hubs = collect_hubs(urls(1), |url| async move { Ok(hub(&url)) }, Duration::from_secs(60), None, &cancel).await;
+        assert_eq!(hubs.len(), 1);
+        assert!(started.elapsed() >= Duration::from_secs(1) && started.elapsed() < Duration::from_secs(2));
+    }
 }
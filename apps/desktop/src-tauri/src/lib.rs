@@ -1,12 +1,13 @@
 //! GNS Browser - Shared Library for Desktop and Mobile
 
 use std::sync::Arc;
-use tauri::Manager;
-use tokio::sync::Mutex;
+use tauri::{Emitter, Manager};
+use tokio::sync::{mpsc, watch, Mutex};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 // Re-export modules
 pub mod commands;
+pub mod config;
 pub mod crypto;
 pub mod location;
 pub mod message_handler;
@@ -15,12 +16,37 @@ pub mod stellar;
 pub mod storage;
 pub mod dix;
 pub mod home;
+pub mod settings;
+pub mod profile;
+pub mod devices;
+pub mod services;
+pub mod scheduler;
+pub mod diagnostics;
+pub mod read_sync;
+pub mod replay_guard;
+pub mod thread_meta;
+pub mod cache_rebuild;
+pub mod record_sync;
+pub mod notifications;
+pub mod debug_snapshot;
+pub mod events;
+pub mod migration_handoff;
 
-use crate::crypto::IdentityManager;
-use crate::network::{ApiClient, RelayConnection};
-use crate::stellar::StellarService;
+use crate::config::{DesktopConfig, CONFIG_KEY};
+use crate::crypto::{IdentityManager, RatchetSessions};
+use crate::debug_snapshot::ErrorLog;
+use crate::events::EventPacer;
+use crate::network::{
+    ApiClient, Connectivity, ConnectivityMonitor, IdempotencyKeys, IncomingMessage, RelayConnection,
+    RelayShutdown, CONNECTIVITY_EVENT, RELAY_READY_EVENT,
+};
+use crate::read_sync::ReadSync;
+use crate::scheduler::{SendLater, SCHEDULED_SENT_EVENT};
+use crate::services::{ServiceGate, ServicesStatus};
+use crate::settings::Endpoints;
+use crate::stellar::{Operations, StellarNetwork, StellarService};
 use crate::storage::Database;
-use crate::dix::DixService;
+use crate::dix::{DixService, ENGAGEMENT_UPDATED_EVENT};
 use crate::home::HomeService;
 
 #[cfg(any(target_os = "ios", target_os = "android"))]
@@ -28,42 +54,155 @@ use crate::location::BreadcrumbCollector;
 
 /// Application state shared across all commands
 pub struct AppState {
+    /// Defaults from tauri.conf.json, under runtime setting overrides
+    pub config: DesktopConfig,
     pub identity: Arc<Mutex<IdentityManager>>,
     pub database: Arc<Mutex<Database>>,
     pub api: Arc<ApiClient>,
     pub relay: Arc<Mutex<RelayConnection>>,
-    pub stellar: Arc<Mutex<StellarService>>,
+    /// Cancels relay reconnects without taking the relay lock
+    pub relay_shutdown: RelayShutdown,
+    /// Key the relay has confirmed a subscription for, without the relay lock
+    pub relay_ready: watch::Receiver<Option<String>>,
+    /// Cached online/offline state, fed by the relay and API client
+    pub connectivity: Connectivity,
+    /// Swapped out whole on a network change; clone the service out rather
+    /// than holding the lock across network calls
+    pub stellar: Arc<Mutex<Arc<StellarService>>>,
+    /// Sends and claims the UI can still cancel
+    pub operations: Operations,
     pub dix: Arc<DixService>,
     pub home: Arc<HomeService>,
+    /// Whether the network-bound services in `services` have been started
+    pub services: ServiceGate,
+    /// Wakes the send-later scheduler when the schedule changes
+    pub send_later: Arc<SendLater>,
+    /// Wakes the read-state sync when a thread is marked read
+    pub read_sync: Arc<ReadSync>,
+    /// Recent warnings and errors, for debug snapshots
+    pub errors: ErrorLog,
+    /// Batches and throttles high-frequency UI events
+    pub events: EventPacer,
+    /// Created when services start, not at launch
     #[cfg(any(target_os = "ios", target_os = "android"))]
-    pub breadcrumb_collector: Arc<Mutex<BreadcrumbCollector>>,
+    pub breadcrumb_collector: Arc<Mutex<Option<BreadcrumbCollector>>>,
 }
 
-/// Initialize application state
-fn setup_app_state() -> Result<AppState, Box<dyn std::error::Error>> {
-    let database = Arc::new(Mutex::new(Database::open()?));
-    let identity = Arc::new(Mutex::new(IdentityManager::new()?));
-    let api = Arc::new(ApiClient::new("https://gns-browser-production.up.railway.app")?);
-    let relay = Arc::new(Mutex::new(RelayConnection::new("wss://gns-browser-production.up.railway.app")?));
-    let stellar = Arc::new(Mutex::new(StellarService::mainnet()));
+/// Initialize application state from `config`.
+///
+/// Nothing here touches the network; see `services::initialize` for what
+/// starts once there is an identity. The returned monitor drives
+/// `AppState::connectivity` and must be spawned; the receiver gets what
+/// the relay delivers and goes to the message handler.
+#[allow(clippy::type_complexity)]
+fn setup_app_state(
+    config: DesktopConfig,
+    errors: ErrorLog,
+    events: EventPacer,
+) -> Result<(AppState, ConnectivityMonitor, mpsc::Receiver<IncomingMessage>), Box<dyn std::error::Error>> {
+    let db = Database::open()?;
+    let endpoints = Endpoints::load(&db, &config);
+    let stellar_network = settings::load_stellar_network(&db, &config);
+    tracing::info!("Using API {} / relay {}", endpoints.api_url, endpoints.relay_url);
+    if stellar_network == StellarNetwork::Testnet {
+        tracing::warn!("⚠️ Stellar is on TESTNET - balances are not real funds");
+    }
+
+    let mut identity_mgr = IdentityManager::new()?
+        .with_message_key_ttl(config.message_key_ttl())
+        .with_tagged_signatures(config.tagged_signatures)
+        .with_compression(config.compression);
+    if config.forward_secrecy {
+        let sessions = match identity_mgr.public_key_hex().map(|pk| db.load_ratchet_sessions(&pk)) {
+            Some(Ok(sessions)) => sessions,
+            Some(Err(e)) => {
+                tracing::error!("Failed to load ratchet sessions, negotiating new ones: {}", e);
+                RatchetSessions::new()
+            }
+            None => RatchetSessions::new(),
+        };
+        identity_mgr = identity_mgr.with_forward_secrecy(sessions);
+        tracing::info!("🔐 Forward secrecy is on");
+    }
+
+    let pending_keys = db.load_idempotency_keys().unwrap_or_else(|e| {
+        tracing::error!("Failed to load pending idempotency keys, starting without them: {}", e);
+        Default::default()
+    });
+
+    let database = Arc::new(Mutex::new(db));
+    let identity = Arc::new(Mutex::new(identity_mgr));
+    let (connectivity, connectivity_monitor) = Connectivity::new();
+    let api = Arc::new(
+        ApiClient::with_timeout(&endpoints.api_url, config.network_timeout())?
+            .with_connectivity(connectivity.clone())
+            .with_idempotency_keys(IdempotencyKeys::persisted(database.clone(), pending_keys)),
+    );
+    let (incoming_tx, incoming_rx) = mpsc::channel(256);
+    let relay = RelayConnection::new(&endpoints.relay_url)?
+        .with_connectivity(connectivity.clone())
+        .with_reconnect_policy(config.reconnect)
+        .with_incoming_channel(incoming_tx);
+    let relay_shutdown = relay.shutdown_handle();
+    let relay_ready = relay.readiness();
+    let relay = Arc::new(Mutex::new(relay));
+
+    let mut stellar_config = stellar_network.config();
+    stellar_config.horizon_url = endpoints.horizon_url;
+    let stellar = Arc::new(Mutex::new(Arc::new(StellarService::new(stellar_config).with_client(api.client().clone()))));
 
     let dix = Arc::new(DixService::new(identity.clone(), api.clone()));
-    let home = Arc::new(HomeService::new(identity.clone()));
+    let home = Arc::new(HomeService::new(identity.clone(), database.clone()));
 
     #[cfg(any(target_os = "ios", target_os = "android"))]
-    let breadcrumb_collector = Arc::new(Mutex::new(BreadcrumbCollector::new()));
+    let breadcrumb_collector = Arc::new(Mutex::new(None));
 
-    Ok(AppState {
+    Ok((AppState {
+        config,
         identity,
         database,
         api,
         relay,
+        relay_shutdown,
+        relay_ready,
+        connectivity,
         stellar,
+        operations: Operations::default(),
         dix,
         home,
+        services: ServiceGate::default(),
+        send_later: Arc::new(SendLater::default()),
+        read_sync: Arc::new(ReadSync::default()),
+        errors,
+        events,
         #[cfg(any(target_os = "ios", target_os = "android"))]
         breadcrumb_collector,
-    })
+    }, connectivity_monitor, incoming_rx))
+}
+
+/// Close connections and persist state before the process exits.
+///
+/// Safe to run more than once; both the last window closing and the
+/// final exit event end up here.
+async fn shutdown_app_state(state: &AppState) {
+    // Stop a pending reconnect first - it holds the relay lock while it backs off
+    state.relay_shutdown.trigger();
+    state.relay.lock().await.shutdown().await;
+
+    #[cfg(any(target_os = "ios", target_os = "android"))]
+    if let Some(collector) = state.breadcrumb_collector.lock().await.as_mut() {
+        collector.stop();
+    }
+
+    state.identity.lock().await.clear_message_keys();
+
+    // Writes run under the database lock, so once we hold it nothing is mid-transaction
+    let db = state.database.lock().await;
+    if let Err(e) = db.flush() {
+        tracing::error!("Failed to flush database on shutdown: {}", e);
+    }
+
+    tracing::info!("Shutdown complete");
 }
 
 /// Setup deep link handler
@@ -82,14 +221,15 @@ fn setup_deep_links(_app_handle: tauri::AppHandle) {
 // Mobile entry point
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    println!("🔥 [RUST] GNS Browser run() called");
     // Initialize logging
+    let errors = ErrorLog::default();
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| "gns_browser=debug,tauri=info,tauri_plugin_gns=debug".into()),
         )
         .with(tracing_subscriber::fmt::layer())
+        .with(errors.clone())
         .init();
 
     tracing::error!("🔥 [RUST] Tracing initialized");
@@ -111,64 +251,220 @@ pub fn run() {
         .plugin(tauri_plugin_barcode_scanner::init());
 
     builder
-        .setup(|app| {
+        .setup(move |app| {
             tracing::error!("🔥 [RUST] Setup block entered");
             tracing::info!("Setting up application...");
 
-            let state = setup_app_state()?;
-            
-            // ... (keep existing setup logic for app-specific state like Stellar)
-            
-            let public_key = {
-                let identity = state.identity.try_lock().expect("Failed to lock identity");
-                identity.public_key_hex()
-            };
-            
-            if let Some(ref pk) = public_key {
-                tracing::info!("Public Key found: {}", pk);
-            }
+            let config = DesktopConfig::from_value(app.config().plugins.0.get(CONFIG_KEY));
+            let (events, event_pacer) = EventPacer::new(config.events);
+            let (state, connectivity_monitor, incoming_rx) = setup_app_state(config, errors, events)?;
+
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(event_pacer.run(move |event, payload| {
+                if let Err(e) = handle.emit(event, &payload) {
+                    tracing::error!("Failed to emit {} event: {}", event, e);
+                }
+            }));
 
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(connectivity_monitor.run(move |change| {
+                if let Err(e) = handle.emit(CONNECTIVITY_EVENT, &change) {
+                    tracing::error!("Failed to emit {} event: {}", CONNECTIVITY_EVENT, e);
+                }
+            }));
+            
             // Bind app state for remaining custom commands
             app.manage(state);
 
+            // Send-later runs from launch so overdue messages go out promptly
+            let handle = app.handle().clone();
+            let state = handle.state::<AppState>();
+            let (database, send_later, online) =
+                (state.database.clone(), state.send_later.clone(), state.connectivity.subscribe());
+            let (send_handle, sent_handle) = (handle.clone(), handle.clone());
+            tauri::async_runtime::spawn(scheduler::run(
+                database,
+                send_later,
+                online,
+                move |message| {
+                    let handle = send_handle.clone();
+                    async move {
+                        commands::messaging::send(
+                            &handle.state::<AppState>(),
+                            message.recipient_handle,
+                            message.recipient_public_key,
+                            message.payload_type,
+                            message.payload,
+                            message.thread_id,
+                            message.reply_to_id,
+                        )
+                        .await
+                    }
+                },
+                move |sent| {
+                    if let Err(e) = sent_handle.emit(SCHEDULED_SENT_EVENT, &sent) {
+                        tracing::error!("Failed to emit {} event: {}", SCHEDULED_SENT_EVENT, e);
+                    }
+                },
+            ));
+
+            // Send read markers to our other devices as threads are read
+            let state = handle.state::<AppState>();
+            let (database, read_sync, online) =
+                (state.database.clone(), state.read_sync.clone(), state.connectivity.subscribe());
+            let sync_handle = handle.clone();
+            tauri::async_runtime::spawn(read_sync::run(database, read_sync, online, move |markers| {
+                let handle = sync_handle.clone();
+                async move { read_sync::send(&handle.state::<AppState>(), markers).await }
+            }));
+
+            // Idle until the UI watches a post
+            let state = handle.state::<AppState>();
+            let (dix, events) = (state.dix.clone(), state.events.clone());
+            tauri::async_runtime::spawn(dix::engagement::run(dix, move |update| {
+                events.throttled(ENGAGEMENT_UPDATED_EVENT, update.post_id.clone(), &update);
+            }));
+
+            // The handler is listening before the relay can connect, so
+            // nothing it delivers goes unprocessed
+            let state = handle.state::<AppState>();
+            message_handler::start_message_handler(
+                handle.clone(),
+                state.identity.clone(),
+                state.database.clone(),
+                state.relay.clone(),
+                state.events.clone(),
+                incoming_rx,
+                state.config.replay,
+            );
+            let ready_handle = handle.clone();
+            tauri::async_runtime::spawn(network::watch_relay_ready(state.relay_ready.clone(), move |ready| {
+                if let Err(e) = ready_handle.emit(RELAY_READY_EVENT, &ready) {
+                    tracing::error!("Failed to emit {} event: {}", RELAY_READY_EVENT, e);
+                }
+            }));
+
             setup_deep_links(app.handle().clone());
 
-            tracing::info!("Application setup complete");
+            // With a stored identity, start the relay and friends right away
+            // and finish setup once the relay confirms the subscription; a
+            // fresh install waits for `initialize_services` after onboarding
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let state = handle.state::<AppState>();
+                if services::initialize(&state).await == ServicesStatus::NoIdentity {
+                    tracing::info!("Application setup complete, waiting for an identity");
+                    return;
+                }
+                match services::ensure_relay_ready(&state).await {
+                    Ok(()) => tracing::info!("Application setup complete, relay subscribed"),
+                    Err(e) => tracing::warn!("⚠️ Application setup complete without relay: {}", e),
+                }
+            });
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             // Network commands (App specific)
             commands::network::get_connection_status,
             commands::network::reconnect,
+            commands::network::initialize_services,
+            commands::network::connect_relay,
             // Stellar/GNS Token commands (App specific)
             commands::stellar::get_stellar_address,
+            commands::stellar::prove_stellar_control,
+            commands::stellar::verify_stellar_control,
             commands::stellar::get_stellar_explorer_url,
             commands::stellar::get_stellar_balances,
             commands::stellar::claim_gns_tokens,
+            commands::stellar::claim_all_balances,
+            commands::stellar::onboard_wallet,
             commands::stellar::create_gns_trustline,
+            commands::stellar::remove_gns_trustline,
             commands::stellar::send_gns,
+            commands::stellar::send_gns_to_handle,
+            commands::stellar::cancel_operation,
+            commands::stellar::can_receive_gns,
+            commands::stellar::estimate_send_fee,
+            commands::stellar::check_reserve_headroom,
+            commands::stellar::send_asset,
             commands::stellar::fund_testnet_account,
             commands::stellar::get_payment_history,
+            commands::stellar::get_claimable_balance_history,
+            commands::stellar::get_outgoing_claimable_balances,
+            commands::stellar::reclaim_expired_balance,
+            commands::stellar::get_stellar_network,
+            commands::stellar::set_stellar_network,
+            commands::stellar::get_gns_asset_info,
+            commands::stellar::verify_gns_asset_toml,
+            commands::stellar::list_known_assets,
+            // Messaging commands
+            commands::messaging::search_thread,
+            commands::messaging::get_thread_id,
+            commands::messaging::export_thread_transcript,
+            commands::messaging::verify_transcript,
+            commands::messaging::schedule_message,
+            commands::messaging::list_scheduled,
+            commands::messaging::cancel_scheduled,
+            commands::messaging::reschedule,
+            commands::messaging::rebuild_decrypted_cache,
+            commands::messaging::set_thread_name,
+            commands::messaging::get_thread_meta,
             // Utility commands
             commands::utils::get_app_version,
             commands::utils::open_external_url,
             commands::utils::get_offline_status,
+            commands::utils::run_diagnostics,
+            commands::utils::compact_storage,
+            commands::utils::verify_database_integrity,
+            commands::utils::repair_database,
+            commands::utils::migrate_thread_ids,
+            commands::utils::capture_debug_snapshot,
+            commands::utils::load_debug_snapshot,
             // Dix commands (App specific extension)
             commands::dix::create_post,
             commands::dix::get_timeline,
             commands::dix::like_post,
             commands::dix::repost_post,
+            commands::dix::verify_repost,
             commands::dix::get_post,
             commands::dix::get_post,
             commands::dix::get_posts_by_user,
+            commands::dix::get_trending_tags,
+            commands::dix::get_posts_by_tag,
+            commands::dix::fetch_link_preview,
+            commands::dix::bookmark_post,
+            commands::dix::unbookmark_post,
+            commands::dix::get_bookmarks,
+            commands::dix::follow,
+            commands::dix::unfollow,
+            commands::dix::get_following,
+            commands::dix::get_followers,
+            commands::dix::get_following_timeline,
+            commands::dix::watch_post_engagement,
+            commands::dix::stop_watching_engagement,
             // Home commands
             commands::home::discover_hubs,
+            commands::home::stop_discovery,
+            commands::home::forget_hub,
             commands::home::get_devices,
             commands::home::get_devices,
             commands::home::execute_command,
             // Profile commands
             commands::profile::get_profile,
             commands::profile::update_profile,
+            commands::profile::set_avatar,
+            commands::profile::get_avatar,
+            commands::profile::generate_identicon,
+            commands::devices::list_devices,
+            commands::devices::rename_device,
+            commands::devices::revoke_device,
+            commands::notifications::get_notifications,
+            commands::notifications::mark_notification_read,
+            commands::notifications::get_notifications_enabled,
+            commands::notifications::set_notifications_enabled,
+            commands::notifications::get_notification_prefs,
+            commands::notifications::set_notification_prefs,
             // Handle commands
             commands::commands_handle::validate_handle_format,
             commands::commands_handle::check_handle_available,
@@ -176,8 +472,53 @@ pub fn run() {
             commands::commands_handle::get_identity_info,
             commands::commands_handle::reserve_handle,
             commands::commands_handle::claim_handle,
+            commands::commands_handle::release_reserved_handle,
             commands::commands_handle::publish_identity,
+            commands::commands_handle::preview_identity_record,
+            // Identity migration commands
+            commands::identity::create_migration_token,
+            commands::identity::consume_migration_token,
+            commands::identity::revoke_migration_token,
+            commands::identity::export_identity_backup,
+            commands::identity::import_identity_backup,
+            commands::identity::rotate_encryption_key,
+            commands::identity::rotate_signing_key,
+            commands::identity::get_key_history,
+            commands::identity::lock_message_keys,
+            commands::identity::import_stellar_secret,
+            commands::identity::sign_typed_data,
+            commands::identity::ed25519_public_to_x25519_public,
+            // Identity card commands
+            commands::identity::export_identity_card,
+            commands::identity::import_identity_card,
+            // Audit log commands
+            commands::audit::get_audit_log,
+            commands::audit::export_audit_log,
+            commands::audit::verify_audit_log,
+            // Settings commands
+            commands::settings::get_endpoints,
+            commands::settings::set_api_url,
+            commands::settings::set_relay_url,
+            commands::settings::set_horizon_url,
+            commands::settings::reset_endpoints,
         ])
-        .run(tauri::generate_context!())
-        .expect("Error while running GNS Browser");
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::CloseRequested { .. } = event {
+                // Last window going away means the app is about to exit
+                if window.app_handle().webview_windows().len() <= 1 {
+                    if let Some(state) = window.app_handle().try_state::<AppState>() {
+                        tauri::async_runtime::block_on(shutdown_app_state(&state));
+                    }
+                }
+            }
+        })
+        .build(tauri::generate_context!())
+        .expect("Error while building GNS Browser")
+        .run(|app_handle, event| {
+            if let tauri::RunEvent::Exit = event {
+                if let Some(state) = app_handle.try_state::<AppState>() {
+                    tauri::async_runtime::block_on(shutdown_app_state(&state));
+                }
+            }
+        });
 }
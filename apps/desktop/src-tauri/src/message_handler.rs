@@ -2,15 +2,34 @@
 //!
 //! Receives envelopes from WebSocket, decrypts them, stores in DB, and emits UI events.
 
+use crate::commands::notifications::notify_message;
+use crate::config::ReplayPolicy;
 use crate::crypto::IdentityManager;
+use crate::events::EventPacer;
+use crate::migration_handoff::{self, MIGRATION_CLAIM_PAYLOAD_TYPE};
 use crate::network::{IncomingMessage, RelayConnection};
-use crate::storage::Database;
-use gns_crypto_core::{open_envelope, GnsEnvelope};
+use crate::read_sync::{self, READ_STATE_PAYLOAD_TYPE, READ_STATE_SYNCED_EVENT};
+use crate::replay_guard::ReplayGuard;
+use crate::thread_meta::{self, THREAD_META_PAYLOAD_TYPE, THREAD_META_UPDATED_EVENT};
+use crate::storage::{Database, DatabaseError};
+use gns_crypto_core::{
+    compute_thread_id, open_envelope_with_cache, open_envelope_with_ratchet, CryptoError, GnsEnvelope, GnsIdentity,
+    MessageKeyCache, RatchetSessions,
+};
+use std::collections::{HashSet, VecDeque};
+use std::future::Future;
 use std::sync::Arc;
 use tauri::{AppHandle, Emitter};
 use tokio::sync::{mpsc, Mutex};
+use tracing::Instrument;
 use sha2::Digest;
 
+/// Envelopes decrypted in parallel while working through a burst
+const OPEN_WORKERS: usize = 4;
+
+/// Most envelopes pulled off the channel into a single burst
+const MAX_BURST: usize = 128;
+
 /// Incoming message payload for UI
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct IncomingMessageEvent {
@@ -22,6 +41,9 @@ pub struct IncomingMessageEvent {
     pub payload: serde_json::Value,
     pub timestamp: i64,
     pub signature_valid: bool,
+    /// Kept so the message can later be exported with its signature
+    #[serde(skip)]
+    pub envelope: GnsEnvelope,
 }
 
 /// Start the message handler task
@@ -30,15 +52,42 @@ pub fn start_message_handler(
     identity: Arc<Mutex<IdentityManager>>,
     database: Arc<Mutex<Database>>,
     relay: Arc<Mutex<RelayConnection>>,
+    events: EventPacer,
     mut incoming_rx: mpsc::Receiver<IncomingMessage>,
+    replay: ReplayPolicy,
 ) {
     tauri::async_runtime::spawn(async move {
         tracing::info!("Message handler started");
+        let mut replay_guard = ReplayGuard::new(replay);
+
+        // A non-envelope message that ended the previous burst
+        let mut pending: Option<IncomingMessage> = None;
+
+        loop {
+            let msg = match pending.take() {
+                Some(msg) => msg,
+                None => match incoming_rx.recv().await {
+                    Some(msg) => msg,
+                    None => break,
+                },
+            };
 
-        while let Some(msg) = incoming_rx.recv().await {
             match msg {
                 IncomingMessage::Envelope(envelope) => {
-                    handle_envelope(&app_handle, &identity, &database, &relay, envelope).await;
+                    // After a reconnect the relay flushes its queue; take
+                    // everything already waiting and open it as one burst
+                    let mut burst = vec![envelope];
+                    while burst.len() < MAX_BURST {
+                        match incoming_rx.try_recv() {
+                            Ok(IncomingMessage::Envelope(envelope)) => burst.push(envelope),
+                            Ok(other) => {
+                                pending = Some(other);
+                                break;
+                            }
+                            Err(_) => break,
+                        }
+                    }
+                    handle_envelopes(&app_handle, &identity, &database, &relay, &events, &mut replay_guard, burst).await;
                 }
                 IncomingMessage::Welcome { public_key } => {
                     tracing::info!("Welcome received for {}", &public_key[..16]);
@@ -58,15 +107,12 @@ pub fn start_message_handler(
                     if let Some(gns_id) = identity_guard.get_identity() {
                         let my_pk = gns_id.public_key_hex();
                         
-                        // Calculate Thread ID (deterministic)
-                        let mut keys = vec![my_pk.as_str(), conversation_with.as_str()];
-                        keys.sort();
-                        let thread_id = format!("direct_{}", &keys.join("_")[..32]);
+                        let thread_id = compute_thread_id(&my_pk, &conversation_with);
                         
                         // Fetch messages from DB
                         let result: Result<Vec<crate::commands::messaging::Message>, _> = {
                             let db = database.lock().await;
-                            db.get_messages(&thread_id, limit)
+                            db.get_messages(&thread_id, limit, None)
                         };
 
                         if let Ok(messages) = result {
@@ -231,7 +277,7 @@ pub fn start_message_handler(
 
                         // Emit 'new_message' to trigger generic UI updates (like EmailList refresh)
                         // Payload doesn't need to match generic event perfectly if UI just refetches
-                        let _ = app_handle.emit("new_message", serde_json::json!({
+                        events.message(&serde_json::json!({
                             "id": message_id,
                             "payload_type": "email", // Assume email for now
                             "timestamp": timestamp
@@ -248,54 +294,179 @@ pub fn start_message_handler(
     });
 }
 
-/// Handle an incoming envelope
-async fn handle_envelope(
+/// Handle a burst of incoming envelopes.
+///
+/// Decryption runs on a small worker pool; storing, emitting and browser
+/// sync happen one message at a time in timestamp order, so threads never
+/// see an older message land after a newer one. Envelopes `replay_guard`
+/// turns away are dropped before delivery.
+async fn handle_envelopes(
     app_handle: &AppHandle,
     identity: &Arc<Mutex<IdentityManager>>,
     database: &Arc<Mutex<Database>>,
     relay: &Arc<Mutex<RelayConnection>>,
-    envelope: GnsEnvelope,
+    events: &EventPacer,
+    replay_guard: &mut ReplayGuard,
+    envelopes: Vec<GnsEnvelope>,
 ) {
-    println!("🔥 [RUST] handle_envelope called: {}", envelope.id);
-    println!("🔥 [RUST] Envelope Sender: {}", envelope.from_public_key);
-    tracing::info!("Processing envelope {} from {}", envelope.id, &envelope.from_public_key[..16]);
-
-    // Get our identity for decryption
-    let identity_guard = identity.lock().await;
-    let gns_identity = match identity_guard.get_identity() {
-        Some(id) => id,
-        None => {
-            tracing::error!("No identity available for decryption");
-            return;
+    // Workers get their own copy of the keys so the identity lock isn't
+    // held for the whole burst
+    let (keys, message_keys, ratchet, compression) = {
+        let identity_guard = identity.lock().await;
+        match identity_guard.get_identity().map(|id| GnsIdentity::from_hex(&id.private_key_hex())) {
+            Some(Ok(keys)) => (
+                Arc::new(keys),
+                identity_guard.message_keys(),
+                identity_guard.ratchet_sessions(),
+                identity_guard.compression_peers(),
+            ),
+            Some(Err(e)) => {
+                tracing::error!(error = %e, "Failed to copy identity for decryption");
+                return;
+            }
+            None => {
+                tracing::error!("No identity available for decryption");
+                return;
+            }
         }
     };
+    let my_pk = keys.public_key_hex();
+    let my_pk = my_pk.as_str();
+    let signer = keys.clone();
+    let signer = signer.as_ref();
 
-    // Verify and decrypt the envelope
-    let opened = match open_envelope(gns_identity, &envelope) {
-        Ok(o) => o,
-        Err(e) => {
-            tracing::error!("Failed to open envelope: {}", e);
-            return;
+    if envelopes.len() > 1 {
+        tracing::info!(count = envelopes.len(), "Processing envelope burst");
+    }
+
+    open_burst(keys, message_keys, ratchet.clone(), envelopes, OPEN_WORKERS, |event| {
+        let now = chrono::Utc::now().timestamp_millis();
+        let verdict = replay_guard.check(&event.from_public_key, &event.envelope, now);
+        if let (Some(peers), Ok(())) = (&compression, &verdict) {
+            peers.note(&event.envelope, event.signature_valid);
         }
-    };
+        async move {
+            match verdict {
+                Ok(()) => deliver_message(app_handle, database, relay, events, signer, event).await,
+                Err(e) => tracing::warn!(
+                    sender = %event.from_public_key.get(..16).unwrap_or(&event.from_public_key),
+                    "⚠️ Dropping replayed envelope: {}",
+                    e
+                ),
+            }
+        }
+    })
+    .await;
 
-    if !opened.signature_valid {
-        tracing::warn!("Envelope {} has invalid signature!", envelope.id);
-        // Still process it but mark as unverified
+    // Offers answered and keys used up have to survive a restart
+    if let Some(ratchet) = ratchet {
+        if let Err(e) = database.lock().await.save_ratchet_sessions(my_pk, &ratchet) {
+            tracing::error!(error = %e, "Failed to save ratchet sessions");
+        }
     }
+}
+
+/// Decrypt `envelopes` on up to `workers` blocking tasks and pass each
+/// result to `deliver` in timestamp order, as soon as it and every earlier
+/// envelope are done. Envelope ids repeated within the burst are dropped.
+async fn open_burst<F, Fut>(
+    keys: Arc<GnsIdentity>,
+    message_keys: Arc<MessageKeyCache>,
+    ratchet: Option<Arc<RatchetSessions>>,
+    mut envelopes: Vec<GnsEnvelope>,
+    workers: usize,
+    mut deliver: F,
+) where
+    F: FnMut(IncomingMessageEvent) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    envelopes.sort_by_key(|e| e.timestamp);
+    let mut seen = HashSet::new();
+    envelopes.retain(|e| seen.insert(e.id.clone()));
+
+    let mut queue = envelopes.into_iter();
+    let mut in_flight = VecDeque::with_capacity(workers);
 
-    // Parse the payload
-    let payload: serde_json::Value = match serde_json::from_slice(&opened.payload) {
+    loop {
+        while in_flight.len() < workers.max(1) {
+            let Some(envelope) = queue.next() else { break };
+            let span = tracing::info_span!(
+                "incoming_envelope",
+                correlation_id = %uuid::Uuid::new_v4(),
+                envelope_id = %envelope.id,
+                sender = %envelope.from_public_key.get(..16).unwrap_or(&envelope.from_public_key),
+                thread_id = tracing::field::Empty,
+            );
+            let keys = keys.clone();
+            let message_keys = message_keys.clone();
+            let ratchet = ratchet.clone();
+            let worker_span = span.clone();
+            let task = tokio::task::spawn_blocking(move || {
+                worker_span.in_scope(|| decrypt_envelope(&keys, &message_keys, ratchet.as_deref(), &envelope))
+            });
+            in_flight.push_back((span, task));
+        }
+
+        let Some((span, task)) = in_flight.pop_front() else { break };
+        match task.await {
+            Ok(Some(event)) => deliver(event).instrument(span).await,
+            Ok(None) => {}
+            Err(e) => tracing::error!(parent: &span, error = %e, "Envelope worker failed"),
+        }
+    }
+}
+
+/// Parse a decrypted payload, wrapping one that isn't JSON as text
+pub(crate) fn decode_payload(payload: &[u8]) -> serde_json::Value {
+    match serde_json::from_slice(payload) {
         Ok(p) => p,
         Err(e) => {
             // If not JSON, treat as plain text
             tracing::debug!("Payload is not JSON, treating as text: {}", e);
             serde_json::json!({
-                "text": String::from_utf8_lossy(&opened.payload).to_string()
+                "text": String::from_utf8_lossy(payload).to_string()
             })
         }
+    }
+}
+
+/// Verify and decrypt an envelope, working out which thread it belongs to.
+/// With `ratchet`, forward secrecy envelopes open too and offers get answered.
+pub(crate) fn decrypt_envelope(
+    gns_identity: &GnsIdentity,
+    message_keys: &MessageKeyCache,
+    ratchet: Option<&RatchetSessions>,
+    envelope: &GnsEnvelope,
+) -> Option<IncomingMessageEvent> {
+    tracing::info!("Processing envelope");
+
+    // Verify and decrypt the envelope
+    let opened = match ratchet {
+        Some(ratchet) => open_envelope_with_ratchet(gns_identity, envelope, message_keys, ratchet),
+        None => open_envelope_with_cache(gns_identity, envelope, message_keys),
+    };
+    let opened = match opened {
+        Ok(o) => o,
+        Err(CryptoError::UnsupportedCryptoVersion(version)) => {
+            tracing::warn!(
+                crypto_version = version,
+                "⚠️ Envelope uses a crypto version this build cannot open - the sender may be on a newer app"
+            );
+            return None;
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to open envelope");
+            return None;
+        }
     };
 
+    if !opened.signature_valid {
+        tracing::warn!("Envelope has invalid signature!");
+        // Still process it but mark as unverified
+    }
+
+    let payload = decode_payload(&opened.payload);
+
     tracing::info!(
         "Decrypted message from {}: {:?}",
         opened.from_handle.as_deref().unwrap_or(&opened.from_public_key[..16]),
@@ -313,7 +484,6 @@ async fn handle_envelope(
         let subject = payload.get("subject").and_then(|s| s.as_str()).unwrap_or("");
         
         let s = normalize_subject(subject);
-        println!("🔥 [RUST] Subject Hashing. Original: '{}', Normalized: '{}'", subject, s);
         if s.is_empty() {
              opened.thread_id.clone().unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
         } else {
@@ -321,7 +491,6 @@ async fn handle_envelope(
              hasher.update(s.as_bytes());
              let result = hasher.finalize();
              let hash = hex::encode(result);
-             println!("🔥 [RUST] Generated Thread Hash: {}", hash);
              hash
         }
     } else if let Some(tid) = opened.thread_id.clone() {
@@ -329,37 +498,17 @@ async fn handle_envelope(
         tid
     } else {
         // Direct message / Chat -> Deterministic based on participants
-        let my_pk = gns_identity.public_key_hex();
-        let other_pk = &opened.from_public_key;
-        let mut keys = vec![my_pk.as_str(), other_pk.as_str()];
-        keys.sort();
-        format!("direct_{}", &keys.join("_")[..32])
+        compute_thread_id(&gns_identity.public_key_hex(), &opened.from_public_key)
     };
 
-    println!("🔥 [RUST] Decrypted Message: Type={}", opened.payload_type);
-    println!("🔥 [RUST] Thread ID: {}", thread_id);
-    println!("🔥 [RUST] Sender Handle: {:?}", opened.from_handle);
-
-    // Store in database
-    {
-        let mut db = database.lock().await;
-        if let Err(e) = db.save_received_message(
-            &envelope.id,
-            &thread_id,
-            &opened.from_public_key,
-            opened.from_handle.as_deref(),
-            &opened.payload_type,
-            &payload,
-            opened.timestamp,
-            opened.signature_valid,
-            None,
-        ) {
-            tracing::error!("Failed to save message to database: {}", e);
-        }
-    }
+    tracing::Span::current().record("thread_id", thread_id.as_str());
+    tracing::debug!(
+        payload_type = %opened.payload_type,
+        from_handle = opened.from_handle.as_deref(),
+        "Envelope decrypted"
+    );
 
-    // Create event for UI
-    let event = IncomingMessageEvent {
+    Some(IncomingMessageEvent {
         id: envelope.id.clone(),
         thread_id: Some(thread_id),
         from_public_key: opened.from_public_key,
@@ -368,33 +517,111 @@ async fn handle_envelope(
         payload,
         timestamp: opened.timestamp,
         signature_valid: opened.signature_valid,
-    };
+        envelope: envelope.clone(),
+    })
+}
+
+/// Persist a decrypted message
+fn store_message(db: &mut Database, event: &IncomingMessageEvent) -> Result<(), DatabaseError> {
+    db.save_received_message(
+        &event.id,
+        event.thread_id.as_deref().unwrap_or_default(),
+        &event.from_public_key,
+        event.from_handle.as_deref(),
+        &event.payload_type,
+        &event.payload,
+        event.timestamp,
+        event.signature_valid,
+        None,
+    )?;
+    db.save_message_envelope(&event.id, &event.envelope)
+}
 
-    // Emit to UI
-    if let Err(e) = app_handle.emit("new_message", &event) {
-        tracing::error!("Failed to emit new_message event: {}", e);
+/// Store a decrypted message, emit it to the UI and forward it to browsers
+async fn deliver_message(
+    app_handle: &AppHandle,
+    database: &Arc<Mutex<Database>>,
+    relay: &Arc<Mutex<RelayConnection>>,
+    events: &EventPacer,
+    keys: &GnsIdentity,
+    event: IncomingMessageEvent,
+) {
+    let my_pk = keys.public_key_hex();
+    let my_pk = my_pk.as_str();
+
+    // A new device asking for a migration token we issued
+    if event.payload_type == MIGRATION_CLAIM_PAYLOAD_TYPE {
+        let now = chrono::Utc::now().timestamp();
+        let answer = migration_handoff::answer_claim(&mut *database.lock().await, keys, &event, now);
+        match answer {
+            Ok(None) => {}
+            Ok(Some(release)) => {
+                let claimant = event.from_public_key.get(..16).unwrap_or(&event.from_public_key);
+                tracing::info!("Answering migration claim from {}", claimant);
+                if let Err(e) = relay.lock().await.send_envelope(&release).await {
+                    tracing::error!("Failed to answer migration claim: {}", e);
+                }
+            }
+            Err(e) => tracing::warn!("⚠️ {}", e),
+        }
+        return;
     }
 
-    tracing::info!("Message {} processed and emitted to UI", envelope.id);
+    // Read state from another of our devices, not a message
+    if event.payload_type == READ_STATE_PAYLOAD_TYPE {
+        let applied = read_sync::apply_sync(&mut *database.lock().await, my_pk, &event);
+        match applied {
+            Ok(markers) if markers.is_empty() => {}
+            Ok(markers) => {
+                tracing::info!("Applied {} read marker(s) from another device", markers.len());
+                if let Err(e) = app_handle.emit(READ_STATE_SYNCED_EVENT, &markers) {
+                    tracing::error!("Failed to emit {} event: {}", READ_STATE_SYNCED_EVENT, e);
+                }
+            }
+            Err(e) => tracing::warn!("⚠️ {}", e),
+        }
+        return;
+    }
+
+    // A group thread's name, avatar or members changed
+    if event.payload_type == THREAD_META_PAYLOAD_TYPE {
+        let applied = thread_meta::apply_update(&mut *database.lock().await, &event);
+        match applied {
+            Ok(None) => {}
+            Ok(Some(meta)) => {
+                tracing::info!("Thread metadata of {} updated", meta.thread_id);
+                if let Err(e) = app_handle.emit(THREAD_META_UPDATED_EVENT, &meta) {
+                    tracing::error!("Failed to emit {} event: {}", THREAD_META_UPDATED_EVENT, e);
+                }
+            }
+            Err(e) => tracing::warn!("⚠️ {}", e),
+        }
+        return;
+    }
+
+    // Store in database
+    {
+        let mut db = database.lock().await;
+        if let Err(e) = store_message(&mut db, &event) {
+            tracing::error!(error = %e, "Failed to save message to database");
+        }
+    }
+
+    // Emit to UI, batched with whatever else arrives in the same burst
+    events.message(&event);
+    notify_message(app_handle, database, my_pk, &event).await;
+
+    tracing::info!("Message processed and emitted to UI");
 
     // Sync to Browser (Phase 1.5)
     // Forward decrypted content to any connected browsers
     {
         let relay_guard = relay.lock().await;
-        // Construct sync event
+        // Route to our own devices; the sender is the conversation partner
         let sync_event = serde_json::json!({
             "type": "message_synced",
-            "to": [event.from_public_key], // Route to myself (User's devices) - Wait, from_public_key is Sender. 
-            // If Incoming: Sender is Echo. I want to route to ME.
-            // I need MY PK.
-            // event.from_public_key is ECHO.
-            // I need to inject MY PK.
-            // But I don't have MY PK in this scope easily?
-            // `gns_identity` is available in handle_envelope!
-            // Line 153: let gns_identity = ...
-            // So I can use gns_identity.public_key_hex().
-            "to": [gns_identity.public_key_hex()],
-            "messageId": envelope.id,
+            "to": [my_pk],
+            "messageId": event.id,
             "conversationWith": event.from_public_key,
             "decryptedText": event.payload.get("text").and_then(|t| t.as_str()).unwrap_or(""),
             "direction": "incoming",
@@ -406,7 +633,7 @@ async fn handle_envelope(
         if let Err(e) = relay_guard.send_raw(&sync_event.to_string()).await {
              tracing::debug!("Failed to sync message to browser (likely no browsers connected): {}", e);
         } else {
-             tracing::info!("Synced message {} to browser(s)", envelope.id);
+             tracing::info!("Synced message to browser(s)");
         }
     }
 }
@@ -436,3 +663,76 @@ pub fn normalize_subject(subject: &str) -> String {
     
     s
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gns_crypto_core::create_envelope_with_metadata;
+
+    #[tokio::test]
+    async fn test_burst_is_stored_once_in_order() {
+        let sender = GnsIdentity::generate();
+        let recipient = GnsIdentity::generate();
+        let threads = ["thread-a", "thread-b", "thread-c"];
+
+        let mut envelopes = Vec::new();
+        for i in 0..100 {
+            let payload = serde_json::json!({ "text": format!("message {}", i) });
+            envelopes.push(
+                create_envelope_with_metadata(
+                    &sender,
+                    None,
+                    &recipient.public_key_hex(),
+                    &recipient.encryption_key_hex(),
+                    "text/plain",
+                    payload.to_string().as_bytes(),
+                    Some(threads[i % threads.len()]),
+                    None,
+                )
+                .unwrap(),
+            );
+            // Distinct millisecond timestamps
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        // Deliver out of order, with a redelivered duplicate
+        let duplicate = envelopes[42].clone();
+        envelopes.reverse();
+        envelopes.swap(10, 70);
+        envelopes.push(duplicate);
+
+        let database = Arc::new(Mutex::new(Database::open_in_memory().unwrap()));
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+
+        open_burst(Arc::new(recipient), Arc::default(), None, envelopes, OPEN_WORKERS, |event| {
+            let database = database.clone();
+            let delivered = delivered.clone();
+            async move {
+                store_message(&mut *database.lock().await, &event).unwrap();
+                delivered.lock().await.push((event.thread_id.unwrap(), event.timestamp));
+            }
+        })
+        .await;
+
+        let delivered = delivered.lock().await;
+        assert_eq!(delivered.len(), 100);
+
+        let db = database.lock().await;
+        let previews = db.get_threads(false, 10, 0).unwrap();
+        for thread in threads {
+            let timestamps: Vec<i64> = delivered
+                .iter()
+                .filter(|(t, _)| t == thread)
+                .map(|(_, ts)| *ts)
+                .collect();
+            assert!(timestamps.windows(2).all(|w| w[0] < w[1]));
+
+            let stored = db.get_messages(thread, 200, None).unwrap();
+            assert_eq!(stored.len(), timestamps.len());
+
+            let preview = previews.iter().find(|p| p.id == thread).unwrap();
+            assert_eq!(preview.unread_count as usize, timestamps.len());
+            assert_eq!(preview.last_message_at, *timestamps.last().unwrap());
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Migration Handoff
+//!
+//! A migration token only carries the sealed seed; the key to open it stays
+//! on the old device (see `crypto::migration`). To redeem a token the new
+//! device connects to the relay under a throwaway identity and sends a
+//! claim to the identity being moved. The old device's message handler
+//! answers from its token ledger: the first claim of an issued, unexpired
+//! token gets the transport key, later ones and claims of revoked tokens
+//! are refused. The answer is signed by the identity itself, so the new
+//! device knows it came from the old device.
+//!
+//! Other devices of the same identity get the claim as well. They don't
+//! know the token and stay silent, so only the issuing device's ledger
+//! counts.
+
+use crate::crypto::migration::{MigrationError, MigrationToken};
+use crate::message_handler::IncomingMessageEvent;
+use crate::network::{IncomingMessage, RelayConnection};
+use crate::storage::{Database, MigrationTokenStatus};
+use gns_crypto_core::{create_envelope, open_envelope, CryptoError, GnsEnvelope, GnsIdentity};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Payload type of a new device's claim on a migration token
+pub const MIGRATION_CLAIM_PAYLOAD_TYPE: &str = "gns/migration_claim";
+
+/// Payload type of the old device's answer to a claim
+pub const MIGRATION_RELEASE_PAYLOAD_TYPE: &str = "gns/migration_release";
+
+/// How long the new device waits for the old device to answer
+const RELEASE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Payload of a claim envelope
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationClaim {
+    pub token_id: String,
+    /// Encryption key of the claiming device's throwaway identity
+    pub encryption_key: String,
+}
+
+/// Payload of a release envelope
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "status")]
+pub enum MigrationRelease {
+    Released { token_id: String, transport_key: String },
+    Revoked { token_id: String },
+    AlreadyUsed { token_id: String },
+    Expired { token_id: String },
+}
+
+impl MigrationRelease {
+    fn token_id(&self) -> &str {
+        match self {
+            Self::Released { token_id, .. }
+            | Self::Revoked { token_id }
+            | Self::AlreadyUsed { token_id }
+            | Self::Expired { token_id } => token_id,
+        }
+    }
+}
+
+/// Seal a claim on `token` from the new device's throwaway identity
+pub fn claim_envelope(claimant: &GnsIdentity, token: &MigrationToken) -> Result<GnsEnvelope, CryptoError> {
+    let claim = MigrationClaim {
+        token_id: token.id.clone(),
+        encryption_key: claimant.encryption_key_hex(),
+    };
+    let payload = serde_json::to_vec(&claim).expect("claim serializes");
+    create_envelope(
+        claimant,
+        &token.public_key,
+        &token.encryption_key,
+        MIGRATION_CLAIM_PAYLOAD_TYPE,
+        &payload,
+    )
+}
+
+/// Answer a decrypted claim envelope from the ledger in `db`, releasing the
+/// transport key at most once. Returns `None` for tokens this device
+/// didn't issue.
+pub fn answer_claim(
+    db: &mut Database,
+    identity: &GnsIdentity,
+    event: &IncomingMessageEvent,
+    now: i64,
+) -> Result<Option<GnsEnvelope>, String> {
+    if !event.signature_valid {
+        return Err("Ignoring migration claim with an invalid signature".to_string());
+    }
+    let claim: MigrationClaim =
+        serde_json::from_value(event.payload.clone()).map_err(|e| format!("Malformed migration claim: {}", e))?;
+
+    let token_id = claim.token_id.clone();
+    let release = match db.release_migration_token(&claim.token_id, now).map_err(|e| e.to_string())? {
+        Some(transport_key) => MigrationRelease::Released { token_id, transport_key },
+        None => match db.migration_token_status(&claim.token_id).map_err(|e| e.to_string())? {
+            None => return Ok(None),
+            Some(MigrationTokenStatus::Revoked) => MigrationRelease::Revoked { token_id },
+            Some(MigrationTokenStatus::Consumed) => MigrationRelease::AlreadyUsed { token_id },
+            Some(MigrationTokenStatus::Issued) => MigrationRelease::Expired { token_id },
+        },
+    };
+
+    let payload = serde_json::to_vec(&release).expect("release serializes");
+    create_envelope(
+        identity,
+        &event.from_public_key,
+        &claim.encryption_key,
+        MIGRATION_RELEASE_PAYLOAD_TYPE,
+        &payload,
+    )
+    .map(Some)
+    .map_err(|e| format!("Failed to create migration release: {}", e))
+}
+
+/// Read the old device's answer to our claim on `token`. Returns `None`
+/// for envelopes that aren't a validly signed answer about this token.
+pub fn read_release(
+    claimant: &GnsIdentity,
+    token: &MigrationToken,
+    envelope: &GnsEnvelope,
+) -> Option<Result<String, MigrationError>> {
+    let opened = open_envelope(claimant, envelope).ok()?;
+    if opened.payload_type != MIGRATION_RELEASE_PAYLOAD_TYPE
+        || !opened.signature_valid
+        || !opened.from_public_key.eq_ignore_ascii_case(&token.public_key)
+    {
+        return None;
+    }
+
+    let release: MigrationRelease = serde_json::from_slice(&opened.payload).ok()?;
+    if release.token_id() != token.id {
+        return None;
+    }
+    Some(match release {
+        MigrationRelease::Released { transport_key, .. } => Ok(transport_key),
+        MigrationRelease::Revoked { .. } => Err(MigrationError::Revoked),
+        MigrationRelease::AlreadyUsed { .. } => Err(MigrationError::AlreadyUsed),
+        MigrationRelease::Expired { .. } => Err(MigrationError::Expired),
+    })
+}
+
+/// Claim `token` from the old device through the relay at `relay_url` and
+/// return the transport key it released
+pub async fn claim(relay_url: &str, token: &MigrationToken) -> Result<String, MigrationError> {
+    let claimant = GnsIdentity::generate();
+    let envelope = claim_envelope(&claimant, token).map_err(|e| MigrationError::Corrupted(e.to_string()))?;
+
+    let (tx, mut rx) = mpsc::channel(16);
+    let relay = RelayConnection::new(relay_url)
+        .map_err(|e| MigrationError::Network(e.to_string()))?
+        .with_incoming_channel(tx);
+    relay
+        .connect(&claimant.public_key_hex())
+        .await
+        .map_err(|e| MigrationError::Network(e.to_string()))?;
+
+    let answer = async {
+        relay
+            .send_envelope(&envelope)
+            .await
+            .map_err(|e| MigrationError::Network(e.to_string()))?;
+        while let Some(message) = rx.recv().await {
+            if let IncomingMessage::Envelope(envelope) = message {
+                if let Some(answer) = read_release(&claimant, token, &envelope) {
+                    return answer;
+                }
+            }
+        }
+        Err(MigrationError::NoAnswer)
+    };
+    let answer = tokio::time::timeout(RELEASE_TIMEOUT, answer)
+        .await
+        .unwrap_or(Err(MigrationError::NoAnswer));
+
+    relay.shutdown().await;
+    answer
+}
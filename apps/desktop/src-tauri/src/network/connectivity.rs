@@ -0,0 +1,218 @@
+//! Connectivity Tracking
+//!
+//! Folds relay connection changes and API call outcomes into one cached
+//! online/offline state, so the UI and background features can react to
+//! changes instead of probing the network.
+//!
+//! The relay is the primary signal: while it is connected we are online,
+//! whatever individual API calls do. While it is down, the most recent API
+//! call decides. Going online is reported immediately; going offline only
+//! once it has lasted `OFFLINE_DEBOUNCE`, so a socket that drops and comes
+//! straight back doesn't flash the offline banner.
+
+use serde::Serialize;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::time::Instant;
+
+/// Tauri event emitted when the online state flips
+pub const CONNECTIVITY_EVENT: &str = "connectivity_changed";
+
+/// How long we must stay offline before it is reported
+pub const OFFLINE_DEBOUNCE: Duration = Duration::from_secs(3);
+
+/// What caused the online state to change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectivityReason {
+    RelayConnected,
+    RelayDisconnected,
+    ApiReachable,
+    ApiUnreachable,
+}
+
+/// Payload of the `connectivity_changed` event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConnectivityChanged {
+    pub online: bool,
+    pub reason: ConnectivityReason,
+}
+
+/// Reports connectivity signals and reads the current state. Cheap to clone.
+#[derive(Clone)]
+pub struct Connectivity {
+    signals: mpsc::UnboundedSender<ConnectivityReason>,
+    online: watch::Receiver<bool>,
+}
+
+/// Runs the state machine; see [`ConnectivityMonitor::run`]
+pub struct ConnectivityMonitor {
+    signals: mpsc::UnboundedReceiver<ConnectivityReason>,
+    online: watch::Sender<bool>,
+    debounce: Duration,
+}
+
+impl Connectivity {
+    /// Start offline; the monitor must be spawned for changes to apply
+    pub fn new() -> (Self, ConnectivityMonitor) {
+        Self::with_debounce(OFFLINE_DEBOUNCE)
+    }
+
+    pub fn with_debounce(debounce: Duration) -> (Self, ConnectivityMonitor) {
+        let (signal_tx, signal_rx) = mpsc::unbounded_channel();
+        let (online_tx, online_rx) = watch::channel(false);
+        (
+            Self { signals: signal_tx, online: online_rx },
+            ConnectivityMonitor { signals: signal_rx, online: online_tx, debounce },
+        )
+    }
+
+    pub fn report_relay(&self, connected: bool) {
+        let _ = self.signals.send(if connected {
+            ConnectivityReason::RelayConnected
+        } else {
+            ConnectivityReason::RelayDisconnected
+        });
+    }
+
+    pub fn report_api(&self, reachable: bool) {
+        let _ = self.signals.send(if reachable {
+            ConnectivityReason::ApiReachable
+        } else {
+            ConnectivityReason::ApiUnreachable
+        });
+    }
+
+    /// Last reported state (debounced)
+    pub fn is_online(&self) -> bool {
+        *self.online.borrow()
+    }
+
+    /// Watch the online state, e.g. to flush a queue on reconnect
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.online.clone()
+    }
+}
+
+impl ConnectivityMonitor {
+    /// Apply signals until every `Connectivity` handle is dropped, calling
+    /// `on_change` each time the reported state flips.
+    pub async fn run<F>(mut self, mut on_change: F)
+    where
+        F: FnMut(ConnectivityChanged),
+    {
+        let mut relay_up = false;
+        let mut api_up: Option<bool> = None;
+        // Offline candidate waiting out the debounce
+        let mut going_offline: Option<(ConnectivityReason, Instant)> = None;
+
+        loop {
+            let deadline = going_offline.map(|(_, since)| since + self.debounce);
+            let signal = tokio::select! {
+                signal = self.signals.recv() => match signal {
+                    Some(signal) => Some(signal),
+                    None => break,
+                },
+                _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => None,
+            };
+
+            let Some(reason) = signal else {
+                // Debounce elapsed without recovering
+                if let Some((reason, _)) = going_offline.take() {
+                    self.commit(false, reason, &mut on_change);
+                }
+                continue;
+            };
+
+            match reason {
+                ConnectivityReason::RelayConnected => relay_up = true,
+                ConnectivityReason::RelayDisconnected => {
+                    relay_up = false;
+                    // An API call from before the drop says nothing about now
+                    api_up = None;
+                }
+                ConnectivityReason::ApiReachable => api_up = Some(true),
+                ConnectivityReason::ApiUnreachable => api_up = Some(false),
+            }
+
+            let online = relay_up || api_up == Some(true);
+            if online == *self.online.borrow() {
+                // Flapped back before the debounce ran out
+                going_offline = None;
+            } else if online {
+                going_offline = None;
+                self.commit(true, reason, &mut on_change);
+            } else if going_offline.is_none() {
+                going_offline = Some((reason, Instant::now()));
+            }
+        }
+    }
+
+    fn commit<F>(&self, online: bool, reason: ConnectivityReason, on_change: &mut F)
+    where
+        F: FnMut(ConnectivityChanged),
+    {
+        self.online.send_replace(online);
+        if online {
+            tracing::info!(?reason, "🌐 Back online");
+        } else {
+            tracing::warn!(?reason, "📴 Offline");
+        }
+        on_change(ConnectivityChanged { online, reason });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_transitions_are_debounced() {
+        let (connectivity, monitor) = Connectivity::new();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        tokio::spawn(monitor.run(move |event| sink.lock().unwrap().push(event)));
+
+        let settle = || tokio::time::sleep(Duration::from_millis(10));
+        let changed = |online, reason| ConnectivityChanged { online, reason };
+
+        // Online is reported straight away
+        connectivity.report_relay(true);
+        settle().await;
+        assert!(connectivity.is_online());
+
+        // A quick flap is swallowed
+        connectivity.report_relay(false);
+        tokio::time::sleep(OFFLINE_DEBOUNCE / 2).await;
+        connectivity.report_relay(true);
+        tokio::time::sleep(OFFLINE_DEBOUNCE * 2).await;
+        assert!(connectivity.is_online());
+
+        // API failures don't matter while the relay is up
+        connectivity.report_api(false);
+        tokio::time::sleep(OFFLINE_DEBOUNCE * 2).await;
+        assert!(connectivity.is_online());
+
+        // A lasting drop is reported once the debounce runs out
+        connectivity.report_relay(false);
+        settle().await;
+        assert!(connectivity.is_online());
+        tokio::time::sleep(OFFLINE_DEBOUNCE).await;
+        assert!(!connectivity.is_online());
+
+        // With the relay down, a successful API call means we're online
+        connectivity.report_api(true);
+        settle().await;
+        assert!(connectivity.is_online());
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                changed(true, ConnectivityReason::RelayConnected),
+                changed(false, ConnectivityReason::RelayDisconnected),
+                changed(true, ConnectivityReason::ApiReachable),
+            ]
+        );
+    }
+}
@@ -0,0 +1,131 @@
+//! Idempotency Keys
+//!
+//! Mutating requests carry a client-generated key so the server can drop
+//! duplicates. A key stays pending until the server gives a definitive
+//! answer, and the same logical operation reuses it until then - including
+//! after a restart, since pending keys are kept in the database.
+
+use crate::storage::Database;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// Header carrying the client-generated idempotency key
+pub const IDEMPOTENCY_HEADER: &str = "Idempotency-Key";
+
+/// How long an unacknowledged key is reused for the same operation
+const IDEMPOTENCY_KEY_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// Derive a stable operation id from the parts identifying a logical request
+pub fn idempotency_operation(parts: &[&str]) -> String {
+    hex::encode(Sha256::digest(parts.join("\n").as_bytes()))
+}
+
+/// A key sent for an operation the server hasn't acknowledged yet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingIdempotencyKey {
+    pub key: String,
+    /// Unix milliseconds
+    pub created_at: i64,
+}
+
+impl PendingIdempotencyKey {
+    fn expired(&self, now: i64) -> bool {
+        now - self.created_at >= IDEMPOTENCY_KEY_TTL.as_millis() as i64
+    }
+}
+
+/// Pending keys by operation, written through to the database if there is one
+#[derive(Default)]
+pub struct IdempotencyKeys {
+    pending: std::sync::Mutex<HashMap<String, PendingIdempotencyKey>>,
+    database: Option<Arc<Mutex<Database>>>,
+}
+
+impl IdempotencyKeys {
+    /// Keys loaded from `database`, saved back to it on every change
+    pub fn persisted(database: Arc<Mutex<Database>>, pending: HashMap<String, PendingIdempotencyKey>) -> Self {
+        Self {
+            pending: std::sync::Mutex::new(pending),
+            database: Some(database),
+        }
+    }
+
+    /// Get the pending key for an operation, generating one if there is none
+    pub async fn key_for(&self, operation: &str) -> String {
+        let now = chrono::Utc::now().timestamp_millis();
+        let (key, changed) = {
+            let mut pending = self.pending.lock().unwrap();
+            let before = pending.len();
+            pending.retain(|_, key| !key.expired(now));
+            let mut created = false;
+            let key = pending
+                .entry(operation.to_string())
+                .or_insert_with(|| {
+                    created = true;
+                    PendingIdempotencyKey { key: uuid::Uuid::new_v4().to_string(), created_at: now }
+                })
+                .key
+                .clone();
+            (key, created || pending.len() != before)
+        };
+        if changed {
+            self.save().await;
+        }
+        key
+    }
+
+    /// Forget an operation's key once the server gave a definitive answer
+    pub async fn complete(&self, operation: &str) {
+        let removed = self.pending.lock().unwrap().remove(operation).is_some();
+        if removed {
+            self.save().await;
+        }
+    }
+
+    async fn save(&self) {
+        let Some(database) = &self.database else {
+            return;
+        };
+        let mut db = database.lock().await;
+        let pending = self.pending.lock().unwrap().clone();
+        if let Err(e) = db.save_idempotency_keys(&pending) {
+            tracing::warn!("⚠️ Could not save pending idempotency keys: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pending_keys_survive_a_restart() {
+        let database = Arc::new(Mutex::new(Database::open_in_memory().unwrap()));
+        let keys = IdempotencyKeys::persisted(database.clone(), HashMap::new());
+        let key = keys.key_for("publish").await;
+        let done = keys.key_for("send").await;
+        keys.complete("send").await;
+
+        let loaded = database.lock().await.load_idempotency_keys().unwrap();
+        let restarted = IdempotencyKeys::persisted(database.clone(), loaded);
+        assert_eq!(restarted.key_for("publish").await, key);
+        assert_ne!(restarted.key_for("send").await, done);
+    }
+
+    #[tokio::test]
+    async fn test_expired_keys_are_replaced() {
+        let stale = PendingIdempotencyKey {
+            key: "stale".to_string(),
+            created_at: chrono::Utc::now().timestamp_millis() - IDEMPOTENCY_KEY_TTL.as_millis() as i64,
+        };
+        let keys = IdempotencyKeys {
+            pending: std::sync::Mutex::new(HashMap::from([("publish".to_string(), stale)])),
+            database: None,
+        };
+        assert_ne!(keys.key_for("publish").await, "stale");
+    }
+}
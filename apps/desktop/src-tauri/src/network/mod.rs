@@ -4,51 +4,156 @@
 //! 
 //! Updated: Added handle reservation, claiming, and record publishing
 
+use crate::config::ReconnectPolicy;
+use crate::devices::DeviceList;
+use crate::profile::ProfileRecord;
 use gns_crypto_core::{Breadcrumb, GnsEnvelope};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use std::time::Duration;
+use tokio::sync::{mpsc, watch, RwLock};
+use tokio::task::JoinHandle;
 use futures_util::{SinkExt, StreamExt};
 use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::Instrument;
+
+mod connectivity;
+mod idempotency;
+mod response;
+
+pub use connectivity::{
+    Connectivity, ConnectivityChanged, ConnectivityMonitor, ConnectivityReason, CONNECTIVITY_EVENT,
+};
+pub use idempotency::{idempotency_operation, IdempotencyKeys, PendingIdempotencyKey, IDEMPOTENCY_HEADER};
+pub use response::ApiEnvelope;
 
 // ==================== API Client ====================
 
+/// Attempts made by `send_idempotent` before giving up
+const MAX_IDEMPOTENT_ATTEMPTS: u32 = 3;
+
 pub struct ApiClient {
     client: Client,
-    base_url: String,
+    /// Swappable at runtime via the endpoint settings
+    base_url: std::sync::RwLock<String>,
+    connectivity: Option<Connectivity>,
+    idempotency_keys: IdempotencyKeys,
 }
 
 impl ApiClient {
     pub fn new(base_url: &str) -> Result<Self, NetworkError> {
+        Self::with_timeout(base_url, Duration::from_secs(30))
+    }
+
+    /// A client whose requests give up after `timeout`
+    pub fn with_timeout(base_url: &str, timeout: Duration) -> Result<Self, NetworkError> {
         let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
+            .timeout(timeout)
             .build()
             .map_err(|e| NetworkError::ClientError(e.to_string()))?;
 
         Ok(Self {
             client,
-            base_url: base_url.to_string(),
+            base_url: std::sync::RwLock::new(base_url.trim_end_matches('/').to_string()),
+            connectivity: None,
+            idempotency_keys: IdempotencyKeys::default(),
         })
     }
 
-    pub fn base_url(&self) -> &str {
-        &self.base_url
+    /// Report whether requests reach the backend
+    pub fn with_connectivity(mut self, connectivity: Connectivity) -> Self {
+        self.connectivity = Some(connectivity);
+        self
+    }
+
+    /// Reuse `keys` for unacknowledged mutating requests, so retries after
+    /// a restart carry the same key
+    pub fn with_idempotency_keys(mut self, keys: IdempotencyKeys) -> Self {
+        self.idempotency_keys = keys;
+        self
+    }
+
+    fn report_reachable(&self, reachable: bool) {
+        if let Some(connectivity) = &self.connectivity {
+            connectivity.report_api(reachable);
+        }
+    }
+
+    /// GET `url`. Any HTTP response, even an error status, counts as the
+    /// backend being reachable.
+    async fn get(&self, url: &str) -> Result<reqwest::Response, NetworkError> {
+        let result = self.client.get(url).send().await;
+        self.report_reachable(result.is_ok());
+        result.map_err(|e| NetworkError::RequestError(e.to_string()))
+    }
+
+    pub fn base_url(&self) -> String {
+        self.base_url.read().unwrap().clone()
+    }
+
+    /// Point the client at a different backend
+    pub fn set_base_url(&self, base_url: &str) {
+        *self.base_url.write().unwrap() = base_url.trim_end_matches('/').to_string();
     }
 
     pub fn client(&self) -> &Client {
         &self.client
     }
 
+    /// Send a mutating request with an `Idempotency-Key` header.
+    ///
+    /// Transport failures and 5xx responses are retried with the same key,
+    /// so the server can drop duplicates. The key is released once a
+    /// non-5xx response arrives; otherwise it is kept for the caller's retry.
+    pub async fn send_idempotent<F>(
+        &self,
+        operation: &str,
+        build: F,
+    ) -> Result<reqwest::Response, NetworkError>
+    where
+        F: Fn(&Client) -> reqwest::RequestBuilder,
+    {
+        let key = self.idempotency_keys.key_for(operation).await;
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+            let result = build(&self.client)
+                .header(IDEMPOTENCY_HEADER, &key)
+                .send()
+                .await;
+
+            self.report_reachable(result.is_ok());
+            match result {
+                Ok(response) if !response.status().is_server_error() => {
+                    self.idempotency_keys.complete(operation).await;
+                    return Ok(response);
+                }
+                Ok(response) if attempt >= MAX_IDEMPOTENT_ATTEMPTS => return Ok(response),
+                Err(e) if attempt >= MAX_IDEMPOTENT_ATTEMPTS => {
+                    return Err(NetworkError::RequestError(e.to_string()));
+                }
+                Ok(response) => {
+                    tracing::warn!("Attempt {} returned {}, retrying with key {}", attempt, response.status(), key);
+                }
+                Err(e) => {
+                    tracing::warn!("Attempt {} failed ({}), retrying with key {}", attempt, e, key);
+                }
+            }
+
+            tokio::time::sleep(Duration::from_millis(250 * 2u64.pow(attempt - 1))).await;
+        }
+    }
+
     // ==================== Identity/Handle Resolution ====================
 
     pub async fn resolve_handle(&self, handle: &str) -> Result<Option<IdentityInfo>, NetworkError> {
         let clean_handle = handle.trim_start_matches('@').to_lowercase();
-        let url = format!("{}/handles/{}", self.base_url, clean_handle);
+        let url = format!("{}/handles/{}", self.base_url(), clean_handle);
 
-        let response = self.client.get(&url).send().await
-            .map_err(|e| NetworkError::RequestError(e.to_string()))?;
+        let response = self.get(&url).await?;
 
         if response.status() == 404 {
             return Ok(None);
@@ -58,24 +163,26 @@ impl ApiClient {
             return Err(NetworkError::ApiError(format!("API returned status: {}", response.status())));
         }
 
-        let data: serde_json::Value = response.json().await
-            .map_err(|e| NetworkError::ParseError(e.to_string()))?;
+        let data = Self::read_json(response).await?;
+
+        let public_key = data["data"]["public_key"].as_str().unwrap_or_default().to_string();
+        let profile = verified_profile(&public_key, &data["data"]);
 
         Ok(Some(IdentityInfo {
-            public_key: data["data"]["public_key"].as_str().unwrap_or_default().to_string(),
             encryption_key: data["data"]["encryption_key"].as_str().unwrap_or_default().to_string(),
             handle: data["data"]["handle"].as_str().map(|s| s.to_string()),
             avatar_url: data["data"]["avatar_url"].as_str().map(|s| s.to_string()),
-            display_name: data["data"]["display_name"].as_str().map(|s| s.to_string()),
+            display_name: profile.as_ref().and_then(|p| p.display_name.clone()),
             is_verified: data["data"]["is_verified"].as_bool().unwrap_or(false),
+            public_key,
+            profile,
         }))
     }
 
     pub async fn get_handle_for_key(&self, public_key: &str) -> Result<Option<String>, NetworkError> {
-        let url = format!("{}/identities/{}", self.base_url, public_key);
+        let url = format!("{}/identities/{}", self.base_url(), public_key);
 
-        let response = self.client.get(&url).send().await
-            .map_err(|e| NetworkError::RequestError(e.to_string()))?;
+        let response = self.get(&url).await?;
 
         if response.status() == 404 {
             return Ok(None);
@@ -85,17 +192,15 @@ impl ApiClient {
             return Err(NetworkError::ApiError(format!("API returned status: {}", response.status())));
         }
 
-        let data: serde_json::Value = response.json().await
-            .map_err(|e| NetworkError::ParseError(e.to_string()))?;
+        let data = Self::read_json(response).await?;
 
         Ok(data["data"]["handle"].as_str().map(|s| s.to_string()))
     }
 
     pub async fn get_identity(&self, public_key: &str) -> Result<Option<IdentityInfo>, NetworkError> {
-        let url = format!("{}/identities/{}", self.base_url, public_key);
+        let url = format!("{}/identities/{}", self.base_url(), public_key);
 
-        let response = self.client.get(&url).send().await
-            .map_err(|e| NetworkError::RequestError(e.to_string()))?;
+        let response = self.get(&url).await?;
 
         if response.status() == 404 {
             return Ok(None);
@@ -105,34 +210,76 @@ impl ApiClient {
             return Err(NetworkError::ApiError(format!("API returned status: {}", response.status())));
         }
 
-        let data: serde_json::Value = response.json().await
-            .map_err(|e| NetworkError::ParseError(e.to_string()))?;
+        let data = Self::read_json(response).await?;
+
+        let public_key = data["data"]["public_key"].as_str().unwrap_or(public_key).to_string();
+        let profile = verified_profile(&public_key, &data["data"]);
 
         Ok(Some(IdentityInfo {
-            public_key: data["data"]["public_key"].as_str().unwrap_or(public_key).to_string(),
             encryption_key: data["data"]["encryption_key"].as_str().unwrap_or_default().to_string(),
             handle: data["data"]["handle"].as_str().map(|s| s.to_string()),
             avatar_url: data["data"]["avatar_url"].as_str().map(|s| s.to_string()),
-            display_name: data["data"]["display_name"].as_str().map(|s| s.to_string()),
+            display_name: profile.as_ref().and_then(|p| p.display_name.clone()),
             is_verified: data["data"]["is_verified"].as_bool().unwrap_or(false),
+            public_key,
+            profile,
         }))
     }
 
+    /// Fetch the signed identity record for a key
+    /// GET /records/{public_key}
+    pub async fn get_record(&self, public_key: &str) -> Result<Option<SignedRecord>, NetworkError> {
+        Ok(self.get_record_versioned(public_key).await?.map(|v| v.record))
+    }
+
+    /// Fetch the signed identity record along with the version to send
+    /// back as `If-Match` when publishing over it: the `ETag` header if
+    /// the server sends one, otherwise the record's `updated_at`
+    pub async fn get_record_versioned(&self, public_key: &str) -> Result<Option<VersionedRecord>, NetworkError> {
+        let url = format!("{}/records/{}", self.base_url(), public_key);
+
+        let response = self.get(&url).await?;
+
+        if response.status() == 404 {
+            return Ok(None);
+        }
+
+        if !response.status().is_success() {
+            return Err(NetworkError::ApiError(format!("API returned status: {}", response.status())));
+        }
+
+        let etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        let data = Self::read_json(response).await?;
+
+        let record_json = data["data"]["record_json"].clone();
+        let signature = data["data"]["signature"].as_str().unwrap_or_default().to_string();
+        if record_json.is_null() || signature.is_empty() {
+            return Ok(None);
+        }
+
+        let version = etag
+            .or_else(|| record_json["updated_at"].as_str().map(|v| format!("\"{}\"", v)))
+            .unwrap_or_default();
+        Ok(Some(VersionedRecord { record: SignedRecord { record_json, signature }, version }))
+    }
+
     // ==================== Handle Availability & Reservation ====================
 
     /// Check if a handle is available
     /// GET /aliases?check={handle}
     pub async fn check_handle_available(&self, handle: &str) -> Result<HandleCheckResult, NetworkError> {
         let clean_handle = handle.trim_start_matches('@').to_lowercase();
-        let url = format!("{}/aliases?check={}", self.base_url, clean_handle);
+        let url = format!("{}/aliases?check={}", self.base_url(), clean_handle);
 
         tracing::debug!("Checking handle availability: {}", clean_handle);
 
-        let response = self.client.get(&url).send().await
-            .map_err(|e| NetworkError::RequestError(e.to_string()))?;
+        let response = self.get(&url).await?;
 
-        let data: serde_json::Value = response.json().await
-            .map_err(|e| NetworkError::ParseError(e.to_string()))?;
+        let data = Self::read_json(response).await?;
 
         // Handle both old format (data.available) and new format (available)
         let available = data["data"]["available"].as_bool()
@@ -165,7 +312,7 @@ impl ApiClient {
         timestamp: &str,
     ) -> Result<HandleReservationResult, NetworkError> {
         let clean_handle = handle.trim_start_matches('@').to_lowercase();
-        let url = format!("{}/aliases/{}/reserve", self.base_url, clean_handle);
+        let url = format!("{}/aliases/{}/reserve", self.base_url(), clean_handle);
 
         tracing::info!("Reserving handle @{} for {}...", clean_handle, &public_key[..16]);
 
@@ -176,15 +323,13 @@ impl ApiClient {
             "timestamp": timestamp,
         });
 
-        let response = self.client.post(&url)
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| NetworkError::RequestError(e.to_string()))?;
+        let operation = idempotency_operation(&["reserve_handle", &clean_handle, public_key]);
+        let response = self
+            .send_idempotent(&operation, |client| client.post(&url).json(&request_body))
+            .await?;
 
         let status = response.status();
-        let data: serde_json::Value = response.json().await
-            .map_err(|e| NetworkError::ParseError(e.to_string()))?;
+        let data = Self::read_json(response).await?;
 
         if status.is_success() && data["success"].as_bool().unwrap_or(false) {
             tracing::info!("✅ Handle @{} reserved successfully!", clean_handle);
@@ -226,7 +371,7 @@ impl ApiClient {
         signature: &str,
     ) -> Result<HandleClaimResult, NetworkError> {
         let clean_handle = handle.trim_start_matches('@').to_lowercase();
-        let url = format!("{}/aliases/{}", self.base_url, clean_handle);
+        let url = format!("{}/aliases/{}", self.base_url(), clean_handle);
 
         tracing::info!("Claiming handle @{} with {} breadcrumbs", clean_handle, proof.breadcrumb_count);
 
@@ -242,15 +387,13 @@ impl ApiClient {
             "signature": signature,
         });
 
-        let response = self.client.put(&url)
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| NetworkError::RequestError(e.to_string()))?;
+        let operation = idempotency_operation(&["claim_handle", &clean_handle, public_key]);
+        let response = self
+            .send_idempotent(&operation, |client| client.put(&url).json(&request_body))
+            .await?;
 
         let status = response.status();
-        let data: serde_json::Value = response.json().await
-            .map_err(|e| NetworkError::ParseError(e.to_string()))?;
+        let data = Self::read_json(response).await?;
 
         if status.is_success() && data["success"].as_bool().unwrap_or(false) {
             tracing::info!("🎉 Handle @{} claimed successfully!", clean_handle);
@@ -276,6 +419,50 @@ impl ApiClient {
         }
     }
 
+    // ==================== Handle Release ====================
+
+    /// Give up a reserved or claimed handle so it can be reserved again
+    /// POST /aliases/{handle}/release
+    pub async fn release_handle(
+        &self,
+        handle: &str,
+        public_key: &str,
+        signature: &str,
+        timestamp: &str,
+    ) -> Result<HandleReleaseResult, NetworkError> {
+        let clean_handle = handle.trim_start_matches('@').to_lowercase();
+        let url = format!("{}/aliases/{}/release", self.base_url(), clean_handle);
+
+        tracing::info!("Releasing handle @{} for {}...", clean_handle, &public_key[..16]);
+
+        let request_body = json!({
+            "identity": public_key,
+            "signature": signature,
+            "timestamp": timestamp,
+        });
+
+        let operation = idempotency_operation(&["release_handle", &clean_handle, public_key]);
+        let response = self
+            .send_idempotent(&operation, |client| client.post(&url).json(&request_body))
+            .await?;
+
+        let status = response.status();
+        let data = Self::read_json(response).await?;
+
+        if status.is_success() && data["success"].as_bool().unwrap_or(false) {
+            tracing::info!("✅ Handle @{} released", clean_handle);
+            Ok(HandleReleaseResult { success: true, handle: clean_handle, error: None })
+        } else {
+            let error_msg = data["error"].as_str()
+                .or_else(|| data["message"].as_str())
+                .unwrap_or("Unknown error")
+                .to_string();
+
+            tracing::warn!("❌ Handle release failed: {}", error_msg);
+            Ok(HandleReleaseResult { success: false, handle: clean_handle, error: Some(error_msg) })
+        }
+    }
+
     /// Legacy claim_handle (kept for compatibility)
     pub async fn claim_handle(
         &self,
@@ -286,7 +473,7 @@ impl ApiClient {
         breadcrumbs: Vec<Breadcrumb>,
     ) -> Result<ClaimResponse, NetworkError> {
         let clean_handle = handle.trim_start_matches('@').to_lowercase();
-        let url = format!("{}/aliases/{}/claim", self.base_url, clean_handle);
+        let url = format!("{}/aliases/{}/claim", self.base_url(), clean_handle);
 
         let request = ClaimRequest {
             handle: clean_handle,
@@ -296,10 +483,12 @@ impl ApiClient {
             breadcrumbs,
         };
 
-        let response = self.client.post(&url).json(&request).send().await
-            .map_err(|e| NetworkError::RequestError(e.to_string()))?;
+        let operation = idempotency_operation(&["claim_handle_legacy", &request.handle, public_key]);
+        let response = self
+            .send_idempotent(&operation, |client| client.post(&url).json(&request))
+            .await?;
 
-        let data: ClaimResponse = response.json().await
+        let data: ClaimResponse = serde_json::from_value(Self::read_json(response).await?)
             .map_err(|e| NetworkError::ParseError(e.to_string()))?;
 
         Ok(data)
@@ -322,7 +511,7 @@ impl ApiClient {
         // This is problematic for signature verification if the caller signed a different timestamp
         // Kept for backward compatibility but should be avoided
         
-        let url = format!("{}/records/{}", self.base_url, public_key);
+        let url = format!("{}/records/{}", self.base_url(), public_key);
         let now = chrono::Utc::now().to_rfc3339();
 
         let mut record_json = json!({
@@ -347,15 +536,13 @@ impl ApiClient {
             "signature": signature,
         });
 
-        let response = self.client.put(&url)
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| NetworkError::RequestError(e.to_string()))?;
+        let operation = idempotency_operation(&["publish_record", public_key, signature]);
+        let response = self
+            .send_idempotent(&operation, |client| client.put(&url).json(&request_body))
+            .await?;
 
         let status = response.status();
-        let data: serde_json::Value = response.json().await
-            .map_err(|e| NetworkError::ParseError(e.to_string()))?;
+        let data = Self::read_json(response).await?;
 
         if status.is_success() && data["success"].as_bool().unwrap_or(false) {
             tracing::info!("✅ Record published successfully!");
@@ -375,7 +562,34 @@ impl ApiClient {
         record_json: &serde_json::Value,
         signature: &str,
     ) -> Result<(), NetworkError> {
-        let url = format!("{}/records/{}", self.base_url, public_key);
+        self.put_record(public_key, record_json, signature, None).await
+    }
+
+    /// Publish a pre-signed record only over the version it was built from
+    /// (`None`: only if no record exists yet). If someone else published in
+    /// the meantime the server refuses it with [`NetworkError::Conflict`].
+    pub async fn publish_signed_record_if_match(
+        &self,
+        public_key: &str,
+        record_json: &serde_json::Value,
+        signature: &str,
+        expected_version: Option<&str>,
+    ) -> Result<(), NetworkError> {
+        let precondition = match expected_version {
+            Some(version) => (reqwest::header::IF_MATCH, version),
+            None => (reqwest::header::IF_NONE_MATCH, "*"),
+        };
+        self.put_record(public_key, record_json, signature, Some(precondition)).await
+    }
+
+    async fn put_record(
+        &self,
+        public_key: &str,
+        record_json: &serde_json::Value,
+        signature: &str,
+        precondition: Option<(reqwest::header::HeaderName, &str)>,
+    ) -> Result<(), NetworkError> {
+        let url = format!("{}/records/{}", self.base_url(), public_key);
 
         tracing::info!("Publishing signed record for {}...", &public_key[..16]);
 
@@ -384,15 +598,24 @@ impl ApiClient {
             "signature": signature,
         });
 
-        let response = self.client.put(&url)
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| NetworkError::RequestError(e.to_string()))?;
+        let record = record_json.to_string();
+        let operation = idempotency_operation(&["publish_record", public_key, &record]);
+        let response = self
+            .send_idempotent(&operation, |client| {
+                let request = client.put(&url).json(&request_body);
+                match &precondition {
+                    Some((header, value)) => request.header(header.clone(), *value),
+                    None => request,
+                }
+            })
+            .await?;
 
         let status = response.status();
-        let data: serde_json::Value = response.json().await
-            .map_err(|e| NetworkError::ParseError(e.to_string()))?;
+        if status == reqwest::StatusCode::PRECONDITION_FAILED || status == reqwest::StatusCode::CONFLICT {
+            tracing::info!("Record for {} changed since it was read", &public_key[..16]);
+            return Err(NetworkError::Conflict(format!("Record changed since it was read (HTTP {})", status.as_u16())));
+        }
+        let data = Self::read_json(response).await?;
 
         if status.is_success() && data["success"].as_bool().unwrap_or(false) {
             tracing::info!("✅ Record published successfully!");
@@ -415,7 +638,7 @@ impl ApiClient {
         payload: &str,
         signature: &str,
     ) -> Result<bool, NetworkError> {
-        let url = format!("{}/breadcrumbs", self.base_url);
+        let url = format!("{}/breadcrumbs", self.base_url());
 
         let request_body = json!({
             "pk_root": pk_root,
@@ -423,11 +646,10 @@ impl ApiClient {
             "signature": signature,
         });
 
-        let response = self.client.post(&url)
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| NetworkError::RequestError(e.to_string()))?;
+        let operation = idempotency_operation(&["upload_breadcrumb", pk_root, payload]);
+        let response = self
+            .send_idempotent(&operation, |client| client.post(&url).json(&request_body))
+            .await?;
 
         if response.status().is_success() {
             Ok(true)
@@ -441,17 +663,15 @@ impl ApiClient {
     /// Fetch encrypted breadcrumbs from server
     /// GET /breadcrumbs/{pk}
     pub async fn fetch_breadcrumbs(&self, pk_root: &str) -> Result<Vec<serde_json::Value>, NetworkError> {
-        let url = format!("{}/breadcrumbs/{}", self.base_url, pk_root);
+        let url = format!("{}/breadcrumbs/{}", self.base_url(), pk_root);
 
-        let response = self.client.get(&url).send().await
-            .map_err(|e| NetworkError::RequestError(e.to_string()))?;
+        let response = self.get(&url).await?;
 
         if !response.status().is_success() {
             return Err(NetworkError::ApiError(format!("API returned status: {}", response.status())));
         }
 
-        let data: serde_json::Value = response.json().await
-            .map_err(|e| NetworkError::ParseError(e.to_string()))?;
+        let data = Self::read_json(response).await?;
 
         let breadcrumbs = data["data"].as_array()
             .map(|arr| arr.clone())
@@ -463,10 +683,12 @@ impl ApiClient {
     // ==================== Messaging ====================
 
     pub async fn send_envelope(&self, envelope: &GnsEnvelope) -> Result<(), NetworkError> {
-        let url = format!("{}/messages", self.base_url);
+        let url = format!("{}/messages", self.base_url());
 
-        let response = self.client.post(&url).json(envelope).send().await
-            .map_err(|e| NetworkError::RequestError(e.to_string()))?;
+        let operation = idempotency_operation(&["send_envelope", &envelope.id]);
+        let response = self
+            .send_idempotent(&operation, |client| client.post(&url).json(envelope))
+            .await?;
 
         if !response.status().is_success() {
             let error_text = response.text().await.unwrap_or_default();
@@ -477,17 +699,15 @@ impl ApiClient {
     }
 
     pub async fn fetch_pending_messages(&self, public_key: &str) -> Result<Vec<GnsEnvelope>, NetworkError> {
-        let url = format!("{}/messages/pending/{}", self.base_url, public_key);
+        let url = format!("{}/messages/pending/{}", self.base_url(), public_key);
 
-        let response = self.client.get(&url).send().await
-            .map_err(|e| NetworkError::RequestError(e.to_string()))?;
+        let response = self.get(&url).await?;
 
         if !response.status().is_success() {
             return Ok(Vec::new());
         }
 
-        let data: serde_json::Value = response.json().await
-            .map_err(|e| NetworkError::ParseError(e.to_string()))?;
+        let data = Self::read_json(response).await?;
 
         let envelopes: Vec<GnsEnvelope> = serde_json::from_value(data["messages"].clone()).unwrap_or_default();
 
@@ -549,6 +769,51 @@ pub enum IncomingMessage {
     Unknown(String),
 }
 
+/// How long shutdown waits for queued messages to reach the socket
+const SHUTDOWN_FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long `connect` waits for the relay to confirm the subscription
+const SUBSCRIBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Emitted each time the relay confirms a subscription
+pub const RELAY_READY_EVENT: &str = "relay_ready";
+
+/// Payload of [`RELAY_READY_EVENT`]
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayReady {
+    pub public_key: String,
+}
+
+/// Call `on_ready` each time the relay confirms a subscription, as seen
+/// through [`RelayConnection::readiness`]. Ends when the relay is dropped.
+pub async fn watch_relay_ready(mut readiness: watch::Receiver<Option<String>>, on_ready: impl Fn(RelayReady)) {
+    while readiness.changed().await.is_ok() {
+        let subscribed = readiness.borrow_and_update().clone();
+        if let Some(public_key) = subscribed {
+            tracing::info!("📡 Relay subscription active");
+            on_ready(RelayReady { public_key });
+        }
+    }
+}
+
+/// Stops a relay's reconnect backoff without locking the connection.
+///
+/// Callers hold the relay mutex for the whole of `reconnect`, backoff
+/// included, so the exit path needs a way in that doesn't wait on it.
+#[derive(Clone)]
+pub struct RelayShutdown(Arc<watch::Sender<bool>>);
+
+impl RelayShutdown {
+    /// Mark the relay as shut down. Returns false if it already was.
+    pub fn trigger(&self) -> bool {
+        !self.0.send_replace(true)
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.0.borrow()
+    }
+}
+
 pub struct RelayConnection {
     url: String,
     state: Arc<RwLock<ConnectionState>>,
@@ -557,30 +822,52 @@ pub struct RelayConnection {
     sender: Arc<RwLock<Option<mpsc::Sender<String>>>>,
     /// Channel for incoming messages
     incoming_tx: Option<mpsc::Sender<IncomingMessage>>,
+    shutdown: RelayShutdown,
+    /// Public key the relay has confirmed a subscription for; none until
+    /// it does and again once the socket drops
+    ready: Arc<watch::Sender<Option<String>>>,
+    reader_task: Arc<RwLock<Option<JoinHandle<()>>>>,
+    writer_task: Arc<RwLock<Option<JoinHandle<()>>>>,
+    connectivity: Option<Connectivity>,
+    reconnect_policy: ReconnectPolicy,
 }
 
 impl RelayConnection {
     pub fn new(url: &str) -> Result<Self, NetworkError> {
-        let ws_url = if url.starts_with("https://") {
-            url.replace("https://", "wss://") + "/ws"
-        } else if url.starts_with("wss://") && !url.ends_with("/ws") {
-            url.to_string() + "/ws"
-        } else if url.starts_with("http://") {
-            url.replace("http://", "ws://") + "/ws"
-        } else {
-            url.to_string()
-        };
-
         Ok(Self {
-            url: ws_url,
+            url: Self::websocket_url(url),
             state: Arc::new(RwLock::new(ConnectionState::Disconnected)),
             last_message_time: Arc::new(RwLock::new(None)),
             reconnect_attempts: Arc::new(RwLock::new(0)),
             sender: Arc::new(RwLock::new(None)),
             incoming_tx: None,
+            shutdown: RelayShutdown(Arc::new(watch::channel(false).0)),
+            ready: Arc::new(watch::channel(None).0),
+            reader_task: Arc::new(RwLock::new(None)),
+            writer_task: Arc::new(RwLock::new(None)),
+            connectivity: None,
+            reconnect_policy: ReconnectPolicy::default(),
         })
     }
 
+    /// Back off between reconnects according to `policy`
+    pub fn with_reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = policy;
+        self
+    }
+
+    /// Report connects and drops to `connectivity`
+    pub fn with_connectivity(mut self, connectivity: Connectivity) -> Self {
+        self.connectivity = Some(connectivity);
+        self
+    }
+
+    fn report_connected(&self, connected: bool) {
+        if let Some(connectivity) = &self.connectivity {
+            connectivity.report_relay(connected);
+        }
+    }
+
     pub fn with_incoming_channel(mut self, tx: mpsc::Sender<IncomingMessage>) -> Self {
         self.incoming_tx = Some(tx);
         self
@@ -594,6 +881,12 @@ impl RelayConnection {
             reconnect_attempts: self.reconnect_attempts.clone(),
             sender: self.sender.clone(),
             incoming_tx: Some(tx),
+            shutdown: self.shutdown.clone(),
+            ready: self.ready.clone(),
+            reader_task: self.reader_task.clone(),
+            writer_task: self.writer_task.clone(),
+            connectivity: self.connectivity.clone(),
+            reconnect_policy: self.reconnect_policy,
         }
     }
 
@@ -601,6 +894,38 @@ impl RelayConnection {
         &self.url
     }
 
+    pub fn shutdown_handle(&self) -> RelayShutdown {
+        self.shutdown.clone()
+    }
+
+    /// Follow the subscription state without locking the connection
+    pub fn readiness(&self) -> watch::Receiver<Option<String>> {
+        self.ready.subscribe()
+    }
+
+    /// Whether the relay has confirmed the subscription for this socket
+    pub fn is_ready(&self) -> bool {
+        self.ready.borrow().is_some()
+    }
+
+    /// Change the relay URL; takes effect on the next connect
+    pub fn set_url(&mut self, url: &str) {
+        self.url = Self::websocket_url(url);
+    }
+
+    /// Normalise an http(s)/ws(s) base URL to the relay's /ws endpoint
+    fn websocket_url(url: &str) -> String {
+        if url.starts_with("https://") {
+            url.replace("https://", "wss://") + "/ws"
+        } else if url.starts_with("wss://") && !url.ends_with("/ws") {
+            url.to_string() + "/ws"
+        } else if url.starts_with("http://") {
+            url.replace("http://", "ws://") + "/ws"
+        } else {
+            url.to_string()
+        }
+    }
+
     pub async fn is_connected(&self) -> bool {
         *self.state.read().await == ConnectionState::Connected
     }
@@ -617,9 +942,34 @@ impl RelayConnection {
         *self.reconnect_attempts.read().await
     }
 
+    /// Open the relay socket for `public_key`. Returns once the relay has
+    /// confirmed the subscription, so the incoming channel never sees a
+    /// message for a connection that isn't live yet.
+    ///
+    /// Connecting again after `shutdown` is allowed and lifts it.
     pub async fn connect(&self, public_key: &str) -> Result<(), NetworkError> {
+        self.shutdown.0.send_replace(false);
+        self.open(public_key).await
+    }
+
+    /// `connect` without lifting a shutdown, for retries that a shutdown
+    /// must stop
+    #[tracing::instrument(
+        name = "relay_connect",
+        skip_all,
+        fields(
+            correlation_id = %uuid::Uuid::new_v4(),
+            url = %self.url,
+            identity = %public_key.get(..16).unwrap_or(public_key),
+        )
+    )]
+    async fn open(&self, public_key: &str) -> Result<(), NetworkError> {
+        if self.shutdown.is_triggered() {
+            return Err(NetworkError::ShutDown);
+        }
+        self.ready.send_replace(None);
         *self.state.write().await = ConnectionState::Connecting;
-        tracing::info!("Connecting to relay: {}", self.url);
+        tracing::info!("Connecting to relay");
 
         #[cfg(any(target_os = "ios", target_os = "android"))]
         let device_type = "mobile";
@@ -629,24 +979,69 @@ impl RelayConnection {
         let url_with_auth = format!("{}?pk={}&device={}", self.url, public_key, device_type);
 
         let (ws_stream, _) = connect_async(&url_with_auth).await.map_err(|e| {
-            tracing::error!("WebSocket connection failed: {}", e);
+            tracing::error!(error = %e, "WebSocket connection failed");
+            self.report_connected(false);
             NetworkError::ConnectionError(e.to_string())
         })?;
 
-        tracing::info!("WebSocket connected to {}", self.url);
+        tracing::info!("WebSocket connected");
+
+        // A reader left over from the previous socket must not mark this one
+        // as disconnected when it finally winds down
+        if let Some(stale) = self.reader_task.write().await.take() {
+            stale.abort();
+        }
 
         let (mut write, mut read) = ws_stream.split();
+
+        // Nothing is handed on until the relay confirms the subscription;
+        // whatever it sends before then is delivered right after
+        let early = match tokio::time::timeout(SUBSCRIBE_TIMEOUT, await_subscription(&mut read)).await {
+            Ok(Ok(early)) => early,
+            Ok(Err(e)) => {
+                tracing::error!(error = %e, "Relay subscription failed");
+                *self.state.write().await = ConnectionState::Disconnected;
+                self.report_connected(false);
+                return Err(e);
+            }
+            Err(_) => {
+                tracing::error!("Relay did not confirm the subscription within {:?}", SUBSCRIBE_TIMEOUT);
+                *self.state.write().await = ConnectionState::Disconnected;
+                self.report_connected(false);
+                return Err(NetworkError::ConnectionError(format!(
+                    "Relay did not confirm the subscription within {}s",
+                    SUBSCRIBE_TIMEOUT.as_secs()
+                )));
+            }
+        };
+        tracing::info!("Relay subscription confirmed");
+
         let (tx, mut rx) = mpsc::channel::<String>(100);
         *self.sender.write().await = Some(tx);
         *self.state.write().await = ConnectionState::Connected;
         *self.reconnect_attempts.write().await = 0;
+        *self.last_message_time.write().await = Some(chrono::Utc::now().timestamp());
+        self.report_connected(true);
 
         let state = self.state.clone();
         let last_message_time = self.last_message_time.clone();
         let incoming_tx = self.incoming_tx.clone();
 
+        // Keep the read/write loops inside the connect span so their
+        // log lines carry the same correlation id
+        let span = tracing::Span::current();
+
         let read_state = state.clone();
-        tokio::spawn(async move {
+        let read_connectivity = self.connectivity.clone();
+        let read_ready = self.ready.clone();
+        let reader = tokio::spawn(async move {
+            if let Some(ref tx) = incoming_tx {
+                for parsed in early {
+                    if let Err(e) = tx.send(parsed).await {
+                        tracing::error!("Failed to send incoming message to channel: {}", e);
+                    }
+                }
+            }
             while let Some(msg) = read.next().await {
                 match msg {
                     Ok(Message::Text(text)) => {
@@ -677,18 +1072,36 @@ impl RelayConnection {
                     _ => {}
                 }
             }
-        });
+            read_ready.send_replace(None);
+            if let Some(connectivity) = read_connectivity {
+                connectivity.report_relay(false);
+            }
+        }.instrument(span.clone()));
 
+        // Runs until the sender is dropped, so everything queued before a
+        // disconnect still goes out ahead of the close frame
         let write_state = state.clone();
-        tokio::spawn(async move {
+        let write_connectivity = self.connectivity.clone();
+        let write_ready = self.ready.clone();
+        let writer = tokio::spawn(async move {
             while let Some(msg) = rx.recv().await {
                 if write.send(Message::Text(msg)).await.is_err() {
                     tracing::error!("Failed to send WebSocket message");
                     *write_state.write().await = ConnectionState::Disconnected;
-                    break;
+                    write_ready.send_replace(None);
+                    if let Some(connectivity) = write_connectivity {
+                        connectivity.report_relay(false);
+                    }
+                    return;
                 }
             }
-        });
+            let _ = write.send(Message::Close(None)).await;
+            let _ = write.close().await;
+        }.instrument(span));
+
+        *self.reader_task.write().await = Some(reader);
+        *self.writer_task.write().await = Some(writer);
+        self.ready.send_replace(Some(public_key.to_string()));
 
         Ok(())
     }
@@ -696,20 +1109,60 @@ impl RelayConnection {
     pub async fn disconnect(&self) -> Result<(), NetworkError> {
         tracing::info!("Disconnecting from relay");
         *self.state.write().await = ConnectionState::Disconnected;
+        self.ready.send_replace(None);
         *self.sender.write().await = None;
+        self.report_connected(false);
         Ok(())
     }
 
+    /// Close the relay for good before the app exits: cancel any reconnect
+    /// backoff, flush the outbox, send a close frame and stop the socket
+    /// tasks. Calling it again is a no-op.
+    pub async fn shutdown(&self) {
+        if self.shutdown.trigger() {
+            tracing::info!("Shutting down relay connection");
+        }
+        *self.state.write().await = ConnectionState::Disconnected;
+        self.ready.send_replace(None);
+        *self.sender.write().await = None;
+
+        let writer = self.writer_task.write().await.take();
+        if let Some(mut writer) = writer {
+            if tokio::time::timeout(SHUTDOWN_FLUSH_TIMEOUT, &mut writer).await.is_err() {
+                tracing::warn!("Relay outbox not flushed within {:?}, dropping it", SHUTDOWN_FLUSH_TIMEOUT);
+                writer.abort();
+            }
+        }
+        if let Some(reader) = self.reader_task.write().await.take() {
+            reader.abort();
+        }
+    }
+
+    #[tracing::instrument(
+        name = "relay_reconnect",
+        skip_all,
+        fields(correlation_id = %uuid::Uuid::new_v4(), attempt = tracing::field::Empty)
+    )]
     pub async fn reconnect(&self, public_key: &str) -> Result<(), NetworkError> {
         *self.reconnect_attempts.write().await += 1;
         *self.state.write().await = ConnectionState::Reconnecting;
         self.disconnect().await?;
         
         let attempts = *self.reconnect_attempts.read().await;
-        let delay = std::cmp::min(1000 * 2u64.pow(attempts), 30000);
-        tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+        let delay = self.reconnect_policy.delay(attempts).as_millis() as u64;
+        tracing::Span::current().record("attempt", attempts);
+        tracing::info!(delay_ms = delay, "Reconnecting to relay after backoff");
+
+        let mut shutdown = self.shutdown.0.subscribe();
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_millis(delay)) => {}
+            _ = shutdown.wait_for(|down| *down) => {
+                tracing::info!("Reconnect cancelled by shutdown");
+                return Err(NetworkError::ShutDown);
+            }
+        }
         
-        self.connect(public_key).await
+        self.open(public_key).await
     }
 
     pub async fn send_envelope(&self, envelope: &GnsEnvelope) -> Result<(), NetworkError> {
@@ -764,11 +1217,36 @@ impl RelayConnection {
     }
 }
 
+/// Read a fresh relay socket up to the welcome that confirms the
+/// subscription, returning everything received so far, welcome included
+async fn await_subscription<S>(read: &mut S) -> Result<Vec<IncomingMessage>, NetworkError>
+where
+    S: futures_util::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    let mut early = Vec::new();
+    while let Some(msg) = read.next().await {
+        match msg {
+            Ok(Message::Text(text)) => {
+                let parsed = parse_incoming_message(&text);
+                let subscribed = matches!(parsed, IncomingMessage::Welcome { .. });
+                early.push(parsed);
+                if subscribed {
+                    return Ok(early);
+                }
+            }
+            Ok(Message::Close(_)) => break,
+            Ok(_) => {}
+            Err(e) => return Err(NetworkError::ConnectionError(e.to_string())),
+        }
+    }
+    Err(NetworkError::ConnectionError("Relay closed before confirming the subscription".to_string()))
+}
+
 /// Parse incoming WebSocket message into typed enum
 fn parse_incoming_message(text: &str) -> IncomingMessage {
     // Truncate log for privacy/size
     let log_len = std::cmp::min(text.len(), 300);
-    println!("🔥 [RUST] WebSocket received: {}", &text[..log_len]);
+    tracing::trace!(len = text.len(), "WebSocket received: {}", &text[..log_len]);
     
     // Try to parse as JSON
     let json: serde_json::Value = match serde_json::from_str(text) {
@@ -871,8 +1349,56 @@ pub struct IdentityInfo {
     pub encryption_key: String,
     pub handle: Option<String>,
     pub avatar_url: Option<String>,
+    /// From the verified profile only, never the server's own field
     pub display_name: Option<String>,
     pub is_verified: bool,
+    /// Profile from the signed record, present only if its signature verified
+    #[serde(default)]
+    pub profile: Option<ProfileRecord>,
+}
+
+/// An identity record together with its owner's signature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedRecord {
+    pub record_json: serde_json::Value,
+    pub signature: String,
+}
+
+/// A signed record and the version it was read at
+#[derive(Debug, Clone)]
+pub struct VersionedRecord {
+    pub record: SignedRecord,
+    /// Opaque; send back as `If-Match`
+    pub version: String,
+}
+
+impl SignedRecord {
+    /// The verified profile section, if any
+    pub fn profile(&self, public_key: &str) -> Result<Option<ProfileRecord>, String> {
+        ProfileRecord::from_signed_record(public_key, &self.record_json, &self.signature)
+    }
+
+    /// The verified device list; empty if the record has none
+    pub fn devices(&self, public_key: &str) -> Result<DeviceList, String> {
+        DeviceList::from_signed_record(public_key, &self.record_json, &self.signature)
+    }
+}
+
+/// Profile from a response that embeds the signed record, dropped (with a
+/// warning) if the signature doesn't check out
+fn verified_profile(public_key: &str, data: &serde_json::Value) -> Option<ProfileRecord> {
+    let record = SignedRecord {
+        record_json: data.get("record_json")?.clone(),
+        signature: data["signature"].as_str()?.to_string(),
+    };
+
+    match record.profile(public_key) {
+        Ok(profile) => profile,
+        Err(e) => {
+            tracing::warn!("⚠️ Ignoring unverified profile for {}: {}", public_key, e);
+            None
+        }
+    }
 }
 
 /// Result of checking handle availability
@@ -903,6 +1429,14 @@ pub struct HandleClaimResult {
     pub error: Option<String>,
 }
 
+/// Result of releasing a handle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HandleReleaseResult {
+    pub success: bool,
+    pub handle: String,
+    pub error: Option<String>,
+}
+
 /// Proof for claiming a handle (Proof of Trajectory)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClaimProof {
@@ -922,6 +1456,7 @@ struct ClaimRequest {
 
 #[derive(Debug, Deserialize)]
 pub struct ClaimResponse {
+    #[serde(default)]
     pub success: bool,
     pub transaction_id: Option<String>,
     pub error: Option<String>,
@@ -937,8 +1472,111 @@ pub enum NetworkError {
     ApiError(String),
     #[error("Parse error: {0}")]
     ParseError(String),
+    /// A conditional write lost to a concurrent one
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    /// The body wasn't what the API sends: not JSON, cut short, or the
+    /// wrong shape
+    #[error("Unexpected response (HTTP {status}): {detail} - {snippet:?}")]
+    UnexpectedResponse { status: u16, detail: String, snippet: String },
     #[error("Connection error: {0}")]
     ConnectionError(String),
     #[error("Not connected to relay")]
     NotConnected,
+    #[error("Relay connection has been shut down")]
+    ShutDown,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_shutdown_is_idempotent_and_cancels_reconnect_backoff() {
+        let relay = Arc::new(RelayConnection::new("ws://127.0.0.1:9").unwrap());
+        // Enough failed attempts that the next backoff is the 30s cap
+        *relay.reconnect_attempts.write().await = 10;
+
+        let reconnecting = relay.clone();
+        let pending = tokio::spawn(async move { reconnecting.reconnect("pk").await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        relay.shutdown().await;
+        relay.shutdown().await;
+
+        let result = tokio::time::timeout(Duration::from_secs(1), pending)
+            .await
+            .expect("backoff should be cancelled")
+            .unwrap();
+        assert!(matches!(result, Err(NetworkError::ShutDown)));
+        assert_eq!(relay.get_state().await, ConnectionState::Disconnected);
+        assert!(!relay.shutdown_handle().trigger());
+        assert!(matches!(relay.reconnect("pk").await, Err(NetworkError::ShutDown)));
+
+        // An explicit connect lifts the shutdown; with nothing listening it
+        // then fails like any other connect
+        assert!(matches!(relay.connect("pk").await, Err(NetworkError::ConnectionError(_))));
+        assert!(!relay.shutdown_handle().is_triggered());
+    }
+
+    #[tokio::test]
+    async fn test_no_envelope_delivered_before_subscription_ack() {
+        use gns_crypto_core::{create_envelope_with_metadata, GnsIdentity};
+        use tokio::net::TcpListener;
+
+        let sender = GnsIdentity::generate();
+        let recipient = GnsIdentity::generate();
+        let envelope = create_envelope_with_metadata(
+            &sender,
+            None,
+            &recipient.public_key_hex(),
+            &recipient.encryption_key_hex(),
+            "text/plain",
+            b"early",
+            None,
+            None,
+        )
+        .unwrap();
+
+        // A relay that pushes an envelope first and only acks when told to
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/ws", listener.local_addr().unwrap());
+        let (ack_tx, ack_rx) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let message = json!({ "type": "message", "envelope": envelope });
+            ws.send(Message::Text(message.to_string())).await.unwrap();
+            ack_rx.await.unwrap();
+            let welcome = json!({ "type": "welcome", "publicKey": "pk" });
+            ws.send(Message::Text(welcome.to_string())).await.unwrap();
+            ws
+        });
+
+        let (tx, mut rx) = mpsc::channel(8);
+        let relay = Arc::new(RelayConnection::new(&url).unwrap().with_incoming_channel(tx));
+        let readiness = relay.readiness();
+        let connecting = relay.clone();
+        let pending = tokio::spawn(async move { connecting.connect("pk").await });
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!pending.is_finished(), "connect returned before the ack");
+        assert!(rx.try_recv().is_err(), "envelope delivered before the ack");
+        assert!(!relay.is_ready());
+        assert_eq!(relay.get_state().await, ConnectionState::Connecting);
+
+        ack_tx.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(2), pending).await.unwrap().unwrap().unwrap();
+        assert_eq!(*readiness.borrow(), Some("pk".to_string()));
+        assert_eq!(relay.get_state().await, ConnectionState::Connected);
+
+        // The held-back envelope follows, in the order it arrived
+        let first = tokio::time::timeout(Duration::from_secs(2), rx.recv()).await.unwrap();
+        assert!(matches!(first, Some(IncomingMessage::Envelope(e)) if e.from_public_key == sender.public_key_hex()));
+        assert!(matches!(rx.recv().await, Some(IncomingMessage::Welcome { .. })));
+
+        relay.disconnect().await.unwrap();
+        assert!(!relay.is_ready());
+        drop(server);
+    }
 }
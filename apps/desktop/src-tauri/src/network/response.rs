@@ -0,0 +1,209 @@
+//! API Response Parsing
+//!
+//! Reads GNS API responses without trusting them to be well formed. A
+//! proxy's HTML error page or a body cut off mid-way comes back as
+//! [`NetworkError::UnexpectedResponse`] carrying the status and the start
+//! of the body, instead of an opaque serde message.
+
+use super::{ApiClient, NetworkError};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+/// Longest part of an unexpected body kept for diagnosis, in characters
+const SNIPPET_LEN: usize = 200;
+
+/// The `{ success, data, error }` envelope most endpoints answer with
+#[derive(Debug)]
+pub struct ApiEnvelope<T> {
+    pub success: bool,
+    pub data: Option<T>,
+    pub error: Option<String>,
+}
+
+/// The envelope as sent. Every field may be missing; `error` may be a
+/// string or an object.
+#[derive(Deserialize)]
+struct RawEnvelope {
+    success: Option<bool>,
+    data: Option<serde_json::Value>,
+    error: Option<serde_json::Value>,
+}
+
+impl ApiClient {
+    /// Read a JSON body, refusing anything else
+    pub async fn read_json(response: reqwest::Response) -> Result<serde_json::Value, NetworkError> {
+        let (status, content_type, body) = read_body(response).await?;
+        parse_json(status, content_type.as_deref(), &body)
+    }
+
+    /// Read the `{ success, data, error }` envelope without judging it.
+    /// A missing `success` counts as true for a 2xx without an `error`.
+    pub async fn read_envelope<T: DeserializeOwned>(
+        response: reqwest::Response,
+    ) -> Result<ApiEnvelope<T>, NetworkError> {
+        let (status, content_type, body) = read_body(response).await?;
+        parse_envelope(status, content_type.as_deref(), &body)
+    }
+
+    /// Read the envelope's `data`. An error status or `success: false`
+    /// becomes `ApiError` with the server's message.
+    pub async fn read_data<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, NetworkError> {
+        let (status, content_type, body) = read_body(response).await?;
+        parse_data(status, content_type.as_deref(), &body)
+    }
+
+    /// [`ApiClient::read_envelope`] for a body already read, e.g. to check
+    /// a signature over it first
+    pub fn envelope_from_body<T: DeserializeOwned>(
+        status: StatusCode,
+        content_type: Option<&str>,
+        body: &str,
+    ) -> Result<ApiEnvelope<T>, NetworkError> {
+        parse_envelope(status, content_type, body)
+    }
+
+    /// [`ApiClient::read_data`] for a body already read
+    pub fn data_from_body<T: DeserializeOwned>(
+        status: StatusCode,
+        content_type: Option<&str>,
+        body: &str,
+    ) -> Result<T, NetworkError> {
+        parse_data(status, content_type, body)
+    }
+}
+
+async fn read_body(response: reqwest::Response) -> Result<(StatusCode, Option<String>, String), NetworkError> {
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let body = response.text().await.map_err(|e| NetworkError::RequestError(e.to_string()))?;
+    Ok((status, content_type, body))
+}
+
+fn unexpected(status: StatusCode, detail: impl Into<String>, body: &str) -> NetworkError {
+    let snippet: String = body.split_whitespace().collect::<Vec<_>>().join(" ").chars().take(SNIPPET_LEN).collect();
+    NetworkError::UnexpectedResponse {
+        status: status.as_u16(),
+        detail: detail.into(),
+        snippet,
+    }
+}
+
+fn parse_json(status: StatusCode, content_type: Option<&str>, body: &str) -> Result<serde_json::Value, NetworkError> {
+    serde_json::from_str(body).map_err(|e| match content_type {
+        Some(ct) if !ct.contains("json") => unexpected(status, format!("expected JSON, got {}", ct), body),
+        _ => unexpected(status, format!("invalid JSON: {}", e), body),
+    })
+}
+
+fn parse_envelope<T: DeserializeOwned>(
+    status: StatusCode,
+    content_type: Option<&str>,
+    body: &str,
+) -> Result<ApiEnvelope<T>, NetworkError> {
+    let value = parse_json(status, content_type, body)?;
+    let raw: RawEnvelope = serde_json::from_value(value)
+        .map_err(|e| unexpected(status, format!("not an API envelope: {}", e), body))?;
+
+    let error = raw.error.filter(|e| !e.is_null()).map(|e| match e {
+        serde_json::Value::String(s) => s,
+        other => other.to_string(),
+    });
+    let data = match raw.data {
+        None | Some(serde_json::Value::Null) => None,
+        Some(data) => Some(
+            serde_json::from_value(data)
+                .map_err(|e| unexpected(status, format!("unexpected data: {}", e), body))?,
+        ),
+    };
+
+    Ok(ApiEnvelope {
+        success: raw.success.unwrap_or(status.is_success() && error.is_none()),
+        data,
+        error,
+    })
+}
+
+fn parse_data<T: DeserializeOwned>(status: StatusCode, content_type: Option<&str>, body: &str) -> Result<T, NetworkError> {
+    let envelope = parse_envelope::<T>(status, content_type, body)?;
+    if !status.is_success() || !envelope.success {
+        return Err(NetworkError::ApiError(
+            envelope.error.unwrap_or_else(|| format!("API returned status: {}", status)),
+        ));
+    }
+    envelope.data.ok_or_else(|| unexpected(status, "no data returned", body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Post {
+        id: String,
+        #[serde(default)]
+        likes: u32,
+    }
+
+    #[test]
+    fn test_html_error_page() {
+        let page = "<html>\n  <head><title>502 Bad Gateway</title></head>\n  <body>nginx</body>\n</html>";
+        let err = parse_data::<Post>(StatusCode::BAD_GATEWAY, Some("text/html"), page).unwrap_err();
+
+        match err {
+            NetworkError::UnexpectedResponse { status, detail, snippet } => {
+                assert_eq!(status, 502);
+                assert_eq!(detail, "expected JSON, got text/html");
+                assert!(snippet.starts_with("<html> <head><title>502 Bad Gateway"));
+            }
+            other => panic!("expected UnexpectedResponse, got {:?}", other),
+        }
+
+        let long = format!("<html>{}</html>", "x".repeat(1000));
+        match parse_json(StatusCode::OK, Some("text/html"), &long).unwrap_err() {
+            NetworkError::UnexpectedResponse { snippet, .. } => assert_eq!(snippet.chars().count(), SNIPPET_LEN),
+            other => panic!("expected UnexpectedResponse, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_partial_json_body() {
+        let truncated = r#"{"success":true,"data":{"id":"p1","lik"#;
+        let err = parse_data::<Post>(StatusCode::OK, Some("application/json"), truncated).unwrap_err();
+        assert!(matches!(
+            err,
+            NetworkError::UnexpectedResponse { status: 200, ref detail, ref snippet }
+                if detail.starts_with("invalid JSON") && snippet == truncated
+        ));
+
+        // Missing and unknown fields are fine where the type allows them
+        let drifted = r#"{"data":{"id":"p1","reactions":{"fire":2}},"requestId":"abc"}"#;
+        let post: Post = parse_data(StatusCode::OK, Some("application/json"), drifted).unwrap();
+        assert_eq!((post.id.as_str(), post.likes), ("p1", 0));
+
+        // ...but not where it doesn't
+        let wrong = r#"{"success":true,"data":{"likes":3}}"#;
+        let err = parse_data::<Post>(StatusCode::OK, None, wrong).unwrap_err();
+        assert!(matches!(err, NetworkError::UnexpectedResponse { ref detail, .. } if detail.contains("missing field `id`")));
+    }
+
+    #[test]
+    fn test_envelope_errors() {
+        let failed = r#"{"success":false,"error":"Post not found"}"#;
+        let err = parse_data::<Post>(StatusCode::OK, Some("application/json"), failed).unwrap_err();
+        assert!(matches!(err, NetworkError::ApiError(ref e) if e == "Post not found"));
+
+        let structured = r#"{"error":{"code":"rate_limited"}}"#;
+        let envelope = parse_envelope::<Post>(StatusCode::TOO_MANY_REQUESTS, None, structured).unwrap();
+        assert!(!envelope.success);
+        assert_eq!(envelope.error.as_deref(), Some(r#"{"code":"rate_limited"}"#));
+
+        let empty = r#"{"success":true}"#;
+        let err = parse_data::<Post>(StatusCode::OK, None, empty).unwrap_err();
+        assert!(matches!(err, NetworkError::UnexpectedResponse { ref detail, .. } if detail == "no data returned"));
+    }
+}
@@ -0,0 +1,215 @@
+//! Notification Preferences
+//!
+//! Decides whether an event worth a notification also gets an OS alert.
+//! Each identity can switch categories off and set quiet hours. Neither
+//! stops the event being recorded or counted in the app's badges; they
+//! only hold back the banner and its sound.
+
+use crate::settings::load_notifications_enabled;
+use crate::storage::{Database, DatabaseError};
+use chrono::{NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+
+pub const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// What a notification is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationCategory {
+    DirectMessages,
+    Mentions,
+    Reactions,
+    PaymentsReceived,
+}
+
+impl NotificationCategory {
+    /// The `kind` its events are recorded under in the notification list
+    pub fn kind(self) -> &'static str {
+        match self {
+            Self::DirectMessages => "direct_message",
+            Self::Mentions => "mention",
+            Self::Reactions => "reaction",
+            Self::PaymentsReceived => "payment_received",
+        }
+    }
+}
+
+/// A daily window, in minutes after local midnight. One that ends before
+/// it starts runs past midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start_minute: u16,
+    pub end_minute: u16,
+}
+
+impl QuietHours {
+    pub fn contains(self, minute: u16) -> bool {
+        if self.start_minute <= self.end_minute {
+            (self.start_minute..self.end_minute).contains(&minute)
+        } else {
+            minute >= self.start_minute || minute < self.end_minute
+        }
+    }
+}
+
+/// An identity's notification settings. Everything alerts by default.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationPrefs {
+    pub direct_messages: bool,
+    pub mentions: bool,
+    pub reactions: bool,
+    pub payments_received: bool,
+    pub quiet_hours: Vec<QuietHours>,
+}
+
+impl Default for NotificationPrefs {
+    fn default() -> Self {
+        Self {
+            direct_messages: true,
+            mentions: true,
+            reactions: true,
+            payments_received: true,
+            quiet_hours: Vec::new(),
+        }
+    }
+}
+
+/// How an event reaches the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Delivery {
+    /// OS notification with sound
+    Alert,
+    /// In quiet hours: only the in-app badge
+    Quiet,
+    /// The category (or all notifications) is off: only the in-app badge
+    Muted,
+}
+
+impl NotificationPrefs {
+    pub fn allows(&self, category: NotificationCategory) -> bool {
+        match category {
+            NotificationCategory::DirectMessages => self.direct_messages,
+            NotificationCategory::Mentions => self.mentions,
+            NotificationCategory::Reactions => self.reactions,
+            NotificationCategory::PaymentsReceived => self.payments_received,
+        }
+    }
+
+    /// How an event of `category` is delivered at local time `now`
+    pub fn delivery(&self, category: NotificationCategory, now: NaiveTime) -> Delivery {
+        let minute = (now.hour() * 60 + now.minute()) as u16;
+        if !self.allows(category) {
+            Delivery::Muted
+        } else if self.quiet_hours.iter().any(|window| window.contains(minute)) {
+            Delivery::Quiet
+        } else {
+            Delivery::Alert
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for window in &self.quiet_hours {
+            if window.start_minute >= MINUTES_PER_DAY || window.end_minute >= MINUTES_PER_DAY {
+                return Err(format!("Quiet hours must be within 0..{} minutes", MINUTES_PER_DAY));
+            }
+            if window.start_minute == window.end_minute {
+                return Err("Quiet hours must not start and end at the same time".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How to deliver an event of `category` to `owner_pk` at local time
+/// `now`. Turning notifications off altogether mutes every category.
+pub fn delivery_for(db: &Database, owner_pk: &str, category: NotificationCategory, now: NaiveTime) -> Delivery {
+    if !load_notifications_enabled(db) {
+        return Delivery::Muted;
+    }
+    let prefs = db.load_notification_prefs(owner_pk).unwrap_or_else(|e| {
+        tracing::warn!("⚠️ Failed to load notification preferences: {}", e);
+        NotificationPrefs::default()
+    });
+    prefs.delivery(category, now)
+}
+
+/// An event for the notification list
+pub struct NotificationEvent<'a> {
+    pub category: NotificationCategory,
+    /// What it's about, e.g. the post someone was mentioned in
+    pub item_id: &'a str,
+    pub actor_public_key: &'a str,
+    pub actor_handle: Option<&'a str>,
+    pub preview: &'a str,
+}
+
+/// Record `event` for `owner_pk` and decide how to deliver it. `None` if
+/// it was recorded before, so nothing is delivered twice.
+pub fn record(
+    db: &mut Database,
+    owner_pk: &str,
+    event: &NotificationEvent,
+    now: NaiveTime,
+) -> Result<Option<Delivery>, DatabaseError> {
+    let is_new = db.add_notification(
+        owner_pk,
+        event.category.kind(),
+        event.item_id,
+        event.actor_public_key,
+        event.actor_handle,
+        event.preview,
+    )?;
+    Ok(is_new.then(|| delivery_for(db, owner_pk, event.category, now)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::save_notifications_enabled;
+
+    #[test]
+    fn test_muted_category_is_recorded_without_alert() {
+        let mut db = Database::open_in_memory().unwrap();
+        let owner = "ab".repeat(32);
+        let at = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let prefs = NotificationPrefs {
+            mentions: false,
+            quiet_hours: vec![QuietHours { start_minute: 22 * 60, end_minute: 7 * 60 }],
+            ..Default::default()
+        };
+        db.save_notification_prefs(&owner, &prefs).unwrap();
+
+        let mention = NotificationEvent {
+            category: NotificationCategory::Mentions,
+            item_id: "post-1",
+            actor_public_key: &"cd".repeat(32),
+            actor_handle: Some("carol"),
+            preview: "hey @alice",
+        };
+        assert_eq!(record(&mut db, &owner, &mention, at(12, 0)).unwrap(), Some(Delivery::Muted));
+        let recorded = db.get_notifications(&owner, true, 10).unwrap();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!((recorded[0].kind.as_str(), recorded[0].post_id.as_str()), ("mention", "post-1"));
+        // Seen already: neither recorded nor delivered again
+        assert_eq!(record(&mut db, &owner, &mention, at(12, 0)).unwrap(), None);
+
+        let dm = NotificationCategory::DirectMessages;
+        assert_eq!(delivery_for(&db, &owner, dm, at(12, 0)), Delivery::Alert);
+        assert_eq!(delivery_for(&db, &owner, dm, at(23, 30)), Delivery::Quiet);
+        assert_eq!(delivery_for(&db, &owner, dm, at(6, 59)), Delivery::Quiet);
+        assert_eq!(delivery_for(&db, &owner, dm, at(7, 0)), Delivery::Alert);
+        // Another identity on the same device keeps the defaults
+        assert_eq!(delivery_for(&db, &"ef".repeat(32), NotificationCategory::Mentions, at(23, 30)), Delivery::Alert);
+
+        save_notifications_enabled(&mut db, false).unwrap();
+        assert_eq!(delivery_for(&db, &owner, dm, at(12, 0)), Delivery::Muted);
+
+        let backwards = NotificationPrefs { quiet_hours: vec![QuietHours { start_minute: 60, end_minute: 60 }], ..Default::default() };
+        assert!(backwards.validate().is_err());
+        let past_midnight = NotificationPrefs { quiet_hours: vec![QuietHours { start_minute: 0, end_minute: 1440 }], ..Default::default() };
+        assert!(past_midnight.validate().is_err());
+        assert!(prefs.validate().is_ok());
+    }
+}
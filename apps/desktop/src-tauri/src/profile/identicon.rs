@@ -0,0 +1,106 @@
+//! Identicons
+//!
+//! The avatar an identity gets until it sets one: a mirrored 5x5 grid in a
+//! single colour, both picked from a hash of the public key. Rendered as
+//! SVG, so it needs no image library, network or storage and looks the
+//! same wherever the key is shown.
+
+use base64::Engine;
+use sha2::{Digest, Sha256};
+
+pub const DEFAULT_IDENTICON_SIZE: u32 = 64;
+pub const MIN_IDENTICON_SIZE: u32 = 16;
+pub const MAX_IDENTICON_SIZE: u32 = 1024;
+
+/// Keeps identicons from changing if the key is ever hashed for
+/// something else
+const IDENTICON_DOMAIN: &[u8] = b"gns-identicon-v1:";
+
+const GRID: usize = 5;
+/// Columns up to and including the middle one
+const HALF: usize = (GRID + 1) / 2;
+
+/// The identicon for `public_key` (hex), `size` pixels square, as an
+/// `image/svg+xml` data URI
+pub fn identicon_data_uri(public_key: &str, size: u32) -> Result<String, String> {
+    let svg = identicon_svg(public_key, size)?;
+    Ok(format!(
+        "data:image/svg+xml;base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(svg)
+    ))
+}
+
+/// The identicon for `public_key` (hex) as SVG markup
+pub fn identicon_svg(public_key: &str, size: u32) -> Result<String, String> {
+    let key = hex::decode(public_key.trim()).map_err(|_| format!("Invalid public key: {}", public_key))?;
+    if key.len() != 32 {
+        return Err(format!("Invalid public key: expected 32 bytes, got {}", key.len()));
+    }
+    if !(MIN_IDENTICON_SIZE..=MAX_IDENTICON_SIZE).contains(&size) {
+        return Err(format!(
+            "Identicon size must be between {} and {} pixels",
+            MIN_IDENTICON_SIZE, MAX_IDENTICON_SIZE
+        ));
+    }
+
+    let hash = Sha256::new().chain_update(IDENTICON_DOMAIN).chain_update(&key).finalize();
+    let hue = u16::from_be_bytes([hash[0], hash[1]]) % 360;
+    let saturation = 45 + hash[2] % 30;
+    let lightness = 40 + hash[3] % 20;
+
+    // A cell on the left half decides its mirror on the right
+    let mut cells = String::new();
+    for row in 0..GRID {
+        for col in 0..HALF {
+            let bit = row * HALF + col;
+            if hash[4 + bit / 8] & (1 << (bit % 8)) == 0 {
+                continue;
+            }
+            for x in [col, GRID - 1 - col] {
+                cells.push_str(&format!(r#"<rect x="{}" y="{}" width="1" height="1"/>"#, x + 1, row + 1));
+                if x == GRID - 1 - x {
+                    break;
+                }
+            }
+        }
+    }
+
+    let extent = GRID + 2;
+    Ok(format!(
+        concat!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{size}" height="{size}" viewBox="0 0 {extent} {extent}" shape-rendering="crispEdges">"#,
+            r#"<rect width="{extent}" height="{extent}" fill="hsl({hue},{saturation}%,94%)"/>"#,
+            r#"<g fill="hsl({hue},{saturation}%,{lightness}%)">{cells}</g></svg>"#
+        ),
+        size = size,
+        extent = extent,
+        hue = hue,
+        saturation = saturation,
+        lightness = lightness,
+        cells = cells,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identicon_is_stable_per_key() {
+        let alice = "ab".repeat(32);
+        let bob = "ab".repeat(31) + "ac";
+
+        let first = identicon_data_uri(&alice, 64).unwrap();
+        assert!(first.starts_with("data:image/svg+xml;base64,"));
+        // Case doesn't make it another key
+        assert_eq!(identicon_data_uri(&alice.to_uppercase(), 64).unwrap(), first);
+        assert_eq!(identicon_svg(&alice, 64).unwrap().as_bytes(), identicon_svg(&alice, 64).unwrap().as_bytes());
+
+        assert_ne!(identicon_svg(&alice, 64).unwrap(), identicon_svg(&bob, 64).unwrap());
+        assert_ne!(identicon_svg(&alice, 64).unwrap(), identicon_svg(&alice, 128).unwrap());
+
+        assert!(identicon_svg("not a key", 64).is_err());
+        assert!(identicon_svg(&"ab".repeat(16), 64).is_err());
+        assert!(identicon_svg(&alice, 4096).is_err());
+    }
+}
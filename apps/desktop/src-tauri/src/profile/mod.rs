@@ -0,0 +1,288 @@
+//! Profile Module - Signed Profile Records
+//!
+//! A profile (display name, bio, avatar and links) is published inside the
+//! signed identity record, so a resolver can check the display fields were
+//! written by the key's owner before showing them. Avatars are referenced
+//! by the SHA-256 of their bytes; the bytes live in the local blob store.
+
+pub mod identicon;
+
+use crate::commands::handles::canonical_json;
+use crate::crypto::SignatureDomain;
+use crate::storage::{Database, Profile};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+pub const MAX_DISPLAY_NAME_CHARS: usize = 64;
+pub const MAX_BIO_CHARS: usize = 500;
+pub const MAX_LINKS: usize = 10;
+pub const MAX_LINK_URL_CHARS: usize = 512;
+pub const MAX_LINK_LABEL_CHARS: usize = 32;
+pub const MAX_AVATAR_BYTES: usize = 2 * 1024 * 1024;
+
+const BLOB_REF_PREFIX: &str = "sha256:";
+
+/// A link shown on a profile
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProfileLink {
+    pub type_: String,
+    pub url: String,
+    pub icon: String,
+}
+
+/// The `profile` section of a signed identity record
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProfileRecord {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bio: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar_blob_ref: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<ProfileLink>,
+}
+
+impl ProfileRecord {
+    /// Strip control characters, drop empty fields and enforce length limits
+    pub fn sanitized(self) -> Result<Self, String> {
+        let display_name = clean_text(self.display_name, "Display name", MAX_DISPLAY_NAME_CHARS, false)?;
+        let bio = clean_text(self.bio, "Bio", MAX_BIO_CHARS, true)?;
+
+        let avatar_blob_ref = match self.avatar_blob_ref.map(|r| r.trim().to_lowercase()) {
+            Some(r) if r.is_empty() => None,
+            Some(r) if is_blob_ref(&r) => Some(r),
+            Some(r) => return Err(format!("Invalid avatar reference: {}", r)),
+            None => None,
+        };
+
+        if self.links.len() > MAX_LINKS {
+            return Err(format!("At most {} links are allowed", MAX_LINKS));
+        }
+
+        let links = self
+            .links
+            .into_iter()
+            .map(ProfileLink::sanitized)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { display_name, bio, avatar_blob_ref, links })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.display_name.is_none() && self.bio.is_none() && self.avatar_blob_ref.is_none() && self.links.is_empty()
+    }
+
+    /// Build the record section from the locally stored profile
+    pub fn from_stored(profile: &Profile) -> Self {
+        Self {
+            display_name: profile.display_name.clone(),
+            bio: profile.bio.clone(),
+            avatar_blob_ref: profile.avatar_blob_ref.clone(),
+            links: profile
+                .links
+                .as_deref()
+                .and_then(|json| serde_json::from_str(json).ok())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Extract the profile from a signed identity record.
+    ///
+    /// The signature over the canonical record must verify against
+    /// `public_key` before any field is trusted. Records without a
+    /// profile section yield `None`.
+    pub fn from_signed_record(
+        public_key: &str,
+        record_json: &serde_json::Value,
+        signature: &str,
+    ) -> Result<Option<Self>, String> {
+        verify_record(public_key, record_json, signature)?;
+
+        match record_json.get("profile") {
+            None | Some(serde_json::Value::Null) => Ok(None),
+            Some(value) => {
+                let profile: ProfileRecord = serde_json::from_value(value.clone())
+                    .map_err(|e| format!("Malformed profile: {}", e))?;
+                // Signed but still remote input
+                profile.sanitized().map(Some)
+            }
+        }
+    }
+}
+
+impl ProfileLink {
+    fn sanitized(self) -> Result<Self, String> {
+        let url = strip_controls(&self.url, false).trim().to_string();
+        if url.chars().count() > MAX_LINK_URL_CHARS {
+            return Err(format!("Link URL exceeds {} characters", MAX_LINK_URL_CHARS));
+        }
+
+        let parsed = Url::parse(&url).map_err(|e| format!("Invalid link URL: {}", e))?;
+        if parsed.scheme() != "https" && parsed.scheme() != "http" {
+            return Err(format!("Unsupported link scheme '{}'", parsed.scheme()));
+        }
+
+        Ok(Self {
+            type_: clean_label(&self.type_, "Link type")?,
+            url,
+            icon: clean_label(&self.icon, "Link icon")?,
+        })
+    }
+}
+
+/// Check a signed identity record was signed by `public_key` and is about
+/// that key
+pub fn verify_record(public_key: &str, record_json: &serde_json::Value, signature: &str) -> Result<(), String> {
+    let identity = record_json["identity"].as_str().unwrap_or_default();
+    if !identity.eq_ignore_ascii_case(public_key) {
+        return Err("Record belongs to a different identity".to_string());
+    }
+
+    let data = canonical_json(record_json);
+    let valid = gns_crypto_core::verify_in_domain_hex(
+        public_key,
+        SignatureDomain::Record,
+        data.as_bytes(),
+        signature,
+        gns_crypto_core::TRANSITION_POLICY,
+    )
+    .map_err(|e| format!("Invalid record signature: {}", e))?;
+    if !valid {
+        return Err("Record signature does not match".to_string());
+    }
+    Ok(())
+}
+
+/// The profile section for our own record, if one has been set
+pub fn record_value(db: &Database, public_key: &str) -> Option<serde_json::Value> {
+    let stored = db.get_profile(public_key).ok().flatten()?;
+    let record = ProfileRecord::from_stored(&stored);
+    if record.is_empty() {
+        return None;
+    }
+    serde_json::to_value(record).ok()
+}
+
+/// Content reference for an avatar: `sha256:<hex>` of the plaintext bytes
+pub fn avatar_blob_ref(bytes: &[u8]) -> String {
+    format!("{}{}", BLOB_REF_PREFIX, hex::encode(Sha256::digest(bytes)))
+}
+
+fn is_blob_ref(value: &str) -> bool {
+    value
+        .strip_prefix(BLOB_REF_PREFIX)
+        .is_some_and(|h| h.len() == 64 && h.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn strip_controls(value: &str, keep_newlines: bool) -> String {
+    value
+        .chars()
+        .filter(|c| !c.is_control() || (keep_newlines && *c == '\n'))
+        .collect()
+}
+
+fn clean_text(
+    value: Option<String>,
+    field: &str,
+    max_chars: usize,
+    keep_newlines: bool,
+) -> Result<Option<String>, String> {
+    let Some(value) = value else { return Ok(None) };
+
+    let cleaned = strip_controls(&value, keep_newlines).trim().to_string();
+    if cleaned.is_empty() {
+        return Ok(None);
+    }
+    if cleaned.chars().count() > max_chars {
+        return Err(format!("{} exceeds {} characters", field, max_chars));
+    }
+
+    Ok(Some(cleaned))
+}
+
+fn clean_label(value: &str, field: &str) -> Result<String, String> {
+    Ok(clean_text(Some(value.to_string()), field, MAX_LINK_LABEL_CHARS, false)?.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gns_crypto_core::GnsIdentity;
+
+    #[test]
+    fn test_sanitize_profile() {
+        let profile = ProfileRecord {
+            display_name: Some("  Ada\u{0007} Lovelace ".to_string()),
+            bio: Some("line one\nline two\u{001b}[31m".to_string()),
+            avatar_blob_ref: Some(format!(" {} ", avatar_blob_ref(b"png bytes"))),
+            links: vec![ProfileLink {
+                type_: "web".to_string(),
+                url: "https://example.com".to_string(),
+                icon: "globe".to_string(),
+            }],
+        }
+        .sanitized()
+        .unwrap();
+
+        assert_eq!(profile.display_name.as_deref(), Some("Ada Lovelace"));
+        assert_eq!(profile.bio.as_deref(), Some("line one\nline two[31m"));
+        assert_eq!(profile.avatar_blob_ref, Some(avatar_blob_ref(b"png bytes")));
+
+        let too_long = ProfileRecord {
+            display_name: Some("x".repeat(MAX_DISPLAY_NAME_CHARS + 1)),
+            ..Default::default()
+        };
+        assert!(too_long.sanitized().is_err());
+
+        let bad_link = ProfileRecord {
+            links: vec![ProfileLink {
+                type_: "x".to_string(),
+                url: "javascript:alert(1)".to_string(),
+                icon: String::new(),
+            }],
+            ..Default::default()
+        };
+        assert!(bad_link.sanitized().is_err());
+    }
+
+    #[test]
+    fn test_profile_signature_is_checked() {
+        let identity = GnsIdentity::generate();
+        let public_key = identity.public_key_hex();
+
+        let mut record = serde_json::json!({
+            "identity": public_key,
+            "version": 1,
+            "profile": { "display_name": "Ada" },
+        });
+        let signature = hex::encode(identity.sign_bytes(canonical_json(&record).as_bytes()));
+
+        let profile = ProfileRecord::from_signed_record(&public_key, &record, &signature)
+            .unwrap()
+            .unwrap();
+        assert_eq!(profile.display_name.as_deref(), Some("Ada"));
+
+        record["profile"]["display_name"] = serde_json::json!("Mallory");
+        assert!(ProfileRecord::from_signed_record(&public_key, &record, &signature).is_err());
+    }
+
+    #[test]
+    fn test_record_signed_in_other_domain_is_rejected() {
+        let identity = GnsIdentity::generate();
+        let public_key = identity.public_key_hex();
+        let record = serde_json::json!({
+            "identity": public_key,
+            "version": 1,
+            "profile": { "display_name": "Ada" },
+        });
+        let data = canonical_json(&record);
+
+        let as_record = hex::encode(identity.sign_in_domain(SignatureDomain::Record, data.as_bytes()));
+        assert!(ProfileRecord::from_signed_record(&public_key, &record, &as_record).is_ok());
+
+        let as_claim = hex::encode(identity.sign_in_domain(SignatureDomain::Claim, data.as_bytes()));
+        assert!(ProfileRecord::from_signed_record(&public_key, &record, &as_claim).is_err());
+    }
+}
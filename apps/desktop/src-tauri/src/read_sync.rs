@@ -0,0 +1,237 @@
+//! Read-State Sync
+//!
+//! Keeps unread counts the same on every device of one identity. Marking
+//! a thread read stores a [`ReadMarker`]; a background task sends the
+//! markers not yet sent to our own public key, so the relay hands them to
+//! each of our other devices. There the message handler applies them
+//! instead of storing a message. The newest marker per thread wins, so
+//! markers can arrive late, twice, or out of order.
+//!
+//! Like the send-later scheduler, markers that can't go out (the relay is
+//! offline) stay queued and are retried when connectivity returns.
+
+use crate::message_handler::IncomingMessageEvent;
+use crate::storage::{Database, ReadMarker};
+use crate::AppState;
+use gns_crypto_core::{create_envelope_with_cache, Compression, CryptoError, GnsEnvelope, GnsIdentity, MessageKeyCache};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, Mutex, Notify};
+
+/// Payload type of a read-state sync envelope
+pub const READ_STATE_PAYLOAD_TYPE: &str = "gns/read_state";
+
+/// Tauri event carrying the [`ReadMarker`]s another device set
+pub const READ_STATE_SYNCED_EVENT: &str = "read_state_synced";
+
+/// Wait before retrying markers that failed to send while still online
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Payload of a read-state sync envelope
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadStateSync {
+    pub markers: Vec<ReadMarker>,
+}
+
+/// Lets commands tell the sync task there are new markers
+#[derive(Default)]
+pub struct ReadSync {
+    wake: Notify,
+}
+
+impl ReadSync {
+    /// Send unsynced markers now
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+}
+
+/// Run the sync task until the app exits. `send` delivers one batch of
+/// markers to our other devices.
+pub async fn run<S, Fut>(
+    database: Arc<Mutex<Database>>,
+    read_sync: Arc<ReadSync>,
+    mut online: watch::Receiver<bool>,
+    mut send: S,
+) where
+    S: FnMut(Vec<ReadMarker>) -> Fut,
+    Fut: Future<Output = Result<(), String>>,
+{
+    loop {
+        let blocked = send_unsynced(&database, &mut send).await;
+
+        let retry = async {
+            if blocked {
+                tokio::time::sleep(RETRY_DELAY).await
+            } else {
+                std::future::pending().await
+            }
+        };
+        tokio::select! {
+            _ = retry => {}
+            _ = read_sync.wake.notified() => {}
+            changed = online.changed() => {
+                if changed.is_err() {
+                    // Connectivity is gone for good; fall back to the timer alone
+                    online = watch::channel(true).1;
+                }
+            }
+        }
+    }
+}
+
+/// Send every unsynced marker as one batch. Returns true if that failed.
+async fn send_unsynced<S, Fut>(database: &Mutex<Database>, send: &mut S) -> bool
+where
+    S: FnMut(Vec<ReadMarker>) -> Fut,
+    Fut: Future<Output = Result<(), String>>,
+{
+    let markers = match database.lock().await.unsynced_read_markers() {
+        Ok(markers) if markers.is_empty() => return false,
+        Ok(markers) => markers,
+        Err(e) => {
+            tracing::warn!("⚠️ Could not read unsynced read markers: {}", e);
+            return true;
+        }
+    };
+
+    let count = markers.len();
+    match send(markers.clone()).await {
+        Ok(()) => {
+            if let Err(e) = database.lock().await.mark_read_markers_synced(&markers) {
+                // Harmless: they go out again and are ignored as not newer
+                tracing::warn!("⚠️ Sent read markers but could not record it: {}", e);
+            }
+            tracing::debug!("Synced {} read marker(s) to our other devices", count);
+            false
+        }
+        Err(e) => {
+            tracing::debug!("Read markers not synced, will retry: {}", e);
+            true
+        }
+    }
+}
+
+/// Send `markers` to our other devices through the relay
+pub async fn send(state: &AppState, markers: Vec<ReadMarker>) -> Result<(), String> {
+    let envelope = {
+        let identity_mgr = state.identity.lock().await;
+        let identity = identity_mgr.get_identity().ok_or("No identity configured")?;
+        let compression = identity_mgr.compression_for(&identity.public_key_hex());
+        create_sync_envelope(identity, &identity_mgr.message_keys(), compression, markers)
+            .map_err(|e| format!("Failed to create envelope: {}", e))?
+    };
+
+    let relay = state.relay.lock().await;
+    relay
+        .send_envelope(&envelope)
+        .await
+        .map_err(|e| format!("Failed to send: {}", e))
+}
+
+/// Seal `markers` in an envelope addressed to `identity` itself
+pub fn create_sync_envelope(
+    identity: &GnsIdentity,
+    message_keys: &MessageKeyCache,
+    compression: Compression,
+    markers: Vec<ReadMarker>,
+) -> Result<GnsEnvelope, CryptoError> {
+    let payload = serde_json::to_vec(&ReadStateSync { markers }).expect("markers serialize");
+    create_envelope_with_cache(
+        identity,
+        None,
+        &identity.public_key_hex(),
+        &identity.encryption_key_hex(),
+        READ_STATE_PAYLOAD_TYPE,
+        &payload,
+        None,
+        None,
+        compression,
+        message_keys,
+    )
+}
+
+/// Apply a decrypted read-state sync envelope, returning the markers that
+/// were newer than ours. Only our own, validly signed envelopes count: a
+/// peer has no say over what we have read.
+pub fn apply_sync(db: &mut Database, my_pk: &str, event: &IncomingMessageEvent) -> Result<Vec<ReadMarker>, String> {
+    if !event.from_public_key.eq_ignore_ascii_case(my_pk) || !event.signature_valid {
+        return Err(format!(
+            "Ignoring read state from {}: not signed by our identity",
+            event.from_public_key.get(..16).unwrap_or(&event.from_public_key)
+        ));
+    }
+
+    let sync: ReadStateSync =
+        serde_json::from_value(event.payload.clone()).map_err(|e| format!("Malformed read state: {}", e))?;
+    let mut applied = Vec::new();
+    for marker in sync.markers {
+        if db.apply_read_marker(&marker).map_err(|e| e.to_string())? {
+            applied.push(marker);
+        }
+    }
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_handler::decrypt_envelope;
+    use gns_crypto_core::compute_thread_id;
+
+    #[test]
+    fn test_self_sync_marks_incoming_messages_read() {
+        let me = GnsIdentity::generate();
+        let peer = GnsIdentity::generate();
+        let (my_pk, peer_pk) = (me.public_key_hex(), peer.public_key_hex());
+        let thread_id = compute_thread_id(&my_pk, &peer_pk);
+
+        let mut db = Database::open_in_memory().unwrap();
+        db.save_synced_incoming_message("in-1", &peer_pk, "hi", 1_000, None, &my_pk).unwrap();
+        db.save_browser_sent_message("out-1", &peer_pk, "hello", 1_500, &my_pk).unwrap();
+        db.save_synced_incoming_message("in-2", &peer_pk, "you there?", 3_000, None, &my_pk).unwrap();
+        assert_eq!(db.get_thread(&thread_id).unwrap().unwrap().unread_count, 2);
+
+        // Another device read the thread up to 2s
+        let marker = ReadMarker { thread_id: thread_id.clone(), read_up_to: 2_000, updated_at: 10_000 };
+        let cache = MessageKeyCache::default();
+        let envelope = create_sync_envelope(&me, &cache, Compression::Off, vec![marker.clone()]).unwrap();
+        let event = decrypt_envelope(&me, &cache, None, &envelope).unwrap();
+        assert_eq!(event.payload_type, READ_STATE_PAYLOAD_TYPE);
+
+        assert_eq!(apply_sync(&mut db, &my_pk, &event).unwrap(), vec![marker]);
+        assert_eq!(db.get_thread(&thread_id).unwrap().unwrap().unread_count, 1);
+        let message = |id: &str| db.get_message(id).unwrap().unwrap();
+        assert!(message("in-1").is_read);
+        assert!(!message("in-2").is_read);
+        // Our own message is left alone; its status comes from the peer's
+        // read receipts
+        let sent = message("out-1");
+        assert!(!sent.is_read);
+        assert_eq!(sent.status, "sent");
+
+        // Delivered again: nothing new
+        assert!(apply_sync(&mut db, &my_pk, &event).unwrap().is_empty());
+
+        // A peer can't mark our messages read
+        let forged = ReadMarker { thread_id: thread_id.clone(), read_up_to: 5_000, updated_at: 20_000 };
+        let envelope = create_envelope_with_cache(
+            &peer,
+            None,
+            &my_pk,
+            &me.encryption_key_hex(),
+            READ_STATE_PAYLOAD_TYPE,
+            &serde_json::to_vec(&ReadStateSync { markers: vec![forged] }).unwrap(),
+            None,
+            None,
+            Compression::Off,
+            &MessageKeyCache::default(),
+        )
+        .unwrap();
+        let event = decrypt_envelope(&me, &cache, None, &envelope).unwrap();
+        assert!(apply_sync(&mut db, &my_pk, &event).is_err());
+        assert_eq!(db.get_thread(&thread_id).unwrap().unwrap().unread_count, 1);
+    }
+}
@@ -0,0 +1,259 @@
+//! Identity Record Sync
+//!
+//! Field-scoped updates to the published identity record. An update reads
+//! the record as published, changes only its own fields, signs the result
+//! and publishes it over the version it read (`If-Match`). When another
+//! device or task published in between, the server refuses the write and
+//! the update is applied again on top of the newer record, so concurrent
+//! changes (a trust refresh and a new endpoint, say) both survive.
+
+use crate::commands::handles::{canonical_json, record_timestamp, validate_record};
+use crate::crypto::{IdentityManager, SignatureDomain};
+use crate::network::{ApiClient, NetworkError};
+use gns_crypto_core::{verify_in_domain_hex, TRANSITION_POLICY};
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+/// Publishes attempted before giving up on a record that keeps changing
+pub const MAX_ATTEMPTS: u32 = 3;
+
+/// A change to some of the record's fields, leaving the rest as published
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordUpdate {
+    Trust { trust_score: f64, breadcrumb_count: u32 },
+    /// `None` drops the handle from the record
+    Handle(Option<String>),
+    Endpoints(Vec<Value>),
+}
+
+impl RecordUpdate {
+    fn apply(&self, record: &mut Value) {
+        match self {
+            Self::Trust { trust_score, breadcrumb_count } => {
+                record["trust_score"] = (*trust_score).into();
+                record["breadcrumb_count"] = (*breadcrumb_count).into();
+            }
+            Self::Handle(Some(handle)) => record["handle"] = handle.as_str().into(),
+            Self::Handle(None) => {
+                if let Some(fields) = record.as_object_mut() {
+                    fields.remove("handle");
+                }
+            }
+            Self::Endpoints(endpoints) => record["endpoints"] = Value::Array(endpoints.clone()),
+        }
+        record["updated_at"] = record_timestamp(chrono::Utc::now()).into();
+    }
+}
+
+/// Apply `update` to the latest published record and publish it, reading
+/// and retrying when a concurrent publish wins. Returns the record as
+/// published.
+pub async fn update_record(
+    api: &ApiClient,
+    identity: &Mutex<IdentityManager>,
+    update: &RecordUpdate,
+) -> Result<Value, String> {
+    let public_key = identity.lock().await.public_key_hex().ok_or("No identity found")?;
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        let current = api
+            .get_record_versioned(&public_key)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or("No published record to update; publish the full record first")?;
+
+        // Re-signing whatever the server handed us would vouch for it
+        let signed_by_us = verify_in_domain_hex(
+            &public_key,
+            SignatureDomain::Record,
+            canonical_json(&current.record.record_json).as_bytes(),
+            &current.record.signature,
+            TRANSITION_POLICY,
+        )
+        .unwrap_or(false);
+        if !signed_by_us {
+            return Err("Published record isn't signed by this identity".to_string());
+        }
+
+        let mut record = current.record.record_json;
+        update.apply(&mut record);
+        validate_record(&record, &public_key).map_err(|e| e.to_string())?;
+
+        let signature = identity
+            .lock()
+            .await
+            .sign_for_backend(SignatureDomain::Record, &canonical_json(&record))
+            .ok_or("Identity not found")?;
+
+        match api
+            .publish_signed_record_if_match(&public_key, &record, &signature, Some(&current.version))
+            .await
+        {
+            Ok(()) => return Ok(record),
+            Err(NetworkError::Conflict(e)) => {
+                tracing::info!("🔁 Record changed while updating (attempt {}): {}", attempt, e);
+            }
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+
+    Err(format!("Record kept changing; gave up after {} attempts", MAX_ATTEMPTS))
+}
+
+/// Refresh the trust score and breadcrumb count in the published record
+pub async fn update_trust_fields(
+    api: &ApiClient,
+    identity: &Mutex<IdentityManager>,
+    trust_score: f64,
+    breadcrumb_count: u32,
+) -> Result<Value, String> {
+    update_record(api, identity, &RecordUpdate::Trust { trust_score, breadcrumb_count }).await
+}
+
+/// Set (or with `None`, remove) the handle in the published record
+pub async fn update_handle(
+    api: &ApiClient,
+    identity: &Mutex<IdentityManager>,
+    handle: Option<&str>,
+) -> Result<Value, String> {
+    update_record(api, identity, &RecordUpdate::Handle(handle.map(str::to_string))).await
+}
+
+/// Replace the endpoints in the published record
+pub async fn update_endpoints(
+    api: &ApiClient,
+    identity: &Mutex<IdentityManager>,
+    endpoints: Vec<Value>,
+) -> Result<Value, String> {
+    update_record(api, identity, &RecordUpdate::Endpoints(endpoints)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gns_crypto_core::GnsIdentity;
+    use serde_json::json;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn signed(identity: &GnsIdentity, record: &Value) -> Value {
+        let signature = identity.sign_in_domain(SignatureDomain::Record, canonical_json(record).as_bytes());
+        json!({ "success": true, "data": { "record_json": record, "signature": hex::encode(signature) } })
+    }
+
+    /// Answer each request in turn with `(status, etag, body)`, returning
+    /// the request lines and their `If-Match` headers
+    async fn serve(listener: TcpListener, responses: Vec<(u16, Option<&'static str>, Value)>) -> Vec<(String, Option<String>)> {
+        let mut seen = Vec::new();
+        for (status, etag, body) in responses {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = Vec::new();
+            let mut chunk = [0u8; 4096];
+            let header_end = loop {
+                let n = socket.read(&mut chunk).await.unwrap();
+                buf.extend_from_slice(&chunk[..n]);
+                if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break pos + 4;
+                }
+            };
+
+            let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+            let header = |name: &str| {
+                head.lines()
+                    .filter_map(|l| l.split_once(':'))
+                    .find(|(k, _)| k.trim().eq_ignore_ascii_case(name))
+                    .map(|(_, v)| v.trim().to_string())
+            };
+            let content_length: usize = header("content-length").and_then(|v| v.parse().ok()).unwrap_or(0);
+            while buf.len() < header_end + content_length {
+                let n = socket.read(&mut chunk).await.unwrap();
+                buf.extend_from_slice(&chunk[..n]);
+            }
+            let request_line = head.lines().next().unwrap_or_default();
+            let request = request_line.rsplit_once(' ').map_or(request_line, |(r, _)| r).to_string();
+            seen.push((request, header("if-match")));
+
+            let body = body.to_string();
+            let etag = etag.map(|e| format!("ETag: {}\r\n", e)).unwrap_or_default();
+            let response = format!(
+                "HTTP/1.1 {} X\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                etag,
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+        seen
+    }
+
+    #[tokio::test]
+    async fn test_conflicting_publish_is_refetched_and_retried() {
+        let identity = GnsIdentity::generate();
+        let public_key = identity.public_key_hex();
+        let record = json!({
+            "identity": public_key,
+            "encryption_key": identity.encryption_key_hex(),
+            "trust_score": 0.0,
+            "breadcrumb_count": 0,
+            "version": 1,
+            "created_at": "2024-01-01T00:00:00.000Z",
+            "updated_at": "2024-01-01T00:00:00.000Z",
+            "modules": [],
+            "endpoints": [],
+            "epoch_roots": [],
+        });
+        // Another device adds an endpoint between our read and our write
+        let mut concurrent = record.clone();
+        concurrent["endpoints"] = json!([{ "type": "relay", "url": "wss://relay.example" }]);
+        concurrent["updated_at"] = json!("2024-01-02T00:00:00.000Z");
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(serve(
+            listener,
+            vec![
+                (200, Some("\"v1\""), signed(&identity, &record)),
+                (412, None, json!({ "success": false, "error": "Precondition failed" })),
+                (200, Some("\"v2\""), signed(&identity, &concurrent)),
+                (200, None, json!({ "success": true })),
+            ],
+        ));
+
+        let api = ApiClient::new(&base_url).unwrap();
+        let manager = Arc::new(Mutex::new(IdentityManager::from_identity(identity)));
+        let published = update_trust_fields(&api, &manager, 42.5, 120).await.unwrap();
+
+        // The retry kept the concurrent endpoint and still applied ours
+        assert_eq!(published["endpoints"], concurrent["endpoints"]);
+        assert_eq!(published["trust_score"], json!(42.5));
+        assert_eq!(published["breadcrumb_count"], json!(120));
+
+        let seen = server.await.unwrap();
+        let path = format!("/records/{}", public_key);
+        assert_eq!(
+            seen,
+            vec![
+                (format!("GET {}", path), None),
+                (format!("PUT {}", path), Some("\"v1\"".to_string())),
+                (format!("GET {}", path), None),
+                (format!("PUT {}", path), Some("\"v2\"".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_updates_touch_only_their_fields() {
+        let mut record = json!({ "handle": "alice", "trust_score": 10.0, "endpoints": [] });
+
+        RecordUpdate::Endpoints(vec![json!({ "url": "https://a.example" })]).apply(&mut record);
+        assert_eq!(record["handle"], "alice");
+        assert_eq!(record["trust_score"], json!(10.0));
+
+        RecordUpdate::Handle(None).apply(&mut record);
+        assert!(record.get("handle").is_none());
+        assert_eq!(record["endpoints"].as_array().unwrap().len(), 1);
+        assert!(record["updated_at"].is_string());
+    }
+}
@@ -0,0 +1,165 @@
+//! Replay Guard
+//!
+//! Dropping repeated envelope ids stops a relay redelivering an envelope,
+//! but not someone re-sending a captured one under a fresh id. The guard
+//! looks at what can't be changed without breaking decryption instead:
+//! the encrypted content. For each sender it remembers the content hashes
+//! of the last envelopes seen, and turns away one it has seen before or
+//! whose timestamp falls outside what [`ReplayPolicy`] allows.
+//!
+//! The window lives in memory. After a restart only the age limit applies
+//! until the window fills again.
+
+use crate::config::ReplayPolicy;
+use gns_crypto_core::GnsEnvelope;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Why an envelope was turned away
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ReplayError {
+    #[error("timestamp is {0} ms ahead of our clock")]
+    FromTheFuture(i64),
+    #[error("envelope is {0} ms old")]
+    TooOld(i64),
+    #[error("same content already received at {0}")]
+    Replayed(i64),
+}
+
+/// Recently seen envelopes of one sender, oldest first
+#[derive(Default)]
+struct SenderWindow {
+    order: VecDeque<([u8; 32], i64)>,
+    hashes: HashSet<[u8; 32]>,
+}
+
+/// Per-sender sliding windows of `(timestamp, content hash)`
+pub struct ReplayGuard {
+    policy: ReplayPolicy,
+    senders: HashMap<String, SenderWindow>,
+}
+
+impl ReplayGuard {
+    pub fn new(policy: ReplayPolicy) -> Self {
+        Self {
+            policy,
+            senders: HashMap::new(),
+        }
+    }
+
+    /// Accept `envelope` from `sender` at `now_ms` and remember it, or say
+    /// why it's a replay. Nothing is remembered for a rejected envelope.
+    pub fn check(&mut self, sender: &str, envelope: &GnsEnvelope, now_ms: i64) -> Result<(), ReplayError> {
+        let ahead = envelope.timestamp - now_ms;
+        if ahead > self.policy.max_future_skew_ms as i64 {
+            return Err(ReplayError::FromTheFuture(ahead));
+        }
+        if -ahead > self.policy.max_age_ms as i64 {
+            return Err(ReplayError::TooOld(-ahead));
+        }
+
+        let hash = content_hash(envelope);
+        let window = self.senders.entry(sender.to_lowercase()).or_default();
+        if window.hashes.contains(&hash) {
+            let first_seen = window.order.iter().find(|(h, _)| *h == hash).map_or(0, |(_, ts)| *ts);
+            return Err(ReplayError::Replayed(first_seen));
+        }
+
+        window.hashes.insert(hash);
+        window.order.push_back((hash, envelope.timestamp));
+        while window.order.len() > self.policy.window_size {
+            if let Some((old, _)) = window.order.pop_front() {
+                window.hashes.remove(&old);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Hash of the encrypted content. Every send gets a fresh nonce, so the
+/// same text sent twice hashes differently, but a re-sent envelope hashes
+/// the same whatever its id.
+fn content_hash(envelope: &GnsEnvelope) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(serde_json::to_vec(&envelope.encrypted_payload).unwrap_or_default());
+    for part in [&envelope.ephemeral_public_key, &envelope.nonce] {
+        hasher.update([0]);
+        hasher.update(part.as_deref().unwrap_or_default().as_bytes());
+    }
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gns_crypto_core::{create_envelope, GnsIdentity};
+
+    fn text(sender: &GnsIdentity, recipient: &GnsIdentity, text: &str) -> GnsEnvelope {
+        let payload = serde_json::json!({ "text": text });
+        create_envelope(
+            sender,
+            &recipient.public_key_hex(),
+            &recipient.encryption_key_hex(),
+            "text/plain",
+            payload.to_string().as_bytes(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_replay_under_new_id_is_rejected() {
+        let alice = GnsIdentity::generate();
+        let bob = GnsIdentity::generate();
+        let sender = alice.public_key_hex();
+        let mut guard = ReplayGuard::new(ReplayPolicy::default());
+
+        let envelope = text(&alice, &bob, "transfer approved");
+        let now = envelope.timestamp + 1_000;
+        assert_eq!(guard.check(&sender, &envelope, now), Ok(()));
+
+        let mut replayed = envelope.clone();
+        replayed.id = uuid::Uuid::new_v4().to_string();
+        assert_eq!(
+            guard.check(&sender, &replayed, now + 60_000),
+            Err(ReplayError::Replayed(envelope.timestamp))
+        );
+    }
+
+    #[test]
+    fn test_repeated_text_is_accepted() {
+        let alice = GnsIdentity::generate();
+        let bob = GnsIdentity::generate();
+        let sender = alice.public_key_hex();
+        let mut guard = ReplayGuard::new(ReplayPolicy::default());
+
+        let first = text(&alice, &bob, "ok");
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let second = text(&alice, &bob, "ok");
+        assert_ne!((&first.id, first.timestamp), (&second.id, second.timestamp));
+
+        let now = second.timestamp;
+        assert_eq!(guard.check(&sender, &first, now), Ok(()));
+        assert_eq!(guard.check(&sender, &second, now), Ok(()));
+    }
+
+    #[test]
+    fn test_timestamps_outside_the_skew_are_rejected() {
+        let alice = GnsIdentity::generate();
+        let bob = GnsIdentity::generate();
+        let sender = alice.public_key_hex();
+        let policy = ReplayPolicy { max_future_skew_ms: 1_000, max_age_ms: 60_000, window_size: 2 };
+        let mut guard = ReplayGuard::new(policy);
+
+        let envelope = text(&alice, &bob, "hi");
+        let sent = envelope.timestamp;
+        assert_eq!(guard.check(&sender, &envelope, sent - 5_000), Err(ReplayError::FromTheFuture(5_000)));
+        assert_eq!(guard.check(&sender, &envelope, sent + 90_000), Err(ReplayError::TooOld(90_000)));
+        // Rejected ones aren't remembered
+        assert_eq!(guard.check(&sender, &envelope, sent), Ok(()));
+
+        // Once pushed out of the window an envelope is only caught by age
+        guard.check(&sender, &text(&alice, &bob, "two"), sent).unwrap();
+        guard.check(&sender, &text(&alice, &bob, "three"), sent).unwrap();
+        assert_eq!(guard.check(&sender, &envelope, sent), Ok(()));
+    }
+}
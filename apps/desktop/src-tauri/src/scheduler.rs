@@ -0,0 +1,361 @@
+//! Send-Later Scheduler
+//!
+//! One background task walks `scheduled_messages` in send order, hands
+//! each due message to the normal send path and sleeps until the next
+//! send time. It reads the table afresh every round, so messages
+//! scheduled before a restart (including ones that came due while the app
+//! was closed) go out as soon as it starts.
+//!
+//! Messages to one recipient go out in order: if one can't be sent, later
+//! ones to the same recipient wait behind it while the rest still go out.
+//! Nothing is tried while offline. The task retries failed messages when
+//! connectivity returns or after `RETRY_DELAY`, and gives up on one after
+//! `MAX_SEND_ATTEMPTS` failures; it then stays listed with its error until
+//! it is rescheduled or cancelled.
+
+use crate::commands::messaging::SendResult;
+use crate::storage::{Database, ScheduledMessage, MAX_SEND_ATTEMPTS};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, Mutex, Notify};
+
+/// Tauri event carrying a [`ScheduledSent`]
+pub const SCHEDULED_SENT_EVENT: &str = "scheduled_message_sent";
+
+/// Wait before retrying a send that failed while still online
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Longest sleep with nothing scheduled; changes wake the task anyway
+const IDLE_DELAY: Duration = Duration::from_secs(3600);
+
+/// Payload of the `scheduled_message_sent` event
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduledSent {
+    pub scheduled_id: String,
+    pub message_id: String,
+    pub thread_id: Option<String>,
+}
+
+/// Lets commands tell the scheduler the table changed
+#[derive(Default)]
+pub struct SendLater {
+    wake: Notify,
+}
+
+impl SendLater {
+    /// Re-read the schedule now
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+}
+
+/// Run the scheduler until the app exits. `send` dispatches one message;
+/// `on_sent` is told about each one that went out.
+pub async fn run<S, Fut, E>(
+    database: Arc<Mutex<Database>>,
+    send_later: Arc<SendLater>,
+    mut online: watch::Receiver<bool>,
+    mut send: S,
+    mut on_sent: E,
+) where
+    S: FnMut(ScheduledMessage) -> Fut,
+    Fut: Future<Output = Result<SendResult, String>>,
+    E: FnMut(ScheduledSent),
+{
+    loop {
+        let online_now = *online.borrow_and_update();
+        let blocked = !online_now || dispatch_due(&database, &mut send, &mut on_sent).await;
+
+        let delay = if blocked {
+            RETRY_DELAY
+        } else {
+            let next = database.lock().await.next_scheduled_at().unwrap_or_else(|e| {
+                tracing::warn!("⚠️ Could not read message schedule: {}", e);
+                None
+            });
+            match next {
+                Some(at) => Duration::from_millis((at - now_millis()).max(0) as u64).min(IDLE_DELAY),
+                None => IDLE_DELAY,
+            }
+        };
+
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = send_later.wake.notified() => {}
+            changed = online.changed() => {
+                if changed.is_err() {
+                    // Connectivity is gone for good; fall back to the timer alone
+                    online = watch::channel(true).1;
+                }
+            }
+        }
+    }
+}
+
+/// Send everything that is due, oldest first. Returns true if a send
+/// failed and is to be retried.
+async fn dispatch_due<S, Fut, E>(database: &Mutex<Database>, send: &mut S, on_sent: &mut E) -> bool
+where
+    S: FnMut(ScheduledMessage) -> Fut,
+    Fut: Future<Output = Result<SendResult, String>>,
+    E: FnMut(ScheduledSent),
+{
+    let due = match database.lock().await.due_scheduled_messages(now_millis()) {
+        Ok(due) => due,
+        Err(e) => {
+            tracing::warn!("⚠️ Could not read due scheduled messages: {}", e);
+            return true;
+        }
+    };
+
+    let mut blocked = false;
+    // Recipients with a failed message this round; their later ones wait
+    let mut held = HashSet::new();
+    for message in due {
+        let id = message.id.clone();
+
+        // Earlier sends took time; it may have been cancelled or moved since
+        let message = match database.lock().await.get_scheduled_message(&id) {
+            Ok(Some(message)) if message.send_at <= now_millis() => message,
+            Ok(_) => continue,
+            Err(e) => {
+                tracing::warn!("⚠️ Could not re-read scheduled message {}: {}", id, e);
+                blocked = true;
+                continue;
+            }
+        };
+        let recipient = recipient_of(&message);
+        if held.contains(&recipient) {
+            continue;
+        }
+
+        match send(message).await {
+            Ok(sent) => {
+                if let Err(e) = database.lock().await.delete_scheduled_message(&id) {
+                    // Leaving it would send it twice
+                    tracing::error!("Sent scheduled message {} but could not remove it: {}", id, e);
+                    return true;
+                }
+                tracing::info!("⏰ Sent scheduled message {}", id);
+                on_sent(ScheduledSent {
+                    scheduled_id: id,
+                    message_id: sent.message_id,
+                    thread_id: sent.thread_id,
+                });
+            }
+            Err(e) => {
+                held.insert(recipient);
+                match database.lock().await.record_scheduled_failure(&id, &e) {
+                    Ok(attempts) if attempts >= MAX_SEND_ATTEMPTS => {
+                        tracing::error!("Giving up on scheduled message {} after {} attempts: {}", id, attempts, e);
+                    }
+                    _ => {
+                        tracing::warn!("⚠️ Scheduled message {} not sent, will retry: {}", id, e);
+                        blocked = true;
+                    }
+                }
+            }
+        }
+    }
+    blocked
+}
+
+/// Who a scheduled message goes to, for keeping per-recipient order
+fn recipient_of(message: &ScheduledMessage) -> String {
+    match (&message.recipient_public_key, &message.recipient_handle) {
+        (Some(public_key), _) => public_key.to_lowercase(),
+        (None, Some(handle)) => format!("@{}", handle.trim_start_matches('@').to_lowercase()),
+        (None, None) => String::new(),
+    }
+}
+
+fn now_millis() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduled(id: &str, send_at: i64) -> ScheduledMessage {
+        ScheduledMessage {
+            id: id.to_string(),
+            recipient_handle: Some("alice".to_string()),
+            recipient_public_key: None,
+            payload_type: "text".to_string(),
+            payload: serde_json::json!({ "text": id }),
+            thread_id: None,
+            reply_to_id: None,
+            send_at,
+            created_at: send_at,
+            last_error: None,
+            attempts: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_past_due_message_fires_on_startup() {
+        // Left over from before a restart: one overdue, one far in the future
+        let mut db = Database::open_in_memory().unwrap();
+        let now = now_millis();
+        db.save_scheduled_message(&scheduled("overdue-2", now - 1_000)).unwrap();
+        db.save_scheduled_message(&scheduled("overdue-1", now - 60_000)).unwrap();
+        db.save_scheduled_message(&scheduled("tomorrow", now + 86_400_000)).unwrap();
+        let database = Arc::new(Mutex::new(db));
+
+        let (sent_tx, mut sent_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (_online_tx, online) = watch::channel(true);
+        tokio::spawn(run(
+            database.clone(),
+            Arc::new(SendLater::default()),
+            online,
+            |message: ScheduledMessage| async move {
+                Ok(SendResult { message_id: format!("msg-{}", message.id), thread_id: None })
+            },
+            move |sent| sent_tx.send(sent).unwrap(),
+        ));
+
+        let mut order = Vec::new();
+        for _ in 0..2 {
+            let sent = tokio::time::timeout(Duration::from_secs(2), sent_rx.recv())
+                .await
+                .expect("overdue message should fire promptly")
+                .unwrap();
+            assert_eq!(sent.message_id, format!("msg-{}", sent.scheduled_id));
+            order.push(sent.scheduled_id);
+        }
+        assert_eq!(order, vec!["overdue-1", "overdue-2"]);
+
+        let left = database.lock().await.list_scheduled_messages().unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].id, "tomorrow");
+    }
+
+    #[tokio::test]
+    async fn test_failed_send_holds_back_later_messages() {
+        let mut db = Database::open_in_memory().unwrap();
+        let now = now_millis();
+        db.save_scheduled_message(&scheduled("first", now - 2_000)).unwrap();
+        db.save_scheduled_message(&scheduled("second", now - 1_000)).unwrap();
+        let database = Mutex::new(db);
+
+        let mut attempted = Vec::new();
+        let blocked = dispatch_due(
+            &database,
+            &mut |message: ScheduledMessage| {
+                attempted.push(message.id);
+                async { Err::<SendResult, _>("Failed to send: Not connected".to_string()) }
+            },
+            &mut |_| panic!("nothing should be sent"),
+        )
+        .await;
+
+        assert!(blocked);
+        assert_eq!(attempted, vec!["first"]);
+        let left = database.lock().await.list_scheduled_messages().unwrap();
+        assert_eq!(left.len(), 2);
+        assert_eq!(left[0].last_error.as_deref(), Some("Failed to send: Not connected"));
+    }
+
+    fn to(id: &str, recipient: &str, send_at: i64) -> ScheduledMessage {
+        ScheduledMessage { recipient_handle: Some(recipient.to_string()), ..scheduled(id, send_at) }
+    }
+
+    #[tokio::test]
+    async fn test_failed_send_holds_back_only_that_recipient() {
+        let mut db = Database::open_in_memory().unwrap();
+        let now = now_millis();
+        db.save_scheduled_message(&to("first", "alice", now - 3_000)).unwrap();
+        db.save_scheduled_message(&to("second", "alice", now - 2_000)).unwrap();
+        db.save_scheduled_message(&to("other", "bob", now - 1_000)).unwrap();
+        let database = Mutex::new(db);
+
+        let mut attempted = Vec::new();
+        let mut sent = Vec::new();
+        let blocked = dispatch_due(
+            &database,
+            &mut |message: ScheduledMessage| {
+                attempted.push(message.id.clone());
+                async move {
+                    if message.id == "first" {
+                        Err("Failed to send: no route".to_string())
+                    } else {
+                        Ok(SendResult { message_id: message.id, thread_id: None })
+                    }
+                }
+            },
+            &mut |s: ScheduledSent| sent.push(s.scheduled_id),
+        )
+        .await;
+
+        assert!(blocked);
+        assert_eq!(attempted, vec!["first", "other"]);
+        assert_eq!(sent, vec!["other"]);
+        let left = database.lock().await.list_scheduled_messages().unwrap();
+        assert_eq!(left.len(), 2);
+        assert_eq!(left[0].last_error.as_deref(), Some("Failed to send: no route"));
+        assert_eq!(left[0].attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn test_failing_message_is_given_up_on() {
+        let mut db = Database::open_in_memory().unwrap();
+        let now = now_millis();
+        db.save_scheduled_message(&to("broken", "alice", now - 2_000)).unwrap();
+        db.save_scheduled_message(&to("next", "alice", now - 1_000)).unwrap();
+        let database = Mutex::new(db);
+
+        let mut fail = |message: ScheduledMessage| async move {
+            if message.id == "broken" {
+                Err("Failed to send: rejected".to_string())
+            } else {
+                Ok(SendResult { message_id: message.id, thread_id: None })
+            }
+        };
+        for _ in 1..MAX_SEND_ATTEMPTS {
+            assert!(dispatch_due(&database, &mut fail, &mut |_| panic!("held back behind broken")).await);
+        }
+
+        // The last failure gives up on it, and the next round moves on
+        let mut sent = Vec::new();
+        assert!(!dispatch_due(&database, &mut fail, &mut |s: ScheduledSent| sent.push(s.scheduled_id)).await);
+        assert!(sent.is_empty());
+        assert!(!dispatch_due(&database, &mut fail, &mut |s: ScheduledSent| sent.push(s.scheduled_id)).await);
+        assert_eq!(sent, vec!["next"]);
+
+        let left = database.lock().await.list_scheduled_messages().unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].attempts, MAX_SEND_ATTEMPTS);
+    }
+
+    #[tokio::test]
+    async fn test_message_cancelled_mid_round_is_not_sent() {
+        let mut db = Database::open_in_memory().unwrap();
+        let now = now_millis();
+        db.save_scheduled_message(&to("first", "alice", now - 2_000)).unwrap();
+        db.save_scheduled_message(&to("cancelled", "bob", now - 1_000)).unwrap();
+        let database = Arc::new(Mutex::new(db));
+
+        // The user cancels the second message while the first is being sent
+        let mut attempted = Vec::new();
+        let cancel = database.clone();
+        dispatch_due(
+            &database,
+            &mut |message: ScheduledMessage| {
+                attempted.push(message.id.clone());
+                let cancel = cancel.clone();
+                async move {
+                    cancel.lock().await.delete_scheduled_message("cancelled").unwrap();
+                    Ok(SendResult { message_id: message.id, thread_id: None })
+                }
+            },
+            &mut |_| {},
+        )
+        .await;
+
+        assert_eq!(attempted, vec!["first"]);
+    }
+}
@@ -0,0 +1,169 @@
+//! Deferred Service Startup
+//!
+//! `setup_app_state` only builds what every launch needs. Anything that
+//! talks to the network on its own - the relay socket, the GNS asset check
+//! and (on mobile) breadcrumb collection - waits here until there is an
+//! identity to run it for: at launch when one is already stored, otherwise
+//! when the frontend calls `initialize_services` after onboarding.
+
+use crate::network::RelayConnection;
+use crate::AppState;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+#[cfg(any(target_os = "ios", target_os = "android"))]
+use crate::location::BreadcrumbCollector;
+
+/// Outcome of asking for services to start
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServicesStatus {
+    /// Nothing started: there is no identity yet
+    NoIdentity,
+    /// Services were started by this call
+    Started,
+    /// Services were already running
+    AlreadyRunning,
+}
+
+/// Remembers whether services are running so they start only once
+#[derive(Default)]
+pub struct ServiceGate {
+    started: AtomicBool,
+}
+
+impl ServiceGate {
+    /// Claim the start. Only a `Started` caller should bring services up.
+    pub fn open(&self, public_key: Option<&str>) -> ServicesStatus {
+        if public_key.is_none() {
+            return ServicesStatus::NoIdentity;
+        }
+        if self.started.swap(true, Ordering::SeqCst) {
+            ServicesStatus::AlreadyRunning
+        } else {
+            ServicesStatus::Started
+        }
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.started.load(Ordering::SeqCst)
+    }
+
+    /// Allow services to start again, e.g. for the next identity after
+    /// the current one is deleted
+    pub fn reset(&self) {
+        self.started.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Start the network-bound services for the stored identity, if any
+pub async fn initialize(state: &AppState) -> ServicesStatus {
+    let public_key = state.identity.lock().await.public_key_hex();
+    let status = connect_relay(&state.services, public_key.as_deref(), &state.relay);
+    if status != ServicesStatus::Started {
+        tracing::debug!(?status, "Services not started");
+        return status;
+    }
+
+    if let Some(public_key) = &public_key {
+        let mut db = state.database.lock().await;
+        if db.thread_ids_pending() {
+            match db.migrate_thread_ids(public_key) {
+                Ok(done) => tracing::info!(
+                    "🧵 Migrated thread ids: {} messages moved, {} threads merged",
+                    done.messages_moved,
+                    done.threads_merged
+                ),
+                Err(e) => tracing::warn!("⚠️ Could not migrate thread ids, will retry next launch: {}", e),
+            }
+        }
+    }
+
+    crate::commands::stellar::spawn_gns_asset_check(state.stellar.clone());
+
+    #[cfg(any(target_os = "ios", target_os = "android"))]
+    {
+        let enabled = state.database.lock().await.get_collection_enabled();
+        let mut collector = state.breadcrumb_collector.lock().await;
+        let collector = collector.get_or_insert_with(BreadcrumbCollector::new);
+        if enabled {
+            if let Err(e) = collector.start() {
+                tracing::warn!("⚠️ Could not start breadcrumb collection: {}", e);
+            }
+        }
+    }
+
+    tracing::info!("🚀 Services started");
+    status
+}
+
+/// Start services if needed and make sure the relay is connected with its
+/// subscription confirmed, connecting now if it isn't
+pub async fn ensure_relay_ready(state: &AppState) -> Result<(), String> {
+    if initialize(state).await == ServicesStatus::NoIdentity {
+        return Err("No identity configured".to_string());
+    }
+    let public_key = state.identity.lock().await.public_key_hex().ok_or("No identity configured")?;
+
+    // Holding the lock waits out a connect that is already under way
+    let relay = state.relay.lock().await;
+    if relay.is_ready() {
+        return Ok(());
+    }
+    relay.connect(&public_key).await.map_err(|e| e.to_string())
+}
+
+/// Claim the start for `public_key` and, if this call won it, connect the
+/// relay in the background
+fn connect_relay(
+    gate: &ServiceGate,
+    public_key: Option<&str>,
+    relay: &Arc<Mutex<RelayConnection>>,
+) -> ServicesStatus {
+    let status = gate.open(public_key);
+    if let (ServicesStatus::Started, Some(public_key)) = (status, public_key) {
+        let relay = relay.clone();
+        let public_key = public_key.to_string();
+        tauri::async_runtime::spawn(async move {
+            let relay = relay.lock().await;
+            // `ensure_relay_ready` may have got the lock first
+            if relay.is_ready() {
+                return;
+            }
+            if let Err(e) = relay.connect(&public_key).await {
+                tracing::warn!("⚠️ Initial relay connect failed: {}", e);
+            }
+        });
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_no_relay_connection_without_identity() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let relay = Arc::new(Mutex::new(RelayConnection::new(&url).unwrap()));
+        let gate = ServiceGate::default();
+
+        assert_eq!(connect_relay(&gate, None, &relay), ServicesStatus::NoIdentity);
+        assert!(!gate.is_started());
+        let attempt = tokio::time::timeout(Duration::from_millis(200), listener.accept()).await;
+        assert!(attempt.is_err(), "relay was contacted without an identity");
+
+        // Once onboarding has produced a key, the relay is dialled exactly once
+        assert_eq!(connect_relay(&gate, Some("pk"), &relay), ServicesStatus::Started);
+        tokio::time::timeout(Duration::from_secs(2), listener.accept())
+            .await
+            .expect("relay should be contacted")
+            .unwrap();
+        assert_eq!(connect_relay(&gate, Some("pk"), &relay), ServicesStatus::AlreadyRunning);
+    }
+}
@@ -0,0 +1,177 @@
+//! Settings Module - Runtime Endpoint Configuration
+//!
+//! Endpoint overrides are kept in the local `settings` table so the app
+//! can be pointed at staging or self-hosted infrastructure without a
+//! rebuild. Anything not overridden falls back to the `DesktopConfig`
+//! defaults.
+
+use crate::config::DesktopConfig;
+use crate::stellar::StellarNetwork;
+use crate::storage::{Database, DatabaseError};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_API_URL: &str = "https://gns-browser-production.up.railway.app";
+pub const DEFAULT_RELAY_URL: &str = "wss://gns-browser-production.up.railway.app";
+
+const API_URL_KEY: &str = "api_url";
+const RELAY_URL_KEY: &str = "relay_url";
+const HORIZON_URL_KEY: &str = "horizon_url";
+const STELLAR_NETWORK_KEY: &str = "stellar_network";
+const NOTIFICATIONS_ENABLED_KEY: &str = "notifications_enabled";
+
+/// Which endpoint a setting refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endpoint {
+    Api,
+    Relay,
+    Horizon,
+}
+
+impl Endpoint {
+    fn key(self) -> &'static str {
+        match self {
+            Endpoint::Api => API_URL_KEY,
+            Endpoint::Relay => RELAY_URL_KEY,
+            Endpoint::Horizon => HORIZON_URL_KEY,
+        }
+    }
+
+    fn allowed_schemes(self) -> &'static [&'static str] {
+        match self {
+            Endpoint::Api | Endpoint::Horizon => &["https", "http"],
+            Endpoint::Relay => &["wss", "ws", "https", "http"],
+        }
+    }
+}
+
+/// Endpoints the app talks to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Endpoints {
+    pub api_url: String,
+    pub relay_url: String,
+    pub horizon_url: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self::defaults_for(&DesktopConfig::default(), StellarNetwork::Mainnet)
+    }
+}
+
+impl Endpoints {
+    /// Configured endpoints, with Horizon matching the given network
+    pub fn defaults_for(config: &DesktopConfig, network: StellarNetwork) -> Self {
+        Self {
+            api_url: config.api_url.clone(),
+            relay_url: config.relay_url.clone(),
+            horizon_url: config.horizon_url_for(network),
+        }
+    }
+
+    /// Load endpoints, applying any stored overrides on top of the defaults
+    pub fn load(db: &Database, config: &DesktopConfig) -> Self {
+        let defaults = Self::defaults_for(config, load_stellar_network(db, config));
+        Self {
+            api_url: db.get_setting(API_URL_KEY).unwrap_or(defaults.api_url),
+            relay_url: db.get_setting(RELAY_URL_KEY).unwrap_or(defaults.relay_url),
+            horizon_url: db.get_setting(HORIZON_URL_KEY).unwrap_or(defaults.horizon_url),
+        }
+    }
+
+    /// Validate and persist an override, returning the normalised URL
+    pub fn save(db: &mut Database, endpoint: Endpoint, url: &str) -> Result<String, String> {
+        let url = validate_url(url, endpoint.allowed_schemes())?;
+        db.set_setting(endpoint.key(), &url).map_err(|e| e.to_string())?;
+        Ok(url)
+    }
+
+    /// Drop all overrides
+    pub fn reset(db: &mut Database, config: &DesktopConfig) -> Result<Self, DatabaseError> {
+        for key in [API_URL_KEY, RELAY_URL_KEY, HORIZON_URL_KEY] {
+            db.delete_setting(key)?;
+        }
+        Ok(Self::defaults_for(config, load_stellar_network(db, config)))
+    }
+}
+
+/// The persisted Stellar network, the configured one unless the user
+/// picked another
+pub fn load_stellar_network(db: &Database, config: &DesktopConfig) -> StellarNetwork {
+    db.get_setting(STELLAR_NETWORK_KEY)
+        .and_then(|name| StellarNetwork::parse(&name))
+        .unwrap_or(config.stellar_network)
+}
+
+/// Persist the Stellar network choice.
+///
+/// A Horizon override points at one specific network, so it is dropped
+/// when the network changes.
+pub fn save_stellar_network(
+    db: &mut Database,
+    config: &DesktopConfig,
+    network: StellarNetwork,
+) -> Result<(), DatabaseError> {
+    if load_stellar_network(db, config) != network {
+        db.delete_setting(HORIZON_URL_KEY)?;
+    }
+    db.set_setting(STELLAR_NETWORK_KEY, network.as_str())
+}
+
+/// Whether desktop notifications are on (default: on)
+pub fn load_notifications_enabled(db: &Database) -> bool {
+    db.get_setting(NOTIFICATIONS_ENABLED_KEY).map_or(true, |v| v != "false")
+}
+
+pub fn save_notifications_enabled(db: &mut Database, enabled: bool) -> Result<(), DatabaseError> {
+    db.set_setting(NOTIFICATIONS_ENABLED_KEY, if enabled { "true" } else { "false" })
+}
+
+/// Check that a URL is absolute, uses an allowed scheme and has a host
+pub fn validate_url(url: &str, schemes: &[&str]) -> Result<String, String> {
+    let parsed = Url::parse(url.trim()).map_err(|e| format!("Invalid URL: {}", e))?;
+
+    if !schemes.contains(&parsed.scheme()) {
+        return Err(format!(
+            "Unsupported scheme '{}' (expected one of: {})",
+            parsed.scheme(),
+            schemes.join(", ")
+        ));
+    }
+
+    if parsed.host_str().map_or(true, |h| h.is_empty()) {
+        return Err("URL must include a host".to_string());
+    }
+
+    if parsed.query().is_some() || parsed.fragment().is_some() {
+        return Err("URL must not include a query or fragment".to_string());
+    }
+
+    if !parsed.username().is_empty() || parsed.password().is_some() {
+        return Err("URL must not include credentials".to_string());
+    }
+
+    Ok(parsed.as_str().trim_end_matches('/').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_url() {
+        let http = &["https", "http"];
+
+        assert_eq!(
+            validate_url(" https://staging.example.com/ ", http).unwrap(),
+            "https://staging.example.com"
+        );
+        assert!(validate_url("http://localhost:3000", http).is_ok());
+
+        assert!(validate_url("not a url", http).is_err());
+        assert!(validate_url("ftp://example.com", http).is_err());
+        assert!(validate_url("https://example.com/?debug=1", http).is_err());
+        assert!(validate_url("https://user:pw@example.com", http).is_err());
+        assert!(validate_url("wss://relay.example.com", &["wss"]).is_ok());
+    }
+}
@@ -0,0 +1,383 @@
+//! Stellar Assets
+//!
+//! GNS is the wallet's own token, but a Stellar account can hold any credit
+//! asset. An [`Asset`] names one by code and issuer. GNS keeps going
+//! through the backend (which sponsors fees and handles claimable
+//! balances); for the other configured assets the transaction is built
+//! here and submitted straight to Horizon.
+
+use super::send_guard::{self, Destination};
+use super::{
+    build_transaction, decode_account_id, parse_trust_limit, HorizonTransactionResponse,
+    StellarError, StellarService, TransactionResult, MAX_TRUST_LIMIT,
+};
+use serde::{Deserialize, Serialize};
+use stellar_xdr::curr::{
+    AccountId, AlphaNum12, AlphaNum4, AssetCode12, AssetCode4, ChangeTrustAsset, ChangeTrustOp, ClaimPredicate,
+    Claimant, ClaimantV0, CreateClaimableBalanceOp, MuxedAccount, Operation, OperationBody, PaymentOp, PublicKey,
+    Uint256,
+};
+
+/// Stroops in one unit of any Stellar asset
+const STROOPS_PER_UNIT: f64 = 10_000_000.0;
+
+/// A Stellar credit asset
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Asset {
+    /// 1-12 letters or digits, e.g. "GNS"
+    pub code: String,
+    /// G... address of the issuing account
+    pub issuer: String,
+}
+
+impl Asset {
+    pub fn new(code: &str, issuer: &str) -> Self {
+        Self { code: code.to_string(), issuer: issuer.to_string() }
+    }
+
+    /// Whether a Horizon balance or claimable balance is in this asset
+    pub fn matches(&self, code: &str, issuer: Option<&str>) -> bool {
+        self.code == code && issuer == Some(self.issuer.as_str())
+    }
+
+    /// XDR form used in payments. Codes of up to 4 characters are
+    /// alphanum4, longer ones alphanum12, zero-padded either way.
+    pub fn to_xdr(&self) -> Result<stellar_xdr::curr::Asset, StellarError> {
+        let code = self.code.as_bytes();
+        if code.is_empty() || code.len() > 12 || !code.iter().all(u8::is_ascii_alphanumeric) {
+            return Err(StellarError::Validation(format!("Invalid asset code: {}", self.code)));
+        }
+
+        let issuer = decode_account_id(&self.issuer).map_err(|e| {
+            StellarError::Validation(format!("{} is not a valid Stellar account ({})", self.issuer, e))
+        })?;
+        let issuer = AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(issuer)));
+
+        Ok(if code.len() <= 4 {
+            let mut asset_code = [0u8; 4];
+            asset_code[..code.len()].copy_from_slice(code);
+            stellar_xdr::curr::Asset::CreditAlphanum4(AlphaNum4 { asset_code: AssetCode4(asset_code), issuer })
+        } else {
+            let mut asset_code = [0u8; 12];
+            asset_code[..code.len()].copy_from_slice(code);
+            stellar_xdr::curr::Asset::CreditAlphanum12(AlphaNum12 { asset_code: AssetCode12(asset_code), issuer })
+        })
+    }
+
+    /// XDR form used in trustlines
+    pub fn to_trust_xdr(&self) -> Result<ChangeTrustAsset, StellarError> {
+        Ok(match self.to_xdr()? {
+            stellar_xdr::curr::Asset::CreditAlphanum4(a) => ChangeTrustAsset::CreditAlphanum4(a),
+            stellar_xdr::curr::Asset::CreditAlphanum12(a) => ChangeTrustAsset::CreditAlphanum12(a),
+            stellar_xdr::curr::Asset::Native => ChangeTrustAsset::Native,
+        })
+    }
+}
+
+impl StellarService {
+    /// Send `amount` of a configured asset to a Stellar address.
+    ///
+    /// GNS goes through [`Self::send_gns_to_address`] and reaches recipients
+    /// without a trustline as a claimable balance. Any other asset is paid
+    /// directly, so the recipient must already trust it. A send to the
+    /// asset's issuer is blocked unless `confirmed`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn send_asset(
+        &self,
+        code: &str,
+        issuer: &str,
+        sender_public_key: &str,
+        sender_private_key: &[u8],
+        recipient_address: &str,
+        amount: f64,
+        confirmed: bool,
+    ) -> Result<TransactionResult, StellarError> {
+        let asset = self.config.known_asset(code, issuer).ok_or_else(|| {
+            StellarError::Validation(format!("{}:{} is not a configured asset", code, issuer))
+        })?;
+
+        if asset == self.config.gns_asset() {
+            let delivery = self.gns_delivery(recipient_address).await?;
+            return self
+                .send_gns_to_address(sender_public_key, sender_private_key, recipient_address, amount, delivery, confirmed)
+                .await;
+        }
+
+        let sender_address = Self::gns_key_to_stellar(sender_public_key)?;
+        let warning = match send_guard::check_destination(&sender_address, recipient_address, &asset, confirmed)? {
+            Destination::Allowed(warning) => warning,
+            Destination::Blocked(result) => return Ok(result),
+        };
+
+        let payment = transfer_operation(&asset, recipient_address, amount, false)?;
+        match self.has_trustline(recipient_address, &asset).await {
+            Ok(true) => {}
+            Ok(false) | Err(StellarError::AccountNotFound) => {
+                return Err(StellarError::Validation(format!(
+                    "Recipient has no {} trustline",
+                    asset.code
+                )))
+            }
+            Err(e) => return Err(e),
+        }
+
+        let result = self.submit_operation(sender_public_key, sender_private_key, payment).await?;
+        tracing::info!(success = result.success, "{} transfer finished", asset.code);
+        Ok(TransactionResult { warning, ..result })
+    }
+
+    /// Add, resize or (with limit "0") remove a trustline for a non-GNS asset
+    pub(super) async fn submit_change_trust(
+        &self,
+        public_key_hex: &str,
+        private_key_bytes: &[u8],
+        asset: &Asset,
+        limit: Option<&str>,
+    ) -> Result<TransactionResult, StellarError> {
+        let limit = match limit {
+            Some(limit) => (parse_trust_limit(limit)? * STROOPS_PER_UNIT).round() as i64,
+            None => i64::MAX,
+        };
+
+        let change_trust = OperationBody::ChangeTrust(ChangeTrustOp {
+            line: asset.to_trust_xdr()?,
+            limit,
+        });
+        self.submit_operation(public_key_hex, private_key_bytes, change_trust).await
+    }
+
+    /// Sign and submit a one-operation transaction from the wallet's account
+    pub(super) async fn submit_operation(
+        &self,
+        public_key_hex: &str,
+        private_key_bytes: &[u8],
+        body: OperationBody,
+    ) -> Result<TransactionResult, StellarError> {
+        let stellar_address = Self::gns_key_to_stellar(public_key_hex)?;
+        let source = decode_account_id(&stellar_address)?;
+        let sequence = self.next_sequence(&stellar_address).await?;
+
+        let operation = Operation { source_account: None, body };
+        let xdr = build_transaction(source, sequence, vec![operation])?;
+        let signed = self.sign_transaction(&xdr, private_key_bytes)?;
+        let response = self.submit_transaction(&signed).await?;
+        Ok(transaction_result(response))
+    }
+}
+
+/// Convert a user-entered amount to stroops
+fn to_stroops(amount: f64) -> Result<i64, StellarError> {
+    if !amount.is_finite() || amount <= 0.0 || amount > MAX_TRUST_LIMIT {
+        return Err(StellarError::Validation(format!("Invalid amount: {}", amount)));
+    }
+    let stroops = (amount * STROOPS_PER_UNIT).round() as i64;
+    if stroops == 0 {
+        return Err(StellarError::Validation(format!("Amount {} is below one stroop", amount)));
+    }
+    Ok(stroops)
+}
+
+/// A payment of `amount` to `destination`, or with `claimable` a claimable
+/// balance that only `destination` can claim
+pub(super) fn transfer_operation(
+    asset: &Asset,
+    destination: &str,
+    amount: f64,
+    claimable: bool,
+) -> Result<OperationBody, StellarError> {
+    let amount = to_stroops(amount)?;
+    let destination = decode_account_id(destination)?;
+    let asset = asset.to_xdr()?;
+
+    if !claimable {
+        return Ok(OperationBody::Payment(PaymentOp {
+            destination: MuxedAccount::Ed25519(Uint256(destination)),
+            asset,
+            amount,
+        }));
+    }
+
+    let claimant = Claimant::ClaimantTypeV0(ClaimantV0 {
+        destination: AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(destination))),
+        predicate: ClaimPredicate::Unconditional,
+    });
+    Ok(OperationBody::CreateClaimableBalance(CreateClaimableBalanceOp {
+        asset,
+        amount,
+        claimants: vec![claimant]
+            .try_into()
+            .map_err(|_| StellarError::Validation("Too many claimants".to_string()))?,
+    }))
+}
+
+/// Horizon's answer as a `TransactionResult`, with its result codes as the error
+pub(super) fn transaction_result(response: HorizonTransactionResponse) -> TransactionResult {
+    if response.successful == Some(true) {
+        return TransactionResult::succeeded(response.hash);
+    }
+
+    let codes = response.extras.and_then(|e| e.result_codes);
+    let tx_code = codes.as_ref()
+        .and_then(|c| c.transaction.clone())
+        .unwrap_or_else(|| "tx_failed".to_string());
+    let op_codes = codes.and_then(|c| c.operations).unwrap_or_default();
+
+    TransactionResult::err(match op_codes.iter().find(|c| *c != "op_success") {
+        Some(op_code) => format!("{} ({})", tx_code, op_code),
+        None => tx_code,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stellar::test_support::mock_stellar;
+    use gns_crypto_core::GnsIdentity;
+
+    fn issuer() -> String {
+        StellarService::gns_key_to_stellar(&GnsIdentity::generate().public_key_hex()).unwrap()
+    }
+
+    #[test]
+    fn test_asset_code_encoding() {
+        use base64::Engine;
+        use stellar_xdr::curr::{Limits, ReadXdr, TransactionEnvelope};
+
+        let issuer = issuer();
+        let issuer_key = decode_account_id(&issuer).unwrap();
+
+        // Up to four characters: alphanum4, zero-padded
+        for (code, expected) in [("GNS", *b"GNS\0"), ("USDC", *b"USDC")] {
+            match Asset::new(code, &issuer).to_xdr().unwrap() {
+                stellar_xdr::curr::Asset::CreditAlphanum4(a) => {
+                    assert_eq!(a.asset_code.0, expected);
+                    assert_eq!(a.issuer, AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(issuer_key))));
+                }
+                other => panic!("{} should be alphanum4, got {:?}", code, other),
+            }
+        }
+
+        // Five to twelve: alphanum12
+        for (code, expected) in [("GNSPT", *b"GNSPT\0\0\0\0\0\0\0"), ("GNSREWARDS12", *b"GNSREWARDS12")] {
+            match Asset::new(code, &issuer).to_trust_xdr().unwrap() {
+                ChangeTrustAsset::CreditAlphanum12(a) => assert_eq!(a.asset_code.0, expected),
+                other => panic!("{} should be alphanum12, got {:?}", code, other),
+            }
+        }
+
+        for code in ["", "THIRTEENCHARS", "GN$"] {
+            assert!(Asset::new(code, &issuer).to_xdr().is_err(), "{:?} should be rejected", code);
+        }
+        assert!(Asset::new("GNS", "GABC").to_xdr().is_err());
+
+        // The code survives a round trip through a payment transaction
+        let payment = Operation {
+            source_account: None,
+            body: OperationBody::Payment(PaymentOp {
+                destination: MuxedAccount::Ed25519(Uint256([9; 32])),
+                asset: Asset::new("GNSREWARDS", &issuer).to_xdr().unwrap(),
+                amount: 5 * STROOPS_PER_UNIT as i64,
+            }),
+        };
+        let xdr = build_transaction([7; 32], 42, vec![payment]).unwrap();
+        let bytes = base64::engine::general_purpose::STANDARD.decode(xdr).unwrap();
+        let TransactionEnvelope::Tx(envelope) = TransactionEnvelope::from_xdr(bytes, Limits::none()).unwrap() else {
+            panic!("expected a v1 envelope");
+        };
+        let OperationBody::Payment(op) = &envelope.tx.operations[0].body else {
+            panic!("expected a payment");
+        };
+        let stellar_xdr::curr::Asset::CreditAlphanum12(asset) = &op.asset else {
+            panic!("expected alphanum12, got {:?}", op.asset);
+        };
+        assert_eq!(&asset.asset_code.0, b"GNSREWARDS\0\0");
+        assert_eq!(op.amount, 50_000_000);
+    }
+
+    #[test]
+    fn test_to_stroops() {
+        assert_eq!(to_stroops(1.0).unwrap(), 10_000_000);
+        assert_eq!(to_stroops(0.0000001).unwrap(), 1);
+        assert!(to_stroops(0.0).is_err());
+        assert!(to_stroops(-1.0).is_err());
+        assert!(to_stroops(0.00000001).is_err());
+        assert!(to_stroops(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_transfer_operation() {
+        let asset = Asset::new("GNS", &issuer());
+        let recipient = issuer();
+
+        assert!(matches!(
+            transfer_operation(&asset, &recipient, 1.0, false).unwrap(),
+            OperationBody::Payment(PaymentOp { amount: 10_000_000, .. })
+        ));
+
+        let OperationBody::CreateClaimableBalance(op) = transfer_operation(&asset, &recipient, 1.0, true).unwrap() else {
+            panic!("expected a claimable balance");
+        };
+        assert_eq!(op.amount, 10_000_000);
+        let [Claimant::ClaimantTypeV0(claimant)] = op.claimants.as_slice() else {
+            panic!("expected one claimant");
+        };
+        assert_eq!(claimant.predicate, ClaimPredicate::Unconditional);
+        assert_eq!(
+            claimant.destination,
+            AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(decode_account_id(&recipient).unwrap())))
+        );
+
+        assert!(transfer_operation(&asset, "GABC", 1.0, true).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_asset_pays_configured_asset_directly() {
+        let usdc = Asset::new("USDC", &issuer());
+        let recipient = issuer();
+        let line = usdc.clone();
+        let (stellar, requests) = mock_stellar(move |method, path| match (method, path) {
+            ("GET", p) if p.starts_with("/accounts/") => serde_json::json!({
+                "id": "G",
+                "sequence": "100",
+                "balances": [
+                    { "balance": "10.0000000", "asset_type": "native" },
+                    {
+                        "balance": "0.0000000",
+                        "asset_type": "credit_alphanum4",
+                        "asset_code": line.code,
+                        "asset_issuer": line.issuer,
+                    },
+                ],
+            }),
+            ("POST", "/transactions") => serde_json::json!({ "successful": true, "hash": "payhash" }),
+            _ => serde_json::json!({ "success": false, "error": "unexpected request" }),
+        })
+        .await;
+
+        let mut config = stellar.config().clone();
+        config.extra_assets.push(usdc.clone());
+        let stellar = StellarService::new(config);
+        assert_eq!(stellar.config().known_assets().len(), 2);
+
+        let sender = GnsIdentity::generate();
+        let private_key = hex::decode(sender.private_key_hex()).unwrap();
+
+        let result = stellar
+            .send_asset(&usdc.code, &usdc.issuer, &sender.public_key_hex(), &private_key, &recipient, 2.5, false)
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert_eq!(result.hash.as_deref(), Some("payhash"));
+
+        let requests = requests.lock().unwrap().clone();
+        assert!(requests.iter().any(|r| r == "POST /transactions"), "{:?}", requests);
+        assert!(!requests.iter().any(|r| r.contains("/backend")), "{:?}", requests);
+
+        // Unknown assets are refused before anything is sent
+        let other = Asset::new("EURC", &issuer());
+        assert!(stellar
+            .send_asset(&other.code, &other.issuer, &sender.public_key_hex(), &private_key, &recipient, 1.0, false)
+            .await
+            .is_err());
+    }
+}
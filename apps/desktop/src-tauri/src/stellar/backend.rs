@@ -30,6 +30,9 @@ pub struct SendGnsRequest {
     pub amount: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub memo: Option<String>,
+    /// Send as a claimable balance instead of a payment (recipient has no trustline)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claimable_balance: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub signed_xdr: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -40,11 +43,22 @@ pub struct SendGnsRequest {
 pub struct CreateTrustlineRequest {
     pub public_key: String,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub signed_xdr: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub network: Option<String>,
 }
 
+/// Ask again for the unsigned transaction behind `operation` (the
+/// endpoint name, e.g. "claim-gns"), with the original request's fields
+#[derive(Debug, Serialize)]
+pub struct UnsignedXdrRequest<'a, T: Serialize> {
+    pub operation: &'a str,
+    #[serde(flatten)]
+    pub request: &'a T,
+}
+
 #[derive(Debug, Serialize)]
 pub struct SubmitTransactionRequest {
     pub xdr: String,
@@ -68,6 +82,70 @@ pub struct BackendTransactionResponse {
     pub hash: Option<String>,
     pub error: Option<String>,
     pub ledger: Option<u32>,
+    /// Machine-readable status, sent by newer backends alongside `error`
+    #[serde(default)]
+    pub code: Option<String>,
+    /// Transaction to sign; older backends put it in `hash` instead
+    #[serde(default)]
+    pub xdr: Option<String>,
+}
+
+/// Where a backend-built transaction stands in the signing round-trip
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackendSignState {
+    /// Submitted to the network (transaction hash, if returned)
+    Complete(Option<String>),
+    /// The user must sign this XDR and send it back
+    SignRequired(String),
+    /// The backend has signed (e.g. as fee sponsor) and needs the user's signature on this XDR
+    CosignRequired(String),
+    /// The backend asked for a (co-)signature but sent nothing to sign
+    MissingXdr { status: String, cosign: bool },
+    Error(String),
+}
+
+impl From<BackendTransactionResponse> for BackendSignState {
+    fn from(response: BackendTransactionResponse) -> Self {
+        if response.success {
+            return Self::Complete(response.hash);
+        }
+
+        let status = response
+            .code
+            .as_deref()
+            .or(response.error.as_deref())
+            .map(normalize_status)
+            .unwrap_or_default();
+
+        let wants_signature = match status.as_str() {
+            "SIGN_REQUIRED" | "SIGNATURE_REQUIRED" => Some(false),
+            "COSIGN_REQUIRED" | "CO_SIGN_REQUIRED" | "COSIGNATURE_REQUIRED" => Some(true),
+            _ => None,
+        };
+
+        let Some(cosign) = wants_signature else {
+            return Self::Error(response.error.or(response.code).unwrap_or_else(|| "Unknown error".to_string()));
+        };
+
+        let xdr = response.xdr.as_deref().or(response.hash.as_deref()).filter(|x| !x.trim().is_empty());
+        match xdr {
+            Some(xdr) if cosign => Self::CosignRequired(xdr.to_string()),
+            Some(xdr) => Self::SignRequired(xdr.to_string()),
+            None => {
+                tracing::warn!("⚠️ Backend returned {} without a transaction to sign: {:?}", status, response);
+                Self::MissingXdr { status, cosign }
+            }
+        }
+    }
+}
+
+/// `sign-required`, `Sign required` and `SIGN_REQUIRED` all mean the same thing
+fn normalize_status(status: &str) -> String {
+    status
+        .trim()
+        .chars()
+        .map(|c| if c == '-' || c.is_whitespace() { '_' } else { c.to_ascii_uppercase() })
+        .collect()
 }
 
 #[derive(Debug, Deserialize)]
@@ -114,6 +192,12 @@ impl StellarBackendClient {
         }
     }
 
+    /// Send requests through `client`
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
     /// Sign a request body and create signature header value
     fn sign_request_body(body: &impl Serialize, sign_fn: &impl Fn(&str) -> Result<String, String>) -> Result<(String, i64), String> {
         let timestamp = chrono::Utc::now().timestamp_millis();
@@ -161,7 +245,7 @@ impl StellarBackendClient {
         signed_xdr: Option<&str>,
         sign_fn: impl Fn(&str) -> Result<String, String>,
         balance_ids: Option<Vec<String>>,
-    ) -> Result<BackendTransactionResponse, String> {
+    ) -> Result<BackendSignState, String> {
         let request = ClaimGnsRequest {
             public_key: public_key_hex.to_string(),
             balance_ids,
@@ -183,6 +267,7 @@ impl StellarBackendClient {
         
         response.json::<BackendTransactionResponse>()
             .await
+            .map(BackendSignState::from)
             .map_err(|e| format!("Parse error: {}", e))
     }
 
@@ -193,17 +278,19 @@ impl StellarBackendClient {
         recipient_public_key: Option<&str>,
         amount: f64,
         memo: Option<&str>,
+        claimable_balance: Option<bool>,
         public_key_hex: &str,
         network: Option<&str>,
         signed_xdr: Option<&str>,
         sign_fn: impl Fn(&str) -> Result<String, String>,
-    ) -> Result<BackendTransactionResponse, String> {
+    ) -> Result<BackendSignState, String> {
         let request = SendGnsRequest {
             public_key: public_key_hex.to_string(),
             recipient_address: recipient_stellar_address.map(|s| s.to_string()),
             recipient_public_key: recipient_public_key.map(|s| s.to_string()),
             amount: format!("{:.7}", amount),
             memo: memo.map(|s| s.to_string()),
+            claimable_balance,
             signed_xdr: signed_xdr.map(|s| s.to_string()),
             network: network.map(|s| s.to_string()),
         };
@@ -222,19 +309,22 @@ impl StellarBackendClient {
         
         response.json::<BackendTransactionResponse>()
             .await
+            .map(BackendSignState::from)
             .map_err(|e| format!("Parse error: {}", e))
     }
 
-    /// Create GNS trustline via backend
+    /// Create or update the GNS trustline via backend (`limit` of "0" removes it)
     pub async fn create_trustline(
         &self,
         public_key_hex: &str,
+        limit: Option<&str>,
         network: Option<&str>,
         signed_xdr: Option<&str>,
         sign_fn: impl Fn(&str) -> Result<String, String>,
-    ) -> Result<BackendTransactionResponse, String> {
+    ) -> Result<BackendSignState, String> {
         let request = CreateTrustlineRequest {
             public_key: public_key_hex.to_string(),
+            limit: limit.map(|s| s.to_string()),
             signed_xdr: signed_xdr.map(|s| s.to_string()),
             network: network.map(|s| s.to_string()),
         };
@@ -253,9 +343,42 @@ impl StellarBackendClient {
         
         response.json::<BackendTransactionResponse>()
             .await
+            .map(BackendSignState::from)
             .map_err(|e| format!("Parse error: {}", e))
     }
 
+    /// Ask for the unsigned XDR of `operation` explicitly, after a
+    /// `*_REQUIRED` answer that didn't carry one. `None` if the backend
+    /// still doesn't send it.
+    pub async fn get_unsigned_xdr(
+        &self,
+        operation: &str,
+        request: &impl Serialize,
+        sign_fn: impl Fn(&str) -> Result<String, String>,
+    ) -> Result<Option<String>, String> {
+        let request = UnsignedXdrRequest { operation, request };
+        let (signature, timestamp) = Self::sign_request_body(&request, &sign_fn)?;
+
+        let response = self.client
+            .post(&format!("{}/unsigned-xdr", self.base_url))
+            .header("Content-Type", "application/json")
+            .header("X-GNS-Signature", signature)
+            .header("X-GNS-Timestamp", timestamp.to_string())
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| format!("Network error: {}", e))?;
+
+        let response = response.json::<BackendTransactionResponse>()
+            .await
+            .map_err(|e| format!("Parse error: {}", e))?;
+        let xdr = response.xdr.as_deref().or(response.hash.as_deref()).filter(|x| !x.trim().is_empty());
+        if xdr.is_none() {
+            tracing::warn!("⚠️ Backend sent no unsigned XDR for {}: {:?}", operation, response);
+        }
+        Ok(xdr.map(str::to_string))
+    }
+
     /// Fund account via Friendbot (testnet only)
     pub async fn fund_testnet(&self, public_key_hex: &str) -> Result<BackendTransactionResponse, String> {
         let request = FundTestnetRequest {
@@ -305,3 +428,60 @@ pub fn sign_message(identity: &IdentityManager, message: &str) -> Result<String,
     let signature = private_key.sign(message.as_bytes());
     Ok(hex::encode(signature.to_bytes()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(json: &str) -> BackendSignState {
+        serde_json::from_str::<BackendTransactionResponse>(json).unwrap().into()
+    }
+
+    #[test]
+    fn test_backend_sign_states() {
+        assert_eq!(
+            state(r#"{"success":true,"hash":"abc123","ledger":42}"#),
+            BackendSignState::Complete(Some("abc123".to_string()))
+        );
+
+        // Legacy shape: XDR carried in `hash`
+        assert_eq!(
+            state(r#"{"success":false,"error":"SIGN_REQUIRED","hash":"AAAAXDR"}"#),
+            BackendSignState::SignRequired("AAAAXDR".to_string())
+        );
+        assert_eq!(
+            state(r#"{"success":false,"error":"COSIGN_REQUIRED","hash":"AAAAXDR"}"#),
+            BackendSignState::CosignRequired("AAAAXDR".to_string())
+        );
+
+        // Newer shape: explicit code and xdr, free-form error text
+        assert_eq!(
+            state(r#"{"success":false,"code":"cosign-required","error":"Please co-sign","xdr":"BBBBXDR"}"#),
+            BackendSignState::CosignRequired("BBBBXDR".to_string())
+        );
+        assert_eq!(
+            state(r#"{"success":false,"error":"Sign required","xdr":"CCCCXDR","hash":null}"#),
+            BackendSignState::SignRequired("CCCCXDR".to_string())
+        );
+    }
+
+    #[test]
+    fn test_backend_sign_state_errors() {
+        assert_eq!(
+            state(r#"{"success":false,"error":"op_underfunded"}"#),
+            BackendSignState::Error("op_underfunded".to_string())
+        );
+        assert_eq!(
+            state(r#"{"success":false}"#),
+            BackendSignState::Error("Unknown error".to_string())
+        );
+        assert_eq!(
+            state(r#"{"success":false,"error":"SIGN_REQUIRED"}"#),
+            BackendSignState::MissingXdr { status: "SIGN_REQUIRED".to_string(), cosign: false }
+        );
+        assert_eq!(
+            state(r#"{"success":false,"code":"COSIGN_REQUIRED","xdr":" "}"#),
+            BackendSignState::MissingXdr { status: "COSIGN_REQUIRED".to_string(), cosign: true }
+        );
+    }
+}
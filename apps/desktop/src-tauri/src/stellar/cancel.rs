@@ -0,0 +1,137 @@
+//! Operation Cancellation
+//!
+//! Sends and claims can take a while (handle lookups, backend round
+//! trips, waiting for a trustline to confirm), so the UI may cancel them
+//! by operation id. Cancellation is only honoured up to the point of
+//! submission: once a transaction may have reached the network the
+//! operation runs to completion, so a cancelled-but-successful transfer
+//! is never reported as a failure.
+
+use super::StellarError;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+/// Cancellation flag shared between an operation and whoever may cancel it
+#[derive(Clone)]
+pub struct CancelToken(Arc<watch::Sender<bool>>);
+
+impl Default for CancelToken {
+    fn default() -> Self {
+        Self(Arc::new(watch::channel(false).0))
+    }
+}
+
+impl CancelToken {
+    /// A token nobody else holds, for operations that can't be cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark the operation as cancelled. Returns false if it already was.
+    pub fn cancel(&self) -> bool {
+        !self.0.send_replace(true)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// `Err(Cancelled)` once the operation has been cancelled
+    pub fn check(&self) -> Result<(), StellarError> {
+        if self.is_cancelled() {
+            Err(StellarError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Resolves once the operation is cancelled
+    pub async fn cancelled(&self) {
+        let mut rx = self.0.subscribe();
+        // The sender lives in `self`, so this only ends on cancellation
+        let _ = rx.wait_for(|cancelled| *cancelled).await;
+    }
+
+    /// Await `fut` unless the operation is cancelled first. Only for
+    /// steps before submission; dropping a submission halfway would leave
+    /// its outcome unknown.
+    pub async fn guard<T>(&self, fut: impl Future<Output = T>) -> Result<T, StellarError> {
+        self.check()?;
+        tokio::select! {
+            biased;
+            _ = self.cancelled() => Err(StellarError::Cancelled),
+            value = fut => Ok(value),
+        }
+    }
+}
+
+/// In-flight cancellable operations, by the id the UI gave them
+#[derive(Default)]
+pub struct Operations {
+    tokens: Mutex<HashMap<String, CancelToken>>,
+}
+
+impl Operations {
+    /// Track operation `id` until the returned guard is dropped
+    pub fn register(&self, id: &str) -> Result<OperationGuard<'_>, String> {
+        let mut tokens = self.tokens.lock().unwrap();
+        if tokens.contains_key(id) {
+            return Err(format!("Operation {} is already running", id));
+        }
+        let token = CancelToken::new();
+        tokens.insert(id.to_string(), token.clone());
+        Ok(OperationGuard { operations: self, id: id.to_string(), token })
+    }
+
+    /// Track `id` if given; operations without an id can't be cancelled
+    pub fn start(&self, id: Option<&str>) -> Result<(CancelToken, Option<OperationGuard<'_>>), String> {
+        match id {
+            Some(id) => {
+                let guard = self.register(id)?;
+                Ok((guard.token.clone(), Some(guard)))
+            }
+            None => Ok((CancelToken::new(), None)),
+        }
+    }
+
+    /// Cancel operation `id`. Returns false if it isn't running (or
+    /// finished already).
+    pub fn cancel(&self, id: &str) -> bool {
+        self.tokens.lock().unwrap().get(id).is_some_and(|token| token.cancel())
+    }
+}
+
+/// Keeps an operation cancellable; stops tracking it when dropped
+pub struct OperationGuard<'a> {
+    operations: &'a Operations,
+    id: String,
+    token: CancelToken,
+}
+
+impl Drop for OperationGuard<'_> {
+    fn drop(&mut self) {
+        self.operations.tokens.lock().unwrap().remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operations_track_until_dropped() {
+        let operations = Operations::default();
+        let guard = operations.register("send-1").unwrap();
+        assert!(operations.register("send-1").is_err());
+
+        assert!(operations.cancel("send-1"));
+        assert!(!operations.cancel("send-1"));
+        assert!(guard.token.is_cancelled());
+
+        drop(guard);
+        assert!(!operations.cancel("send-1"));
+        assert!(operations.register("send-1").is_ok());
+    }
+}
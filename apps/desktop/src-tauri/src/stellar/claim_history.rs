@@ -0,0 +1,347 @@
+//! Claimable Balance History
+//!
+//! `get_claimable_balances` only sees balances still on the ledger. An
+//! airdrop ledger also needs the ones already claimed or reclaimed, so this
+//! walks the account's `claimable_balance_claimant_created` effects (one
+//! per balance ever offered to it) and works out what became of each:
+//!
+//! - still on the ledger: `pending`, or `expired` once its predicate can
+//!   no longer be met
+//! - gone, last operation a claim by this account: `claimed`
+//! - gone any other way (reclaimed by the sponsor, clawed back, claimed by
+//!   another claimant): `expired`
+
+use super::{split_asset, ClaimableBalance, StellarError, StellarService};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Horizon effect recorded on an account when a balance names it as claimant
+const CLAIMANT_CREATED: &str = "claimable_balance_claimant_created";
+
+/// Horizon's largest page size
+const MAX_PAGE_SIZE: u32 = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClaimStatus {
+    Pending,
+    Claimed,
+    Expired,
+}
+
+/// A claimable balance offered to the account, and what became of it
+#[derive(Debug, Clone, Serialize)]
+pub struct ClaimableBalanceRecord {
+    pub balance_id: String,
+    pub asset_code: String,
+    pub asset_issuer: Option<String>,
+    pub amount: String,
+    pub status: ClaimStatus,
+    pub created_at: String,
+    /// When this account claimed it, for `claimed` balances
+    pub claimed_at: Option<String>,
+}
+
+/// One page of claimable balance history, newest first
+#[derive(Debug, Clone, Serialize)]
+pub struct ClaimableBalanceHistory {
+    pub records: Vec<ClaimableBalanceRecord>,
+    /// Pass back to fetch the next (older) page; `None` once there is no more
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HorizonEffectsResponse {
+    #[serde(rename = "_embedded")]
+    embedded: HorizonEffectsEmbedded,
+}
+
+#[derive(Debug, Deserialize)]
+struct HorizonEffectsEmbedded {
+    records: Vec<HorizonEffect>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HorizonEffect {
+    paging_token: String,
+    #[serde(rename = "type")]
+    effect_type: String,
+    created_at: String,
+    balance_id: Option<String>,
+    asset: Option<String>,
+    amount: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HorizonOperationsResponse {
+    #[serde(rename = "_embedded")]
+    embedded: HorizonOperationsEmbedded,
+}
+
+#[derive(Debug, Deserialize)]
+struct HorizonOperationsEmbedded {
+    records: Vec<HorizonOperation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HorizonOperation {
+    #[serde(rename = "type")]
+    operation_type: String,
+    created_at: String,
+    source_account: String,
+    claimant: Option<String>,
+}
+
+impl StellarService {
+    /// One page of every claimable balance ever offered to `stellar_address`,
+    /// newest first. Start with no cursor and pass back `next_cursor` for
+    /// older pages. An account with no history gets an empty page.
+    pub async fn get_claimable_balance_history(
+        &self,
+        stellar_address: &str,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<ClaimableBalanceHistory, StellarError> {
+        let limit = limit.clamp(1, MAX_PAGE_SIZE);
+        let mut url = format!(
+            "{}/accounts/{}/effects?order=desc&limit={}",
+            self.config.horizon_url, stellar_address, limit
+        );
+        if let Some(cursor) = cursor {
+            url.push_str(&format!("&cursor={}", cursor));
+        }
+
+        let response = self.client.get(&url).send().await
+            .map_err(|e| StellarError::NetworkError(e.to_string()))?;
+
+        // Horizon answers 404 for an account it has never seen
+        if !response.status().is_success() {
+            return Ok(ClaimableBalanceHistory { records: vec![], next_cursor: None });
+        }
+
+        let effects = response.json::<HorizonEffectsResponse>().await
+            .map_err(|e| StellarError::ParseError(e.to_string()))?
+            .embedded
+            .records;
+
+        // A short page is the last one
+        let next_cursor = if effects.len() as u32 == limit {
+            effects.last().map(|e| e.paging_token.clone())
+        } else {
+            None
+        };
+
+        let offered: Vec<HorizonEffect> = effects
+            .into_iter()
+            .filter(|e| e.effect_type == CLAIMANT_CREATED && e.balance_id.is_some())
+            .collect();
+        if offered.is_empty() {
+            return Ok(ClaimableBalanceHistory { records: vec![], next_cursor });
+        }
+
+        let on_ledger: HashMap<String, ClaimableBalance> = self
+            .get_claimable_balances(stellar_address)
+            .await?
+            .into_iter()
+            .map(|b| (b.balance_id.clone(), b))
+            .collect();
+
+        let now = chrono::Utc::now().timestamp();
+        let mut records = Vec::with_capacity(offered.len());
+        for effect in offered {
+            let balance_id = effect.balance_id.unwrap_or_default();
+
+            let (status, claimed_at) = match on_ledger.get(&balance_id) {
+                Some(balance) => {
+                    let status = if balance.is_expired(now) { ClaimStatus::Expired } else { ClaimStatus::Pending };
+                    (status, None)
+                }
+                None => match self.last_balance_operation(&balance_id).await? {
+                    Some(op)
+                        if op.operation_type == "claim_claimable_balance"
+                            && op.claimant.as_deref().unwrap_or(&op.source_account) == stellar_address =>
+                    {
+                        (ClaimStatus::Claimed, Some(op.created_at))
+                    }
+                    _ => (ClaimStatus::Expired, None),
+                },
+            };
+
+            let (asset_code, asset_issuer) = split_asset(effect.asset.as_deref().unwrap_or("native"));
+            records.push(ClaimableBalanceRecord {
+                balance_id,
+                asset_code,
+                asset_issuer,
+                amount: effect.amount.unwrap_or_default(),
+                status,
+                created_at: effect.created_at,
+                claimed_at,
+            });
+        }
+
+        Ok(ClaimableBalanceHistory { records, next_cursor })
+    }
+
+    /// The operation that last touched a claimable balance; for one that
+    /// is gone, the claim or clawback that removed it
+    async fn last_balance_operation(&self, balance_id: &str) -> Result<Option<HorizonOperation>, StellarError> {
+        let url = format!(
+            "{}/claimable_balances/{}/operations?order=desc&limit=1",
+            self.config.horizon_url, balance_id
+        );
+
+        let response = self.client.get(&url).send().await
+            .map_err(|e| StellarError::NetworkError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Ok(None);
+        }
+
+        let data: HorizonOperationsResponse = response.json().await
+            .map_err(|e| StellarError::ParseError(e.to_string()))?;
+
+        Ok(data.embedded.records.into_iter().next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stellar::test_support::mock_stellar;
+    use crate::stellar::StellarConfig;
+
+    const ACCOUNT: &str = "GACCOUNT";
+
+    fn balance_id(n: u32) -> String {
+        format!("00000000{:064x}", n)
+    }
+
+    fn offered(config: &StellarConfig, n: u32) -> serde_json::Value {
+        serde_json::json!({
+            "paging_token": format!("{}-1", n),
+            "type": CLAIMANT_CREATED,
+            "created_at": format!("2025-01-0{}T00:00:00Z", n),
+            "balance_id": balance_id(n),
+            "asset": format!("{}:{}", config.gns_token_code, config.gns_issuer),
+            "amount": "100.0000000",
+            "predicate": { "unconditional": true },
+        })
+    }
+
+    /// Balance `n` as Horizon lists it while still on the ledger
+    fn on_ledger(config: &StellarConfig, n: u32, predicate: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "id": balance_id(n),
+            "asset": format!("{}:{}", config.gns_token_code, config.gns_issuer),
+            "amount": "100.0000000",
+            "sponsor": "GSPONSOR",
+            "claimants": [{ "destination": ACCOUNT, "predicate": predicate }],
+        })
+    }
+
+    fn records(records: Vec<serde_json::Value>) -> serde_json::Value {
+        serde_json::json!({ "_embedded": { "records": records } })
+    }
+
+    /// Balance 1 pending, 2 claimed by us, 3 expired on the ledger,
+    /// 4 reclaimed by the sponsor
+    fn route(method: &str, path: &str) -> serde_json::Value {
+        let config = StellarConfig::testnet();
+        let expired = serde_json::json!({ "abs_before_epoch": "1000" });
+        match (method, path) {
+            ("GET", p) if p.starts_with("/accounts/") => records(vec![
+                offered(&config, 1),
+                serde_json::json!({
+                    "paging_token": "2-2",
+                    "type": "account_credited",
+                    "created_at": "2025-01-02T00:00:00Z",
+                }),
+                offered(&config, 2),
+                offered(&config, 3),
+                offered(&config, 4),
+            ]),
+            ("GET", p) if p.starts_with("/claimable_balances?") => records(vec![
+                on_ledger(&config, 1, serde_json::json!({ "unconditional": true })),
+                on_ledger(&config, 3, expired),
+            ]),
+            ("GET", p) if p.starts_with(&format!("/claimable_balances/{}/", balance_id(2))) => {
+                records(vec![serde_json::json!({
+                    "type": "claim_claimable_balance",
+                    "created_at": "2025-01-05T00:00:00Z",
+                    "source_account": ACCOUNT,
+                    "claimant": ACCOUNT,
+                })])
+            }
+            ("GET", p) if p.starts_with(&format!("/claimable_balances/{}/", balance_id(4))) => {
+                records(vec![serde_json::json!({
+                    "type": "claim_claimable_balance",
+                    "created_at": "2025-02-01T00:00:00Z",
+                    "source_account": "GSPONSOR",
+                    "claimant": "GSPONSOR",
+                })])
+            }
+            _ => records(vec![]),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_history_classifies_each_balance() {
+        let (stellar, _) = mock_stellar(route).await;
+
+        let history = stellar.get_claimable_balance_history(ACCOUNT, None, 5).await.unwrap();
+
+        let statuses: Vec<_> = history.records.iter().map(|r| (r.balance_id.clone(), r.status)).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (balance_id(1), ClaimStatus::Pending),
+                (balance_id(2), ClaimStatus::Claimed),
+                (balance_id(3), ClaimStatus::Expired),
+                (balance_id(4), ClaimStatus::Expired),
+            ]
+        );
+        assert_eq!(history.records[1].claimed_at.as_deref(), Some("2025-01-05T00:00:00Z"));
+        assert!(history.records.iter().all(|r| r.asset_code == "GNS" && r.amount == "100.0000000"));
+
+        // Five effects filled the page, so there may be more
+        assert_eq!(history.next_cursor.as_deref(), Some("4-1"));
+    }
+
+    #[tokio::test]
+    async fn test_balance_with_a_deadline_still_ahead_is_pending() {
+        let now = chrono::Utc::now().timestamp();
+        let until = |t: i64| serde_json::json!({ "abs_before_epoch": t.to_string() });
+        let (stellar, _) = mock_stellar(move |method, path| {
+            let config = StellarConfig::testnet();
+            match (method, path) {
+                ("GET", p) if p.starts_with("/accounts/") => records(vec![offered(&config, 1), offered(&config, 2)]),
+                ("GET", p) if p.starts_with("/claimable_balances?") => records(vec![
+                    // Claimable now, until a day from now
+                    on_ledger(&config, 1, until(now + 86_400)),
+                    // Claimable between one and two days from now
+                    on_ledger(&config, 2, serde_json::json!({ "and": [{ "not": until(now + 86_400) }, until(now + 172_800)] })),
+                ]),
+                _ => records(vec![]),
+            }
+        })
+        .await;
+
+        let history = stellar.get_claimable_balance_history(ACCOUNT, None, 20).await.unwrap();
+
+        let statuses: Vec<_> = history.records.iter().map(|r| r.status).collect();
+        assert_eq!(statuses, vec![ClaimStatus::Pending, ClaimStatus::Pending]);
+    }
+
+    #[tokio::test]
+    async fn test_history_without_activity_is_empty() {
+        let (stellar, requests) = mock_stellar(|_, _| records(vec![])).await;
+
+        let history = stellar.get_claimable_balance_history(ACCOUNT, Some("123"), 20).await.unwrap();
+
+        assert!(history.records.is_empty());
+        assert!(history.next_cursor.is_none());
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains("cursor=123"), "{}", requests[0]);
+    }
+}
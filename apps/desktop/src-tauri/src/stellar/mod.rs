@@ -4,9 +4,23 @@
 //! - Balance queries via Horizon REST API
 //! - Trustline creation
 //! - GNS token transfers
-//! - Claimable balance claims
+//! - Claimable balance claims, and reclaiming the ones it sponsors
+//!
+//! GNS is the default asset; other configured assets go through [`assets`].
 
+pub mod assets;
 pub mod backend;
+pub mod cancel;
+pub mod claim_history;
+pub mod onboarding;
+pub mod outcome;
+pub mod outgoing_claims;
+pub mod reserve;
+pub mod send_failure;
+pub mod send_guard;
+pub mod stellar_toml;
+#[cfg(test)]
+mod test_support;
 
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -14,10 +28,19 @@ use gns_crypto_core::GnsIdentity;
 // Imports moved to inner function scope where needed or removed if unused
 
 
+use send_guard::Destination;
 use std::convert::TryInto; // For array conversion
 use base64::Engine; // Import Engine trait
+use tokio::sync::OnceCell;
 
-pub use backend::StellarBackendClient;
+pub use assets::Asset;
+pub use backend::{BackendSignState, StellarBackendClient};
+pub use cancel::{CancelToken, Operations};
+pub use outcome::{OpResult, TransactionOutcome};
+pub use reserve::ReserveHeadroom;
+pub use send_failure::{Remediation, SendFailure};
+pub use send_guard::SendWarning;
+pub use stellar_toml::AssetVerification;
 
 // ==================== CONFIGURATION ====================
 
@@ -30,6 +53,8 @@ pub struct StellarConfig {
     pub gns_issuer: String,
     pub use_testnet: bool,
     pub backend_url: Option<String>,
+    /// Assets offered alongside GNS, e.g. stablecoins on this network
+    pub extra_assets: Vec<Asset>,
 }
 
 impl Default for StellarConfig {
@@ -47,6 +72,7 @@ impl StellarConfig {
             gns_issuer: "GBVZTFST4PIPV5C3APDIVULNZYZENQSLGDSOKOVQI77GSMT6WVYGF5GL".to_string(),
             use_testnet: false,
             backend_url: Some("https://gns-stellar-backend-production.up.railway.app/stellar".to_string()),
+            extra_assets: Vec::new(),
         }
     }
 
@@ -58,7 +84,73 @@ impl StellarConfig {
             gns_issuer: "GBVZTFST4PIPV5C3APDIVULNZYZENQSLGDSOKOVQI77GSMT6WVYGF5GL".to_string(),
             use_testnet: true,
             backend_url: Some("https://gns-stellar-backend-production.up.railway.app/stellar".to_string()),
+            extra_assets: Vec::new(),
+        }
+    }
+}
+
+/// Which Stellar network the app is talking to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StellarNetwork {
+    Mainnet,
+    Testnet,
+}
+
+impl StellarNetwork {
+    /// Default configuration for this network
+    pub fn config(self) -> StellarConfig {
+        match self {
+            StellarNetwork::Mainnet => StellarConfig::mainnet(),
+            StellarNetwork::Testnet => StellarConfig::testnet(),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StellarNetwork::Mainnet => "mainnet",
+            StellarNetwork::Testnet => "testnet",
+        }
+    }
+
+    /// Parse a network name ("mainnet"/"public" or "testnet")
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "mainnet" | "public" => Some(StellarNetwork::Mainnet),
+            "testnet" => Some(StellarNetwork::Testnet),
+            _ => None,
+        }
+    }
+}
+
+impl StellarConfig {
+    pub fn network(&self) -> StellarNetwork {
+        if self.use_testnet {
+            StellarNetwork::Testnet
+        } else {
+            StellarNetwork::Mainnet
+        }
+    }
+
+    /// The GNS token on this network
+    pub fn gns_asset(&self) -> Asset {
+        Asset::new(&self.gns_token_code, &self.gns_issuer)
+    }
+
+    /// Every asset the wallet works with, GNS first
+    pub fn known_assets(&self) -> Vec<Asset> {
+        let mut assets = vec![self.gns_asset()];
+        for asset in &self.extra_assets {
+            if !assets.contains(asset) {
+                assets.push(asset.clone());
+            }
         }
+        assets
+    }
+
+    /// The known asset with this code and issuer
+    pub fn known_asset(&self, code: &str, issuer: &str) -> Option<Asset> {
+        self.known_assets().into_iter().find(|a| a.matches(code, Some(issuer)))
     }
 }
 
@@ -85,6 +177,62 @@ pub struct ClaimableBalance {
     pub asset_issuer: Option<String>,
     pub amount: String,
     pub sponsor: Option<String>,
+    /// Horizon's claim predicate for the queried account (unconditional if absent)
+    #[serde(default)]
+    pub predicate: Option<serde_json::Value>,
+}
+
+impl ClaimableBalance {
+    /// Why this balance can't be claimed at `now` (unix seconds), if it can't
+    pub fn unclaimable_reason(&self, now: i64) -> Option<&'static str> {
+        let Some(predicate) = &self.predicate else {
+            return None;
+        };
+        if predicate_allows(predicate, now) {
+            None
+        } else if predicate_allows_from(predicate, now) {
+            Some("Not claimable yet")
+        } else {
+            Some("Expired")
+        }
+    }
+
+    /// Whether this balance can no longer be claimed, now or later
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.predicate.as_ref().is_some_and(|p| !predicate_allows_from(p, now))
+    }
+}
+
+/// Outcome of claiming one balance in a sweep
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalanceClaimResult {
+    pub balance_id: String,
+    pub amount: String,
+    pub success: bool,
+    pub hash: Option<String>,
+    /// Why the balance was skipped or its claim failed
+    pub error: Option<String>,
+    /// Horizon's code for the claim operation, when it was submitted
+    #[serde(default)]
+    pub code: Option<String>,
+    /// The claim itself was fine, but another one in its transaction
+    /// failed and took the whole transaction down
+    #[serde(default)]
+    pub rolled_back: bool,
+}
+
+impl BalanceClaimResult {
+    fn failed(balance: &ClaimableBalance, error: String) -> Self {
+        Self {
+            balance_id: balance.balance_id.clone(),
+            amount: balance.amount.clone(),
+            success: false,
+            hash: None,
+            error: Some(error),
+            code: None,
+            rolled_back: false,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -97,21 +245,164 @@ pub struct StellarBalances {
     pub claimable_gns: Vec<ClaimableBalance>,
 }
 
+/// How a GNS transfer reaches the recipient
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GnsDelivery {
+    /// Straight to the recipient's GNS trustline
+    Payment,
+    /// Held on-ledger until the recipient adds a trustline and claims it
+    ClaimableBalance,
+}
+
+/// What sending GNS to an address would look like, checked before sending
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecipientStatus {
+    pub stellar_address: String,
+    pub account_exists: bool,
+    pub has_trustline: bool,
+    /// GNS would arrive as a claimable balance rather than a payment
+    pub will_use_claimable: bool,
+}
+
+/// Network fee for a transaction, shown before the user confirms a send
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeEstimate {
+    pub operation_count: u32,
+    /// Per-operation base fee of the last ledger, or our default offline
+    pub base_fee_stroops: u64,
+    /// What the transaction will be charged: the base fee per operation
+    pub fee_stroops: u64,
+    pub fee_xlm: String,
+    /// Median fee recently charged for as many operations
+    pub recommended_fee_stroops: u64,
+    pub recommended_fee_xlm: String,
+    /// 90th percentile fee recently charged, what busy periods cost
+    pub congested_fee_stroops: u64,
+    pub congested_fee_xlm: String,
+    /// False when Horizon's fee stats were unavailable and defaults were used
+    pub from_network: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionResult {
     pub success: bool,
     pub hash: Option<String>,
     pub error: Option<String>,
+    /// Set when the destination looked wrong; see [`SendWarning`]
+    #[serde(default)]
+    pub warning: Option<SendWarning>,
+    /// Set when the error names a failure with a next step; see
+    /// [`SendFailure`]
+    #[serde(default)]
+    pub failure: Option<SendFailure>,
+    #[serde(default)]
+    pub remediation: Option<Remediation>,
 }
 
 impl TransactionResult {
     pub fn ok(hash: String) -> Self {
-        Self { success: true, hash: Some(hash), error: None }
+        Self::succeeded(Some(hash))
+    }
+
+    /// Submitted, with the hash if we were given one
+    pub fn succeeded(hash: Option<String>) -> Self {
+        Self { success: true, hash, error: None, warning: None, failure: None, remediation: None }
     }
 
     pub fn err(error: String) -> Self {
-        Self { success: false, hash: None, error: Some(error) }
+        let failure = SendFailure::from_error(&error);
+        Self {
+            success: false,
+            hash: None,
+            error: Some(error),
+            warning: None,
+            failure,
+            remediation: failure.map(SendFailure::remediation),
+        }
+    }
+
+    /// Not sent until the user confirms past `warning`
+    pub fn blocked(warning: SendWarning) -> Self {
+        Self {
+            success: false,
+            hash: None,
+            error: Some(warning.message()),
+            warning: Some(warning),
+            failure: None,
+            remediation: None,
+        }
+    }
+}
+
+/// The GNS asset as issued on the active network
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GnsAssetInfo {
+    pub code: String,
+    pub issuer: String,
+    pub network: StellarNetwork,
+    pub home_domain: Option<String>,
+    /// Holders need the issuer's approval before they can hold GNS
+    pub auth_required: bool,
+    /// The issuer can freeze existing trustlines
+    pub auth_revocable: bool,
+    /// The flags above are locked and can never change
+    pub auth_immutable: bool,
+    /// The issuer can claw GNS back from holders
+    pub clawback_enabled: bool,
+}
+
+/// Signed statement that a GNS identity controls its derived Stellar address
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StellarControlProof {
+    pub public_key: String,
+    pub stellar_address: String,
+    /// Hex Ed25519 signature over `STELLAR_CONTROL_DOMAIN` + challenge
+    pub signature: String,
+}
+
+/// Prefix signed ahead of the challenge, so a proof can't be passed off as
+/// a signature over anything else the identity key signs
+pub const STELLAR_CONTROL_DOMAIN: &str = "GNS Stellar control proof:\n";
+
+impl StellarControlProof {
+    /// Sign a caller-supplied challenge with the identity key
+    pub fn create(identity: &GnsIdentity, challenge: &str) -> Result<Self, StellarError> {
+        if challenge.is_empty() {
+            return Err(StellarError::Validation("Challenge must not be empty".to_string()));
+        }
+
+        let public_key = identity.public_key_hex();
+        let message = format!("{}{}", STELLAR_CONTROL_DOMAIN, challenge);
+        Ok(Self {
+            stellar_address: StellarService::gns_key_to_stellar(&public_key)?,
+            public_key,
+            signature: hex::encode(identity.sign_bytes(message.as_bytes())),
+        })
+    }
+}
+
+/// Check a control proof and return the Stellar address `public_key` controls.
+///
+/// Needs no network access or local identity, so anyone holding the proof
+/// can run it.
+pub fn verify_stellar_control(
+    public_key: &str,
+    challenge: &str,
+    signature: &str,
+) -> Result<String, StellarError> {
+    let stellar_address = StellarService::gns_key_to_stellar(public_key)?;
+
+    let message = format!("{}{}", STELLAR_CONTROL_DOMAIN, challenge);
+    let valid = gns_crypto_core::signing::verify_signature_hex(public_key, message.as_bytes(), signature)
+        .map_err(|e| StellarError::Validation(format!("Malformed proof: {}", e)))?;
+
+    if !valid {
+        return Err(StellarError::Validation(
+            "Signature does not match this key and challenge".to_string(),
+        ));
     }
+    Ok(stellar_address)
 }
 
 // ==================== HORIZON API RESPONSES ====================
@@ -121,12 +412,60 @@ struct HorizonAccount {
     #[allow(dead_code)]
     id: String,
     sequence: String,
+    /// Trustlines, offers, signers and data entries the account owns
+    #[serde(default)]
+    subentry_count: u32,
+    /// Entries this account pays the reserve for on behalf of others
+    #[serde(default)]
+    num_sponsoring: u32,
+    /// Own entries whose reserve another account pays
+    #[serde(default)]
+    num_sponsored: u32,
     balances: Vec<HorizonBalance>,
 }
 
+#[derive(Debug, Deserialize)]
+struct HorizonIssuerAccount {
+    #[serde(default)]
+    home_domain: Option<String>,
+    #[serde(default)]
+    flags: HorizonAccountFlags,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct HorizonAccountFlags {
+    #[serde(default)]
+    auth_required: bool,
+    #[serde(default)]
+    auth_revocable: bool,
+    #[serde(default)]
+    auth_immutable: bool,
+    #[serde(default)]
+    auth_clawback_enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct HorizonAssetsResponse {
+    #[serde(rename = "_embedded")]
+    embedded: HorizonAssetsEmbedded,
+}
+
+#[derive(Debug, Deserialize)]
+struct HorizonAssetsEmbedded {
+    records: Vec<HorizonAssetRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HorizonAssetRecord {
+    asset_code: String,
+    asset_issuer: String,
+}
+
 #[derive(Debug, Deserialize)]
 struct HorizonBalance {
     balance: String,
+    #[serde(default)]
+    selling_liabilities: Option<String>,
     asset_type: String,
     asset_code: Option<String>,
     asset_issuer: Option<String>,
@@ -149,6 +488,14 @@ struct HorizonClaimableBalance {
     asset: String,
     amount: String,
     sponsor: Option<String>,
+    #[serde(default)]
+    claimants: Vec<HorizonClaimant>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HorizonClaimant {
+    destination: String,
+    predicate: serde_json::Value,
 }
 
 #[derive(Debug, Deserialize)]
@@ -166,7 +513,6 @@ struct HorizonExtras {
 
 #[derive(Debug, Deserialize)]
 struct HorizonResultCodes {
-    #[allow(dead_code)]
     transaction: Option<String>,
     operations: Option<Vec<String>>,
 }
@@ -197,12 +543,48 @@ struct HorizonPayment {
     asset_type: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+struct HorizonTransactionOperations {
+    #[serde(rename = "_embedded")]
+    embedded: HorizonTransactionOperationsEmbedded,
+}
+
+#[derive(Debug, Deserialize)]
+struct HorizonTransactionOperationsEmbedded {
+    records: Vec<HorizonTransactionOperation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HorizonTransactionOperation {
+    #[serde(rename = "type")]
+    operation_type: String,
+}
+
+/// The parts of Horizon's `/fee_stats` the fee estimate uses
+#[derive(Debug, Clone, Deserialize)]
+struct HorizonFeeStats {
+    last_ledger_base_fee: String,
+    fee_charged: HorizonFeePercentiles,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct HorizonFeePercentiles {
+    p50: String,
+    p90: String,
+}
+
 // ==================== STELLAR SERVICE ====================
 
 pub struct StellarService {
     config: StellarConfig,
     client: Client,
     backend: StellarBackendClient,
+    /// Filled on first successful lookup; a new service (network switch) starts empty
+    asset_info: OnceCell<GnsAssetInfo>,
+    /// Set once the issuer's stellar.toml has verified the asset
+    asset_toml: OnceCell<AssetVerification>,
+    /// Last fee stats and when they were fetched
+    fee_stats: std::sync::Mutex<Option<(std::time::Instant, HorizonFeeStats)>>,
 }
 
 impl StellarService {
@@ -211,9 +593,20 @@ impl StellarService {
             client: Client::new(),
             backend: StellarBackendClient::new(config.backend_url.as_deref()),
             config,
+            asset_info: OnceCell::new(),
+            asset_toml: OnceCell::new(),
+            fee_stats: std::sync::Mutex::new(None),
         }
     }
 
+    /// Send Horizon and backend requests through `client`, e.g. one with
+    /// the app's request timeout
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.backend = self.backend.with_client(client.clone());
+        self.client = client;
+        self
+    }
+
     pub fn mainnet() -> Self {
         Self::new(StellarConfig::mainnet())
     }
@@ -256,6 +649,144 @@ impl StellarService {
         Ok(base32_encode(&payload))
     }
 
+    /// Decode a Stellar S... secret key into its 32-byte Ed25519 seed,
+    /// which is also a GNS private key
+    pub fn stellar_secret_to_seed(secret: &str) -> Result<[u8; 32], StellarError> {
+        let invalid = |reason: String| StellarError::Validation(format!("Invalid Stellar secret key: {}", reason));
+
+        if !secret.starts_with('S') {
+            return Err(invalid("it should start with 'S'".to_string()));
+        }
+        if secret.len() != 56 {
+            return Err(invalid(format!("expected 56 characters, got {}", secret.len())));
+        }
+        let payload = base32_decode(secret)
+            .ok_or_else(|| invalid("only the characters A-Z and 2-7 are allowed".to_string()))?;
+        if payload[0] != SECRET_SEED_VERSION {
+            return Err(invalid("not a secret seed".to_string()));
+        }
+        let checksum = crc16_xmodem(&payload[..33]);
+        if payload[33] != (checksum & 0xFF) as u8 || payload[34] != (checksum >> 8) as u8 {
+            return Err(invalid("checksum mismatch, check it for typos".to_string()));
+        }
+
+        Ok(payload[1..33].try_into().expect("32-byte slice"))
+    }
+
+    // ==================== GNS ASSET ====================
+
+    /// Issuer and flags of the GNS asset on the active network.
+    ///
+    /// Looked up once and cached for the lifetime of this service. Errors
+    /// aren't cached, so a failed check (e.g. offline at startup) is retried
+    /// on the next call.
+    pub async fn gns_asset_info(&self) -> Result<GnsAssetInfo, StellarError> {
+        self.asset_info
+            .get_or_try_init(|| self.fetch_gns_asset_info())
+            .await
+            .cloned()
+    }
+
+    async fn fetch_gns_asset_info(&self) -> Result<GnsAssetInfo, StellarError> {
+        let issuer = &self.config.gns_issuer;
+        let code = &self.config.gns_token_code;
+        let network = self.config.network();
+
+        decode_account_id(issuer).map_err(|e| {
+            StellarError::InvalidIssuer(format!("{} is not a valid Stellar account ({})", issuer, e))
+        })?;
+
+        let url = format!("{}/accounts/{}", self.config.horizon_url, issuer);
+        let response = self.client.get(&url).send().await
+            .map_err(|e| StellarError::NetworkError(e.to_string()))?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(StellarError::InvalidIssuer(format!(
+                "{} does not exist on {} - is it configured for a different network?",
+                issuer,
+                network.as_str()
+            )));
+        }
+        if !response.status().is_success() {
+            return Err(StellarError::NetworkError(format!(
+                "Horizon returned {} for the GNS issuer",
+                response.status()
+            )));
+        }
+
+        let account: HorizonIssuerAccount = response.json().await
+            .map_err(|e| StellarError::ParseError(e.to_string()))?;
+
+        let url = format!(
+            "{}/assets?asset_code={}&asset_issuer={}",
+            self.config.horizon_url, code, issuer
+        );
+        let assets: HorizonAssetsResponse = self.client.get(&url).send().await
+            .map_err(|e| StellarError::NetworkError(e.to_string()))?
+            .json().await
+            .map_err(|e| StellarError::ParseError(e.to_string()))?;
+
+        let issued = assets.embedded.records.iter()
+            .any(|a| &a.asset_code == code && &a.asset_issuer == issuer);
+        if !issued {
+            return Err(StellarError::InvalidIssuer(format!(
+                "{} exists on {} but has not issued {}",
+                issuer,
+                network.as_str(),
+                code
+            )));
+        }
+
+        Ok(asset_info_from_account(code, issuer, network, account))
+    }
+
+    // ==================== FEES ====================
+
+    /// Estimate the network fee of a transaction with `operation_count`
+    /// operations. Falls back to the default base fee when Horizon's fee
+    /// stats can't be fetched, so confirming a send never waits on them.
+    pub async fn estimate_fee(&self, operation_count: u32) -> Result<FeeEstimate, StellarError> {
+        if operation_count == 0 || operation_count as usize > MAX_OPERATIONS_PER_TX {
+            return Err(StellarError::Validation(format!(
+                "A transaction has 1 to {} operations, not {}",
+                MAX_OPERATIONS_PER_TX, operation_count
+            )));
+        }
+
+        let stats = match self.get_fee_stats().await {
+            Ok(stats) => Some(stats),
+            Err(e) => {
+                tracing::warn!("⚠️ Fee stats unavailable, using the default base fee: {}", e);
+                None
+            }
+        };
+        Ok(fee_estimate(operation_count, stats.as_ref()))
+    }
+
+    /// Horizon's fee stats, cached for `FEE_STATS_TTL`
+    async fn get_fee_stats(&self) -> Result<HorizonFeeStats, StellarError> {
+        if let Some((fetched_at, stats)) = self.fee_stats.lock().unwrap().as_ref() {
+            if fetched_at.elapsed() < FEE_STATS_TTL {
+                return Ok(stats.clone());
+            }
+        }
+
+        let url = format!("{}/fee_stats", self.config.horizon_url);
+        let response = self.client.get(&url).send().await
+            .map_err(|e| StellarError::NetworkError(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(StellarError::NetworkError(format!(
+                "Horizon returned {} for fee stats",
+                response.status()
+            )));
+        }
+
+        let stats: HorizonFeeStats = response.json().await
+            .map_err(|e| StellarError::ParseError(e.to_string()))?;
+        *self.fee_stats.lock().unwrap() = Some((std::time::Instant::now(), stats.clone()));
+        Ok(stats)
+    }
+
     // ==================== ACCOUNT OPERATIONS ====================
 
     /// Check if Stellar account exists
@@ -298,35 +829,41 @@ impl StellarService {
         }).collect())
     }
 
+    /// Get the balance of one asset (0 without a trustline)
+    pub async fn get_asset_balance(&self, stellar_address: &str, asset: &Asset) -> Result<f64, StellarError> {
+        let balances = self.get_balances(stellar_address).await?;
+
+        Ok(balances.iter()
+            .find(|b| asset.matches(&b.asset_code, b.asset_issuer.as_deref()))
+            .map(|b| b.amount())
+            .unwrap_or(0.0))
+    }
+
     /// Get GNS token balance specifically
     pub async fn get_gns_balance(&self, stellar_address: &str) -> Result<f64, StellarError> {
-        let balances = self.get_balances(stellar_address).await?;
+        self.get_asset_balance(stellar_address, &self.config.gns_asset()).await
+    }
 
-        for balance in balances {
-            if balance.asset_code == self.config.gns_token_code
-                && balance.asset_issuer.as_deref() == Some(&self.config.gns_issuer)
-            {
-                return Ok(balance.amount());
-            }
-        }
+    /// Check if account has a trustline for `asset`
+    pub async fn has_trustline(&self, stellar_address: &str, asset: &Asset) -> Result<bool, StellarError> {
+        let balances = self.get_balances(stellar_address).await?;
 
-        Ok(0.0)
+        Ok(balances.iter().any(|b| asset.matches(&b.asset_code, b.asset_issuer.as_deref())))
     }
 
     /// Check if account has GNS trustline
     pub async fn has_gns_trustline(&self, stellar_address: &str) -> Result<bool, StellarError> {
-        let balances = self.get_balances(stellar_address).await?;
-
-        Ok(balances.iter().any(|b| {
-            b.asset_code == self.config.gns_token_code
-                && b.asset_issuer.as_deref() == Some(&self.config.gns_issuer)
-        }))
+        self.has_trustline(stellar_address, &self.config.gns_asset()).await
     }
 
     /// Get comprehensive balance info
     pub async fn get_stellar_balances(&self, gns_hex_public_key: &str) -> Result<StellarBalances, StellarError> {
         let stellar_address = Self::gns_key_to_stellar(gns_hex_public_key)?;
+        self.get_stellar_balances_at(stellar_address).await
+    }
 
+    /// Comprehensive balance info for an already-derived Stellar address
+    pub async fn get_stellar_balances_at(&self, stellar_address: String) -> Result<StellarBalances, StellarError> {
         let account_exists = self.account_exists(&stellar_address).await;
 
         let (xlm_balance, gns_balance, has_trustline) = if account_exists {
@@ -337,16 +874,12 @@ impl StellarService {
                 .map(|b| b.amount())
                 .unwrap_or(0.0);
 
-            let gns = balances.iter()
-                .find(|b| b.asset_code == self.config.gns_token_code
-                    && b.asset_issuer.as_deref() == Some(&self.config.gns_issuer))
-                .map(|b| b.amount())
-                .unwrap_or(0.0);
+            let gns_asset = self.config.gns_asset();
+            let gns_line = balances.iter()
+                .find(|b| gns_asset.matches(&b.asset_code, b.asset_issuer.as_deref()));
 
-            let has_trustline = balances.iter().any(|b|
-                b.asset_code == self.config.gns_token_code
-                    && b.asset_issuer.as_deref() == Some(&self.config.gns_issuer)
-            );
+            let gns = gns_line.map(|b| b.amount()).unwrap_or(0.0);
+            let has_trustline = gns_line.is_some();
 
             (xlm, gns, has_trustline)
         } else {
@@ -388,17 +921,11 @@ impl StellarService {
             .map_err(|e| StellarError::ParseError(e.to_string()))?;
 
         Ok(data.embedded.records.into_iter().map(|r| {
-            // Parse asset string (e.g., "GNS:GBVZ..." or "native")
-            let (asset_code, asset_issuer) = if r.asset == "native" {
-                ("XLM".to_string(), None)
-            } else {
-                let parts: Vec<&str> = r.asset.split(':').collect();
-                if parts.len() == 2 {
-                    (parts[0].to_string(), Some(parts[1].to_string()))
-                } else {
-                    (r.asset.clone(), None)
-                }
-            };
+            let (asset_code, asset_issuer) = split_asset(&r.asset);
+
+            let predicate = r.claimants.into_iter()
+                .find(|c| c.destination == stellar_address)
+                .map(|c| c.predicate);
 
             ClaimableBalance {
                 balance_id: r.id,
@@ -406,18 +933,161 @@ impl StellarService {
                 asset_issuer,
                 amount: r.amount,
                 sponsor: r.sponsor,
+                predicate,
             }
         }).collect())
     }
 
+    /// Get claimable balances of one asset
+    pub async fn get_asset_claimable_balances(
+        &self,
+        stellar_address: &str,
+        asset: &Asset,
+    ) -> Result<Vec<ClaimableBalance>, StellarError> {
+        let all = self.get_claimable_balances(stellar_address).await?;
+
+        Ok(all.into_iter()
+            .filter(|cb| asset.matches(&cb.asset_code, cb.asset_issuer.as_deref()))
+            .collect())
+    }
+
     /// Get GNS claimable balances specifically
     pub async fn get_gns_claimable_balances(&self, stellar_address: &str) -> Result<Vec<ClaimableBalance>, StellarError> {
-        let all = self.get_claimable_balances(stellar_address).await?;
+        self.get_asset_claimable_balances(stellar_address, &self.config.gns_asset()).await
+    }
 
-        Ok(all.into_iter().filter(|cb| {
-            cb.asset_code == self.config.gns_token_code
-                && cb.asset_issuer.as_deref() == Some(&self.config.gns_issuer)
-        }).collect())
+    /// Claim every outstanding GNS claimable balance without the backend.
+    ///
+    /// Adds the GNS trustline first if it's missing, then submits the claims
+    /// directly to Horizon, `MAX_OPERATIONS_PER_TX` per transaction. Balances
+    /// that are expired or not yet claimable are skipped and reported. When
+    /// a transaction fails, each balance in it says whether its own claim
+    /// failed or it was rolled back with the rest. Once `cancel` fires no
+    /// further transaction is submitted; balances left unclaimed after
+    /// the first batch are reported as cancelled.
+    pub async fn claim_all_balances(
+        &self,
+        public_key_hex: &str,
+        private_key_bytes: &[u8],
+        cancel: &CancelToken,
+    ) -> Result<Vec<BalanceClaimResult>, StellarError> {
+        let stellar_address = Self::gns_key_to_stellar(public_key_hex)?;
+        let balances = cancel.guard(self.get_gns_claimable_balances(&stellar_address)).await??;
+        if balances.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let (batches, mut results) = plan_claim_batches(&balances, chrono::Utc::now().timestamp());
+        if batches.is_empty() {
+            return Ok(results);
+        }
+
+        if !cancel.guard(self.has_gns_trustline(&stellar_address)).await?? {
+            let trust = self.create_gns_trustline(public_key_hex, private_key_bytes, None, cancel).await?;
+            if !trust.success {
+                return Err(StellarError::Validation(format!(
+                    "Could not add GNS trustline: {}",
+                    trust.error.unwrap_or_else(|| "Unknown error".to_string())
+                )));
+            }
+        }
+
+        let source = GnsIdentity::from_hex(&hex::encode(private_key_bytes))
+            .map_err(|_| StellarError::Validation("Invalid identity".to_string()))?
+            .public_key_bytes();
+
+        let mut submitted = false;
+        for batch in batches {
+            if cancel.is_cancelled() {
+                if !submitted {
+                    return Err(StellarError::Cancelled);
+                }
+                results.extend(batch.iter().map(|b| BalanceClaimResult::failed(b, StellarError::Cancelled.to_string())));
+                continue;
+            }
+            submitted = true;
+
+            let ids: Vec<&str> = batch.iter().map(|b| b.balance_id.as_str()).collect();
+            let outcome = self.submit_claim_batch(&stellar_address, source, &ids, private_key_bytes).await;
+
+            match outcome.map(|response| TransactionOutcome::from_horizon(response, batch.len())) {
+                Ok(outcome) => {
+                    if outcome.successful {
+                        tracing::info!("🪙 Claimed {} GNS balance(s)", batch.len());
+                    } else {
+                        tracing::warn!(
+                            "⚠️ Claim batch of {} failed: {}",
+                            batch.len(),
+                            outcome.code.as_deref().unwrap_or_default()
+                        );
+                    }
+
+                    let batch_error = outcome.error();
+                    results.extend(batch.iter().zip(&outcome.operations).map(|(b, op)| {
+                        let rolled_back = outcome.rolled_back && op.succeeded;
+                        BalanceClaimResult {
+                            balance_id: b.balance_id.clone(),
+                            amount: b.amount.clone(),
+                            success: outcome.successful,
+                            hash: outcome.hash.clone().filter(|_| outcome.successful),
+                            error: match (outcome.successful, rolled_back) {
+                                (true, _) => None,
+                                (false, true) => batch_error.as_ref().map(|e| format!("Rolled back: {}", e)),
+                                (false, false) => Some(op.reason.clone()),
+                            },
+                            code: Some(op.code.clone()),
+                            rolled_back,
+                        }
+                    }));
+                }
+                Err(e) => {
+                    tracing::warn!("⚠️ Claim batch of {} failed: {}", batch.len(), e);
+                    results.extend(batch.iter().map(|b| BalanceClaimResult::failed(b, e.to_string())));
+                }
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Build, sign and submit one batch of claims
+    async fn submit_claim_batch(
+        &self,
+        stellar_address: &str,
+        source: [u8; 32],
+        balance_ids: &[&str],
+        private_key_bytes: &[u8],
+    ) -> Result<HorizonTransactionResponse, StellarError> {
+        // Re-read the sequence each time: a rejected batch may or may not have used one
+        let sequence = self.next_sequence(stellar_address).await?;
+
+        let xdr = build_claim_transaction(source, sequence, balance_ids)?;
+        let signed = self.sign_transaction(&xdr, private_key_bytes)?;
+        self.submit_transaction(&signed).await
+    }
+
+    /// Sequence number for the account's next transaction
+    async fn next_sequence(&self, stellar_address: &str) -> Result<i64, StellarError> {
+        let sequence: i64 = self.get_account(stellar_address).await?
+            .sequence
+            .parse()
+            .map_err(|e| StellarError::ParseError(format!("Invalid sequence number: {}", e)))?;
+        Ok(sequence + 1)
+    }
+
+    /// Submit a signed transaction envelope to Horizon
+    async fn submit_transaction(&self, signed_xdr: &str) -> Result<HorizonTransactionResponse, StellarError> {
+        let url = format!("{}/transactions", self.config.horizon_url);
+
+        let response = self.client.post(&url)
+            .form(&[("tx", signed_xdr)])
+            .send()
+            .await
+            .map_err(|e| StellarError::NetworkError(e.to_string()))?;
+
+        // Horizon answers 400 with result codes for failed transactions
+        response.json().await
+            .map_err(|e| StellarError::ParseError(e.to_string()))
     }
 
     // ==================== PAYMENT HISTORY ====================
@@ -502,58 +1172,118 @@ impl StellarService {
     // ==================== TRANSACTION OPERATIONS ====================
     // Note: These require XDR building. For MVP, recommend using backend-assisted signing.
 
-    /// Create GNS trustline via backend
+    /// Create GNS trustline via backend, optionally capped at `limit`.
+    ///
+    /// Without a limit the trustline is unlimited. A limit must be positive
+    /// and at least the current GNS balance. `cancel` stops it until the
+    /// change is submitted.
     pub async fn create_gns_trustline(
         &self,
         public_key_hex: &str,
         private_key_bytes: &[u8],
+        limit: Option<&str>,
+        cancel: &CancelToken,
     ) -> Result<TransactionResult, StellarError> {
-        let private_key_hex = hex::encode(private_key_bytes);
-        
-        // Reconstruct identity for signing (since we have the seed/bytes)
-        let identity = GnsIdentity::from_hex(&private_key_hex)
-            .map_err(|e| StellarError::InvalidKeyLength(e.to_string().len()))?; // Rough mapping
-            // Note: Ideally we'd map to a generic "KeyError", but using what we have.
-
-        let sign_fn = |msg: &str| {
-            let signature = identity.sign(msg.as_bytes());
-            Ok(hex::encode(signature.to_bytes()))
-        };
+        self.create_trustline(public_key_hex, private_key_bytes, &self.config.gns_asset(), limit, cancel)
+            .await
+    }
 
-        let network = if self.config.use_testnet { Some("testnet") } else { None };
+    /// Create a trustline for `asset`, with the same limit rules as
+    /// [`Self::create_gns_trustline`]. GNS goes through the backend; other
+    /// assets are submitted straight to Horizon.
+    pub async fn create_trustline(
+        &self,
+        public_key_hex: &str,
+        private_key_bytes: &[u8],
+        asset: &Asset,
+        limit: Option<&str>,
+        cancel: &CancelToken,
+    ) -> Result<TransactionResult, StellarError> {
+        if let Some(limit) = limit {
+            let value = parse_trust_limit(limit)?;
+            if value <= 0.0 {
+                return Err(StellarError::Validation(
+                    "Trustline limit must be greater than zero".to_string(),
+                ));
+            }
 
-        let initial_response = self.backend.create_trustline(public_key_hex, network, None, sign_fn).await;
-
-        match initial_response {
-            Ok(response) => {
-                if response.success {
-                    Ok(TransactionResult { success: true, hash: response.hash, error: None })
-                } else if response.error.as_deref() == Some("SIGN_REQUIRED") {
-                     // Get XDR, sign it, and resubmit
-                     if let Some(xdr) = response.hash {
-                        let signed_xdr = self.sign_transaction(&xdr, private_key_bytes)?;
-                        
-                        // Re-create sign_fn because it's consumed or we need a fresh one? 
-                        // Actually Fn is OK.
-                        let sign_fn_2 = |msg: &str| {
-                            let signature = identity.sign(msg.as_bytes());
-                            Ok(hex::encode(signature.to_bytes()))
-                        };
-
-                        let final_res = self.backend.create_trustline(public_key_hex, network, Some(&signed_xdr), sign_fn_2).await;
-                        match final_res {
-                            Ok(r) => Ok(TransactionResult { success: r.success, hash: r.hash, error: r.error }),
-                            Err(e) => Ok(TransactionResult { success: false, hash: None, error: Some(e) }),
-                        }
-                     } else {
-                        Ok(TransactionResult { success: false, hash: None, error: Some("SIGN_REQUIRED but no XDR returned".to_string()) })
-                     }
-                } else {
-                    Ok(TransactionResult { success: false, hash: response.hash, error: response.error })
+            let stellar_address = Self::gns_key_to_stellar(public_key_hex)?;
+            if cancel.guard(self.account_exists(&stellar_address)).await? {
+                let balance = cancel.guard(self.get_asset_balance(&stellar_address, asset)).await??;
+                if balance > value {
+                    return Err(StellarError::Validation(format!(
+                        "Trustline limit {} is below the current balance of {} {}",
+                        limit, balance, asset.code
+                    )));
                 }
-            },
-            Err(e) => Ok(TransactionResult { success: false, hash: None, error: Some(e) }),
+            }
         }
+
+        if *asset == self.config.gns_asset() {
+            self.change_gns_trust(public_key_hex, private_key_bytes, limit, cancel).await
+        } else {
+            cancel.check()?;
+            self.submit_change_trust(public_key_hex, private_key_bytes, asset, limit).await
+        }
+    }
+
+    /// Remove the GNS trustline (limit 0). Only allowed once the GNS balance
+    /// is zero. `cancel` stops it until the removal is submitted.
+    pub async fn remove_gns_trustline(
+        &self,
+        public_key_hex: &str,
+        private_key_bytes: &[u8],
+        cancel: &CancelToken,
+    ) -> Result<TransactionResult, StellarError> {
+        let stellar_address = Self::gns_key_to_stellar(public_key_hex)?;
+
+        if !cancel.guard(self.has_gns_trustline(&stellar_address)).await?? {
+            return Ok(TransactionResult::succeeded(None));
+        }
+
+        let balance = cancel.guard(self.get_gns_balance(&stellar_address)).await??;
+        if balance > 0.0 {
+            return Err(StellarError::Validation(format!(
+                "Cannot remove trustline while holding {} GNS - send or burn the balance first",
+                balance
+            )));
+        }
+
+        self.change_gns_trust(public_key_hex, private_key_bytes, Some("0"), cancel).await
+    }
+
+    /// Submit a ChangeTrust for GNS through the backend, signing the returned XDR if asked to
+    async fn change_gns_trust(
+        &self,
+        public_key_hex: &str,
+        private_key_bytes: &[u8],
+        limit: Option<&str>,
+        cancel: &CancelToken,
+    ) -> Result<TransactionResult, StellarError> {
+        let private_key_hex = hex::encode(private_key_bytes);
+        
+        // Reconstruct identity for signing (since we have the seed/bytes)
+        let identity = GnsIdentity::from_hex(&private_key_hex)
+            .map_err(|e| StellarError::InvalidKeyLength(e.to_string().len()))?; // Rough mapping
+            // Note: Ideally we'd map to a generic "KeyError", but using what we have.
+
+        let sign_fn = |msg: &str| {
+            let signature = identity.sign(msg.as_bytes());
+            Ok(hex::encode(signature.to_bytes()))
+        };
+
+        let network = if self.config.use_testnet { Some("testnet") } else { None };
+
+        // The backend may submit a sponsored change straight away
+        cancel.check()?;
+        let first = self.backend.create_trustline(public_key_hex, limit, network, None, sign_fn).await;
+
+        self.finish_backend_transaction(first, private_key_bytes, cancel, |signed_xdr| async move {
+            self.backend
+                .create_trustline(public_key_hex, limit, network, Some(&signed_xdr), sign_fn)
+                .await
+        })
+        .await
     }
 
     /// Claim a claimable balance (placeholder - needs XDR implementation or backend)
@@ -570,6 +1300,7 @@ impl StellarService {
     }
 
     /// Send GNS tokens via backend
+    #[allow(clippy::too_many_arguments)]
     pub async fn send_gns(
         &self,
         sender_public_key: &str,
@@ -581,6 +1312,149 @@ impl StellarService {
         // wait, backend.send_gns has recipient_stellar_address OR recipient_public_key.
         recipient_input: &str, // This could be address or public key
         amount: f64,
+        confirmed: bool,
+        cancel: &CancelToken,
+    ) -> Result<TransactionResult, StellarError> {
+        // Determine if recipient is address or key
+        let (recipient_address, recipient_pk) = if recipient_input.starts_with('G') {
+            (Some(recipient_input), None)
+        } else {
+            (None, Some(recipient_input))
+        };
+
+        let destination = match recipient_address {
+            Some(address) => address.to_string(),
+            None => Self::gns_key_to_stellar(recipient_input)?,
+        };
+        let warning = match self.check_gns_destination(sender_public_key, &destination, confirmed)? {
+            Destination::Allowed(warning) => warning,
+            Destination::Blocked(result) => return Ok(result),
+        };
+
+        let result = self
+            .submit_gns_transfer(sender_public_key, sender_private_key, recipient_address, recipient_pk, amount, None, cancel)
+            .await?;
+        Ok(TransactionResult { warning, ..result })
+    }
+
+    /// Check a GNS send from `sender_public_key` to `recipient_address`;
+    /// see [`send_guard`]
+    fn check_gns_destination(
+        &self,
+        sender_public_key: &str,
+        recipient_address: &str,
+        confirmed: bool,
+    ) -> Result<Destination, StellarError> {
+        let sender_address = Self::gns_key_to_stellar(sender_public_key)?;
+        send_guard::check_destination(&sender_address, recipient_address, &self.config.gns_asset(), confirmed)
+    }
+
+    /// How GNS sent to `stellar_address` would arrive: as a payment if it
+    /// holds a GNS trustline, otherwise as a claimable balance (which also
+    /// covers accounts that don't exist yet)
+    pub async fn gns_delivery(&self, stellar_address: &str) -> Result<GnsDelivery, StellarError> {
+        match self.has_gns_trustline(stellar_address).await {
+            Ok(true) => Ok(GnsDelivery::Payment),
+            Ok(false) | Err(StellarError::AccountNotFound) => Ok(GnsDelivery::ClaimableBalance),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// How a submitted GNS transfer actually arrived, read from its
+    /// operations on Horizon. The backend builds the transaction and may
+    /// not honour the claimable balance flag. `None` if the transaction
+    /// holds neither a payment nor a claimable balance.
+    pub async fn submitted_gns_delivery(&self, tx_hash: &str) -> Result<Option<GnsDelivery>, StellarError> {
+        let url = format!("{}/transactions/{}/operations", self.config.horizon_url, tx_hash);
+
+        let response = self.client.get(&url).send().await
+            .map_err(|e| StellarError::NetworkError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(StellarError::NetworkError(format!(
+                "Horizon has no transaction {} ({})",
+                tx_hash,
+                response.status()
+            )));
+        }
+
+        let data: HorizonTransactionOperations = response.json().await
+            .map_err(|e| StellarError::ParseError(e.to_string()))?;
+
+        let has = |operation_type: &str| data.embedded.records.iter().any(|op| op.operation_type == operation_type);
+        Ok(if has("create_claimable_balance") {
+            Some(GnsDelivery::ClaimableBalance)
+        } else if has("payment") {
+            Some(GnsDelivery::Payment)
+        } else {
+            None
+        })
+    }
+
+    /// Whether `stellar_address` exists and trusts GNS, and so how a
+    /// transfer to it would be delivered
+    pub async fn recipient_status(&self, stellar_address: &str) -> Result<RecipientStatus, StellarError> {
+        let account_exists = self.account_exists(stellar_address).await;
+        let has_trustline = account_exists && self.has_gns_trustline(stellar_address).await?;
+
+        Ok(RecipientStatus {
+            stellar_address: stellar_address.to_string(),
+            account_exists,
+            has_trustline,
+            will_use_claimable: !has_trustline,
+        })
+    }
+
+    /// Send GNS to a Stellar address, as a payment or a claimable balance
+    pub async fn send_gns_to_address(
+        &self,
+        sender_public_key: &str,
+        sender_private_key: &[u8],
+        recipient_address: &str,
+        amount: f64,
+        delivery: GnsDelivery,
+        confirmed: bool,
+    ) -> Result<TransactionResult, StellarError> {
+        let warning = match self.check_gns_destination(sender_public_key, recipient_address, confirmed)? {
+            Destination::Allowed(warning) => warning,
+            Destination::Blocked(result) => return Ok(result),
+        };
+
+        let claimable = delivery == GnsDelivery::ClaimableBalance;
+        let result = self
+            .submit_gns_transfer(
+                sender_public_key,
+                sender_private_key,
+                Some(recipient_address),
+                None,
+                amount,
+                Some(claimable),
+                &CancelToken::new(),
+            )
+            .await?;
+        Ok(TransactionResult { warning, ..result })
+    }
+
+    #[tracing::instrument(
+        name = "send_gns",
+        skip_all,
+        fields(
+            correlation_id = %uuid::Uuid::new_v4(),
+            recipient = %recipient_address.or(recipient_pk).map(|r| r.get(..8).unwrap_or(r)).unwrap_or_default(),
+            amount = amount,
+            tx_hash = tracing::field::Empty,
+        )
+    )]
+    #[allow(clippy::too_many_arguments)]
+    async fn submit_gns_transfer(
+        &self,
+        sender_public_key: &str,
+        sender_private_key: &[u8],
+        recipient_address: Option<&str>,
+        recipient_pk: Option<&str>,
+        amount: f64,
+        claimable_balance: Option<bool>,
+        cancel: &CancelToken,
     ) -> Result<TransactionResult, StellarError> {
         let private_key_hex = hex::encode(sender_private_key);
         let identity = GnsIdentity::from_hex(&private_key_hex)
@@ -591,63 +1465,69 @@ impl StellarService {
             Ok(hex::encode(signature.to_bytes()))
         };
 
-        // Determine if recipient is address or key
-        let (recipient_address, recipient_pk) = if recipient_input.starts_with('G') {
-            (Some(recipient_input), None)
-        } else {
-            (None, Some(recipient_input))
-        };
-
         let network = if self.config.use_testnet { Some("testnet") } else { None };
 
-        let initial_res = self.backend.send_gns(
+        // The backend may submit on this first call when it holds the keys
+        cancel.check()?;
+        let first = self.backend.send_gns(
             recipient_address, 
             recipient_pk, 
             amount, 
             None, 
+            claimable_balance,
             sender_public_key, 
             network,
             None,
             sign_fn
         ).await;
 
-        match initial_res {
-             Ok(response) => {
-                  if response.success {
-                      Ok(TransactionResult { success: true, hash: response.hash, error: None })
-                  } else if response.error.as_deref() == Some("SIGN_REQUIRED") {
-                       if let Some(xdr) = response.hash {
-                           let signed_xdr = self.sign_transaction(&xdr, sender_private_key)?;
-
-                           let sign_fn_2 = |msg: &str| {
-                                let signature = identity.sign(msg.as_bytes());
-                                Ok(hex::encode(signature.to_bytes()))
-                           };
-
-                           let final_res = self.backend.send_gns(
-                                recipient_address, 
-                                recipient_pk, 
-                                amount, 
-                                None, 
-                                sender_public_key, 
-                                network,
-                                Some(&signed_xdr),
-                                sign_fn_2
-                           ).await;
-
-                           match final_res {
-                                Ok(r) => Ok(TransactionResult { success: r.success, hash: r.hash, error: r.error }),
-                                Err(e) => Ok(TransactionResult { success: false, hash: None, error: Some(e) }),
-                           }
-                       } else {
-                           Ok(TransactionResult { success: false, hash: None, error: Some("SIGN_REQUIRED but no XDR".to_string()) })
-                       }
-                  } else {
-                      Ok(TransactionResult { success: false, hash: response.hash, error: response.error })
-                  }
-             },
-             Err(e) => Ok(TransactionResult { success: false, hash: None, error: Some(e) }),
+        let request = backend::SendGnsRequest {
+            public_key: sender_public_key.to_string(),
+            recipient_address: recipient_address.map(str::to_string),
+            recipient_public_key: recipient_pk.map(str::to_string),
+            amount: format!("{:.7}", amount),
+            memo: None,
+            claimable_balance,
+            signed_xdr: None,
+            network: network.map(str::to_string),
+        };
+        let first = recover_missing_xdr(first, || self.backend.get_unsigned_xdr("send-gns", &request, sign_fn)).await;
+
+        let result = match first {
+            Some(first) => {
+                self.finish_backend_transaction(first, sender_private_key, cancel, |signed_xdr| async move {
+                    self.backend
+                        .send_gns(
+                            recipient_address,
+                            recipient_pk,
+                            amount,
+                            None,
+                            claimable_balance,
+                            sender_public_key,
+                            network,
+                            Some(&signed_xdr),
+                            sign_fn,
+                        )
+                        .await
+                })
+                .await?
+            }
+            None => {
+                cancel.check()?;
+                self.send_gns_locally(sender_public_key, sender_private_key, recipient_address, recipient_pk, amount, claimable_balance)
+                    .await?
+            }
+        };
+
+        if let Some(hash) = &result.hash {
+            tracing::Span::current().record("tx_hash", hash.as_str());
         }
+        match &result.error {
+            None => tracing::info!(success = result.success, "GNS transfer finished"),
+            Some(error) => tracing::warn!(error = %error, "GNS transfer failed"),
+        }
+
+        Ok(result)
     }
 
     /// Claim all GNS tokens via backend
@@ -655,6 +1535,7 @@ impl StellarService {
         &self,
         public_key_hex: &str,
         private_key_bytes: &[u8],
+        cancel: &CancelToken,
     ) -> Result<TransactionResult, StellarError> {
         let private_key_hex = hex::encode(private_key_bytes);
         let identity = GnsIdentity::from_hex(&private_key_hex)
@@ -667,37 +1548,121 @@ impl StellarService {
 
         let network = if self.config.use_testnet { Some("testnet") } else { None };
 
-        let initial_res = self.backend.claim_gns(public_key_hex, network, None, sign_fn, None).await;
-
-        match initial_res {
-             Ok(response) => {
-                  if response.success {
-                       Ok(TransactionResult { success: true, hash: response.hash, error: None })
-                  } else if response.error.as_deref() == Some("SIGN_REQUIRED") || response.error.as_deref() == Some("COSIGN_REQUIRED") {
-                       // Note: COSIGN_REQUIRED uses same mechanism
-                       if let Some(xdr) = response.hash {
-                            let signed_xdr = self.sign_transaction(&xdr, private_key_bytes)?;
-                            
-                            let sign_fn_2 = |msg: &str| {
-                                let signature = identity.sign(msg.as_bytes());
-                                Ok(hex::encode(signature.to_bytes()))
-                            };
-
-                            let final_res = self.backend.claim_gns(public_key_hex, network, Some(&signed_xdr), sign_fn_2, None).await;
-                            
-                             match final_res {
-                                Ok(r) => Ok(TransactionResult { success: r.success, hash: r.hash, error: r.error }),
-                                Err(e) => Ok(TransactionResult { success: false, hash: None, error: Some(e) }),
-                            }
-                       } else {
-                            Ok(TransactionResult { success: false, hash: None, error: Some("SIGN_REQUIRED but no XDR".to_string()) })
-                       }
-                  } else {
-                       Ok(TransactionResult { success: false, hash: response.hash, error: response.error })
-                  }
-             },
-             Err(e) => Ok(TransactionResult { success: false, hash: None, error: Some(e) }),
+        cancel.check()?;
+        let first = self.backend.claim_gns(public_key_hex, network, None, sign_fn, None).await;
+
+        let request = backend::ClaimGnsRequest {
+            public_key: public_key_hex.to_string(),
+            balance_ids: None,
+            signed_xdr: None,
+            network: network.map(str::to_string),
+        };
+        let Some(first) =
+            recover_missing_xdr(first, || self.backend.get_unsigned_xdr("claim-gns", &request, sign_fn)).await
+        else {
+            return self.claim_gns_locally(public_key_hex, private_key_bytes, cancel).await;
+        };
+
+        self.finish_backend_transaction(first, private_key_bytes, cancel, |signed_xdr| async move {
+            self.backend
+                .claim_gns(public_key_hex, network, Some(&signed_xdr), sign_fn, None)
+                .await
+        })
+        .await
+    }
+
+    /// Send GNS with a transaction built here rather than by the backend.
+    /// Nobody sponsors it, so the sender pays the fee, and the reserve if
+    /// it becomes a claimable balance.
+    async fn send_gns_locally(
+        &self,
+        sender_public_key: &str,
+        sender_private_key: &[u8],
+        recipient_address: Option<&str>,
+        recipient_pk: Option<&str>,
+        amount: f64,
+        claimable_balance: Option<bool>,
+    ) -> Result<TransactionResult, StellarError> {
+        let destination = match (recipient_address, recipient_pk) {
+            (Some(address), _) => address.to_string(),
+            (None, Some(pk)) => Self::gns_key_to_stellar(pk)?,
+            (None, None) => return Err(StellarError::Validation("No recipient".to_string())),
+        };
+        let claimable = match claimable_balance {
+            Some(claimable) => claimable,
+            None => self.gns_delivery(&destination).await? == GnsDelivery::ClaimableBalance,
+        };
+
+        tracing::info!("🔧 Building GNS transfer locally (claimable: {})", claimable);
+        let body = assets::transfer_operation(&self.config.gns_asset(), &destination, amount, claimable)?;
+        self.submit_operation(sender_public_key, sender_private_key, body).await
+    }
+
+    /// Claim GNS with transactions built here rather than by the backend,
+    /// as one result: the last hash if every claim went through, otherwise
+    /// the first failure
+    async fn claim_gns_locally(
+        &self,
+        public_key_hex: &str,
+        private_key_bytes: &[u8],
+        cancel: &CancelToken,
+    ) -> Result<TransactionResult, StellarError> {
+        tracing::info!("🔧 Building GNS claims locally");
+        let results = self.claim_all_balances(public_key_hex, private_key_bytes, cancel).await?;
+        if results.is_empty() {
+            return Ok(TransactionResult::err("No GNS balances to claim".to_string()));
         }
+
+        let failed: Vec<_> = results.iter().filter(|r| !r.success).collect();
+        Ok(match failed.first() {
+            None => TransactionResult::succeeded(results.iter().rev().find_map(|r| r.hash.clone())),
+            Some(first) => TransactionResult::err(format!(
+                "{} of {} claims failed: {}",
+                failed.len(),
+                results.len(),
+                first.error.as_deref().unwrap_or("Unknown error")
+            )),
+        })
+    }
+
+    /// Finish a backend-built transaction.
+    ///
+    /// When the backend asks for a signature (or a co-signature on a
+    /// transaction it has already signed) the XDR is signed locally and
+    /// handed to `resubmit` once, unless `cancel` fired first. A hash the
+    /// backend already returned is reported whether or not it did.
+    async fn finish_backend_transaction<F, Fut>(
+        &self,
+        first: Result<BackendSignState, String>,
+        private_key_bytes: &[u8],
+        cancel: &CancelToken,
+        resubmit: F,
+    ) -> Result<TransactionResult, StellarError>
+    where
+        F: FnOnce(String) -> Fut,
+        Fut: std::future::Future<Output = Result<BackendSignState, String>>,
+    {
+        let xdr = match first {
+            Ok(BackendSignState::SignRequired(xdr)) | Ok(BackendSignState::CosignRequired(xdr)) => xdr,
+            Ok(BackendSignState::Complete(hash)) => return Ok(TransactionResult::succeeded(hash)),
+            Ok(BackendSignState::MissingXdr { status, .. }) => {
+                return Ok(TransactionResult::err(format!("Backend returned {} without a transaction to sign", status)))
+            }
+            Ok(BackendSignState::Error(e)) | Err(e) => return Ok(TransactionResult::err(e)),
+        };
+
+        let signed_xdr = self.sign_transaction(&xdr, private_key_bytes)?;
+        cancel.check()?;
+
+        Ok(match resubmit(signed_xdr).await {
+            Ok(BackendSignState::Complete(hash)) => TransactionResult::succeeded(hash),
+            Ok(BackendSignState::SignRequired(_))
+            | Ok(BackendSignState::CosignRequired(_))
+            | Ok(BackendSignState::MissingXdr { .. }) => {
+                TransactionResult::err("Backend asked for another signature on a signed transaction".to_string())
+            }
+            Ok(BackendSignState::Error(e)) | Err(e) => TransactionResult::err(e),
+        })
     }
 
     // ==================== SIGNING HELPER ====================
@@ -831,10 +1796,327 @@ pub enum StellarError {
 
     #[error("Validation error: {0}")]
     Validation(String),
+
+    #[error("Invalid GNS issuer: {0}")]
+    InvalidIssuer(String),
+
+    #[error("Operation cancelled")]
+    Cancelled,
 }
 
 // ==================== HELPER FUNCTIONS ====================
 
+/// Stellar's cap on operations in one transaction
+pub const MAX_OPERATIONS_PER_TX: usize = 100;
+
+/// Base fee per operation, in stroops
+const BASE_FEE_STROOPS: u32 = 100;
+
+/// How long fetched fee stats are reused
+const FEE_STATS_TTL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Fee estimate for `operation_count` operations from Horizon's fee
+/// stats, or from `BASE_FEE_STROOPS` alone without them. Fees Horizon
+/// reports below the base fee (or can't be parsed) count as the base fee.
+fn fee_estimate(operation_count: u32, stats: Option<&HorizonFeeStats>) -> FeeEstimate {
+    let default = BASE_FEE_STROOPS as u64;
+    let per_op = |value: Option<&String>, floor: u64| {
+        value.and_then(|v| v.parse::<u64>().ok()).unwrap_or(floor).max(floor)
+    };
+
+    let base = per_op(stats.map(|s| &s.last_ledger_base_fee), default);
+    let recommended = per_op(stats.map(|s| &s.fee_charged.p50), base);
+    let congested = per_op(stats.map(|s| &s.fee_charged.p90), recommended);
+
+    let ops = operation_count as u64;
+    FeeEstimate {
+        operation_count,
+        base_fee_stroops: base,
+        fee_stroops: base * ops,
+        fee_xlm: stroops_to_xlm(base * ops),
+        recommended_fee_stroops: recommended * ops,
+        recommended_fee_xlm: stroops_to_xlm(recommended * ops),
+        congested_fee_stroops: congested * ops,
+        congested_fee_xlm: stroops_to_xlm(congested * ops),
+        from_network: stats.is_some(),
+    }
+}
+
+/// Format stroops as XLM without trailing zeros, e.g. 100 -> "0.00001"
+fn stroops_to_xlm(stroops: u64) -> String {
+    let xlm = format!("{}.{:07}", stroops / 10_000_000, stroops % 10_000_000);
+    xlm.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// Split a Horizon asset string ("GNS:GBVZ..." or "native") into code and issuer
+fn split_asset(asset: &str) -> (String, Option<String>) {
+    if asset == "native" {
+        return ("XLM".to_string(), None);
+    }
+    match asset.split_once(':') {
+        Some((code, issuer)) if !issuer.contains(':') => (code.to_string(), Some(issuer.to_string())),
+        _ => (asset.to_string(), None),
+    }
+}
+
+/// Whether a Horizon claim predicate (JSON form) is satisfied at `now`
+fn predicate_allows(predicate: &serde_json::Value, now: i64) -> bool {
+    if predicate.get("unconditional").is_some() {
+        return true;
+    }
+    if let Some(all) = predicate.get("and").and_then(|v| v.as_array()) {
+        return all.iter().all(|p| predicate_allows(p, now));
+    }
+    if let Some(any) = predicate.get("or").and_then(|v| v.as_array()) {
+        return any.iter().any(|p| predicate_allows(p, now));
+    }
+    if let Some(inner) = predicate.get("not") {
+        return !predicate_allows(inner, now);
+    }
+    match predicate_before(predicate) {
+        Some(before) => now < before,
+        // Unknown predicate: let the network decide
+        None => true,
+    }
+}
+
+/// Whether `predicate` holds at `now` or at any later time. A predicate
+/// only changes value at its `abs_before` times, so it is enough to check
+/// `now` and each of those still to come.
+fn predicate_allows_from(predicate: &serde_json::Value, now: i64) -> bool {
+    let mut times = vec![now];
+    predicate_times(predicate, &mut times);
+    times.into_iter().filter(|&t| t >= now).any(|t| predicate_allows(predicate, t))
+}
+
+/// Collect every `abs_before` time in `predicate`
+fn predicate_times(predicate: &serde_json::Value, times: &mut Vec<i64>) {
+    let nested = ["and", "or"]
+        .into_iter()
+        .filter_map(|key| predicate.get(key).and_then(|v| v.as_array()))
+        .flatten()
+        .chain(predicate.get("not"));
+    for inner in nested {
+        predicate_times(inner, times);
+    }
+    times.extend(predicate_before(predicate));
+}
+
+/// The time a leaf predicate stops holding, if it is an `abs_before`
+fn predicate_before(predicate: &serde_json::Value) -> Option<i64> {
+    // Horizon turns relative predicates into absolute ones once the balance exists
+    predicate
+        .get("abs_before_epoch")
+        .and_then(|v| v.as_str().map(str::to_string).or_else(|| v.as_i64().map(|n| n.to_string())))
+        .and_then(|s| s.parse::<i64>().ok())
+        .or_else(|| {
+            predicate.get("abs_before")
+                .and_then(|v| v.as_str())
+                .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+                .map(|t| t.timestamp())
+        })
+}
+
+/// Split claimable balances into per-transaction batches, setting aside the
+/// ones that can't be claimed at `now`
+fn plan_claim_batches(
+    balances: &[ClaimableBalance],
+    now: i64,
+) -> (Vec<Vec<&ClaimableBalance>>, Vec<BalanceClaimResult>) {
+    let mut claimable = Vec::new();
+    let mut skipped = Vec::new();
+    for balance in balances {
+        match balance.unclaimable_reason(now) {
+            Some(reason) => skipped.push(BalanceClaimResult::failed(balance, reason.to_string())),
+            None => claimable.push(balance),
+        }
+    }
+
+    let batches = claimable
+        .chunks(MAX_OPERATIONS_PER_TX)
+        .map(|chunk| chunk.to_vec())
+        .collect();
+    (batches, skipped)
+}
+
+/// When the backend asked for a signature but sent nothing to sign, ask it
+/// once more for the unsigned transaction. `None` means it still has none
+/// and the caller should build the transaction itself; any other answer
+/// passes through.
+async fn recover_missing_xdr<F, Fut>(
+    first: Result<BackendSignState, String>,
+    refetch: F,
+) -> Option<Result<BackendSignState, String>>
+where
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = Result<Option<String>, String>>,
+{
+    let (status, cosign) = match first {
+        Ok(BackendSignState::MissingXdr { status, cosign }) => (status, cosign),
+        other => return Some(other),
+    };
+
+    match refetch().await {
+        Ok(Some(xdr)) if cosign => Some(Ok(BackendSignState::CosignRequired(xdr))),
+        Ok(Some(xdr)) => Some(Ok(BackendSignState::SignRequired(xdr))),
+        Ok(None) => {
+            tracing::warn!("⚠️ Backend still has no transaction after {}; building it locally", status);
+            None
+        }
+        Err(e) => {
+            tracing::warn!("⚠️ Re-requesting the XDR after {} failed ({}); building it locally", status, e);
+            None
+        }
+    }
+}
+
+/// Parse a Horizon claimable balance id (hex, type prefix + hash)
+fn parse_balance_id(balance_id: &str) -> Result<stellar_xdr::curr::ClaimableBalanceId, StellarError> {
+    use stellar_xdr::curr::{ClaimableBalanceId, Hash};
+
+    let hash_hex = match balance_id.len() {
+        72 if balance_id.starts_with("00000000") => &balance_id[8..],
+        64 => balance_id,
+        _ => return Err(StellarError::Validation(format!("Invalid balance id: {}", balance_id))),
+    };
+    let bytes: [u8; 32] = hex::decode(hash_hex)
+        .map_err(|e| StellarError::HexDecodeError(e.to_string()))?
+        .try_into()
+        .map_err(|_| StellarError::Validation(format!("Invalid balance id: {}", balance_id)))?;
+    Ok(ClaimableBalanceId::ClaimableBalanceIdTypeV0(Hash(bytes)))
+}
+
+/// Build an unsigned transaction (base64 XDR) claiming `balance_ids`
+fn build_claim_transaction(
+    source: [u8; 32],
+    sequence: i64,
+    balance_ids: &[&str],
+) -> Result<String, StellarError> {
+    use stellar_xdr::curr::{ClaimClaimableBalanceOp, Operation, OperationBody};
+
+    if balance_ids.is_empty() || balance_ids.len() > MAX_OPERATIONS_PER_TX {
+        return Err(StellarError::Validation(format!(
+            "A transaction holds 1 to {} claims, got {}",
+            MAX_OPERATIONS_PER_TX,
+            balance_ids.len()
+        )));
+    }
+
+    let operations = balance_ids
+        .iter()
+        .map(|id| {
+            Ok(Operation {
+                source_account: None,
+                body: OperationBody::ClaimClaimableBalance(ClaimClaimableBalanceOp {
+                    balance_id: parse_balance_id(id)?,
+                }),
+            })
+        })
+        .collect::<Result<Vec<_>, StellarError>>()?;
+
+    build_transaction(source, sequence, operations)
+}
+
+/// Wrap `operations` in an unsigned transaction (base64 XDR) from `source`
+fn build_transaction(
+    source: [u8; 32],
+    sequence: i64,
+    operations: Vec<stellar_xdr::curr::Operation>,
+) -> Result<String, StellarError> {
+    use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+    use stellar_xdr::curr::{
+        Limits, Memo, MuxedAccount, Preconditions, SequenceNumber, Transaction,
+        TransactionEnvelope, TransactionExt, TransactionV1Envelope, Uint256, VecM, WriteXdr,
+    };
+
+    let tx = Transaction {
+        source_account: MuxedAccount::Ed25519(Uint256(source)),
+        fee: BASE_FEE_STROOPS * operations.len() as u32,
+        seq_num: SequenceNumber(sequence),
+        cond: Preconditions::None,
+        memo: Memo::None,
+        operations: operations
+            .try_into()
+            .map_err(|_| StellarError::Validation("Too many operations".to_string()))?,
+        ext: TransactionExt::V0,
+    };
+
+    let envelope = TransactionEnvelope::Tx(TransactionV1Envelope {
+        tx,
+        signatures: VecM::default(),
+    });
+    let bytes = envelope
+        .to_xdr(Limits::none())
+        .map_err(|e| StellarError::Validation(format!("XDR encoding error: {}", e)))?;
+    Ok(BASE64_STANDARD.encode(bytes))
+}
+
+/// Largest trustline limit Stellar accepts (i64::MAX stroops)
+const MAX_TRUST_LIMIT: f64 = 922_337_203_685.477_580_7;
+
+/// Validate a user-supplied trustline limit (non-negative, at most 7 decimals)
+fn parse_trust_limit(limit: &str) -> Result<f64, StellarError> {
+    let limit = limit.trim();
+    let invalid = || StellarError::Validation(format!("Invalid trustline limit: {}", limit));
+
+    if limit.is_empty() || !limit.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return Err(invalid());
+    }
+    if limit.split_once('.').is_some_and(|(_, frac)| frac.len() > 7) {
+        return Err(StellarError::Validation(
+            "Trustline limit supports at most 7 decimal places".to_string(),
+        ));
+    }
+
+    let value: f64 = limit.parse().map_err(|_| invalid())?;
+    if value > MAX_TRUST_LIMIT {
+        return Err(invalid());
+    }
+    Ok(value)
+}
+
+fn asset_info_from_account(
+    code: &str,
+    issuer: &str,
+    network: StellarNetwork,
+    account: HorizonIssuerAccount,
+) -> GnsAssetInfo {
+    GnsAssetInfo {
+        code: code.to_string(),
+        issuer: issuer.to_string(),
+        network,
+        home_domain: account.home_domain.filter(|d| !d.is_empty()),
+        auth_required: account.flags.auth_required,
+        auth_revocable: account.flags.auth_revocable,
+        auth_immutable: account.flags.auth_immutable,
+        clawback_enabled: account.flags.auth_clawback_enabled,
+    }
+}
+
+/// Version byte of an S... secret seed (18 << 3)
+const SECRET_SEED_VERSION: u8 = 0x90;
+
+/// Decode a G... account address into its Ed25519 public key, checking the
+/// version byte and CRC16 checksum
+fn decode_account_id(address: &str) -> Result<[u8; 32], StellarError> {
+    let invalid = |reason: &str| StellarError::Validation(reason.to_string());
+
+    let payload = base32_decode(address).ok_or_else(|| invalid("not base32"))?;
+    if payload.len() != 35 {
+        return Err(invalid("wrong length"));
+    }
+    if payload[0] != 0x30 {
+        return Err(invalid("not an account address"));
+    }
+
+    let checksum = crc16_xmodem(&payload[..33]);
+    if payload[33] != (checksum & 0xFF) as u8 || payload[34] != (checksum >> 8) as u8 {
+        return Err(invalid("checksum mismatch"));
+    }
+
+    Ok(payload[1..33].try_into().expect("32-byte slice"))
+}
+
 /// CRC16-XModem checksum (used by Stellar for address encoding)
 fn crc16_xmodem(data: &[u8]) -> u16 {
     let mut crc: u16 = 0;
@@ -875,6 +2157,30 @@ fn base32_encode(data: &[u8]) -> String {
     result
 }
 
+/// Base32 decode (RFC 4648, no padding). Returns None on invalid characters.
+fn base32_decode(s: &str) -> Option<Vec<u8>> {
+    let mut result = Vec::with_capacity(s.len() * 5 / 8);
+    let mut buffer: u64 = 0;
+    let mut bits_left = 0;
+
+    for c in s.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'2'..=b'7' => c - b'2' + 26,
+            _ => return None,
+        };
+        buffer = (buffer << 5) | value as u64;
+        bits_left += 5;
+
+        if bits_left >= 8 {
+            bits_left -= 8;
+            result.push((buffer >> bits_left) as u8);
+        }
+    }
+
+    Some(result)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -906,4 +2212,366 @@ mod tests {
         let crc = crc16_xmodem(&data);
         assert!(crc > 0);
     }
+
+    #[test]
+    fn test_parse_trust_limit() {
+        assert_eq!(parse_trust_limit("1000").unwrap(), 1000.0);
+        assert_eq!(parse_trust_limit(" 0.5 ").unwrap(), 0.5);
+        assert!(parse_trust_limit("922337203685.4775807").is_ok());
+
+        assert!(parse_trust_limit("").is_err());
+        assert!(parse_trust_limit("-5").is_err());
+        assert!(parse_trust_limit("1.12345678").is_err());
+        assert!(parse_trust_limit("1e9").is_err());
+        assert!(parse_trust_limit("1000000000000").is_err());
+    }
+
+    #[test]
+    fn test_decode_account_id() {
+        let gns_key = "5940f0ab33863be19c2b437ddcea18ef88ddce56dcc9f3f87cf88cb6954aee7c";
+        let address = StellarService::gns_key_to_stellar(gns_key).unwrap();
+        assert_eq!(hex::encode(decode_account_id(&address).unwrap()), gns_key);
+
+        for config in [StellarConfig::mainnet(), StellarConfig::testnet()] {
+            assert!(decode_account_id(&config.gns_issuer).is_ok());
+        }
+
+        // Flip one character so the checksum no longer matches
+        let mut tampered = address.into_bytes();
+        tampered[10] = if tampered[10] == b'A' { b'B' } else { b'A' };
+        assert!(decode_account_id(std::str::from_utf8(&tampered).unwrap()).is_err());
+
+        assert!(decode_account_id("not-an-address").is_err());
+        assert!(decode_account_id("GABC").is_err());
+    }
+
+    #[test]
+    fn test_stellar_secret_to_seed() {
+        // Keypair from the Stellar SDK test suite
+        let secret = "SAV76USXIJOBMEQXPANUOQM6F5LIOTLPDIDVRJBFFE2MDJXG24TAPUU7";
+        let seed = StellarService::stellar_secret_to_seed(secret).unwrap();
+        assert_eq!(hex::encode(seed), "2bff5257425c161217781b47419e2f56874d6f1a0758a4252934c1a6e6d72607");
+
+        let reason = |secret: &str| StellarService::stellar_secret_to_seed(secret).unwrap_err().to_string();
+        let mut typo = secret.to_string();
+        typo.replace_range(20..21, "A");
+        assert!(reason(&typo).contains("checksum mismatch"));
+        assert!(reason("GCFXHS4GXL6BVUCXBWXGTITROWLVYXQKQLF4YH5O5JT3YZXCYPAFBJZB").contains("start with 'S'"));
+        assert!(reason(&secret[..55]).contains("expected 56 characters"));
+        assert!(reason(&secret.to_lowercase().replacen('s', "S", 1)).contains("A-Z and 2-7"));
+    }
+
+    #[test]
+    fn test_asset_info_from_account() {
+        let account: HorizonIssuerAccount = serde_json::from_str(
+            r#"{"home_domain": "gns.earth", "flags": {"auth_required": false, "auth_revocable": true, "auth_immutable": false, "auth_clawback_enabled": true}}"#,
+        ).unwrap();
+        let info = asset_info_from_account("GNS", "GISSUER", StellarNetwork::Mainnet, account);
+        assert_eq!(info.home_domain.as_deref(), Some("gns.earth"));
+        assert!(info.auth_revocable && info.clawback_enabled);
+        assert!(!info.auth_required && !info.auth_immutable);
+
+        let bare: HorizonIssuerAccount = serde_json::from_str("{}").unwrap();
+        let info = asset_info_from_account("GNS", "GISSUER", StellarNetwork::Testnet, bare);
+        assert_eq!(info.home_domain, None);
+        assert!(!info.clawback_enabled);
+    }
+
+    #[test]
+    fn test_stellar_control_proof() {
+        let identity = GnsIdentity::generate();
+        let proof = StellarControlProof::create(&identity, "example.com:nonce-1234").unwrap();
+
+        let address = verify_stellar_control(&proof.public_key, "example.com:nonce-1234", &proof.signature).unwrap();
+        assert_eq!(address, proof.stellar_address);
+        assert_eq!(address, StellarService::gns_key_to_stellar(&identity.public_key_hex()).unwrap());
+    }
+
+    #[test]
+    fn test_stellar_control_proof_challenge_mismatch() {
+        let identity = GnsIdentity::generate();
+        let proof = StellarControlProof::create(&identity, "nonce-1").unwrap();
+
+        assert!(verify_stellar_control(&proof.public_key, "nonce-2", &proof.signature).is_err());
+
+        // Same signature presented for someone else's key
+        let other = GnsIdentity::generate().public_key_hex();
+        assert!(verify_stellar_control(&other, "nonce-1", &proof.signature).is_err());
+
+        assert!(StellarControlProof::create(&identity, "").is_err());
+    }
+
+    /// Horizon with one account, with or without a GNS trustline
+    fn recipient_route(with_trustline: bool) -> impl Fn(&str, &str) -> serde_json::Value {
+        let config = StellarConfig::testnet();
+        move |_, path| {
+            if path != "/accounts/GRECIPIENT" {
+                return serde_json::Value::Null;
+            }
+            let mut balances = vec![serde_json::json!({ "balance": "5.0000000", "asset_type": "native" })];
+            if with_trustline {
+                balances.push(serde_json::json!({
+                    "balance": "0.0000000",
+                    "asset_type": "credit_alphanum4",
+                    "asset_code": config.gns_token_code,
+                    "asset_issuer": config.gns_issuer,
+                }));
+            }
+            serde_json::json!({ "id": "GRECIPIENT", "sequence": "1", "balances": balances })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fee_estimate_scales_with_operations() {
+        let fee_stats = serde_json::json!({
+            "last_ledger": "1",
+            "last_ledger_base_fee": "100",
+            "fee_charged": { "p50": "150", "p90": "2000" },
+        });
+        let (stellar, requests) = test_support::mock_stellar(move |_, path| match path {
+            "/fee_stats" => fee_stats.clone(),
+            _ => serde_json::Value::Null,
+        })
+        .await;
+
+        let one = stellar.estimate_fee(1).await.unwrap();
+        assert!(one.from_network);
+        assert_eq!((one.fee_stroops, one.fee_xlm.as_str()), (100, "0.00001"));
+        assert_eq!((one.recommended_fee_stroops, one.congested_fee_stroops), (150, 2000));
+
+        let three = stellar.estimate_fee(3).await.unwrap();
+        assert_eq!(three.fee_stroops, 3 * one.fee_stroops);
+        assert_eq!(three.recommended_fee_stroops, 3 * one.recommended_fee_stroops);
+        assert_eq!(three.congested_fee_xlm, "0.0006");
+
+        // The second estimate reused the cached stats
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        assert!(stellar.estimate_fee(0).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fee_estimate_falls_back_to_base_fee() {
+        let (stellar, _) = test_support::mock_stellar(|_, _| serde_json::Value::Null).await;
+
+        let estimate = stellar.estimate_fee(2).await.unwrap();
+        assert!(!estimate.from_network);
+        assert_eq!(estimate.base_fee_stroops, BASE_FEE_STROOPS as u64);
+        assert_eq!(estimate.fee_stroops, 2 * BASE_FEE_STROOPS as u64);
+        assert_eq!(estimate.recommended_fee_stroops, estimate.fee_stroops);
+        assert_eq!(estimate.fee_xlm, "0.00002");
+    }
+
+    #[tokio::test]
+    async fn test_recipient_status() {
+        let (stellar, _) = test_support::mock_stellar(recipient_route(true)).await;
+        let status = stellar.recipient_status("GRECIPIENT").await.unwrap();
+        assert!(status.account_exists && status.has_trustline);
+        assert!(!status.will_use_claimable);
+
+        let (stellar, _) = test_support::mock_stellar(recipient_route(false)).await;
+        let status = stellar.recipient_status("GRECIPIENT").await.unwrap();
+        assert!(status.account_exists && !status.has_trustline);
+        assert!(status.will_use_claimable);
+
+        let (stellar, requests) = test_support::mock_stellar(recipient_route(true)).await;
+        let status = stellar.recipient_status("GNOBODY").await.unwrap();
+        assert!(!status.account_exists && !status.has_trustline);
+        assert!(status.will_use_claimable);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_submitted_delivery_is_read_from_the_transaction() {
+        let (stellar, _) = test_support::mock_stellar(|_, path| {
+            let operation_type = match path {
+                "/transactions/claimable/operations" => "create_claimable_balance",
+                "/transactions/paid/operations" => "payment",
+                "/transactions/other/operations" => "change_trust",
+                _ => return serde_json::Value::Null,
+            };
+            serde_json::json!({ "_embedded": { "records": [{ "type": operation_type }] } })
+        })
+        .await;
+
+        let delivery = |hash: &'static str| stellar.submitted_gns_delivery(hash);
+        assert_eq!(delivery("claimable").await.unwrap(), Some(GnsDelivery::ClaimableBalance));
+        assert_eq!(delivery("paid").await.unwrap(), Some(GnsDelivery::Payment));
+        assert_eq!(delivery("other").await.unwrap(), None);
+        assert!(delivery("unknown").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_before_submit_sends_nothing() {
+        let (stellar, requests) = test_support::mock_stellar(|_, _| {
+            serde_json::json!({ "success": true, "data": { "hash": "sent" } })
+        })
+        .await;
+        let identity = GnsIdentity::generate();
+        let private_key = hex::decode(identity.private_key_hex()).unwrap();
+        let cancel = CancelToken::new();
+        assert!(cancel.cancel());
+
+        let sent = stellar
+            .send_gns(&identity.public_key_hex(), &private_key, None, None, &identity.public_key_hex(), 1.0, false, &cancel)
+            .await;
+        assert!(matches!(sent, Err(StellarError::Cancelled)));
+        let claimed = stellar.claim_all_gns(&identity.public_key_hex(), &private_key, &cancel).await;
+        assert!(matches!(claimed, Err(StellarError::Cancelled)));
+        let claimed = stellar.claim_all_balances(&identity.public_key_hex(), &private_key, &cancel).await;
+        assert!(matches!(claimed, Err(StellarError::Cancelled)));
+        let trusted = stellar.create_gns_trustline(&identity.public_key_hex(), &private_key, None, &cancel).await;
+        assert!(matches!(trusted, Err(StellarError::Cancelled)));
+        let removed = stellar.remove_gns_trustline(&identity.public_key_hex(), &private_key, &cancel).await;
+        assert!(matches!(removed, Err(StellarError::Cancelled)));
+        assert!(requests.lock().unwrap().is_empty());
+
+        // A transaction the backend already submitted is still reported
+        let submitted = stellar
+            .finish_backend_transaction(
+                Ok(BackendSignState::Complete(Some("hash".to_string()))),
+                &private_key,
+                &cancel,
+                |_| async { unreachable!() },
+            )
+            .await
+            .unwrap();
+        assert!(submitted.success);
+        assert_eq!(submitted.hash.as_deref(), Some("hash"));
+    }
+
+    /// Horizon where every account holds a GNS trustline, behind a backend
+    /// that asks for a signature on send-gns without sending the XDR, and
+    /// answers `unsigned-xdr` with `refetched`
+    fn missing_xdr_route(refetched: serde_json::Value) -> impl Fn(&str, &str) -> serde_json::Value {
+        let config = StellarConfig::testnet();
+        let sends = std::sync::atomic::AtomicUsize::new(0);
+        move |method, path| match (method, path) {
+            ("POST", "/backend/send-gns") => match sends.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                0 => serde_json::json!({ "success": false, "error": "SIGN_REQUIRED" }),
+                _ => serde_json::json!({ "success": true, "hash": "backendhash" }),
+            },
+            ("POST", "/backend/unsigned-xdr") => refetched.clone(),
+            ("GET", p) if p.starts_with("/accounts/") => serde_json::json!({
+                "id": "G",
+                "sequence": "100",
+                "balances": [
+                    { "balance": "5.0000000", "asset_type": "native" },
+                    {
+                        "balance": "0.0000000",
+                        "asset_type": "credit_alphanum4",
+                        "asset_code": config.gns_token_code,
+                        "asset_issuer": config.gns_issuer,
+                    },
+                ],
+            }),
+            ("POST", "/transactions") => serde_json::json!({ "successful": true, "hash": "localhash" }),
+            _ => serde_json::Value::Null,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_missing_xdr_falls_back_to_local_transaction() {
+        let (stellar, requests) = test_support::mock_stellar(missing_xdr_route(serde_json::json!({ "success": false }))).await;
+        let sender = GnsIdentity::generate();
+        let private_key = hex::decode(sender.private_key_hex()).unwrap();
+        let recipient = StellarService::gns_key_to_stellar(&GnsIdentity::generate().public_key_hex()).unwrap();
+
+        let result = stellar
+            .send_gns(&sender.public_key_hex(), &private_key, None, None, &recipient, 1.5, false, &CancelToken::new())
+            .await
+            .unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.hash.as_deref(), Some("localhash"));
+
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.iter().filter(|r| *r == "POST /backend/send-gns").count(), 1, "{:?}", requests);
+        assert!(requests.iter().any(|r| r == "POST /backend/unsigned-xdr"), "{:?}", requests);
+        assert!(requests.iter().any(|r| r == "POST /transactions"), "{:?}", requests);
+    }
+
+    #[tokio::test]
+    async fn test_missing_xdr_is_re_requested_from_backend() {
+        let config = StellarConfig::testnet();
+        let payment = assets::transfer_operation(&config.gns_asset(), &config.gns_issuer, 1.0, false).unwrap();
+        let operation = stellar_xdr::curr::Operation { source_account: None, body: payment };
+        let xdr = build_transaction([7; 32], 42, vec![operation]).unwrap();
+        let (stellar, requests) = test_support::mock_stellar(missing_xdr_route(serde_json::json!({ "success": true, "xdr": xdr }))).await;
+        let sender = GnsIdentity::generate();
+        let private_key = hex::decode(sender.private_key_hex()).unwrap();
+        let recipient = StellarService::gns_key_to_stellar(&GnsIdentity::generate().public_key_hex()).unwrap();
+
+        let result = stellar
+            .send_gns(&sender.public_key_hex(), &private_key, None, None, &recipient, 1.0, false, &CancelToken::new())
+            .await
+            .unwrap();
+        assert_eq!(result.hash.as_deref(), Some("backendhash"));
+
+        // Signed and handed back to the backend; nothing built locally
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.iter().filter(|r| *r == "POST /backend/send-gns").count(), 2, "{:?}", requests);
+        assert!(!requests.iter().any(|r| r == "POST /transactions"), "{:?}", requests);
+    }
+
+    fn gns_balance(i: usize, predicate: Option<serde_json::Value>) -> ClaimableBalance {
+        ClaimableBalance {
+            balance_id: format!("00000000{:064x}", i),
+            asset_code: "GNS".to_string(),
+            asset_issuer: None,
+            amount: "10.0000000".to_string(),
+            sponsor: None,
+            predicate,
+        }
+    }
+
+    #[test]
+    fn test_claims_are_batched_per_transaction() {
+        use stellar_xdr::curr::{Limits, ReadXdr, TransactionEnvelope};
+
+        let now = 1_700_000_000;
+        let expired = serde_json::json!({ "abs_before": "2020-01-01T00:00:00Z", "abs_before_epoch": "1577836800" });
+        let mut balances: Vec<_> = (0..250).map(|i| gns_balance(i, None)).collect();
+        balances.push(gns_balance(250, Some(expired)));
+        balances.push(gns_balance(251, Some(serde_json::json!({ "unconditional": true }))));
+
+        let (batches, skipped) = plan_claim_batches(&balances, now);
+        assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), vec![100, 100, 51]);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].balance_id, balances[250].balance_id);
+        assert_eq!(skipped[0].error.as_deref(), Some("Expired"));
+
+        let ids: Vec<&str> = batches[0].iter().map(|b| b.balance_id.as_str()).collect();
+        let xdr = build_claim_transaction([7; 32], 42, &ids).unwrap();
+        let bytes = base64::engine::general_purpose::STANDARD.decode(xdr).unwrap();
+        let TransactionEnvelope::Tx(envelope) = TransactionEnvelope::from_xdr(bytes, Limits::none()).unwrap() else {
+            panic!("expected a v1 envelope");
+        };
+        assert_eq!(envelope.tx.operations.len(), 100);
+        assert_eq!(envelope.tx.fee, 100 * BASE_FEE_STROOPS);
+        assert_eq!(envelope.tx.seq_num.0, 42);
+
+        let too_many: Vec<&str> = balances[..101].iter().map(|b| b.balance_id.as_str()).collect();
+        assert!(build_claim_transaction([7; 32], 42, &too_many).is_err());
+    }
+
+    #[test]
+    fn test_claim_predicates() {
+        let now = 1_700_000_000;
+        let before = |t: i64| serde_json::json!({ "abs_before_epoch": t.to_string() });
+
+        assert_eq!(gns_balance(0, Some(before(now + 60))).unclaimable_reason(now), None);
+        assert_eq!(gns_balance(0, Some(before(now))).unclaimable_reason(now), Some("Expired"));
+        assert_eq!(
+            gns_balance(0, Some(serde_json::json!({ "not": before(now + 60) }))).unclaimable_reason(now),
+            Some("Not claimable yet")
+        );
+        let window = serde_json::json!({ "and": [{ "not": before(now - 60) }, before(now + 60)] });
+        assert_eq!(gns_balance(0, Some(window)).unclaimable_reason(now), None);
+
+        // A window that hasn't opened yet isn't expired, one that closed is
+        let later = serde_json::json!({ "and": [{ "not": before(now + 60) }, before(now + 120)] });
+        assert_eq!(gns_balance(0, Some(later.clone())).unclaimable_reason(now), Some("Not claimable yet"));
+        assert!(!gns_balance(0, Some(later.clone())).is_expired(now));
+        assert!(gns_balance(0, Some(later)).is_expired(now + 120));
+        assert!(!gns_balance(0, Some(before(now + 60))).is_expired(now));
+    }
 }
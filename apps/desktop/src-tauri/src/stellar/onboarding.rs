@@ -0,0 +1,359 @@
+//! Wallet Onboarding
+//!
+//! Takes a new wallet from "welcome airdrop waiting" to "holding GNS":
+//! add the GNS trustline, wait for Horizon to show it, then claim every
+//! pending GNS balance. Each step first looks at the ledger and is skipped
+//! if already done, so a run that failed halfway (or a user who did part
+//! of it by hand) simply carries on from where the wallet is. That
+//! includes a run that was cancelled: cancelling is possible until the
+//! first transaction goes out, after which the run finishes.
+
+use super::outcome::claim_summary;
+use super::{BalanceClaimResult, CancelToken, ReserveHeadroom, StellarError, StellarService};
+use serde::Serialize;
+use std::time::Duration;
+
+/// Tauri event carrying an [`OnboardingProgress`]
+pub const ONBOARDING_EVENT: &str = "wallet_onboarding_progress";
+
+/// How long to wait for a new trustline to show up on Horizon
+const CONFIRM_ATTEMPTS: u32 = 15;
+const CONFIRM_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnboardingStep {
+    Trustline,
+    Confirmation,
+    Claim,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Started,
+    /// Already done before this run
+    Skipped,
+    Done,
+}
+
+/// Payload of the `wallet_onboarding_progress` event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OnboardingProgress {
+    pub step: OnboardingStep,
+    pub status: StepStatus,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OnboardingResult {
+    /// Whether this run added the trustline
+    pub trustline_created: bool,
+    /// One entry per balance this run tried to claim
+    pub claims: Vec<BalanceClaimResult>,
+    pub gns_balance: f64,
+}
+
+/// Why onboarding stopped, sent to the UI as `{ kind, message }`
+#[derive(Debug, thiserror::Error)]
+pub enum OnboardingError {
+    #[error("No identity found")]
+    NoIdentity,
+
+    #[error("Account {0} doesn't exist yet - send it some XLM to activate it")]
+    AccountNotFunded(String),
+
+    #[error("Adding the GNS trustline needs {required:.2} XLM but the account holds {available:.2} XLM - add at least {:.2} XLM", required - available)]
+    InsufficientXlm { available: f64, required: f64 },
+
+    #[error("Could not add GNS trustline: {0}")]
+    Trustline(String),
+
+    #[error("The GNS trustline hasn't shown up on the network yet - try again in a minute")]
+    NotConfirmed,
+
+    #[error("Wallet onboarding timed out after {seconds}s")]
+    Timeout { seconds: u64 },
+
+    #[error(transparent)]
+    Stellar(#[from] StellarError),
+}
+
+impl OnboardingError {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::NoIdentity => "no_identity",
+            Self::AccountNotFunded(_) => "account_not_funded",
+            Self::InsufficientXlm { .. } => "insufficient_xlm",
+            Self::Trustline(_) => "trustline_failed",
+            Self::NotConfirmed => "not_confirmed",
+            Self::Timeout { .. } => "timeout",
+            Self::Stellar(StellarError::Cancelled) => "cancelled",
+            Self::Stellar(_) => "stellar",
+        }
+    }
+}
+
+impl Serialize for OnboardingError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut error = serializer.serialize_struct("OnboardingError", 2)?;
+        error.serialize_field("kind", self.kind())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
+    }
+}
+
+impl StellarService {
+    /// Add the GNS trustline if missing, then claim all pending GNS
+    /// balances, reporting each step to `on_progress`. `cancel` is
+    /// honoured until the trustline or first claim is submitted.
+    pub async fn onboard_wallet(
+        &self,
+        public_key_hex: &str,
+        private_key_bytes: &[u8],
+        cancel: &CancelToken,
+        mut on_progress: impl FnMut(OnboardingProgress),
+    ) -> Result<OnboardingResult, OnboardingError> {
+        let mut report = |step, status, message: Option<String>| {
+            on_progress(OnboardingProgress { step, status, message })
+        };
+        let stellar_address = Self::gns_key_to_stellar(public_key_hex)?;
+
+        let account = match cancel.guard(self.get_account(&stellar_address)).await? {
+            Ok(account) => account,
+            Err(StellarError::AccountNotFound) => {
+                return Err(OnboardingError::AccountNotFunded(stellar_address))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let gns_asset = self.config.gns_asset();
+        let is_gns = |b: &&super::HorizonBalance| {
+            gns_asset.matches(b.asset_code.as_deref().unwrap_or_default(), b.asset_issuer.as_deref())
+        };
+        let has_trustline = account.balances.iter().any(|b| is_gns(&b));
+
+        let trustline_created = if has_trustline {
+            report(OnboardingStep::Trustline, StepStatus::Skipped, None);
+            report(OnboardingStep::Confirmation, StepStatus::Skipped, None);
+            false
+        } else {
+            let headroom = ReserveHeadroom::for_account(&stellar_address, &account, 1);
+            if !headroom.affordable {
+                return Err(OnboardingError::InsufficientXlm {
+                    available: headroom.xlm_balance,
+                    required: headroom.required_xlm,
+                });
+            }
+
+            cancel.check()?;
+            report(OnboardingStep::Trustline, StepStatus::Started, None);
+            let trust = self.create_gns_trustline(public_key_hex, private_key_bytes, None, cancel).await?;
+            if !trust.success {
+                return Err(OnboardingError::Trustline(
+                    trust.error.unwrap_or_else(|| "Unknown error".to_string()),
+                ));
+            }
+            report(OnboardingStep::Trustline, StepStatus::Done, trust.hash);
+
+            report(OnboardingStep::Confirmation, StepStatus::Started, None);
+            let mut confirmed = false;
+            for _ in 0..CONFIRM_ATTEMPTS {
+                if self.has_gns_trustline(&stellar_address).await.unwrap_or(false) {
+                    confirmed = true;
+                    break;
+                }
+                tokio::time::sleep(CONFIRM_INTERVAL).await;
+            }
+            if !confirmed {
+                return Err(OnboardingError::NotConfirmed);
+            }
+            report(OnboardingStep::Confirmation, StepStatus::Done, None);
+            true
+        };
+
+        // Past a new trustline there is nothing left to cancel
+        let pending = if trustline_created {
+            self.get_gns_claimable_balances(&stellar_address).await?
+        } else {
+            cancel.guard(self.get_gns_claimable_balances(&stellar_address)).await??
+        };
+        let claims = if pending.is_empty() {
+            report(OnboardingStep::Claim, StepStatus::Skipped, Some("Nothing to claim".to_string()));
+            Vec::new()
+        } else {
+            if !trustline_created {
+                cancel.check()?;
+            }
+            report(
+                OnboardingStep::Claim,
+                StepStatus::Started,
+                Some(format!("Claiming {} balance(s)", pending.len())),
+            );
+            let no_cancel = CancelToken::new();
+            let claim_cancel = if trustline_created { &no_cancel } else { cancel };
+            let claims = self.claim_all_balances(public_key_hex, private_key_bytes, claim_cancel).await?;
+            report(OnboardingStep::Claim, StepStatus::Done, Some(claim_summary(&claims)));
+            claims
+        };
+
+        Ok(OnboardingResult {
+            trustline_created,
+            claims,
+            gns_balance: self.get_gns_balance(&stellar_address).await?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stellar::test_support::mock_stellar;
+    use crate::stellar::StellarConfig;
+    use gns_crypto_core::GnsIdentity;
+
+    fn account(config: &StellarConfig, xlm: &str, with_trustline: bool) -> serde_json::Value {
+        let mut balances = vec![serde_json::json!({ "balance": xlm, "asset_type": "native" })];
+        if with_trustline {
+            balances.push(serde_json::json!({
+                "balance": "0.0000000",
+                "asset_type": "credit_alphanum4",
+                "asset_code": config.gns_token_code,
+                "asset_issuer": config.gns_issuer,
+            }));
+        }
+        serde_json::json!({
+            "id": "G",
+            "sequence": "100",
+            "subentry_count": balances.len() - 1,
+            "balances": balances,
+        })
+    }
+
+    fn claimable(config: &StellarConfig, count: usize) -> serde_json::Value {
+        let records: Vec<_> = (0..count)
+            .map(|i| serde_json::json!({
+                "id": format!("00000000{:064x}", i + 1),
+                "asset": format!("{}:{}", config.gns_token_code, config.gns_issuer),
+                "amount": "100.0000000",
+                "sponsor": null,
+                "claimants": [],
+            }))
+            .collect();
+        serde_json::json!({ "_embedded": { "records": records } })
+    }
+
+    fn run_route(xlm: &'static str, with_trustline: bool, pending: usize) -> impl Fn(&str, &str) -> serde_json::Value {
+        let config = StellarConfig::testnet();
+        move |method, path| match (method, path) {
+            ("GET", p) if p.starts_with("/accounts/") => account(&config, xlm, with_trustline),
+            ("GET", p) if p.starts_with("/claimable_balances") => claimable(&config, pending),
+            ("POST", "/transactions") => serde_json::json!({ "successful": true, "hash": "claimhash" }),
+            _ => serde_json::json!({ "success": false, "error": "unexpected request" }),
+        }
+    }
+
+    fn keys() -> (String, Vec<u8>) {
+        let identity = GnsIdentity::generate();
+        (identity.public_key_hex(), hex::decode(identity.private_key_hex()).unwrap())
+    }
+
+    fn steps(progress: &[OnboardingProgress]) -> Vec<(OnboardingStep, StepStatus)> {
+        progress.iter().map(|p| (p.step, p.status)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_onboarding_already_complete_does_nothing() {
+        let (stellar, requests) = mock_stellar(run_route("5.0000000", true, 0)).await;
+        let (public_key, private_key) = keys();
+        let mut progress = Vec::new();
+
+        let result = stellar
+            .onboard_wallet(&public_key, &private_key, &CancelToken::new(), |p| progress.push(p))
+            .await
+            .unwrap();
+
+        assert!(!result.trustline_created);
+        assert!(result.claims.is_empty());
+        assert_eq!(
+            steps(&progress),
+            vec![
+                (OnboardingStep::Trustline, StepStatus::Skipped),
+                (OnboardingStep::Confirmation, StepStatus::Skipped),
+                (OnboardingStep::Claim, StepStatus::Skipped),
+            ]
+        );
+        assert!(requests.lock().unwrap().iter().all(|r| r.starts_with("GET ")));
+    }
+
+    #[tokio::test]
+    async fn test_onboarding_with_trustline_only_claims() {
+        let (stellar, requests) = mock_stellar(run_route("5.0000000", true, 2)).await;
+        let (public_key, private_key) = keys();
+        let mut progress = Vec::new();
+
+        let result = stellar
+            .onboard_wallet(&public_key, &private_key, &CancelToken::new(), |p| progress.push(p))
+            .await
+            .unwrap();
+
+        assert!(!result.trustline_created);
+        assert_eq!(result.claims.len(), 2);
+        assert!(result.claims.iter().all(|c| c.success));
+        assert_eq!(
+            steps(&progress),
+            vec![
+                (OnboardingStep::Trustline, StepStatus::Skipped),
+                (OnboardingStep::Confirmation, StepStatus::Skipped),
+                (OnboardingStep::Claim, StepStatus::Started),
+                (OnboardingStep::Claim, StepStatus::Done),
+            ]
+        );
+
+        // Nothing went to the backend's trustline endpoint
+        let posts: Vec<_> = requests.lock().unwrap().iter().filter(|r| r.starts_with("POST ")).cloned().collect();
+        assert_eq!(posts, vec!["POST /transactions".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_onboarding_reports_missing_reserve() {
+        let (stellar, requests) = mock_stellar(run_route("1.0000000", false, 1)).await;
+        let (public_key, private_key) = keys();
+        let mut progress = Vec::new();
+
+        let err = stellar
+            .onboard_wallet(&public_key, &private_key, &CancelToken::new(), |p| progress.push(p))
+            .await
+            .unwrap_err();
+
+        match err {
+            OnboardingError::InsufficientXlm { available, required } => {
+                assert_eq!(available, 1.0);
+                // Two base entries plus the trustline, and the fee margin
+                assert_eq!(required, 1.51);
+            }
+            other => panic!("expected InsufficientXlm, got {:?}", other),
+        }
+        assert!(progress.is_empty());
+        assert!(requests.lock().unwrap().iter().all(|r| r.starts_with("GET ")));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_onboarding_submits_nothing() {
+        let (stellar, requests) = mock_stellar(run_route("5.0000000", false, 2)).await;
+        let (public_key, private_key) = keys();
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let mut progress = Vec::new();
+
+        let err = stellar
+            .onboard_wallet(&public_key, &private_key, &cancel, |p| progress.push(p))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, OnboardingError::Stellar(StellarError::Cancelled)));
+        assert_eq!(err.kind(), "cancelled");
+        assert!(progress.is_empty());
+        assert!(requests.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,206 @@
+//! Transaction Outcomes
+//!
+//! Horizon reports a failed transaction with one code for the transaction
+//! and one per operation. For a batch (a claim sweep, say) that is what
+//! tells "this balance had expired" apart from "this one was fine but went
+//! down with it": transactions are atomic, so when any operation fails
+//! none of them take effect.
+
+use super::{BalanceClaimResult, HorizonTransactionResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How one operation in a submitted transaction fared
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpResult {
+    /// Position of the operation in the transaction
+    pub index: usize,
+    /// Horizon's result code, e.g. `op_success` or `op_cannot_claim`
+    pub code: String,
+    /// Whether the operation itself passed. In a rolled-back transaction
+    /// it still didn't take effect.
+    pub succeeded: bool,
+    pub reason: String,
+}
+
+/// Result of a submitted transaction, operation by operation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionOutcome {
+    pub successful: bool,
+    pub hash: Option<String>,
+    /// Transaction-level result code, e.g. `tx_failed`; none on success
+    pub code: Option<String>,
+    /// The transaction failed, so no operation in it took effect
+    pub rolled_back: bool,
+    pub operations: Vec<OpResult>,
+}
+
+impl TransactionOutcome {
+    /// Read Horizon's answer for a transaction of `op_count` operations.
+    ///
+    /// Horizon leaves out the operation codes when the transaction failed
+    /// before running them (a bad sequence number, too low a fee); those
+    /// operations get the transaction's code.
+    pub(super) fn from_horizon(response: HorizonTransactionResponse, op_count: usize) -> Self {
+        if response.successful == Some(true) {
+            return Self {
+                successful: true,
+                hash: response.hash,
+                code: None,
+                rolled_back: false,
+                operations: (0..op_count).map(|index| op_result(index, "op_success")).collect(),
+            };
+        }
+
+        let codes = response.extras.and_then(|e| e.result_codes);
+        let tx_code = codes.as_ref()
+            .and_then(|c| c.transaction.clone())
+            .unwrap_or_else(|| "tx_failed".to_string());
+        let op_codes = codes.and_then(|c| c.operations).unwrap_or_default();
+
+        Self {
+            successful: false,
+            hash: response.hash,
+            operations: (0..op_count)
+                .map(|index| op_result(index, op_codes.get(index).unwrap_or(&tx_code)))
+                .collect(),
+            code: Some(tx_code),
+            rolled_back: true,
+        }
+    }
+
+    /// Why the transaction failed: the first failing operation, if Horizon
+    /// named one, otherwise the transaction code
+    pub fn error(&self) -> Option<String> {
+        if self.successful {
+            return None;
+        }
+        Some(match self.operations.iter().find(|op| !op.succeeded) {
+            Some(op) => op.reason.clone(),
+            None => describe(self.code.as_deref().unwrap_or("tx_failed")).to_string(),
+        })
+    }
+}
+
+fn op_result(index: usize, code: &str) -> OpResult {
+    OpResult {
+        index,
+        code: code.to_string(),
+        succeeded: code == "op_success",
+        reason: describe(code).to_string(),
+    }
+}
+
+/// Plain-language meaning of a Horizon result code
+pub(super) fn describe(code: &str) -> &'static str {
+    match code {
+        "op_success" => "Succeeded",
+        "op_does_not_exist" => "Already claimed or no longer exists",
+        "op_cannot_claim" => "Expired or not claimable yet",
+        "op_line_full" => "Trustline limit reached",
+        "op_no_trust" => "Missing trustline",
+        "op_not_authorized" => "Not authorized by the issuer",
+        "op_underfunded" => "Not enough funds",
+        "op_low_reserve" => "Not enough XLM for the reserve",
+        "op_no_destination" => "Destination account doesn't exist",
+        "tx_bad_seq" => "Out of sequence, try again",
+        "tx_insufficient_fee" => "Network fee too low",
+        "tx_insufficient_balance" => "Not enough XLM for the fee",
+        "tx_too_late" => "Transaction expired before it was included",
+        _ => "Operation failed",
+    }
+}
+
+/// One line for a claim sweep, e.g. "Claimed 2 of 3 balance(s); 1 expired
+/// or not claimable yet"
+pub fn claim_summary(results: &[BalanceClaimResult]) -> String {
+    let claimed = results.iter().filter(|r| r.success).count();
+    let mut summary = format!("Claimed {} of {} balance(s)", claimed, results.len());
+
+    let mut failures: BTreeMap<String, usize> = BTreeMap::new();
+    for result in results.iter().filter(|r| !r.success) {
+        let reason = if result.rolled_back {
+            "rolled back with the rest of their batch".to_string()
+        } else {
+            result.error.as_deref().unwrap_or("failed").to_lowercase()
+        };
+        *failures.entry(reason).or_default() += 1;
+    }
+    for (reason, count) in failures {
+        summary.push_str(&format!("; {} {}", count, reason));
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn horizon(value: serde_json::Value) -> HorizonTransactionResponse {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_mixed_operation_codes() {
+        let response = horizon(serde_json::json!({
+            "successful": false,
+            "extras": { "result_codes": {
+                "transaction": "tx_failed",
+                "operations": ["op_success", "op_cannot_claim", "op_success"],
+            }},
+        }));
+        let outcome = TransactionOutcome::from_horizon(response, 3);
+
+        assert!(!outcome.successful && outcome.rolled_back);
+        assert_eq!(outcome.code.as_deref(), Some("tx_failed"));
+        let passed: Vec<bool> = outcome.operations.iter().map(|op| op.succeeded).collect();
+        assert_eq!(passed, vec![true, false, true]);
+        assert_eq!(outcome.operations[1].code, "op_cannot_claim");
+        assert_eq!(outcome.error().as_deref(), Some("Expired or not claimable yet"));
+    }
+
+    #[test]
+    fn test_missing_operation_codes_use_transaction_code() {
+        let response = horizon(serde_json::json!({
+            "successful": false,
+            "extras": { "result_codes": { "transaction": "tx_bad_seq" } },
+        }));
+        let outcome = TransactionOutcome::from_horizon(response, 2);
+        assert!(outcome.operations.iter().all(|op| !op.succeeded && op.code == "tx_bad_seq"));
+        assert_eq!(outcome.error().as_deref(), Some("Out of sequence, try again"));
+
+        let ok = TransactionOutcome::from_horizon(horizon(serde_json::json!({ "successful": true, "hash": "h" })), 2);
+        assert!(ok.successful && !ok.rolled_back);
+        assert!(ok.operations.iter().all(|op| op.succeeded));
+        assert_eq!(ok.error(), None);
+    }
+
+    #[test]
+    fn test_claim_summary() {
+        let result = |success, error: Option<&str>, rolled_back| BalanceClaimResult {
+            balance_id: String::new(),
+            amount: "1".to_string(),
+            success,
+            hash: None,
+            error: error.map(str::to_string),
+            code: None,
+            rolled_back,
+        };
+        let results = vec![
+            result(true, None, false),
+            result(true, None, false),
+            result(false, Some("Expired"), false),
+        ];
+        assert_eq!(claim_summary(&results), "Claimed 2 of 3 balance(s); 1 expired");
+
+        let results = vec![
+            result(false, Some("Expired or not claimable yet"), false),
+            result(false, Some("Rolled back"), true),
+            result(false, Some("Rolled back"), true),
+        ];
+        assert_eq!(
+            claim_summary(&results),
+            "Claimed 0 of 3 balance(s); 1 expired or not claimable yet; 2 rolled back with the rest of their batch"
+        );
+    }
+}
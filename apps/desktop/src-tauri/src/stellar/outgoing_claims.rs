@@ -0,0 +1,261 @@
+//! Outgoing Claimable Balances
+//!
+//! Balances this wallet created for someone else, e.g. GNS sent to a
+//! recipient without a trustline. Horizon lists them by sponsor. A balance
+//! that also names the sponsor as claimant, usually behind a "not before"
+//! predicate, can be taken back once that predicate allows it; until then
+//! it's only the recipient's to claim.
+
+use super::assets::transaction_result;
+use super::{
+    decode_account_id, predicate_allows, split_asset, HorizonClaimableBalance, HorizonClaimableBalancesResponse,
+    StellarError, StellarService, TransactionResult,
+};
+use serde::Serialize;
+
+/// Horizon's largest page size
+const MAX_PAGE_SIZE: u32 = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutgoingBalanceStatus {
+    /// Waiting for the recipient; the sponsor can't take it back (yet)
+    Pending,
+    /// The sponsor's own predicate allows it to reclaim the balance now
+    ClaimableBySponsor,
+}
+
+/// A claimable balance the wallet sponsors for another account
+#[derive(Debug, Clone, Serialize)]
+pub struct OutgoingClaimableBalance {
+    pub balance_id: String,
+    pub asset_code: String,
+    pub asset_issuer: Option<String>,
+    pub amount: String,
+    /// The account it was created for
+    pub claimant: Option<String>,
+    /// The claimant's predicate, i.e. when its claim expires
+    pub predicate: Option<serde_json::Value>,
+    /// When the sponsor may reclaim it; `None` if it isn't a claimant
+    pub sponsor_predicate: Option<serde_json::Value>,
+    pub status: OutgoingBalanceStatus,
+}
+
+impl OutgoingClaimableBalance {
+    /// Read a Horizon record of a balance sponsored by `sponsor`, as of
+    /// `now` (unix seconds)
+    fn from_horizon(record: HorizonClaimableBalance, sponsor: &str, now: i64) -> Self {
+        let (asset_code, asset_issuer) = split_asset(&record.asset);
+        let (sponsor_claims, other_claims): (Vec<_>, Vec<_>) =
+            record.claimants.into_iter().partition(|c| c.destination == sponsor);
+
+        let sponsor_predicate = sponsor_claims.into_iter().next().map(|c| c.predicate);
+        let status = match &sponsor_predicate {
+            Some(predicate) if predicate_allows(predicate, now) => OutgoingBalanceStatus::ClaimableBySponsor,
+            _ => OutgoingBalanceStatus::Pending,
+        };
+        let (claimant, predicate) = match other_claims.into_iter().next() {
+            Some(c) => (Some(c.destination), Some(c.predicate)),
+            None => (None, None),
+        };
+
+        Self {
+            balance_id: record.id,
+            asset_code,
+            asset_issuer,
+            amount: record.amount,
+            claimant,
+            predicate,
+            sponsor_predicate,
+            status,
+        }
+    }
+}
+
+impl StellarService {
+    /// Claimable balances sponsored by `stellar_address`, i.e. created by
+    /// it for others and not claimed yet
+    pub async fn get_outgoing_claimable_balances(
+        &self,
+        stellar_address: &str,
+    ) -> Result<Vec<OutgoingClaimableBalance>, StellarError> {
+        let url = format!(
+            "{}/claimable_balances?sponsor={}&limit={}",
+            self.config.horizon_url, stellar_address, MAX_PAGE_SIZE
+        );
+
+        let response = self.client.get(&url).send().await
+            .map_err(|e| StellarError::NetworkError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Ok(vec![]);
+        }
+
+        let data: HorizonClaimableBalancesResponse = response.json().await
+            .map_err(|e| StellarError::ParseError(e.to_string()))?;
+
+        let now = chrono::Utc::now().timestamp();
+        Ok(data.embedded.records.into_iter()
+            .map(|r| OutgoingClaimableBalance::from_horizon(r, stellar_address, now))
+            .collect())
+    }
+
+    /// Claim back a balance the wallet sponsors, once its predicate lets
+    /// the sponsor do so. Checked against Horizon first, so a balance that
+    /// isn't ours or isn't reclaimable yet is refused without submitting.
+    pub async fn reclaim_expired_balance(
+        &self,
+        public_key_hex: &str,
+        private_key_bytes: &[u8],
+        balance_id: &str,
+    ) -> Result<TransactionResult, StellarError> {
+        let stellar_address = Self::gns_key_to_stellar(public_key_hex)?;
+
+        let url = format!("{}/claimable_balances/{}", self.config.horizon_url, balance_id);
+        let response = self.client.get(&url).send().await
+            .map_err(|e| StellarError::NetworkError(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(StellarError::Validation(format!(
+                "Balance {} was already claimed or doesn't exist",
+                balance_id
+            )));
+        }
+        let record: HorizonClaimableBalance = response.json().await
+            .map_err(|e| StellarError::ParseError(e.to_string()))?;
+
+        if record.sponsor.as_deref() != Some(stellar_address.as_str()) {
+            return Err(StellarError::Validation(format!(
+                "Balance {} isn't sponsored by this wallet",
+                balance_id
+            )));
+        }
+        let balance = OutgoingClaimableBalance::from_horizon(record, &stellar_address, chrono::Utc::now().timestamp());
+        match (&balance.sponsor_predicate, balance.status) {
+            (_, OutgoingBalanceStatus::ClaimableBySponsor) => {}
+            (None, _) => {
+                return Err(StellarError::Validation(
+                    "This balance can only be claimed by its recipient".to_string(),
+                ))
+            }
+            (Some(_), _) => {
+                return Err(StellarError::Validation("This balance can't be reclaimed yet".to_string()))
+            }
+        }
+
+        let source = decode_account_id(&stellar_address)?;
+        let response = self
+            .submit_claim_batch(&stellar_address, source, &[balance_id], private_key_bytes)
+            .await?;
+        let result = transaction_result(response);
+        tracing::info!(success = result.success, "🪙 Reclaimed {} {} from {}", balance.amount, balance.asset_code, balance_id);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stellar::test_support::mock_stellar;
+    use crate::stellar::{build_claim_transaction, parse_balance_id, StellarConfig};
+
+    const SENDER_PK: &str = "ab01ab01ab01ab01ab01ab01ab01ab01ab01ab01ab01ab01ab01ab01ab01ab01";
+    const RECIPIENT: &str = "GRECIPIENT";
+
+    fn balance_id(n: u32) -> String {
+        format!("00000000{:064x}", n)
+    }
+
+    /// Balance 1 goes back to us after a past date, 2 only after a future
+    /// one, 3 never
+    fn sponsored(sender: &str) -> serde_json::Value {
+        let config = StellarConfig::testnet();
+        let not_before = |t: i64| serde_json::json!({ "not": { "abs_before_epoch": t.to_string() } });
+        let record = |n: u32, claimants: serde_json::Value| {
+            serde_json::json!({
+                "id": balance_id(n),
+                "asset": format!("{}:{}", config.gns_token_code, config.gns_issuer),
+                "amount": "25.0000000",
+                "sponsor": sender,
+                "claimants": claimants,
+            })
+        };
+        let to_recipient = serde_json::json!({
+            "destination": RECIPIENT,
+            "predicate": { "abs_before_epoch": "4102444800" },
+        });
+        serde_json::json!({ "_embedded": { "records": [
+            record(1, serde_json::json!([to_recipient, { "destination": sender, "predicate": not_before(1_000) }])),
+            record(2, serde_json::json!([{ "destination": sender, "predicate": not_before(4_102_444_800) }, to_recipient])),
+            record(3, serde_json::json!([to_recipient])),
+        ] } })
+    }
+
+    #[tokio::test]
+    async fn test_sponsored_balances_are_parsed() {
+        let sender = StellarService::gns_key_to_stellar(SENDER_PK).unwrap();
+        let body = sponsored(&sender);
+        let (stellar, requests) = mock_stellar(move |_, _| body.clone()).await;
+
+        let balances = stellar.get_outgoing_claimable_balances(&sender).await.unwrap();
+
+        let statuses: Vec<_> = balances.iter().map(|b| (b.balance_id.clone(), b.status)).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (balance_id(1), OutgoingBalanceStatus::ClaimableBySponsor),
+                (balance_id(2), OutgoingBalanceStatus::Pending),
+                (balance_id(3), OutgoingBalanceStatus::Pending),
+            ]
+        );
+        for balance in &balances {
+            assert_eq!(balance.claimant.as_deref(), Some(RECIPIENT));
+            assert_eq!(balance.predicate, Some(serde_json::json!({ "abs_before_epoch": "4102444800" })));
+            assert_eq!((balance.asset_code.as_str(), balance.amount.as_str()), ("GNS", "25.0000000"));
+        }
+        assert!(balances[2].sponsor_predicate.is_none());
+
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].contains(&format!("sponsor={}", sender)), "{}", requests[0]);
+    }
+
+    #[tokio::test]
+    async fn test_reclaim_only_submits_when_allowed() {
+        use base64::Engine;
+        use stellar_xdr::curr::{Limits, OperationBody, ReadXdr, TransactionEnvelope};
+
+        let sender = StellarService::gns_key_to_stellar(SENDER_PK).unwrap();
+        let body = sponsored(&sender);
+        let (stellar, requests) = mock_stellar(move |_, path| {
+            let id = path.trim_start_matches("/claimable_balances/");
+            body["_embedded"]["records"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|r| r["id"] == id)
+                .cloned()
+                .unwrap_or(serde_json::Value::Null)
+        })
+        .await;
+
+        for n in [2, 3, 9] {
+            assert!(stellar.reclaim_expired_balance(SENDER_PK, &[1; 32], &balance_id(n)).await.is_err());
+        }
+        assert!(requests.lock().unwrap().iter().all(|r| r.starts_with("GET /claimable_balances/")));
+
+        // What gets submitted for balance 1
+        let source = decode_account_id(&sender).unwrap();
+        let xdr = build_claim_transaction(source, 43, &[&balance_id(1)]).unwrap();
+        let bytes = base64::engine::general_purpose::STANDARD.decode(xdr).unwrap();
+        let TransactionEnvelope::Tx(envelope) = TransactionEnvelope::from_xdr(bytes, Limits::none()).unwrap() else {
+            panic!("expected a v1 envelope");
+        };
+        assert_eq!(envelope.tx.seq_num.0, 43);
+        let [operation] = envelope.tx.operations.as_slice() else {
+            panic!("expected one operation");
+        };
+        let OperationBody::ClaimClaimableBalance(claim) = &operation.body else {
+            panic!("expected a claim");
+        };
+        assert_eq!(claim.balance_id, parse_balance_id(&balance_id(1)).unwrap());
+    }
+}
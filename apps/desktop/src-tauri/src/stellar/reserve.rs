@@ -0,0 +1,161 @@
+//! Reserve Headroom
+//!
+//! Every ledger entry an account owns (trustlines, offers, signers, data
+//! entries, and claimable balances it sponsors) locks another base reserve
+//! of XLM. An account sitting near its minimum balance can't add one: the
+//! transaction fails with an opaque `op_low_reserve`, or goes through and
+//! leaves nothing spare for fees. Checking the headroom first lets the UI
+//! say how much XLM to add instead.
+//!
+//! Math is done in stroops so the reserve boundary is exact.
+
+use super::{HorizonAccount, StellarError, StellarService};
+use serde::Serialize;
+
+/// XLM locked per ledger entry the account owns (plus two for the account)
+const BASE_RESERVE_STROOPS: i64 = 5_000_000;
+
+/// Left over for the transaction fee
+const FEE_MARGIN_STROOPS: i64 = 100_000;
+
+/// `xlm_to_add` is rounded up to a multiple of this (0.1 XLM)
+const TOP_UP_STEP_STROOPS: i64 = 1_000_000;
+
+const STROOPS_PER_XLM: f64 = 10_000_000.0;
+
+/// Whether an account can afford `added_subentries` more ledger entries
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReserveHeadroom {
+    pub stellar_address: String,
+    pub added_subentries: u32,
+    pub xlm_balance: f64,
+    pub minimum_balance: f64,
+    /// Minimum balance once the new entries exist
+    pub new_minimum_balance: f64,
+    /// XLM spendable now, above the minimum and any selling liabilities
+    pub available_xlm: f64,
+    /// Balance needed for the operation: the new minimum, liabilities and
+    /// a fee margin
+    pub required_xlm: f64,
+    pub affordable: bool,
+    /// How far the balance falls short of `required_xlm`; zero if affordable
+    pub shortfall_xlm: f64,
+    /// The shortfall rounded up to a tenth of an XLM, to suggest adding
+    pub xlm_to_add: f64,
+}
+
+impl ReserveHeadroom {
+    pub(super) fn for_account(stellar_address: &str, account: &HorizonAccount, added_subentries: u32) -> Self {
+        let native = account.balances.iter().find(|b| b.asset_type == "native");
+        let balance = native.and_then(|b| parse_stroops(&b.balance)).unwrap_or(0);
+        let liabilities = native
+            .and_then(|b| b.selling_liabilities.as_deref())
+            .and_then(parse_stroops)
+            .unwrap_or(0);
+
+        let entries = 2 + account.subentry_count as i64 + account.num_sponsoring as i64
+            - account.num_sponsored as i64;
+        headroom(stellar_address, added_subentries, balance, liabilities, entries.max(2))
+    }
+
+    /// A friendly explanation when the operation can't be afforded
+    pub fn warning(&self) -> Option<String> {
+        if self.affordable {
+            return None;
+        }
+        Some(format!(
+            "This needs {} XLM in your wallet to cover the network reserve, but it holds {} XLM. \
+             Add at least {} XLM and try again.",
+            self.required_xlm, self.xlm_balance, self.xlm_to_add
+        ))
+    }
+}
+
+impl StellarService {
+    /// Check whether `stellar_address` can afford `added_subentries` more
+    /// ledger entries, e.g. 1 for a trustline or a claimable balance with
+    /// one claimant
+    pub async fn reserve_headroom(
+        &self,
+        stellar_address: &str,
+        added_subentries: u32,
+    ) -> Result<ReserveHeadroom, StellarError> {
+        let account = self.get_account(stellar_address).await?;
+        Ok(ReserveHeadroom::for_account(stellar_address, &account, added_subentries))
+    }
+}
+
+fn headroom(stellar_address: &str, added_subentries: u32, balance: i64, liabilities: i64, entries: i64) -> ReserveHeadroom {
+    let minimum = entries * BASE_RESERVE_STROOPS;
+    let new_minimum = (entries + added_subentries as i64) * BASE_RESERVE_STROOPS;
+    let required = new_minimum + liabilities + FEE_MARGIN_STROOPS;
+    let shortfall = (required - balance).max(0);
+    let to_add = (shortfall + TOP_UP_STEP_STROOPS - 1) / TOP_UP_STEP_STROOPS * TOP_UP_STEP_STROOPS;
+
+    ReserveHeadroom {
+        stellar_address: stellar_address.to_string(),
+        added_subentries,
+        xlm_balance: xlm(balance),
+        minimum_balance: xlm(minimum),
+        new_minimum_balance: xlm(new_minimum),
+        available_xlm: xlm((balance - minimum - liabilities).max(0)),
+        required_xlm: xlm(required),
+        affordable: shortfall == 0,
+        shortfall_xlm: xlm(shortfall),
+        xlm_to_add: xlm(to_add),
+    }
+}
+
+fn xlm(stroops: i64) -> f64 {
+    stroops as f64 / STROOPS_PER_XLM
+}
+
+/// Parse a Horizon amount ("12.3456789") into stroops
+fn parse_stroops(amount: &str) -> Option<i64> {
+    let (whole, fraction) = amount.trim().split_once('.').unwrap_or((amount.trim(), ""));
+    if fraction.len() > 7 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let whole: i64 = whole.parse().ok()?;
+    let fraction: i64 = format!("{:0<7}", fraction).parse().ok()?;
+    whole.checked_mul(10_000_000)?.checked_add(fraction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headroom_at_reserve_boundary() {
+        // Bare account: 2 entries, 1 XLM minimum; a trustline needs 1.51 XLM
+        let exact = headroom("G", 1, 15_100_000, 0, 2);
+        assert!(exact.affordable);
+        assert_eq!((exact.minimum_balance, exact.new_minimum_balance), (1.0, 1.5));
+        assert_eq!(exact.available_xlm, 0.51);
+        assert_eq!((exact.shortfall_xlm, exact.xlm_to_add), (0.0, 0.0));
+        assert!(exact.warning().is_none());
+
+        let one_stroop_short = headroom("G", 1, 15_099_999, 0, 2);
+        assert!(!one_stroop_short.affordable);
+        assert_eq!(one_stroop_short.shortfall_xlm, 0.0000001);
+        assert_eq!(one_stroop_short.xlm_to_add, 0.1);
+        assert!(one_stroop_short.warning().unwrap().contains("Add at least 0.1 XLM"));
+
+        // Below the current minimum nothing is available, and liabilities
+        // and existing entries raise what's needed
+        let underfunded = headroom("G", 2, 5_000_000, 10_000_000, 3);
+        assert_eq!(underfunded.available_xlm, 0.0);
+        assert_eq!(underfunded.required_xlm, 3.51);
+        assert_eq!(underfunded.shortfall_xlm, 3.01);
+        assert_eq!(underfunded.xlm_to_add, 3.1);
+    }
+
+    #[test]
+    fn test_parse_stroops() {
+        assert_eq!(parse_stroops("1.5100000"), Some(15_100_000));
+        assert_eq!(parse_stroops("0.0000001"), Some(1));
+        assert_eq!(parse_stroops("42"), Some(420_000_000));
+        assert_eq!(parse_stroops("1.00000001"), None);
+        assert_eq!(parse_stroops("abc"), None);
+    }
+}
@@ -0,0 +1,108 @@
+//! Send Failures
+//!
+//! A few of the operation codes a failed send can come back with have an
+//! obvious next step for the user: add the missing trustline, top up,
+//! fund the recipient's account first. Those are picked out of the error,
+//! whether it came from Horizon (`tx_failed (op_no_trust)`) or from the
+//! backend, so the UI can offer that step instead of only showing the
+//! code.
+
+use super::outcome::describe;
+use serde::{Deserialize, Serialize};
+
+/// A send failure the user can do something about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SendFailure {
+    /// `op_no_trust`: the asset has no trustline to move along
+    NoTrust,
+    /// `op_underfunded`: the sender holds less than the amount
+    Underfunded,
+    /// `op_no_destination`: the recipient account doesn't exist yet
+    NoDestination,
+    /// `op_line_full`: the amount would take the recipient past its
+    /// trustline limit
+    LineFull,
+}
+
+/// What the UI offers for a [`SendFailure`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Remediation {
+    /// Create the trustline and send again
+    CreateTrustline,
+    AddFunds,
+    /// Create (fund) the recipient's account, or send a claimable balance
+    CreateAccountFirst,
+    /// Nothing to do on our side; the recipient has to raise its limit
+    RecipientLineFull,
+}
+
+impl SendFailure {
+    /// The failure for a Horizon operation result code, if it's one we
+    /// have a remedy for
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "op_no_trust" => Some(Self::NoTrust),
+            "op_underfunded" => Some(Self::Underfunded),
+            "op_no_destination" => Some(Self::NoDestination),
+            "op_line_full" => Some(Self::LineFull),
+            _ => None,
+        }
+    }
+
+    /// Find a known operation code anywhere in an error message
+    pub fn from_error(error: &str) -> Option<Self> {
+        error
+            .to_ascii_lowercase()
+            .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .find_map(Self::from_code)
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::NoTrust => "op_no_trust",
+            Self::Underfunded => "op_underfunded",
+            Self::NoDestination => "op_no_destination",
+            Self::LineFull => "op_line_full",
+        }
+    }
+
+    pub fn remediation(self) -> Remediation {
+        match self {
+            Self::NoTrust => Remediation::CreateTrustline,
+            Self::Underfunded => Remediation::AddFunds,
+            Self::NoDestination => Remediation::CreateAccountFirst,
+            Self::LineFull => Remediation::RecipientLineFull,
+        }
+    }
+
+    pub fn message(self) -> &'static str {
+        describe(self.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_result_codes_map_to_remediation() {
+        let cases = [
+            ("tx_failed (op_no_trust)", Remediation::CreateTrustline),
+            ("op_underfunded", Remediation::AddFunds),
+            ("Transaction failed: tx_failed, operations: [op_no_destination]", Remediation::CreateAccountFirst),
+            ("OP_LINE_FULL", Remediation::RecipientLineFull),
+        ];
+        for (error, remediation) in cases {
+            let failure = SendFailure::from_error(error).unwrap_or_else(|| panic!("no failure in {:?}", error));
+            assert_eq!(failure.remediation(), remediation, "{}", error);
+            assert_eq!(SendFailure::from_code(failure.code()), Some(failure));
+        }
+
+        for error in ["tx_bad_seq", "op_src_no_trust", "tx_failed (op_low_reserve)", "Unknown error", ""] {
+            assert_eq!(SendFailure::from_error(error), None, "{}", error);
+        }
+        assert_eq!(SendFailure::NoDestination.message(), "Destination account doesn't exist");
+    }
+}
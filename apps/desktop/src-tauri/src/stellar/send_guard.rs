@@ -0,0 +1,125 @@
+//! Send Guards
+//!
+//! Looks at a destination before anything leaves the wallet. Sending to
+//! yourself only wastes the fee, so it goes ahead with a warning. Paying
+//! an asset back to its issuer burns it for good, so that waits for the
+//! user to confirm. Addresses that aren't valid Stellar accounts are
+//! refused outright.
+
+use super::{decode_account_id, Asset, StellarError, TransactionResult};
+use serde::{Deserialize, Serialize};
+
+/// Something odd about a send's destination, sent to the UI as
+/// `{ kind, ... }`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SendWarning {
+    /// The recipient is the sending account
+    SelfSend,
+    /// The recipient issued the asset, which burns whatever it receives
+    IssuerDestination { asset_code: String },
+}
+
+impl SendWarning {
+    pub fn message(&self) -> String {
+        match self {
+            Self::SelfSend => "You are sending to your own address; only the network fee will be spent".to_string(),
+            Self::IssuerDestination { asset_code } => format!(
+                "The recipient is the {} issuer - anything sent there is burned and can't be recovered",
+                asset_code
+            ),
+        }
+    }
+
+    /// Whether the send waits for the user to confirm it
+    pub fn needs_confirmation(&self) -> bool {
+        matches!(self, Self::IssuerDestination { .. })
+    }
+}
+
+/// What to do with a send after [`check_destination`]
+#[derive(Debug)]
+pub(super) enum Destination {
+    /// Go ahead, attaching the warning (if any) to the result
+    Allowed(Option<SendWarning>),
+    /// Stop and return this result until the user confirms
+    Blocked(TransactionResult),
+}
+
+/// Check a send of `asset` from `sender_address` to `recipient_address`.
+/// `confirmed` lets through a send that would otherwise be blocked.
+pub(super) fn check_destination(
+    sender_address: &str,
+    recipient_address: &str,
+    asset: &Asset,
+    confirmed: bool,
+) -> Result<Destination, StellarError> {
+    decode_account_id(recipient_address)
+        .map_err(|e| StellarError::Validation(format!("Invalid destination {}: {}", recipient_address, e)))?;
+
+    let warning = if recipient_address == sender_address {
+        Some(SendWarning::SelfSend)
+    } else if recipient_address == asset.issuer {
+        Some(SendWarning::IssuerDestination { asset_code: asset.code.clone() })
+    } else {
+        None
+    };
+
+    Ok(match warning {
+        Some(warning) if warning.needs_confirmation() && !confirmed => {
+            Destination::Blocked(TransactionResult::blocked(warning))
+        }
+        warning => Destination::Allowed(warning),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stellar::{StellarConfig, StellarService};
+
+    fn address(seed: u8) -> String {
+        StellarService::gns_key_to_stellar(&hex::encode([seed; 32])).unwrap()
+    }
+
+    #[test]
+    fn test_self_send_warns_without_blocking() {
+        let gns = StellarConfig::testnet().gns_asset();
+        let me = address(1);
+
+        match check_destination(&me, &me, &gns, false).unwrap() {
+            Destination::Allowed(warning) => assert_eq!(warning, Some(SendWarning::SelfSend)),
+            other => panic!("expected Allowed, got {:?}", other),
+        }
+        match check_destination(&me, &address(2), &gns, false).unwrap() {
+            Destination::Allowed(warning) => assert_eq!(warning, None),
+            other => panic!("expected Allowed, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_issuer_destination_needs_confirmation() {
+        let gns = StellarConfig::testnet().gns_asset();
+        let me = address(1);
+        let expected = SendWarning::IssuerDestination { asset_code: gns.code.clone() };
+
+        match check_destination(&me, &gns.issuer, &gns, false).unwrap() {
+            Destination::Blocked(result) => {
+                assert!(!result.success && result.hash.is_none());
+                assert_eq!(result.warning, Some(expected.clone()));
+                assert_eq!(result.error, Some(expected.message()));
+            }
+            other => panic!("expected Blocked, got {:?}", other),
+        }
+        match check_destination(&me, &gns.issuer, &gns, true).unwrap() {
+            Destination::Allowed(warning) => assert_eq!(warning, Some(expected)),
+            other => panic!("expected Allowed, got {:?}", other),
+        }
+
+        // Not an account at all: refused even when confirmed
+        let mut broken = address(2);
+        broken.replace_range(55.., if broken.ends_with('A') { "B" } else { "A" });
+        assert!(check_destination(&me, &broken, &gns, true).is_err());
+        assert!(check_destination(&me, "not-an-address", &gns, true).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+//! Asset Verification (SEP-1)
+//!
+//! Anyone can issue an asset called GNS. What ties the real one to us is
+//! that its issuer account names a home domain, and that domain's
+//! `/.well-known/stellar.toml` lists the asset under `[[CURRENCIES]]` with
+//! the same code and issuer. It takes both sides agreeing, so a look-alike
+//! issuer that points its home domain at ours still fails the check.
+
+use super::{GnsAssetInfo, StellarError, StellarService};
+use serde::{Deserialize, Serialize};
+
+/// SEP-1 caps a stellar.toml at 100 KB
+const MAX_TOML_BYTES: usize = 100 * 1024;
+
+/// Whether the GNS asset is listed in its issuer's stellar.toml, with the
+/// metadata declared there
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AssetVerification {
+    pub code: String,
+    pub issuer: String,
+    pub home_domain: Option<String>,
+    pub verified: bool,
+    /// Why the asset isn't verified
+    pub reason: Option<String>,
+    pub name: Option<String>,
+    pub image: Option<String>,
+    pub description: Option<String>,
+}
+
+impl AssetVerification {
+    fn unverified(info: &GnsAssetInfo, reason: impl Into<String>) -> Self {
+        Self {
+            code: info.code.clone(),
+            issuer: info.issuer.clone(),
+            home_domain: info.home_domain.clone(),
+            verified: false,
+            reason: Some(reason.into()),
+            name: None,
+            image: None,
+            description: None,
+        }
+    }
+}
+
+/// The part of a stellar.toml we read; everything else is ignored
+#[derive(Debug, Deserialize)]
+struct StellarToml {
+    #[serde(rename = "CURRENCIES", default)]
+    currencies: Vec<TomlCurrency>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TomlCurrency {
+    code: Option<String>,
+    issuer: Option<String>,
+    name: Option<String>,
+    image: Option<String>,
+    desc: Option<String>,
+}
+
+impl StellarService {
+    /// Check the GNS asset against the stellar.toml at its issuer's home
+    /// domain.
+    ///
+    /// A missing home domain, a missing or malformed TOML, or a TOML that
+    /// doesn't list the asset comes back unverified with a reason; only
+    /// failing to reach the domain at all is an error. A verified result
+    /// is cached for the lifetime of this service, anything else is
+    /// checked again on the next call.
+    pub async fn verify_gns_asset_toml(&self) -> Result<AssetVerification, StellarError> {
+        if let Some(verification) = self.asset_toml.get() {
+            return Ok(verification.clone());
+        }
+
+        let info = self.gns_asset_info().await?;
+        let Some(domain) = info.home_domain.as_deref() else {
+            return Ok(AssetVerification::unverified(&info, "The GNS issuer has no home domain set"));
+        };
+        if !is_bare_domain(domain) {
+            return Ok(AssetVerification::unverified(
+                &info,
+                format!("The issuer's home domain \"{}\" is not a domain name", domain),
+            ));
+        }
+
+        let verification = match self.fetch_stellar_toml(domain).await? {
+            Ok(text) => check_stellar_toml(&info, &text),
+            Err(reason) => AssetVerification::unverified(&info, reason),
+        };
+        if verification.verified {
+            let _ = self.asset_toml.set(verification.clone());
+        }
+        Ok(verification)
+    }
+
+    /// The stellar.toml text, or why there isn't a usable one
+    async fn fetch_stellar_toml(&self, domain: &str) -> Result<Result<String, String>, StellarError> {
+        let url = format!("https://{}/.well-known/stellar.toml", domain);
+        let response = self.client.get(&url).send().await
+            .map_err(|e| StellarError::NetworkError(format!("Could not reach {}: {}", domain, e)))?;
+
+        let status = response.status();
+        if status.is_server_error() {
+            return Err(StellarError::NetworkError(format!("{} returned {}", url, status)));
+        }
+        if !status.is_success() {
+            return Ok(Err(format!("No stellar.toml at {} ({})", url, status)));
+        }
+        if response.content_length().is_some_and(|len| len as usize > MAX_TOML_BYTES) {
+            return Ok(Err(format!("The stellar.toml at {} is larger than 100 KB", url)));
+        }
+
+        let body = response.bytes().await
+            .map_err(|e| StellarError::NetworkError(e.to_string()))?;
+        if body.len() > MAX_TOML_BYTES {
+            return Ok(Err(format!("The stellar.toml at {} is larger than 100 KB", url)));
+        }
+        Ok(String::from_utf8(body.to_vec())
+            .map_err(|_| format!("The stellar.toml at {} is not UTF-8 text", url)))
+    }
+}
+
+/// Look the asset up in a stellar.toml
+fn check_stellar_toml(info: &GnsAssetInfo, text: &str) -> AssetVerification {
+    let toml: StellarToml = match toml::from_str(text) {
+        Ok(toml) => toml,
+        Err(e) => return AssetVerification::unverified(info, format!("The issuer's stellar.toml is invalid: {}", e.message())),
+    };
+
+    let listed = toml.currencies.into_iter().find(|currency| {
+        currency.code.as_deref() == Some(info.code.as_str())
+            && currency.issuer.as_deref() == Some(info.issuer.as_str())
+    });
+    match listed {
+        Some(currency) => AssetVerification {
+            code: info.code.clone(),
+            issuer: info.issuer.clone(),
+            home_domain: info.home_domain.clone(),
+            verified: true,
+            reason: None,
+            name: currency.name,
+            image: currency.image,
+            description: currency.desc,
+        },
+        None => AssetVerification::unverified(
+            info,
+            format!("The issuer's stellar.toml does not list {} issued by {}", info.code, info.issuer),
+        ),
+    }
+}
+
+/// A host name only: no scheme, port, path or credentials
+fn is_bare_domain(domain: &str) -> bool {
+    !domain.is_empty()
+        && domain.contains('.')
+        && domain.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stellar::StellarNetwork;
+
+    const ISSUER: &str = "GBVZTFST4PIPV5C3APDIVULNZYZENQSLGDSOKOVQI77GSMT6WVYGF5GL";
+    const LOOKALIKE: &str = "GAQ2FVBZH5J6S3L6JY6TZ5A3F6V6W6GSBJHVLNZSAKVBHE5BRZBQ4QUL";
+
+    const SAMPLE_TOML: &str = r#"
+VERSION = "2.7.0"
+NETWORK_PASSPHRASE = "Public Global Stellar Network ; September 2015"
+ACCOUNTS = ["GBVZTFST4PIPV5C3APDIVULNZYZENQSLGDSOKOVQI77GSMT6WVYGF5GL"]
+
+[DOCUMENTATION]
+ORG_NAME = "GNS Foundation"
+ORG_URL = "https://gns.earth"
+
+[[CURRENCIES]]
+code = "USDC"
+issuer = "GA5ZSEJYB37JRC5AVCIA5MOP4RHTM335X2KGX3IHOJAPP5RE34K4KZVN"
+display_decimals = 2
+
+[[CURRENCIES]]
+code = "GNS"
+issuer = "GBVZTFST4PIPV5C3APDIVULNZYZENQSLGDSOKOVQI77GSMT6WVYGF5GL"
+name = "GNS Token"
+desc = "Utility token of the GNS identity network"
+image = "https://gns.earth/images/gns-token.png"
+is_asset_anchored = false
+"#;
+
+    fn info(issuer: &str) -> GnsAssetInfo {
+        GnsAssetInfo {
+            code: "GNS".to_string(),
+            issuer: issuer.to_string(),
+            network: StellarNetwork::Mainnet,
+            home_domain: Some("gns.earth".to_string()),
+            auth_required: false,
+            auth_revocable: false,
+            auth_immutable: false,
+            clawback_enabled: false,
+        }
+    }
+
+    #[test]
+    fn test_sample_stellar_toml() {
+        let verification = check_stellar_toml(&info(ISSUER), SAMPLE_TOML);
+        assert!(verification.verified, "{:?}", verification.reason);
+        assert_eq!(verification.name.as_deref(), Some("GNS Token"));
+        assert_eq!(verification.description.as_deref(), Some("Utility token of the GNS identity network"));
+        assert_eq!(verification.image.as_deref(), Some("https://gns.earth/images/gns-token.png"));
+
+        // Same code from another issuer isn't listed
+        let lookalike = check_stellar_toml(&info(LOOKALIKE), SAMPLE_TOML);
+        assert!(!lookalike.verified);
+        assert!(lookalike.reason.unwrap().contains("does not list GNS"));
+        assert_eq!(lookalike.name, None);
+    }
+
+    #[test]
+    fn test_invalid_stellar_toml() {
+        let broken = check_stellar_toml(&info(ISSUER), "[[CURRENCIES]\ncode = \"GNS\"");
+        assert!(!broken.verified);
+        assert!(broken.reason.unwrap().contains("invalid"));
+
+        // An HTML page served in its place
+        let html = check_stellar_toml(&info(ISSUER), "<html><body>Not Found</body></html>");
+        assert!(!html.verified);
+
+        let empty = check_stellar_toml(&info(ISSUER), "");
+        assert!(!empty.verified);
+        assert!(empty.reason.unwrap().contains("does not list"));
+    }
+
+    #[test]
+    fn test_is_bare_domain() {
+        assert!(is_bare_domain("gns.earth"));
+        assert!(is_bare_domain("stellar.gns-foundation.org"));
+        assert!(!is_bare_domain("https://gns.earth"));
+        assert!(!is_bare_domain("gns.earth/evil"));
+        assert!(!is_bare_domain("user@gns.earth"));
+        assert!(!is_bare_domain("localhost"));
+        assert!(!is_bare_domain(""));
+    }
+}
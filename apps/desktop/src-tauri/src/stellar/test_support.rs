@@ -0,0 +1,68 @@
+//! Test helpers shared by the Stellar modules
+
+use super::{StellarConfig, StellarService};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Stand-in for Horizon and the Stellar backend. Answers every request
+/// with `route(method, path)` and records `"METHOD path"`. A `null` answer
+/// becomes a 404, as Horizon gives for unknown accounts.
+pub(super) async fn mock_stellar<F>(route: F) -> (StellarService, Arc<Mutex<Vec<String>>>)
+where
+    F: Fn(&str, &str) -> serde_json::Value + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let seen = requests.clone();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = Vec::new();
+            let mut chunk = [0u8; 4096];
+            let header_end = loop {
+                let n = socket.read(&mut chunk).await.unwrap();
+                buf.extend_from_slice(&chunk[..n]);
+                if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break pos + 4;
+                }
+            };
+            let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+            let content_length: usize = head.lines()
+                .filter_map(|l| l.split_once(':'))
+                .find(|(k, _)| k.trim().eq_ignore_ascii_case("content-length"))
+                .and_then(|(_, v)| v.trim().parse().ok())
+                .unwrap_or(0);
+            while buf.len() < header_end + content_length {
+                let n = socket.read(&mut chunk).await.unwrap();
+                buf.extend_from_slice(&chunk[..n]);
+            }
+
+            let mut request_line = head.lines().next().unwrap_or_default().split(' ');
+            let method = request_line.next().unwrap_or_default().to_string();
+            let path = request_line.next().unwrap_or_default().to_string();
+            seen.lock().unwrap().push(format!("{} {}", method, path));
+
+            let (status, body) = match route(&method, &path) {
+                serde_json::Value::Null => ("404 Not Found", r#"{"status":404}"#.to_string()),
+                body => ("200 OK", body.to_string()),
+            };
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+
+    let config = StellarConfig {
+        horizon_url: base_url.clone(),
+        backend_url: Some(format!("{}/backend", base_url)),
+        ..StellarConfig::testnet()
+    };
+    (StellarService::new(config), requests)
+}
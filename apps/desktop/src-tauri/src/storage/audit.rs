@@ -0,0 +1,418 @@
+//! Security Audit Log
+//!
+//! A local, append-only record of what this device did with the identity
+//! key: signatures, exports, key moves, payments and handle claims. It is
+//! for the user, not for debugging, so it lives in the database rather
+//! than the app log.
+//!
+//! Each entry is hash-chained to the one before it, the same way
+//! breadcrumbs chain with `prev_hash`. An exported log is signed over its
+//! head hash and length, so dropping, inserting or editing any entry -
+//! including the last one - breaks verification.
+
+use super::{Database, DatabaseError};
+use gns_crypto_core::GnsIdentity;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const AUDIT_EXPORT_VERSION: u32 = 1;
+
+/// `prev_hash` of the first entry
+pub const AUDIT_GENESIS_HASH: &str =
+    "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    SignMessage,
+    ExportIdentity,
+    ImportIdentity,
+    DeleteIdentity,
+    CreateMigrationToken,
+    ConsumeMigrationToken,
+    SendGns,
+    SendAsset,
+    ReserveHandle,
+    ClaimHandle,
+    ReleaseHandle,
+    ExportTranscript,
+    RotateEncryptionKey,
+    RotateSigningKey,
+    RevokeDevice,
+    SignTypedData,
+}
+
+impl AuditAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::SignMessage => "sign_message",
+            Self::ExportIdentity => "export_identity",
+            Self::ImportIdentity => "import_identity",
+            Self::DeleteIdentity => "delete_identity",
+            Self::CreateMigrationToken => "create_migration_token",
+            Self::ConsumeMigrationToken => "consume_migration_token",
+            Self::SendGns => "send_gns",
+            Self::SendAsset => "send_asset",
+            Self::ReserveHandle => "reserve_handle",
+            Self::ClaimHandle => "claim_handle",
+            Self::ReleaseHandle => "release_handle",
+            Self::ExportTranscript => "export_transcript",
+            Self::RotateEncryptionKey => "rotate_encryption_key",
+            Self::RotateSigningKey => "rotate_signing_key",
+            Self::RevokeDevice => "revoke_device",
+            Self::SignTypedData => "sign_typed_data",
+        }
+    }
+}
+
+/// One link in the audit chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// 1-based position in the chain
+    pub seq: i64,
+    pub timestamp: i64,
+    pub action: String,
+    /// What the action applied to (recipient, handle, key prefix...)
+    pub target: Option<String>,
+    /// "ok", or the error that stopped the action
+    pub result: String,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    fn compute_hash(
+        seq: i64,
+        timestamp: i64,
+        action: &str,
+        target: Option<&str>,
+        result: &str,
+        prev_hash: &str,
+    ) -> String {
+        let mut hasher = Sha256::new();
+        // Length-prefix the free-text fields so they can't bleed into each other
+        for field in [action, target.unwrap_or(""), result] {
+            hasher.update((field.len() as u64).to_be_bytes());
+            hasher.update(field.as_bytes());
+        }
+        hasher.update(seq.to_be_bytes());
+        hasher.update(timestamp.to_be_bytes());
+        hasher.update(prev_hash.as_bytes());
+        hex::encode(hasher.finalize())
+    }
+
+    fn expected_hash(&self) -> String {
+        Self::compute_hash(
+            self.seq,
+            self.timestamp,
+            &self.action,
+            self.target.as_deref(),
+            &self.result,
+            &self.prev_hash,
+        )
+    }
+}
+
+/// Why an audit chain failed to verify
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AuditChainError {
+    #[error("Entry {0} is out of sequence (missing or inserted entries)")]
+    OutOfSequence(i64),
+
+    #[error("Entry {0} does not link to the entry before it")]
+    BrokenLink(i64),
+
+    #[error("Entry {0} was modified")]
+    Modified(i64),
+
+    #[error("Log does not end where it was signed (entries removed from the end?)")]
+    HeadMismatch,
+
+    #[error("Log signature is invalid")]
+    BadSignature,
+}
+
+/// Check that `entries` (oldest first) form one unbroken chain from the
+/// genesis hash. Returns the head hash.
+pub fn verify_audit_chain(entries: &[AuditEntry]) -> Result<String, AuditChainError> {
+    let mut prev_hash = AUDIT_GENESIS_HASH.to_string();
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.seq != i as i64 + 1 {
+            return Err(AuditChainError::OutOfSequence(entry.seq));
+        }
+        if entry.prev_hash != prev_hash {
+            return Err(AuditChainError::BrokenLink(entry.seq));
+        }
+        if entry.hash != entry.expected_hash() {
+            return Err(AuditChainError::Modified(entry.seq));
+        }
+        prev_hash = entry.hash.clone();
+    }
+    Ok(prev_hash)
+}
+
+/// An exported audit log, signed by the identity it belongs to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedAuditLog {
+    pub version: u32,
+    pub public_key: String,
+    pub exported_at: i64,
+    /// Oldest first
+    pub entries: Vec<AuditEntry>,
+    pub head_hash: String,
+    pub signature: String,
+}
+
+impl SignedAuditLog {
+    /// Sign a full chain (oldest first) read from the database
+    pub fn sign(
+        identity: &GnsIdentity,
+        entries: Vec<AuditEntry>,
+        exported_at: i64,
+    ) -> Result<Self, AuditChainError> {
+        let head_hash = verify_audit_chain(&entries)?;
+        let public_key = identity.public_key_hex();
+        let payload = Self::signing_payload(&public_key, exported_at, entries.len(), &head_hash);
+        let signature = hex::encode(identity.sign(payload.as_bytes()).to_bytes());
+
+        Ok(Self {
+            version: AUDIT_EXPORT_VERSION,
+            public_key,
+            exported_at,
+            entries,
+            head_hash,
+            signature,
+        })
+    }
+
+    /// Check the chain, that it ends at the signed head, and the signature
+    pub fn verify(&self) -> Result<(), AuditChainError> {
+        let head_hash = verify_audit_chain(&self.entries)?;
+        if head_hash != self.head_hash {
+            return Err(AuditChainError::HeadMismatch);
+        }
+
+        let payload =
+            Self::signing_payload(&self.public_key, self.exported_at, self.entries.len(), &head_hash);
+        match gns_crypto_core::signing::verify_signature_hex(
+            &self.public_key,
+            payload.as_bytes(),
+            &self.signature,
+        ) {
+            Ok(true) => Ok(()),
+            _ => Err(AuditChainError::BadSignature),
+        }
+    }
+
+    fn signing_payload(public_key: &str, exported_at: i64, count: usize, head_hash: &str) -> String {
+        format!(
+            "gns-audit-log:v{}:{}:{}:{}:{}",
+            AUDIT_EXPORT_VERSION, public_key, exported_at, count, head_hash
+        )
+    }
+}
+
+pub(super) fn create_table(conn: &Connection) -> Result<(), DatabaseError> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS audit_log (
+            seq INTEGER PRIMARY KEY,
+            timestamp INTEGER NOT NULL,
+            action TEXT NOT NULL,
+            target TEXT,
+            result TEXT NOT NULL,
+            prev_hash TEXT NOT NULL,
+            hash TEXT NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_audit_log_time ON audit_log(timestamp DESC);
+        "#,
+    )
+    .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+}
+
+fn entry_from_row(row: &rusqlite::Row) -> rusqlite::Result<AuditEntry> {
+    Ok(AuditEntry {
+        seq: row.get(0)?,
+        timestamp: row.get(1)?,
+        action: row.get(2)?,
+        target: row.get(3)?,
+        result: row.get(4)?,
+        prev_hash: row.get(5)?,
+        hash: row.get(6)?,
+    })
+}
+
+impl Database {
+    /// Append an entry to the audit chain
+    pub fn record_audit(
+        &mut self,
+        action: AuditAction,
+        target: Option<&str>,
+        result: &str,
+    ) -> Result<AuditEntry, DatabaseError> {
+        self.record_audit_at(chrono::Utc::now().timestamp(), action, target, result)
+    }
+
+    fn record_audit_at(
+        &mut self,
+        timestamp: i64,
+        action: AuditAction,
+        target: Option<&str>,
+        result: &str,
+    ) -> Result<AuditEntry, DatabaseError> {
+        let tx = self
+            .conn
+            .transaction()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        let head: Option<(i64, String)> = tx
+            .query_row(
+                "SELECT seq, hash FROM audit_log ORDER BY seq DESC LIMIT 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        let (prev_seq, prev_hash) = head.unwrap_or((0, AUDIT_GENESIS_HASH.to_string()));
+
+        let seq = prev_seq + 1;
+        let action = action.as_str();
+        let hash = AuditEntry::compute_hash(seq, timestamp, action, target, result, &prev_hash);
+
+        tx.execute(
+            "INSERT INTO audit_log (seq, timestamp, action, target, result, prev_hash, hash)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![seq, timestamp, action, target, result, prev_hash, hash],
+        )
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        tx.commit().map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        Ok(AuditEntry {
+            seq,
+            timestamp,
+            action: action.to_string(),
+            target: target.map(str::to_string),
+            result: result.to_string(),
+            prev_hash,
+            hash,
+        })
+    }
+
+    /// Most recent entries first, optionally only those at or after `since`
+    pub fn get_audit_log(
+        &self,
+        limit: u32,
+        since: Option<i64>,
+    ) -> Result<Vec<AuditEntry>, DatabaseError> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT seq, timestamp, action, target, result, prev_hash, hash FROM audit_log
+                 WHERE timestamp >= ? ORDER BY seq DESC LIMIT ?",
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        let entries = stmt
+            .query_map(params![since.unwrap_or(i64::MIN), limit], entry_from_row)
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(entries)
+    }
+
+    /// The whole chain, oldest first
+    pub fn get_audit_chain(&self) -> Result<Vec<AuditEntry>, DatabaseError> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT seq, timestamp, action, target, result, prev_hash, hash FROM audit_log
+                 ORDER BY seq ASC",
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        let entries = stmt
+            .query_map([], entry_from_row)
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(db: &mut Database) -> Vec<AuditEntry> {
+        db.record_audit_at(100, AuditAction::SignMessage, Some("abcd"), "ok").unwrap();
+        db.record_audit_at(200, AuditAction::SendGns, Some("GABC"), "ok").unwrap();
+        db.record_audit_at(300, AuditAction::ClaimHandle, Some("alice"), "Requirements not met")
+            .unwrap();
+        db.get_audit_chain().unwrap()
+    }
+
+    #[test]
+    fn test_audit_chain_links() {
+        let mut db = Database::open_in_memory().unwrap();
+        let entries = chain(&mut db);
+
+        assert_eq!(entries[0].prev_hash, AUDIT_GENESIS_HASH);
+        assert_eq!(entries[1].prev_hash, entries[0].hash);
+        assert_eq!(verify_audit_chain(&entries), Ok(entries[2].hash.clone()));
+
+        let recent = db.get_audit_log(10, Some(200)).unwrap();
+        assert_eq!(recent.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![3, 2]);
+        assert_eq!(db.get_audit_log(1, None).unwrap()[0].action, "claim_handle");
+    }
+
+    #[test]
+    fn test_audit_chain_detects_tampering() {
+        let mut db = Database::open_in_memory().unwrap();
+        let entries = chain(&mut db);
+
+        let mut deleted = entries.clone();
+        deleted.remove(1);
+        assert_eq!(verify_audit_chain(&deleted), Err(AuditChainError::OutOfSequence(3)));
+
+        // An inserted entry, renumbered to hide the gap, still can't link up
+        let mut inserted = entries.clone();
+        let mut forged = entries[1].clone();
+        forged.target = Some("GEVIL".to_string());
+        inserted.insert(1, forged);
+        for (i, entry) in inserted.iter_mut().enumerate() {
+            entry.seq = i as i64 + 1;
+        }
+        assert_eq!(verify_audit_chain(&inserted), Err(AuditChainError::Modified(2)));
+
+        let mut edited = entries.clone();
+        edited[2].result = "ok".to_string();
+        assert_eq!(verify_audit_chain(&edited), Err(AuditChainError::Modified(3)));
+
+        let mut relinked = entries;
+        relinked[2].prev_hash = AUDIT_GENESIS_HASH.to_string();
+        assert_eq!(verify_audit_chain(&relinked), Err(AuditChainError::BrokenLink(3)));
+    }
+
+    #[test]
+    fn test_signed_export() {
+        let mut db = Database::open_in_memory().unwrap();
+        let identity = GnsIdentity::generate();
+        let log = SignedAuditLog::sign(&identity, chain(&mut db), 400).unwrap();
+        assert_eq!(log.verify(), Ok(()));
+
+        // Dropping the newest entry leaves a valid chain, but not the signed one
+        let mut truncated = log.clone();
+        truncated.entries.pop();
+        assert_eq!(truncated.verify(), Err(AuditChainError::HeadMismatch));
+
+        let mut resigned = log.clone();
+        resigned.public_key = GnsIdentity::generate().public_key_hex();
+        assert_eq!(resigned.verify(), Err(AuditChainError::BadSignature));
+
+        let mut edited = log;
+        edited.entries[0].target = None;
+        assert_eq!(edited.verify(), Err(AuditChainError::Modified(1)));
+    }
+}
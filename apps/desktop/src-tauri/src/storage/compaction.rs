@@ -0,0 +1,141 @@
+//! Compaction
+//!
+//! SQLite keeps the pages a delete frees for later inserts rather than
+//! giving them back, so the file never shrinks on its own. Bulk deletes
+//! count the rows they remove; once enough have gone, `VACUUM` rewrites
+//! the file without the free pages.
+
+use super::{Database, DatabaseError};
+use rusqlite::params;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Rows deleted since the last compaction that make another one due
+pub const AUTO_COMPACT_ROWS: i64 = 5_000;
+
+const DELETED_ROWS_KEY: &str = "rows_deleted_since_compaction";
+
+/// Size of the database before and after compacting, WAL included
+#[derive(Debug, Clone, Serialize)]
+pub struct CompactionReport {
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub bytes_reclaimed: u64,
+}
+
+impl Database {
+    /// Checkpoint the WAL and `VACUUM`. Blocks for as long as the rewrite
+    /// takes; see [`compact_in_background`].
+    pub fn compact(&mut self) -> Result<CompactionReport, DatabaseError> {
+        let bytes_before = self.file_size();
+
+        self.conn
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        self.conn
+            .execute_batch("VACUUM")
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        // VACUUM itself goes through the WAL
+        self.conn
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        self.conn
+            .execute("DELETE FROM sync_state WHERE key = ?", params![DELETED_ROWS_KEY])
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        let bytes_after = self.file_size();
+        Ok(CompactionReport {
+            bytes_before,
+            bytes_after,
+            bytes_reclaimed: bytes_before.saturating_sub(bytes_after),
+        })
+    }
+
+    /// Whether enough rows have been deleted to make compacting worthwhile
+    pub fn compaction_due(&self) -> bool {
+        self.deleted_rows() >= AUTO_COMPACT_ROWS
+    }
+
+    /// Count `rows` towards the next compaction
+    pub(super) fn record_deleted_rows(&mut self, rows: usize) -> Result<(), DatabaseError> {
+        if rows == 0 {
+            return Ok(());
+        }
+        let total = self.deleted_rows().saturating_add(rows as i64);
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO sync_state (key, value) VALUES (?, ?)",
+                params![DELETED_ROWS_KEY, total.to_string()],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(())
+    }
+
+    fn deleted_rows(&self) -> i64 {
+        self.conn
+            .query_row(
+                "SELECT value FROM sync_state WHERE key = ?",
+                params![DELETED_ROWS_KEY],
+                |row| row.get::<_, String>(0),
+            )
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0)
+    }
+
+    /// Bytes on disk for the database and its WAL; 0 in memory
+    fn file_size(&self) -> u64 {
+        let Some(path) = self.conn.path().filter(|p| !p.is_empty()) else {
+            return 0;
+        };
+        let size = |path: &Path| std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        size(Path::new(path)) + size(Path::new(&format!("{}-wal", path)))
+    }
+}
+
+/// Compact on a blocking thread. The database lock is held throughout, so
+/// nothing writes while `VACUUM` runs; the UI thread isn't held up.
+pub async fn compact_in_background(database: Arc<Mutex<Database>>) -> Result<CompactionReport, DatabaseError> {
+    tokio::task::spawn_blocking(move || database.blocking_lock().compact())
+        .await
+        .map_err(|e| DatabaseError::IoError(format!("Compaction task failed: {}", e)))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vacuum_shrinks_file_after_large_delete() {
+        let path = std::env::temp_dir().join(format!("gns-compact-{}.db", uuid::Uuid::new_v4()));
+        let mut db = Database::open_path(&path).unwrap();
+        let me = "11".repeat(32);
+        let peer = "22".repeat(32);
+        let text = "x".repeat(1_000);
+        for i in 0..2_000 {
+            db.save_synced_incoming_message(&format!("m{}", i), &peer, &text, i, None, &me).unwrap();
+        }
+        let thread_id = gns_crypto_core::compute_thread_id(&me, &peer);
+        let full = db.file_size();
+
+        db.delete_thread(&thread_id).unwrap();
+        assert!(!db.compaction_due());
+        // Deleting frees pages but keeps the file
+        assert!(db.file_size() >= full);
+
+        let report = db.compact().unwrap();
+        assert!(report.bytes_after < report.bytes_before / 4, "{:?}", report);
+        assert_eq!(report.bytes_reclaimed, report.bytes_before - report.bytes_after);
+        assert_eq!(db.deleted_rows(), 0);
+
+        db.record_deleted_rows(AUTO_COMPACT_ROWS as usize).unwrap();
+        assert!(db.compaction_due());
+
+        drop(db);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Contacts
+//!
+//! People an identity has added from their identity card. Each keeps the
+//! encryption key from the card, so sending to a contact doesn't need a
+//! handle or identity lookup first.
+
+use super::{Database, DatabaseError};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+
+pub(super) fn create_table(conn: &Connection) -> Result<(), DatabaseError> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS contacts (
+            owner_pk TEXT NOT NULL,
+            public_key TEXT NOT NULL,
+            handle TEXT,
+            display_name TEXT,
+            encryption_key TEXT NOT NULL,
+            stellar_address TEXT NOT NULL,
+            added_at INTEGER NOT NULL,
+            PRIMARY KEY (owner_pk, public_key)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_contacts_handle ON contacts(owner_pk, handle);
+        "#,
+    )
+    .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Contact {
+    pub public_key: String,
+    pub handle: Option<String>,
+    pub display_name: Option<String>,
+    pub encryption_key: String,
+    pub stellar_address: String,
+    pub added_at: i64,
+}
+
+const CONTACT_COLUMNS: &str = "public_key, handle, display_name, encryption_key, stellar_address, added_at";
+
+fn contact_from_row(row: &Row<'_>) -> rusqlite::Result<Contact> {
+    Ok(Contact {
+        public_key: row.get(0)?,
+        handle: row.get(1)?,
+        display_name: row.get(2)?,
+        encryption_key: row.get(3)?,
+        stellar_address: row.get(4)?,
+        added_at: row.get(5)?,
+    })
+}
+
+impl Database {
+    /// Add or update one of `owner_pk`'s contacts
+    pub fn save_contact(&self, owner_pk: &str, contact: &Contact) -> Result<(), DatabaseError> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO contacts (owner_pk, public_key, handle, display_name, encryption_key, stellar_address, added_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    owner_pk.to_lowercase(),
+                    contact.public_key.to_lowercase(),
+                    contact.handle.as_deref().map(str::to_lowercase),
+                    contact.display_name,
+                    contact.encryption_key,
+                    contact.stellar_address,
+                    contact.added_at,
+                ],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(())
+    }
+
+    pub fn get_contact(&self, owner_pk: &str, public_key: &str) -> Result<Option<Contact>, DatabaseError> {
+        self.conn
+            .query_row(
+                &format!("SELECT {} FROM contacts WHERE owner_pk = ? AND public_key = ?", CONTACT_COLUMNS),
+                params![owner_pk.to_lowercase(), public_key.to_lowercase()],
+                contact_from_row,
+            )
+            .optional()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+
+    /// The contact `handle` (with or without `@`) was last seen on
+    pub fn find_contact_by_handle(&self, owner_pk: &str, handle: &str) -> Result<Option<Contact>, DatabaseError> {
+        self.conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM contacts WHERE owner_pk = ? AND handle = ? ORDER BY added_at DESC LIMIT 1",
+                    CONTACT_COLUMNS
+                ),
+                params![owner_pk.to_lowercase(), handle.trim_start_matches('@').to_lowercase()],
+                contact_from_row,
+            )
+            .optional()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contacts_are_per_owner_and_found_by_handle() {
+        let db = Database::open_in_memory().unwrap();
+        let (me, other_me) = ("aa".repeat(32), "cc".repeat(32));
+        let bob = Contact {
+            public_key: "BB".repeat(32),
+            handle: Some("Bob".into()),
+            display_name: Some("Bob".into()),
+            encryption_key: "ee".repeat(32),
+            stellar_address: "GBOB".into(),
+            added_at: 1_000,
+        };
+        db.save_contact(&me, &bob).unwrap();
+
+        let found = db.find_contact_by_handle(&me, "@BOB").unwrap().unwrap();
+        assert_eq!(found.public_key, "bb".repeat(32));
+        assert_eq!(db.get_contact(&me, &"bb".repeat(32)).unwrap(), Some(found));
+        assert!(db.get_contact(&other_me, &"bb".repeat(32)).unwrap().is_none());
+
+        // Re-importing a newer card replaces the key
+        db.save_contact(&me, &Contact { encryption_key: "ff".repeat(32), added_at: 2_000, ..bob }).unwrap();
+        assert_eq!(db.find_contact_by_handle(&me, "bob").unwrap().unwrap().encryption_key, "ff".repeat(32));
+    }
+}
@@ -0,0 +1,115 @@
+//! Decrypted Cache
+//!
+//! A received message is stored twice: the signed envelope as it arrived
+//! (`envelope_json`) and the payload we decrypted from it
+//! (`payload_json`), which is what the UI, search and the thread list
+//! read. The payload is only a cache of the envelope, so after the
+//! encryption key changes it can be rebuilt from the envelopes; see
+//! `crate::cache_rebuild`. Envelopes that no longer open are marked with
+//! `decrypt_failed` and keep the payload they had.
+
+use super::{preview_text, Database, DatabaseError};
+use rusqlite::params;
+
+/// A received message's envelope, in storage order
+#[derive(Debug, Clone)]
+pub struct StoredEnvelope {
+    pub rowid: i64,
+    pub message_id: String,
+    pub envelope_json: String,
+}
+
+/// What opening a stored envelope again gave
+#[derive(Debug, Clone)]
+pub enum CacheRepair {
+    Decrypted {
+        payload: serde_json::Value,
+        signature_valid: bool,
+    },
+    Failed,
+}
+
+impl Database {
+    /// How many received messages have their envelope stored
+    pub fn count_stored_envelopes(&self) -> Result<u64, DatabaseError> {
+        self.conn
+            .query_row(
+                "SELECT COUNT(*) FROM messages WHERE is_outgoing = 0 AND envelope_json IS NOT NULL",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+
+    /// Up to `limit` stored envelopes of received messages after `after_rowid`
+    pub fn stored_envelopes(&self, after_rowid: i64, limit: u32) -> Result<Vec<StoredEnvelope>, DatabaseError> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                r#"
+                SELECT rowid, id, envelope_json FROM messages
+                WHERE is_outgoing = 0 AND envelope_json IS NOT NULL AND rowid > ?
+                ORDER BY rowid LIMIT ?
+                "#,
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        let rows = stmt
+            .query_map(params![after_rowid, limit], |row| {
+                Ok(StoredEnvelope {
+                    rowid: row.get(0)?,
+                    message_id: row.get(1)?,
+                    envelope_json: row.get(2)?,
+                })
+            })
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+
+    /// Write back what reopening envelopes gave, in one transaction.
+    /// Returns how many cached payloads were wrong and have been replaced.
+    pub fn repair_decrypted_cache(&mut self, repairs: &[(String, CacheRepair)]) -> Result<usize, DatabaseError> {
+        let tx = self
+            .conn
+            .transaction()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        let mut replaced = 0;
+        for (message_id, repair) in repairs {
+            match repair {
+                CacheRepair::Decrypted { payload, signature_valid } => {
+                    let payload_json = serde_json::to_string(payload).unwrap_or_default();
+                    let changed = tx
+                        .execute(
+                            r#"
+                            UPDATE messages SET payload_json = ?1, signature_valid = ?2, decrypt_failed = 0
+                            WHERE id = ?3 AND (payload_json IS NOT ?1 OR signature_valid IS NOT ?2 OR decrypt_failed != 0)
+                            "#,
+                            params![payload_json, signature_valid, message_id],
+                        )
+                        .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+                    if changed == 0 {
+                        continue;
+                    }
+                    replaced += 1;
+                    tx.execute(
+                        "UPDATE threads SET preview_text = ? WHERE last_message_id = ?",
+                        params![preview_text(&payload_json), message_id],
+                    )
+                    .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+                }
+                CacheRepair::Failed => {
+                    tx.execute(
+                        "UPDATE messages SET decrypt_failed = 1 WHERE id = ?",
+                        params![message_id],
+                    )
+                    .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+                }
+            }
+        }
+
+        tx.commit()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(replaced)
+    }
+}
@@ -0,0 +1,77 @@
+//! Derived Keys
+//!
+//! Values that follow deterministically from an identity public key, such
+//! as its Stellar address. They're derived once, when the identity is
+//! created or imported, so the wallet can show them before any network
+//! call and balance lookups skip the base32/CRC work.
+
+use super::{Database, DatabaseError};
+use crate::stellar::StellarService;
+use rusqlite::{params, Connection, OptionalExtension};
+
+pub(super) fn create_table(conn: &Connection) -> Result<(), DatabaseError> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS derived_keys (
+            public_key TEXT PRIMARY KEY,
+            stellar_address TEXT NOT NULL,
+            created_at INTEGER NOT NULL
+        );
+        "#,
+    )
+    .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+}
+
+impl Database {
+    /// Stellar address of `public_key`, derived and stored on first use
+    pub fn stellar_address(&mut self, public_key: &str) -> Result<String, DatabaseError> {
+        let public_key = public_key.to_lowercase();
+        if let Some(address) = self.cached_stellar_address(&public_key)? {
+            return Ok(address);
+        }
+
+        let address = StellarService::gns_key_to_stellar(&public_key)
+            .map_err(|e| DatabaseError::InvalidKey(e.to_string()))?;
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO derived_keys (public_key, stellar_address, created_at) VALUES (?, ?, ?)",
+                params![public_key, address, chrono::Utc::now().timestamp_millis()],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(address)
+    }
+
+    /// Stored Stellar address of `public_key`, without deriving it
+    pub fn cached_stellar_address(&self, public_key: &str) -> Result<Option<String>, DatabaseError> {
+        self.conn
+            .query_row(
+                "SELECT stellar_address FROM derived_keys WHERE public_key = ?",
+                params![public_key.to_lowercase()],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gns_crypto_core::GnsIdentity;
+
+    #[test]
+    fn test_cached_address_matches_fresh_derivation() {
+        let mut db = Database::open_in_memory().unwrap();
+        let public_key = GnsIdentity::generate().public_key_hex();
+        let fresh = StellarService::gns_key_to_stellar(&public_key).unwrap();
+
+        assert_eq!(db.cached_stellar_address(&public_key).unwrap(), None);
+        assert_eq!(db.stellar_address(&public_key).unwrap(), fresh);
+        assert_eq!(db.cached_stellar_address(&public_key).unwrap().as_deref(), Some(fresh.as_str()));
+
+        // Same row whatever the hex case
+        assert_eq!(db.stellar_address(&public_key.to_uppercase()).unwrap(), fresh);
+
+        assert!(db.stellar_address("not-a-key").is_err());
+    }
+}
@@ -0,0 +1,88 @@
+//! Paired Hubs
+//!
+//! The public key each Home Hub advertised the first time we talked to
+//! it. From then on its responses have to be signed with that key, so a
+//! device that takes over the hub's address on the LAN can't stand in for
+//! it.
+
+use super::{Database, DatabaseError};
+use rusqlite::{params, Connection, OptionalExtension};
+
+pub(super) fn create_table(conn: &Connection) -> Result<(), DatabaseError> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS paired_hubs (
+            url TEXT PRIMARY KEY,
+            public_key TEXT NOT NULL,
+            name TEXT NOT NULL,
+            paired_at INTEGER NOT NULL
+        );
+        "#,
+    )
+    .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+}
+
+impl Database {
+    /// Key pinned for the hub at `url`, if we have paired with it
+    pub fn paired_hub_key(&self, url: &str) -> Result<Option<String>, DatabaseError> {
+        self.conn
+            .query_row(
+                "SELECT public_key FROM paired_hubs WHERE url = ?",
+                params![url],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+
+    /// Pin `public_key` for the hub at `url`. A key already pinned is
+    /// kept; the key pinned either way is returned.
+    pub fn pair_hub(&mut self, url: &str, public_key: &str, name: &str) -> Result<String, DatabaseError> {
+        self.conn
+            .execute(
+                "INSERT OR IGNORE INTO paired_hubs (url, public_key, name, paired_at) VALUES (?, ?, ?, ?)",
+                params![url, public_key.to_lowercase(), name, chrono::Utc::now().timestamp_millis()],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        self.conn
+            .query_row(
+                "SELECT public_key FROM paired_hubs WHERE url = ?",
+                params![url],
+                |row| row.get(0),
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+
+    /// Unpin the hub at `url`, e.g. after it was reset, so the next
+    /// contact pairs again. Returns false if it wasn't paired.
+    pub fn forget_hub(&mut self, url: &str) -> Result<bool, DatabaseError> {
+        let removed = self
+            .conn
+            .execute("DELETE FROM paired_hubs WHERE url = ?", params![url])
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(removed > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_pairing_wins() {
+        let mut db = Database::open_in_memory().unwrap();
+        let url = "http://192.168.1.20:8080";
+        let hub_key = "aa".repeat(32);
+        let rogue_key = "bb".repeat(32);
+
+        assert_eq!(db.paired_hub_key(url).unwrap(), None);
+        assert_eq!(db.pair_hub(url, &hub_key.to_uppercase(), "Living room").unwrap(), hub_key);
+        // A second device claiming the address doesn't replace the pin
+        assert_eq!(db.pair_hub(url, &rogue_key, "Living room").unwrap(), hub_key);
+        assert_eq!(db.paired_hub_key(url).unwrap().as_deref(), Some(hub_key.as_str()));
+
+        assert!(db.forget_hub(url).unwrap());
+        assert!(!db.forget_hub(url).unwrap());
+        assert_eq!(db.pair_hub(url, &rogue_key, "Replaced").unwrap(), rogue_key);
+    }
+}
@@ -0,0 +1,50 @@
+//! Pending Idempotency Keys
+//!
+//! Keys of mutating requests the server hasn't acknowledged yet, kept as
+//! JSON in `sync_state` so a retry after a restart sends the same key.
+
+use super::{Database, DatabaseError};
+use crate::network::PendingIdempotencyKey;
+use rusqlite::{params, OptionalExtension};
+use std::collections::HashMap;
+
+const PENDING_IDEMPOTENCY_KEYS_KEY: &str = "pending_idempotency_keys";
+
+impl Database {
+    /// Pending keys by operation
+    pub fn load_idempotency_keys(&self) -> Result<HashMap<String, PendingIdempotencyKey>, DatabaseError> {
+        let json: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT value FROM sync_state WHERE key = ?",
+                params![PENDING_IDEMPOTENCY_KEYS_KEY],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        match json {
+            Some(json) => serde_json::from_str(&json).map_err(|e| DatabaseError::SqliteError(e.to_string())),
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    /// Replace the stored pending keys
+    pub fn save_idempotency_keys(&mut self, keys: &HashMap<String, PendingIdempotencyKey>) -> Result<(), DatabaseError> {
+        if keys.is_empty() {
+            self.conn
+                .execute("DELETE FROM sync_state WHERE key = ?", params![PENDING_IDEMPOTENCY_KEYS_KEY])
+                .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+            return Ok(());
+        }
+
+        let json = serde_json::to_string(keys).map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO sync_state (key, value) VALUES (?, ?)",
+                params![PENDING_IDEMPOTENCY_KEYS_KEY, json],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(())
+    }
+}
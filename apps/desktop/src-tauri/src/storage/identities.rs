@@ -0,0 +1,163 @@
+//! Identity Key History
+//!
+//! Every signing key this identity has used, linked by `superseded_by`
+//! to the key that replaced it, with the rotation certificate that did.
+//! Only keys that have been part of a rotation are stored; an identity
+//! that never rotated has no rows and a history of just its current key.
+//! The private keys are not kept here - the keychain only ever holds the
+//! current one.
+
+use super::{Database, DatabaseError};
+use crate::crypto::key_rotation::RotationCertificate;
+use rusqlite::{params, Connection, OptionalExtension};
+
+pub(super) fn create_table(conn: &Connection) -> Result<(), DatabaseError> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS identities (
+            public_key TEXT PRIMARY KEY,
+            created_at INTEGER NOT NULL,
+            superseded_by TEXT,
+            rotation_certificate TEXT
+        );
+        "#,
+    )
+    .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+}
+
+/// Keys from the first one to `current`, oldest first
+pub(super) fn key_history(conn: &Connection, current: &str) -> Result<Vec<String>, DatabaseError> {
+    let mut keys = vec![current.to_string()];
+    let mut key = current.to_string();
+    while let Some(previous) = conn
+        .query_row(
+            "SELECT public_key FROM identities WHERE superseded_by = ?",
+            params![key],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))?
+    {
+        // A cycle would only come from a corrupted table
+        if keys.contains(&previous) {
+            break;
+        }
+        keys.push(previous.clone());
+        key = previous;
+    }
+    keys.reverse();
+    Ok(keys)
+}
+
+impl Database {
+    /// Record that `certificate.previous_key` was replaced by its new key
+    pub fn record_key_rotation(&mut self, certificate: &RotationCertificate) -> Result<(), DatabaseError> {
+        let certificate_json =
+            serde_json::to_string(certificate).map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        let tx = self
+            .conn
+            .transaction()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        tx.execute(
+            r#"
+            INSERT INTO identities (public_key, created_at, superseded_by, rotation_certificate)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT(public_key) DO UPDATE SET superseded_by = ?3, rotation_certificate = ?4
+            "#,
+            params![certificate.previous_key, certificate.rotated_at, certificate.new_key, certificate_json],
+        )
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        tx.execute(
+            "INSERT OR IGNORE INTO identities (public_key, created_at) VALUES (?, ?)",
+            params![certificate.new_key, certificate.rotated_at],
+        )
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        tx.commit().map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+
+    /// Every key this identity has had, oldest first, ending with `current`
+    pub fn key_history(&self, current: &str) -> Result<Vec<String>, DatabaseError> {
+        key_history(&self.conn, current)
+    }
+
+    /// Certificates linking the first key to `current`, oldest first
+    pub fn rotation_certificates(&self, current: &str) -> Result<Vec<RotationCertificate>, DatabaseError> {
+        let keys = self.key_history(current)?;
+        let mut certificates = Vec::with_capacity(keys.len().saturating_sub(1));
+        for key in &keys[..keys.len() - 1] {
+            let json: String = self
+                .conn
+                .query_row(
+                    "SELECT rotation_certificate FROM identities WHERE public_key = ?",
+                    params![key],
+                    |row| row.get(0),
+                )
+                .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+            certificates.push(serde_json::from_str(&json).map_err(|e| DatabaseError::SqliteError(e.to_string()))?);
+        }
+        Ok(certificates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::key_rotation::follow_chain;
+    use crate::crypto::{IdentityError, IdentityManager};
+    use crate::stellar::{verify_stellar_control, StellarControlProof, StellarService};
+    use gns_crypto_core::GnsIdentity;
+
+    #[test]
+    fn test_rotations_chain_back_to_the_first_key() {
+        let mut db = Database::open_in_memory().unwrap();
+        let keys: Vec<GnsIdentity> = (0..3).map(|_| GnsIdentity::generate()).collect();
+        let first = keys[0].public_key_hex();
+        assert_eq!(db.key_history(&first).unwrap(), [first.as_str()]);
+        assert!(db.rotation_certificates(&first).unwrap().is_empty());
+
+        for (i, pair) in keys.windows(2).enumerate() {
+            let certificate = RotationCertificate::create(&pair[0], &pair[1].public_key_hex(), i as i64);
+            db.record_key_rotation(&certificate).unwrap();
+        }
+
+        let current = keys[2].public_key_hex();
+        let history: Vec<String> = keys.iter().map(|k| k.public_key_hex()).collect();
+        assert_eq!(db.key_history(&current).unwrap(), history);
+        assert_eq!(db.key_history(&keys[1].public_key_hex()).unwrap(), history[..2]);
+        assert_eq!(follow_chain(&db.rotation_certificates(&current).unwrap()).unwrap(), history);
+    }
+
+    #[test]
+    fn test_rotation_that_cannot_be_recorded_keeps_the_old_key() {
+        let mut db = Database::open_in_memory().unwrap();
+        db.conn.execute_batch("DROP TABLE identities").unwrap();
+        let me = GnsIdentity::generate();
+        let public_key = me.public_key_hex();
+        let mut identity = IdentityManager::from_identity(me);
+
+        let rotated = identity.rotate_key(|certificate| db.record_key_rotation(certificate));
+        assert!(matches!(rotated, Err(IdentityError::RotationNotRecorded(_))));
+        assert_eq!(identity.public_key_hex(), Some(public_key));
+    }
+
+    #[test]
+    fn test_wallet_stays_controllable_after_rotation() {
+        keyring::set_default_credential_builder(keyring::mock::default_credential_builder());
+        let mut db = Database::open_in_memory().unwrap();
+        let me = GnsIdentity::generate();
+        let address = StellarService::gns_key_to_stellar(&me.public_key_hex()).unwrap();
+        let mut identity = IdentityManager::from_identity(me);
+
+        for _ in 0..2 {
+            identity.rotate_key(|certificate| db.record_key_rotation(certificate)).unwrap();
+        }
+
+        let seed: [u8; 32] = identity.wallet_private_key_bytes().unwrap().try_into().unwrap();
+        let wallet = GnsIdentity::from_bytes(&seed).unwrap();
+        assert_eq!(StellarService::gns_key_to_stellar(&wallet.public_key_hex()).unwrap(), address);
+        assert_ne!(identity.public_key_hex(), identity.wallet_public_key());
+
+        let proof = StellarControlProof::create(identity.wallet_identity().unwrap(), "nonce").unwrap();
+        assert_eq!(verify_stellar_control(&proof.public_key, "nonce", &proof.signature).unwrap(), address);
+    }
+}
@@ -0,0 +1,413 @@
+//! Integrity Checks
+//!
+//! SQLite's own checks (`integrity_check`, `foreign_key_check`) plus the
+//! invariants the app relies on: messages come from who their thread says
+//! they should, and each breadcrumb links to the one before it. Repair
+//! moves rows that can't be made consistent into `quarantine`, as JSON,
+//! rather than deleting them, and rebuilds the indexes.
+
+use super::{breadcrumb_chain_hash, identities, search, Database, DatabaseError};
+use gns_crypto_core::Breadcrumb;
+use rusqlite::types::ValueRef;
+use rusqlite::{params, Connection, Transaction};
+use serde::Serialize;
+
+pub(super) fn create_table(conn: &Connection) -> Result<(), DatabaseError> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS quarantine (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            source_table TEXT NOT NULL,
+            row_json TEXT NOT NULL,
+            reason TEXT NOT NULL,
+            quarantined_at INTEGER NOT NULL
+        );
+        "#,
+    )
+    .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegrityIssueKind {
+    /// `PRAGMA integrity_check` found damage in the file
+    Corruption,
+    /// A row refers to a parent row that doesn't exist
+    Orphaned,
+    /// A message from a key its thread doesn't expect
+    UnexpectedSender,
+    /// A breadcrumb whose `prev_hash` isn't the hash of the one before it
+    BrokenBreadcrumbChain,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IntegrityIssue {
+    pub kind: IntegrityIssueKind,
+    pub table: String,
+    /// rowid of the offending row, when there is one
+    pub rowid: Option<i64>,
+    pub detail: String,
+    /// Whether `repair_database` quarantines the row
+    pub repairable: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityReport {
+    pub checked_at: i64,
+    /// True when no issues were found
+    pub ok: bool,
+    pub issues: Vec<IntegrityIssue>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RepairReport {
+    /// Rows moved to the `quarantine` table
+    pub quarantined: usize,
+    /// What is left after repairing
+    pub after: IntegrityReport,
+}
+
+impl Database {
+    /// Check the database. `own_public_key` is who our outgoing messages
+    /// must be from; without it that check is skipped.
+    pub fn verify_integrity(&self, own_public_key: Option<&str>) -> Result<IntegrityReport, DatabaseError> {
+        let mut issues = sqlite_corruption(&self.conn)?;
+        issues.extend(orphaned_rows(&self.conn)?);
+        issues.extend(unexpected_senders(&self.conn, own_public_key)?);
+        issues.extend(breadcrumb_chain(self)?);
+
+        Ok(IntegrityReport {
+            checked_at: chrono::Utc::now().timestamp_millis(),
+            ok: issues.is_empty(),
+            issues,
+        })
+    }
+
+    /// Quarantine the rows `verify_integrity` marks repairable, rebuild
+    /// every index and check again
+    pub fn repair(&mut self, own_public_key: Option<&str>) -> Result<RepairReport, DatabaseError> {
+        let issues = self.verify_integrity(own_public_key)?.issues;
+
+        let tx = self
+            .conn
+            .transaction()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        let mut quarantined = 0;
+        for issue in issues.iter().filter(|i| i.repairable) {
+            if let Some(rowid) = issue.rowid {
+                quarantined += quarantine_row(&tx, &issue.table, rowid, &issue.detail)?;
+            }
+        }
+        tx.commit().map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        self.conn
+            .execute_batch("REINDEX;")
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        search::rebuild_index(&self.conn)?;
+        if quarantined > 0 {
+            tracing::warn!("🩹 Quarantined {} inconsistent rows", quarantined);
+        }
+
+        Ok(RepairReport {
+            quarantined,
+            after: self.verify_integrity(own_public_key)?,
+        })
+    }
+
+    /// Rows quarantined so far
+    pub fn count_quarantined(&self) -> Result<u32, DatabaseError> {
+        self.conn
+            .query_row("SELECT COUNT(*) FROM quarantine", [], |row| row.get(0))
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+}
+
+fn sqlite_corruption(conn: &Connection) -> Result<Vec<IntegrityIssue>, DatabaseError> {
+    let mut stmt = conn
+        .prepare("PRAGMA integrity_check")
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+    let lines = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+    Ok(lines
+        .into_iter()
+        .filter(|line| line != "ok")
+        .map(|detail| IntegrityIssue {
+            kind: IntegrityIssueKind::Corruption,
+            table: String::new(),
+            rowid: None,
+            detail,
+            repairable: false,
+        })
+        .collect())
+}
+
+/// Rows whose foreign key points nowhere, e.g. reactions to a deleted
+/// message or messages of a deleted thread
+fn orphaned_rows(conn: &Connection) -> Result<Vec<IntegrityIssue>, DatabaseError> {
+    let mut stmt = conn
+        .prepare("PRAGMA foreign_key_check")
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<i64>>(1)?, row.get::<_, String>(2)?))
+        })
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(table, rowid, parent)| IntegrityIssue {
+            kind: IntegrityIssueKind::Orphaned,
+            detail: format!("{} row refers to a missing {} row", table, parent),
+            table,
+            repairable: rowid.is_some(),
+            rowid,
+        })
+        .collect())
+}
+
+/// Our messages must be from our key, or one it replaced. In a direct
+/// thread the peer's must be from the thread's participant; in a group
+/// thread, from a member.
+fn unexpected_senders(conn: &Connection, own_public_key: Option<&str>) -> Result<Vec<IntegrityIssue>, DatabaseError> {
+    let own_keys = own_public_key.map(|own| identities::key_history(conn, own)).transpose()?;
+    let mut stmt = conn
+        .prepare(
+            r#"
+            SELECT m.rowid, m.id, m.from_public_key, m.is_outgoing, t.participant_public_key, tm.members_json
+            FROM messages m
+            JOIN threads t ON t.id = m.thread_id
+            LEFT JOIN thread_meta tm ON tm.thread_id = m.thread_id
+            "#,
+        )
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, bool>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, Option<String>>(5)?,
+            ))
+        })
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+    let mut issues = Vec::new();
+    for row in rows {
+        let (rowid, id, from, is_outgoing, participant, members) =
+            row.map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        let expected = if is_outgoing {
+            own_keys.as_ref().map_or(true, |keys| keys.iter().any(|own| from.eq_ignore_ascii_case(own)))
+        } else if let Some(members) = members {
+            let members: Vec<String> = serde_json::from_str(&members).unwrap_or_default();
+            members.iter().any(|m| m.eq_ignore_ascii_case(&from))
+        } else {
+            from.eq_ignore_ascii_case(&participant)
+        };
+
+        if !expected {
+            issues.push(IntegrityIssue {
+                kind: IntegrityIssueKind::UnexpectedSender,
+                table: "messages".to_string(),
+                rowid: Some(rowid),
+                detail: format!("Message {} is from {}, not a party to its thread", id, from),
+                repairable: false,
+            });
+        }
+    }
+    Ok(issues)
+}
+
+/// Each breadcrumb, oldest first, must name the hash of the previous one
+fn breadcrumb_chain(db: &Database) -> Result<Vec<IntegrityIssue>, DatabaseError> {
+    let mut stmt = db
+        .conn
+        .prepare("SELECT rowid, h3_index, timestamp, signature, prev_hash FROM breadcrumbs ORDER BY timestamp, rowid")
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                Breadcrumb {
+                    h3_index: row.get(1)?,
+                    timestamp: row.get(2)?,
+                    public_key: String::new(),
+                    signature: row.get(3)?,
+                    resolution: 7,
+                    prev_hash: row.get(4)?,
+                },
+            ))
+        })
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+    let mut issues = Vec::new();
+    let mut previous: Option<Breadcrumb> = None;
+    for row in rows {
+        let (rowid, breadcrumb) = row.map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        let expected = previous.as_ref().map(breadcrumb_chain_hash);
+        // The oldest may link to breadcrumbs that are no longer stored
+        let linked = match &expected {
+            Some(_) => breadcrumb.prev_hash == expected,
+            None => true,
+        };
+        if !linked {
+            issues.push(IntegrityIssue {
+                kind: IntegrityIssueKind::BrokenBreadcrumbChain,
+                table: "breadcrumbs".to_string(),
+                rowid: Some(rowid),
+                detail: format!("Breadcrumb at {} does not link to the one before it", breadcrumb.timestamp),
+                repairable: false,
+            });
+        }
+        previous = Some(breadcrumb);
+    }
+    Ok(issues)
+}
+
+/// Move row `rowid` of `table` into `quarantine`. Returns how many rows
+/// moved (0 if it was already gone).
+fn quarantine_row(tx: &Transaction, table: &str, rowid: i64, reason: &str) -> Result<usize, DatabaseError> {
+    // `table` comes from SQLite's own pragma output; quote it all the same
+    let table_sql = format!("\"{}\"", table.replace('"', "\"\""));
+    let mut stmt = tx
+        .prepare(&format!("SELECT * FROM {} WHERE rowid = ?", table_sql))
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let mut rows = stmt
+        .query(params![rowid])
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+    let Some(row) = rows.next().map_err(|e| DatabaseError::SqliteError(e.to_string()))? else {
+        return Ok(0);
+    };
+
+    let mut json = serde_json::Map::new();
+    for (i, column) in columns.iter().enumerate() {
+        let value = match row.get_ref(i).map_err(|e| DatabaseError::SqliteError(e.to_string()))? {
+            ValueRef::Null => serde_json::Value::Null,
+            ValueRef::Integer(n) => n.into(),
+            ValueRef::Real(f) => f.into(),
+            ValueRef::Text(text) => String::from_utf8_lossy(text).into(),
+            ValueRef::Blob(blob) => hex::encode(blob).into(),
+        };
+        json.insert(column.clone(), value);
+    }
+    drop(rows);
+
+    tx.execute(
+        "INSERT INTO quarantine (source_table, row_json, reason, quarantined_at) VALUES (?, ?, ?, ?)",
+        params![table, serde_json::Value::Object(json).to_string(), reason, chrono::Utc::now().timestamp_millis()],
+    )
+    .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+    tx.execute(&format!("DELETE FROM {} WHERE rowid = ?", table_sql), params![rowid])
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gns_crypto_core::GnsIdentity;
+
+    fn kinds(report: &IntegrityReport) -> Vec<(IntegrityIssueKind, &str)> {
+        report.issues.iter().map(|i| (i.kind, i.table.as_str())).collect()
+    }
+
+    #[test]
+    fn test_inconsistent_rows_are_detected_and_quarantined() {
+        let mut db = Database::open_in_memory().unwrap();
+        let me = GnsIdentity::generate().public_key_hex();
+        let peer = GnsIdentity::generate().public_key_hex();
+        let stranger = GnsIdentity::generate().public_key_hex();
+
+        db.save_received_message("m1", "t1", &peer, None, "text", &serde_json::json!({ "text": "hi" }), 1_000, true, None)
+            .unwrap();
+        let mut crumbs = Vec::new();
+        for (i, h3) in ["872830828ffffff", "872830829ffffff", "87283082affffff"].iter().enumerate() {
+            let crumb = Breadcrumb {
+                h3_index: h3.to_string(),
+                timestamp: 100 + i as i64,
+                public_key: String::new(),
+                signature: format!("sig{}", i),
+                resolution: 7,
+                prev_hash: crumbs.last().map(breadcrumb_chain_hash),
+            };
+            db.save_breadcrumb(&crumb).unwrap();
+            crumbs.push(crumb);
+        }
+        assert!(db.verify_integrity(Some(&me)).unwrap().ok);
+
+        // A spoofed sender, a reaction and a message left without parents
+        // (as a crash mid-write can), and a breadcrumb that skips its
+        // predecessor
+        db.save_received_message("m2", "t1", &stranger, None, "text", &serde_json::json!({ "text": "?" }), 2_000, true, None)
+            .unwrap();
+        db.conn
+            .execute_batch(&format!(
+                r#"
+                PRAGMA foreign_keys = OFF;
+                INSERT INTO reactions (message_id, from_public_key, emoji, timestamp) VALUES ('gone', '{peer}', '👍', 1);
+                INSERT INTO messages (id, thread_id, from_public_key, payload_type, payload_json, timestamp, is_outgoing)
+                    VALUES ('m3', 'no-such-thread', '{peer}', 'text', '{{}}', 3000, 0);
+                UPDATE breadcrumbs SET prev_hash = '{skip}' WHERE timestamp = 102;
+                PRAGMA foreign_keys = ON;
+                "#,
+                peer = peer,
+                skip = breadcrumb_chain_hash(&crumbs[0]),
+            ))
+            .unwrap();
+
+        let report = db.verify_integrity(Some(&me)).unwrap();
+        assert!(!report.ok);
+        let mut found = kinds(&report);
+        found.sort_by_key(|(kind, table)| (*kind as u8, table.to_string()));
+        assert_eq!(
+            found,
+            [
+                (IntegrityIssueKind::Orphaned, "messages"),
+                (IntegrityIssueKind::Orphaned, "reactions"),
+                (IntegrityIssueKind::UnexpectedSender, "messages"),
+                (IntegrityIssueKind::BrokenBreadcrumbChain, "breadcrumbs"),
+            ]
+        );
+
+        // Orphans can't be fixed in place; the rest needs a person to look
+        let repair = db.repair(Some(&me)).unwrap();
+        assert_eq!(repair.quarantined, 2);
+        assert_eq!(db.count_quarantined().unwrap(), 2);
+        assert_eq!(
+            kinds(&repair.after),
+            [
+                (IntegrityIssueKind::UnexpectedSender, "messages"),
+                (IntegrityIssueKind::BrokenBreadcrumbChain, "breadcrumbs"),
+            ]
+        );
+        assert!(db.get_message("m3").unwrap().is_none());
+        assert!(db.get_message("m1").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_outgoing_messages_must_be_ours() {
+        let mut db = Database::open_in_memory().unwrap();
+        let me = GnsIdentity::generate().public_key_hex();
+        let peer = GnsIdentity::generate().public_key_hex();
+        db.get_or_create_thread("t1", &peer, None, None).unwrap();
+        db.conn
+            .execute(
+                "INSERT INTO messages (id, thread_id, from_public_key, payload_type, payload_json, timestamp, is_outgoing)
+                 VALUES ('m1', 't1', ?, 'text', '{}', 1, 1)",
+                params![peer],
+            )
+            .unwrap();
+
+        let report = db.verify_integrity(Some(&me)).unwrap();
+        assert_eq!(kinds(&report), [(IntegrityIssueKind::UnexpectedSender, "messages")]);
+        // Without knowing our key there is nothing to compare against
+        assert!(db.verify_integrity(None).unwrap().ok);
+    }
+}
@@ -0,0 +1,180 @@
+//! Migration Token Ledger
+//!
+//! Tracks the identity migration tokens this device issued, by id, along
+//! with the transport key each one's seed is sealed to. The key is
+//! released to the first new device that claims the token while it is
+//! unexpired and unrevoked, and forgotten once the token is used or
+//! revoked, so the issuing device alone decides whether a token still
+//! works.
+
+use super::{Database, DatabaseError};
+use rusqlite::{params, Connection, OptionalExtension};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationTokenStatus {
+    Issued,
+    Revoked,
+    Consumed,
+}
+
+impl MigrationTokenStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Issued => "issued",
+            Self::Revoked => "revoked",
+            Self::Consumed => "consumed",
+        }
+    }
+
+    fn parse(s: &str) -> Self {
+        match s {
+            "revoked" => Self::Revoked,
+            "consumed" => Self::Consumed,
+            _ => Self::Issued,
+        }
+    }
+}
+
+pub(super) fn create_table(conn: &Connection) -> Result<(), DatabaseError> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS migration_tokens (
+            id TEXT PRIMARY KEY,
+            public_key TEXT NOT NULL,
+            expires_at INTEGER NOT NULL,
+            status TEXT NOT NULL,
+            updated_at INTEGER NOT NULL
+        );
+        "#,
+    )
+    .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+    // Migration: tokens now keep their transport key on the issuing device
+    let _ = conn.execute("ALTER TABLE migration_tokens ADD COLUMN transport_key TEXT", []);
+    Ok(())
+}
+
+impl Database {
+    /// Remember a token this device issued, with the private transport key
+    /// its seed is sealed to
+    pub fn record_migration_token(
+        &mut self,
+        id: &str,
+        public_key: &str,
+        expires_at: i64,
+        transport_key: &str,
+    ) -> Result<(), DatabaseError> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO migration_tokens (id, public_key, expires_at, status, updated_at, transport_key)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+                params![
+                    id,
+                    public_key,
+                    expires_at,
+                    MigrationTokenStatus::Issued.as_str(),
+                    chrono::Utc::now().timestamp(),
+                    transport_key
+                ],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Revoke an outstanding token. Returns false if it was unknown or
+    /// already used.
+    pub fn revoke_migration_token(&mut self, id: &str) -> Result<bool, DatabaseError> {
+        let changed = self
+            .conn
+            .execute(
+                "UPDATE migration_tokens SET status = ?, updated_at = ?, transport_key = NULL
+                 WHERE id = ? AND status = ?",
+                params![
+                    MigrationTokenStatus::Revoked.as_str(),
+                    chrono::Utc::now().timestamp(),
+                    id,
+                    MigrationTokenStatus::Issued.as_str()
+                ],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(changed > 0)
+    }
+
+    pub fn migration_token_status(
+        &self,
+        id: &str,
+    ) -> Result<Option<MigrationTokenStatus>, DatabaseError> {
+        self.conn
+            .query_row(
+                "SELECT status FROM migration_tokens WHERE id = ?",
+                params![id],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .map(|status| status.map(|s| MigrationTokenStatus::parse(&s)))
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+
+    /// Hand out an issued token's transport key and mark the token used.
+    ///
+    /// Returns `None` without changing anything if the token is unknown,
+    /// revoked, already used or expired at `now`; `migration_token_status`
+    /// tells which.
+    pub fn release_migration_token(&mut self, id: &str, now: i64) -> Result<Option<String>, DatabaseError> {
+        let transport_key: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT transport_key FROM migration_tokens WHERE id = ? AND status = ? AND expires_at > ?",
+                params![id, MigrationTokenStatus::Issued.as_str(), now],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?
+            .flatten();
+        let Some(transport_key) = transport_key else {
+            return Ok(None);
+        };
+
+        self.conn
+            .execute(
+                "UPDATE migration_tokens SET status = ?, updated_at = ?, transport_key = NULL WHERE id = ?",
+                params![MigrationTokenStatus::Consumed.as_str(), now, id],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(Some(transport_key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_is_released_once() {
+        let mut db = Database::open_in_memory().unwrap();
+        db.record_migration_token("t1", "pk", 100, "key").unwrap();
+        assert_eq!(db.migration_token_status("t1").unwrap(), Some(MigrationTokenStatus::Issued));
+
+        assert_eq!(db.release_migration_token("t1", 50).unwrap().as_deref(), Some("key"));
+        assert_eq!(db.release_migration_token("t1", 50).unwrap(), None);
+        assert_eq!(db.migration_token_status("t1").unwrap(), Some(MigrationTokenStatus::Consumed));
+        // Used tokens can't be revoked after the fact
+        assert!(!db.revoke_migration_token("t1").unwrap());
+    }
+
+    #[test]
+    fn test_revoked_or_expired_token_is_not_released() {
+        let mut db = Database::open_in_memory().unwrap();
+        db.record_migration_token("t1", "pk", 100, "key").unwrap();
+        assert!(db.revoke_migration_token("t1").unwrap());
+        assert!(!db.revoke_migration_token("t1").unwrap());
+        assert_eq!(db.release_migration_token("t1", 50).unwrap(), None);
+        assert_eq!(db.migration_token_status("t1").unwrap(), Some(MigrationTokenStatus::Revoked));
+
+        db.record_migration_token("t2", "pk", 100, "key").unwrap();
+        assert_eq!(db.release_migration_token("t2", 100).unwrap(), None);
+        assert_eq!(db.migration_token_status("t2").unwrap(), Some(MigrationTokenStatus::Issued));
+
+        assert_eq!(db.release_migration_token("unknown", 50).unwrap(), None);
+    }
+}
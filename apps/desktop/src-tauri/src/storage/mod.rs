@@ -2,11 +2,46 @@
 //!
 //! SQLite database for storing messages, threads, and breadcrumbs.
 
-use gns_crypto_core::{Breadcrumb, GnsEnvelope};
-use rusqlite::{params, Connection};
-use std::path::PathBuf;
+use gns_crypto_core::{compute_thread_id, Breadcrumb, GnsEnvelope};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::path::{Path, PathBuf};
 
 use crate::commands::messaging::{Message, ThreadPreview, Reaction};
+use crate::dix::{FollowAction, FollowRecord};
+
+mod audit;
+mod compaction;
+mod contacts;
+mod decrypted_cache;
+mod derived_keys;
+mod hubs;
+mod idempotency;
+mod identities;
+mod integrity;
+mod migration;
+mod notification_prefs;
+mod ratchet;
+mod read_state;
+mod scheduled;
+mod search;
+mod snapshot;
+mod thread_meta;
+mod threads;
+mod transcript;
+
+pub use audit::{verify_audit_chain, AuditAction, AuditChainError, AuditEntry, SignedAuditLog};
+pub use compaction::{compact_in_background, CompactionReport, AUTO_COMPACT_ROWS};
+pub use contacts::Contact;
+pub use decrypted_cache::{CacheRepair, StoredEnvelope};
+pub use integrity::{IntegrityIssue, IntegrityIssueKind, IntegrityReport, RepairReport};
+pub use migration::MigrationTokenStatus;
+pub use read_state::ReadMarker;
+pub use scheduled::{ScheduledMessage, MAX_SEND_ATTEMPTS};
+pub use search::{MessageSearchHit, SearchOrder, SnippetSegment};
+pub use snapshot::ThreadCounts;
+pub use thread_meta::ThreadMeta;
+pub use threads::ThreadIdMigration;
+pub use transcript::{ThreadTranscript, TranscriptError, TranscriptMessage};
 
 /// Profile data stored in the database
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
@@ -15,12 +50,52 @@ pub struct Profile {
     pub display_name: Option<String>,
     pub bio: Option<String>,
     pub avatar_url: Option<String>,
+    pub avatar_blob_ref: Option<String>,
     pub links: Option<String>, // JSON array
     pub location_public: bool,
     pub location_resolution: i32,
     pub updated_at: i64,
 }
 
+/// A content-addressed blob (e.g. an avatar image)
+#[derive(Debug, Clone)]
+pub struct StoredBlob {
+    pub blob_ref: String,
+    pub mime_type: String,
+    pub data: Vec<u8>,
+    /// Whether `data` is a serialized `EncryptedPayload` sealed to our own key
+    pub encrypted: bool,
+    pub created_at: i64,
+}
+
+/// A local notification (e.g. being mentioned in a DIX post)
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct Notification {
+    pub id: i64,
+    pub kind: String,
+    pub post_id: String,
+    pub actor_public_key: String,
+    pub actor_handle: Option<String>,
+    pub preview: String,
+    pub created_at: i64,
+    pub read: bool,
+}
+
+/// A privately bookmarked DIX post
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct Bookmark {
+    pub post_id: String,
+    pub saved_at: i64,
+}
+
+/// Version of the schema `initialize_tables` creates, kept in SQLite's
+/// `user_version`. Bump it with every migration.
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// First version with messages filed under canonical thread ids. Older
+/// databases have `migrate_thread_ids` run once an identity is loaded.
+const CANONICAL_THREAD_IDS_VERSION: u32 = 2;
+
 /// Local database
 pub struct Database {
     conn: Connection,
@@ -29,15 +104,18 @@ pub struct Database {
 impl Database {
     /// Open or create the database
     pub fn open() -> Result<Self, DatabaseError> {
-        let path = Self::database_path()?;
+        Self::open_path(&Self::database_path()?)
+    }
 
+    /// Open or create the database at `path`
+    pub(crate) fn open_path(path: &Path) -> Result<Self, DatabaseError> {
         // Ensure parent directory exists
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent).map_err(|e| DatabaseError::IoError(e.to_string()))?;
         }
 
         let conn =
-            Connection::open(&path).map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+            Connection::open(path).map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
 
         let db = Self { conn };
         db.initialize_tables()?;
@@ -45,6 +123,24 @@ impl Database {
         Ok(db)
     }
 
+    /// Open a throwaway in-memory database with the full schema
+    #[cfg(test)]
+    pub(crate) fn open_in_memory() -> Result<Self, DatabaseError> {
+        let conn = Connection::open_in_memory()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        let db = Self { conn };
+        db.initialize_tables()?;
+        Ok(db)
+    }
+
+    /// Refuse all writes from now on, standing in for a broken database
+    #[cfg(test)]
+    pub(crate) fn make_read_only(&self) -> Result<(), DatabaseError> {
+        self.conn
+            .execute_batch("PRAGMA query_only = ON;")
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+
     /// Get the database file path
     fn database_path() -> Result<PathBuf, DatabaseError> {
         let data_dir = dirs::data_dir()
@@ -55,6 +151,11 @@ impl Database {
 
     /// Initialize database tables
     fn initialize_tables(&self) -> Result<(), DatabaseError> {
+        let previous_version: u32 = self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
         self.conn
             .execute_batch(
                 r#"
@@ -67,7 +168,9 @@ impl Database {
                 is_pinned INTEGER DEFAULT 0,
                 is_muted INTEGER DEFAULT 0,
                 is_archived INTEGER DEFAULT 0,
-                subject TEXT
+                subject TEXT,
+                last_message_id TEXT,
+                preview_text TEXT
             );
             
             CREATE TABLE IF NOT EXISTS messages (
@@ -84,6 +187,9 @@ impl Database {
                 reply_to_id TEXT,
                 is_starred INTEGER DEFAULT 0,
                 forwarded_from_id TEXT,
+                envelope_json TEXT,
+                is_read INTEGER DEFAULT 0,
+                decrypt_failed INTEGER DEFAULT 0,
                 FOREIGN KEY (thread_id) REFERENCES threads(id)
             );
             
@@ -118,6 +224,8 @@ impl Database {
             );
 
             CREATE INDEX IF NOT EXISTS idx_messages_thread ON messages(thread_id, timestamp DESC);
+            CREATE INDEX IF NOT EXISTS idx_messages_thread_type ON messages(thread_id, payload_type, timestamp DESC);
+            CREATE INDEX IF NOT EXISTS idx_threads_recent ON threads(last_message_at DESC);
             CREATE INDEX IF NOT EXISTS idx_breadcrumbs_time ON breadcrumbs(timestamp DESC);
             CREATE INDEX IF NOT EXISTS idx_reactions_message ON reactions(message_id);
 
@@ -131,6 +239,54 @@ impl Database {
                 location_resolution INTEGER DEFAULT 7,
                 updated_at INTEGER NOT NULL
             );
+
+            CREATE TABLE IF NOT EXISTS bookmarks (
+                owner_pk TEXT NOT NULL,
+                post_id TEXT NOT NULL,
+                saved_at INTEGER NOT NULL,
+                PRIMARY KEY (owner_pk, post_id)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_bookmarks_owner ON bookmarks(owner_pk, saved_at DESC);
+
+            CREATE TABLE IF NOT EXISTS follows (
+                follower_pk TEXT NOT NULL,
+                followee_pk TEXT NOT NULL,
+                following INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL,
+                signature TEXT NOT NULL,
+                PRIMARY KEY (follower_pk, followee_pk)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_follows_followee ON follows(followee_pk);
+
+            CREATE TABLE IF NOT EXISTS notifications (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                owner_pk TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                post_id TEXT NOT NULL,
+                actor_public_key TEXT NOT NULL,
+                actor_handle TEXT,
+                preview TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                is_read INTEGER DEFAULT 0,
+                UNIQUE(owner_pk, kind, post_id)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_notifications_owner ON notifications(owner_pk, created_at DESC);
+
+            CREATE TABLE IF NOT EXISTS settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS blobs (
+                blob_ref TEXT PRIMARY KEY,
+                mime_type TEXT NOT NULL,
+                data BLOB NOT NULL,
+                encrypted INTEGER DEFAULT 0,
+                created_at INTEGER NOT NULL
+            );
         "#,
             )
             .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
@@ -139,8 +295,52 @@ impl Database {
         let _ = self.conn.execute("ALTER TABLE messages ADD COLUMN reply_to_id TEXT", []);
         let _ = self.conn.execute("ALTER TABLE messages ADD COLUMN is_starred INTEGER DEFAULT 0", []);
         let _ = self.conn.execute("ALTER TABLE messages ADD COLUMN forwarded_from_id TEXT", []);
+        let _ = self.conn.execute("ALTER TABLE messages ADD COLUMN envelope_json TEXT", []);
+        let _ = self.conn.execute("ALTER TABLE messages ADD COLUMN is_read INTEGER DEFAULT 0", []);
+        let _ = self.conn.execute("ALTER TABLE messages ADD COLUMN decrypt_failed INTEGER DEFAULT 0", []);
         // Migration for subject column
         let _ = self.conn.execute("ALTER TABLE threads ADD COLUMN subject TEXT", []);
+        // Thread list summary columns; filled in for threads written before them
+        let _ = self.conn.execute("ALTER TABLE threads ADD COLUMN last_message_id TEXT", []);
+        let _ = self.conn.execute("ALTER TABLE threads ADD COLUMN preview_text TEXT", []);
+        self.conn
+            .execute(
+                &format!(
+                    "UPDATE threads SET {} WHERE last_message_id IS NULL AND EXISTS (SELECT 1 FROM messages WHERE thread_id = threads.id)",
+                    LAST_MESSAGE_COLUMNS
+                ),
+                [],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        let _ = self.conn.execute("ALTER TABLE profiles ADD COLUMN avatar_blob_ref TEXT", []);
+
+        search::create_index(&self.conn)?;
+        migration::create_table(&self.conn)?;
+        audit::create_table(&self.conn)?;
+        scheduled::create_table(&self.conn)?;
+        derived_keys::create_table(&self.conn)?;
+        read_state::create_table(&self.conn)?;
+        hubs::create_table(&self.conn)?;
+        ratchet::create_table(&self.conn)?;
+        notification_prefs::create_table(&self.conn)?;
+        contacts::create_table(&self.conn)?;
+        thread_meta::create_table(&self.conn)?;
+        integrity::create_table(&self.conn)?;
+        identities::create_table(&self.conn)?;
+
+        // Needs our key, so it can only be flagged here
+        if previous_version < CANONICAL_THREAD_IDS_VERSION {
+            self.conn
+                .execute(
+                    "INSERT OR REPLACE INTO sync_state (key, value) VALUES (?, '1')",
+                    params![threads::THREAD_IDS_PENDING_KEY],
+                )
+                .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        }
+
+        self.conn
+            .execute_batch(&format!("PRAGMA user_version = {};", SCHEMA_VERSION))
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
 
         Ok(())
     }
@@ -176,81 +376,58 @@ impl Database {
         Ok(())
     }
 
-    /// Update thread with new message
+    /// Update the thread's summary for a new message. The summary keeps
+    /// the newest message, so one synced in late doesn't replace it.
     fn update_thread_for_message(
         &mut self,
         thread_id: &str,
+        message_id: &str,
         timestamp: i64,
+        payload_json: &str,
         is_incoming: bool,
     ) -> Result<(), DatabaseError> {
         if is_incoming {
             // Increment unread count for incoming messages
             self.conn
                 .execute(
-                    "UPDATE threads SET last_message_at = ?, unread_count = unread_count + 1 WHERE id = ?",
-                    params![timestamp, thread_id],
-                )
-                .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
-        } else {
-            self.conn
-                .execute(
-                    "UPDATE threads SET last_message_at = ? WHERE id = ?",
-                    params![timestamp, thread_id],
+                    "UPDATE threads SET unread_count = unread_count + 1 WHERE id = ?",
+                    params![thread_id],
                 )
                 .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
         }
+        self.conn
+            .execute(
+                r#"
+                UPDATE threads SET last_message_id = ?2, last_message_at = ?3, preview_text = ?4
+                WHERE id = ?1 AND (last_message_id IS NULL OR last_message_id = ?2 OR last_message_at <= ?3)
+                "#,
+                params![thread_id, message_id, timestamp, preview_text(payload_json)],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
         Ok(())
     }
 
-    /// Get all threads
+    /// Get threads, most recently active first, `limit` at a time from
+    /// `offset`. Reads only the thread summaries, never the messages.
     pub fn get_threads(
         &self,
         include_archived: bool,
         limit: u32,
+        offset: u32,
     ) -> Result<Vec<ThreadPreview>, DatabaseError> {
-        let sql = if include_archived {
-            r#"
-            SELECT t.*, 
-                   (SELECT payload_json FROM messages m WHERE m.thread_id = t.id ORDER BY timestamp DESC LIMIT 1) as last_payload
-            FROM threads t 
-            ORDER BY last_message_at DESC LIMIT ?
-            "#
-        } else {
-            r#"
-            SELECT t.*, 
-                   (SELECT payload_json FROM messages m WHERE m.thread_id = t.id ORDER BY timestamp DESC LIMIT 1) as last_payload
-            FROM threads t 
-            WHERE is_archived = 0 
-            ORDER BY last_message_at DESC LIMIT ?
-            "#
-        };
+        let sql = format!(
+            "SELECT {} FROM threads {} ORDER BY last_message_at DESC LIMIT ? OFFSET ?",
+            THREAD_COLUMNS,
+            if include_archived { "" } else { "WHERE is_archived = 0" }
+        );
 
         let mut stmt = self
             .conn
-            .prepare(sql)
+            .prepare(&sql)
             .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
 
         let threads = stmt
-            .query_map([limit], |row| {
-                let last_payload: Option<String> = row.get(9).ok();
-                let preview = last_payload.and_then(|p| {
-                    serde_json::from_str::<serde_json::Value>(&p)
-                        .ok()
-                        .and_then(|v| v["text"].as_str().map(|s| s.to_string()))
-                });
-
-                Ok(ThreadPreview {
-                    id: row.get(0)?,
-                    participant_public_key: row.get(1)?,
-                    participant_handle: row.get(2)?,
-                    last_message_preview: preview,
-                    last_message_at: row.get(3)?,
-                    unread_count: row.get(4)?,
-                    is_pinned: row.get::<_, i32>(5)? == 1,
-                    is_muted: row.get::<_, i32>(6)? == 1,
-                    subject: row.get(8).ok(),
-                })
-            })
+            .query_map([limit, offset], thread_from_row)
             .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
 
         threads
@@ -260,39 +437,15 @@ impl Database {
 
     /// Get a single thread by ID
     pub fn get_thread(&self, thread_id: &str) -> Result<Option<ThreadPreview>, DatabaseError> {
-        let sql = r#"
-            SELECT t.*, 
-                   (SELECT payload_json FROM messages m WHERE m.thread_id = t.id ORDER BY timestamp DESC LIMIT 1) as last_payload
-            FROM threads t 
-            WHERE id = ?
-        "#;
+        let sql = format!("SELECT {} FROM threads WHERE id = ?", THREAD_COLUMNS);
 
         let mut stmt = self
             .conn
-            .prepare(sql)
+            .prepare(&sql)
             .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
 
         let mut rows = stmt
-            .query_map([thread_id], |row| {
-                let last_payload: Option<String> = row.get(9).ok();
-                let preview = last_payload.and_then(|p| {
-                    serde_json::from_str::<serde_json::Value>(&p)
-                        .ok()
-                        .and_then(|v| v["text"].as_str().map(|s| s.to_string()))
-                });
-
-                Ok(ThreadPreview {
-                    id: row.get(0)?,
-                    participant_public_key: row.get(1)?,
-                    participant_handle: row.get(2)?,
-                    last_message_preview: preview,
-                    last_message_at: row.get(3)?,
-                    unread_count: row.get(4)?,
-                    is_pinned: row.get::<_, i32>(5)? == 1,
-                    is_muted: row.get::<_, i32>(6)? == 1,
-                    subject: row.get(8).ok(),
-                })
-            })
+            .query_map([thread_id], thread_from_row)
             .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
 
         if let Some(row) = rows.next() {
@@ -302,20 +455,10 @@ impl Database {
         }
     }
 
-    /// Mark thread as read
-    pub fn mark_thread_read(&mut self, thread_id: &str) -> Result<(), DatabaseError> {
-        self.conn
-            .execute(
-                "UPDATE threads SET unread_count = 0 WHERE id = ?",
-                params![thread_id],
-            )
-            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
-        Ok(())
-    }
-
     /// Delete a thread
     pub fn delete_thread(&mut self, thread_id: &str) -> Result<(), DatabaseError> {
-        self.conn
+        let deleted = self
+            .conn
             .execute(
                 "DELETE FROM messages WHERE thread_id = ?",
                 params![thread_id],
@@ -324,37 +467,73 @@ impl Database {
         self.conn
             .execute("DELETE FROM threads WHERE id = ?", params![thread_id])
             .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
-        Ok(())
+        self.record_deleted_rows(deleted)
     }
 
-    /// Delete a message
+    /// Delete a message, moving its thread's summary to the message
+    /// before it if it was the latest
     pub fn delete_message(&mut self, message_id: &str) -> Result<(), DatabaseError> {
+        let deleted: Option<(String, bool)> = self
+            .conn
+            .query_row(
+                &format!(
+                    "SELECT thread_id, is_outgoing = 0 AND is_read = 0 AND {} FROM messages WHERE id = ?",
+                    not_control_sql()
+                ),
+                params![message_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        let Some((thread_id, was_unread)) = deleted else {
+            return Ok(());
+        };
+
         self.conn
             .execute(
                 "DELETE FROM messages WHERE id = ?",
                 params![message_id],
             )
             .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
-        Ok(())
+        if was_unread {
+            self.conn
+                .execute(
+                    "UPDATE threads SET unread_count = MAX(unread_count - 1, 0) WHERE id = ?",
+                    params![thread_id],
+                )
+                .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        }
+        refresh_last_message(&self.conn, &thread_id)
     }
 
     // ==================== Message Operations ====================
 
-    /// Get messages in a thread
+    /// Get messages in a thread, only those of `payload_types` if given.
+    /// Without it control messages are left out.
     pub fn get_messages(
         &self,
         thread_id: &str,
         limit: u32,
+        payload_types: Option<&[String]>,
     ) -> Result<Vec<Message>, DatabaseError> {
+        let type_filter = match payload_types {
+            Some(types) => format!("payload_type IN ({})", vec!["?"; types.len()].join(", ")),
+            None => not_control_sql(),
+        };
+        let mut query_params: Vec<&dyn rusqlite::ToSql> = vec![&thread_id];
+        query_params.extend(payload_types.unwrap_or_default().iter().map(|t| t as &dyn rusqlite::ToSql));
+        query_params.push(&limit);
+
         let mut stmt = self
             .conn
-            .prepare(
-                "SELECT id, thread_id, from_public_key, from_handle, payload_type, payload_json, timestamp, is_outgoing, status, reply_to_id, is_starred, forwarded_from_id FROM messages WHERE thread_id = ? ORDER BY timestamp DESC LIMIT ?",
-            )
+            .prepare(&format!(
+                "SELECT id, thread_id, from_public_key, from_handle, payload_type, payload_json, timestamp, is_outgoing, status, reply_to_id, is_starred, forwarded_from_id, is_read FROM messages WHERE thread_id = ? AND {} ORDER BY timestamp DESC LIMIT ?",
+                type_filter
+            ))
             .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
 
         let mut messages = stmt
-            .query_map(params![thread_id, limit], |row| {
+            .query_map(query_params.as_slice(), |row| {
                 let payload_str: String = row.get(5)?;
                 let payload_json: serde_json::Value =
                     serde_json::from_str(&payload_str).unwrap_or_default();
@@ -372,6 +551,7 @@ impl Database {
                     reply_to_id: row.get(9)?,
                     is_starred: row.get(10).unwrap_or(false),
                     forwarded_from_id: row.get(11)?,
+                    is_read: row.get(12).unwrap_or(false),
                     reactions: Vec::new(),
                 })
             })
@@ -408,7 +588,7 @@ impl Database {
         let mut stmt = self
             .conn
             .prepare(
-                "SELECT id, thread_id, from_public_key, from_handle, payload_type, payload_json, timestamp, is_outgoing, status, reply_to_id, is_starred, forwarded_from_id FROM messages WHERE id = ?",
+                "SELECT id, thread_id, from_public_key, from_handle, payload_type, payload_json, timestamp, is_outgoing, status, reply_to_id, is_starred, forwarded_from_id, is_read FROM messages WHERE id = ?",
             )
             .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
 
@@ -431,6 +611,7 @@ impl Database {
                     reply_to_id: row.get(9)?,
                     is_starred: row.get(10).unwrap_or(false),
                     forwarded_from_id: row.get(11)?,
+                    is_read: row.get(12).unwrap_or(false),
                     reactions: Vec::new(),
                 })
             })
@@ -458,12 +639,7 @@ impl Database {
 
         // Determine thread ID
         let thread_id = envelope.thread_id.clone().unwrap_or_else(|| {
-            // Match message_handler.rs logic: direct_{sorted_keys}
-            let my_pk = &envelope.from_public_key;
-            let other_pk = &envelope.to_public_keys[0];
-            let mut keys = vec![my_pk.as_str(), other_pk.as_str()];
-            keys.sort();
-            format!("direct_{}", &keys.join("_")[..32])
+            compute_thread_id(&envelope.from_public_key, &envelope.to_public_keys[0])
         });
 
         // Extract subject if available (for email threads)
@@ -472,14 +648,15 @@ impl Database {
         // Get or create thread
         let recipient_pk = &envelope.to_public_keys[0];
         self.get_or_create_thread(&thread_id, recipient_pk, _recipient_handle, subject)?;
+        let payload_text = serde_json::to_string(&payload_json).unwrap_or_default();
 
         // Insert message
         self.conn
             .execute(
                 r#"
                 INSERT OR REPLACE INTO messages 
-                (id, thread_id, from_public_key, from_handle, payload_type, payload_json, timestamp, is_outgoing, status, signature_valid, reply_to_id)
-                VALUES (?, ?, ?, ?, ?, ?, ?, 1, 'sent', 1, ?)
+                (id, thread_id, from_public_key, from_handle, payload_type, payload_json, timestamp, is_outgoing, status, signature_valid, reply_to_id, envelope_json)
+                VALUES (?, ?, ?, ?, ?, ?, ?, 1, 'sent', 1, ?, ?)
                 "#,
                 params![
                     envelope.id,
@@ -487,15 +664,16 @@ impl Database {
                     envelope.from_public_key,
                     envelope.from_handle,
                     envelope.payload_type,
-                    serde_json::to_string(&payload_json).unwrap_or_default(),
+                    payload_text,
                     envelope.timestamp,
                     reply_to_id,
+                    envelope.to_json().ok(),
                 ],
             )
             .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
 
         // Update thread
-        self.update_thread_for_message(&thread_id, envelope.timestamp, false)?;
+        self.update_thread_for_message(&thread_id, &envelope.id, envelope.timestamp, &payload_text, false)?;
 
         Ok(())
     }
@@ -520,14 +698,16 @@ impl Database {
 
         // Get or create thread
         self.get_or_create_thread(thread_id, from_public_key, from_handle, subject)?;
+        let already_read = self.is_read_up_to(thread_id, timestamp)?;
+        let payload_text = serde_json::to_string(payload).unwrap_or_default();
 
         // Insert message
         self.conn
             .execute(
                 r#"
                 INSERT OR REPLACE INTO messages 
-                (id, thread_id, from_public_key, from_handle, payload_type, payload_json, timestamp, is_outgoing, status, signature_valid, reply_to_id)
-                VALUES (?, ?, ?, ?, ?, ?, ?, 0, 'received', ?, ?)
+                (id, thread_id, from_public_key, from_handle, payload_type, payload_json, timestamp, is_outgoing, status, signature_valid, reply_to_id, is_read)
+                VALUES (?, ?, ?, ?, ?, ?, ?, 0, 'received', ?, ?, ?)
                 "#,
                 params![
                     message_id,
@@ -535,16 +715,18 @@ impl Database {
                     from_public_key,
                     from_handle,
                     payload_type,
-                    serde_json::to_string(payload).unwrap_or_default(),
+                    payload_text,
                     timestamp,
                     if signature_valid { 1 } else { 0 },
                     reply_to_id,
+                    already_read,
                 ],
             )
             .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
 
         // Update thread with incremented unread
-        self.update_thread_for_message(thread_id, timestamp, true)?;
+        let unread = !already_read && !is_control_payload_type(payload_type);
+        self.update_thread_for_message(thread_id, message_id, timestamp, &payload_text, unread)?;
 
         Ok(())
     }
@@ -579,34 +761,35 @@ impl Database {
         // Determine thread ID (Direct Message fallback style)
         // Note: This relies on participants. If emails need Subject grouping, 
         // we are limited here until Mobile sends Subject.
-        let mut keys = vec![my_pk, from_pk];
-        keys.sort();
-        let thread_id = format!("direct_{}", &keys.join("_")[..32]);
+        let thread_id = compute_thread_id(my_pk, from_pk);
         
         // Get or create thread
         self.get_or_create_thread(&thread_id, from_pk, from_handle, None)?;
+        let already_read = self.is_read_up_to(&thread_id, timestamp)?;
         
         // Insert Message
         let payload_json = serde_json::json!({ "text": text });
+        let payload_text = serde_json::to_string(&payload_json).unwrap_or_default();
         
         self.conn.execute(
             r#"
             INSERT OR REPLACE INTO messages 
-            (id, thread_id, from_public_key, from_handle, payload_type, payload_json, timestamp, is_outgoing, status, signature_valid)
-            VALUES (?, ?, ?, ?, 'text', ?, ?, 0, 'received', 1)
+            (id, thread_id, from_public_key, from_handle, payload_type, payload_json, timestamp, is_outgoing, status, signature_valid, is_read)
+            VALUES (?, ?, ?, ?, 'text', ?, ?, 0, 'received', 1, ?)
             "#,
             params![
                 message_id,
                 thread_id,
                 from_pk,
                 from_handle,
-                serde_json::to_string(&payload_json).unwrap_or_default(),
+                payload_text,
                 timestamp,
+                already_read,
             ],
         ).map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
         
         // Update Thread
-        self.update_thread_for_message(&thread_id, timestamp, true)?;
+        self.update_thread_for_message(&thread_id, message_id, timestamp, &payload_text, !already_read)?;
         
         Ok(())
     }
@@ -620,16 +803,13 @@ impl Database {
         timestamp: i64,
         my_pk: &str,
     ) -> Result<(), DatabaseError> {
-        // Determine thread ID
-        // Match message_handler.rs logic: direct_{sorted_keys}
-        let mut keys = vec![my_pk, to_pk];
-        keys.sort();
-        let thread_id = format!("direct_{}", &keys.join("_")[..32]);
+        let thread_id = compute_thread_id(my_pk, to_pk);
 
         // Get or create thread
         self.get_or_create_thread(&thread_id, to_pk, None, None)?;
 
         let payload_json = serde_json::json!({ "text": text });
+        let payload_text = serde_json::to_string(&payload_json).unwrap_or_default();
 
         // Insert message
         self.conn
@@ -643,14 +823,14 @@ impl Database {
                     message_id,
                     thread_id,
                     my_pk,
-                    serde_json::to_string(&payload_json).unwrap_or_default(),
+                    payload_text,
                     timestamp,
                 ],
             )
             .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
 
         // Update thread
-        self.update_thread_for_message(&thread_id, timestamp, false)?;
+        self.update_thread_for_message(&thread_id, message_id, timestamp, &payload_text, false)?;
 
         Ok(())
     }
@@ -751,7 +931,8 @@ impl Database {
             .map_err(|e| DatabaseError::SqliteError(e.to_string()))
     }
 
-    /// Save a breadcrumb
+    /// Save a breadcrumb; its `prev_hash` should be the
+    /// [`breadcrumb_chain_hash`] of the newest one stored
     pub fn save_breadcrumb(&mut self, breadcrumb: &Breadcrumb) -> Result<(), DatabaseError> {
         self.conn.execute(
             "INSERT OR IGNORE INTO breadcrumbs (h3_index, timestamp, signature, prev_hash) VALUES (?, ?, ?, ?)",
@@ -787,7 +968,7 @@ impl Database {
         Ok(())
     }
 
-    /// Clear all data from database
+    /// Clear all data of the identity from the database
     pub fn clear_all(&mut self) -> Result<(), DatabaseError> {
         tracing::info!("🗑️ Clearing all database data...");
         
@@ -795,7 +976,29 @@ impl Database {
             .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
         self.conn.execute("DELETE FROM threads", [])
             .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
-        let _ = self.conn.execute("DELETE FROM breadcrumbs", []);
+        // Everything else that belongs to the identity. Settings, paired
+        // hubs and the audit log stay with the device.
+        for table in [
+            "breadcrumbs",
+            "derived_keys",
+            "contacts",
+            "identities",
+            "scheduled_messages",
+            "pending_messages",
+            "ratchet_sessions",
+            "read_markers",
+            "thread_meta",
+            "reactions",
+            "quarantine",
+            "migration_tokens",
+            "notifications",
+            "notification_prefs",
+            "profiles",
+            "follows",
+            "bookmarks",
+        ] {
+            let _ = self.conn.execute(&format!("DELETE FROM {}", table), []);
+        }
         self.conn.execute("VACUUM", [])
             .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
         
@@ -803,6 +1006,20 @@ impl Database {
         Ok(())
     }
 
+    /// Commit anything still pending and checkpoint the WAL (if any) so
+    /// the file on disk is complete before the process exits
+    pub fn flush(&self) -> Result<(), DatabaseError> {
+        if !self.conn.is_autocommit() {
+            tracing::warn!("Committing transaction left open at shutdown");
+            self.conn
+                .execute_batch("COMMIT")
+                .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        }
+        self.conn
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+
     // ==================== Collection State ====================
 
     /// Get collection enabled state
@@ -830,6 +1047,34 @@ impl Database {
         Ok(())
     }
 
+    // ==================== Settings ====================
+
+    /// Get a setting value
+    pub fn get_setting(&self, key: &str) -> Option<String> {
+        self.conn
+            .query_row("SELECT value FROM settings WHERE key = ?", params![key], |row| row.get(0))
+            .ok()
+    }
+
+    /// Set a setting value
+    pub fn set_setting(&mut self, key: &str, value: &str) -> Result<(), DatabaseError> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO settings (key, value) VALUES (?, ?)",
+                params![key, value],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Remove a setting, restoring its default
+    pub fn delete_setting(&mut self, key: &str) -> Result<(), DatabaseError> {
+        self.conn
+            .execute("DELETE FROM settings WHERE key = ?", params![key])
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(())
+    }
+
     // ==================== Profile Operations ====================
 
     /// Get profile for a public key
@@ -837,7 +1082,7 @@ impl Database {
         let mut stmt = self
             .conn
             .prepare(
-                "SELECT public_key, display_name, bio, avatar_url, links_json, location_public, location_resolution, updated_at FROM profiles WHERE public_key = ?",
+                "SELECT public_key, display_name, bio, avatar_url, links_json, location_public, location_resolution, updated_at, avatar_blob_ref FROM profiles WHERE public_key = ?",
             )
             .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
 
@@ -848,6 +1093,7 @@ impl Database {
                     display_name: row.get(1)?,
                     bio: row.get(2)?,
                     avatar_url: row.get(3)?,
+                    avatar_blob_ref: row.get(8)?,
                     links: row.get(4)?,
                     location_public: row.get::<_, i32>(5)? == 1,
                     location_resolution: row.get(6)?,
@@ -868,12 +1114,13 @@ impl Database {
         self.conn
             .execute(
                 r#"
-                INSERT INTO profiles (public_key, display_name, bio, avatar_url, links_json, location_public, location_resolution, updated_at)
-                VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                INSERT INTO profiles (public_key, display_name, bio, avatar_url, links_json, location_public, location_resolution, updated_at, avatar_blob_ref)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
                 ON CONFLICT(public_key) DO UPDATE SET
                     display_name = excluded.display_name,
                     bio = excluded.bio,
                     avatar_url = excluded.avatar_url,
+                    avatar_blob_ref = excluded.avatar_blob_ref,
                     links_json = excluded.links_json,
                     location_public = excluded.location_public,
                     location_resolution = excluded.location_resolution,
@@ -887,12 +1134,320 @@ impl Database {
                     profile.links,
                     if profile.location_public { 1 } else { 0 },
                     profile.location_resolution,
-                    profile.updated_at
+                    profile.updated_at,
+                    profile.avatar_blob_ref
+                ],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(())
+    }
+
+    // ==================== Blob Operations ====================
+
+    /// Store a blob under its content reference (no-op if already present)
+    pub fn put_blob(&mut self, blob: &StoredBlob) -> Result<(), DatabaseError> {
+        self.conn
+            .execute(
+                "INSERT OR IGNORE INTO blobs (blob_ref, mime_type, data, encrypted, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    blob.blob_ref,
+                    blob.mime_type,
+                    blob.data,
+                    if blob.encrypted { 1 } else { 0 },
+                    blob.created_at
                 ],
             )
             .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
         Ok(())
     }
+
+    /// Get a blob by content reference
+    pub fn get_blob(&self, blob_ref: &str) -> Result<Option<StoredBlob>, DatabaseError> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT blob_ref, mime_type, data, encrypted, created_at FROM blobs WHERE blob_ref = ?1")
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        let mut rows = stmt
+            .query_map(params![blob_ref], |row| {
+                Ok(StoredBlob {
+                    blob_ref: row.get(0)?,
+                    mime_type: row.get(1)?,
+                    data: row.get(2)?,
+                    encrypted: row.get::<_, i32>(3)? == 1,
+                    created_at: row.get(4)?,
+                })
+            })
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        match rows.next() {
+            Some(row) => row.map(Some).map_err(|e| DatabaseError::SqliteError(e.to_string())),
+            None => Ok(None),
+        }
+    }
+
+    // ==================== Bookmark Operations ====================
+
+    /// Bookmark a post (no-op if already bookmarked)
+    pub fn add_bookmark(&mut self, owner_pk: &str, post_id: &str) -> Result<(), DatabaseError> {
+        self.conn
+            .execute(
+                "INSERT OR IGNORE INTO bookmarks (owner_pk, post_id, saved_at) VALUES (?, ?, ?)",
+                params![owner_pk, post_id, chrono::Utc::now().timestamp_millis()],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Remove a bookmark
+    pub fn remove_bookmark(&mut self, owner_pk: &str, post_id: &str) -> Result<(), DatabaseError> {
+        self.conn
+            .execute(
+                "DELETE FROM bookmarks WHERE owner_pk = ? AND post_id = ?",
+                params![owner_pk, post_id],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Get bookmarks for an identity, newest first
+    pub fn get_bookmarks(&self, owner_pk: &str) -> Result<Vec<Bookmark>, DatabaseError> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT post_id, saved_at FROM bookmarks WHERE owner_pk = ? ORDER BY saved_at DESC")
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        let bookmarks = stmt
+            .query_map(params![owner_pk], |row| {
+                Ok(Bookmark {
+                    post_id: row.get(0)?,
+                    saved_at: row.get(1)?,
+                })
+            })
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        Ok(bookmarks)
+    }
+
+    // ==================== Follow Operations ====================
+
+    /// Apply a follow/unfollow record if it is newer than what we have for
+    /// the pair. Returns whether the stored state changed.
+    pub fn apply_follow_record(&mut self, record: &FollowRecord) -> Result<bool, DatabaseError> {
+        let changed = self
+            .conn
+            .execute(
+                r#"
+                INSERT INTO follows (follower_pk, followee_pk, following, updated_at, signature)
+                VALUES (?, ?, ?, ?, ?)
+                ON CONFLICT(follower_pk, followee_pk) DO UPDATE SET
+                    following = excluded.following,
+                    updated_at = excluded.updated_at,
+                    signature = excluded.signature
+                WHERE excluded.updated_at > follows.updated_at
+                "#,
+                params![
+                    record.follower.to_lowercase(),
+                    record.followee.to_lowercase(),
+                    if record.action == FollowAction::Follow { 1 } else { 0 },
+                    record.timestamp,
+                    record.signature
+                ],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(changed > 0)
+    }
+
+    /// Keys `follower_pk` currently follows, most recent first
+    pub fn get_following(&self, follower_pk: &str) -> Result<Vec<String>, DatabaseError> {
+        self.follow_keys(
+            "SELECT followee_pk FROM follows WHERE follower_pk = ? AND following = 1 ORDER BY updated_at DESC",
+            follower_pk,
+        )
+    }
+
+    /// Keys known to follow `followee_pk`, most recent first
+    pub fn get_followers(&self, followee_pk: &str) -> Result<Vec<String>, DatabaseError> {
+        self.follow_keys(
+            "SELECT follower_pk FROM follows WHERE followee_pk = ? AND following = 1 ORDER BY updated_at DESC",
+            followee_pk,
+        )
+    }
+
+    fn follow_keys(&self, sql: &str, public_key: &str) -> Result<Vec<String>, DatabaseError> {
+        let mut stmt = self
+            .conn
+            .prepare(sql)
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        let keys = stmt
+            .query_map(params![public_key.to_lowercase()], |row| row.get(0))
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        Ok(keys)
+    }
+
+    // ==================== Notification Operations ====================
+
+    /// Record a notification. Returns false if one already exists for
+    /// this post and kind, so callers only alert once.
+    pub fn add_notification(
+        &mut self,
+        owner_pk: &str,
+        kind: &str,
+        post_id: &str,
+        actor_public_key: &str,
+        actor_handle: Option<&str>,
+        preview: &str,
+    ) -> Result<bool, DatabaseError> {
+        let inserted = self
+            .conn
+            .execute(
+                r#"
+                INSERT OR IGNORE INTO notifications (owner_pk, kind, post_id, actor_public_key, actor_handle, preview, created_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?)
+                "#,
+                params![
+                    owner_pk,
+                    kind,
+                    post_id,
+                    actor_public_key,
+                    actor_handle,
+                    preview,
+                    chrono::Utc::now().timestamp_millis()
+                ],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(inserted > 0)
+    }
+
+    /// Get notifications for an identity, newest first
+    pub fn get_notifications(
+        &self,
+        owner_pk: &str,
+        unread_only: bool,
+        limit: u32,
+    ) -> Result<Vec<Notification>, DatabaseError> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                r#"
+                SELECT id, kind, post_id, actor_public_key, actor_handle, preview, created_at, is_read
+                FROM notifications
+                WHERE owner_pk = ? AND (? = 0 OR is_read = 0)
+                ORDER BY created_at DESC
+                LIMIT ?
+                "#,
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        let notifications = stmt
+            .query_map(params![owner_pk, unread_only as i32, limit], |row| {
+                Ok(Notification {
+                    id: row.get(0)?,
+                    kind: row.get(1)?,
+                    post_id: row.get(2)?,
+                    actor_public_key: row.get(3)?,
+                    actor_handle: row.get(4)?,
+                    preview: row.get(5)?,
+                    created_at: row.get(6)?,
+                    read: row.get::<_, i32>(7)? == 1,
+                })
+            })
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        Ok(notifications)
+    }
+
+    /// Mark a notification as read
+    pub fn mark_notification_read(&mut self, owner_pk: &str, id: i64) -> Result<(), DatabaseError> {
+        self.conn
+            .execute(
+                "UPDATE notifications SET is_read = 1 WHERE owner_pk = ? AND id = ?",
+                params![owner_pk, id],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(())
+    }
+}
+
+/// Longest thread list preview, in characters
+const PREVIEW_CHARS: usize = 120;
+
+/// Columns `thread_from_row` reads, in order
+const THREAD_COLUMNS: &str = "id, participant_public_key, participant_handle, preview_text, \
+    last_message_at, unread_count, is_pinned, is_muted, subject, last_message_id";
+
+/// Assignments pointing a thread's summary at its newest message, for an
+/// `UPDATE threads` over a thread row. The preview is cut at
+/// `PREVIEW_CHARS`, as `preview_text` does.
+/// Payload types of control messages. They're stored like any other
+/// message but left out of the conversation and of unread counts.
+pub const CONTROL_PAYLOAD_TYPES: [&str; 3] = ["reaction", "receipt", "typing"];
+
+pub fn is_control_payload_type(payload_type: &str) -> bool {
+    CONTROL_PAYLOAD_TYPES.contains(&payload_type)
+}
+
+/// SQL condition on `payload_type` leaving out control messages
+fn not_control_sql() -> String {
+    let types: Vec<String> = CONTROL_PAYLOAD_TYPES.iter().map(|t| format!("'{}'", t)).collect();
+    format!("payload_type NOT IN ({})", types.join(", "))
+}
+
+/// The hash a breadcrumb's successor names as its `prev_hash`
+pub fn breadcrumb_chain_hash(breadcrumb: &Breadcrumb) -> String {
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{}:{}:{}", breadcrumb.h3_index, breadcrumb.timestamp, breadcrumb.signature));
+    hex::encode(hasher.finalize())
+}
+
+const LAST_MESSAGE_COLUMNS: &str = r#"
+    last_message_id = (SELECT id FROM messages WHERE thread_id = threads.id ORDER BY timestamp DESC, id DESC LIMIT 1),
+    last_message_at = COALESCE((SELECT MAX(timestamp) FROM messages WHERE thread_id = threads.id), last_message_at),
+    preview_text = (
+        SELECT CASE WHEN json_valid(payload_json) THEN substr(json_extract(payload_json, '$.text'), 1, 120) END
+        FROM messages WHERE thread_id = threads.id ORDER BY timestamp DESC, id DESC LIMIT 1
+    )
+"#;
+
+/// Thread list preview of a message payload: its text, if it has any
+fn preview_text(payload_json: &str) -> Option<String> {
+    let payload: serde_json::Value = serde_json::from_str(payload_json).ok()?;
+    payload["text"].as_str().map(|text| text.chars().take(PREVIEW_CHARS).collect())
+}
+
+/// Point the summary of `thread_id` back at its newest message, after the
+/// one it showed was deleted or moved
+fn refresh_last_message(conn: &Connection, thread_id: &str) -> Result<(), DatabaseError> {
+    conn.execute(
+        &format!("UPDATE threads SET {} WHERE id = ?", LAST_MESSAGE_COLUMNS),
+        params![thread_id],
+    )
+    .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+    Ok(())
+}
+
+fn thread_from_row(row: &Row) -> rusqlite::Result<ThreadPreview> {
+    Ok(ThreadPreview {
+        id: row.get(0)?,
+        participant_public_key: row.get(1)?,
+        participant_handle: row.get(2)?,
+        last_message_preview: row.get(3)?,
+        last_message_at: row.get(4)?,
+        unread_count: row.get(5)?,
+        is_pinned: row.get::<_, i32>(6)? == 1,
+        is_muted: row.get::<_, i32>(7)? == 1,
+        subject: row.get(8)?,
+        last_message_id: row.get(9)?,
+    })
 }
 
 /// Database errors
@@ -906,4 +1461,107 @@ pub enum DatabaseError {
 
     #[error("Encryption error: {0}")]
     EncryptionError(String),
+
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_filter_by_payload_type() {
+        let mut db = Database::open_in_memory().unwrap();
+        let peer = "22".repeat(32);
+        let thread_id = "thread-1";
+        let received = [
+            ("m1", "text", 1_000),
+            ("m2", "reaction", 2_000),
+            ("m3", "file", 3_000),
+            ("m4", "typing", 4_000),
+            ("m5", "receipt", 5_000),
+        ];
+        for (id, payload_type, timestamp) in received {
+            let payload = serde_json::json!({ "text": id });
+            db.save_received_message(id, thread_id, &peer, None, payload_type, &payload, timestamp, true, None)
+                .unwrap();
+        }
+        let ids = |messages: Vec<Message>| messages.into_iter().map(|m| m.id).collect::<Vec<_>>();
+
+        // By default control messages are left out, and aren't unread
+        assert_eq!(ids(db.get_messages(thread_id, 10, None).unwrap()), ["m3", "m1"]);
+        assert_eq!(db.get_thread(thread_id).unwrap().unwrap().unread_count, 2);
+
+        let displayable = ["text".to_string(), "file".to_string()];
+        assert_eq!(ids(db.get_messages(thread_id, 10, Some(&displayable)).unwrap()), ["m3", "m1"]);
+        let reactions = ["reaction".to_string()];
+        assert_eq!(ids(db.get_messages(thread_id, 10, Some(&reactions)).unwrap()), ["m2"]);
+        assert!(db.get_messages(thread_id, 10, Some(&[])).unwrap().is_empty());
+
+        // Deleting an unread control message leaves the count alone
+        db.delete_message("m4").unwrap();
+        assert_eq!(db.get_thread(thread_id).unwrap().unwrap().unread_count, 2);
+    }
+
+    #[test]
+    fn test_bookmarks_are_per_identity_and_newest_first() {
+        let mut db = Database::open_in_memory().unwrap();
+        let (me, other) = ("11".repeat(32), "22".repeat(32));
+        let ids = |bookmarks: Vec<Bookmark>| bookmarks.into_iter().map(|b| b.post_id).collect::<Vec<_>>();
+
+        db.add_bookmark(&me, "older").unwrap();
+        let saved_at = db.get_bookmarks(&me).unwrap()[0].saved_at;
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        db.add_bookmark(&me, "newer").unwrap();
+        db.add_bookmark(&other, "theirs").unwrap();
+
+        // Saving again keeps the original time
+        db.add_bookmark(&me, "older").unwrap();
+        let mine = db.get_bookmarks(&me).unwrap();
+        assert_eq!(mine[1].saved_at, saved_at);
+        assert_eq!(ids(mine), ["newer", "older"]);
+        assert_eq!(ids(db.get_bookmarks(&other).unwrap()), ["theirs"]);
+
+        db.remove_bookmark(&me, "newer").unwrap();
+        db.remove_bookmark(&me, "theirs").unwrap();
+        assert_eq!(ids(db.get_bookmarks(&me).unwrap()), ["older"]);
+        assert_eq!(ids(db.get_bookmarks(&other).unwrap()), ["theirs"]);
+    }
+
+    #[test]
+    fn test_clear_all_leaves_nothing_of_the_identity() {
+        let mut db = Database::open_in_memory().unwrap();
+        let peer = "22".repeat(32);
+        let payload = serde_json::json!({ "text": "hi" });
+        db.save_received_message("m1", "thread-1", &peer, None, "text", &payload, 1_000, true, None).unwrap();
+        db.apply_read_marker(&ReadMarker { thread_id: "thread-1".to_string(), read_up_to: 1_000, updated_at: 1 })
+            .unwrap();
+        db.record_migration_token("t1", &peer, 100, "key").unwrap();
+        db.add_bookmark(&peer, "post-1").unwrap();
+        db.save_scheduled_message(&ScheduledMessage {
+            id: "s1".to_string(),
+            recipient_handle: Some("alice".to_string()),
+            recipient_public_key: None,
+            payload_type: "text".to_string(),
+            payload,
+            thread_id: None,
+            reply_to_id: None,
+            send_at: 1_000,
+            created_at: 1_000,
+            last_error: None,
+            attempts: 0,
+        })
+        .unwrap();
+
+        db.clear_all().unwrap();
+
+        for table in ["messages", "threads", "read_markers", "migration_tokens", "bookmarks", "scheduled_messages"] {
+            let count: i64 = db
+                .conn
+                .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(count, 0, "{} not cleared", table);
+        }
+    }
 }
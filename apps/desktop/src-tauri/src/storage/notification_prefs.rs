@@ -0,0 +1,51 @@
+//! Notification Preferences
+//!
+//! One row per identity holding its `NotificationPrefs` as JSON, so new
+//! toggles can be added without a migration. An identity without a row
+//! gets the defaults.
+
+use super::{Database, DatabaseError};
+use crate::notifications::NotificationPrefs;
+use rusqlite::{params, Connection, OptionalExtension};
+
+pub(super) fn create_table(conn: &Connection) -> Result<(), DatabaseError> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS notification_prefs (
+            owner_pk TEXT PRIMARY KEY,
+            prefs_json TEXT NOT NULL,
+            updated_at INTEGER NOT NULL
+        );
+        "#,
+    )
+    .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+}
+
+impl Database {
+    pub fn load_notification_prefs(&self, owner_pk: &str) -> Result<NotificationPrefs, DatabaseError> {
+        let json: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT prefs_json FROM notification_prefs WHERE owner_pk = ?",
+                params![owner_pk.to_lowercase()],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        match json {
+            Some(json) => serde_json::from_str(&json).map_err(|e| DatabaseError::SqliteError(e.to_string())),
+            None => Ok(NotificationPrefs::default()),
+        }
+    }
+
+    pub fn save_notification_prefs(&self, owner_pk: &str, prefs: &NotificationPrefs) -> Result<(), DatabaseError> {
+        let json = serde_json::to_string(prefs).map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO notification_prefs (owner_pk, prefs_json, updated_at) VALUES (?, ?, ?)",
+                params![owner_pk.to_lowercase(), json, chrono::Utc::now().timestamp_millis()],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+//! Ratchet Sessions
+//!
+//! Forward secrecy sessions (see `gns_crypto_core::ratchet`) of each
+//! identity, kept as the crypto core exports them. They have to outlive a
+//! restart: a peer that negotiated a session goes on sending in it, and
+//! those messages can't be opened any other way. The export holds chain
+//! keys, but only ever the ones for messages not yet received.
+
+use super::{Database, DatabaseError};
+use gns_crypto_core::RatchetSessions;
+use rusqlite::{params, Connection, OptionalExtension};
+
+pub(super) fn create_table(conn: &Connection) -> Result<(), DatabaseError> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS ratchet_sessions (
+            identity_public_key TEXT PRIMARY KEY,
+            sessions_json TEXT NOT NULL,
+            updated_at INTEGER NOT NULL
+        );
+        "#,
+    )
+    .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+}
+
+impl Database {
+    /// Sessions saved for `identity_public_key`; none if nothing was saved
+    pub fn load_ratchet_sessions(&self, identity_public_key: &str) -> Result<RatchetSessions, DatabaseError> {
+        let json: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT sessions_json FROM ratchet_sessions WHERE identity_public_key = ?",
+                params![identity_public_key.to_lowercase()],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        match json {
+            Some(json) => RatchetSessions::import(&json).map_err(|e| DatabaseError::EncryptionError(e.to_string())),
+            None => Ok(RatchetSessions::new()),
+        }
+    }
+
+    /// Replace what is saved for `identity_public_key` with `sessions`
+    pub fn save_ratchet_sessions(
+        &self,
+        identity_public_key: &str,
+        sessions: &RatchetSessions,
+    ) -> Result<(), DatabaseError> {
+        let json = sessions
+            .export()
+            .map_err(|e| DatabaseError::EncryptionError(e.to_string()))?;
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO ratchet_sessions (identity_public_key, sessions_json, updated_at) VALUES (?, ?, ?)",
+                params![identity_public_key.to_lowercase(), json.as_str(), chrono::Utc::now().timestamp_millis()],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gns_crypto_core::{create_envelope_with_ratchet, open_envelope_with_ratchet, Compression, GnsIdentity, MessageKeyCache};
+
+    #[test]
+    fn test_saved_sessions_open_later_messages() {
+        let alice = GnsIdentity::generate();
+        let bob = GnsIdentity::generate();
+        let (alice_sessions, bob_sessions) = (RatchetSessions::new(), RatchetSessions::new());
+        let keys = MessageKeyCache::default();
+        let send = |from: &GnsIdentity, sessions: &RatchetSessions, to: &GnsIdentity, text: &str| {
+            create_envelope_with_ratchet(
+                from,
+                None,
+                &to.public_key_hex(),
+                &to.encryption_key_hex(),
+                "text/plain",
+                text.as_bytes(),
+                None,
+                None,
+                Compression::Off,
+                &keys,
+                sessions,
+            )
+            .unwrap()
+        };
+
+        // Alice offers, Bob answers
+        open_envelope_with_ratchet(&bob, &send(&alice, &alice_sessions, &bob, "hi"), &keys, &bob_sessions).unwrap();
+        open_envelope_with_ratchet(&alice, &send(&bob, &bob_sessions, &alice, "hey"), &keys, &alice_sessions).unwrap();
+        let ratcheted = send(&alice, &alice_sessions, &bob, "still there?");
+
+        let db = Database::open_in_memory().unwrap();
+        let bob_pk = bob.public_key_hex();
+        assert!(!db.load_ratchet_sessions(&bob_pk).unwrap().has_session(&alice.public_key_hex()));
+        db.save_ratchet_sessions(&bob_pk.to_uppercase(), &bob_sessions).unwrap();
+
+        // As after a restart
+        let restored = db.load_ratchet_sessions(&bob_pk).unwrap();
+        let opened = open_envelope_with_ratchet(&bob, &ratcheted, &keys, &restored).unwrap();
+        assert_eq!(opened.payload, b"still there?");
+    }
+}
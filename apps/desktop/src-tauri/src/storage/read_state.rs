@@ -0,0 +1,240 @@
+//! Read State
+//!
+//! Which incoming messages the user has read, as a per-thread marker:
+//! everything up to `read_up_to` is read. Markers are what devices of the
+//! same identity exchange (see `crate::read_sync`), so each remembers
+//! when it was set and whether it still has to go out. A marker only ever
+//! touches incoming messages; the status of our own messages belongs to
+//! the peer's read receipts.
+
+use super::{not_control_sql, Database, DatabaseError};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde::{Deserialize, Serialize};
+
+pub(super) fn create_table(conn: &Connection) -> Result<(), DatabaseError> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS read_markers (
+            thread_id TEXT PRIMARY KEY,
+            read_up_to INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL,
+            synced INTEGER NOT NULL DEFAULT 0
+        );
+        "#,
+    )
+    .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+}
+
+/// Incoming messages in `thread_id` up to `read_up_to` (ms) are read, as
+/// of `updated_at` (ms). The latest `updated_at` wins.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadMarker {
+    pub thread_id: String,
+    pub read_up_to: i64,
+    pub updated_at: i64,
+}
+
+impl Database {
+    /// Mark a thread as read up to its latest message. The marker is kept
+    /// for the user's other devices and returned.
+    pub fn mark_thread_read(&mut self, thread_id: &str) -> Result<ReadMarker, DatabaseError> {
+        let tx = self
+            .conn
+            .transaction()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        let read_up_to: i64 = tx
+            .query_row(
+                "SELECT COALESCE(MAX(timestamp), 0) FROM messages WHERE thread_id = ?",
+                params![thread_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        // A local read always wins, even over a marker from a device whose
+        // clock runs ahead
+        let updated_at = match stored_marker(&tx, thread_id)? {
+            Some(stored) => now_millis().max(stored.updated_at + 1),
+            None => now_millis(),
+        };
+
+        let marker = ReadMarker {
+            thread_id: thread_id.to_string(),
+            read_up_to,
+            updated_at,
+        };
+        store_marker(&tx, &marker, false)?;
+        tx.commit()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(marker)
+    }
+
+    /// Apply a marker from another device. Returns false, changing
+    /// nothing, if the stored marker for the thread is as new or newer.
+    pub fn apply_read_marker(&mut self, marker: &ReadMarker) -> Result<bool, DatabaseError> {
+        let tx = self
+            .conn
+            .transaction()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        if let Some(stored) = stored_marker(&tx, &marker.thread_id)? {
+            if stored.updated_at >= marker.updated_at {
+                return Ok(false);
+            }
+        }
+        store_marker(&tx, marker, true)?;
+        tx.commit()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(true)
+    }
+
+    /// Markers set on this device that other devices haven't been sent
+    pub fn unsynced_read_markers(&self) -> Result<Vec<ReadMarker>, DatabaseError> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT thread_id, read_up_to, updated_at FROM read_markers WHERE synced = 0 ORDER BY updated_at")
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(ReadMarker {
+                    thread_id: row.get(0)?,
+                    read_up_to: row.get(1)?,
+                    updated_at: row.get(2)?,
+                })
+            })
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+
+    /// Record that `markers` went out. A thread read again since stays
+    /// unsynced.
+    pub fn mark_read_markers_synced(&mut self, markers: &[ReadMarker]) -> Result<(), DatabaseError> {
+        for marker in markers {
+            self.conn
+                .execute(
+                    "UPDATE read_markers SET synced = 1 WHERE thread_id = ? AND updated_at = ?",
+                    params![marker.thread_id, marker.updated_at],
+                )
+                .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Whether an incoming message at `timestamp` in `thread_id` is
+    /// already covered by the thread's marker, e.g. because another device
+    /// read it before it reached this one
+    pub(super) fn is_read_up_to(&self, thread_id: &str, timestamp: i64) -> Result<bool, DatabaseError> {
+        let read_up_to: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT read_up_to FROM read_markers WHERE thread_id = ?",
+                params![thread_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(read_up_to.is_some_and(|read_up_to| timestamp <= read_up_to))
+    }
+}
+
+fn stored_marker(tx: &Transaction, thread_id: &str) -> Result<Option<ReadMarker>, DatabaseError> {
+    tx.query_row(
+        "SELECT thread_id, read_up_to, updated_at FROM read_markers WHERE thread_id = ?",
+        params![thread_id],
+        |row| {
+            Ok(ReadMarker {
+                thread_id: row.get(0)?,
+                read_up_to: row.get(1)?,
+                updated_at: row.get(2)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+}
+
+/// Store `marker` and bring the thread's messages and unread count in
+/// line with it
+fn store_marker(tx: &Transaction, marker: &ReadMarker, synced: bool) -> Result<(), DatabaseError> {
+    tx.execute(
+        r#"
+        INSERT INTO read_markers (thread_id, read_up_to, updated_at, synced)
+        VALUES (?1, ?2, ?3, ?4)
+        ON CONFLICT(thread_id) DO UPDATE SET
+            read_up_to = excluded.read_up_to,
+            updated_at = excluded.updated_at,
+            synced = excluded.synced
+        "#,
+        params![marker.thread_id, marker.read_up_to, marker.updated_at, synced],
+    )
+    .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+    tx.execute(
+        "UPDATE messages SET is_read = 1 WHERE thread_id = ? AND is_outgoing = 0 AND timestamp <= ?",
+        params![marker.thread_id, marker.read_up_to],
+    )
+    .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+    tx.execute(
+        &format!(
+            r#"
+            UPDATE threads SET unread_count = (
+                SELECT COUNT(*) FROM messages WHERE thread_id = ?1 AND is_outgoing = 0 AND is_read = 0 AND {}
+            )
+            WHERE id = ?1
+            "#,
+            not_control_sql()
+        ),
+        params![marker.thread_id],
+    )
+    .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+    Ok(())
+}
+
+fn now_millis() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_read(db: &Database, message_id: &str) -> bool {
+        db.conn
+            .query_row("SELECT is_read FROM messages WHERE id = ?", params![message_id], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_newer_marker_wins() {
+        let mut db = Database::open_in_memory().unwrap();
+        let me = "11".repeat(32);
+        let peer = "22".repeat(32);
+        db.save_synced_incoming_message("m1", &peer, "one", 1_000, None, &me).unwrap();
+        db.save_synced_incoming_message("m2", &peer, "two", 2_000, None, &me).unwrap();
+        let thread_id = gns_crypto_core::compute_thread_id(&me, &peer);
+
+        let marker = |read_up_to, updated_at| ReadMarker { thread_id: thread_id.clone(), read_up_to, updated_at };
+        assert!(db.apply_read_marker(&marker(1_000, 5_000)).unwrap());
+        assert!(is_read(&db, "m1") && !is_read(&db, "m2"));
+        assert_eq!(db.get_thread(&thread_id).unwrap().unwrap().unread_count, 1);
+
+        // An older marker arriving late changes nothing
+        assert!(!db.apply_read_marker(&marker(2_000, 4_000)).unwrap());
+        assert!(!is_read(&db, "m2"));
+
+        // A message the marker already covers arrives read
+        db.save_synced_incoming_message("m0", &peer, "zero", 500, None, &me).unwrap();
+        assert!(is_read(&db, "m0"));
+        assert_eq!(db.get_thread(&thread_id).unwrap().unwrap().unread_count, 1);
+
+        // Remote markers don't go back out; local ones do, and win over
+        // a marker stamped ahead of our clock
+        assert!(db.unsynced_read_markers().unwrap().is_empty());
+        db.apply_read_marker(&marker(1_000, i64::MAX - 1)).unwrap();
+        let local = db.mark_thread_read(&thread_id).unwrap();
+        assert_eq!((local.read_up_to, local.updated_at), (2_000, i64::MAX));
+        assert!(is_read(&db, "m2"));
+        assert_eq!(db.unsynced_read_markers().unwrap(), vec![local.clone()]);
+        db.mark_read_markers_synced(&[local]).unwrap();
+        assert!(db.unsynced_read_markers().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,262 @@
+//! Scheduled Messages
+//!
+//! Messages composed now and sent later. Rows stay here until the
+//! scheduler hands them to the normal send path, so pending sends survive
+//! a restart. A message that failed `MAX_SEND_ATTEMPTS` times stays here,
+//! no longer due, until it is rescheduled or cancelled.
+
+use super::{Database, DatabaseError};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+
+/// Failed sends after which a message is no longer retried
+pub const MAX_SEND_ATTEMPTS: u32 = 5;
+
+/// A message waiting for its send time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScheduledMessage {
+    pub id: String,
+    pub recipient_handle: Option<String>,
+    pub recipient_public_key: Option<String>,
+    pub payload_type: String,
+    pub payload: serde_json::Value,
+    pub thread_id: Option<String>,
+    pub reply_to_id: Option<String>,
+    /// Unix millis
+    pub send_at: i64,
+    pub created_at: i64,
+    /// Why the last send attempt failed, if one did
+    pub last_error: Option<String>,
+    /// Failed send attempts so far
+    pub attempts: u32,
+}
+
+pub(super) fn create_table(conn: &Connection) -> Result<(), DatabaseError> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS scheduled_messages (
+            id TEXT PRIMARY KEY,
+            recipient_handle TEXT,
+            recipient_public_key TEXT,
+            payload_type TEXT NOT NULL,
+            payload_json TEXT NOT NULL,
+            thread_id TEXT,
+            reply_to_id TEXT,
+            send_at INTEGER NOT NULL,
+            created_at INTEGER NOT NULL,
+            last_error TEXT
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_scheduled_send_at ON scheduled_messages(send_at, created_at);
+        "#,
+    )
+    .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+    // Migration: count failed sends so a broken message is eventually given up on
+    let _ = conn.execute("ALTER TABLE scheduled_messages ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0", []);
+    Ok(())
+}
+
+const COLUMNS: &str = "id, recipient_handle, recipient_public_key, payload_type, payload_json, \
+                       thread_id, reply_to_id, send_at, created_at, last_error, attempts";
+
+/// Send order: by send time, then by when they were scheduled
+const ORDER: &str = "ORDER BY send_at ASC, created_at ASC, rowid ASC";
+
+fn from_row(row: &rusqlite::Row) -> rusqlite::Result<ScheduledMessage> {
+    let payload_json: String = row.get(4)?;
+    Ok(ScheduledMessage {
+        id: row.get(0)?,
+        recipient_handle: row.get(1)?,
+        recipient_public_key: row.get(2)?,
+        payload_type: row.get(3)?,
+        payload: serde_json::from_str(&payload_json).unwrap_or_default(),
+        thread_id: row.get(5)?,
+        reply_to_id: row.get(6)?,
+        send_at: row.get(7)?,
+        created_at: row.get(8)?,
+        last_error: row.get(9)?,
+        attempts: row.get(10)?,
+    })
+}
+
+impl Database {
+    pub fn save_scheduled_message(&mut self, message: &ScheduledMessage) -> Result<(), DatabaseError> {
+        self.conn
+            .execute(
+                &format!("INSERT INTO scheduled_messages ({}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)", COLUMNS),
+                params![
+                    message.id,
+                    message.recipient_handle,
+                    message.recipient_public_key,
+                    message.payload_type,
+                    message.payload.to_string(),
+                    message.thread_id,
+                    message.reply_to_id,
+                    message.send_at,
+                    message.created_at,
+                    message.last_error,
+                    message.attempts,
+                ],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Every scheduled message, in send order
+    pub fn list_scheduled_messages(&self) -> Result<Vec<ScheduledMessage>, DatabaseError> {
+        self.query_scheduled(&format!("SELECT {} FROM scheduled_messages {}", COLUMNS, ORDER), params![])
+    }
+
+    /// Messages whose send time is at or before `now`, in send order,
+    /// leaving out those that failed too often
+    pub fn due_scheduled_messages(&self, now: i64) -> Result<Vec<ScheduledMessage>, DatabaseError> {
+        self.query_scheduled(
+            &format!(
+                "SELECT {} FROM scheduled_messages WHERE send_at <= ? AND attempts < ? {}",
+                COLUMNS, ORDER
+            ),
+            params![now, MAX_SEND_ATTEMPTS],
+        )
+    }
+
+    /// Send time of the earliest scheduled message still to be tried
+    pub fn next_scheduled_at(&self) -> Result<Option<i64>, DatabaseError> {
+        self.conn
+            .query_row(
+                "SELECT MIN(send_at) FROM scheduled_messages WHERE attempts < ?",
+                params![MAX_SEND_ATTEMPTS],
+                |row| row.get(0),
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+
+    pub fn get_scheduled_message(&self, id: &str) -> Result<Option<ScheduledMessage>, DatabaseError> {
+        self.conn
+            .query_row(
+                &format!("SELECT {} FROM scheduled_messages WHERE id = ?", COLUMNS),
+                params![id],
+                from_row,
+            )
+            .optional()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+
+    /// Move a message to a new send time, giving it a fresh set of send
+    /// attempts. Returns false if it no longer exists (already sent or
+    /// cancelled).
+    pub fn reschedule_message(&mut self, id: &str, send_at: i64) -> Result<bool, DatabaseError> {
+        let changed = self
+            .conn
+            .execute(
+                "UPDATE scheduled_messages SET send_at = ?, last_error = NULL, attempts = 0 WHERE id = ?",
+                params![send_at, id],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(changed > 0)
+    }
+
+    /// Drop a scheduled message, after it was sent or when cancelled.
+    /// Returns false if it was already gone.
+    pub fn delete_scheduled_message(&mut self, id: &str) -> Result<bool, DatabaseError> {
+        let changed = self
+            .conn
+            .execute("DELETE FROM scheduled_messages WHERE id = ?", params![id])
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(changed > 0)
+    }
+
+    /// Count a failed send attempt. Returns the attempts made so far.
+    pub fn record_scheduled_failure(&mut self, id: &str, error: &str) -> Result<u32, DatabaseError> {
+        self.conn
+            .execute(
+                "UPDATE scheduled_messages SET last_error = ?, attempts = attempts + 1 WHERE id = ?",
+                params![error, id],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        self.conn
+            .query_row("SELECT attempts FROM scheduled_messages WHERE id = ?", params![id], |row| row.get(0))
+            .optional()
+            .map(Option::unwrap_or_default)
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+
+    fn query_scheduled(
+        &self,
+        sql: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> Result<Vec<ScheduledMessage>, DatabaseError> {
+        let mut stmt = self
+            .conn
+            .prepare(sql)
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        let rows = stmt
+            .query_map(params, from_row)
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduled(id: &str, send_at: i64, created_at: i64) -> ScheduledMessage {
+        ScheduledMessage {
+            id: id.to_string(),
+            recipient_handle: Some("alice".to_string()),
+            recipient_public_key: None,
+            payload_type: "text".to_string(),
+            payload: serde_json::json!({ "text": id }),
+            thread_id: None,
+            reply_to_id: None,
+            send_at,
+            created_at,
+            last_error: None,
+            attempts: 0,
+        }
+    }
+
+    #[test]
+    fn test_due_messages_in_send_order() {
+        let mut db = Database::open_in_memory().unwrap();
+        db.save_scheduled_message(&scheduled("later", 300, 1)).unwrap();
+        db.save_scheduled_message(&scheduled("second", 100, 5)).unwrap();
+        db.save_scheduled_message(&scheduled("first", 100, 2)).unwrap();
+        db.save_scheduled_message(&scheduled("earliest", 50, 9)).unwrap();
+
+        let due: Vec<_> = db.due_scheduled_messages(200).unwrap().into_iter().map(|m| m.id).collect();
+        assert_eq!(due, vec!["earliest", "first", "second"]);
+        assert_eq!(db.next_scheduled_at().unwrap(), Some(50));
+
+        assert!(db.reschedule_message("later", 10).unwrap());
+        assert_eq!(db.due_scheduled_messages(200).unwrap()[0].id, "later");
+
+        assert!(db.delete_scheduled_message("later").unwrap());
+        assert!(!db.delete_scheduled_message("later").unwrap());
+        assert!(!db.reschedule_message("later", 10).unwrap());
+        assert_eq!(db.list_scheduled_messages().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_message_is_given_up_after_max_attempts() {
+        let mut db = Database::open_in_memory().unwrap();
+        db.save_scheduled_message(&scheduled("broken", 100, 1)).unwrap();
+
+        for attempt in 1..=MAX_SEND_ATTEMPTS {
+            assert_eq!(db.due_scheduled_messages(200).unwrap().len(), 1);
+            assert_eq!(db.record_scheduled_failure("broken", "no route").unwrap(), attempt);
+        }
+        assert!(db.due_scheduled_messages(200).unwrap().is_empty());
+        assert_eq!(db.next_scheduled_at().unwrap(), None);
+        // Still listed, with why it failed
+        let listed = db.list_scheduled_messages().unwrap();
+        assert_eq!(listed[0].attempts, MAX_SEND_ATTEMPTS);
+        assert_eq!(listed[0].last_error.as_deref(), Some("no route"));
+
+        // Rescheduling tries again
+        assert!(db.reschedule_message("broken", 150).unwrap());
+        assert_eq!(db.due_scheduled_messages(200).unwrap()[0].attempts, 0);
+    }
+}
@@ -0,0 +1,303 @@
+//! Message Search - SQLite FTS5 index over message bodies
+//!
+//! `messages_fts` shares rowids with `messages` and is kept in sync by
+//! triggers, so every write path (sent, received, synced) is indexed
+//! without touching the insert code. Snippets are built per row by FTS5,
+//! so highlighting never spans two messages.
+
+use super::{Database, DatabaseError};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+/// Markers FTS5 wraps around matched terms; split out before returning
+const MATCH_START: char = '\u{0002}';
+const MATCH_END: char = '\u{0003}';
+const SNIPPET_TOKENS: u32 = 16;
+
+/// Result ordering for message search
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchOrder {
+    #[default]
+    Recent,
+    Relevance,
+}
+
+/// A run of snippet text, flagged if it matched the query
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SnippetSegment {
+    pub text: String,
+    pub highlighted: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageSearchHit {
+    pub message_id: String,
+    pub thread_id: String,
+    pub from_public_key: String,
+    pub is_outgoing: bool,
+    pub timestamp: i64,
+    pub snippet: Vec<SnippetSegment>,
+}
+
+/// Searchable text of a message row: subject plus text/body
+fn body_sql(row: &str) -> String {
+    format!(
+        "CASE WHEN json_valid({r}.payload_json) THEN trim(\
+            COALESCE(json_extract({r}.payload_json, '$.subject'), '') || ' ' || \
+            COALESCE(json_extract({r}.payload_json, '$.text'), json_extract({r}.payload_json, '$.body'), '')\
+        ) ELSE '' END",
+        r = row
+    )
+}
+
+/// Create the index and its triggers, and index any rows written before
+/// the index existed
+pub(super) fn create_index(conn: &Connection) -> Result<(), DatabaseError> {
+    let new_body = body_sql("new");
+    let sql = format!(
+        r#"
+        -- INSERT OR REPLACE must fire the delete trigger for the old row
+        PRAGMA recursive_triggers = ON;
+
+        CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
+            body,
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+
+        CREATE TRIGGER IF NOT EXISTS messages_fts_insert AFTER INSERT ON messages BEGIN
+            INSERT INTO messages_fts(rowid, body) VALUES (new.rowid, {new_body});
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS messages_fts_delete AFTER DELETE ON messages BEGIN
+            DELETE FROM messages_fts WHERE rowid = old.rowid;
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS messages_fts_update AFTER UPDATE OF payload_json ON messages BEGIN
+            DELETE FROM messages_fts WHERE rowid = old.rowid;
+            INSERT INTO messages_fts(rowid, body) VALUES (new.rowid, {new_body});
+        END;
+
+        INSERT INTO messages_fts(rowid, body)
+            SELECT rowid, {existing_body} FROM messages
+            WHERE rowid NOT IN (SELECT rowid FROM messages_fts);
+        "#,
+        new_body = new_body,
+        existing_body = body_sql("messages"),
+    );
+
+    conn.execute_batch(&sql)
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+}
+
+/// Drop the index contents and index every message again
+pub(super) fn rebuild_index(conn: &Connection) -> Result<(), DatabaseError> {
+    conn.execute_batch(&format!(
+        "DELETE FROM messages_fts; INSERT INTO messages_fts(rowid, body) SELECT rowid, {} FROM messages;",
+        body_sql("messages")
+    ))
+    .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+}
+
+impl Database {
+    /// Full-text search over messages, optionally limited to one thread
+    pub fn search_messages(
+        &self,
+        query: &str,
+        thread_id: Option<&str>,
+        order: SearchOrder,
+        limit: u32,
+    ) -> Result<Vec<MessageSearchHit>, DatabaseError> {
+        let Some(fts_query) = to_fts_query(query) else {
+            return Ok(Vec::new());
+        };
+
+        let order_by = match order {
+            SearchOrder::Recent => "m.timestamp DESC",
+            SearchOrder::Relevance => "bm25(messages_fts), m.timestamp DESC",
+        };
+
+        let sql = format!(
+            r#"
+            SELECT m.id, m.thread_id, m.from_public_key, m.is_outgoing, m.timestamp,
+                   snippet(messages_fts, 0, char(2), char(3), '…', {tokens})
+            FROM messages_fts
+            JOIN messages m ON m.rowid = messages_fts.rowid
+            WHERE messages_fts MATCH ?1 AND (?2 IS NULL OR m.thread_id = ?2)
+            ORDER BY {order_by}
+            LIMIT ?3
+            "#,
+            tokens = SNIPPET_TOKENS,
+            order_by = order_by,
+        );
+
+        let mut stmt = self
+            .conn
+            .prepare(&sql)
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        let hits = stmt
+            .query_map(params![fts_query, thread_id, limit], |row| {
+                let snippet: String = row.get(5)?;
+                Ok(MessageSearchHit {
+                    message_id: row.get(0)?,
+                    thread_id: row.get(1)?,
+                    from_public_key: row.get(2)?,
+                    is_outgoing: row.get::<_, i32>(3)? == 1,
+                    timestamp: row.get(4)?,
+                    snippet: split_snippet(&snippet),
+                })
+            })
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        Ok(hits)
+    }
+
+    /// Search within a single conversation
+    pub fn search_thread(
+        &self,
+        thread_id: &str,
+        query: &str,
+        order: SearchOrder,
+        limit: u32,
+    ) -> Result<Vec<MessageSearchHit>, DatabaseError> {
+        self.search_messages(query, Some(thread_id), order, limit)
+    }
+}
+
+/// Turn free text into an FTS5 query: every word must appear, the last
+/// one as a prefix so results update while typing. Words are quoted so
+/// FTS operators in user input are treated literally.
+fn to_fts_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|t| t.replace(['\u{0002}', '\u{0003}'], ""))
+        .filter(|t| !t.is_empty())
+        .map(|t| format!("\"{}\"", t.replace('"', "\"\"")))
+        .collect();
+
+    let last = terms.len().checked_sub(1)?;
+    let parts: Vec<String> = terms
+        .into_iter()
+        .enumerate()
+        .map(|(i, t)| if i == last { format!("{}*", t) } else { t })
+        .collect();
+
+    Some(parts.join(" "))
+}
+
+fn split_snippet(snippet: &str) -> Vec<SnippetSegment> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut highlighted = false;
+
+    for c in snippet.chars() {
+        let toggle = match c {
+            MATCH_START => !highlighted,
+            MATCH_END => highlighted,
+            _ => false,
+        };
+
+        if !toggle {
+            if c != MATCH_START && c != MATCH_END {
+                current.push(c);
+            }
+            continue;
+        }
+
+        if !current.is_empty() {
+            segments.push(SnippetSegment { text: std::mem::take(&mut current), highlighted });
+        }
+        highlighted = !highlighted;
+    }
+
+    if !current.is_empty() {
+        segments.push(SnippetSegment { text: current, highlighted });
+    }
+
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Database {
+        let db = Database { conn: Connection::open_in_memory().unwrap() };
+        db.initialize_tables().unwrap();
+        db
+    }
+
+    fn insert(db: &Database, id: &str, thread: &str, text: &str, timestamp: i64) {
+        db.conn
+            .execute(
+                "INSERT OR IGNORE INTO threads (id, participant_public_key, last_message_at) VALUES (?, 'pk', ?)",
+                params![thread, timestamp],
+            )
+            .unwrap();
+        db.conn
+            .execute(
+                r#"
+                INSERT OR REPLACE INTO messages
+                (id, thread_id, from_public_key, payload_type, payload_json, timestamp, is_outgoing)
+                VALUES (?, ?, 'pk', 'text/plain', ?, ?, 0)
+                "#,
+                params![id, thread, serde_json::json!({ "text": text }).to_string(), timestamp],
+            )
+            .unwrap();
+    }
+
+    fn ids(hits: &[MessageSearchHit]) -> Vec<&str> {
+        hits.iter().map(|h| h.message_id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_search_thread_filters_and_orders() {
+        let db = test_db();
+        insert(&db, "a1", "t1", "lunch on friday?", 100);
+        insert(&db, "a2", "t1", "friday works, friday lunch it is", 200);
+        insert(&db, "b1", "t2", "friday standup moved", 300);
+
+        let recent = db.search_thread("t1", "friday", SearchOrder::Recent, 10).unwrap();
+        assert_eq!(ids(&recent), vec!["a2", "a1"]);
+
+        let all = db.search_messages("friday", None, SearchOrder::Recent, 10).unwrap();
+        assert_eq!(ids(&all), vec!["b1", "a2", "a1"]);
+
+        // Prefix match on the last word, all words required
+        assert_eq!(ids(&db.search_thread("t1", "lunch fri", SearchOrder::Recent, 10).unwrap()).len(), 2);
+        assert!(db.search_thread("t1", "standup", SearchOrder::Recent, 10).unwrap().is_empty());
+
+        // Operators in input are literal, not syntax errors
+        assert!(db.search_thread("t1", "\"fri NOT (", SearchOrder::Relevance, 10).is_ok());
+    }
+
+    #[test]
+    fn test_snippet_highlights_stay_within_message() {
+        let db = test_db();
+        insert(&db, "m1", "t1", "see you at the cafe", 1);
+        insert(&db, "m2", "t1", "cafe closed today", 2);
+
+        let hits = db.search_thread("t1", "cafe", SearchOrder::Recent, 10).unwrap();
+        assert_eq!(hits.len(), 2);
+
+        for hit in &hits {
+            let highlighted: Vec<_> = hit.snippet.iter().filter(|s| s.highlighted).collect();
+            assert_eq!(highlighted.len(), 1);
+            assert_eq!(highlighted[0].text, "cafe");
+        }
+        assert_eq!(hits[0].snippet.last().unwrap().text, " closed today");
+    }
+
+    #[test]
+    fn test_replaced_message_is_reindexed() {
+        let db = test_db();
+        insert(&db, "m1", "t1", "original words", 1);
+        insert(&db, "m1", "t1", "edited words", 2);
+
+        assert!(db.search_thread("t1", "original", SearchOrder::Recent, 10).unwrap().is_empty());
+        assert_eq!(db.search_thread("t1", "edited", SearchOrder::Recent, 10).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,113 @@
+//! Snapshot Counts
+//!
+//! The shape of the message store without its contents, for debug
+//! snapshots (see `crate::debug_snapshot`): how many threads there are and
+//! how many messages each holds. A dev build can seed an empty database to
+//! the same shape with placeholder messages.
+
+use super::{Database, DatabaseError, LAST_MESSAGE_COLUMNS};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+/// Message counts of one thread, with nothing that identifies it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThreadCounts {
+    pub messages: u32,
+    /// Messages we sent
+    pub outgoing: u32,
+    pub unread: u32,
+}
+
+impl Database {
+    /// Schema version the database was last opened with
+    pub fn schema_version(&self) -> Result<u32, DatabaseError> {
+        self.conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+
+    /// Counts of every thread, most recently active first
+    pub fn thread_counts(&self) -> Result<Vec<ThreadCounts>, DatabaseError> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                r#"
+                SELECT COUNT(m.id), COALESCE(SUM(m.is_outgoing), 0), t.unread_count
+                FROM threads t LEFT JOIN messages m ON m.thread_id = t.id
+                GROUP BY t.id
+                ORDER BY t.last_message_at DESC
+                "#,
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(ThreadCounts {
+                    messages: row.get(0)?,
+                    outgoing: row.get(1)?,
+                    unread: row.get(2)?,
+                })
+            })
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+
+    /// Create a thread with `peer_public_key` filled to `counts` with
+    /// placeholder text messages, `at` (ms) being the newest. The unread
+    /// messages are the newest incoming ones.
+    pub fn seed_thread(
+        &mut self,
+        thread_id: &str,
+        peer_public_key: &str,
+        own_public_key: &str,
+        counts: ThreadCounts,
+        at: i64,
+    ) -> Result<(), DatabaseError> {
+        let outgoing = counts.outgoing.min(counts.messages);
+        let unread = counts.unread.min(counts.messages - outgoing);
+
+        let tx = self
+            .conn
+            .transaction()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        tx.execute(
+            "INSERT INTO threads (id, participant_public_key, last_message_at, unread_count) VALUES (?, ?, ?, ?)",
+            params![thread_id, peer_public_key, at, unread],
+        )
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        // Oldest first: our messages, then the incoming ones, unread last
+        for i in 0..counts.messages {
+            let is_outgoing = i < outgoing;
+            let is_read = is_outgoing || i < counts.messages - unread;
+            let from = if is_outgoing { own_public_key } else { peer_public_key };
+            let payload = serde_json::json!({ "text": format!("Placeholder message {}", i + 1) });
+            tx.execute(
+                r#"
+                INSERT INTO messages
+                (id, thread_id, from_public_key, payload_type, payload_json, timestamp, is_outgoing, status, is_read)
+                VALUES (?, ?, ?, 'text', ?, ?, ?, ?, ?)
+                "#,
+                params![
+                    format!("{}-{}", thread_id, i),
+                    thread_id,
+                    from,
+                    payload.to_string(),
+                    at - i64::from(counts.messages - 1 - i) * 1000,
+                    is_outgoing,
+                    if is_outgoing { "sent" } else { "received" },
+                    is_read,
+                ],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        }
+
+        tx.execute(
+            &format!("UPDATE threads SET {} WHERE id = ?", LAST_MESSAGE_COLUMNS),
+            params![thread_id],
+        )
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        tx.commit().map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+}
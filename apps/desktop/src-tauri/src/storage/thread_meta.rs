@@ -0,0 +1,114 @@
+//! Thread Metadata
+//!
+//! Name, avatar and member list of group threads, kept decrypted together
+//! with the thread key they travel under between members (see
+//! `crate::thread_meta`). The latest `updated_at` wins.
+
+use super::{Database, DatabaseError};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+pub(super) fn create_table(conn: &Connection) -> Result<(), DatabaseError> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS thread_meta (
+            thread_id TEXT PRIMARY KEY,
+            thread_key TEXT NOT NULL,
+            name TEXT,
+            avatar_ref TEXT,
+            members_json TEXT NOT NULL,
+            author_public_key TEXT NOT NULL,
+            updated_at INTEGER NOT NULL
+        );
+        "#,
+    )
+    .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+}
+
+/// A thread's metadata as of `updated_at` (ms), last changed by
+/// `author_public_key`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThreadMeta {
+    pub thread_id: String,
+    pub name: Option<String>,
+    pub avatar_ref: Option<String>,
+    /// Public keys of the members, lowercase
+    pub members: Vec<String>,
+    pub author_public_key: String,
+    pub updated_at: i64,
+}
+
+impl ThreadMeta {
+    pub fn is_member(&self, public_key: &str) -> bool {
+        self.members.iter().any(|m| m.eq_ignore_ascii_case(public_key))
+    }
+}
+
+impl Database {
+    /// Stored metadata of `thread_id`, if any
+    pub fn get_thread_meta(&self, thread_id: &str) -> Result<Option<ThreadMeta>, DatabaseError> {
+        self.conn
+            .query_row(
+                "SELECT thread_id, name, avatar_ref, members_json, author_public_key, updated_at
+                 FROM thread_meta WHERE thread_id = ?",
+                params![thread_id],
+                |row| {
+                    let members: String = row.get(3)?;
+                    Ok(ThreadMeta {
+                        thread_id: row.get(0)?,
+                        name: row.get(1)?,
+                        avatar_ref: row.get(2)?,
+                        members: serde_json::from_str(&members).unwrap_or_default(),
+                        author_public_key: row.get(4)?,
+                        updated_at: row.get(5)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+
+    /// The thread key (hex) `thread_id`'s metadata is encrypted under
+    pub fn get_thread_key(&self, thread_id: &str) -> Result<Option<String>, DatabaseError> {
+        self.conn
+            .query_row(
+                "SELECT thread_key FROM thread_meta WHERE thread_id = ?",
+                params![thread_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+
+    /// Store `meta` and its thread key. Returns false, changing nothing,
+    /// if the stored metadata is as new or newer.
+    pub fn save_thread_meta(&mut self, meta: &ThreadMeta, thread_key_hex: &str) -> Result<bool, DatabaseError> {
+        let members = serde_json::to_string(&meta.members).unwrap_or_default();
+        let changed = self
+            .conn
+            .execute(
+                "INSERT INTO thread_meta
+                    (thread_id, thread_key, name, avatar_ref, members_json, author_public_key, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                 ON CONFLICT(thread_id) DO UPDATE SET
+                    thread_key = excluded.thread_key,
+                    name = excluded.name,
+                    avatar_ref = excluded.avatar_ref,
+                    members_json = excluded.members_json,
+                    author_public_key = excluded.author_public_key,
+                    updated_at = excluded.updated_at
+                 WHERE excluded.updated_at > thread_meta.updated_at",
+                params![
+                    meta.thread_id,
+                    thread_key_hex,
+                    meta.name,
+                    meta.avatar_ref,
+                    members,
+                    meta.author_public_key,
+                    meta.updated_at
+                ],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(changed > 0)
+    }
+}
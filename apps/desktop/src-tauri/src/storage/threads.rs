@@ -0,0 +1,366 @@
+//! Direct Thread Ids
+//!
+//! One-to-one threads used to be keyed `direct_` + the first 32 chars of
+//! both keys sorted and joined, which is really just a prefix of whichever
+//! key sorts lower - so every peer whose key sorts above ours landed in the
+//! same thread. They are now keyed by `gns_crypto_core::compute_thread_id`.
+//!
+//! Incoming messages were also once filed under `direct_` + the sender's
+//! key, so a conversation could be split between that thread and the one
+//! our sent messages went to. `migrate_thread_ids` files every message of
+//! a direct thread under the canonical id of its peer, whichever of the
+//! old ids it was under, once per database after the schema upgrade that
+//! introduced it.
+
+use super::{refresh_last_message, Database, DatabaseError};
+use gns_crypto_core::{compute_thread_id, DIRECT_THREAD_PREFIX};
+use rusqlite::{params, OptionalExtension, Transaction};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// `sync_state` key set while `migrate_thread_ids` still has to run
+pub(super) const THREAD_IDS_PENDING_KEY: &str = "thread_ids_pending";
+
+/// What [`Database::migrate_thread_ids`] changed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ThreadIdMigration {
+    /// Messages now in a different thread
+    pub messages_moved: usize,
+    /// Threads emptied into another one and removed
+    pub threads_merged: usize,
+}
+
+/// A message of a direct thread, as the migration sees it
+struct DirectMessage {
+    id: String,
+    from_public_key: String,
+    is_outgoing: bool,
+    is_unread: bool,
+}
+
+impl Database {
+    /// Whether the one-time thread id migration hasn't run yet
+    pub fn thread_ids_pending(&self) -> bool {
+        self.conn
+            .query_row(
+                "SELECT 1 FROM sync_state WHERE key = ?",
+                params![THREAD_IDS_PENDING_KEY],
+                |_| Ok(()),
+            )
+            .optional()
+            .ok()
+            .flatten()
+            .is_some()
+    }
+
+    /// File every message of a direct thread under
+    /// `compute_thread_id(my_pk, peer)`, merging threads that were split
+    /// between id schemes. The peer of an incoming message is its sender,
+    /// of one we sent the thread's participant. Merged threads keep their
+    /// messages' timestamps, so they read in order, and get their summary
+    /// and unread count rebuilt. Safe to run again; clears the pending
+    /// flag.
+    pub fn migrate_thread_ids(&mut self, my_pk: &str) -> Result<ThreadIdMigration, DatabaseError> {
+        let tx = self
+            .conn
+            .transaction()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        let threads: Vec<(String, String, i64)> = {
+            let mut stmt = tx
+                .prepare("SELECT id, participant_public_key, unread_count FROM threads WHERE substr(id, 1, ?) = ?")
+                .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+            let rows = stmt
+                .query_map(
+                    params![DIRECT_THREAD_PREFIX.len() as i64, DIRECT_THREAD_PREFIX],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get::<_, Option<i64>>(2)?.unwrap_or(0))),
+                )
+                .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+            rows.collect::<Result<_, _>>()
+                .map_err(|e| DatabaseError::SqliteError(e.to_string()))?
+        };
+
+        let mut migration = ThreadIdMigration::default();
+        for (thread_id, participant, unread_count) in threads {
+            let home = compute_thread_id(my_pk, &participant);
+
+            // Messages that belong elsewhere, by the thread they belong to
+            let mut moves: BTreeMap<String, (String, Vec<DirectMessage>)> = BTreeMap::new();
+            for message in direct_messages(&tx, &thread_id)? {
+                let peer = if message.is_outgoing || message.from_public_key.eq_ignore_ascii_case(my_pk) {
+                    participant.clone()
+                } else {
+                    message.from_public_key.clone()
+                };
+                let target = compute_thread_id(my_pk, &peer);
+                if target != thread_id {
+                    moves.entry(target).or_insert_with(|| (peer, Vec::new())).1.push(message);
+                }
+            }
+            if moves.is_empty() && thread_id == home {
+                continue;
+            }
+
+            let remaining: i64 = tx
+                .query_row(
+                    "SELECT COUNT(*) FROM messages WHERE thread_id = ?",
+                    params![thread_id],
+                    |row| row.get(0),
+                )
+                .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+            let moved: usize = moves.values().map(|(_, messages)| messages.len()).sum();
+            // Everything goes, so the thread row goes too, into its home
+            let emptied = thread_id != home && remaining as usize == moved;
+            if emptied {
+                moves.entry(home.clone()).or_insert_with(|| (participant.clone(), Vec::new()));
+            }
+
+            for (target, (peer, messages)) in &moves {
+                tx.execute(
+                    r#"
+                    INSERT OR IGNORE INTO threads
+                    (id, participant_public_key, participant_handle, last_message_at, unread_count,
+                     is_pinned, is_muted, is_archived, subject, last_message_id, preview_text)
+                    SELECT ?, ?, participant_handle, last_message_at, 0,
+                           is_pinned, is_muted, is_archived, subject, NULL, NULL
+                    FROM threads WHERE id = ?
+                    "#,
+                    params![target, peer, thread_id],
+                )
+                .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+                for message in messages {
+                    tx.execute(
+                        "UPDATE messages SET thread_id = ? WHERE id = ?",
+                        params![target, message.id],
+                    )
+                    .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+                }
+
+                // A thread moving whole brings its count along; one split
+                // up hands each part its unread incoming messages
+                let unread = if emptied && moves.len() == 1 {
+                    unread_count
+                } else {
+                    messages.iter().filter(|m| m.is_unread).count() as i64
+                };
+                tx.execute(
+                    "UPDATE threads SET unread_count = unread_count + ? WHERE id = ?",
+                    params![unread, target],
+                )
+                .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+                if !emptied {
+                    tx.execute(
+                        "UPDATE threads SET unread_count = MAX(unread_count - ?, 0) WHERE id = ?",
+                        params![unread, thread_id],
+                    )
+                    .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+                }
+                refresh_last_message(&tx, target)?;
+            }
+
+            if emptied {
+                // Keep the read marker and scheduled sends with the conversation
+                tx.execute(
+                    "UPDATE OR IGNORE read_markers SET thread_id = ? WHERE thread_id = ?",
+                    params![home, thread_id],
+                )
+                .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+                tx.execute("DELETE FROM read_markers WHERE thread_id = ?", params![thread_id])
+                    .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+                tx.execute(
+                    "UPDATE scheduled_messages SET thread_id = ? WHERE thread_id = ?",
+                    params![home, thread_id],
+                )
+                .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+                tx.execute("DELETE FROM threads WHERE id = ?", params![thread_id])
+                    .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+                migration.threads_merged += 1;
+            } else {
+                refresh_last_message(&tx, &thread_id)?;
+            }
+            migration.messages_moved += moved;
+        }
+
+        tx.execute("DELETE FROM sync_state WHERE key = ?", params![THREAD_IDS_PENDING_KEY])
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        tx.commit()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(migration)
+    }
+}
+
+fn direct_messages(tx: &Transaction, thread_id: &str) -> Result<Vec<DirectMessage>, DatabaseError> {
+    let mut stmt = tx
+        .prepare("SELECT id, from_public_key, is_outgoing, COALESCE(is_read, 0) FROM messages WHERE thread_id = ?")
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+    let rows = stmt
+        .query_map(params![thread_id], |row| {
+            let is_outgoing = row.get::<_, i64>(2)? != 0;
+            Ok(DirectMessage {
+                id: row.get(0)?,
+                from_public_key: row.get(1)?,
+                is_outgoing,
+                is_unread: !is_outgoing && row.get::<_, i64>(3)? == 0,
+            })
+        })
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+    rows.collect::<Result<_, _>>()
+        .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The id the old rule gave the thread between `my_pk` and `peer_pk`
+    fn legacy_thread_id(my_pk: &str, peer_pk: &str) -> String {
+        let mut keys = [my_pk, peer_pk];
+        keys.sort();
+        let joined = keys.join("_");
+        format!("{}{}", DIRECT_THREAD_PREFIX, &joined[..joined.len().min(32)])
+    }
+
+    #[test]
+    fn test_legacy_direct_threads_are_rekeyed() {
+        let mut db = Database::open_in_memory().unwrap();
+        let me = "11".repeat(32);
+        let peer = "22".repeat(32);
+        let legacy = legacy_thread_id(&me, &peer);
+
+        // Written by an older version under the legacy id
+        db.conn
+            .execute(
+                "INSERT INTO threads (id, participant_public_key, last_message_at) VALUES (?1, ?2, 1)",
+                params![legacy, peer],
+            )
+            .unwrap();
+        db.conn
+            .execute(
+                r#"
+                INSERT INTO messages
+                (id, thread_id, from_public_key, payload_type, payload_json, timestamp, is_outgoing)
+                VALUES ('m1', ?1, ?2, 'text', '{"text":"hi"}', 1, 1)
+                "#,
+                params![legacy, me],
+            )
+            .unwrap();
+
+        // A reply that arrived after upgrading, already under the new id
+        db.save_synced_incoming_message("m2", &peer, "hey", 2, None, &me).unwrap();
+
+        let migration = db.migrate_thread_ids(&me).unwrap();
+        assert_eq!(migration, ThreadIdMigration { messages_moved: 1, threads_merged: 1 });
+        assert_eq!(db.migrate_thread_ids(&me).unwrap(), ThreadIdMigration::default());
+
+        let thread_id = compute_thread_id(&peer, &me);
+        assert!(db.get_thread(&legacy).unwrap().is_none());
+        assert!(db.get_thread(&thread_id).unwrap().is_some());
+        assert_eq!(db.get_messages(&thread_id, 10, None).unwrap().len(), 2);
+        let thread = db.get_thread(&thread_id).unwrap().unwrap();
+        assert_eq!(thread.last_message_id.as_deref(), Some("m2"));
+        assert_eq!(thread.last_message_preview.as_deref(), Some("hey"));
+    }
+
+    #[test]
+    fn test_split_inbound_and_outbound_threads_merge() {
+        let mut db = Database::open_in_memory().unwrap();
+        assert!(db.thread_ids_pending());
+        let me = "11".repeat(32);
+        let peer = "22".repeat(32);
+        let canonical = compute_thread_id(&me, &peer);
+        let inbound = format!("{}{}", DIRECT_THREAD_PREFIX, peer);
+
+        // Incoming messages filed under the sender's key by an older version
+        db.conn
+            .execute(
+                "INSERT INTO threads (id, participant_public_key, last_message_at, unread_count) VALUES (?1, ?2, 3000, 1)",
+                params![inbound, peer],
+            )
+            .unwrap();
+        for (id, at, is_read) in [("in-1", 1_000, 1), ("in-2", 3_000, 0)] {
+            db.conn
+                .execute(
+                    r#"
+                    INSERT INTO messages
+                    (id, thread_id, from_public_key, payload_type, payload_json, timestamp, is_outgoing, is_read)
+                    VALUES (?1, ?2, ?3, 'text', '{"text":"from peer"}', ?4, 0, ?5)
+                    "#,
+                    params![id, inbound, peer, at, is_read],
+                )
+                .unwrap();
+        }
+        db.conn
+            .execute(
+                "INSERT INTO read_markers (thread_id, read_up_to, updated_at) VALUES (?1, 1000, 1000)",
+                params![inbound],
+            )
+            .unwrap();
+        // Our side of the conversation, already under the canonical id
+        db.save_browser_sent_message("out-1", &peer, "reply", 2_000, &me).unwrap();
+        db.save_browser_sent_message("out-2", &peer, "latest", 4_000, &me).unwrap();
+
+        let migration = db.migrate_thread_ids(&me).unwrap();
+        assert_eq!(migration, ThreadIdMigration { messages_moved: 2, threads_merged: 1 });
+        assert!(!db.thread_ids_pending());
+        assert_eq!(db.migrate_thread_ids(&me).unwrap(), ThreadIdMigration::default());
+
+        assert!(db.get_thread(&inbound).unwrap().is_none());
+        let ids: Vec<String> = db.get_messages(&canonical, 10, None).unwrap().into_iter().map(|m| m.id).collect();
+        assert_eq!(ids, ["out-2", "in-2", "out-1", "in-1"]);
+
+        let thread = db.get_thread(&canonical).unwrap().unwrap();
+        assert_eq!(thread.unread_count, 1);
+        assert_eq!(thread.last_message_id.as_deref(), Some("out-2"));
+        assert_eq!(thread.last_message_at, 4_000);
+        assert_eq!(db.get_threads(false, 10, 0).unwrap().len(), 1);
+        let marker: String = db
+            .conn
+            .query_row("SELECT thread_id FROM read_markers", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(marker, canonical);
+    }
+
+    #[test]
+    fn test_thread_summary_follows_messages() {
+        let mut db = Database::open_in_memory().unwrap();
+        let me = "11".repeat(32);
+        let peer = "22".repeat(32);
+        let thread_id = compute_thread_id(&me, &peer);
+        let summary = |db: &Database| {
+            let thread = db.get_thread(&thread_id).unwrap().unwrap();
+            (thread.last_message_id, thread.last_message_at, thread.unread_count, thread.last_message_preview)
+        };
+
+        db.save_synced_incoming_message("in-1", &peer, "hi", 1_000, None, &me).unwrap();
+        db.save_synced_incoming_message("in-2", &peer, "are you there?", 3_000, None, &me).unwrap();
+        assert_eq!(summary(&db), (Some("in-2".into()), 3_000, 2, Some("are you there?".into())));
+
+        // Synced in late: counts as unread but doesn't become the latest
+        db.save_synced_incoming_message("in-0", &peer, "earlier", 500, None, &me).unwrap();
+        assert_eq!(summary(&db), (Some("in-2".into()), 3_000, 3, Some("are you there?".into())));
+
+        let long = "y".repeat(500);
+        db.save_browser_sent_message("out-1", &peer, &long, 4_000, &me).unwrap();
+        let (last_id, last_at, unread, preview) = summary(&db);
+        assert_eq!((last_id.as_deref(), last_at, unread), (Some("out-1"), 4_000, 3));
+        assert_eq!(preview.unwrap().len(), 120);
+
+        // Deleting the latest falls back to the one before it
+        db.delete_message("out-1").unwrap();
+        assert_eq!(summary(&db), (Some("in-2".into()), 3_000, 3, Some("are you there?".into())));
+        db.delete_message("in-2").unwrap();
+        assert_eq!(summary(&db), (Some("in-1".into()), 1_000, 2, Some("hi".into())));
+
+        db.mark_thread_read(&thread_id).unwrap();
+        assert_eq!(summary(&db).2, 0);
+
+        let other = "33".repeat(32);
+        db.save_synced_incoming_message("x-1", &other, "newer thread", 2_000, None, &me).unwrap();
+        let page = |offset| {
+            db.get_threads(false, 1, offset).unwrap().into_iter().map(|t| t.id).collect::<Vec<_>>()
+        };
+        assert_eq!(page(0), vec![compute_thread_id(&me, &other)]);
+        assert_eq!(page(1), vec![thread_id.clone()]);
+        assert!(page(2).is_empty());
+    }
+}
@@ -0,0 +1,294 @@
+//! Verifiable Thread Transcripts
+//!
+//! Exports one conversation in a form someone else can check, e.g. to
+//! settle a dispute. Each message carries the envelope its sender signed,
+//! and the exporting identity signs the transcript as a whole.
+//!
+//! Envelope signatures cover the ciphertext, not the decrypted content:
+//! they prove who sent each message and when. That the content shown is
+//! what was inside rests on the exporter's signature. Messages stored
+//! without their envelope (older ones, or ones synced from another device)
+//! are still exported, vouched for by the exporter alone.
+
+use super::{Database, DatabaseError};
+use gns_crypto_core::signing::{canonicalize_for_signing, verify_signature_hex};
+use gns_crypto_core::{GnsEnvelope, GnsIdentity};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+const TRANSCRIPT_VERSION: u32 = 1;
+
+/// One message as exported
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptMessage {
+    pub id: String,
+    pub from_public_key: String,
+    pub from_handle: Option<String>,
+    pub payload_type: String,
+    /// Decrypted payload
+    pub content: serde_json::Value,
+    pub timestamp: i64,
+    /// The envelope as the sender signed it, if we kept it
+    pub envelope: Option<GnsEnvelope>,
+}
+
+/// Why a transcript failed to verify
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TranscriptError {
+    #[error("Message {0} has an invalid sender signature")]
+    BadMessageSignature(String),
+
+    #[error("Message {0} does not match its signed envelope")]
+    EnvelopeMismatch(String),
+
+    #[error("Message {0} was neither sent to nor by the transcript owner")]
+    ForeignMessage(String),
+
+    #[error("Transcript signature is invalid")]
+    BadSignature,
+}
+
+/// An exported thread, signed by the identity that exported it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreadTranscript {
+    pub version: u32,
+    pub thread_id: String,
+    pub public_key: String,
+    pub exported_at: i64,
+    /// Oldest first
+    pub messages: Vec<TranscriptMessage>,
+    pub signature: String,
+}
+
+impl ThreadTranscript {
+    pub fn sign(
+        identity: &GnsIdentity,
+        thread_id: &str,
+        messages: Vec<TranscriptMessage>,
+        exported_at: i64,
+    ) -> Self {
+        let mut transcript = Self {
+            version: TRANSCRIPT_VERSION,
+            thread_id: thread_id.to_string(),
+            public_key: identity.public_key_hex(),
+            exported_at,
+            messages,
+            signature: String::new(),
+        };
+        transcript.signature = hex::encode(identity.sign_bytes(&transcript.signing_payload()));
+        transcript
+    }
+
+    /// Check every sender signature that is present, then the exporter's
+    /// signature over the whole transcript. Returns how many messages had
+    /// their sender signature checked.
+    pub fn verify(&self) -> Result<usize, TranscriptError> {
+        let mut sender_verified = 0;
+        for message in &self.messages {
+            let Some(envelope) = &message.envelope else {
+                continue;
+            };
+            if !envelope.verify_signature().unwrap_or(false) {
+                return Err(TranscriptError::BadMessageSignature(message.id.clone()));
+            }
+            if envelope.id != message.id
+                || !envelope.from_public_key.eq_ignore_ascii_case(&message.from_public_key)
+                || envelope.payload_type != message.payload_type
+                || envelope.timestamp != message.timestamp
+            {
+                return Err(TranscriptError::EnvelopeMismatch(message.id.clone()));
+            }
+            if !envelope.from_public_key.eq_ignore_ascii_case(&self.public_key)
+                && !envelope.is_for(&self.public_key)
+            {
+                return Err(TranscriptError::ForeignMessage(message.id.clone()));
+            }
+            sender_verified += 1;
+        }
+
+        match verify_signature_hex(&self.public_key, &self.signing_payload(), &self.signature) {
+            Ok(true) => Ok(sender_verified),
+            _ => Err(TranscriptError::BadSignature),
+        }
+    }
+
+    fn signing_payload(&self) -> Vec<u8> {
+        let body = serde_json::json!({
+            "version": self.version,
+            "thread_id": self.thread_id,
+            "public_key": self.public_key,
+            "exported_at": self.exported_at,
+            "messages": self.messages,
+        });
+        let mut payload = format!("gns-transcript:v{}:", self.version).into_bytes();
+        payload.extend(canonicalize_for_signing(&body));
+        payload
+    }
+}
+
+impl Database {
+    /// Keep the signed envelope of a received message for later export
+    pub fn save_message_envelope(
+        &mut self,
+        message_id: &str,
+        envelope: &GnsEnvelope,
+    ) -> Result<(), DatabaseError> {
+        let json = envelope
+            .to_json()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        self.conn
+            .execute(
+                "UPDATE messages SET envelope_json = ? WHERE id = ?",
+                params![json, message_id],
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Every message in a thread, oldest first, ready for export
+    pub fn get_transcript_messages(
+        &self,
+        thread_id: &str,
+    ) -> Result<Vec<TranscriptMessage>, DatabaseError> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT id, from_public_key, from_handle, payload_type, payload_json, timestamp, envelope_json
+                 FROM messages WHERE thread_id = ? ORDER BY timestamp ASC, id ASC",
+            )
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        let rows = stmt
+            .query_map(params![thread_id], |row| {
+                let id: String = row.get(0)?;
+                let payload_str: String = row.get(4)?;
+                let envelope_json: Option<String> = row.get(6)?;
+                let envelope = envelope_json.and_then(|json| match GnsEnvelope::from_json(&json) {
+                    Ok(envelope) => Some(envelope),
+                    Err(e) => {
+                        tracing::warn!("⚠️ Stored envelope for {} is unreadable: {}", id, e);
+                        None
+                    }
+                });
+
+                Ok(TranscriptMessage {
+                    from_public_key: row.get(1)?,
+                    from_handle: row.get(2)?,
+                    payload_type: row.get(3)?,
+                    content: serde_json::from_str(&payload_str).unwrap_or_default(),
+                    timestamp: row.get(5)?,
+                    envelope,
+                    id,
+                })
+            })
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))?;
+
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gns_crypto_core::create_envelope_with_metadata;
+
+    fn send(
+        db: &mut Database,
+        from: &GnsIdentity,
+        to: &GnsIdentity,
+        thread_id: &str,
+        text: &str,
+        outgoing: bool,
+    ) {
+        let payload = serde_json::json!({ "text": text });
+        let envelope = create_envelope_with_metadata(
+            from,
+            None,
+            &to.public_key_hex(),
+            &to.encryption_key_hex(),
+            "text",
+            payload.to_string().as_bytes(),
+            Some(thread_id),
+            None,
+        )
+        .unwrap();
+
+        if outgoing {
+            db.save_sent_message(&envelope, payload.to_string().as_bytes(), None, None)
+                .unwrap();
+        } else {
+            db.save_received_message(
+                &envelope.id,
+                thread_id,
+                &envelope.from_public_key,
+                None,
+                "text",
+                &payload,
+                envelope.timestamp,
+                true,
+                None,
+            )
+            .unwrap();
+            db.save_message_envelope(&envelope.id, &envelope).unwrap();
+        }
+        // Distinct millisecond timestamps
+        std::thread::sleep(std::time::Duration::from_millis(2));
+    }
+
+    fn transcript() -> ThreadTranscript {
+        let mut db = Database::open_in_memory().unwrap();
+        let me = GnsIdentity::generate();
+        let peer = GnsIdentity::generate();
+        let thread_id = "direct_test";
+
+        send(&mut db, &peer, &me, thread_id, "hi", false);
+        send(&mut db, &me, &peer, thread_id, "hello", true);
+        send(&mut db, &peer, &me, thread_id, "you owe me 5 GNS", false);
+
+        let messages = db.get_transcript_messages(thread_id).unwrap();
+        ThreadTranscript::sign(&me, thread_id, messages, 1_700_000_000)
+    }
+
+    #[test]
+    fn test_transcript_verifies() {
+        let transcript = transcript();
+        assert_eq!(transcript.messages.len(), 3);
+        assert_eq!(transcript.messages[2].content["text"], "you owe me 5 GNS");
+        assert_eq!(transcript.verify(), Ok(3));
+
+        // Survives the trip through JSON
+        let json = serde_json::to_string(&transcript).unwrap();
+        let parsed: ThreadTranscript = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.verify(), Ok(3));
+    }
+
+    #[test]
+    fn test_altered_message_fails_verification() {
+        let transcript = transcript();
+
+        for i in 0..transcript.messages.len() {
+            let mut edited = transcript.clone();
+            edited.messages[i].content = serde_json::json!({ "text": "edited" });
+            assert_eq!(edited.verify(), Err(TranscriptError::BadSignature));
+        }
+
+        let mut retimed = transcript.clone();
+        retimed.messages[0].timestamp += 1;
+        assert_eq!(
+            retimed.verify(),
+            Err(TranscriptError::EnvelopeMismatch(retimed.messages[0].id.clone()))
+        );
+
+        let mut forged = transcript.clone();
+        forged.messages[2].envelope.as_mut().unwrap().timestamp += 1;
+        assert_eq!(
+            forged.verify(),
+            Err(TranscriptError::BadMessageSignature(forged.messages[2].id.clone()))
+        );
+
+        let mut dropped = transcript;
+        dropped.messages.remove(1);
+        assert_eq!(dropped.verify(), Err(TranscriptError::BadSignature));
+    }
+}
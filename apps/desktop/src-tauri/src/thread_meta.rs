@@ -0,0 +1,257 @@
+//! Thread Metadata Sync
+//!
+//! A group thread's name, avatar and member list are encrypted under the
+//! thread's own key (see `gns_crypto_core::thread_meta`) and signed by the
+//! member who changed them. Every change goes out as a `thread_meta`
+//! envelope to each member, ourselves included so our other devices follow
+//! along; the thread key rides in that envelope, which only its member can
+//! open. Receiving devices store the metadata decrypted, and only accept
+//! changes authored by a member.
+
+use crate::message_handler::IncomingMessageEvent;
+use crate::storage::{Database, ThreadMeta};
+use crate::AppState;
+use gns_crypto_core::{
+    create_envelope_with_cache, open_thread_meta, seal_thread_meta, Compression, CryptoError, GnsEnvelope, GnsIdentity,
+    MessageKeyCache, SealedThreadMeta, ThreadKey,
+};
+use serde::{Deserialize, Serialize};
+
+/// Payload type of a thread metadata envelope
+pub const THREAD_META_PAYLOAD_TYPE: &str = "thread_meta";
+
+/// Tauri event carrying a [`ThreadMeta`] another member or device changed
+pub const THREAD_META_UPDATED_EVENT: &str = "thread_meta_updated";
+
+/// Longest thread name accepted
+pub const MAX_THREAD_NAME_LEN: usize = 100;
+
+/// What is encrypted under the thread key
+#[derive(Debug, Serialize, Deserialize)]
+struct MetaContents {
+    name: Option<String>,
+    avatar_ref: Option<String>,
+    members: Vec<String>,
+    updated_at: i64,
+}
+
+/// Payload of a thread metadata envelope
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreadMetaUpdate {
+    /// Thread key (hex), readable only by the member the envelope is for
+    pub thread_key: String,
+    pub sealed: SealedThreadMeta,
+}
+
+/// Encrypt `meta` under `key` and sign it as `author`
+pub fn seal_update(author: &GnsIdentity, key: &ThreadKey, meta: &ThreadMeta) -> Result<ThreadMetaUpdate, CryptoError> {
+    let contents = MetaContents {
+        name: meta.name.clone(),
+        avatar_ref: meta.avatar_ref.clone(),
+        members: meta.members.clone(),
+        updated_at: meta.updated_at,
+    };
+    let plaintext = serde_json::to_vec(&contents)?;
+    Ok(ThreadMetaUpdate {
+        thread_key: key.to_hex(),
+        sealed: seal_thread_meta(author, key, &meta.thread_id, &plaintext)?,
+    })
+}
+
+/// Seal `update` in an envelope only `member_pk` can open
+pub fn create_update_envelope(
+    author: &GnsIdentity,
+    message_keys: &MessageKeyCache,
+    update: &ThreadMetaUpdate,
+    member_pk: &str,
+    member_encryption_key: &str,
+    compression: Compression,
+) -> Result<GnsEnvelope, CryptoError> {
+    let payload = serde_json::to_vec(update)?;
+    create_envelope_with_cache(
+        author,
+        None,
+        member_pk,
+        member_encryption_key,
+        THREAD_META_PAYLOAD_TYPE,
+        &payload,
+        Some(&update.sealed.thread_id),
+        None,
+        compression,
+        message_keys,
+    )
+}
+
+/// Send `meta` to every member of the thread, sealed under `key`
+pub async fn send(state: &AppState, meta: &ThreadMeta, key: &ThreadKey) -> Result<(), String> {
+    let (update, my_pk, my_encryption_key) = {
+        let identity_mgr = state.identity.lock().await;
+        let identity = identity_mgr.get_identity().ok_or("No identity configured")?;
+        let update = seal_update(identity, key, meta).map_err(|e| format!("Failed to seal thread metadata: {}", e))?;
+        (update, identity.public_key_hex(), identity.encryption_key_hex())
+    };
+
+    let mut failed = 0;
+    for member in &meta.members {
+        let encryption_key = if member.eq_ignore_ascii_case(&my_pk) {
+            my_encryption_key.clone()
+        } else {
+            match state.api.get_identity(member).await {
+                Ok(Some(info)) => info.encryption_key,
+                Ok(None) | Err(_) => {
+                    tracing::warn!("⚠️ Could not look up thread member {}", member.get(..16).unwrap_or(member));
+                    failed += 1;
+                    continue;
+                }
+            }
+        };
+
+        let envelope = {
+            let identity_mgr = state.identity.lock().await;
+            let identity = identity_mgr.get_identity().ok_or("No identity configured")?;
+            let compression = identity_mgr.compression_for(member);
+            create_update_envelope(identity, &identity_mgr.message_keys(), &update, member, &encryption_key, compression)
+                .map_err(|e| format!("Failed to create envelope: {}", e))?
+        };
+        if let Err(e) = state.relay.lock().await.send_envelope(&envelope).await {
+            tracing::warn!("⚠️ Thread metadata not sent to {}: {}", member.get(..16).unwrap_or(member), e);
+            failed += 1;
+        }
+    }
+
+    match failed {
+        0 => Ok(()),
+        n => Err(format!("Thread metadata not delivered to {} of {} members", n, meta.members.len())),
+    }
+}
+
+/// Apply a decrypted thread metadata envelope, returning the metadata if
+/// it was newer than ours. The update has to be signed by the envelope's
+/// sender, and the sender has to be a member: of the thread as we know
+/// it, or for a thread new to us, of the thread as the update describes.
+pub fn apply_update(db: &mut Database, event: &IncomingMessageEvent) -> Result<Option<ThreadMeta>, String> {
+    let sender = &event.from_public_key;
+    if !event.signature_valid {
+        return Err(format!("Ignoring thread metadata from {}: bad envelope signature", short(sender)));
+    }
+    let update: ThreadMetaUpdate =
+        serde_json::from_value(event.payload.clone()).map_err(|e| format!("Malformed thread metadata: {}", e))?;
+    let sealed = &update.sealed;
+    if !sealed.author_public_key.eq_ignore_ascii_case(sender) {
+        return Err(format!("Ignoring thread metadata from {}: signed by someone else", short(sender)));
+    }
+
+    let key = ThreadKey::from_hex(&update.thread_key).map_err(|e| format!("Bad thread key: {}", e))?;
+    let plaintext = open_thread_meta(sealed, &key).map_err(|e| format!("Unreadable thread metadata: {}", e))?;
+    let contents: MetaContents =
+        serde_json::from_slice(&plaintext).map_err(|e| format!("Malformed thread metadata: {}", e))?;
+
+    let meta = ThreadMeta {
+        thread_id: sealed.thread_id.clone(),
+        name: contents.name,
+        avatar_ref: contents.avatar_ref,
+        members: contents.members.iter().map(|m| m.to_lowercase()).collect(),
+        author_public_key: sender.to_lowercase(),
+        updated_at: contents.updated_at,
+    };
+    let existing = db.get_thread_meta(&meta.thread_id).map_err(|e| e.to_string())?;
+    if !existing.as_ref().unwrap_or(&meta).is_member(sender) {
+        return Err(format!("Ignoring thread metadata from {}: not a member", short(sender)));
+    }
+
+    let saved = db.save_thread_meta(&meta, &key.to_hex()).map_err(|e| e.to_string())?;
+    Ok(saved.then_some(meta))
+}
+
+/// Check a thread name, returning it trimmed; an empty name clears it
+pub fn normalize_name(name: &str) -> Result<Option<String>, String> {
+    let name = name.trim();
+    if name.chars().count() > MAX_THREAD_NAME_LEN {
+        return Err(format!("Thread name is longer than {} characters", MAX_THREAD_NAME_LEN));
+    }
+    Ok((!name.is_empty()).then(|| name.to_string()))
+}
+
+fn short(public_key: &str) -> &str {
+    public_key.get(..16).unwrap_or(public_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_handler::decrypt_envelope;
+
+    fn meta(author: &GnsIdentity, members: &[&GnsIdentity], name: &str, updated_at: i64) -> ThreadMeta {
+        ThreadMeta {
+            thread_id: "group-1".to_string(),
+            name: Some(name.to_string()),
+            avatar_ref: None,
+            members: members.iter().map(|m| m.public_key_hex()).collect(),
+            author_public_key: author.public_key_hex(),
+            updated_at,
+        }
+    }
+
+    #[test]
+    fn test_only_members_can_read_thread_name() {
+        let alice = GnsIdentity::generate();
+        let bob = GnsIdentity::generate();
+        let carol = GnsIdentity::generate();
+        let cache = MessageKeyCache::default();
+
+        let key = ThreadKey::generate();
+        let update = seal_update(&alice, &key, &meta(&alice, &[&alice, &bob], "Hiking", 1_000)).unwrap();
+        let envelope =
+            create_update_envelope(&alice, &cache, &update, &bob.public_key_hex(), &bob.encryption_key_hex(), Compression::Off).unwrap();
+        // The relay sees neither the name nor the key
+        let on_the_wire = serde_json::to_string(&envelope).unwrap();
+        assert!(!on_the_wire.contains("Hiking") && !on_the_wire.contains(&update.thread_key));
+
+        // Bob, a member, can open it and read the name
+        let event = decrypt_envelope(&bob, &MessageKeyCache::default(), None, &envelope).unwrap();
+        assert_eq!(event.payload_type, THREAD_META_PAYLOAD_TYPE);
+        let mut db = Database::open_in_memory().unwrap();
+        let applied = apply_update(&mut db, &event).unwrap().unwrap();
+        assert_eq!(applied.name.as_deref(), Some("Hiking"));
+        assert_eq!(db.get_thread_meta("group-1").unwrap(), Some(applied));
+
+        // Carol, who isn't, can't open Bob's envelope, nor the metadata
+        // without the key
+        assert!(decrypt_envelope(&carol, &MessageKeyCache::default(), None, &envelope).is_none());
+        assert!(open_thread_meta(&update.sealed, &ThreadKey::generate()).is_err());
+
+        // Delivered again: nothing new
+        assert_eq!(apply_update(&mut db, &event).unwrap(), None);
+    }
+
+    #[test]
+    fn test_non_members_cannot_change_metadata() {
+        let alice = GnsIdentity::generate();
+        let bob = GnsIdentity::generate();
+        let mallory = GnsIdentity::generate();
+        let cache = MessageKeyCache::default();
+        let mut db = Database::open_in_memory().unwrap();
+
+        let key = ThreadKey::generate();
+        let update = seal_update(&alice, &key, &meta(&alice, &[&alice, &bob], "Hiking", 1_000)).unwrap();
+        let envelope =
+            create_update_envelope(&alice, &cache, &update, &bob.public_key_hex(), &bob.encryption_key_hex(), Compression::Off).unwrap();
+        apply_update(&mut db, &decrypt_envelope(&bob, &cache, None, &envelope).unwrap()).unwrap();
+
+        // Mallory learned the key somehow and renames the thread, even
+        // listing herself as a member
+        let renamed = meta(&mallory, &[&alice, &bob, &mallory], "Scam", 2_000);
+        let update = seal_update(&mallory, &key, &renamed).unwrap();
+        let envelope =
+            create_update_envelope(&mallory, &cache, &update, &bob.public_key_hex(), &bob.encryption_key_hex(), Compression::Off).unwrap();
+        assert!(apply_update(&mut db, &decrypt_envelope(&bob, &cache, None, &envelope).unwrap()).is_err());
+
+        // Alice's metadata passed off as Mallory's envelope
+        let update = seal_update(&alice, &key, &meta(&alice, &[&alice, &bob, &mallory], "Scam", 3_000)).unwrap();
+        let envelope =
+            create_update_envelope(&mallory, &cache, &update, &bob.public_key_hex(), &bob.encryption_key_hex(), Compression::Off).unwrap();
+        assert!(apply_update(&mut db, &decrypt_envelope(&bob, &cache, None, &envelope).unwrap()).is_err());
+
+        assert_eq!(db.get_thread_meta("group-1").unwrap().unwrap().name.as_deref(), Some("Hiking"));
+    }
+}
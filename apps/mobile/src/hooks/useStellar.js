@@ -22,7 +22,7 @@ export function useStellar() {
             setError(null);
         } catch (e) {
             console.error('Failed to load balances:', e);
-            setError(e.toString());
+            setError(e.message || String(e));
         } finally {
             setLoading(false);
         }
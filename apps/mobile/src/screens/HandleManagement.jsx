@@ -46,7 +46,7 @@ export function HandleManagement() {
                 setAvailability({ available: false, reason: result.error });
             }
         } catch (e) {
-            setAvailability({ available: false, reason: e.toString() });
+            setAvailability({ available: false, reason: e.message || String(e) });
         } finally {
             setChecking(false);
         }
@@ -0,0 +1,290 @@
+//! Signature Domains
+//!
+//! Signed payloads are prefixed with a tag naming what they are, so a
+//! signature made for one purpose never verifies as another, even if the
+//! bytes after the tag happen to collide. Tags are versioned; a new payload
+//! format gets a new tag rather than reusing an old one.
+//!
+//! | Domain    | Tag               | Signed payload                               |
+//! |-----------|-------------------|----------------------------------------------|
+//! | `Dix`     | `gns-dix-v1:`     | canonical post JSON, liked/reposted post id, canonical follow record |
+//! | `Reserve` | `gns-reserve-v1:` | `reserve:{handle}:{timestamp}`               |
+//! | `Claim`   | `gns-claim-v1:`   | canonical handle claim JSON                  |
+//! | `Release` | `gns-release-v1:` | `release:{handle}:{timestamp}`               |
+//! | `Record`  | `gns-record-v1:`  | canonical GNS record JSON                    |
+//! | `Hub`     | `gns-hub-v1:`     | Home Hub HTTP response body                  |
+//! | `Card`    | `gns-card-v1:`    | identity card JSON, signature field left out |
+//! | `Typed`   | `gns-typed-v1:`   | `{app domain}:` then canonical JSON of app-supplied data |
+//! | `ThreadMeta` | `gns-thread-meta-v1:` | `{thread id}:{nonce hex}:{ciphertext hex}` of sealed thread metadata |
+//! | `KeyRotation` | `gns-key-rotation-v1:` | `{old key}:{new key}:{rotated at ms}`, signed by the old key |
+//!
+//! Envelopes, breadcrumbs and migration tokens carry their own
+//! self-describing formats and are not signed through this module.
+
+use crate::errors::CryptoError;
+use crate::identity::GnsIdentity;
+use crate::signing::{canonicalize_for_signing, verify_signature_hex};
+
+/// Longest app domain accepted for typed data
+pub const MAX_APP_DOMAIN_LEN: usize = 128;
+
+/// What a signature is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SignatureDomain {
+    Dix,
+    Reserve,
+    Claim,
+    Release,
+    Record,
+    Hub,
+    Card,
+    Typed,
+    ThreadMeta,
+    KeyRotation,
+}
+
+/// Whether verification also accepts signatures over the bare, untagged
+/// message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DomainPolicy {
+    /// Only domain-tagged signatures verify
+    Strict,
+    /// Untagged signatures from older clients still verify
+    AllowUntagged,
+}
+
+/// Policy used while clients and servers move to tagged signatures.
+/// Flip to `Strict` once untagged signatures are no longer in circulation.
+pub const TRANSITION_POLICY: DomainPolicy = DomainPolicy::AllowUntagged;
+
+impl SignatureDomain {
+    pub const ALL: [SignatureDomain; 10] = [
+        Self::Dix,
+        Self::Reserve,
+        Self::Claim,
+        Self::Release,
+        Self::Record,
+        Self::Hub,
+        Self::Card,
+        Self::Typed,
+        Self::ThreadMeta,
+        Self::KeyRotation,
+    ];
+
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Self::Dix => "gns-dix-v1:",
+            Self::Reserve => "gns-reserve-v1:",
+            Self::Claim => "gns-claim-v1:",
+            Self::Release => "gns-release-v1:",
+            Self::Record => "gns-record-v1:",
+            Self::Hub => "gns-hub-v1:",
+            Self::Card => "gns-card-v1:",
+            Self::Typed => "gns-typed-v1:",
+            Self::ThreadMeta => "gns-thread-meta-v1:",
+            Self::KeyRotation => "gns-key-rotation-v1:",
+        }
+    }
+
+    /// The bytes actually signed for `message` in this domain
+    pub fn tag(self, message: &[u8]) -> Vec<u8> {
+        let prefix = self.prefix().as_bytes();
+        let mut tagged = Vec::with_capacity(prefix.len() + message.len());
+        tagged.extend_from_slice(prefix);
+        tagged.extend_from_slice(message);
+        tagged
+    }
+
+    /// The domain whose tag `message` already starts with, if any. A raw
+    /// signature over such a message would verify in that domain.
+    pub fn tagged_in(message: &[u8]) -> Option<SignatureDomain> {
+        Self::ALL
+            .into_iter()
+            .find(|domain| message.starts_with(domain.prefix().as_bytes()))
+    }
+}
+
+impl GnsIdentity {
+    /// Sign `message` within `domain`
+    pub fn sign_in_domain(&self, domain: SignatureDomain, message: &[u8]) -> [u8; 64] {
+        self.sign_bytes(&domain.tag(message))
+    }
+}
+
+/// The message an app's structured data is signed as in the `Typed`
+/// domain: the app's own domain, a colon, then the payload's canonical
+/// JSON. The app domain separates apps from each other the way the tag
+/// separates GNS's own uses, so it may not contain a colon.
+pub fn typed_data_message(
+    app_domain: &str,
+    payload: &serde_json::Value,
+) -> Result<Vec<u8>, CryptoError> {
+    let valid = !app_domain.is_empty()
+        && app_domain.len() <= MAX_APP_DOMAIN_LEN
+        && app_domain
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '/'));
+    if !valid {
+        return Err(CryptoError::InvalidDomain(app_domain.to_string()));
+    }
+
+    let mut message = format!("{}:", app_domain).into_bytes();
+    message.extend_from_slice(&canonicalize_for_signing(payload));
+    Ok(message)
+}
+
+/// Verify a hex signature over `message` made within `domain`
+pub fn verify_in_domain_hex(
+    public_key_hex: &str,
+    domain: SignatureDomain,
+    message: &[u8],
+    signature_hex: &str,
+    policy: DomainPolicy,
+) -> Result<bool, CryptoError> {
+    if verify_signature_hex(public_key_hex, &domain.tag(message), signature_hex)? {
+        return Ok(true);
+    }
+    match policy {
+        DomainPolicy::Strict => Ok(false),
+        DomainPolicy::AllowUntagged => verify_signature_hex(public_key_hex, message, signature_hex),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrong_domain_fails() {
+        let identity = GnsIdentity::generate();
+        let public_key = identity.public_key_hex();
+        let message = b"reserve:alice:2025-01-01T00:00:00Z";
+
+        let signature = hex::encode(identity.sign_in_domain(SignatureDomain::Reserve, message));
+        for policy in [DomainPolicy::Strict, DomainPolicy::AllowUntagged] {
+            for domain in SignatureDomain::ALL {
+                let valid =
+                    verify_in_domain_hex(&public_key, domain, message, &signature, policy).unwrap();
+                assert_eq!(
+                    valid,
+                    domain == SignatureDomain::Reserve,
+                    "{:?} under {:?}",
+                    domain,
+                    policy
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_untagged_signatures_only_pass_during_transition() {
+        let identity = GnsIdentity::generate();
+        let public_key = identity.public_key_hex();
+        let message = b"{\"id\":\"post-1\"}";
+        let untagged = hex::encode(identity.sign_bytes(message));
+
+        assert!(verify_in_domain_hex(
+            &public_key,
+            SignatureDomain::Dix,
+            message,
+            &untagged,
+            DomainPolicy::AllowUntagged
+        )
+        .unwrap());
+        assert!(!verify_in_domain_hex(
+            &public_key,
+            SignatureDomain::Dix,
+            message,
+            &untagged,
+            DomainPolicy::Strict
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn test_tagged_messages_are_recognized() {
+        for domain in SignatureDomain::ALL {
+            assert_eq!(
+                SignatureDomain::tagged_in(&domain.tag(b"payload")),
+                Some(domain)
+            );
+        }
+        assert_eq!(SignatureDomain::tagged_in(b"hello gns-dix-v1:"), None);
+        assert_eq!(SignatureDomain::tagged_in(b"gns-dix-v2:"), None);
+    }
+
+    #[test]
+    fn test_prefixes_are_distinct() {
+        let prefixes: std::collections::HashSet<_> =
+            SignatureDomain::ALL.iter().map(|d| d.prefix()).collect();
+        assert_eq!(prefixes.len(), SignatureDomain::ALL.len());
+    }
+
+    #[test]
+    fn test_typed_data_is_canonical() {
+        let a: serde_json::Value =
+            serde_json::from_str(r#"{"to":"alice","amount":{"value":5,"asset":"GNS"}}"#).unwrap();
+        let b: serde_json::Value = serde_json::from_str(
+            "{\n  \"amount\": { \"asset\": \"GNS\", \"value\": 5 },\n  \"to\": \"alice\"\n}",
+        )
+        .unwrap();
+
+        let message = typed_data_message("com.example.shop", &a).unwrap();
+        assert_eq!(message, typed_data_message("com.example.shop", &b).unwrap());
+        assert_eq!(
+            String::from_utf8(message).unwrap(),
+            r#"com.example.shop:{"amount":{"asset":"GNS","value":5},"to":"alice"}"#
+        );
+
+        // Ed25519 is deterministic, so the signature is too
+        let identity = GnsIdentity::generate();
+        let message = typed_data_message("com.example.shop", &a).unwrap();
+        assert_eq!(
+            identity.sign_in_domain(SignatureDomain::Typed, &message),
+            identity.sign_in_domain(
+                SignatureDomain::Typed,
+                &typed_data_message("com.example.shop", &b).unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_typed_data_domains_are_separate() {
+        let identity = GnsIdentity::generate();
+        let public_key = identity.public_key_hex();
+        let payload = serde_json::json!({ "action": "transfer", "amount": 5 });
+
+        let shop = typed_data_message("com.example.shop", &payload).unwrap();
+        let game = typed_data_message("com.example.game", &payload).unwrap();
+        let shop_signature = hex::encode(identity.sign_in_domain(SignatureDomain::Typed, &shop));
+        let game_signature = hex::encode(identity.sign_in_domain(SignatureDomain::Typed, &game));
+        assert_ne!(shop_signature, game_signature);
+
+        // Neither verifies for the other app, nor outside the Typed domain
+        let verify = |message: &[u8], domain| {
+            verify_in_domain_hex(
+                &public_key,
+                domain,
+                message,
+                &shop_signature,
+                DomainPolicy::Strict,
+            )
+            .unwrap()
+        };
+        assert!(verify(&shop, SignatureDomain::Typed));
+        assert!(!verify(&game, SignatureDomain::Typed));
+        assert!(!verify(&shop, SignatureDomain::Dix));
+
+        for bad in [
+            "",
+            "com.example:shop",
+            "shop app",
+            &"a".repeat(MAX_APP_DOMAIN_LEN + 1),
+        ] {
+            assert!(matches!(
+                typed_data_message(bad, &payload),
+                Err(CryptoError::InvalidDomain(_))
+            ));
+        }
+    }
+}
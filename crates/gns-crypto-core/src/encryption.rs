@@ -7,7 +7,7 @@
 //! 4. Encrypt with ChaCha20-Poly1305 AEAD
 
 use chacha20poly1305::{
-    aead::{Aead, KeyInit},
+    aead::{Aead, KeyInit, Payload},
     ChaCha20Poly1305, Nonce,
 };
 use hkdf::Hkdf;
@@ -16,7 +16,7 @@ use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 use x25519_dalek::{EphemeralSecret, PublicKey as X25519PublicKey, StaticSecret};
-use zeroize::Zeroize;
+use zeroize::Zeroizing;
 
 use crate::errors::CryptoError;
 
@@ -55,6 +55,47 @@ pub fn encrypt_for_recipient(
     plaintext: &[u8],
     recipient_x25519_public: &[u8; 32],
 ) -> Result<EncryptedPayload, CryptoError> {
+    encrypt_for_recipient_with_aad(plaintext, recipient_x25519_public, &[])
+}
+
+/// Encrypt data for a recipient, binding `aad` to the ciphertext.
+///
+/// `aad` is authenticated but not encrypted or sent: decryption only
+/// succeeds when given the same bytes. An empty `aad` is the same as
+/// [`encrypt_for_recipient`].
+pub fn encrypt_for_recipient_with_aad(
+    plaintext: &[u8],
+    recipient_x25519_public: &[u8; 32],
+    aad: &[u8],
+) -> Result<EncryptedPayload, CryptoError> {
+    let (ephemeral_public, symmetric_key) = derive_sending_key(recipient_x25519_public)?;
+    seal(plaintext, ephemeral_public, &symmetric_key, aad)
+}
+
+/// Decrypt data sent to us
+pub fn decrypt_from_sender(
+    our_x25519_secret: &[u8; 32],
+    encrypted: &EncryptedPayload,
+) -> Result<Vec<u8>, CryptoError> {
+    decrypt_from_sender_with_aad(our_x25519_secret, encrypted, &[])
+}
+
+/// Decrypt data sent to us that was encrypted with associated data `aad`
+pub fn decrypt_from_sender_with_aad(
+    our_x25519_secret: &[u8; 32],
+    encrypted: &EncryptedPayload,
+    aad: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    let ephemeral_public = validate_payload(encrypted)?;
+    let symmetric_key = derive_receiving_key(our_x25519_secret, &ephemeral_public)?;
+    open(encrypted, &symmetric_key, aad)
+}
+
+/// Generate an ephemeral keypair and derive the key it shares with the
+/// recipient. Returns the ephemeral public key and the symmetric key.
+pub(crate) fn derive_sending_key(
+    recipient_x25519_public: &[u8; 32],
+) -> Result<([u8; 32], Zeroizing<[u8; 32]>), CryptoError> {
     // Generate ephemeral keypair
     let ephemeral_secret = EphemeralSecret::random_from_rng(OsRng);
     let ephemeral_public = X25519PublicKey::from(&ephemeral_secret);
@@ -64,84 +105,105 @@ pub fn encrypt_for_recipient(
     let shared_secret = ephemeral_secret.diffie_hellman(&recipient_public);
 
     // Derive symmetric key using HKDF
-    let mut symmetric_key = derive_symmetric_key(
+    let symmetric_key = derive_symmetric_key(
         shared_secret.as_bytes(),
         ephemeral_public.as_bytes(),
         recipient_x25519_public,
     )?;
 
+    Ok((ephemeral_public.to_bytes(), Zeroizing::new(symmetric_key)))
+}
+
+/// Derive the key a sender's ephemeral public key shares with our secret
+pub(crate) fn derive_receiving_key(
+    our_x25519_secret: &[u8; 32],
+    ephemeral_public: &[u8; 32],
+) -> Result<Zeroizing<[u8; 32]>, CryptoError> {
+    // Perform ECDH with our static secret
+    let our_secret = StaticSecret::from(*our_x25519_secret);
+    let our_public = X25519PublicKey::from(&our_secret);
+    let shared_secret = our_secret.diffie_hellman(&X25519PublicKey::from(*ephemeral_public));
+
+    // Derive symmetric key using HKDF (same as encryption)
+    let symmetric_key = derive_symmetric_key(
+        shared_secret.as_bytes(),
+        ephemeral_public,
+        our_public.as_bytes(),
+    )?;
+
+    Ok(Zeroizing::new(symmetric_key))
+}
+
+/// Check the lengths of a received payload, returning its ephemeral key
+pub(crate) fn validate_payload(encrypted: &EncryptedPayload) -> Result<[u8; 32], CryptoError> {
+    let ephemeral_public: [u8; 32] = encrypted
+        .ephemeral_public_key
+        .as_slice()
+        .try_into()
+        .map_err(|_| CryptoError::InvalidKeyLength {
+            expected: 32,
+            got: encrypted.ephemeral_public_key.len(),
+        })?;
+    if encrypted.nonce.len() != 12 {
+        return Err(CryptoError::InvalidNonceLength);
+    }
+    Ok(ephemeral_public)
+}
+
+/// Encrypt under an already derived key with a fresh random nonce
+pub(crate) fn seal(
+    plaintext: &[u8],
+    ephemeral_public: [u8; 32],
+    symmetric_key: &[u8; 32],
+    aad: &[u8],
+) -> Result<EncryptedPayload, CryptoError> {
     // Generate random nonce
     let mut nonce_bytes = [0u8; 12];
     OsRng.fill_bytes(&mut nonce_bytes);
     let nonce = Nonce::from_slice(&nonce_bytes);
 
     // Encrypt with ChaCha20-Poly1305
-    let cipher = ChaCha20Poly1305::new_from_slice(&symmetric_key)
+    let cipher = ChaCha20Poly1305::new_from_slice(symmetric_key)
         .map_err(|e| CryptoError::EncryptionFailed(e.to_string()))?;
 
     let ciphertext = cipher
-        .encrypt(nonce, plaintext)
+        .encrypt(
+            nonce,
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
         .map_err(|e| CryptoError::EncryptionFailed(e.to_string()))?;
 
-    // Zeroize symmetric key
-    symmetric_key.zeroize();
-
     Ok(EncryptedPayload {
-        ephemeral_public_key: ephemeral_public.as_bytes().to_vec(),
+        ephemeral_public_key: ephemeral_public.to_vec(),
         nonce: nonce_bytes.to_vec(),
         ciphertext,
     })
 }
 
-/// Decrypt data sent to us
-pub fn decrypt_from_sender(
-    our_x25519_secret: &[u8; 32],
+/// Decrypt a validated payload under an already derived key
+pub(crate) fn open(
     encrypted: &EncryptedPayload,
+    symmetric_key: &[u8; 32],
+    aad: &[u8],
 ) -> Result<Vec<u8>, CryptoError> {
-    // Validate lengths
-    if encrypted.ephemeral_public_key.len() != 32 {
-        return Err(CryptoError::InvalidKeyLength {
-            expected: 32,
-            got: encrypted.ephemeral_public_key.len(),
-        });
-    }
-    if encrypted.nonce.len() != 12 {
-        return Err(CryptoError::InvalidNonceLength);
-    }
-
-    // Parse ephemeral public key
-    let ephemeral_public_bytes: [u8; 32] =
-        encrypted.ephemeral_public_key.clone().try_into().unwrap();
-    let ephemeral_public = X25519PublicKey::from(ephemeral_public_bytes);
-
-    // Perform ECDH with our static secret
-    let our_secret = StaticSecret::from(*our_x25519_secret);
-    let our_public = X25519PublicKey::from(&our_secret);
-    let shared_secret = our_secret.diffie_hellman(&ephemeral_public);
-
-    // Derive symmetric key using HKDF (same as encryption)
-    let mut symmetric_key = derive_symmetric_key(
-        shared_secret.as_bytes(),
-        &ephemeral_public_bytes,
-        our_public.as_bytes(),
-    )?;
-
-    // Parse nonce
-    let nonce_bytes: [u8; 12] = encrypted.nonce.clone().try_into().unwrap();
-    let nonce = Nonce::from_slice(&nonce_bytes);
+    let nonce = Nonce::from_slice(&encrypted.nonce);
 
     // Decrypt with ChaCha20-Poly1305
-    let cipher = ChaCha20Poly1305::new_from_slice(&symmetric_key)
+    let cipher = ChaCha20Poly1305::new_from_slice(symmetric_key)
         .map_err(|e| CryptoError::DecryptionFailed(e.to_string()))?;
 
-    let plaintext = cipher
-        .decrypt(nonce, encrypted.ciphertext.as_ref())
-        .map_err(|_| CryptoError::DecryptionFailed("Authentication failed".to_string()))?;
-
-    // Zeroize symmetric key
-    symmetric_key.zeroize();
-
-    Ok(plaintext)
+    cipher
+        .decrypt(
+            nonce,
+            Payload {
+                msg: encrypted.ciphertext.as_ref(),
+                aad,
+            },
+        )
+        .map_err(|_| CryptoError::DecryptionFailed("Authentication failed".to_string()))
 }
 
 /// Derive symmetric key from shared secret using HKDF-SHA256
@@ -242,6 +304,25 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_associated_data_must_match() {
+        let recipient = GnsIdentity::generate();
+        let key = recipient.encryption_public_key_bytes();
+
+        let encrypted = encrypt_for_recipient_with_aad(b"Secret message", &key, b"context")
+            .expect("Encryption should succeed");
+
+        let decrypted =
+            decrypt_from_sender_with_aad(recipient.x25519_secret(), &encrypted, b"context")
+                .expect("Decryption should succeed");
+        assert_eq!(decrypted, b"Secret message");
+
+        assert!(
+            decrypt_from_sender_with_aad(recipient.x25519_secret(), &encrypted, b"other").is_err()
+        );
+        assert!(decrypt_from_sender(recipient.x25519_secret(), &encrypted).is_err());
+    }
+
     #[test]
     fn test_encrypted_payload_serialization() {
         let recipient = GnsIdentity::generate();
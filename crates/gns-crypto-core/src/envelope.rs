@@ -11,12 +11,15 @@
 //! ```text
 //! ┌─────────────────────────────────────────┐
 //! │ Header (signed)                         │
+//! │ ├── crypto_version: cipher suite        │
 //! │ ├── id: UUID                            │
 //! │ ├── from_public_key: Ed25519 pubkey     │
 //! │ ├── from_handle: Optional @handle       │
 //! │ ├── to_public_keys: [Ed25519 pubkeys]   │
 //! │ ├── payload_type: MIME type             │
 //! │ ├── timestamp: Unix ms                  │
+//! │ ├── compressed: payload was deflated    │
+//! │ ├── accepts_compression: see below      │
 //! │ └── thread_id: Optional conversation ID │
 //! ├─────────────────────────────────────────┤
 //! │ Encrypted Payload                       │
@@ -27,21 +30,152 @@
 //! │ Signature: Ed25519 over header          │
 //! └─────────────────────────────────────────┘
 //! ```
+//!
+//! ## Crypto Versions
+//! `crypto_version` names the payload cipher suite so the scheme can change
+//! without guessing at old messages. Version 1 is X25519 + HKDF-SHA256 +
+//! ChaCha20-Poly1305; envelopes that predate the field are version 1.
+//! Unknown versions are rejected with [`CryptoError::UnsupportedCryptoVersion`].
+//!
+//! Version 2 is the same cipher keyed by a forward secrecy session instead
+//! of an exchange with the recipient's static key; see [`crate::ratchet`].
+//! Only [`open_envelope_with_ratchet`] opens it, with the session the two
+//! sides negotiated through the envelopes' signed `ratchet` field.
+//!
+//! ## Compression
+//! Readers that predate the `compressed` flag can't open compressed
+//! envelopes, so compression is off unless the sender asks for it, and
+//! senders should only ask for peers known to handle it. A peer says so
+//! with the signed `accepts_compression` flag, which
+//! [`Compression::Accept`] and [`Compression::Deflate`] set.
+//!
+//! With [`Compression::Deflate`], payloads of at least `threshold` bytes
+//! are deflated before encryption, when that makes them smaller, and the
+//! envelope's `compressed` flag is set. The flag is covered by the header
+//! signature and is also the AEAD associated data, so flipping it makes
+//! decryption fail instead of handing back undecoded bytes.
+
+use std::io::{Read, Write};
 
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
-use crate::encryption::{
-    decrypt_from_sender, encrypt_for_recipient, EncryptedPayload, PayloadWrapper,
-};
+use crate::encryption::{encrypt_for_recipient_with_aad, open, seal, EncryptedPayload, PayloadWrapper};
 use crate::errors::CryptoError;
 use crate::identity::GnsIdentity;
+use crate::key_cache::MessageKeyCache;
+use crate::ratchet::{RatchetHeader, RatchetSessions, Sending};
 use crate::signing::{canonicalize_for_signing, verify_signature_hex};
 
+/// X25519 key agreement + HKDF-SHA256 + ChaCha20-Poly1305
+pub const CRYPTO_VERSION_1: u32 = 1;
+
+/// Version 1's cipher under a per-message key from a ratchet session
+pub const CRYPTO_VERSION_2: u32 = 2;
+
+/// Suite used for newly created envelopes
+pub const CURRENT_CRYPTO_VERSION: u32 = CRYPTO_VERSION_1;
+
+fn default_crypto_version() -> u32 {
+    CRYPTO_VERSION_1
+}
+
+fn is_crypto_version_1(version: &u32) -> bool {
+    *version == CRYPTO_VERSION_1
+}
+
+/// Threshold senders use for [`Compression::Deflate`] unless they have a
+/// reason to pick another
+pub const DEFAULT_COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Largest payload a compressed envelope may inflate to
+pub const MAX_DECOMPRESSED_PAYLOAD: usize = 16 * 1024 * 1024;
+
+/// Associated data of compressed payloads; uncompressed ones have none
+const COMPRESSED_AAD: &[u8] = b"gns-envelope-compressed:deflate";
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// Whether to deflate payloads before encrypting them, and whether to
+/// tell the recipient that compressed envelopes can be sent back
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    Off,
+    /// Send payloads as is, but accept compressed envelopes back
+    Accept,
+    /// Deflate payloads of at least `threshold` bytes, and accept
+    /// compressed envelopes back
+    Deflate { threshold: usize },
+}
+
+impl Compression {
+    /// Whether envelopes sealed with this setting say the sender opens
+    /// compressed envelopes
+    pub fn accepts(self) -> bool {
+        self != Compression::Off
+    }
+
+    /// The deflated payload, if this setting applies to it and it shrinks
+    fn apply(self, payload: &[u8]) -> Result<Option<Vec<u8>>, CryptoError> {
+        let Compression::Deflate { threshold } = self else {
+            return Ok(None);
+        };
+        if payload.len() < threshold {
+            return Ok(None);
+        }
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder
+            .write_all(payload)
+            .map_err(|e| CryptoError::EncryptionFailed(e.to_string()))?;
+        let deflated = encoder
+            .finish()
+            .map_err(|e| CryptoError::EncryptionFailed(e.to_string()))?;
+
+        Ok((deflated.len() < payload.len()).then_some(deflated))
+    }
+}
+
+/// Inflate a compressed payload, refusing to go past
+/// [`MAX_DECOMPRESSED_PAYLOAD`]
+fn inflate(deflated: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    let mut payload = Vec::new();
+    DeflateDecoder::new(deflated)
+        .take(MAX_DECOMPRESSED_PAYLOAD as u64 + 1)
+        .read_to_end(&mut payload)
+        .map_err(|e| CryptoError::DecompressionFailed(e.to_string()))?;
+
+    if payload.len() > MAX_DECOMPRESSED_PAYLOAD {
+        return Err(CryptoError::DecompressionFailed(format!(
+            "payload inflates past {} bytes",
+            MAX_DECOMPRESSED_PAYLOAD
+        )));
+    }
+    Ok(payload)
+}
+
+fn payload_aad(compressed: bool) -> &'static [u8] {
+    if compressed {
+        COMPRESSED_AAD
+    } else {
+        &[]
+    }
+}
+
 /// GNS Envelope - the message container
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GnsEnvelope {
+    /// Payload cipher suite (see module docs); absent means version 1
+    #[serde(default = "default_crypto_version")]
+    pub crypto_version: u32,
+
     /// Unique envelope ID
     pub id: String,
 
@@ -69,6 +203,19 @@ pub struct GnsEnvelope {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reply_to_id: Option<String>,
 
+    /// The payload was deflated before encryption
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub compressed: bool,
+
+    /// The sender opens compressed envelopes, so replies may be compressed
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub accepts_compression: bool,
+
+    /// Forward secrecy offer, or the session a version 2 payload is
+    /// encrypted in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ratchet: Option<RatchetHeader>,
+
     /// Encrypted payload (Object or String)
     pub encrypted_payload: PayloadWrapper,
     /// Ephemeral X25519 public key (optional, for flat string payload)
@@ -113,6 +260,21 @@ pub struct OpenedEnvelope {
     pub reply_to_id: Option<String>,
 }
 
+/// Prefix of the ids of one-to-one threads
+pub const DIRECT_THREAD_PREFIX: &str = "direct_";
+
+/// Canonical id of the one-to-one thread between two identities.
+///
+/// The keys are lowercased and sorted before hashing, so both sides
+/// derive the same id whichever of them is "me":
+/// `direct_` + the first 32 hex chars of SHA-256(`"{low}:{high}"`).
+pub fn compute_thread_id(my_public_key: &str, peer_public_key: &str) -> String {
+    let mut keys = [my_public_key.to_lowercase(), peer_public_key.to_lowercase()];
+    keys.sort();
+    let digest = Sha256::digest(keys.join(":").as_bytes());
+    format!("{}{}", DIRECT_THREAD_PREFIX, &hex::encode(digest)[..32])
+}
+
 /// Create a signed and encrypted envelope
 pub fn create_envelope(
     sender: &GnsIdentity,
@@ -120,6 +282,50 @@ pub fn create_envelope(
     recipient_encryption_key_hex: &str,
     payload_type: &str,
     payload: &[u8],
+) -> Result<GnsEnvelope, CryptoError> {
+    create_envelope_with_compression(
+        sender,
+        recipient_public_key_hex,
+        recipient_encryption_key_hex,
+        payload_type,
+        payload,
+        Compression::Off,
+    )
+}
+
+/// Create a signed and encrypted envelope with the given compression
+pub fn create_envelope_with_compression(
+    sender: &GnsIdentity,
+    recipient_public_key_hex: &str,
+    recipient_encryption_key_hex: &str,
+    payload_type: &str,
+    payload: &[u8],
+    compression: Compression,
+) -> Result<GnsEnvelope, CryptoError> {
+    seal_envelope(
+        sender,
+        recipient_public_key_hex,
+        recipient_encryption_key_hex,
+        payload_type,
+        payload,
+        compression,
+        None,
+        None,
+    )
+}
+
+/// Build and sign an envelope, taking the message key from the ratchet
+/// session with the recipient if there is one, else from `cache` if given
+#[allow(clippy::too_many_arguments)]
+fn seal_envelope(
+    sender: &GnsIdentity,
+    recipient_public_key_hex: &str,
+    recipient_encryption_key_hex: &str,
+    payload_type: &str,
+    payload: &[u8],
+    compression: Compression,
+    cache: Option<&MessageKeyCache>,
+    ratchet: Option<&RatchetSessions>,
 ) -> Result<GnsEnvelope, CryptoError> {
     // Parse recipient encryption key
     let recipient_enc_key_bytes = hex::decode(recipient_encryption_key_hex)?;
@@ -131,8 +337,29 @@ pub fn create_envelope(
     }
     let recipient_enc_key: [u8; 32] = recipient_enc_key_bytes.try_into().unwrap();
 
-    // Encrypt payload
-    let encrypted_payload = encrypt_for_recipient(payload, &recipient_enc_key)?;
+    // Compress, then encrypt with the flag as associated data
+    let deflated = compression.apply(payload)?;
+    let compressed = deflated.is_some();
+    let plaintext = deflated.as_deref().unwrap_or(payload);
+    let static_key = || match cache {
+        Some(cache) => cache.encrypt(
+            plaintext,
+            recipient_public_key_hex,
+            &recipient_enc_key,
+            payload_aad(compressed),
+        ),
+        None => encrypt_for_recipient_with_aad(plaintext, &recipient_enc_key, payload_aad(compressed)),
+    };
+    let (crypto_version, encrypted_payload, ratchet) =
+        match ratchet.map(|sessions| sessions.next_sending(recipient_public_key_hex)) {
+            Some(Sending::Session { header, public, key }) => (
+                CRYPTO_VERSION_2,
+                seal(plaintext, public, &key, payload_aad(compressed))?,
+                Some(header),
+            ),
+            Some(Sending::Offer(offer)) => (CURRENT_CRYPTO_VERSION, static_key()?, Some(offer)),
+            None => (CURRENT_CRYPTO_VERSION, static_key()?, None),
+        };
 
     // Generate envelope ID
     let envelope_id = Uuid::new_v4().to_string();
@@ -140,11 +367,15 @@ pub fn create_envelope(
 
     // Create header for signing (without signature)
     let header = EnvelopeHeader {
+        crypto_version,
         id: envelope_id.clone(),
         from_public_key: sender.public_key_hex(),
         to_public_keys: vec![recipient_public_key_hex.to_string()],
         payload_type: payload_type.to_string(),
         timestamp,
+        compressed,
+        accepts_compression: compression.accepts(),
+        ratchet: ratchet.clone(),
         encrypted_payload_hash: blake3::hash(&serde_json::to_vec(&encrypted_payload)?)
             .to_hex()
             .to_string(),
@@ -156,6 +387,7 @@ pub fn create_envelope(
     let signature_hex = hex::encode(signature);
 
     Ok(GnsEnvelope {
+        crypto_version,
         id: envelope_id,
         from_public_key: sender.public_key_hex(),
         from_handle: None, // Caller can set this
@@ -164,6 +396,9 @@ pub fn create_envelope(
         timestamp,
         thread_id: None,
         reply_to_id: None,
+        compressed,
+        accepts_compression: compression.accepts(),
+        ratchet,
         encrypted_payload: PayloadWrapper::Object(encrypted_payload),
         ephemeral_public_key: None,
         nonce: None,
@@ -183,32 +418,87 @@ pub fn create_envelope_with_metadata(
     thread_id: Option<&str>,
     reply_to_id: Option<&str>,
 ) -> Result<GnsEnvelope, CryptoError> {
-    let mut envelope = create_envelope(
+    let envelope = create_envelope(
         sender,
         recipient_public_key_hex,
         recipient_encryption_key_hex,
         payload_type,
         payload,
     )?;
+    with_metadata(envelope, sender, sender_handle, thread_id, reply_to_id)
+}
 
+/// [`create_envelope_with_metadata`] with the given compression, reusing a
+/// message key from `cache` for repeated sends to the same peer
+#[allow(clippy::too_many_arguments)]
+pub fn create_envelope_with_cache(
+    sender: &GnsIdentity,
+    sender_handle: Option<&str>,
+    recipient_public_key_hex: &str,
+    recipient_encryption_key_hex: &str,
+    payload_type: &str,
+    payload: &[u8],
+    thread_id: Option<&str>,
+    reply_to_id: Option<&str>,
+    compression: Compression,
+    cache: &MessageKeyCache,
+) -> Result<GnsEnvelope, CryptoError> {
+    let envelope = seal_envelope(
+        sender,
+        recipient_public_key_hex,
+        recipient_encryption_key_hex,
+        payload_type,
+        payload,
+        compression,
+        Some(cache),
+        None,
+    )?;
+    with_metadata(envelope, sender, sender_handle, thread_id, reply_to_id)
+}
+
+/// [`create_envelope_with_cache`] with forward secrecy: encrypted in the
+/// ratchet session with the recipient once it has answered, and until then
+/// with static-key encryption carrying an offer
+#[allow(clippy::too_many_arguments)]
+pub fn create_envelope_with_ratchet(
+    sender: &GnsIdentity,
+    sender_handle: Option<&str>,
+    recipient_public_key_hex: &str,
+    recipient_encryption_key_hex: &str,
+    payload_type: &str,
+    payload: &[u8],
+    thread_id: Option<&str>,
+    reply_to_id: Option<&str>,
+    compression: Compression,
+    cache: &MessageKeyCache,
+    ratchet: &RatchetSessions,
+) -> Result<GnsEnvelope, CryptoError> {
+    let envelope = seal_envelope(
+        sender,
+        recipient_public_key_hex,
+        recipient_encryption_key_hex,
+        payload_type,
+        payload,
+        compression,
+        Some(cache),
+        Some(ratchet),
+    )?;
+    with_metadata(envelope, sender, sender_handle, thread_id, reply_to_id)
+}
+
+fn with_metadata(
+    mut envelope: GnsEnvelope,
+    sender: &GnsIdentity,
+    sender_handle: Option<&str>,
+    thread_id: Option<&str>,
+    reply_to_id: Option<&str>,
+) -> Result<GnsEnvelope, CryptoError> {
     envelope.from_handle = sender_handle.map(String::from);
     envelope.thread_id = thread_id.map(String::from);
     envelope.reply_to_id = reply_to_id.map(String::from);
 
     // Re-sign with the new metadata
-    let header = EnvelopeHeader {
-        id: envelope.id.clone(),
-        from_public_key: envelope.from_public_key.clone(),
-        to_public_keys: envelope.to_public_keys.clone(),
-        payload_type: envelope.payload_type.clone(),
-        timestamp: envelope.timestamp,
-        encrypted_payload_hash: blake3::hash(&serde_json::to_vec(&envelope.encrypted_payload)?)
-            .to_hex()
-            .to_string(),
-    };
-
-    let header_bytes = canonicalize_for_signing(&serde_json::to_value(&header)?);
-    let signature = sender.sign_bytes(&header_bytes);
+    let signature = sender.sign_bytes(&envelope.header_bytes()?);
     envelope.signature = hex::encode(signature);
 
     Ok(envelope)
@@ -219,26 +509,76 @@ pub fn open_envelope(
     recipient: &GnsIdentity,
     envelope: &GnsEnvelope,
 ) -> Result<OpenedEnvelope, CryptoError> {
-    // Verify signature
-    let header = EnvelopeHeader {
-        id: envelope.id.clone(),
+    open_with(recipient, envelope, None, None)
+}
+
+/// [`open_envelope`], reusing message keys from `cache`
+pub fn open_envelope_with_cache(
+    recipient: &GnsIdentity,
+    envelope: &GnsEnvelope,
+    cache: &MessageKeyCache,
+) -> Result<OpenedEnvelope, CryptoError> {
+    open_with(recipient, envelope, Some(cache), None)
+}
+
+/// [`open_envelope_with_cache`] that also opens version 2 envelopes and
+/// answers the sender's forward secrecy offer, if the envelope carries a
+/// validly signed one
+pub fn open_envelope_with_ratchet(
+    recipient: &GnsIdentity,
+    envelope: &GnsEnvelope,
+    cache: &MessageKeyCache,
+    ratchet: &RatchetSessions,
+) -> Result<OpenedEnvelope, CryptoError> {
+    open_with(recipient, envelope, Some(cache), Some(ratchet))
+}
+
+fn open_with(
+    recipient: &GnsIdentity,
+    envelope: &GnsEnvelope,
+    cache: Option<&MessageKeyCache>,
+    ratchet: Option<&RatchetSessions>,
+) -> Result<OpenedEnvelope, CryptoError> {
+    let signature_valid = envelope.verify_signature()?;
+
+    let payload = match envelope.crypto_version {
+        CRYPTO_VERSION_1 => {
+            let payload = decrypt_v1(recipient, envelope, cache)?;
+            if let (Some(sessions), Some(offer), true) = (ratchet, &envelope.ratchet, signature_valid) {
+                if offer.is_offer() {
+                    sessions.accept_offer(&envelope.from_public_key, offer);
+                }
+            }
+            payload
+        }
+        CRYPTO_VERSION_2 => decrypt_v2(envelope, ratchet)?,
+        version => return Err(CryptoError::UnsupportedCryptoVersion(version)),
+    };
+    let payload = if envelope.compressed {
+        inflate(&payload)?
+    } else {
+        payload
+    };
+
+    Ok(OpenedEnvelope {
         from_public_key: envelope.from_public_key.clone(),
-        to_public_keys: envelope.to_public_keys.clone(),
+        from_handle: envelope.from_handle.clone(),
         payload_type: envelope.payload_type.clone(),
+        payload,
+        signature_valid,
+        envelope_id: envelope.id.clone(),
         timestamp: envelope.timestamp,
-        encrypted_payload_hash: blake3::hash(&serde_json::to_vec(&envelope.encrypted_payload)?)
-            .to_hex()
-            .to_string(),
-    };
-
-    let header_bytes = canonicalize_for_signing(&serde_json::to_value(&header)?);
-    let signature_valid = verify_signature_hex(
-        &envelope.from_public_key,
-        &header_bytes,
-        &envelope.signature,
-    )?;
+        thread_id: envelope.thread_id.clone(),
+        reply_to_id: envelope.reply_to_id.clone(),
+    })
+}
 
-    // Decrypt payload
+/// Decrypt a version 1 (X25519 + ChaCha20-Poly1305) payload
+fn decrypt_v1(
+    recipient: &GnsIdentity,
+    envelope: &GnsEnvelope,
+    cache: Option<&MessageKeyCache>,
+) -> Result<Vec<u8>, CryptoError> {
     let encrypted_payload = match &envelope.encrypted_payload {
         PayloadWrapper::Object(obj) => obj.clone(),
         PayloadWrapper::String(ciphertext_hex) => {
@@ -267,18 +607,31 @@ pub fn open_envelope(
         }
     };
 
-    let payload = decrypt_from_sender(recipient.x25519_secret(), &encrypted_payload)?;
+    let aad = payload_aad(envelope.compressed);
+    match cache {
+        Some(cache) => recipient.decrypt_with_cache(&encrypted_payload, aad, cache),
+        None => recipient.decrypt_with_aad(&encrypted_payload, aad),
+    }
+}
 
-    Ok(OpenedEnvelope {
-        from_public_key: envelope.from_public_key.clone(),
-        from_handle: envelope.from_handle.clone(),
-        payload_type: envelope.payload_type.clone(),
-        payload,
-        signature_valid,
-        envelope_id: envelope.id.clone(),
-        timestamp: envelope.timestamp,
-        thread_id: envelope.thread_id.clone(),
-        reply_to_id: envelope.reply_to_id.clone(),
+/// Decrypt a version 2 payload with the message key of its ratchet session
+fn decrypt_v2(envelope: &GnsEnvelope, ratchet: Option<&RatchetSessions>) -> Result<Vec<u8>, CryptoError> {
+    let (Some(sessions), Some(header)) = (ratchet, &envelope.ratchet) else {
+        return Err(CryptoError::DecryptionFailed(
+            "no ratchet session for this envelope".to_string(),
+        ));
+    };
+    let PayloadWrapper::Object(encrypted_payload) = &envelope.encrypted_payload else {
+        return Err(CryptoError::InvalidEnvelope(
+            "ratchet payloads are always objects".to_string(),
+        ));
+    };
+    if encrypted_payload.nonce.len() != 12 {
+        return Err(CryptoError::InvalidNonceLength);
+    }
+
+    sessions.open(&envelope.from_public_key, header, |key| {
+        open(encrypted_payload, key, payload_aad(envelope.compressed))
     })
 }
 
@@ -286,11 +639,21 @@ pub fn open_envelope(
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct EnvelopeHeader {
+    /// Omitted for version 1 so existing signatures stay valid
+    #[serde(skip_serializing_if = "is_crypto_version_1")]
+    crypto_version: u32,
     id: String,
     from_public_key: String,
     to_public_keys: Vec<String>,
     payload_type: String,
     timestamp: i64,
+    /// Omitted when false, like `crypto_version` 1
+    #[serde(skip_serializing_if = "is_false")]
+    compressed: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    accepts_compression: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ratchet: Option<RatchetHeader>,
     encrypted_payload_hash: String,
 }
 
@@ -302,6 +665,31 @@ impl GnsEnvelope {
             .any(|k| k.eq_ignore_ascii_case(public_key_hex))
     }
 
+    /// Check the sender's signature over the header. Needs no keys, so
+    /// anyone holding the envelope can check who sent it.
+    pub fn verify_signature(&self) -> Result<bool, CryptoError> {
+        verify_signature_hex(&self.from_public_key, &self.header_bytes()?, &self.signature)
+    }
+
+    /// The canonical header bytes the sender signs
+    fn header_bytes(&self) -> Result<Vec<u8>, CryptoError> {
+        let header = EnvelopeHeader {
+            crypto_version: self.crypto_version,
+            id: self.id.clone(),
+            from_public_key: self.from_public_key.clone(),
+            to_public_keys: self.to_public_keys.clone(),
+            payload_type: self.payload_type.clone(),
+            timestamp: self.timestamp,
+            compressed: self.compressed,
+            accepts_compression: self.accepts_compression,
+            ratchet: self.ratchet.clone(),
+            encrypted_payload_hash: blake3::hash(&serde_json::to_vec(&self.encrypted_payload)?)
+                .to_hex()
+                .to_string(),
+        };
+        Ok(canonicalize_for_signing(&serde_json::to_value(&header)?))
+    }
+
     /// Get the envelope as JSON string
     pub fn to_json(&self) -> Result<String, CryptoError> {
         serde_json::to_string(self).map_err(|e| CryptoError::SerializationError(e.to_string()))
@@ -316,6 +704,7 @@ impl GnsEnvelope {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ratchet::RATCHET_WINDOW;
 
     #[test]
     fn test_create_and_open_envelope() {
@@ -429,4 +818,329 @@ mod tests {
         let result = open_envelope(&wrong_recipient, &envelope);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_crypto_version_1_roundtrip() {
+        let sender = GnsIdentity::generate();
+        let recipient = GnsIdentity::generate();
+
+        let envelope = create_envelope(
+            &sender,
+            &recipient.public_key_hex(),
+            &recipient.encryption_key_hex(),
+            "text/plain",
+            b"versioned",
+        )
+        .expect("Envelope creation should succeed");
+        assert_eq!(envelope.crypto_version, CRYPTO_VERSION_1);
+
+        let json = envelope.to_json().expect("Serialization should succeed");
+        assert!(json.contains("\"cryptoVersion\":1"));
+
+        let opened = open_envelope(&recipient, &GnsEnvelope::from_json(&json).unwrap())
+            .expect("Version 1 should open");
+        assert!(opened.signature_valid);
+        assert_eq!(opened.payload, b"versioned");
+
+        // Envelopes from before the field existed are version 1
+        let mut legacy: serde_json::Value = serde_json::from_str(&json).unwrap();
+        legacy.as_object_mut().unwrap().remove("cryptoVersion");
+        let legacy = GnsEnvelope::from_json(&legacy.to_string()).unwrap();
+        assert_eq!(legacy.crypto_version, CRYPTO_VERSION_1);
+        assert!(open_envelope(&recipient, &legacy).unwrap().signature_valid);
+    }
+
+    #[test]
+    fn test_thread_id_is_order_independent() {
+        let alice = GnsIdentity::generate().public_key_hex();
+        let bob = GnsIdentity::generate().public_key_hex();
+        let carol = GnsIdentity::generate().public_key_hex();
+
+        let alice_to_bob = compute_thread_id(&alice, &bob);
+        assert_eq!(alice_to_bob, compute_thread_id(&bob, &alice));
+        assert_eq!(alice_to_bob, compute_thread_id(&alice.to_uppercase(), &bob));
+        assert!(alice_to_bob.starts_with(DIRECT_THREAD_PREFIX));
+        assert_eq!(alice_to_bob.len(), DIRECT_THREAD_PREFIX.len() + 32);
+
+        // Both of Alice's conversations get their own thread, whichever
+        // key sorts first
+        assert_ne!(alice_to_bob, compute_thread_id(&alice, &carol));
+        let low = "00".repeat(32);
+        assert_ne!(compute_thread_id(&low, &bob), compute_thread_id(&low, &carol));
+    }
+
+    #[test]
+    fn test_large_payload_is_compressed() {
+        let sender = GnsIdentity::generate();
+        let recipient = GnsIdentity::generate();
+        let payload = serde_json::to_vec(&serde_json::json!({
+            "post": "All work and no play makes Jack a dull boy. ".repeat(200),
+        }))
+        .unwrap();
+        let deflate = Compression::Deflate {
+            threshold: DEFAULT_COMPRESSION_THRESHOLD,
+        };
+
+        let envelope = create_envelope_with_compression(
+            &sender,
+            &recipient.public_key_hex(),
+            &recipient.encryption_key_hex(),
+            "application/json",
+            &payload,
+            deflate,
+        )
+        .expect("Envelope creation should succeed");
+        assert!(envelope.compressed);
+        assert!(envelope.accepts_compression);
+
+        let json = envelope.to_json().expect("Serialization should succeed");
+        assert!(json.len() < payload.len() / 4, "{} bytes on the wire", json.len());
+
+        let opened = open_envelope(&recipient, &GnsEnvelope::from_json(&json).unwrap())
+            .expect("Opening should succeed");
+        assert!(opened.signature_valid);
+        assert_eq!(opened.payload, payload);
+
+        // Off by default, the same payload goes as is
+        let plain = create_envelope(
+            &sender,
+            &recipient.public_key_hex(),
+            &recipient.encryption_key_hex(),
+            "application/json",
+            &payload,
+        )
+        .unwrap();
+        assert!(!plain.compressed);
+        assert!(!plain.accepts_compression);
+        assert_eq!(open_envelope(&recipient, &plain).unwrap().payload, payload);
+    }
+
+    #[test]
+    fn test_accepting_compression_is_signed() {
+        let sender = GnsIdentity::generate();
+        let recipient = GnsIdentity::generate();
+
+        let mut envelope = create_envelope_with_compression(
+            &sender,
+            &recipient.public_key_hex(),
+            &recipient.encryption_key_hex(),
+            "text/plain",
+            &[b'a'; 4096],
+            Compression::Accept,
+        )
+        .unwrap();
+        assert!(envelope.accepts_compression);
+        assert!(!envelope.compressed);
+        assert!(envelope.verify_signature().unwrap());
+
+        // A relay can't make a peer look like it accepts compression, or
+        // hide that it does
+        envelope.accepts_compression = false;
+        assert!(!envelope.verify_signature().unwrap());
+        let plain = create_envelope(
+            &sender,
+            &recipient.public_key_hex(),
+            &recipient.encryption_key_hex(),
+            "text/plain",
+            b"hi",
+        )
+        .unwrap();
+        assert!(!plain.to_json().unwrap().contains("acceptsCompression"));
+    }
+
+    #[test]
+    fn test_small_payload_is_not_compressed() {
+        let sender = GnsIdentity::generate();
+        let recipient = GnsIdentity::generate();
+
+        let envelope = create_envelope(
+            &sender,
+            &recipient.public_key_hex(),
+            &recipient.encryption_key_hex(),
+            "text/plain",
+            &[b'a'; 64],
+        )
+        .expect("Envelope creation should succeed");
+
+        assert!(!envelope.compressed);
+        assert!(!envelope.to_json().unwrap().contains("compressed"));
+        assert_eq!(open_envelope(&recipient, &envelope).unwrap().payload, [b'a'; 64]);
+    }
+
+    #[test]
+    fn test_flipped_compression_flag_fails() {
+        let sender = GnsIdentity::generate();
+        let recipient = GnsIdentity::generate();
+
+        for payload in [vec![b'x'; 4096], b"short".to_vec()] {
+            let mut envelope = create_envelope_with_compression(
+                &sender,
+                &recipient.public_key_hex(),
+                &recipient.encryption_key_hex(),
+                "text/plain",
+                &payload,
+                Compression::Deflate {
+                    threshold: DEFAULT_COMPRESSION_THRESHOLD,
+                },
+            )
+            .unwrap();
+            envelope.compressed = !envelope.compressed;
+
+            assert!(!envelope.verify_signature().unwrap());
+            match open_envelope(&recipient, &envelope) {
+                Err(CryptoError::DecryptionFailed(_)) => {}
+                other => panic!("expected DecryptionFailed, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_unknown_crypto_version_is_rejected() {
+        let sender = GnsIdentity::generate();
+        let recipient = GnsIdentity::generate();
+
+        let mut envelope = create_envelope(
+            &sender,
+            &recipient.public_key_hex(),
+            &recipient.encryption_key_hex(),
+            "text/plain",
+            b"from the future",
+        )
+        .expect("Envelope creation should succeed");
+        envelope.crypto_version = 99;
+
+        match open_envelope(&recipient, &envelope) {
+            Err(CryptoError::UnsupportedCryptoVersion(99)) => {}
+            other => panic!("expected UnsupportedCryptoVersion(99), got {:?}", other),
+        }
+    }
+
+    fn ratchet_send(sender: &GnsIdentity, sessions: &RatchetSessions, recipient: &GnsIdentity, text: &str) -> GnsEnvelope {
+        create_envelope_with_ratchet(
+            sender,
+            None,
+            &recipient.public_key_hex(),
+            &recipient.encryption_key_hex(),
+            "text/plain",
+            text.as_bytes(),
+            None,
+            None,
+            Compression::Off,
+            &MessageKeyCache::default(),
+            sessions,
+        )
+        .expect("Envelope creation should succeed")
+    }
+
+    fn ratchet_open(
+        recipient: &GnsIdentity,
+        sessions: &RatchetSessions,
+        envelope: &GnsEnvelope,
+    ) -> Result<Vec<u8>, CryptoError> {
+        open_envelope_with_ratchet(recipient, envelope, &MessageKeyCache::default(), sessions)
+            .map(|opened| opened.payload)
+    }
+
+    /// Alice offers, Bob answers, and Alice takes up the session
+    fn negotiate(alice: &GnsIdentity, bob: &GnsIdentity) -> (RatchetSessions, RatchetSessions) {
+        let (alice_sessions, bob_sessions) = (RatchetSessions::new(), RatchetSessions::new());
+
+        let hello = ratchet_send(alice, &alice_sessions, bob, "hello");
+        assert_eq!(hello.crypto_version, CRYPTO_VERSION_1);
+        assert!(hello.ratchet.as_ref().unwrap().is_offer());
+        assert_eq!(ratchet_open(bob, &bob_sessions, &hello).unwrap(), b"hello");
+
+        let reply = ratchet_send(bob, &bob_sessions, alice, "hi");
+        assert_eq!(reply.crypto_version, CRYPTO_VERSION_2);
+        assert_eq!(ratchet_open(alice, &alice_sessions, &reply).unwrap(), b"hi");
+        assert!(alice_sessions.has_session(&bob.public_key_hex()));
+
+        (alice_sessions, bob_sessions)
+    }
+
+    #[test]
+    fn test_ratchet_falls_back_until_answered() {
+        let alice = GnsIdentity::generate();
+        let bob = GnsIdentity::generate();
+        let alice_sessions = RatchetSessions::new();
+
+        // Bob doesn't ratchet: Alice keeps offering over static-key envelopes
+        for text in ["one", "two"] {
+            let envelope = ratchet_send(&alice, &alice_sessions, &bob, text);
+            assert_eq!(envelope.crypto_version, CRYPTO_VERSION_1);
+            assert!(envelope.ratchet.as_ref().unwrap().is_offer());
+            let opened = open_envelope(&bob, &envelope).unwrap();
+            assert!(opened.signature_valid);
+            assert_eq!(opened.payload, text.as_bytes());
+        }
+
+        // A tampered offer breaks the signature and isn't answered
+        let bob_sessions = RatchetSessions::new();
+        let mut envelope = ratchet_send(&alice, &alice_sessions, &bob, "three");
+        envelope.ratchet.as_mut().unwrap().offer = hex::encode([9u8; 32]);
+        assert_eq!(ratchet_open(&bob, &bob_sessions, &envelope).unwrap(), b"three");
+        assert!(!bob_sessions.has_session(&alice.public_key_hex()));
+
+        let (alice_sessions, bob_sessions) = negotiate(&alice, &bob);
+        let envelope = ratchet_send(&alice, &alice_sessions, &bob, "secret");
+        assert_eq!(envelope.crypto_version, CRYPTO_VERSION_2);
+        assert!(envelope.verify_signature().unwrap());
+        assert!(open_envelope(&bob, &envelope).is_err());
+        let json = envelope.to_json().unwrap();
+        assert_eq!(
+            ratchet_open(&bob, &bob_sessions, &GnsEnvelope::from_json(&json).unwrap()).unwrap(),
+            b"secret"
+        );
+    }
+
+    #[test]
+    fn test_out_of_order_ratchet_messages_decrypt() {
+        let alice = GnsIdentity::generate();
+        let bob = GnsIdentity::generate();
+        let (alice_sessions, bob_sessions) = negotiate(&alice, &bob);
+
+        let sent: Vec<_> = (0..5)
+            .map(|i| ratchet_send(&alice, &alice_sessions, &bob, &format!("message {}", i)))
+            .collect();
+        for i in [3, 0, 4, 1, 2] {
+            let payload = ratchet_open(&bob, &bob_sessions, &sent[i]).unwrap();
+            assert_eq!(payload, format!("message {}", i).as_bytes());
+        }
+
+        // Each key opens its message once
+        assert!(ratchet_open(&bob, &bob_sessions, &sent[3]).is_err());
+
+        // Nothing past the window is derived
+        let far: Vec<_> = (0..=RATCHET_WINDOW)
+            .map(|_| ratchet_send(&alice, &alice_sessions, &bob, "later"))
+            .collect();
+        assert!(ratchet_open(&bob, &bob_sessions, far.last().unwrap()).is_err());
+        assert_eq!(ratchet_open(&bob, &bob_sessions, &far[RATCHET_WINDOW as usize - 1]).unwrap(), b"later");
+        assert_eq!(ratchet_open(&bob, &bob_sessions, &far[0]).unwrap(), b"later");
+    }
+
+    #[test]
+    fn test_captured_ratchet_state_cannot_open_earlier_messages() {
+        let alice = GnsIdentity::generate();
+        let bob = GnsIdentity::generate();
+        let (alice_sessions, bob_sessions) = negotiate(&alice, &bob);
+
+        let earlier: Vec<_> = (0..3)
+            .map(|_| ratchet_send(&alice, &alice_sessions, &bob, "before"))
+            .collect();
+        for envelope in &earlier {
+            assert_eq!(ratchet_open(&bob, &bob_sessions, envelope).unwrap(), b"before");
+        }
+        let later = ratchet_send(&alice, &alice_sessions, &bob, "after");
+
+        // Everything Bob holds now: his identity and his exported sessions
+        let captured = RatchetSessions::import(&bob_sessions.export().unwrap()).unwrap();
+        for envelope in &earlier {
+            assert!(ratchet_open(&bob, &captured, envelope).is_err());
+            assert!(ratchet_open(&bob, &RatchetSessions::new(), envelope).is_err());
+        }
+        // Only what comes after is exposed
+        assert_eq!(ratchet_open(&bob, &captured, &later).unwrap(), b"after");
+    }
+
 }
@@ -34,11 +34,20 @@ pub enum CryptoError {
     #[error("Invalid envelope: {0}")]
     InvalidEnvelope(String),
 
+    #[error("Decompression failed: {0}")]
+    DecompressionFailed(String),
+
+    #[error("Unsupported envelope crypto version {0}")]
+    UnsupportedCryptoVersion(u32),
+
     #[error("Hex decode error: {0}")]
     HexDecodeError(String),
 
     #[error("Base64 decode error: {0}")]
     Base64DecodeError(String),
+
+    #[error("Invalid signing domain: {0}")]
+    InvalidDomain(String),
 }
 
 impl From<hex::FromHexError> for CryptoError {
@@ -6,15 +6,34 @@
 //!
 //! The X25519 key is derived from the Ed25519 key using standard
 //! Ed25519-to-X25519 conversion, ensuring a single seed controls both.
+//!
+//! ## Encryption key rotation
+//!
+//! The derived X25519 key can be replaced with an explicitly stored,
+//! randomly generated one via [`GnsIdentity::rotate_encryption_key`], so a
+//! leaked encryption key can be retired without giving up the identity.
+//! Retired keys are kept and still tried when decrypting, so messages
+//! already sent to them stay readable.
+//!
+//! This is not forward secrecy. Envelopes use a fresh ephemeral key per
+//! message (per peer for a short while, with a `MessageKeyCache`) on the
+//! sender's side only; anyone holding a recipient key can
+//! read every message ever encrypted to it, and retired keys are kept
+//! precisely so that remains possible for us. Rotation limits what a
+//! leaked key exposes to messages sent before the rotation reached
+//! senders (they pick up the new key from the signed record); it does not
+//! protect those earlier messages. Dropping retired keys trades old
+//! messages for that protection.
 
 use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use rand::rngs::OsRng;
 use sha2::{Digest, Sha512};
 use x25519_dalek::{PublicKey as X25519PublicKey, StaticSecret as X25519Secret};
-use zeroize::ZeroizeOnDrop;
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
 use crate::encryption::EncryptedPayload;
 use crate::errors::CryptoError;
+use crate::key_cache::MessageKeyCache;
 
 /// GNS Identity - the core cryptographic identity
 ///
@@ -32,6 +51,9 @@ pub struct GnsIdentity {
     /// Cached X25519 public key
     #[zeroize(skip)]
     x25519_public: X25519PublicKey,
+
+    /// Earlier X25519 secrets, most recent first, kept for decryption
+    retired_x25519_secrets: Vec<[u8; 32]>,
 }
 
 impl GnsIdentity {
@@ -72,9 +94,29 @@ impl GnsIdentity {
             signing_key,
             x25519_secret,
             x25519_public,
+            retired_x25519_secrets: Vec::new(),
         }
     }
 
+    /// Use an explicitly stored X25519 secret instead of the derived one,
+    /// along with any retired secrets (most recent first)
+    pub fn with_encryption_secret(mut self, secret: [u8; 32], retired: Vec<[u8; 32]>) -> Self {
+        self.x25519_secret.zeroize();
+        self.x25519_secret = X25519Secret::from(secret).to_bytes();
+        self.x25519_public = X25519PublicKey::from(&X25519Secret::from(self.x25519_secret));
+        self.retired_x25519_secrets = retired;
+        self
+    }
+
+    /// Switch to a fresh random X25519 key. The current one is retired:
+    /// still used for decryption, no longer published.
+    pub fn rotate_encryption_key(&mut self) {
+        let fresh = X25519Secret::random_from_rng(OsRng);
+        self.retired_x25519_secrets.insert(0, self.x25519_secret);
+        self.x25519_secret = fresh.to_bytes();
+        self.x25519_public = X25519PublicKey::from(&fresh);
+    }
+
     // ==================== PUBLIC KEY ACCESSORS ====================
 
     /// Get Ed25519 public key as bytes
@@ -97,6 +139,21 @@ impl GnsIdentity {
         hex::encode(self.encryption_public_key_bytes())
     }
 
+    /// Get the X25519 secret in use (USE WITH CAUTION!)
+    pub fn encryption_secret_bytes(&self) -> [u8; 32] {
+        self.x25519_secret
+    }
+
+    /// Retired X25519 secrets, most recent first (USE WITH CAUTION!)
+    pub fn retired_encryption_secrets(&self) -> &[[u8; 32]] {
+        &self.retired_x25519_secrets
+    }
+
+    /// Whether the X25519 key is the one derived from the Ed25519 seed
+    pub fn has_derived_encryption_key(&self) -> bool {
+        self.x25519_secret == ed25519_to_x25519_secret(self.signing_key.as_bytes())
+    }
+
     /// Get Ed25519 private key as hex (USE WITH CAUTION!)
     pub fn private_key_hex(&self) -> String {
         hex::encode(self.signing_key.as_bytes())
@@ -139,12 +196,51 @@ impl GnsIdentity {
         crate::encryption::encrypt_for_recipient(plaintext, recipient_x25519_public)
     }
 
-    /// Decrypt a message sent to us
+    /// Decrypt a message sent to us, trying retired keys if the current
+    /// one doesn't fit
     pub fn decrypt(&self, encrypted: &EncryptedPayload) -> Result<Vec<u8>, CryptoError> {
-        crate::encryption::decrypt_from_sender(&self.x25519_secret, encrypted)
+        self.decrypt_with_aad(encrypted, &[])
+    }
+
+    /// Like [`Self::decrypt`], for a message encrypted with associated
+    /// data `aad`
+    pub fn decrypt_with_aad(
+        &self,
+        encrypted: &EncryptedPayload,
+        aad: &[u8],
+    ) -> Result<Vec<u8>, CryptoError> {
+        let current =
+            crate::encryption::decrypt_from_sender_with_aad(&self.x25519_secret, encrypted, aad);
+        if current.is_ok() {
+            return current;
+        }
+        self.retired_x25519_secrets
+            .iter()
+            .find_map(|secret| {
+                crate::encryption::decrypt_from_sender_with_aad(secret, encrypted, aad).ok()
+            })
+            .ok_or_else(|| current.unwrap_err())
+    }
+
+    /// Like [`Self::decrypt_with_aad`], reusing keys from `cache`
+    pub fn decrypt_with_cache(
+        &self,
+        encrypted: &EncryptedPayload,
+        aad: &[u8],
+        cache: &MessageKeyCache,
+    ) -> Result<Vec<u8>, CryptoError> {
+        let current = cache.decrypt(&self.x25519_secret, encrypted, aad);
+        if current.is_ok() {
+            return current;
+        }
+        self.retired_x25519_secrets
+            .iter()
+            .find_map(|secret| cache.decrypt(secret, encrypted, aad).ok())
+            .ok_or_else(|| current.unwrap_err())
     }
 
-    /// Get X25519 secret for internal use (encryption operations)
+    /// Get X25519 secret for the encryption tests
+    #[cfg(test)]
     pub(crate) fn x25519_secret(&self) -> &[u8; 32] {
         &self.x25519_secret
     }
@@ -179,6 +275,36 @@ pub fn verify_with_public_key(
     Ok(verifying_key.verify(message, &signature).is_ok())
 }
 
+/// X25519 public key for an Ed25519 public key, by the birational map
+/// from the Edwards curve to its Montgomery form (u = (1 + y) / (1 - y)).
+///
+/// Useful when a peer's signed record, which carries their encryption
+/// key, can't be fetched. The result is only their actual encryption key
+/// if it is still the one derived from their seed: once they've called
+/// [`GnsIdentity::rotate_encryption_key`] (or use a client deriving it
+/// some other way) it's a key nobody holds, and messages sent to it are
+/// lost. Prefer the record whenever there is one.
+pub fn ed25519_public_to_x25519_public(public_key_hex: &str) -> Result<String, CryptoError> {
+    let public_key_bytes = hex::decode(public_key_hex)?;
+    let public_key_arr: [u8; 32] =
+        public_key_bytes
+            .try_into()
+            .map_err(|bytes: Vec<u8>| CryptoError::InvalidKeyLength {
+                expected: 32,
+                got: bytes.len(),
+            })?;
+
+    let verifying_key = VerifyingKey::from_bytes(&public_key_arr)
+        .map_err(|_| CryptoError::InvalidKeyFormat("not a point on Ed25519".to_string()))?;
+    if verifying_key.is_weak() {
+        return Err(CryptoError::InvalidKeyFormat(
+            "small-order Ed25519 public key".to_string(),
+        ));
+    }
+
+    Ok(hex::encode(verifying_key.to_montgomery().as_bytes()))
+}
+
 /// Convert Ed25519 private key to X25519 private key
 ///
 /// This follows the standard conversion:
@@ -249,6 +375,78 @@ mod tests {
         assert!(valid);
     }
 
+    #[test]
+    fn test_rotated_key_still_decrypts_old_messages() {
+        let mut identity = GnsIdentity::generate();
+        let old_key = identity.encryption_public_key_bytes();
+        let before = crate::encryption::encrypt_for_recipient(b"before", &old_key).unwrap();
+
+        identity.rotate_encryption_key();
+        assert!(!identity.has_derived_encryption_key());
+        let new_key = identity.encryption_public_key_bytes();
+        assert_ne!(new_key, old_key);
+        let after = crate::encryption::encrypt_for_recipient(b"after", &new_key).unwrap();
+
+        assert_eq!(identity.decrypt(&before).unwrap(), b"before");
+        assert_eq!(identity.decrypt(&after).unwrap(), b"after");
+
+        // New messages only open with the fresh key
+        assert!(crate::encryption::decrypt_from_sender(&identity.retired_encryption_secrets()[0], &after).is_err());
+
+        // Survives being restored from stored secrets
+        let restored = GnsIdentity::from_hex(&identity.private_key_hex())
+            .unwrap()
+            .with_encryption_secret(
+                identity.encryption_secret_bytes(),
+                identity.retired_encryption_secrets().to_vec(),
+            );
+        assert_eq!(restored.encryption_public_key_bytes(), new_key);
+        assert_eq!(restored.decrypt(&before).unwrap(), b"before");
+
+        // Without the retired key the old message is lost
+        let forgetful = GnsIdentity::from_hex(&identity.private_key_hex())
+            .unwrap()
+            .with_encryption_secret(identity.encryption_secret_bytes(), Vec::new());
+        assert!(forgetful.decrypt(&before).is_err());
+    }
+
+    #[test]
+    fn test_public_key_conversion_matches_derived_keys() {
+        // RFC 8032 test keys 1 and 2, with the X25519 public keys of their
+        // SHA-512 clamped seeds
+        let vectors = [
+            (
+                "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+                "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+                "d85e07ec22b0ad881537c2f44d662d1a143cf830c57aca4305d85c7a90f6b62e",
+            ),
+            (
+                "4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+                "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+                "25c704c594b88afc00a76b69d1ed2b984d7e22550f3ed0802d04fbcd07d38d47",
+            ),
+        ];
+        for (secret, public, x25519_public) in vectors {
+            let identity = GnsIdentity::from_hex(secret).unwrap();
+            assert_eq!(identity.public_key_hex(), public);
+            assert_eq!(identity.encryption_key_hex(), x25519_public);
+            assert_eq!(ed25519_public_to_x25519_public(public).unwrap(), x25519_public);
+        }
+
+        // A rotated key can't be recovered from the public key
+        let mut rotated = GnsIdentity::generate();
+        rotated.rotate_encryption_key();
+        assert_ne!(
+            ed25519_public_to_x25519_public(&rotated.public_key_hex()).unwrap(),
+            rotated.encryption_key_hex()
+        );
+
+        // Wrong length, a y with no point, and the identity point
+        assert!(ed25519_public_to_x25519_public("d75a9801").is_err());
+        assert!(ed25519_public_to_x25519_public(&format!("02{}", "00".repeat(31))).is_err());
+        assert!(ed25519_public_to_x25519_public(&format!("01{}", "00".repeat(31))).is_err());
+    }
+
     #[test]
     fn test_x25519_derivation_is_deterministic() {
         let identity1 = GnsIdentity::from_hex(
@@ -0,0 +1,315 @@
+//! Message Key Cache
+//!
+//! Every envelope costs an X25519 exchange and an HKDF derivation on each
+//! side. In an active conversation that is the same work over and over, so
+//! a [`MessageKeyCache`] keeps derived keys in memory for a short while:
+//!
+//! - Sending, the ephemeral keypair and key for a peer's encryption key are
+//!   reused until they expire. Every message still gets a fresh nonce.
+//! - Receiving, keys are kept by the sender's ephemeral key and our own
+//!   encryption key, so messages from a sender that reuses its ephemeral
+//!   key decrypt without a new exchange.
+//!
+//! Nothing changes on the wire: a recipient without a cache just performs
+//! the exchange. Reusing an ephemeral key does mean that, within the TTL,
+//! messages to a peer share one key, so keep the TTL short. Keys are never
+//! written anywhere and are zeroized when evicted.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use zeroize::Zeroizing;
+
+use crate::encryption::{
+    derive_receiving_key, derive_sending_key, open, seal, validate_payload, EncryptedPayload,
+};
+use crate::errors::CryptoError;
+
+/// How long a derived key is reused unless configured otherwise
+pub const DEFAULT_KEY_CACHE_TTL: Duration = Duration::from_secs(120);
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum CacheKey {
+    /// Sending to `peer` at its encryption key `encryption_key`
+    Send {
+        peer: String,
+        encryption_key: [u8; 32],
+    },
+    /// Receiving on `ephemeral`, for the secret hashing to `ours`
+    Receive { ephemeral: [u8; 32], ours: [u8; 32] },
+}
+
+struct CachedKey {
+    ephemeral_public: [u8; 32],
+    key: Zeroizing<[u8; 32]>,
+    expires: Instant,
+}
+
+/// In-memory cache of derived message keys, safe to share between threads
+pub struct MessageKeyCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<CacheKey, CachedKey>>,
+    derivations: AtomicU64,
+}
+
+impl Default for MessageKeyCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_KEY_CACHE_TTL)
+    }
+}
+
+impl MessageKeyCache {
+    /// Keep derived keys for `ttl`. A zero TTL disables caching.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+            derivations: AtomicU64::new(0),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Number of key exchanges performed so far, cached or not
+    pub fn derivations(&self) -> u64 {
+        self.derivations.load(Ordering::Relaxed)
+    }
+
+    /// Drop every cached key, e.g. when the app locks
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Drop the keys for sending to `peer_public_key`, e.g. because it
+    /// published a new encryption key
+    pub fn invalidate_peer(&self, peer_public_key: &str) {
+        let peer_public_key = peer_public_key.to_lowercase();
+        self.lock()
+            .retain(|key, _| !matches!(key, CacheKey::Send { peer, .. } if *peer == peer_public_key));
+    }
+
+    /// Encrypt for `peer_public_key`'s X25519 key, like
+    /// [`crate::encrypt_for_recipient_with_aad`] but reusing a cached key
+    pub fn encrypt(
+        &self,
+        plaintext: &[u8],
+        peer_public_key: &str,
+        recipient_x25519_public: &[u8; 32],
+        aad: &[u8],
+    ) -> Result<EncryptedPayload, CryptoError> {
+        let (ephemeral_public, key) = self.sending_key(peer_public_key, recipient_x25519_public)?;
+        seal(plaintext, ephemeral_public, &key, aad)
+    }
+
+    /// Decrypt data sent to us, like [`crate::decrypt_from_sender_with_aad`]
+    /// but reusing a cached key
+    pub fn decrypt(
+        &self,
+        our_x25519_secret: &[u8; 32],
+        encrypted: &EncryptedPayload,
+        aad: &[u8],
+    ) -> Result<Vec<u8>, CryptoError> {
+        let ephemeral_public = validate_payload(encrypted)?;
+        let key = self.receiving_key(our_x25519_secret, &ephemeral_public)?;
+        open(encrypted, &key, aad)
+    }
+
+    /// Ephemeral public key and symmetric key for sending to `peer` at
+    /// `recipient_x25519_public`. Keys for the peer's other (older)
+    /// encryption keys are dropped when a new one is derived.
+    fn sending_key(
+        &self,
+        peer_public_key: &str,
+        recipient_x25519_public: &[u8; 32],
+    ) -> Result<([u8; 32], Zeroizing<[u8; 32]>), CryptoError> {
+        let peer = peer_public_key.to_lowercase();
+        let cache_key = CacheKey::Send {
+            peer: peer.clone(),
+            encryption_key: *recipient_x25519_public,
+        };
+        if let Some(cached) = self.get(&cache_key) {
+            return Ok((cached.ephemeral_public, cached.key));
+        }
+
+        let (ephemeral_public, key) = derive_sending_key(recipient_x25519_public)?;
+        self.derivations.fetch_add(1, Ordering::Relaxed);
+        if !self.ttl.is_zero() {
+            let mut entries = self.lock();
+            entries.retain(|k, _| !matches!(k, CacheKey::Send { peer: p, .. } if *p == peer));
+            self.insert(&mut entries, cache_key, ephemeral_public, &key);
+        }
+        Ok((ephemeral_public, key))
+    }
+
+    /// Symmetric key for a message on `ephemeral_public` to our secret
+    fn receiving_key(
+        &self,
+        our_x25519_secret: &[u8; 32],
+        ephemeral_public: &[u8; 32],
+    ) -> Result<Zeroizing<[u8; 32]>, CryptoError> {
+        let cache_key = CacheKey::Receive {
+            ephemeral: *ephemeral_public,
+            // The secret itself never goes into the map
+            ours: *blake3::hash(our_x25519_secret).as_bytes(),
+        };
+        if let Some(cached) = self.get(&cache_key) {
+            return Ok(cached.key);
+        }
+
+        let key = derive_receiving_key(our_x25519_secret, ephemeral_public)?;
+        self.derivations.fetch_add(1, Ordering::Relaxed);
+        if !self.ttl.is_zero() {
+            self.insert(&mut self.lock(), cache_key, *ephemeral_public, &key);
+        }
+        Ok(key)
+    }
+
+    fn get(&self, cache_key: &CacheKey) -> Option<CachedKey> {
+        let mut entries = self.lock();
+        match entries.get(cache_key) {
+            Some(cached) if cached.expires > Instant::now() => Some(CachedKey {
+                ephemeral_public: cached.ephemeral_public,
+                key: cached.key.clone(),
+                expires: cached.expires,
+            }),
+            Some(_) => {
+                entries.remove(cache_key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(
+        &self,
+        entries: &mut HashMap<CacheKey, CachedKey>,
+        cache_key: CacheKey,
+        ephemeral_public: [u8; 32],
+        key: &Zeroizing<[u8; 32]>,
+    ) {
+        let now = Instant::now();
+        entries.retain(|_, cached| cached.expires > now);
+        entries.insert(
+            cache_key,
+            CachedKey {
+                ephemeral_public,
+                key: key.clone(),
+                expires: now + self.ttl,
+            },
+        );
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<CacheKey, CachedKey>> {
+        // A panic mid-update leaves at worst a stale entry, which is fine
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::{create_envelope_with_cache, open_envelope_with_cache};
+    use crate::GnsIdentity;
+
+    fn send(
+        cache: &MessageKeyCache,
+        sender: &GnsIdentity,
+        recipient: &GnsIdentity,
+        text: &str,
+    ) -> crate::GnsEnvelope {
+        create_envelope_with_cache(
+            sender,
+            None,
+            &recipient.public_key_hex(),
+            &recipient.encryption_key_hex(),
+            "text/plain",
+            text.as_bytes(),
+            None,
+            None,
+            crate::Compression::Off,
+            cache,
+        )
+        .unwrap()
+    }
+
+    fn payload(envelope: &crate::GnsEnvelope) -> &EncryptedPayload {
+        match &envelope.encrypted_payload {
+            crate::encryption::PayloadWrapper::Object(payload) => payload,
+            crate::encryption::PayloadWrapper::String(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_second_send_skips_derivation() {
+        let alice = GnsIdentity::generate();
+        let bob = GnsIdentity::generate();
+        let alice_cache = MessageKeyCache::default();
+        let bob_cache = MessageKeyCache::default();
+
+        let first = send(&alice_cache, &alice, &bob, "first");
+        let second = send(&alice_cache, &alice, &bob, "second");
+        assert_eq!(alice_cache.derivations(), 1);
+
+        // Same key, fresh nonce
+        assert_eq!(payload(&first).ephemeral_public_key, payload(&second).ephemeral_public_key);
+        assert_ne!(payload(&first).nonce, payload(&second).nonce);
+
+        // Bob opens both with one exchange; a recipient without a cache
+        // still can
+        for (envelope, text) in [(&first, "first"), (&second, "second")] {
+            let opened = open_envelope_with_cache(&bob, envelope, &bob_cache).unwrap();
+            assert_eq!(opened.payload, text.as_bytes());
+        }
+        assert_eq!(bob_cache.derivations(), 1);
+        assert_eq!(crate::open_envelope(&bob, &second).unwrap().payload, b"second");
+
+        // Nothing is kept with caching disabled
+        let uncached = MessageKeyCache::new(Duration::ZERO);
+        send(&uncached, &alice, &bob, "a");
+        send(&uncached, &alice, &bob, "b");
+        assert_eq!(uncached.derivations(), 2);
+    }
+
+    #[test]
+    fn test_rotation_invalidates_cached_key() {
+        let alice = GnsIdentity::generate();
+        let mut bob = GnsIdentity::generate();
+        let cache = MessageKeyCache::default();
+
+        let before = send(&cache, &alice, &bob, "before");
+        bob.rotate_encryption_key();
+        let after = send(&cache, &alice, &bob, "after");
+
+        // The new encryption key got a fresh exchange, and only the new
+        // key can open the new message
+        assert_eq!(cache.derivations(), 2);
+        assert_ne!(payload(&before).ephemeral_public_key, payload(&after).ephemeral_public_key);
+        let old_bob = GnsIdentity::from_hex(&bob.private_key_hex()).unwrap();
+        assert!(crate::open_envelope(&old_bob, &after).is_err());
+        assert_eq!(crate::open_envelope(&bob, &after).unwrap().payload, b"after");
+
+        // Receiving keys are cached per secret, so both the retired and
+        // the current key keep working, from the cache the second time
+        let bob_cache = MessageKeyCache::default();
+        for _ in 0..2 {
+            for (envelope, text) in [(&before, "before"), (&after, "after")] {
+                let opened = open_envelope_with_cache(&bob, envelope, &bob_cache).unwrap();
+                assert_eq!(opened.payload, text.as_bytes());
+            }
+        }
+        // The message to the retired key tried the current one first
+        assert_eq!(bob_cache.derivations(), 3);
+
+        // Explicit invalidation and clearing force a new exchange
+        cache.invalidate_peer(&bob.public_key_hex().to_uppercase());
+        send(&cache, &alice, &bob, "again");
+        assert_eq!(cache.derivations(), 3);
+        cache.clear();
+        send(&cache, &alice, &bob, "locked");
+        assert_eq!(cache.derivations(), 4);
+    }
+}
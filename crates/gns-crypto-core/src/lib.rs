@@ -12,18 +12,36 @@
 //! - No custom cryptography
 
 pub mod breadcrumb;
+pub mod domain;
 pub mod encryption;
 pub mod envelope;
 pub mod errors;
 pub mod identity;
+pub mod key_cache;
+pub mod ratchet;
 pub mod signing;
+pub mod thread_meta;
 
 pub use breadcrumb::{create_breadcrumb, Breadcrumb};
-pub use encryption::{decrypt_from_sender, encrypt_for_recipient, EncryptedPayload};
-pub use envelope::{create_envelope, create_envelope_with_metadata, open_envelope, GnsEnvelope};
+pub use domain::{
+    typed_data_message, verify_in_domain_hex, DomainPolicy, SignatureDomain, TRANSITION_POLICY,
+};
+pub use encryption::{
+    decrypt_from_sender, decrypt_from_sender_with_aad, encrypt_for_recipient,
+    encrypt_for_recipient_with_aad, EncryptedPayload,
+};
+pub use envelope::{
+    compute_thread_id, create_envelope, create_envelope_with_cache,
+    create_envelope_with_compression, create_envelope_with_metadata, create_envelope_with_ratchet,
+    open_envelope, open_envelope_with_cache, open_envelope_with_ratchet, Compression, GnsEnvelope,
+    CRYPTO_VERSION_2, CURRENT_CRYPTO_VERSION, DEFAULT_COMPRESSION_THRESHOLD, DIRECT_THREAD_PREFIX,
+};
 pub use errors::CryptoError;
-pub use identity::GnsIdentity;
+pub use identity::{ed25519_public_to_x25519_public, GnsIdentity};
+pub use key_cache::{MessageKeyCache, DEFAULT_KEY_CACHE_TTL};
+pub use ratchet::{RatchetHeader, RatchetSessions, RATCHET_WINDOW};
 pub use signing::{sign_message, verify_signature};
+pub use thread_meta::{open_thread_meta, seal_thread_meta, SealedThreadMeta, ThreadKey};
 
 /// Re-export commonly used types
 pub mod prelude {
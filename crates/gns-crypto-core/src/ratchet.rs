@@ -0,0 +1,510 @@
+//! Forward Secrecy Ratchet
+//!
+//! Version 1 envelopes are encrypted to the recipient's static X25519 key,
+//! so whoever later gets hold of that key can read every message ever sent
+//! to it. For active threads two peers can instead agree on a session and
+//! ratchet the message key forward with every message:
+//!
+//! 1. A sender without a session attaches an *offer* to its ordinary
+//!    version 1 envelopes: a fresh X25519 public key. It keeps the secret
+//!    until the offer is answered.
+//! 2. A recipient that supports the ratchet *answers*: it generates its own
+//!    ephemeral keypair, derives the session from the exchange with the
+//!    offer and throws its secret away. Its next message to the peer is a
+//!    version 2 envelope naming both keys.
+//! 3. The offering side derives the same session from that envelope and
+//!    deletes its offer secret. From then on both sides send version 2.
+//!
+//! Each direction of a session is a chain: every message takes the next
+//! key from it and the chain key moves on through a one-way step, so a
+//! captured chain key opens later messages only. Keys for messages that
+//! arrive early are held back for up to [`RATCHET_WINDOW`] messages, and
+//! every message key is deleted once used.
+//!
+//! A peer that never answers keeps receiving version 1 envelopes, which
+//! it can open without knowing about any of this. Sessions live in
+//! [`RatchetSessions`]; persist them with [`RatchetSessions::export`] and
+//! treat the export like the identity key.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use x25519_dalek::{EphemeralSecret, PublicKey as X25519PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+use crate::errors::CryptoError;
+
+/// How far ahead of the next expected message a message may arrive, and
+/// how many held-back keys a session keeps
+pub const RATCHET_WINDOW: u64 = 64;
+
+/// Sessions kept per peer; the oldest is dropped past this
+const MAX_SESSIONS_PER_PEER: usize = 4;
+
+const SESSION_INFO: &[u8] = b"gns-ratchet-v1:session";
+const MESSAGE_KEY_STEP: u8 = 0x01;
+const CHAIN_KEY_STEP: u8 = 0x02;
+
+/// Ratchet fields of an envelope, covered by the header signature.
+///
+/// An offer has only `offer`; a ratcheted message names the session by
+/// both public keys and carries its position in the sender's chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RatchetHeader {
+    /// X25519 public key of the side that offered the session (hex)
+    pub offer: String,
+
+    /// X25519 public key of the side that answered (hex)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub answer: Option<String>,
+
+    /// Index of the message in the sender's chain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub counter: Option<u64>,
+}
+
+impl RatchetHeader {
+    fn offer(public: &[u8; 32]) -> Self {
+        Self {
+            offer: hex::encode(public),
+            answer: None,
+            counter: None,
+        }
+    }
+
+    pub fn is_offer(&self) -> bool {
+        self.answer.is_none()
+    }
+
+    /// The session keys and counter of a ratcheted message
+    fn message_parts(&self) -> Result<([u8; 32], [u8; 32], u64), CryptoError> {
+        let (Some(answer), Some(counter)) = (&self.answer, self.counter) else {
+            return Err(CryptoError::InvalidEnvelope(
+                "ratchet header names no session".to_string(),
+            ));
+        };
+        Ok((decode_key(&self.offer)?, decode_key(answer)?, counter))
+    }
+}
+
+/// What to send a peer with: a session key, or static-key encryption
+/// carrying an offer
+pub(crate) enum Sending {
+    Session {
+        header: RatchetHeader,
+        /// Our public key in the session, sent as the payload's ephemeral key
+        public: [u8; 32],
+        key: Zeroizing<[u8; 32]>,
+    },
+    Offer(RatchetHeader),
+}
+
+#[derive(Clone)]
+struct Session {
+    offer: [u8; 32],
+    answer: [u8; 32],
+    /// We made the offer
+    offerer: bool,
+    send_chain: Zeroizing<[u8; 32]>,
+    send_counter: u64,
+    recv_chain: Zeroizing<[u8; 32]>,
+    recv_counter: u64,
+    /// Keys of messages skipped over, by counter
+    skipped: BTreeMap<u64, Zeroizing<[u8; 32]>>,
+}
+
+impl Session {
+    fn establish(
+        shared_secret: &[u8; 32],
+        offer: [u8; 32],
+        answer: [u8; 32],
+        offerer: bool,
+    ) -> Result<Self, CryptoError> {
+        let salt = [offer, answer].concat();
+        let mut chains = Zeroizing::new([0u8; 64]);
+        Hkdf::<Sha256>::new(Some(&salt), shared_secret)
+            .expand(SESSION_INFO, chains.as_mut())
+            .map_err(|e| CryptoError::KeyDerivationFailed(e.to_string()))?;
+
+        // The first chain carries the offerer's messages
+        let mut offerer_chain = Zeroizing::new([0u8; 32]);
+        let mut answerer_chain = Zeroizing::new([0u8; 32]);
+        offerer_chain.copy_from_slice(&chains[..32]);
+        answerer_chain.copy_from_slice(&chains[32..]);
+        let (send_chain, recv_chain) = if offerer {
+            (offerer_chain, answerer_chain)
+        } else {
+            (answerer_chain, offerer_chain)
+        };
+
+        Ok(Self {
+            offer,
+            answer,
+            offerer,
+            send_chain,
+            send_counter: 0,
+            recv_chain,
+            recv_counter: 0,
+            skipped: BTreeMap::new(),
+        })
+    }
+
+    fn our_public(&self) -> [u8; 32] {
+        if self.offerer {
+            self.offer
+        } else {
+            self.answer
+        }
+    }
+
+    fn next_sending_key(&mut self) -> (u64, Zeroizing<[u8; 32]>) {
+        let key = step(&self.send_chain, MESSAGE_KEY_STEP);
+        self.send_chain = step(&self.send_chain, CHAIN_KEY_STEP);
+        let counter = self.send_counter;
+        self.send_counter += 1;
+        (counter, key)
+    }
+
+    /// Take the key of received message `counter`, advancing the chain
+    /// past it and holding back the keys of any messages skipped over
+    fn take_receiving_key(&mut self, counter: u64) -> Result<Zeroizing<[u8; 32]>, CryptoError> {
+        if counter < self.recv_counter {
+            return self.skipped.remove(&counter).ok_or_else(|| {
+                CryptoError::DecryptionFailed(format!(
+                    "ratchet key {} was already used or has expired",
+                    counter
+                ))
+            });
+        }
+        if counter - self.recv_counter >= RATCHET_WINDOW {
+            return Err(CryptoError::DecryptionFailed(format!(
+                "ratchet counter {} is too far ahead of {}",
+                counter, self.recv_counter
+            )));
+        }
+
+        while self.recv_counter < counter {
+            self.skipped
+                .insert(self.recv_counter, step(&self.recv_chain, MESSAGE_KEY_STEP));
+            self.recv_chain = step(&self.recv_chain, CHAIN_KEY_STEP);
+            self.recv_counter += 1;
+        }
+        let key = step(&self.recv_chain, MESSAGE_KEY_STEP);
+        self.recv_chain = step(&self.recv_chain, CHAIN_KEY_STEP);
+        self.recv_counter += 1;
+
+        while self.skipped.len() as u64 > RATCHET_WINDOW {
+            self.skipped.pop_first();
+        }
+        Ok(key)
+    }
+}
+
+/// One step of a chain: HMAC-SHA256 of a single label byte
+fn step(chain: &[u8; 32], label: u8) -> Zeroizing<[u8; 32]> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(chain).expect("HMAC takes any key length");
+    mac.update(&[label]);
+    Zeroizing::new(mac.finalize().into_bytes().into())
+}
+
+fn decode_key(key_hex: &str) -> Result<[u8; 32], CryptoError> {
+    let bytes = hex::decode(key_hex)?;
+    bytes.as_slice().try_into().map_err(|_| CryptoError::InvalidKeyLength {
+        expected: 32,
+        got: bytes.len(),
+    })
+}
+
+#[derive(Default)]
+struct PeerState {
+    /// Our unanswered offer
+    pending: Option<StaticSecret>,
+    /// Newest last
+    sessions: Vec<Session>,
+}
+
+impl PeerState {
+    fn add_session(&mut self, session: Session) {
+        self.sessions.push(session);
+        if self.sessions.len() > MAX_SESSIONS_PER_PEER {
+            self.sessions.remove(0);
+        }
+    }
+}
+
+/// Ratchet sessions with every peer, safe to share between threads
+#[derive(Default)]
+pub struct RatchetSessions {
+    peers: Mutex<HashMap<String, PeerState>>,
+}
+
+impl RatchetSessions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether messages to `peer_public_key` already go out ratcheted
+    pub fn has_session(&self, peer_public_key: &str) -> bool {
+        self.lock()
+            .get(&peer_public_key.to_lowercase())
+            .is_some_and(|peer| !peer.sessions.is_empty())
+    }
+
+    /// Drop the sessions and offer for `peer_public_key`; the next message
+    /// to it negotiates again
+    pub fn forget_peer(&self, peer_public_key: &str) {
+        self.lock().remove(&peer_public_key.to_lowercase());
+    }
+
+    /// Drop every session, e.g. when the identity changes
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// The session key for the next message to `peer_public_key`, or an
+    /// offer to send along with static-key encryption
+    pub(crate) fn next_sending(&self, peer_public_key: &str) -> Sending {
+        let mut peers = self.lock();
+        let peer = peers.entry(peer_public_key.to_lowercase()).or_default();
+
+        if let Some(session) = peer.sessions.last_mut() {
+            let (counter, key) = session.next_sending_key();
+            return Sending::Session {
+                header: RatchetHeader {
+                    offer: hex::encode(session.offer),
+                    answer: Some(hex::encode(session.answer)),
+                    counter: Some(counter),
+                },
+                public: session.our_public(),
+                key,
+            };
+        }
+
+        let pending = peer
+            .pending
+            .get_or_insert_with(|| StaticSecret::random_from_rng(OsRng));
+        Sending::Offer(RatchetHeader::offer(X25519PublicKey::from(&*pending).as_bytes()))
+    }
+
+    /// Answer `peer_public_key`'s offer, starting a session our next
+    /// message to it will use. Returns false for offers already answered,
+    /// our own, and malformed ones.
+    pub(crate) fn accept_offer(&self, peer_public_key: &str, header: &RatchetHeader) -> bool {
+        let Ok(offer) = decode_key(&header.offer) else {
+            return false;
+        };
+        let mut peers = self.lock();
+        let peer = peers.entry(peer_public_key.to_lowercase()).or_default();
+        let ours = peer
+            .pending
+            .as_ref()
+            .is_some_and(|pending| X25519PublicKey::from(pending).as_bytes() == &offer);
+        if ours || peer.sessions.iter().any(|s| s.offer == offer) {
+            return false;
+        }
+
+        let secret = EphemeralSecret::random_from_rng(OsRng);
+        let answer = X25519PublicKey::from(&secret).to_bytes();
+        let shared = secret.diffie_hellman(&X25519PublicKey::from(offer));
+        match Session::establish(shared.as_bytes(), offer, answer, false) {
+            Ok(session) => {
+                peer.add_session(session);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Decrypt a ratcheted message from `peer_public_key` with `decrypt`,
+    /// given the message key. Session state only moves on if `decrypt`
+    /// succeeds, so a forged or corrupted message can't use up keys.
+    pub(crate) fn open(
+        &self,
+        peer_public_key: &str,
+        header: &RatchetHeader,
+        decrypt: impl FnOnce(&[u8; 32]) -> Result<Vec<u8>, CryptoError>,
+    ) -> Result<Vec<u8>, CryptoError> {
+        let (offer, answer, counter) = header.message_parts()?;
+        let mut peers = self.lock();
+        let peer = peers.entry(peer_public_key.to_lowercase()).or_default();
+
+        let index = peer
+            .sessions
+            .iter()
+            .position(|s| s.offer == offer && s.answer == answer);
+        let mut session = match index {
+            Some(index) => peer.sessions[index].clone(),
+            None => {
+                // The answer to our offer
+                let pending = peer
+                    .pending
+                    .as_ref()
+                    .filter(|pending| X25519PublicKey::from(*pending).as_bytes() == &offer)
+                    .ok_or_else(|| {
+                        CryptoError::DecryptionFailed("no ratchet session for this envelope".to_string())
+                    })?;
+                let shared = pending.diffie_hellman(&X25519PublicKey::from(answer));
+                Session::establish(shared.as_bytes(), offer, answer, true)?
+            }
+        };
+
+        let key = session.take_receiving_key(counter)?;
+        let plaintext = decrypt(&key)?;
+
+        match index {
+            Some(index) => peer.sessions[index] = session,
+            None => {
+                peer.pending = None;
+                peer.add_session(session);
+            }
+        }
+        Ok(plaintext)
+    }
+
+    /// Serialize every session and offer, secrets included
+    pub fn export(&self) -> Result<Zeroizing<String>, CryptoError> {
+        let peers = self.lock();
+        let stored: Vec<StoredPeer> = peers
+            .iter()
+            .map(|(peer_public_key, peer)| StoredPeer {
+                peer: peer_public_key.clone(),
+                pending: peer.pending.as_ref().map(|secret| hex::encode(secret.as_bytes())),
+                sessions: peer.sessions.iter().map(StoredSession::from).collect(),
+            })
+            .collect();
+        Ok(Zeroizing::new(serde_json::to_string(&stored)?))
+    }
+
+    /// Restore sessions from [`RatchetSessions::export`]
+    pub fn import(json: &str) -> Result<Self, CryptoError> {
+        let stored: Vec<StoredPeer> = serde_json::from_str(json)?;
+        let mut peers = HashMap::new();
+        for peer in stored {
+            let pending = match &peer.pending {
+                Some(secret) => Some(StaticSecret::from(decode_key(secret)?)),
+                None => None,
+            };
+            let sessions = peer
+                .sessions
+                .iter()
+                .map(Session::try_from)
+                .collect::<Result<_, _>>()?;
+            peers.insert(peer.peer, PeerState { pending, sessions });
+        }
+        Ok(Self {
+            peers: Mutex::new(peers),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, PeerState>> {
+        // Sessions stay consistent even if a holder panicked
+        self.peers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Export form of a peer's state
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredPeer {
+    peer: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pending: Option<String>,
+    #[serde(default)]
+    sessions: Vec<StoredSession>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredSession {
+    offer: String,
+    answer: String,
+    offerer: bool,
+    send_chain: String,
+    send_counter: u64,
+    recv_chain: String,
+    recv_counter: u64,
+    #[serde(default)]
+    skipped: Vec<(u64, String)>,
+}
+
+impl From<&Session> for StoredSession {
+    fn from(session: &Session) -> Self {
+        Self {
+            offer: hex::encode(session.offer),
+            answer: hex::encode(session.answer),
+            offerer: session.offerer,
+            send_chain: hex::encode(*session.send_chain),
+            send_counter: session.send_counter,
+            recv_chain: hex::encode(*session.recv_chain),
+            recv_counter: session.recv_counter,
+            skipped: session
+                .skipped
+                .iter()
+                .map(|(counter, key)| (*counter, hex::encode(**key)))
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<&StoredSession> for Session {
+    type Error = CryptoError;
+
+    fn try_from(stored: &StoredSession) -> Result<Self, CryptoError> {
+        let skipped = stored
+            .skipped
+            .iter()
+            .map(|(counter, key)| Ok((*counter, Zeroizing::new(decode_key(key)?))))
+            .collect::<Result<_, CryptoError>>()?;
+        Ok(Self {
+            offer: decode_key(&stored.offer)?,
+            answer: decode_key(&stored.answer)?,
+            offerer: stored.offerer,
+            send_chain: Zeroizing::new(decode_key(&stored.send_chain)?),
+            send_counter: stored.send_counter,
+            recv_chain: Zeroizing::new(decode_key(&stored.recv_chain)?),
+            recv_counter: stored.recv_counter,
+            skipped,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_keys_only_move_forward() {
+        let (alice, bob) = (RatchetSessions::new(), RatchetSessions::new());
+        let Sending::Offer(offer) = alice.next_sending("bb") else {
+            panic!("no session yet, expected an offer");
+        };
+        assert!(bob.accept_offer("aa", &offer));
+        // A repeated offer doesn't start another session
+        assert!(!bob.accept_offer("aa", &offer));
+        assert!(bob.has_session("AA") && !alice.has_session("bb"));
+
+        let Sending::Session { header, key, .. } = bob.next_sending("aa") else {
+            panic!("expected a session key");
+        };
+        let opened = alice.open("bb", &header, |k| {
+            assert_eq!(k, &*key);
+            Ok(b"ok".to_vec())
+        });
+        assert_eq!(opened.unwrap(), b"ok");
+        assert!(alice.has_session("bb"));
+
+        // A failed decryption leaves the next key in place
+        let Sending::Session { header, key, .. } = bob.next_sending("aa") else {
+            panic!("expected a session key");
+        };
+        let failed = alice.open("bb", &header, |_| Err(CryptoError::DecryptionFailed("forged".into())));
+        assert!(failed.is_err());
+        assert_eq!(alice.open("bb", &header, |k| Ok(k.to_vec())).unwrap(), key.to_vec());
+    }
+}
@@ -0,0 +1,188 @@
+//! Thread Metadata Encryption
+//!
+//! A group thread's name, avatar and member list are encrypted under a
+//! symmetric key of the thread's own, so a relay passing metadata updates
+//! along only ever sees ciphertext. The key reaches each member inside an
+//! ordinary envelope, which is already encrypted for that member alone;
+//! whoever never received it can't read the metadata.
+//!
+//! Each update is signed by its author over the thread id, nonce and
+//! ciphertext in the `ThreadMeta` domain, and the thread id is also bound
+//! in as associated data, so an update can't be passed off as another
+//! member's or replayed into a different thread.
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Nonce,
+};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::domain::{verify_in_domain_hex, DomainPolicy, SignatureDomain};
+use crate::errors::CryptoError;
+use crate::identity::GnsIdentity;
+
+/// A thread's metadata key
+#[derive(Clone)]
+pub struct ThreadKey(Zeroizing<[u8; 32]>);
+
+impl ThreadKey {
+    /// A fresh random key
+    pub fn generate() -> Self {
+        let mut key = Zeroizing::new([0u8; 32]);
+        OsRng.fill_bytes(key.as_mut());
+        Self(key)
+    }
+
+    pub fn from_hex(key_hex: &str) -> Result<Self, CryptoError> {
+        let bytes = Zeroizing::new(hex::decode(key_hex)?);
+        let key: [u8; 32] = bytes.as_slice().try_into().map_err(|_| CryptoError::InvalidKeyLength {
+            expected: 32,
+            got: bytes.len(),
+        })?;
+        Ok(Self(Zeroizing::new(key)))
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0.as_ref())
+    }
+}
+
+impl std::fmt::Debug for ThreadKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ThreadKey(..)")
+    }
+}
+
+/// Encrypted, signed thread metadata as it travels between members
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SealedThreadMeta {
+    pub thread_id: String,
+    /// Ed25519 public key (hex) of the member who made the change
+    pub author_public_key: String,
+    /// ChaCha20-Poly1305 nonce (hex)
+    pub nonce: String,
+    /// Encrypted metadata with its authentication tag (hex)
+    pub ciphertext: String,
+    /// Author's signature (hex) in the `ThreadMeta` domain
+    pub signature: String,
+}
+
+impl SealedThreadMeta {
+    /// The message the author signs
+    pub fn signed_message(&self) -> Vec<u8> {
+        format!("{}:{}:{}", self.thread_id, self.nonce, self.ciphertext).into_bytes()
+    }
+}
+
+/// Encrypt `metadata` for `thread_id` under `key` and sign it as `author`
+pub fn seal_thread_meta(
+    author: &GnsIdentity,
+    key: &ThreadKey,
+    thread_id: &str,
+    metadata: &[u8],
+) -> Result<SealedThreadMeta, CryptoError> {
+    let mut nonce = [0u8; 12];
+    OsRng.fill_bytes(&mut nonce);
+
+    let cipher = ChaCha20Poly1305::new_from_slice(key.0.as_ref())
+        .map_err(|e| CryptoError::EncryptionFailed(e.to_string()))?;
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload { msg: metadata, aad: thread_id.as_bytes() },
+        )
+        .map_err(|e| CryptoError::EncryptionFailed(e.to_string()))?;
+
+    let mut sealed = SealedThreadMeta {
+        thread_id: thread_id.to_string(),
+        author_public_key: author.public_key_hex(),
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(ciphertext),
+        signature: String::new(),
+    };
+    sealed.signature = hex::encode(author.sign_in_domain(SignatureDomain::ThreadMeta, &sealed.signed_message()));
+    Ok(sealed)
+}
+
+/// Check the author's signature, then decrypt with `key`. Fails with
+/// `SignatureVerificationFailed` for a forged or altered update and
+/// `DecryptionFailed` for the wrong key.
+pub fn open_thread_meta(sealed: &SealedThreadMeta, key: &ThreadKey) -> Result<Vec<u8>, CryptoError> {
+    let signed = verify_in_domain_hex(
+        &sealed.author_public_key,
+        SignatureDomain::ThreadMeta,
+        &sealed.signed_message(),
+        &sealed.signature,
+        DomainPolicy::Strict,
+    )?;
+    if !signed {
+        return Err(CryptoError::SignatureVerificationFailed);
+    }
+
+    let nonce = hex::decode(&sealed.nonce)?;
+    if nonce.len() != 12 {
+        return Err(CryptoError::InvalidNonceLength);
+    }
+    let ciphertext = hex::decode(&sealed.ciphertext)?;
+
+    let cipher = ChaCha20Poly1305::new_from_slice(key.0.as_ref())
+        .map_err(|e| CryptoError::DecryptionFailed(e.to_string()))?;
+    cipher
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload { msg: &ciphertext, aad: sealed.thread_id.as_bytes() },
+        )
+        .map_err(|_| CryptoError::DecryptionFailed("Wrong thread key or corrupted metadata".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_key_holders_can_read_metadata() {
+        let alice = GnsIdentity::generate();
+        let key = ThreadKey::generate();
+        let sealed = seal_thread_meta(&alice, &key, "thread-1", br#"{"name":"Hiking"}"#).unwrap();
+        assert!(!sealed.ciphertext.contains(&hex::encode("Hiking")));
+
+        let shared = ThreadKey::from_hex(&key.to_hex()).unwrap();
+        assert_eq!(open_thread_meta(&sealed, &shared).unwrap(), br#"{"name":"Hiking"}"#);
+        assert!(matches!(
+            open_thread_meta(&sealed, &ThreadKey::generate()),
+            Err(CryptoError::DecryptionFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_altered_or_moved_metadata_is_rejected() {
+        let alice = GnsIdentity::generate();
+        let mallory = GnsIdentity::generate();
+        let key = ThreadKey::generate();
+        let sealed = seal_thread_meta(&alice, &key, "thread-1", b"{}").unwrap();
+
+        // Claimed by someone else
+        let mut forged = sealed.clone();
+        forged.author_public_key = mallory.public_key_hex();
+        assert!(matches!(
+            open_thread_meta(&forged, &key),
+            Err(CryptoError::SignatureVerificationFailed)
+        ));
+
+        // Replayed into another thread, even when re-signed
+        let mut moved = sealed.clone();
+        moved.thread_id = "thread-2".to_string();
+        moved.signature =
+            hex::encode(alice.sign_in_domain(SignatureDomain::ThreadMeta, &moved.signed_message()));
+        assert!(matches!(
+            open_thread_meta(&moved, &key),
+            Err(CryptoError::DecryptionFailed(_))
+        ));
+
+        assert!(ThreadKey::from_hex("abcd").is_err());
+    }
+}
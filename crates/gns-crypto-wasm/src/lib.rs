@@ -172,6 +172,13 @@ pub fn open_signed_envelope(
     serde_wasm_bindgen::to_value(&result).map_err(|e| JsError::new(&e.to_string()))
 }
 
+/// Canonical id of the one-to-one thread between two public keys
+/// (same result whichever key is passed first)
+#[wasm_bindgen]
+pub fn compute_thread_id(my_public_key_hex: &str, peer_public_key_hex: &str) -> String {
+    gns_crypto_core::compute_thread_id(my_public_key_hex, peer_public_key_hex)
+}
+
 // ==================== Breadcrumb Operations ====================
 
 /// Create a signed breadcrumb
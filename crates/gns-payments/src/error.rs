@@ -31,6 +31,18 @@ pub enum PaymentError {
     
     #[error("Trustline not established for asset {asset_code}")]
     NoTrustline { asset_code: String },
+    
+    #[error("Trustline for {asset_code} still holds {balance} - move the balance out before removing it")]
+    TrustlineNotEmpty { asset_code: String, balance: String },
+    
+    #[error("Trustline limit {limit} is below the current balance of {balance}")]
+    TrustlineLimitTooLow { limit: String, balance: String },
+    
+    #[error("Account merge is irreversible and must be explicitly confirmed")]
+    MergeNotConfirmed,
+    
+    #[error("Account cannot be merged until these are removed: {}", .blockers.join("; "))]
+    MergeBlocked { blockers: Vec<String> },
 
     // ==================== Transaction Errors ====================
     #[error("Transaction failed: {0}")]
@@ -55,8 +67,8 @@ pub enum PaymentError {
     #[error("Network request failed: {0}")]
     NetworkError(String),
     
-    #[error("Rate limited - try again later")]
-    RateLimited,
+    #[error("Rate limited - try again in {retry_after_secs}s")]
+    RateLimited { retry_after_secs: u64 },
     
     #[error("Network not configured")]
     NetworkNotConfigured,
@@ -93,6 +105,18 @@ pub enum PaymentError {
     SerializationError(String),
 }
 
+impl PaymentError {
+    /// How long to wait before retrying, when Horizon rate limited the call
+    pub fn retry_after(&self) -> Option<std::time::Duration> {
+        match self {
+            PaymentError::RateLimited { retry_after_secs } => {
+                Some(std::time::Duration::from_secs(*retry_after_secs))
+            }
+            _ => None,
+        }
+    }
+}
+
 impl From<reqwest::Error> for PaymentError {
     fn from(err: reqwest::Error) -> Self {
         PaymentError::NetworkError(err.to_string())
@@ -8,10 +8,20 @@
 use crate::config::StellarConfig;
 use crate::error::PaymentError;
 use crate::Result;
-use reqwest::Client;
+use reqwest::{Client, Response};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use tracing::{debug, warn};
 
+/// Wait assumed when a 429 carries no usable `Retry-After`
+const DEFAULT_RETRY_AFTER_SECS: u64 = 5;
+
+/// Times a rate-limited read is retried before giving up
+const MAX_READ_RETRIES: u32 = 2;
+
+/// Longest `Retry-After` a read will sit out; past this the caller decides
+const MAX_READ_RETRY_WAIT_SECS: u64 = 10;
+
 // ============================================================================
 // DATA TYPES
 // ============================================================================
@@ -260,12 +270,12 @@ impl HorizonClient {
     pub async fn account_exists(&self, address: &str) -> Result<bool> {
         let url = format!("{}/accounts/{}", self.config.horizon_url, address);
         
-        let response = self.http.get(&url).send().await?;
+        let response = self.get_with_retry(&url).await?;
         
         match response.status().as_u16() {
             200 => Ok(true),
             404 => Ok(false),
-            429 => Err(PaymentError::RateLimited),
+            429 => Err(rate_limited(&response)),
             status => {
                 let error_text = response.text().await.unwrap_or_default();
                 Err(PaymentError::HorizonError(format!(
@@ -281,7 +291,7 @@ impl HorizonClient {
         
         debug!("Loading account: {}", address);
         
-        let response = self.http.get(&url).send().await?;
+        let response = self.get_with_retry(&url).await?;
         
         match response.status().as_u16() {
             200 => {
@@ -289,7 +299,7 @@ impl HorizonClient {
                 Ok(account)
             }
             404 => Err(PaymentError::AccountNotFound(address.to_string())),
-            429 => Err(PaymentError::RateLimited),
+            429 => Err(rate_limited(&response)),
             status => {
                 let error_text = response.text().await.unwrap_or_default();
                 Err(PaymentError::HorizonError(format!(
@@ -336,7 +346,7 @@ impl HorizonClient {
         
         debug!("Fetching claimable balances for: {}", address);
         
-        let response = self.http.get(&url).send().await?;
+        let response = self.get_with_retry(&url).await?;
         
         match response.status().as_u16() {
             200 => {
@@ -353,7 +363,7 @@ impl HorizonClient {
                 
                 Ok(balances)
             }
-            429 => Err(PaymentError::RateLimited),
+            429 => Err(rate_limited(&response)),
             status => {
                 let error_text = response.text().await.unwrap_or_default();
                 Err(PaymentError::HorizonError(format!(
@@ -413,7 +423,7 @@ impl HorizonClient {
                 warn!("Transaction rejected: {}", reason);
                 Err(PaymentError::TransactionRejected { reason })
             }
-            429 => Err(PaymentError::RateLimited),
+            429 => Err(rate_limited(&response)),
             504 => Err(PaymentError::TransactionTimeout),
             status => {
                 let error_text = response.text().await.unwrap_or_default();
@@ -444,6 +454,7 @@ impl HorizonClient {
                 debug!("Friendbot funded account: {}", address);
                 Ok(())
             }
+            429 => Err(rate_limited(&response)),
             status => {
                 let error_text = response.text().await.unwrap_or_default();
                 Err(PaymentError::HorizonError(format!(
@@ -459,11 +470,64 @@ impl HorizonClient {
     pub async fn get_fee_stats(&self) -> Result<FeeStats> {
         let url = format!("{}/fee_stats", self.config.horizon_url);
         
-        let response = self.http.get(&url).send().await?;
+        let response = self.get_with_retry(&url).await?;
+        if response.status().as_u16() == 429 {
+            return Err(rate_limited(&response));
+        }
         let stats: FeeStats = response.json().await?;
         
         Ok(stats)
     }
+    
+    // ==================== Rate Limiting ====================
+    
+    /// GET for idempotent reads. A 429 is sat out and retried, up to
+    /// `MAX_READ_RETRIES` times and only while Horizon asks for a short
+    /// wait; after that the 429 response is handed back to the caller.
+    async fn get_with_retry(&self, url: &str) -> Result<Response> {
+        let mut retries = 0;
+        loop {
+            let response = self.http.get(url).send().await?;
+            if response.status().as_u16() != 429 || retries == MAX_READ_RETRIES {
+                return Ok(response);
+            }
+            
+            let wait = retry_after_secs(&response);
+            if wait > MAX_READ_RETRY_WAIT_SECS {
+                return Ok(response);
+            }
+            
+            retries += 1;
+            warn!("Horizon rate limited, retrying in {}s ({}/{})", wait, retries, MAX_READ_RETRIES);
+            tokio::time::sleep(Duration::from_secs(wait)).await;
+        }
+    }
+}
+
+/// `RateLimited` carrying the wait Horizon asked for
+fn rate_limited(response: &Response) -> PaymentError {
+    PaymentError::RateLimited { retry_after_secs: retry_after_secs(response) }
+}
+
+/// Seconds to wait according to a response's `Retry-After` header
+fn retry_after_secs(response: &Response) -> u64 {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| parse_retry_after(v, chrono::Utc::now()))
+        .unwrap_or(DEFAULT_RETRY_AFTER_SECS)
+}
+
+/// Parse a `Retry-After` value: delay-seconds or an HTTP date
+fn parse_retry_after(value: &str, now: chrono::DateTime<chrono::Utc>) -> Option<u64> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(secs);
+    }
+    
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.timestamp() - now.timestamp()).max(0) as u64)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -514,6 +578,90 @@ pub struct MaxFeeStats {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    
+    /// Horizon stand-in answering request N with `responses[N]` (the last
+    /// one repeats). Returns a client pointed at it and a request counter.
+    async fn mock_horizon(responses: Vec<String>) -> (HorizonClient, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let horizon_url = format!("http://{}", listener.local_addr().unwrap());
+        let served = Arc::new(AtomicUsize::new(0));
+        let counter = served.clone();
+        
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = [0u8; 4096];
+                let _ = socket.read(&mut buf).await;
+                let n = counter.fetch_add(1, Ordering::SeqCst);
+                let response = &responses[n.min(responses.len() - 1)];
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        
+        let config = StellarConfig { horizon_url, ..StellarConfig::testnet() };
+        (HorizonClient::new(config), served)
+    }
+    
+    fn too_many_requests(retry_after: &str) -> String {
+        format!(
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            retry_after
+        )
+    }
+    
+    fn ok(body: &str) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    }
+    
+    #[test]
+    fn test_parse_retry_after() {
+        let now = chrono::DateTime::parse_from_rfc2822("Wed, 21 Oct 2015 07:28:00 GMT").unwrap().to_utc();
+        
+        assert_eq!(parse_retry_after("120", now), Some(120));
+        assert_eq!(parse_retry_after(" 3 ", now), Some(3));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now), Some(30));
+        // A date already passed means go now
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now), Some(0));
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+    
+    #[tokio::test]
+    async fn test_rate_limited_read_is_retried() {
+        let account = r#"{"id": "GTEST", "sequence": "1", "balances": [], "subentry_count": 0}"#;
+        let (client, served) = mock_horizon(vec![too_many_requests("1"), ok(account)]).await;
+        
+        let started = std::time::Instant::now();
+        let loaded = client.load_account("GTEST").await.unwrap();
+        
+        assert_eq!(loaded.id, "GTEST");
+        assert_eq!(served.load(Ordering::SeqCst), 2);
+        assert!(started.elapsed() >= Duration::from_secs(1));
+    }
+    
+    #[tokio::test]
+    async fn test_rate_limit_surfaces_retry_after() {
+        // Too long to sit out on a read
+        let (client, served) = mock_horizon(vec![too_many_requests("42")]).await;
+        let error = client.account_exists("GTEST").await.unwrap_err();
+        assert!(matches!(error, PaymentError::RateLimited { retry_after_secs: 42 }));
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(42)));
+        assert_eq!(served.load(Ordering::SeqCst), 1);
+        
+        // Submissions are never retried
+        let (client, served) = mock_horizon(vec![too_many_requests("1")]).await;
+        let error = client.submit_transaction("AAAA").await.unwrap_err();
+        assert!(matches!(error, PaymentError::RateLimited { retry_after_secs: 1 }));
+        assert_eq!(error.to_string(), "Rate limited - try again in 1s");
+        assert_eq!(served.load(Ordering::SeqCst), 1);
+    }
     
     #[test]
     fn test_balance_is_native() {
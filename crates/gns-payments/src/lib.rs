@@ -26,7 +26,7 @@ pub use config::{StellarConfig, Network};
 pub use strkey::{gns_to_stellar, stellar_to_gns, encode_stellar_public_key, decode_stellar_public_key};
 pub use horizon::{HorizonClient, AccountInfo, Balance, ClaimableBalance};
 pub use transaction::{TransactionBuilder, TransactionResult};
-pub use stellar_client::{StellarClient, SendResult, AirdropResult, WalletBalance};
+pub use stellar_client::{StellarClient, SendResult, AirdropResult, MergeResult, WalletBalance};
 pub use error::PaymentError;
 
 /// Re-export for convenience
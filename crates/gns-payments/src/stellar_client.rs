@@ -13,9 +13,9 @@
 
 use crate::config::StellarConfig;
 use crate::error::PaymentError;
-use crate::horizon::{HorizonClient, ClaimableBalance};
-use crate::strkey::{gns_to_stellar, stellar_to_gns};
-use crate::transaction::{TransactionBuilder};
+use crate::horizon::{AccountInfo, HorizonClient, ClaimableBalance};
+use crate::strkey::{decode_stellar_public_key, gns_to_stellar, stellar_to_gns};
+use crate::transaction::{format_stroops, parse_stroops, TransactionBuilder};
 use crate::Result;
 use ed25519_dalek::Keypair;
 use serde::{Deserialize, Serialize};
@@ -44,6 +44,17 @@ pub struct AirdropResult {
     pub error: Option<String>,
 }
 
+/// Result of an account merge
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeResult {
+    pub success: bool,
+    pub tx_hash: Option<String>,
+    pub explorer_url: Option<String>,
+    /// XLM moved to the destination (balance minus the transaction fee)
+    pub recovered_xlm: Option<String>,
+    pub error: Option<String>,
+}
+
 /// Wallet balance summary
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WalletBalance {
@@ -388,30 +399,104 @@ impl StellarClient {
     
     // ==================== Trustline Operations ====================
     
-    /// Create GNS trustline for an account
+    /// Create GNS trustline for an account, or change its limit.
+    ///
+    /// With no `limit` the trustline is unlimited and an existing trustline
+    /// is left as is. A `limit` must be positive and cover the current
+    /// balance; use `remove_gns_trustline` to drop the trustline entirely.
     pub async fn create_gns_trustline(
         &self,
         gns_key: &str,
         secret_bytes: &[u8; 32],
+        limit: Option<&str>,
     ) -> Result<SendResult> {
         let address = gns_to_stellar(gns_key)?;
         
         // Load account
         let account = self.horizon.load_account(&address).await?;
+        let existing = account
+            .balances
+            .iter()
+            .find(|b| b.matches_asset(&self.config.gns_asset_code, &self.config.gns_issuer));
+        
+        match (limit, existing) {
+            // Already trusted and no limit requested
+            (None, Some(_)) => {
+                return Ok(SendResult {
+                    success: true,
+                    tx_hash: None,
+                    explorer_url: None,
+                    error: None,
+                });
+            }
+            (Some(limit), existing) => {
+                let limit_stroops = parse_stroops(limit)?;
+                if limit_stroops == 0 {
+                    return Err(PaymentError::InvalidTransaction(
+                        "Trustline limit must be greater than zero (use remove_gns_trustline to remove it)".to_string()
+                    ));
+                }
+                if let Some(balance) = existing {
+                    if parse_stroops(&balance.balance)? > limit_stroops {
+                        return Err(PaymentError::TrustlineLimitTooLow {
+                            limit: limit.to_string(),
+                            balance: balance.balance.clone(),
+                        });
+                    }
+                }
+            }
+            (None, None) => {}
+        }
+        
+        self.submit_gns_trust(&account, limit, secret_bytes).await
+    }
+    
+    /// Remove the GNS trustline by setting its limit to zero.
+    ///
+    /// Stellar only accepts this for an empty trustline, so a non-zero
+    /// balance is rejected up front with `TrustlineNotEmpty`.
+    pub async fn remove_gns_trustline(
+        &self,
+        gns_key: &str,
+        secret_bytes: &[u8; 32],
+    ) -> Result<SendResult> {
+        let address = gns_to_stellar(gns_key)?;
+        let account = self.horizon.load_account(&address).await?;
         
-        // Check if already has trustline
-        if account.has_trustline(&self.config.gns_asset_code, &self.config.gns_issuer) {
+        let existing = account
+            .balances
+            .iter()
+            .find(|b| b.matches_asset(&self.config.gns_asset_code, &self.config.gns_issuer));
+        
+        let Some(balance) = existing else {
+            // Nothing to remove
             return Ok(SendResult {
                 success: true,
                 tx_hash: None,
                 explorer_url: None,
                 error: None,
             });
+        };
+        
+        if parse_stroops(&balance.balance)? != 0 {
+            return Err(PaymentError::TrustlineNotEmpty {
+                asset_code: self.config.gns_asset_code.clone(),
+                balance: balance.balance.clone(),
+            });
         }
         
+        self.submit_gns_trust(&account, Some("0"), secret_bytes).await
+    }
+    
+    async fn submit_gns_trust(
+        &self,
+        account: &AccountInfo,
+        limit: Option<&str>,
+        secret_bytes: &[u8; 32],
+    ) -> Result<SendResult> {
         // Build transaction
-        let builder = TransactionBuilder::new(&self.config, &account)
-            .trust_gns();
+        let builder = TransactionBuilder::new(&self.config, account)
+            .trust_gns(limit);
         
         let unsigned = builder.build()?;
         let signed = unsigned.sign(secret_bytes)?;
@@ -419,7 +504,7 @@ impl StellarClient {
         // Submit
         match self.horizon.submit_transaction(&signed.envelope_xdr).await {
             Ok(response) => {
-                info!("GNS trustline created for: {}", address);
+                info!("GNS trustline updated for {} (limit: {})", account.id, limit.unwrap_or("max"));
                 Ok(SendResult {
                     success: true,
                     tx_hash: Some(response.hash.clone()),
@@ -428,7 +513,7 @@ impl StellarClient {
                 })
             }
             Err(e) => {
-                warn!("Trustline creation failed: {:?}", e);
+                warn!("Trustline change failed: {:?}", e);
                 Ok(SendResult {
                     success: false,
                     tx_hash: None,
@@ -439,6 +524,110 @@ impl StellarClient {
         }
     }
     
+    // ==================== Account Merge ====================
+    
+    /// Merge a GNS identity's Stellar account into `destination`, recovering
+    /// the XLM locked in its base reserve.
+    ///
+    /// Empty trustlines are removed in the same transaction. Trustlines that
+    /// still hold a balance, and any other subentries (offers, data entries,
+    /// extra signers), must be cleared by the user first and are reported in
+    /// `MergeBlocked`. The source account is deleted, so `confirmed` must be
+    /// set explicitly.
+    pub async fn merge_account(
+        &self,
+        source_gns_key: &str,
+        secret_bytes: &[u8; 32],
+        destination: &str,
+        confirmed: bool,
+    ) -> Result<MergeResult> {
+        if !confirmed {
+            return Err(PaymentError::MergeNotConfirmed);
+        }
+        
+        let source_address = gns_to_stellar(source_gns_key)?;
+        decode_stellar_public_key(destination)?;
+        
+        if destination == source_address {
+            return Err(PaymentError::InvalidTransaction(
+                "Cannot merge an account into itself".to_string()
+            ));
+        }
+        
+        // Merging into a missing account fails on-chain after the fee is spent
+        if !self.horizon.account_exists(destination).await? {
+            return Err(PaymentError::AccountNotFound(destination.to_string()));
+        }
+        
+        let account = self.horizon.load_account(&source_address).await?;
+        let trustlines: Vec<_> = account.balances.iter().filter(|b| !b.is_native()).collect();
+        
+        let mut blockers = Vec::new();
+        for trustline in &trustlines {
+            if parse_stroops(&trustline.balance)? != 0 {
+                blockers.push(format!(
+                    "{} trustline holding {}",
+                    trustline.asset_code, trustline.balance
+                ));
+            }
+        }
+        
+        let other_subentries = (account.subentry_count as usize).saturating_sub(trustlines.len());
+        if other_subentries > 0 {
+            blockers.push(format!(
+                "{} other subentries (offers, data entries or signers)",
+                other_subentries
+            ));
+        }
+        
+        if !blockers.is_empty() {
+            return Err(PaymentError::MergeBlocked { blockers });
+        }
+        
+        // Drop the empty trustlines, then merge
+        let mut builder = TransactionBuilder::new(&self.config, &account);
+        for trustline in &trustlines {
+            builder = builder.change_trust(&trustline.asset_code, &trustline.asset_issuer, Some("0"));
+        }
+        builder = builder.account_merge(destination);
+        
+        let fee = self.config.base_fee as i64 * (trustlines.len() as i64 + 1);
+        let xlm = account
+            .balances
+            .iter()
+            .find(|b| b.is_native())
+            .map(|b| parse_stroops(&b.balance))
+            .transpose()?
+            .unwrap_or(0);
+        let recovered = format_stroops((xlm - fee).max(0));
+        
+        let unsigned = builder.build()?;
+        let signed = unsigned.sign(secret_bytes)?;
+        
+        match self.horizon.submit_transaction(&signed.envelope_xdr).await {
+            Ok(response) => {
+                info!("Merged {} into {} ({} XLM)", source_address, destination, recovered);
+                Ok(MergeResult {
+                    success: true,
+                    tx_hash: Some(response.hash.clone()),
+                    explorer_url: Some(self.config.explorer_tx_url(&response.hash)),
+                    recovered_xlm: Some(recovered),
+                    error: None,
+                })
+            }
+            Err(e) => {
+                warn!("Account merge failed: {:?}", e);
+                Ok(MergeResult {
+                    success: false,
+                    tx_hash: None,
+                    explorer_url: None,
+                    recovered_xlm: None,
+                    error: Some(e.to_string()),
+                })
+            }
+        }
+    }
+    
     // ==================== Claimable Balance Operations ====================
     
     /// Create a claimable GNS balance for a recipient
@@ -808,4 +997,15 @@ mod tests {
         
         assert_eq!(gns_key, back);
     }
+    
+    #[tokio::test]
+    async fn test_merge_requires_confirmation() {
+        let client = StellarClient::testnet();
+        let gns_key = "0000000000000000000000000000000000000000000000000000000000000000";
+        let destination = client.gns_to_stellar(&"11".repeat(32)).unwrap();
+        
+        // Rejected before any network access
+        let result = client.merge_account(gns_key, &[0u8; 32], &destination, false).await;
+        assert!(matches!(result, Err(PaymentError::MergeNotConfirmed)));
+    }
 }
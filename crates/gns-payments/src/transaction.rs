@@ -22,6 +22,51 @@ use ed25519_dalek::{Keypair, Signer};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
+// ============================================================================
+// AMOUNTS
+// ============================================================================
+
+/// Parse a decimal amount string into stroops (7 decimal places).
+///
+/// Parsed exactly rather than through f64, so limits near the maximum
+/// encode without rounding.
+pub fn parse_stroops(amount: &str) -> Result<i64> {
+    let invalid = || PaymentError::InvalidTransaction(format!("Invalid amount: {}", amount));
+
+    let (whole, frac) = match amount.trim().split_once('.') {
+        Some((w, f)) => (w, f),
+        None => (amount.trim(), ""),
+    };
+
+    if whole.is_empty() && frac.is_empty() {
+        return Err(invalid());
+    }
+    if !whole.chars().all(|c| c.is_ascii_digit()) || !frac.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    if frac.len() > 7 {
+        return Err(PaymentError::InvalidTransaction(format!(
+            "Amount {} has more than 7 decimal places",
+            amount
+        )));
+    }
+
+    let whole: i64 = if whole.is_empty() { 0 } else { whole.parse().map_err(|_| invalid())? };
+    let frac: i64 = format!("{:0<7}", frac).parse().map_err(|_| invalid())?;
+
+    whole
+        .checked_mul(10_000_000)
+        .and_then(|w| w.checked_add(frac))
+        .ok_or_else(invalid)
+}
+
+/// Format stroops as a decimal amount string (7 decimal places)
+pub fn format_stroops(stroops: i64) -> String {
+    let sign = if stroops < 0 { "-" } else { "" };
+    let abs = stroops.unsigned_abs();
+    format!("{}{}.{:07}", sign, abs / 10_000_000, abs % 10_000_000)
+}
+
 // ============================================================================
 // TRANSACTION TYPES
 // ============================================================================
@@ -59,6 +104,11 @@ pub enum Operation {
     ClaimClaimableBalance {
         balance_id: String,
     },
+    
+    /// Merge the source account into another, deleting it
+    AccountMerge {
+        destination: String,
+    },
 }
 
 /// Stellar asset
@@ -260,13 +310,12 @@ impl TransactionBuilder {
     }
     
     /// Add GNS trustline operation
-    pub fn trust_gns(self) -> Self {
+    ///
+    /// `None` trusts up to the maximum; `Some("0")` removes the trustline.
+    pub fn trust_gns(self, limit: Option<&str>) -> Self {
         let asset_code = self.config.gns_asset_code.clone();
         let issuer = self.config.gns_issuer.clone();
-        self.add_operation(Operation::ChangeTrust {
-            asset: Asset::credit(&asset_code, &issuer),
-            limit: None,
-        })
+        self.change_trust(&asset_code, &issuer, limit)
     }
     
     /// Add create claimable balance operation
@@ -317,6 +366,13 @@ impl TransactionBuilder {
         })
     }
     
+    /// Add account merge operation (transfers all XLM and deletes the source)
+    pub fn account_merge(self, destination: &str) -> Self {
+        self.add_operation(Operation::AccountMerge {
+            destination: destination.to_string(),
+        })
+    }
+    
     /// Build the transaction (returns XDR envelope ready for signing)
     pub fn build(self) -> Result<UnsignedTransaction> {
         if self.operations.is_empty() {
@@ -517,9 +573,11 @@ impl UnsignedTransaction {
                 xdr.extend_from_slice(&[0, 0, 0, 6]);
                 // ChangeTrustAsset (same as Asset for our purposes)
                 self.write_change_trust_asset(xdr, asset)?;
-                // Limit (max if not specified)
-                let limit_value = limit.as_deref().unwrap_or("922337203685.4775807");
-                self.write_int64(xdr, limit_value)?;
+                // Limit (i64::MAX stroops, the most Stellar allows, if not specified)
+                match limit {
+                    Some(limit) => self.write_int64(xdr, limit)?,
+                    None => xdr.extend_from_slice(&i64::MAX.to_be_bytes()),
+                }
             }
             
             Operation::CreateClaimableBalance { asset, amount, claimants } => {
@@ -541,6 +599,12 @@ impl UnsignedTransaction {
                 // Balance ID is a ClaimableBalanceID
                 self.write_claimable_balance_id(xdr, balance_id)?;
             }
+            
+            Operation::AccountMerge { destination } => {
+                // ACCOUNT_MERGE = 8 (body is just the destination)
+                xdr.extend_from_slice(&[0, 0, 0, 8]);
+                self.write_muxed_account(xdr, destination)?;
+            }
         }
         
         Ok(())
@@ -657,10 +721,7 @@ impl UnsignedTransaction {
     }
     
     fn write_int64(&self, xdr: &mut Vec<u8>, amount: &str) -> Result<()> {
-        // Parse amount string to stroops (7 decimal places)
-        let parsed: f64 = amount.parse()
-            .map_err(|_| PaymentError::InvalidTransaction(format!("Invalid amount: {}", amount)))?;
-        let stroops = (parsed * 10_000_000.0) as i64;
+        let stroops = parse_stroops(amount)?;
         xdr.extend_from_slice(&stroops.to_be_bytes());
         Ok(())
     }
@@ -743,4 +804,97 @@ mod tests {
         // Should fail - no operations
         assert!(result.is_err());
     }
+    
+    fn test_account() -> AccountInfo {
+        AccountInfo {
+            id: crate::strkey::gns_to_stellar(&"00".repeat(32)).unwrap(),
+            sequence: "100".to_string(),
+            balances: vec![],
+            subentry_count: 0,
+            thresholds: Default::default(),
+            flags: Default::default(),
+            home_domain: None,
+            inflation_destination: None,
+        }
+    }
+    
+    /// Encode the transaction's only operation and return its trailing limit
+    fn encoded_trust_limit(limit: Option<&str>) -> i64 {
+        let config = StellarConfig::testnet();
+        let tx = TransactionBuilder::new(&config, &test_account())
+            .trust_gns(limit)
+            .build()
+            .unwrap();
+        
+        let mut xdr = Vec::new();
+        tx.write_operation(&mut xdr, &tx.operations[0]).unwrap();
+        
+        // source(4) + type(4) + asset type(4) + code(4) + issuer(36) + limit(8)
+        assert_eq!(xdr.len(), 60);
+        assert_eq!(&xdr[4..8], &[0, 0, 0, 6]);
+        i64::from_be_bytes(xdr[52..60].try_into().unwrap())
+    }
+    
+    #[test]
+    fn test_trust_limit_encoding() {
+        assert_eq!(encoded_trust_limit(None), i64::MAX);
+        assert_eq!(encoded_trust_limit(Some("1000")), 10_000_000_000);
+        assert_eq!(encoded_trust_limit(Some("0.0000001")), 1);
+        assert_eq!(encoded_trust_limit(Some("0")), 0);
+    }
+    
+    #[test]
+    fn test_account_merge_encoding() {
+        let config = StellarConfig::testnet();
+        let destination_key = [7u8; 32];
+        let destination = crate::strkey::encode_stellar_public_key(&destination_key).unwrap();
+        
+        let tx = TransactionBuilder::new(&config, &test_account())
+            .account_merge(&destination)
+            .build()
+            .unwrap();
+        
+        let mut xdr = Vec::new();
+        tx.write_operation(&mut xdr, &tx.operations[0]).unwrap();
+        
+        // source(4) + type(4) + muxed account type(4) + ed25519 key(32)
+        assert_eq!(xdr.len(), 44);
+        assert_eq!(&xdr[0..4], &[0, 0, 0, 0]);
+        assert_eq!(&xdr[4..8], &[0, 0, 0, 8]);
+        assert_eq!(&xdr[8..12], &[0, 0, 0, 0]);
+        assert_eq!(&xdr[12..44], &destination_key);
+    }
+    
+    #[test]
+    fn test_account_merge_rejects_bad_destination() {
+        let config = StellarConfig::testnet();
+        let tx = TransactionBuilder::new(&config, &test_account())
+            .account_merge("GNOTANADDRESS")
+            .build()
+            .unwrap();
+        
+        let mut xdr = Vec::new();
+        assert!(tx.write_operation(&mut xdr, &tx.operations[0]).is_err());
+    }
+    
+    #[test]
+    fn test_format_stroops() {
+        assert_eq!(format_stroops(15_000_000), "1.5000000");
+        assert_eq!(format_stroops(1), "0.0000001");
+        assert_eq!(format_stroops(-25_000_000), "-2.5000000");
+        assert_eq!(parse_stroops(&format_stroops(i64::MAX)).unwrap(), i64::MAX);
+    }
+    
+    #[test]
+    fn test_parse_stroops() {
+        assert_eq!(parse_stroops("922337203685.4775807").unwrap(), i64::MAX);
+        assert_eq!(parse_stroops("12.5").unwrap(), 125_000_000);
+        assert_eq!(parse_stroops(".5").unwrap(), 5_000_000);
+        
+        assert!(parse_stroops("922337203685.4775808").is_err());
+        assert!(parse_stroops("1.00000001").is_err());
+        assert!(parse_stroops("-1").is_err());
+        assert!(parse_stroops("abc").is_err());
+        assert!(parse_stroops(".").is_err());
+    }
 }
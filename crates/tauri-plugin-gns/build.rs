@@ -12,6 +12,7 @@ const COMMANDS: &[&str] = &[
     "import_identity",
     "get_public_key",
     "sign_message",
+    "sign_typed_data",
     "verify_signature",
     "set_default_identity",
     // Messaging commands
@@ -22,6 +23,8 @@ const COMMANDS: &[&str] = &[
     "mark_as_read",
     "delete_message",
     "get_conversations",
+    "get_inbox_status",
+    "upload_attachment",
     // Resolution commands
     "resolve_handle",
     "resolve_identity",
@@ -30,10 +33,16 @@ const COMMANDS: &[&str] = &[
     "release_handle",
     "get_record",
     "update_record",
+    "refresh_handle_cache",
+    "invalidate_handle",
+    "verify_signature_by_handle",
     // Trust commands
     "get_trust_score",
     "get_trust_details",
     "verify_identity",
+    "get_network_trust",
+    // Relay commands
+    "benchmark_relays",
     // Trajectory commands (feature-gated)
     "start_collection",
     "stop_collection",
@@ -41,6 +50,8 @@ const COMMANDS: &[&str] = &[
     "get_breadcrumb_count",
     "publish_epoch",
     "get_epochs",
+    "verify_peer_epoch",
+    "flush_breadcrumbs",
     "add_breadcrumb",
     "verify_chain",
     "get_trajectory_stats",
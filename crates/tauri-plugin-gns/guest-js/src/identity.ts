@@ -12,6 +12,7 @@ import type {
   ExportedIdentity,
   ImportIdentityParams,
   SignatureResult,
+  TypedDataSignature,
   VerifyResult,
 } from './types';
 
@@ -186,6 +187,43 @@ export async function signMessage(
   });
 }
 
+/**
+ * Sign structured data on behalf of an app.
+ * 
+ * The payload is canonicalized and bound to `domain`, so the same data
+ * signed for two apps gives two unrelated signatures. The intent is
+ * recorded in the audit log.
+ * 
+ * @example
+ * ```typescript
+ * const result = await signTypedData(
+ *   'app.example/login',
+ *   'Log in to app.example',
+ *   { nonce: 'f3a1...' }
+ * );
+ * console.log(`Signed bytes: ${result.signedBytes}`);
+ * ```
+ * 
+ * @param domain - App domain the signature is for
+ * @param intentDescription - What the user agreed to (at most 256 characters)
+ * @param payload - JSON-serializable data to sign
+ * @param publicKey - Optional identity to sign with (uses active if not specified)
+ * @returns Typed data signature
+ */
+export async function signTypedData(
+  domain: string,
+  intentDescription: string,
+  payload: unknown,
+  publicKey?: string
+): Promise<TypedDataSignature> {
+  return invoke<TypedDataSignature>('plugin:gns|sign_typed_data', {
+    domain,
+    intentDescription,
+    payloadJson: JSON.stringify(payload),
+    publicKey: publicKey ?? null,
+  });
+}
+
 /**
  * Verify an Ed25519 signature.
  * 
@@ -88,6 +88,8 @@ export const gns = {
     getPublicKey: identity.getPublicKey,
     /** Sign a message */
     sign: identity.signMessage,
+    /** Sign structured data for an app */
+    signTypedData: identity.signTypedData,
     /** Verify a signature */
     verify: identity.verifySignature,
     /** Set default identity */
@@ -114,6 +116,12 @@ export const gns = {
     delete: messaging.deleteMessage,
     /** Get all conversations */
     getConversations: messaging.getConversations,
+    /** Count messages waiting on the relay */
+    inboxStatus: messaging.getInboxStatus,
+    /** Time the configured relays, optionally switching to the fastest */
+    benchmarkRelays: messaging.benchmarkRelays,
+    /** Encrypt and upload a file, resuming if interrupted */
+    uploadAttachment: messaging.uploadAttachment,
     /** Send typing indicator */
     sendTyping: messaging.sendTypingIndicator,
     /** Send read receipt */
@@ -144,6 +152,12 @@ export const gns = {
     getRecord: resolver.getRecord,
     /** Update GNS record */
     updateRecord: resolver.updateRecord,
+    /** Re-resolve all cached handles */
+    refreshCache: resolver.refreshHandleCache,
+    /** Drop one handle from the cache */
+    invalidate: resolver.invalidateHandle,
+    /** Verify a signature against a handle's current key */
+    verifySignature: resolver.verifySignatureByHandle,
     /** Resolve recipient (@handle or public key) */
     resolveRecipient: resolver.resolveRecipient,
     /** Validate handle format */
@@ -160,6 +174,8 @@ export const gns = {
     getScore: trust.getTrustScore,
     /** Get detailed trust breakdown */
     getDetails: trust.getTrustDetails,
+    /** Get the relay's network-wide trust for a handle or key */
+    getNetwork: trust.getNetworkTrust,
     /** Verify identity meets requirements */
     verify: trust.verifyIdentity,
     /** Check if can claim handle */
@@ -204,6 +220,10 @@ export const gns = {
     publishEpoch: trajectory.publishEpoch,
     /** Get epochs */
     getEpochs: trajectory.getEpochs,
+    /** Verify a peer's published epoch */
+    verifyPeerEpoch: trajectory.verifyPeerEpoch,
+    /** Write buffered breadcrumbs to storage */
+    flush: trajectory.flushBreadcrumbs,
     /** Add manual breadcrumb */
     addBreadcrumb: trajectory.addBreadcrumb,
     /** Verify chain integrity */
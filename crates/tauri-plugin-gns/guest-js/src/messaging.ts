@@ -11,6 +11,9 @@ import type {
   Conversation,
   MessageQuery,
   DecryptedPayload,
+  InboxStatus,
+  RelayBenchmark,
+  AttachmentRef,
 } from './types';
 
 /**
@@ -23,6 +26,10 @@ import type {
  * 4. ChaCha20-Poly1305 AEAD encryption
  * 5. Ed25519 signature for authenticity
  * 
+ * If the relay rejects a handle's cached encryption key as outdated, the
+ * handle is re-resolved, a `peer_key_changed` event emitted and the
+ * message sent once more.
+ * 
  * @example
  * ```typescript
  * // Send to @handle
@@ -196,6 +203,70 @@ export async function getConversations(): Promise<Conversation[]> {
   return invoke<Conversation[]>('plugin:gns|get_conversations');
 }
 
+/**
+ * Ask the relay how many messages are waiting for the active identity.
+ * 
+ * @example
+ * ```typescript
+ * const inbox = await getInboxStatus();
+ * if (inbox.status === 'known' && inbox.queued > 0) {
+ *   console.log(`Syncing ${inbox.queued} messages…`);
+ * }
+ * ```
+ * 
+ * @returns The relay's queue, or `unknown` if the relay doesn't report it
+ */
+export async function getInboxStatus(): Promise<InboxStatus> {
+  return invoke<InboxStatus>('plugin:gns|get_inbox_status');
+}
+
+/**
+ * Health-check every configured relay and time the round trips.
+ * 
+ * Results come fastest first, unreachable relays last. With `select`, the
+ * fastest reachable relay is used for the rest of the session. The plugin
+ * already does this at startup and when the relay comes back online.
+ * 
+ * @example
+ * ```typescript
+ * const relays = await benchmarkRelays(true);
+ * const active = relays.find(r => r.active);
+ * console.log(`Using ${active?.url} (${active?.latencyMs} ms)`);
+ * ```
+ */
+export async function benchmarkRelays(select?: boolean): Promise<RelayBenchmark[]> {
+  return invoke<RelayBenchmark[]>('plugin:gns|benchmark_relays', {
+    select: select ?? null,
+  });
+}
+
+/**
+ * Encrypt a file and upload it to the relay in chunks.
+ * 
+ * Progress arrives as `attachment_upload_progress` events carrying an
+ * `UploadProgress`. An interrupted upload is resumed from the last chunk
+ * the relay acknowledged. Send the returned reference inside a message so
+ * the recipient can fetch and decrypt the file.
+ * 
+ * @example
+ * ```typescript
+ * const unlisten = await listen<UploadProgress>('attachment_upload_progress', e => {
+ *   console.log(`${e.payload.uploadedBytes} / ${e.payload.totalBytes}`);
+ * });
+ * const ref = await uploadAttachment(base64Data, 'application/pdf');
+ * unlisten();
+ * ```
+ * 
+ * @param data - File contents, base64 encoded
+ * @param mimeType - MIME type of the file
+ */
+export async function uploadAttachment(data: string, mimeType?: string): Promise<AttachmentRef> {
+  return invoke<AttachmentRef>('plugin:gns|upload_attachment', {
+    data,
+    mimeType: mimeType ?? null,
+  });
+}
+
 /**
  * Send a typing indicator to a peer.
  * 
@@ -5,13 +5,13 @@
  */
 
 import { invoke } from '@tauri-apps/api/core';
-import type { ResolvedHandle, GnsRecord } from './types';
+import type { ResolvedHandle, GnsRecord, HandleCacheRefresh, HandleVerifyResult } from './types';
 
 /**
  * Resolve a @handle to its associated public key and metadata.
  * 
  * Resolution uses local cache when available (TTL configurable).
- * Falls back to network lookup on cache miss.
+ * Falls back to network lookup on cache miss, or always with `force`.
  * 
  * @example
  * ```typescript
@@ -21,12 +21,13 @@ import type { ResolvedHandle, GnsRecord } from './types';
  * ```
  * 
  * @param handle - The handle to resolve (with or without @)
+ * @param force - Skip the cache and always query the network
  * @returns Resolution result with public key and trust info
  */
-export async function resolveHandle(handle: string): Promise<ResolvedHandle> {
+export async function resolveHandle(handle: string, force = false): Promise<ResolvedHandle> {
   // Normalize handle (remove @ if present)
   const normalizedHandle = handle.startsWith('@') ? handle.slice(1) : handle;
-  return invoke<ResolvedHandle>('plugin:gns|resolve_handle', { handle: normalizedHandle });
+  return invoke<ResolvedHandle>('plugin:gns|resolve_handle', { handle: normalizedHandle, force });
 }
 
 /**
@@ -157,6 +158,77 @@ export async function updateRecord(record: GnsRecord): Promise<void> {
   return invoke<void>('plugin:gns|update_record', { record });
 }
 
+/**
+ * Re-resolve every cached handle from the network.
+ * 
+ * Handles that no longer resolve are dropped from the cache. A
+ * `handle_cache_refreshed` event carrying the same counts is emitted.
+ * 
+ * @example
+ * ```typescript
+ * const { updated, removed } = await refreshHandleCache();
+ * ```
+ * 
+ * @returns Refresh counts
+ */
+export async function refreshHandleCache(): Promise<HandleCacheRefresh> {
+  return invoke<HandleCacheRefresh>('plugin:gns|refresh_handle_cache');
+}
+
+/**
+ * Drop a single handle from the cache so the next resolve goes to the network.
+ * 
+ * @param handle - The handle to invalidate (with or without @)
+ * @returns True if the handle was cached
+ */
+export async function invalidateHandle(handle: string): Promise<boolean> {
+  const normalizedHandle = handle.startsWith('@') ? handle.slice(1) : handle;
+  return invoke<boolean>('plugin:gns|invalidate_handle', { handle: normalizedHandle });
+}
+
+/**
+ * Verify a signature from whoever currently holds a handle.
+ * 
+ * Rejects with `GNS_HANDLE_NOT_FOUND` if the handle does not resolve.
+ * Check `publicKey` (or pass `expectedPublicKey`) to confirm which key
+ * the signature was checked against. Signatures made before a key
+ * rotation still verify, with `signedByPreviousKey` set.
+ * 
+ * @example
+ * ```typescript
+ * const result = await verifySignatureByHandle('@alice', statement, signature);
+ * if (result.valid) {
+ *   console.log(`Signed by @alice (${result.publicKey})`);
+ * }
+ * ```
+ * 
+ * @param handle - Signer's handle (with or without @)
+ * @param message - Original message
+ * @param signature - Signature to verify
+ * @param expectedPublicKey - Only accept the signature if the handle resolves to this key
+ * @param force - Skip the cache and resolve from the network
+ * @returns Verification result
+ */
+export async function verifySignatureByHandle(
+  handle: string,
+  message: string | Uint8Array,
+  signature: string,
+  expectedPublicKey?: string,
+  force = false
+): Promise<HandleVerifyResult> {
+  const normalizedHandle = handle.startsWith('@') ? handle.slice(1) : handle;
+  const messageStr = typeof message === 'string'
+    ? message
+    : new TextDecoder().decode(message);
+  return invoke<HandleVerifyResult>('plugin:gns|verify_signature_by_handle', {
+    handle: normalizedHandle,
+    message: messageStr,
+    signature,
+    expectedPublicKey,
+    force,
+  });
+}
+
 /**
  * Resolve a recipient string to a public key.
  * 
@@ -17,6 +17,7 @@ import type {
   BreadcrumbQuery,
   EpochHeader,
   CollectionStatus,
+  PeerEpochVerification,
 } from './types';
 
 /**
@@ -110,6 +111,10 @@ export async function getBreadcrumbCount(publicKey?: string): Promise<number> {
  * 3. The epoch is signed with the identity's key
  * 4. The epoch is published to the network
  * 
+ * With `autoPublishEpochEvery` or `autoPublishEpochWindow` configured this
+ * also happens in the background, emitting `epoch_published` with the
+ * header. A breadcrumb only ever goes into one epoch either way.
+ * 
  * @example
  * ```typescript
  * const epoch = await publishEpoch();
@@ -140,6 +145,44 @@ export async function getEpochs(publicKey?: string): Promise<EpochHeader[]> {
   return invoke<EpochHeader[]>('plugin:gns|get_epochs', { publicKey: publicKey ?? null });
 }
 
+/**
+ * Confirm a peer's claimed trajectory milestone without trusting the relay.
+ * 
+ * Checks that the breadcrumb is signed by the peer and that a Merkle proof
+ * links it to the root of an epoch header the peer signed.
+ * 
+ * @example
+ * ```typescript
+ * const result = await verifyPeerEpoch(peerKey, 3, breadcrumb);
+ * if (!result.verified) console.warn(result.reason);
+ * ```
+ * 
+ * @param publicKey - The peer's identity
+ * @param epochIndex - Epoch the breadcrumb is claimed to be in
+ * @param breadcrumb - The breadcrumb the peer shared
+ * @returns Verification result with the peer's epoch header
+ */
+export async function verifyPeerEpoch(
+  publicKey: string,
+  epochIndex: number,
+  breadcrumb: Breadcrumb
+): Promise<PeerEpochVerification> {
+  return invoke<PeerEpochVerification>('plugin:gns|verify_peer_epoch', { publicKey, epochIndex, breadcrumb });
+}
+
+/**
+ * Write buffered breadcrumbs to storage now.
+ * 
+ * Collected breadcrumbs are saved in batches. `publishEpoch` flushes on its
+ * own; call this before reading breadcrumbs back or before the app is
+ * suspended.
+ * 
+ * @returns Number of breadcrumbs written
+ */
+export async function flushBreadcrumbs(): Promise<number> {
+  return invoke<number>('plugin:gns|flush_breadcrumbs');
+}
+
 /**
  * Manually add a breadcrumb at the current location.
  * 
@@ -11,6 +11,7 @@ import type {
   TrustVerification,
   TrustRequirements,
   TrustComponents,
+  NetworkTrustReport,
 } from './types';
 
 /**
@@ -84,6 +85,31 @@ export async function verifyIdentity(
   });
 }
 
+/**
+ * Get the network-wide trust for a handle or public key.
+ * 
+ * The relay's aggregate figures are checked against the identity's own
+ * signed record and returned next to the local estimate, so profile views
+ * can show the network's numbers and flag any disagreement. Cached for
+ * the handle cache TTL unless `force` is set.
+ * 
+ * @example
+ * ```typescript
+ * const report = await getNetworkTrust('@alice');
+ * console.log(`Network: ${report.network.trustScore}, local: ${report.local?.trustScore}`);
+ * if (!report.consistentWithRecord) console.warn(report.discrepancies);
+ * ```
+ */
+export async function getNetworkTrust(
+  handleOrKey: string,
+  force?: boolean
+): Promise<NetworkTrustReport> {
+  return invoke<NetworkTrustReport>('plugin:gns|get_network_trust', {
+    handleOrKey,
+    force: force ?? null,
+  });
+}
+
 /**
  * Get the display name for a trust tier.
  * 
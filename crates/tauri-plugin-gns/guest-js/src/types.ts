@@ -83,6 +83,16 @@ export interface SignatureResult {
   message: string;
 }
 
+/** Result of signing structured data for an app */
+export interface TypedDataSignature {
+  /** Public key that signed */
+  publicKey: string;
+  /** Ed25519 signature in hex format (128 characters) */
+  signature: string;
+  /** Exact bytes signed, domain tag included, in hex */
+  signedBytes: string;
+}
+
 /** Result of signature verification */
 export interface VerifyResult {
   /** Whether the signature is valid */
@@ -91,6 +101,24 @@ export interface VerifyResult {
   publicKey: string;
 }
 
+/** Result of verifying a signature against a handle's current key */
+export interface HandleVerifyResult {
+  /** Whether the signature is valid (and the key matches `expectedPublicKey`, if given) */
+  valid: boolean;
+  /** Handle that was resolved */
+  handle: string;
+  /** Public key used for verification */
+  publicKey: string;
+  /** Whether the key came from the local cache */
+  fromCache: boolean;
+  /** The cached key failed and the handle now resolves to a different key */
+  keyRotated: boolean;
+  /** The signature was made by one of the handle's earlier keys */
+  signedByPreviousKey: boolean;
+  /** Whether `expectedPublicKey` is the resolved key or an earlier one, if given */
+  matchesExpected: boolean | null;
+}
+
 // ============================================================================
 // Messaging Types
 // ============================================================================
@@ -163,6 +191,52 @@ export interface Conversation {
   updatedAt: string;
 }
 
+/** Envelopes waiting on the relay for the active identity */
+export type InboxStatus =
+  | {
+      status: 'known';
+      /** Undelivered envelopes */
+      queued: number;
+      /** ISO timestamp of the oldest undelivered envelope */
+      oldestAt: string | null;
+    }
+  /** The relay doesn't report its queue */
+  | { status: 'unknown' };
+
+/** One relay's result from a benchmark round */
+export interface RelayBenchmark {
+  url: string;
+  /** Whether the relay answered its health check */
+  reachable: boolean;
+  /** Health check round trip, if it answered */
+  latencyMs: number | null;
+  /** Whether this is the relay in use */
+  active: boolean;
+}
+
+/** What a message carries to let the recipient fetch and decrypt a file */
+export interface AttachmentRef {
+  /** Blob id the relay assigned */
+  blobId: string;
+  /** Attachment key (hex); only ever send it inside an encrypted message */
+  key: string;
+  /** Nonce the blob was encrypted with (hex) */
+  nonce: string;
+  /** Size of the encrypted blob in bytes */
+  size: number;
+  /** SHA-256 of the encrypted blob (hex) */
+  sha256: string;
+  mimeType: string | null;
+}
+
+/** Payload of the `attachment_upload_progress` event */
+export interface UploadProgress {
+  uploadId: string;
+  /** Bytes the relay has acknowledged */
+  uploadedBytes: number;
+  totalBytes: number;
+}
+
 /** Query parameters for fetching messages */
 export interface MessageQuery {
   /** Filter by peer public key */
@@ -265,6 +339,31 @@ export interface ResolvedHandle {
   fromCache: boolean;
   /** ISO timestamp of resolution */
   resolvedAt: string;
+  /** Keys held before `publicKey`, most recent first */
+  previousKeys: string[];
+}
+
+/** Counts from re-resolving the handle cache */
+export interface HandleCacheRefresh {
+  /** Entries that were cached */
+  total: number;
+  /** Entries refreshed from the network */
+  updated: number;
+  /** Entries dropped because the handle no longer resolves */
+  removed: number;
+  /** Entries kept because the lookup failed */
+  failed: number;
+}
+
+/** Payload of `peer_key_changed`, emitted when a send finds a handle's cached encryption key stale */
+export interface PeerKeyChanged {
+  handle: string;
+  publicKey: string;
+  /** The key messages were being sealed to */
+  previousEncryptionKey: string | null;
+  encryptionKey: string | null;
+  /** How many times this peer's key has changed since the app started */
+  generation: number;
 }
 
 // ============================================================================
@@ -305,12 +404,30 @@ export interface TrustScore {
   calculatedAt: string;
   /** Total breadcrumbs */
   breadcrumbCount: number;
+  /** Breadcrumbs after source weighting; what trust requirements count */
+  weightedBreadcrumbCount: number;
   /** Account age in days */
   accountAgeDays: number;
   /** Unique H3 cells visited */
   uniqueLocations: number;
   /** Published epoch count */
   epochCount: number;
+  /** Contribution of each location source */
+  sources: SourceContribution[];
+}
+
+/** Breadcrumbs from one location source and the credit they earned */
+export interface SourceContribution {
+  /** Location source */
+  source: BreadcrumbSource;
+  /** Breadcrumbs collected from this source */
+  breadcrumbCount: number;
+  /** Distinct H3 cells seen from this source */
+  uniqueLocations: number;
+  /** Configured weight (0-1) */
+  weight: number;
+  /** Breadcrumbs counted toward trust after weighting */
+  weightedBreadcrumbs: number;
 }
 
 /** A single verification check */
@@ -341,6 +458,43 @@ export interface TrustVerification {
   verifiedAt: string;
 }
 
+/** The relay's network-wide trust figures for an identity */
+export interface NetworkTrust {
+  publicKey: string;
+  /** Trust score (0-100) as the relay computes it */
+  trustScore: number;
+  /** Breadcrumbs the relay has seen published */
+  breadcrumbCount: number;
+  /** Epochs the relay has accepted */
+  epochCount: number;
+  /** Whether the relay considers the identity verified */
+  verified: boolean;
+  /** Network-wide rank, if the relay ranks identities */
+  rank: number | null;
+  /** ISO timestamp of the lookup */
+  fetchedAt: string;
+}
+
+/** What this device can tell about an identity's trust on its own */
+export interface LocalTrustEstimate {
+  trustScore: number;
+  breadcrumbCount: number;
+  /** `computed` for this device's own identities, else from the last handle resolution */
+  source: 'computed' | 'handle_cache';
+}
+
+/** Network trust checked against the identity's signed record */
+export interface NetworkTrustReport {
+  network: NetworkTrust;
+  /** Whether the relay's figures agree with the signed record */
+  consistentWithRecord: boolean;
+  /** What didn't agree, if anything */
+  discrepancies: string[];
+  local: LocalTrustEstimate | null;
+  /** Whether the network figures came from cache */
+  fromCache: boolean;
+}
+
 /** Requirements for trust verification */
 export interface TrustRequirements {
   /** Minimum trust score (0-100) */
@@ -430,6 +584,16 @@ export interface EpochHeader {
   epochHash: string;
 }
 
+/** Result of checking a peer's published epoch */
+export interface PeerEpochVerification {
+  /** Whether the breadcrumb is signed by the peer and included in the epoch */
+  verified: boolean;
+  /** Epoch header returned by the relay */
+  epoch: EpochHeader;
+  /** First check that failed */
+  reason?: string;
+}
+
 /** Current breadcrumb collection status */
 export interface CollectionStatus {
   /** Whether collection is active */
@@ -476,14 +640,22 @@ export interface GnsConfig {
   encryptStorage: boolean;
   /** Message fetch limit */
   messageLimit: number;
+  /** Largest message payload in bytes (default 65536) */
+  maxMessageBytes: number;
   /** Cache TTL in seconds */
   cacheTtlSeconds: number;
   /** Minimum trust score for handle claim */
   minTrustScoreForHandle: number;
   /** Minimum breadcrumbs for handle claim */
   minBreadcrumbsForHandle: number;
+  /** Trust weight (0-1) per breadcrumb source; omitted sources keep their default */
+  breadcrumbSourceWeights: Partial<Record<BreadcrumbSource, number>>;
   /** Minimum breadcrumbs per epoch */
   minBreadcrumbsForEpoch: number;
+  /** Publish an epoch automatically once this many breadcrumbs are unpublished (0 = off) */
+  autoPublishEpochEvery: number;
+  /** Also publish once the oldest unpublished breadcrumb is this many seconds old (0 = off) */
+  autoPublishEpochWindow: number;
   /** H3 resolution for breadcrumbs (0-15) */
   h3Resolution: number;
   /** Breadcrumb collection interval in seconds */
@@ -504,6 +676,7 @@ export type GnsErrorCode =
   | 'GNS_HANDLE_UNAVAILABLE'
   | 'GNS_INVALID_HANDLE'
   | 'GNS_DECRYPTION_FAILED'
+  | 'GNS_KEY_MISMATCH'
   | 'GNS_INVALID_SIGNATURE'
   | 'GNS_INSUFFICIENT_TRUST'
   | 'GNS_INSUFFICIENT_BREADCRUMBS'
@@ -512,7 +685,8 @@ export type GnsErrorCode =
   | 'GNS_PERMISSION_DENIED'
   | 'GNS_INVALID_INPUT'
   | 'GNS_TIMEOUT'
-  | 'GNS_INTERNAL';
+  | 'GNS_INTERNAL'
+  | 'GNS_MESSAGE_TOO_LARGE';
 
 /** Structured error from plugin */
 export interface GnsError {
@@ -90,6 +90,37 @@ fn decrypt_secret_key(encrypted_hex: &str, salt_hex: &str, passphrase: &str) ->
         .map_err(|e| Error::Crypto(format!("Invalid decrypted key: {}", e)))
 }
 
+/// Keys recovered from an imported secret key
+struct ImportedKeys {
+    public_key: String,
+    enc_secret: String,
+    enc_public: String,
+}
+
+/// Derive an imported identity's keys from its secret key, rejecting it
+/// unless it belongs to `expected_public_key`.
+///
+/// The X25519 key is always re-derived rather than taken from the export,
+/// so it matches what `create_identity` would have stored.
+fn derive_imported_keys(secret_key: &str, expected_public_key: &str) -> Result<ImportedKeys> {
+    let public_key = CryptoEngine::public_key_from_secret(secret_key)
+        .map_err(|e| Error::InvalidInput(format!("Invalid secret key in import: {}", e)))?;
+    if !public_key.eq_ignore_ascii_case(expected_public_key.trim()) {
+        return Err(Error::InvalidInput(format!(
+            "Secret key does not match public key {} - import may be corrupted",
+            expected_public_key
+        )));
+    }
+
+    let (enc_secret, enc_public) = CryptoEngine::derive_encryption_key(secret_key)?;
+
+    Ok(ImportedKeys {
+        public_key,
+        enc_secret,
+        enc_public,
+    })
+}
+
 /// Create a new GNS identity
 ///
 /// Generates an Ed25519 keypair and derives the X25519 encryption key.
@@ -258,26 +289,18 @@ pub async fn import_identity(
     };
 
     // SECURITY: Verify the secret key produces the expected public key
-    let derived_public = CryptoEngine::public_key_from_secret(&secret_key)?;
-    if derived_public != export.public_key {
-        return Err(Error::Crypto(
-            "Secret key does not match public key - import may be corrupted".into()
-        ));
-    }
-
-    // Derive encryption key
-    let (enc_secret, enc_public) = CryptoEngine::derive_encryption_key(&secret_key)?;
+    let keys = derive_imported_keys(&secret_key, &export.public_key)?;
 
     let name = params.new_name.unwrap_or(export.name);
 
     let storage = state.storage.write().await;
-    storage.save_identity(&export.public_key, &secret_key, &enc_secret, &enc_public, &name)?;
+    storage.save_identity(&keys.public_key, &secret_key, &keys.enc_secret, &keys.enc_public, &name)?;
 
     Ok(Identity {
-        public_key: export.public_key,
+        public_key: keys.public_key,
         name,
         handle: export.handle,
-        encryption_key: enc_public,
+        encryption_key: keys.enc_public,
         created_at: chrono::Utc::now().to_rfc3339(),
         is_default: false,
         trust_score: 0.0,
@@ -318,6 +341,65 @@ pub async fn sign_message(
     })
 }
 
+/// Longest intent description `sign_typed_data` accepts
+const MAX_INTENT_LEN: usize = 256;
+
+/// Sign a JSON payload on behalf of `domain` (e.g. "app.example/login").
+/// The payload is canonicalized and prefixed with the domain before
+/// signing, exactly as the desktop app does. `intent_description` says
+/// what the user agreed to and is recorded in the audit log.
+#[command]
+pub async fn sign_typed_data(
+    state: State<'_, GnsState>,
+    domain: String,
+    intent_description: String,
+    payload_json: String,
+    public_key: Option<String>,
+) -> Result<TypedDataSignature> {
+    let intent = intent_description.trim();
+    if intent.is_empty() {
+        return Err(Error::InvalidInput("An intent description is required".to_string()));
+    }
+    if intent.chars().count() > MAX_INTENT_LEN {
+        return Err(Error::InvalidInput(format!(
+            "Intent description is longer than {} characters",
+            MAX_INTENT_LEN
+        )));
+    }
+
+    let payload: serde_json::Value = serde_json::from_str(&payload_json)
+        .map_err(|e| Error::InvalidInput(format!("Invalid payload JSON: {}", e)))?;
+
+    let pk = public_key.or(state.get_active_identity().await);
+    let storage = state.storage.read().await;
+
+    let signed = pk
+        .ok_or_else(|| Error::IdentityNotFound("No active identity".to_string()))
+        .and_then(|pk| {
+            let secret_key = storage
+                .get_secret_key(&pk)?
+                .ok_or_else(|| Error::IdentityNotFound(pk.clone()))?;
+            let (signature, signed_bytes) = CryptoEngine::sign_typed_data(&secret_key, &domain, &payload)?;
+
+            Ok(TypedDataSignature {
+                public_key: pk,
+                signature,
+                signed_bytes: hex::encode(signed_bytes),
+            })
+        });
+
+    let target = format!("{}: {}", domain, intent);
+    let result = match &signed {
+        Ok(_) => "ok".to_string(),
+        Err(e) => e.to_string(),
+    };
+    if let Err(e) = storage.record_audit("sign_typed_data", Some(&target), &result) {
+        log::warn!("Failed to record sign_typed_data in audit log: {}", e);
+    }
+
+    signed
+}
+
 /// Verify a signature
 #[command]
 pub async fn verify_signature(
@@ -339,3 +421,33 @@ pub async fn set_default_identity(state: State<'_, GnsState>, public_key: String
     state.set_active_identity(Some(public_key)).await;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_derives_matching_keys() {
+        let (secret_key, public_key) = CryptoEngine::generate_keypair().unwrap();
+        let (_, enc_public) = CryptoEngine::derive_encryption_key(&secret_key).unwrap();
+
+        let keys = derive_imported_keys(&secret_key, &public_key.to_uppercase()).unwrap();
+        assert_eq!(keys.public_key, public_key);
+        assert_eq!(keys.enc_public, enc_public);
+    }
+
+    #[test]
+    fn test_import_rejects_mismatched_public_key() {
+        let (secret_key, _) = CryptoEngine::generate_keypair().unwrap();
+        let (_, other_public_key) = CryptoEngine::generate_keypair().unwrap();
+
+        assert!(matches!(
+            derive_imported_keys(&secret_key, &other_public_key),
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(matches!(
+            derive_imported_keys("abcd", &other_public_key),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+}
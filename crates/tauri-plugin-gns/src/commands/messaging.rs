@@ -2,24 +2,53 @@
 //!
 //! Tauri commands for encrypted E2E messaging.
 
-use crate::core::CryptoEngine;
+use crate::commands::resolver::resolve_cached;
+use crate::core::{CryptoEngine, NetworkClient, StorageManager};
 use crate::error::{Error, Result};
 use crate::models::*;
 use crate::GnsState;
-use tauri::{command, State};
+use std::collections::HashMap;
+use tauri::{command, AppHandle, Emitter, Runtime, State};
+use tokio::sync::RwLock;
 
 /// Send an encrypted message
+///
+/// A handle's encryption key comes from the handle cache. If the relay
+/// says the recipient has replaced it, the handle is re-resolved,
+/// `peer_key_changed` emitted and the message resealed and sent once more.
 #[command]
-pub async fn send_message(state: State<'_, GnsState>, params: SendMessageParams) -> Result<Message> {
+pub async fn send_message<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, GnsState>,
+    params: SendMessageParams,
+) -> Result<Message> {
     let my_pk = state
         .get_active_identity()
         .await
         .ok_or_else(|| Error::IdentityNotFound("No active identity".to_string()))?;
 
+    // Create payload, rejecting oversize messages before any network or crypto work
+    let payload = DecryptedPayload {
+        message_type: params.message_type,
+        content: params.content,
+        metadata: params.metadata,
+        reply_to: params.reply_to,
+    };
+    let payload_json = encode_payload(&payload, state.config.max_message_bytes)?;
+
     // Resolve recipient
     let recipient = if params.to.starts_with('@') {
         // Resolve handle
-        state.network.resolve_handle(&params.to).await?
+        let handle = params.to.trim_start_matches('@').to_lowercase();
+        let network = state.network.clone();
+        resolve_cached(
+            &state.storage,
+            &handle,
+            state.config.cache_ttl_seconds,
+            false,
+            |h| async move { network.resolve_handle(&h).await },
+        )
+        .await?
     } else {
         // Direct public key
         ResolvedHandle {
@@ -30,21 +59,135 @@ pub async fn send_message(state: State<'_, GnsState>, params: SendMessageParams)
             breadcrumb_count: 0,
             from_cache: false,
             resolved_at: chrono::Utc::now().to_rfc3339(),
+            previous_keys: Vec::new(),
         }
     };
 
-    // Get our encryption keys
-    let storage = state.storage.read().await;
-    let (our_enc_secret, _) = storage
-        .get_encryption_keys(&my_pk)?
-        .ok_or_else(|| Error::IdentityNotFound(my_pk.clone()))?;
-    let our_secret = storage
+    let our_secret = state
+        .storage
+        .read()
+        .await
         .get_secret_key(&my_pk)?
         .ok_or_else(|| Error::IdentityNotFound(my_pk.clone()))?;
-    drop(storage);
 
+    let (recipient, signed_envelope, key_change) = deliver(
+        &state.storage,
+        &state.network,
+        &state.peer_key_generations,
+        recipient,
+        state.config.cache_ttl_seconds,
+        |recipient| seal_envelope(&my_pk, &our_secret, recipient, &payload_json),
+    )
+    .await?;
+
+    if let Some(change) = key_change {
+        app.emit("peer_key_changed", &change)
+            .map_err(|e| Error::Internal(e.to_string()))?;
+    }
+
+    // Store locally
+    let message = Message {
+        id: signed_envelope.message_id,
+        from_pk: my_pk,
+        to_pk: recipient.public_key,
+        payload: signed_envelope.encrypted_payload,
+        ephemeral_key: Some(signed_envelope.ephemeral_key),
+        signature: signed_envelope.signature,
+        created_at: signed_envelope.timestamp,
+        received_at: None,
+        is_read: true,
+        decrypted: Some(payload),
+    };
+
+    let storage = state.storage.write().await;
+    storage.save_message(&message)?;
+
+    Ok(message)
+}
+
+/// Send the envelope `seal` makes for `recipient`. When the relay rejects
+/// a handle's key, the handle is re-resolved and the message resealed and
+/// sent one more time. A send that raced another one to the refresh picks
+/// up the newer cache entry instead of resolving again; only the send that
+/// moved the peer's generation on reports the change.
+async fn deliver<F>(
+    storage: &RwLock<StorageManager>,
+    network: &NetworkClient,
+    key_generations: &RwLock<HashMap<String, u64>>,
+    recipient: ResolvedHandle,
+    ttl_seconds: u64,
+    seal: F,
+) -> Result<(ResolvedHandle, GnsEnvelope, Option<PeerKeyChanged>)>
+where
+    F: Fn(&ResolvedHandle) -> Result<GnsEnvelope>,
+{
+    let generation = key_generations
+        .read()
+        .await
+        .get(&recipient.handle)
+        .copied()
+        .unwrap_or(0);
+
+    let envelope = seal(&recipient)?;
+    match network.send_message(&envelope).await {
+        Ok(()) => return Ok((recipient, envelope, None)),
+        Err(Error::KeyMismatch(reason)) if !recipient.handle.is_empty() => {
+            log::info!("Relay rejected the key cached for @{} ({}), re-resolving", recipient.handle, reason);
+        }
+        Err(e) => return Err(e),
+    }
+
+    let current = key_generations
+        .read()
+        .await
+        .get(&recipient.handle)
+        .copied()
+        .unwrap_or(0);
+    let refreshed = resolve_cached(storage, &recipient.handle, ttl_seconds, current == generation, |h| async move {
+        network.resolve_handle(&h).await
+    })
+    .await?;
+
+    // The handle must still belong to the same identity, or one it rotated to
+    let same_identity = refreshed.public_key == recipient.public_key
+        || refreshed.previous_keys.contains(&recipient.public_key.to_lowercase());
+    if !same_identity || refreshed.encryption_key == recipient.encryption_key {
+        return Err(Error::KeyMismatch(format!(
+            "@{} has no newer encryption key to send to",
+            recipient.handle
+        )));
+    }
+
+    let key_change = {
+        let mut generations = key_generations.write().await;
+        let entry = generations.entry(recipient.handle.clone()).or_insert(0);
+        (*entry == generation).then(|| {
+            *entry += 1;
+            PeerKeyChanged {
+                handle: refreshed.handle.clone(),
+                public_key: refreshed.public_key.clone(),
+                previous_encryption_key: recipient.encryption_key.clone(),
+                encryption_key: refreshed.encryption_key.clone(),
+                generation: *entry,
+            }
+        })
+    };
+
+    let envelope = seal(&refreshed)?;
+    network.send_message(&envelope).await?;
+    Ok((refreshed, envelope, key_change))
+}
+
+/// Encrypt `payload_json` to `recipient`'s X25519 key with a fresh
+/// ephemeral key and sign the envelope as `my_pk`
+fn seal_envelope(
+    my_pk: &str,
+    our_secret: &str,
+    recipient: &ResolvedHandle,
+    payload_json: &str,
+) -> Result<GnsEnvelope> {
     // Get recipient's encryption key
-    let their_enc_public = recipient.encryption_key.ok_or_else(|| {
+    let their_enc_public = recipient.encryption_key.as_deref().ok_or_else(|| {
         Error::Network("Recipient has no encryption key".to_string())
     })?;
 
@@ -52,74 +195,46 @@ pub async fn send_message(state: State<'_, GnsState>, params: SendMessageParams)
     let (ephemeral_secret, ephemeral_public) = CryptoEngine::generate_ephemeral_keypair();
 
     // Derive shared secret using ephemeral key
-    let shared_secret = CryptoEngine::key_exchange(&ephemeral_secret, &their_enc_public)?;
+    let shared_secret = CryptoEngine::key_exchange(&ephemeral_secret, their_enc_public)?;
     let message_key = CryptoEngine::derive_message_key(&shared_secret, b"gns-message")?;
 
-    // Create payload
-    let payload = DecryptedPayload {
-        message_type: params.message_type,
-        content: params.content,
-        metadata: params.metadata,
-        reply_to: params.reply_to,
-    };
-    let payload_json = serde_json::to_string(&payload)?;
-
     // Encrypt
     let (nonce, ciphertext) = CryptoEngine::encrypt(&message_key, payload_json.as_bytes())?;
 
-    // Create message ID
-    let message_id = CryptoEngine::random_id();
-    let timestamp = chrono::Utc::now().to_rfc3339();
-
     // Create envelope
     let envelope = GnsEnvelope {
         version: 1,
-        from_pk: my_pk.clone(),
+        from_pk: my_pk.to_string(),
         to_pk: recipient.public_key.clone(),
         encrypted_payload: format!("{}:{}", nonce, ciphertext),
         ephemeral_key: ephemeral_public,
+        recipient_key: Some(their_enc_public.to_string()),
         signature: String::new(), // Will be set below
-        message_id: message_id.clone(),
-        timestamp: timestamp.clone(),
+        message_id: CryptoEngine::random_id(),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        pow: None,
     };
 
     // Sign the envelope
-    let envelope_data = serde_json::to_string(&serde_json::json!({
-        "from_pk": envelope.from_pk,
-        "to_pk": envelope.to_pk,
-        "encrypted_payload": envelope.encrypted_payload,
-        "ephemeral_key": envelope.ephemeral_key,
-        "message_id": envelope.message_id,
-        "timestamp": envelope.timestamp,
-    }))?;
-    let signature = CryptoEngine::sign(&our_secret, envelope_data.as_bytes())?;
-
-    let signed_envelope = GnsEnvelope {
+    let signature = CryptoEngine::sign(our_secret, envelope.signed_data().as_bytes())?;
+
+    Ok(GnsEnvelope {
         signature,
         ..envelope
-    };
-
-    // Send via relay
-    state.network.send_message(&signed_envelope).await?;
-
-    // Store locally
-    let message = Message {
-        id: message_id,
-        from_pk: my_pk,
-        to_pk: recipient.public_key,
-        payload: signed_envelope.encrypted_payload,
-        ephemeral_key: Some(signed_envelope.ephemeral_key),
-        signature: signed_envelope.signature,
-        created_at: timestamp,
-        received_at: None,
-        is_read: true,
-        decrypted: Some(payload),
-    };
-
-    let storage = state.storage.write().await;
-    storage.save_message(&message)?;
+    })
+}
 
-    Ok(message)
+/// Serialize a payload for encryption, enforcing the size limit
+fn encode_payload(payload: &DecryptedPayload, max_bytes: usize) -> Result<String> {
+    let json = serde_json::to_string(payload)?;
+    if json.len() > max_bytes {
+        return Err(Error::MessageTooLarge(format!(
+            "payload is {} bytes, limit is {}",
+            json.len(),
+            max_bytes
+        )));
+    }
+    Ok(json)
 }
 
 /// Get messages for the current identity
@@ -147,18 +262,77 @@ pub async fn get_message(state: State<'_, GnsState>, message_id: String) -> Resu
         .ok_or_else(|| Error::IdentityNotFound("No active identity".to_string()))?;
 
     let storage = state.storage.read().await;
-    let messages = storage.get_messages(
-        &my_pk,
-        &MessageQuery {
-            limit: 1,
-            ..Default::default()
-        },
-    )?;
+    let message = storage.get_message(&message_id)?;
+
+    Ok(message.filter(|m| m.from_pk == my_pk || m.to_pk == my_pk))
+}
+
+/// Envelopes the relay is holding for the active identity, e.g. to show
+/// "syncing N messages" after coming back online
+#[command]
+pub async fn get_inbox_status(state: State<'_, GnsState>) -> Result<InboxStatus> {
+    let my_pk = state
+        .get_active_identity()
+        .await
+        .ok_or_else(|| Error::IdentityNotFound("No active identity".to_string()))?;
 
-    Ok(messages.into_iter().find(|m| m.id == message_id))
+    state.network.get_inbox_status(&my_pk).await
 }
 
-/// Decrypt a message
+/// Attempts at an attachment upload; each one resumes where the last stopped
+const ATTACHMENT_UPLOAD_ATTEMPTS: usize = 3;
+
+/// Encrypt a file (base64) and upload it to the relay as a blob.
+///
+/// The blob goes up in chunks, emitting `attachment_upload_progress` as the
+/// relay acknowledges each one, and a dropped connection is retried from
+/// the last acknowledged offset. The returned reference is what a message
+/// carries to let the recipient fetch and decrypt the file.
+#[command]
+pub async fn upload_attachment<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, GnsState>,
+    data: String,
+    mime_type: Option<String>,
+) -> Result<AttachmentRef> {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    let plaintext = STANDARD
+        .decode(data.as_bytes())
+        .map_err(|e| Error::InvalidInput(format!("Attachment is not valid base64: {}", e)))?;
+    let (key, nonce, blob) = CryptoEngine::encrypt_blob(&plaintext)?;
+
+    let on_progress = |progress: &UploadProgress| {
+        if let Err(e) = app.emit("attachment_upload_progress", progress) {
+            log::warn!("Failed to emit upload progress: {}", e);
+        }
+    };
+    let mut attempt = 1;
+    let blob_id = loop {
+        match state.network.upload_blob(&blob, on_progress).await {
+            Ok(blob_id) => break blob_id,
+            Err(e) if attempt < ATTACHMENT_UPLOAD_ATTEMPTS => {
+                log::warn!("Attachment upload attempt {} failed, resuming: {}", attempt, e);
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    };
+
+    Ok(AttachmentRef {
+        blob_id,
+        key,
+        nonce,
+        size: blob.len() as u64,
+        sha256: CryptoEngine::sha256(&blob),
+        mime_type,
+    })
+}
+
+/// Decrypt a stored message.
+///
+/// Returns the cached plaintext when present; otherwise the ciphertext is
+/// decrypted with the active identity's X25519 key and the cache refilled.
 #[command]
 pub async fn decrypt_message(
     state: State<'_, GnsState>,
@@ -170,59 +344,74 @@ pub async fn decrypt_message(
         .ok_or_else(|| Error::IdentityNotFound("No active identity".to_string()))?;
 
     let storage = state.storage.read().await;
-    let messages = storage.get_messages(
-        &my_pk,
-        &MessageQuery {
-            limit: 1000,
-            ..Default::default()
-        },
-    )?;
+    decrypt_stored_message(&storage, &my_pk, &message_id)
+}
 
-    let message = messages
-        .into_iter()
-        .find(|m| m.id == message_id)
+/// Decrypt `message_id` for `owner_pk`, writing the cache only on success
+/// so a wrong key or corrupted ciphertext never leaves a bad entry behind.
+pub(crate) fn decrypt_stored_message(
+    storage: &StorageManager,
+    owner_pk: &str,
+    message_id: &str,
+) -> Result<DecryptedPayload> {
+    let message = storage
+        .get_message(message_id)?
+        .filter(|m| m.from_pk == owner_pk || m.to_pk == owner_pk)
         .ok_or_else(|| Error::InvalidInput("Message not found".to_string()))?;
 
-    // Already decrypted?
     if let Some(decrypted) = message.decrypted {
         return Ok(decrypted);
     }
 
-    // Get ephemeral key (required for decryption)
-    let ephemeral_key = message.ephemeral_key
+    // Outgoing messages are sealed to the recipient's key
+    if message.to_pk != owner_pk {
+        return Err(Error::DecryptionFailed(
+            "Sent messages are encrypted to the recipient and cannot be re-decrypted".to_string(),
+        ));
+    }
+
+    let ephemeral_key = message
+        .ephemeral_key
         .ok_or_else(|| Error::DecryptionFailed("Missing ephemeral key".to_string()))?;
 
-    // Get our encryption keys
     let (our_enc_secret, _) = storage
-        .get_encryption_keys(&my_pk)?
-        .ok_or_else(|| Error::IdentityNotFound(my_pk.clone()))?;
+        .get_encryption_keys(owner_pk)?
+        .ok_or_else(|| Error::IdentityNotFound(owner_pk.to_string()))?;
 
-    // Parse the payload (format: nonce:ciphertext)
-    let parts: Vec<&str> = message.payload.split(':').collect();
-    if parts.len() != 2 {
-        return Err(Error::DecryptionFailed("Invalid payload format".to_string()));
-    }
+    // Payload format: nonce:ciphertext
+    let (nonce, ciphertext) = message
+        .payload
+        .split_once(':')
+        .ok_or_else(|| Error::DecryptionFailed("Invalid payload format".to_string()))?;
 
-    let nonce = parts[0];
-    let ciphertext = parts[1];
+    let decrypted = open_payload(&our_enc_secret, &ephemeral_key, nonce, ciphertext)?;
 
-    // Perform X25519 key exchange with the ephemeral key
-    let shared_secret = CryptoEngine::key_exchange(&our_enc_secret, &ephemeral_key)?;
-    let message_key = CryptoEngine::derive_message_key(&shared_secret, b"gns-message")?;
+    storage.update_message_decrypted(message_id, &decrypted)?;
 
-    // Decrypt the ciphertext
-    let plaintext = CryptoEngine::decrypt(&message_key, nonce, ciphertext)?;
-    
-    // Parse the decrypted payload
-    let decrypted: DecryptedPayload = serde_json::from_slice(&plaintext)
-        .map_err(|e| Error::DecryptionFailed(format!("Invalid payload JSON: {}", e)))?;
+    Ok(decrypted)
+}
 
-    // Update the stored message with decrypted content
-    drop(storage);
-    let storage = state.storage.write().await;
-    storage.update_message_decrypted(&message_id, &decrypted)?;
+/// X25519 with the message's ephemeral key, then ChaCha20-Poly1305
+fn open_payload(
+    our_enc_secret: &str,
+    ephemeral_key: &str,
+    nonce: &str,
+    ciphertext: &str,
+) -> Result<DecryptedPayload> {
+    let as_decryption_failure = |e: Error| match e {
+        Error::DecryptionFailed(_) => e,
+        other => Error::DecryptionFailed(other.to_string()),
+    };
 
-    Ok(decrypted)
+    let shared_secret =
+        CryptoEngine::key_exchange(our_enc_secret, ephemeral_key).map_err(as_decryption_failure)?;
+    let message_key = CryptoEngine::derive_message_key(&shared_secret, b"gns-message")
+        .map_err(as_decryption_failure)?;
+    let plaintext =
+        CryptoEngine::decrypt(&message_key, nonce, ciphertext).map_err(as_decryption_failure)?;
+
+    serde_json::from_slice(&plaintext)
+        .map_err(|e| Error::DecryptionFailed(format!("Invalid payload JSON: {}", e)))
 }
 
 /// Mark a message as read
@@ -293,3 +482,190 @@ pub async fn get_conversations(state: State<'_, GnsState>) -> Result<Vec<Convers
 
     Ok(conversations.into_values().collect())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    /// An incoming message for `recipient_enc_public`, encrypted the way `send_message` does it
+    fn incoming_message(id: &str, from_pk: &str, to_pk: &str, recipient_enc_public: &str) -> Message {
+        let (ephemeral_secret, ephemeral_public) = CryptoEngine::generate_ephemeral_keypair();
+        let shared = CryptoEngine::key_exchange(&ephemeral_secret, recipient_enc_public).unwrap();
+        let key = CryptoEngine::derive_message_key(&shared, b"gns-message").unwrap();
+
+        let payload = DecryptedPayload {
+            message_type: MessageType::Text,
+            content: "hello again".to_string(),
+            metadata: None,
+            reply_to: None,
+        };
+        let (nonce, ciphertext) =
+            CryptoEngine::encrypt(&key, serde_json::to_string(&payload).unwrap().as_bytes()).unwrap();
+
+        Message {
+            id: id.to_string(),
+            from_pk: from_pk.to_string(),
+            to_pk: to_pk.to_string(),
+            payload: format!("{}:{}", nonce, ciphertext),
+            ephemeral_key: Some(ephemeral_public),
+            signature: String::new(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            received_at: None,
+            is_read: false,
+            decrypted: None,
+        }
+    }
+
+    #[test]
+    fn test_cache_miss_redecrypts_and_failures_leave_cache_empty() {
+        let dir = tempdir().unwrap();
+        let storage = StorageManager::new(&dir.path().join("test.db"), false).unwrap();
+
+        let me = "aa".repeat(32);
+        let peer = "bb".repeat(32);
+        let (enc_secret, enc_public) = CryptoEngine::generate_ephemeral_keypair();
+        storage.save_identity(&me, "secret", &enc_secret, &enc_public, "Me").unwrap();
+
+        storage.save_message(&incoming_message("m1", &peer, &me, &enc_public)).unwrap();
+        assert!(storage.get_message("m1").unwrap().unwrap().decrypted.is_none());
+
+        let decrypted = decrypt_stored_message(&storage, &me, "m1").unwrap();
+        assert_eq!(decrypted.content, "hello again");
+        assert_eq!(storage.get_message("m1").unwrap().unwrap().decrypted.unwrap().content, "hello again");
+
+        // Sealed to someone else's key: fails cleanly, nothing cached
+        let (_, other_public) = CryptoEngine::generate_ephemeral_keypair();
+        storage.save_message(&incoming_message("m2", &peer, &me, &other_public)).unwrap();
+        assert!(matches!(
+            decrypt_stored_message(&storage, &me, "m2"),
+            Err(Error::DecryptionFailed(_))
+        ));
+        assert!(storage.get_message("m2").unwrap().unwrap().decrypted.is_none());
+
+        // Corrupted payload
+        let mut corrupted = incoming_message("m3", &peer, &me, &enc_public);
+        corrupted.payload = "zz:not-base64".to_string();
+        storage.save_message(&corrupted).unwrap();
+        assert!(matches!(
+            decrypt_stored_message(&storage, &me, "m3"),
+            Err(Error::DecryptionFailed(_))
+        ));
+        assert!(storage.get_message("m3").unwrap().unwrap().decrypted.is_none());
+    }
+
+    #[test]
+    fn test_encode_payload_size_limit() {
+        let payload = |content: &str| DecryptedPayload {
+            message_type: MessageType::Text,
+            content: content.to_string(),
+            metadata: None,
+            reply_to: None,
+        };
+        let limit = serde_json::to_string(&payload("x".repeat(100).as_str())).unwrap().len();
+
+        assert!(encode_payload(&payload(&"x".repeat(100)), limit).is_ok());
+        assert!(matches!(
+            encode_payload(&payload(&"x".repeat(101)), limit),
+            Err(Error::MessageTooLarge(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_rotated_key_is_refreshed_and_retried_once() {
+        use crate::core::{MockTransport, TransportResponse};
+        use reqwest::StatusCode;
+        use std::sync::Arc;
+
+        let dir = tempdir().unwrap();
+        let storage = RwLock::new(StorageManager::new(&dir.path().join("test.db"), false).unwrap());
+        let (my_secret, my_pk) = CryptoEngine::generate_keypair().unwrap();
+        let bob_pk = "bb".repeat(32);
+        let (_, old_key) = CryptoEngine::generate_ephemeral_keypair();
+        let (new_secret, new_key) = CryptoEngine::generate_ephemeral_keypair();
+
+        let cached = ResolvedHandle {
+            handle: "bob".to_string(),
+            public_key: bob_pk.clone(),
+            encryption_key: Some(old_key.clone()),
+            trust_score: 0.0,
+            breadcrumb_count: 0,
+            from_cache: false,
+            resolved_at: chrono::Utc::now().to_rfc3339(),
+            previous_keys: Vec::new(),
+        };
+        storage.read().await.cache_handle("bob", &cached).unwrap();
+
+        // Bob's relay only takes what he can open with his new key
+        let record = serde_json::json!({
+            "data": { "identity": { "identity": bob_pk, "encryption_key": new_key } }
+        });
+        let transport = Arc::new(MockTransport::new(move |request| {
+            if request.path() == "/api/handles/bob" {
+                return Ok(TransportResponse::json(StatusCode::OK, &record));
+            }
+            let envelope: GnsEnvelope = request.read_json()?;
+            let (nonce, ciphertext) = envelope.encrypted_payload.split_once(':').unwrap();
+            Ok(match open_payload(&new_secret, &envelope.ephemeral_key, nonce, ciphertext) {
+                Ok(_) => TransportResponse::json(StatusCode::OK, &serde_json::json!({ "success": true })),
+                Err(_) => TransportResponse::json(
+                    StatusCode::CONFLICT,
+                    &serde_json::json!({ "error": "Recipient key has changed" }),
+                ),
+            })
+        }));
+        let network = NetworkClient::with_transport(&["https://relay.test".to_string()], transport.clone());
+        let generations = RwLock::new(HashMap::new());
+        let payload = encode_payload(
+            &DecryptedPayload {
+                message_type: MessageType::Text,
+                content: "still there?".to_string(),
+                metadata: None,
+                reply_to: None,
+            },
+            1024,
+        )
+        .unwrap();
+        let seal = |recipient: &ResolvedHandle| seal_envelope(&my_pk, &my_secret, recipient, &payload);
+
+        let recipient = storage.read().await.get_cached_handle("bob", 3600).unwrap().unwrap();
+        let (sent_to, envelope, change) = deliver(&storage, &network, &generations, recipient, 3600, seal)
+            .await
+            .unwrap();
+
+        assert_eq!(sent_to.encryption_key.as_deref(), Some(new_key.as_str()));
+        assert_eq!(envelope.recipient_key.as_deref(), Some(new_key.as_str()));
+        assert_eq!(
+            change,
+            Some(PeerKeyChanged {
+                handle: "bob".to_string(),
+                public_key: bob_pk.clone(),
+                previous_encryption_key: Some(old_key.clone()),
+                encryption_key: Some(new_key.clone()),
+                generation: 1,
+            })
+        );
+        let paths: Vec<String> = transport.requests().iter().map(|r| r.path()).collect();
+        assert_eq!(paths, ["/api/messages", "/api/handles/bob", "/api/messages"]);
+        let recached = storage.read().await.get_cached_handle("bob", 3600).unwrap().unwrap();
+        assert_eq!(recached.encryption_key, Some(new_key));
+
+        // A relay that keeps refusing gets one retry, not a loop
+        let refusing = Arc::new(MockTransport::new(move |request| {
+            Ok(if request.path() == "/api/handles/bob" {
+                let rotated_again = CryptoEngine::generate_ephemeral_keypair().1;
+                TransportResponse::json(
+                    StatusCode::OK,
+                    &serde_json::json!({ "data": { "identity": { "identity": "bb".repeat(32), "encryption_key": rotated_again } } }),
+                )
+            } else {
+                TransportResponse::json(StatusCode::CONFLICT, &serde_json::json!({ "error": "Recipient key has changed" }))
+            })
+        }));
+        let network = NetworkClient::with_transport(&["https://relay.test".to_string()], refusing.clone());
+        let err = deliver(&storage, &network, &generations, cached, 3600, seal).await.unwrap_err();
+        assert!(matches!(err, Error::KeyMismatch(_)));
+        assert_eq!(refusing.requests().len(), 3);
+        assert_eq!(generations.read().await.get("bob"), Some(&2));
+    }
+}
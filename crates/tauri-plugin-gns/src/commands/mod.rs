@@ -8,12 +8,14 @@
 //! - **messaging**: E2E encrypted messaging
 //! - **resolver**: Handle resolution and registration
 //! - **trust**: Trust score calculation and verification
+//! - **relay**: Relay benchmarking and selection
 //! - **trajectory**: Breadcrumb collection and epoch publishing (feature-gated)
 
 pub mod identity;
 pub mod messaging;
 pub mod resolver;
 pub mod trust;
+pub mod relay;
 
 #[cfg(feature = "trajectory")]
 pub mod trajectory;
@@ -0,0 +1,20 @@
+//! Relay Commands
+//!
+//! Commands for measuring the configured relays and choosing between them.
+
+use tauri::{command, State};
+use crate::{error::Result, models::RelayBenchmark, GnsState};
+
+/// Health-check every configured relay and time the round trips.
+///
+/// Returns the relays fastest first, with unreachable ones last. With
+/// `select`, the fastest reachable relay becomes the active one for the
+/// rest of the session. The plugin also does this on its own at startup
+/// and whenever the active relay comes back after being unreachable.
+#[command]
+pub async fn benchmark_relays(
+    state: State<'_, GnsState>,
+    select: Option<bool>,
+) -> Result<Vec<RelayBenchmark>> {
+    Ok(state.network.benchmark_relays(select.unwrap_or(false)).await)
+}
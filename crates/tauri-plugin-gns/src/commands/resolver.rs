@@ -2,32 +2,245 @@
 //!
 //! Tauri commands for handle resolution and GNS record management.
 
-use crate::core::CryptoEngine;
+use crate::commands::trust::get_trust_score_for_identity;
+use crate::config::GnsConfig;
+use crate::core::{CryptoEngine, StorageManager};
 use crate::error::{Error, Result};
 use crate::models::*;
 use crate::GnsState;
-use tauri::{command, State};
-
-/// Resolve a handle to identity information
+use futures::stream::{self, StreamExt};
+use std::future::Future;
+use tauri::{command, AppHandle, Emitter, Runtime, State};
+use tokio::sync::RwLock;
+
+/// Maximum concurrent lookups while refreshing the handle cache
+const HANDLE_REFRESH_CONCURRENCY: usize = 8;
+
+/// Resolve a handle to identity information.
+///
+/// Cached resolutions younger than `cache_ttl_seconds` are returned without
+/// a network call unless `force` is set. Every successful network lookup
+/// rewrites the cache entry.
 #[command]
-pub async fn resolve_handle(state: State<'_, GnsState>, handle: String) -> Result<ResolvedHandle> {
+pub async fn resolve_handle(
+    state: State<'_, GnsState>,
+    handle: String,
+    force: Option<bool>,
+) -> Result<ResolvedHandle> {
     let handle = handle.trim_start_matches('@').to_lowercase();
+    let network = state.network.clone();
+
+    resolve_cached(
+        &state.storage,
+        &handle,
+        state.config.cache_ttl_seconds,
+        force.unwrap_or(false),
+        |h| async move { network.resolve_handle(&h).await },
+    )
+    .await
+}
 
-    // Check cache first
-    let storage = state.storage.read().await;
-    if let Some(cached) = storage.get_cached_handle(&handle, state.config.cache_ttl_seconds)? {
-        return Ok(cached);
+/// Cache-aware resolution with the network lookup passed in
+pub(crate) async fn resolve_cached<F, Fut>(
+    storage: &RwLock<StorageManager>,
+    handle: &str,
+    ttl_seconds: u64,
+    force: bool,
+    fetch: F,
+) -> Result<ResolvedHandle>
+where
+    F: FnOnce(String) -> Fut,
+    Fut: Future<Output = Result<ResolvedHandle>>,
+{
+    if !force {
+        if let Some(cached) = storage.read().await.get_cached_handle(handle, ttl_seconds)? {
+            return Ok(cached);
+        }
+    }
+
+    let resolved = fetch(handle.to_string()).await?;
+
+    storage.write().await.cache_handle(handle, &resolved)?;
+
+    Ok(resolved)
+}
+
+/// Re-resolve every cached handle from the network.
+///
+/// Entries are rewritten with fresh keys and trust data; handles that no
+/// longer resolve (released or rotated away) are dropped. Lookups that fail
+/// for other reasons leave the entry as is. Emits `handle_cache_refreshed`
+/// with the counts.
+#[command]
+pub async fn refresh_handle_cache<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, GnsState>,
+) -> Result<HandleCacheRefresh> {
+    let handles = state.storage.read().await.list_cached_handles()?;
+
+    let network = state.network.clone();
+    let results: Vec<(String, Result<ResolvedHandle>)> = stream::iter(handles)
+        .map(|handle| {
+            let network = network.clone();
+            async move {
+                let resolved = network.resolve_handle(&handle).await;
+                (handle, resolved)
+            }
+        })
+        .buffer_unordered(HANDLE_REFRESH_CONCURRENCY)
+        .collect()
+        .await;
+
+    let mut summary = HandleCacheRefresh {
+        total: results.len() as u32,
+        ..Default::default()
+    };
+
+    let storage = state.storage.write().await;
+    for (handle, resolved) in results {
+        match resolved {
+            Ok(resolved) if !resolved.public_key.is_empty() => {
+                storage.cache_handle(&handle, &resolved)?;
+                summary.updated += 1;
+            }
+            Ok(_) | Err(Error::HandleNotFound(_)) => {
+                storage.remove_cached_handle(&handle)?;
+                summary.removed += 1;
+            }
+            Err(e) => {
+                log::warn!("Could not refresh @{}: {}", handle, e);
+                summary.failed += 1;
+            }
+        }
     }
     drop(storage);
 
-    // Fetch from network
-    let resolved = state.network.resolve_handle(&handle).await?;
+    log::info!(
+        "Handle cache refreshed: {} updated, {} removed, {} failed",
+        summary.updated,
+        summary.removed,
+        summary.failed
+    );
+
+    app.emit("handle_cache_refreshed", &summary)
+        .map_err(|e| Error::Internal(e.to_string()))?;
+
+    Ok(summary)
+}
+
+/// Drop a single handle from the cache so the next lookup hits the network
+#[command]
+pub async fn invalidate_handle(state: State<'_, GnsState>, handle: String) -> Result<bool> {
+    let handle = handle.trim_start_matches('@').to_lowercase();
 
-    // Cache the result
     let storage = state.storage.write().await;
-    storage.cache_handle(&handle, &resolved)?;
+    storage.remove_cached_handle(&handle)
+}
 
-    Ok(resolved)
+/// Verify a signature made by whoever holds `handle`.
+///
+/// The handle is resolved through the cache (or the network when `force`
+/// is set). Signatures by a key the identity held before a rotation are
+/// accepted and flagged with `signedByPreviousKey`. If no known key
+/// verifies and the entry was cached, the handle is looked up again in
+/// case the key was rotated since. Pass `expected_public_key` to also
+/// require the handle to resolve to (or to have rotated away from) a
+/// specific key.
+#[command]
+pub async fn verify_signature_by_handle(
+    state: State<'_, GnsState>,
+    handle: String,
+    message: String,
+    signature: String,
+    expected_public_key: Option<String>,
+    force: Option<bool>,
+) -> Result<HandleVerifyResult> {
+    let handle = handle.trim_start_matches('@').to_lowercase();
+    let message_bytes = hex::decode(&message).unwrap_or_else(|_| message.as_bytes().to_vec());
+    let network = state.network.clone();
+
+    verify_by_handle(
+        &state.storage,
+        &handle,
+        &message_bytes,
+        &signature,
+        expected_public_key.as_deref(),
+        state.config.cache_ttl_seconds,
+        force.unwrap_or(false),
+        |h| {
+            let network = network.clone();
+            async move { network.resolve_handle(&h).await }
+        },
+    )
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn verify_by_handle<F, Fut>(
+    storage: &RwLock<StorageManager>,
+    handle: &str,
+    message: &[u8],
+    signature: &str,
+    expected_public_key: Option<&str>,
+    ttl_seconds: u64,
+    force: bool,
+    fetch: F,
+) -> Result<HandleVerifyResult>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<ResolvedHandle>>,
+{
+    let mut resolved = resolve_cached(storage, handle, ttl_seconds, force, &fetch).await?;
+    if resolved.public_key.is_empty() {
+        return Err(Error::HandleNotFound(handle.to_string()));
+    }
+
+    let mut signer = find_signer(&resolved, message, signature)?;
+    let mut key_rotated = false;
+
+    if signer.is_none() && resolved.from_cache {
+        let cached_key = resolved.public_key.clone();
+        resolved = match resolve_cached(storage, handle, ttl_seconds, true, &fetch).await {
+            Ok(fresh) if !fresh.public_key.is_empty() => fresh,
+            Ok(_) | Err(Error::HandleNotFound(_)) => {
+                storage.write().await.remove_cached_handle(handle)?;
+                return Err(Error::HandleNotFound(handle.to_string()));
+            }
+            Err(e) => return Err(e),
+        };
+
+        if resolved.public_key != cached_key {
+            log::info!("@{} key changed since it was cached", handle);
+            key_rotated = true;
+        }
+        signer = find_signer(&resolved, message, signature)?;
+    }
+
+    let matches_expected = expected_public_key.map(|k| {
+        k.eq_ignore_ascii_case(&resolved.public_key)
+            || resolved.previous_keys.iter().any(|p| k.eq_ignore_ascii_case(p))
+    });
+
+    Ok(HandleVerifyResult {
+        valid: signer.is_some() && matches_expected != Some(false),
+        handle: handle.to_string(),
+        signed_by_previous_key: signer.is_some_and(|k| k != resolved.public_key),
+        public_key: resolved.public_key,
+        from_cache: resolved.from_cache,
+        key_rotated,
+        matches_expected,
+    })
+}
+
+/// The key of `resolved` that made `signature`: the current key first,
+/// then earlier keys from most to least recent
+fn find_signer(resolved: &ResolvedHandle, message: &[u8], signature: &str) -> Result<Option<String>> {
+    for key in std::iter::once(&resolved.public_key).chain(&resolved.previous_keys) {
+        if CryptoEngine::verify(key, message, signature)? {
+            return Ok(Some(key.clone()));
+        }
+    }
+    Ok(None)
 }
 
 /// Resolve identity by public key
@@ -36,6 +249,25 @@ pub async fn resolve_identity(state: State<'_, GnsState>, public_key: String) ->
     state.network.get_record(&public_key).await
 }
 
+/// Check the weighted trajectory meets the handle requirements, so
+/// breadcrumbs from sources weighted to nothing don't count
+fn check_claim_trust(config: &GnsConfig, trust: &TrustScore) -> Result<()> {
+    if trust.weighted_breadcrumb_count < config.min_breadcrumbs_for_handle {
+        return Err(Error::InsufficientBreadcrumbs(format!(
+            "Need {} breadcrumbs, have {}",
+            config.min_breadcrumbs_for_handle, trust.weighted_breadcrumb_count
+        )));
+    }
+
+    if trust.score < config.min_trust_score_for_handle {
+        return Err(Error::InsufficientTrust(format!(
+            "Need {}% trust, have {:.1}%",
+            config.min_trust_score_for_handle, trust.score
+        )));
+    }
+    Ok(())
+}
+
 /// Claim a handle for the current identity
 #[command]
 pub async fn claim_handle(state: State<'_, GnsState>, handle: String) -> Result<()> {
@@ -82,27 +314,20 @@ pub async fn claim_handle(state: State<'_, GnsState>, handle: String) -> Result<
     let secret_key = storage
         .get_secret_key(&my_pk)?
         .ok_or_else(|| Error::IdentityNotFound(my_pk.clone()))?;
+    let trust = get_trust_score_for_identity(
+        &storage,
+        &my_pk,
+        identity.breadcrumb_count,
+        &state.config.breadcrumb_source_weights,
+    )?;
     drop(storage);
 
-    // Check trust requirements
-    if identity.breadcrumb_count < state.config.min_breadcrumbs_for_handle {
-        return Err(Error::InsufficientBreadcrumbs(format!(
-            "Need {} breadcrumbs, have {}",
-            state.config.min_breadcrumbs_for_handle, identity.breadcrumb_count
-        )));
-    }
-
-    if identity.trust_score < state.config.min_trust_score_for_handle {
-        return Err(Error::InsufficientTrust(format!(
-            "Need {}% trust, have {}%",
-            state.config.min_trust_score_for_handle, identity.trust_score
-        )));
-    }
+    check_claim_trust(&state.config, &trust)?;
 
     // Create proof
     let proof = PotProof {
         breadcrumb_count: identity.breadcrumb_count,
-        trust_score: identity.trust_score,
+        trust_score: trust.score,
         first_breadcrumb_at: identity.created_at.clone(),
         latest_epoch_root: None,
     };
@@ -223,3 +448,221 @@ pub async fn is_handle_available(state: State<'_, GnsState>, handle: String) ->
     // Check network
     state.network.is_handle_available(&handle).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tempfile::tempdir;
+
+    fn resolved(handle: &str, trust_score: f64) -> ResolvedHandle {
+        ResolvedHandle {
+            handle: handle.to_string(),
+            public_key: "ab".repeat(32),
+            encryption_key: None,
+            trust_score,
+            breadcrumb_count: 100,
+            from_cache: false,
+            resolved_at: chrono::Utc::now().to_rfc3339(),
+            previous_keys: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_uses_cache_within_ttl_unless_forced() {
+        let dir = tempdir().unwrap();
+        let storage = RwLock::new(StorageManager::new(&dir.path().join("test.db"), false).unwrap());
+        let lookups = AtomicU32::new(0);
+
+        let fetch = |h: String| {
+            let n = lookups.fetch_add(1, Ordering::SeqCst) + 1;
+            async move { Ok(resolved(&h, n as f64)) }
+        };
+
+        let first = resolve_cached(&storage, "alice", 300, false, fetch).await.unwrap();
+        assert!(!first.from_cache);
+
+        let second = resolve_cached(&storage, "alice", 300, false, fetch).await.unwrap();
+        assert!(second.from_cache);
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+
+        let forced = resolve_cached(&storage, "alice", 300, true, fetch).await.unwrap();
+        assert!(!forced.from_cache);
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+
+        // The forced lookup rewrote the cache
+        let cached = resolve_cached(&storage, "alice", 300, false, fetch).await.unwrap();
+        assert_eq!(cached.trust_score, 2.0);
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+
+        // A zero TTL never serves from cache
+        resolve_cached(&storage, "alice", 0, false, fetch).await.unwrap();
+        assert_eq!(lookups.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_claim_counts_weighted_breadcrumbs() {
+        let dir = tempdir().unwrap();
+        let storage = StorageManager::new(&dir.path().join("test.db"), false).unwrap();
+        let config = GnsConfig::default();
+
+        // The stored count is plenty, but none of it came from a weighted source
+        let trust = get_trust_score_for_identity(&storage, &"ab".repeat(32), 500, &config.breadcrumb_source_weights).unwrap();
+        assert_eq!((trust.breadcrumb_count, trust.weighted_breadcrumb_count), (500, 0));
+        assert!(matches!(check_claim_trust(&config, &trust), Err(Error::InsufficientBreadcrumbs(_))));
+
+        let weighted = TrustScore { weighted_breadcrumb_count: 500, score: 10.0, ..trust };
+        assert!(matches!(check_claim_trust(&config, &weighted), Err(Error::InsufficientTrust(_))));
+        let trusted = TrustScore { score: 100.0, ..weighted };
+        assert!(check_claim_trust(&config, &trusted).is_ok());
+    }
+
+    fn resolved_with_key(handle: &str, public_key: &str) -> ResolvedHandle {
+        ResolvedHandle {
+            public_key: public_key.to_string(),
+            ..resolved(handle, 50.0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_by_handle() {
+        let dir = tempdir().unwrap();
+        let storage = RwLock::new(StorageManager::new(&dir.path().join("test.db"), false).unwrap());
+
+        let (alice_sk, alice_pk) = CryptoEngine::generate_keypair().unwrap();
+        let (_, other_pk) = CryptoEngine::generate_keypair().unwrap();
+        let message = b"I, @alice, wrote this";
+        let signature = CryptoEngine::sign(&alice_sk, message).unwrap();
+
+        let fetch = |h: String| {
+            let pk = alice_pk.clone();
+            async move { Ok(resolved_with_key(&h, &pk)) }
+        };
+
+        // Valid signature
+        let ok = verify_by_handle(&storage, "alice", message, &signature, None, 300, false, fetch)
+            .await
+            .unwrap();
+        assert!(ok.valid);
+        assert_eq!(ok.public_key, alice_pk);
+        assert_eq!(ok.matches_expected, None);
+
+        // Tampered message
+        let tampered = verify_by_handle(&storage, "alice", b"I, @alice, wrote that", &signature, None, 300, false, fetch)
+            .await
+            .unwrap();
+        assert!(!tampered.valid);
+        assert!(!tampered.key_rotated);
+
+        // Handle resolves to a different key than the caller expected
+        let unexpected = verify_by_handle(&storage, "alice", message, &signature, Some(&other_pk), 300, false, fetch)
+            .await
+            .unwrap();
+        assert!(!unexpected.valid);
+        assert_eq!(unexpected.matches_expected, Some(false));
+        assert_eq!(unexpected.public_key, alice_pk);
+    }
+
+    #[tokio::test]
+    async fn test_verify_by_handle_rotated_and_missing() {
+        let dir = tempdir().unwrap();
+        let storage = RwLock::new(StorageManager::new(&dir.path().join("test.db"), false).unwrap());
+
+        let (_, old_pk) = CryptoEngine::generate_keypair().unwrap();
+        let (new_sk, new_pk) = CryptoEngine::generate_keypair().unwrap();
+        let message = b"signed after rotation";
+        let signature = CryptoEngine::sign(&new_sk, message).unwrap();
+
+        // Cache still holds the pre-rotation key
+        storage.write().await.cache_handle("alice", &resolved_with_key("alice", &old_pk)).unwrap();
+
+        let fetch = |h: String| {
+            let pk = new_pk.clone();
+            async move { Ok(resolved_with_key(&h, &pk)) }
+        };
+        let rotated = verify_by_handle(&storage, "alice", message, &signature, None, 300, false, fetch)
+            .await
+            .unwrap();
+        assert!(rotated.valid);
+        assert!(rotated.key_rotated);
+        assert_eq!(rotated.public_key, new_pk);
+
+        let missing = |h: String| async move { Err::<ResolvedHandle, _>(Error::HandleNotFound(h)) };
+        let err = verify_by_handle(&storage, "nobody", message, &signature, None, 300, false, missing)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::HandleNotFound(_)));
+    }
+
+    #[tokio::test]
+    async fn test_handle_rotated_twice() {
+        let dir = tempdir().unwrap();
+        let storage = RwLock::new(StorageManager::new(&dir.path().join("test.db"), false).unwrap());
+
+        // first_pk -> second_pk -> current_pk
+        let keys: Vec<_> = (0..3).map(|_| CryptoEngine::generate_keypair().unwrap()).collect();
+        let rotations: Vec<_> = keys
+            .windows(2)
+            .map(|pair| {
+                let mut rotation = KeyRotation {
+                    previous_key: pair[0].1.clone(),
+                    new_key: pair[1].1.clone(),
+                    rotated_at: chrono::Utc::now().to_rfc3339(),
+                    signature: String::new(),
+                };
+                rotation.signature =
+                    CryptoEngine::sign(&pair[0].0, rotation.signing_payload().as_bytes()).unwrap();
+                rotation
+            })
+            .collect();
+        let (first_sk, first_pk) = &keys[0];
+        let second_pk = &keys[1].1;
+        let (current_sk, current_pk) = &keys[2];
+
+        let fetch = |h: String| {
+            let resolved = ResolvedHandle {
+                previous_keys: crate::core::network::rotation_chain(current_pk, &rotations),
+                ..resolved_with_key(&h, current_pk)
+            };
+            async move { Ok(resolved) }
+        };
+
+        let fresh = resolve_cached(&storage, "alice", 300, false, fetch).await.unwrap();
+        assert_eq!(fresh.public_key, *current_pk);
+        assert_eq!(fresh.previous_keys, vec![second_pk.clone(), first_pk.clone()]);
+
+        // The chain survives the cache round trip
+        let cached = resolve_cached(&storage, "alice", 300, false, fetch).await.unwrap();
+        assert!(cached.from_cache);
+        assert_eq!(cached.previous_keys, fresh.previous_keys);
+
+        let message = b"still @alice";
+
+        let current_sig = CryptoEngine::sign(current_sk, message).unwrap();
+        let current = verify_by_handle(&storage, "alice", message, &current_sig, None, 300, false, fetch)
+            .await
+            .unwrap();
+        assert!(current.valid);
+        assert!(!current.signed_by_previous_key);
+
+        // Signed under the original key, two rotations ago
+        let first_sig = CryptoEngine::sign(first_sk, message).unwrap();
+        let oldest = verify_by_handle(&storage, "alice", message, &first_sig, Some(second_pk), 300, false, fetch)
+            .await
+            .unwrap();
+        assert!(oldest.valid);
+        assert!(oldest.signed_by_previous_key);
+        assert!(!oldest.key_rotated);
+        assert_eq!(oldest.public_key, *current_pk);
+        assert_eq!(oldest.matches_expected, Some(true));
+
+        // A key outside the chain still fails
+        let (stranger_sk, _) = CryptoEngine::generate_keypair().unwrap();
+        let stranger_sig = CryptoEngine::sign(&stranger_sk, message).unwrap();
+        let stranger = verify_by_handle(&storage, "alice", message, &stranger_sig, None, 300, false, fetch)
+            .await
+            .unwrap();
+        assert!(!stranger.valid);
+        assert!(!stranger.signed_by_previous_key);
+    }
+}
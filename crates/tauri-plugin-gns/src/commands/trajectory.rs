@@ -8,11 +8,12 @@ use crate::{
     error::{Error, Result},
     models::breadcrumb::{
         Breadcrumb, BreadcrumbBlock, BreadcrumbQuery, LocationSource,
-        CollectionStatus, EpochHeader, SignedEpoch,
+        CollectionStatus, EpochHeader, EpochProof, PeerEpochVerification,
     },
     GnsState,
 };
-use chrono::{Utc, DateTime, Duration};
+use crate::core::epoch_publisher::epoch_hash;
+use chrono::Utc;
 use h3o::{CellIndex, LatLng, Resolution};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -64,6 +65,11 @@ pub async fn stop_collection(
     state: State<'_, GnsState>,
 ) -> Result<CollectionStatus> {
     COLLECTION_ACTIVE.store(false, Ordering::SeqCst);
+
+    // Nothing more will arrive to fill the batch
+    let storage = state.storage.read().await;
+    state.breadcrumb_buffer.flush(&storage)?;
+    drop(storage);
     
     log::info!("Stopped breadcrumb collection");
     
@@ -85,8 +91,8 @@ pub async fn get_collection_status(
     
     let (total_count, pending_count) = if let Some(id) = identity {
         let total = storage.get_breadcrumb_count(&id.public_key)?;
-        // For now, estimate pending as total (would need to track published state)
-        (total as u32, 0u32)
+        let (pending, _) = storage.get_unpublished_breadcrumb_stats(&id.public_key)?;
+        (total, pending)
     } else {
         (0, 0)
     };
@@ -159,7 +165,7 @@ pub async fn collect_breadcrumb(
     let cell = latlng.to_cell(resolution);
     
     // Get previous breadcrumb hash for chaining
-    let prev_hash = get_last_breadcrumb_hash(&*storage, &identity.public_key)?
+    let prev_hash = get_last_breadcrumb_hash(&storage, &state.breadcrumb_buffer, &identity.public_key)?
         .unwrap_or_else(|| "genesis".to_string());
     
     // Create breadcrumb
@@ -186,8 +192,8 @@ pub async fn collect_breadcrumb(
         published: false,
     };
     
-    // Save to storage
-    storage.save_breadcrumb(&identity.public_key, &breadcrumb)?;
+    // Queue for the next batched write
+    state.breadcrumb_buffer.push(&storage, &identity.public_key, breadcrumb.clone())?;
     
     log::debug!("Collected breadcrumb: {} at {}", &breadcrumb.id[..8], breadcrumb.h3_index);
     
@@ -198,106 +204,52 @@ pub async fn collect_breadcrumb(
 ///
 /// An epoch bundles breadcrumbs into a verifiable package:
 /// - Groups breadcrumbs into blocks
-/// - Calculates Merkle root for each block
-/// - Creates epoch header with summary
+/// - Calculates the Merkle root over them
+/// - Creates epoch header chained to the previous epoch
 /// - Signs and publishes to GNS network
 ///
+/// The breadcrumbs are then marked published, so neither this nor an
+/// automatic publish includes them again.
+///
 /// Requirements:
-/// - At least 100 unpublished breadcrumbs
-/// - Valid hash chain
+/// - At least `min_breadcrumbs_for_epoch` unpublished breadcrumbs
 #[command]
 pub async fn publish_epoch(
     state: State<'_, GnsState>,
 ) -> Result<EpochHeader> {
-    let storage = state.storage.write().await;
-    let network = &state.network;
-    
-    // Get active identity
-    let identities = storage.list_identities()?;
-    let identity = identities.iter()
-        .find(|i| i.is_default)
-        .or_else(|| identities.first())
-        .ok_or(Error::IdentityNotFound("No identity found".into()))?;
-    
-    let secret_key = storage.get_secret_key(&identity.public_key)?
-        .ok_or(Error::IdentityNotFound("Secret key not found".into()))?;
-    
-    // Get unpublished breadcrumbs
-    let breadcrumb_count = storage.get_breadcrumb_count(&identity.public_key)?;
-    
-    if breadcrumb_count < state.config.min_breadcrumbs_for_epoch as u32 {
-        return Err(Error::InsufficientBreadcrumbs(format!(
-            "Need {} breadcrumbs, have {}",
-            state.config.min_breadcrumbs_for_epoch, breadcrumb_count
-        )));
-    }
-    
-    // In a full implementation:
-    // 1. Fetch unpublished breadcrumbs from storage
-    // 2. Group into blocks of ~10 breadcrumbs each
-    // 3. Calculate Merkle root for each block
-    // 4. Create epoch header
-    // 5. Sign and publish
-    
-    let epoch_index = breadcrumb_count / (state.config.min_breadcrumbs_for_epoch as u32);
-    let prev_epoch_hash = get_last_epoch_hash(&*storage, &identity.public_key)?;
-    
-    // Create epoch header
-    let merkle_root = crate::core::CryptoEngine::sha256(
-        format!("epoch-{}-{}", identity.public_key, epoch_index).as_bytes()
-    );
-    
-    let epoch = EpochHeader {
-        identity: identity.public_key.clone(),
-        epoch_index: epoch_index as u32,
-        start_time: (Utc::now() - Duration::days(7)).to_rfc3339(), // Placeholder
-        end_time: Utc::now().to_rfc3339(),
-        merkle_root: merkle_root.clone(),
-        block_count: (breadcrumb_count as u32 / 10).max(1),
-        prev_epoch_hash,
-        signature: String::new(), // Will be set after signing
-        epoch_hash: String::new(), // Will be set after hashing
+    let identity_pk = {
+        let storage = state.storage.read().await;
+        let identities = storage.list_identities()?;
+        identities.iter()
+            .find(|i| i.is_default)
+            .or_else(|| identities.first())
+            .map(|i| i.public_key.clone())
+            .ok_or(Error::IdentityNotFound("No identity found".into()))?
     };
-    
-    // Calculate epoch hash
-    use chrono::DateTime as ChronoDateTime;
-    let start_dt = ChronoDateTime::parse_from_rfc3339(&epoch.start_time)
-        .map_err(|_| Error::InvalidInput("Invalid start time".into()))?;
-    let end_dt = ChronoDateTime::parse_from_rfc3339(&epoch.end_time)
-        .map_err(|_| Error::InvalidInput("Invalid end time".into()))?;
-    
-    let epoch_data = format!(
-        "{}|{}|{}|{}|{}|{}",
-        epoch.identity,
-        epoch.epoch_index,
-        start_dt.timestamp(),
-        end_dt.timestamp(),
-        epoch.merkle_root,
-        epoch.prev_epoch_hash.as_deref().unwrap_or("genesis")
-    );
-    let epoch_hash = crate::core::CryptoEngine::sha256(epoch_data.as_bytes());
-    
-    // Sign the epoch hash
-    let signature = crate::core::CryptoEngine::sign(&secret_key, epoch_hash.as_bytes())?;
-    
-    let signed_epoch = EpochHeader {
-        epoch_hash: epoch_hash.clone(),
-        signature: signature.clone(),
-        ..epoch
-    };
-    
-    // Publish to network
-    let signed_wrapper = SignedEpoch {
-        pk_root: identity.public_key.clone(),
-        epoch: signed_epoch.clone(),
-        signature,
-    };
-    
-    network.publish_epoch(&signed_wrapper).await?;
-    
-    log::info!("Published epoch {} with {} blocks", signed_epoch.epoch_index, signed_epoch.block_count);
-    
-    Ok(signed_epoch)
+
+    state
+        .epoch_publisher
+        .publish(&state.storage, &state.breadcrumb_buffer, &state.network, &identity_pk)
+        .await
+}
+
+/// Write buffered breadcrumbs to storage now.
+///
+/// Collected breadcrumbs are written in batches. `publish_epoch` flushes on
+/// its own; call this before anything else that reads breadcrumbs from
+/// storage, or before the app is suspended. Returns how many were written.
+#[command]
+pub async fn flush_breadcrumbs(
+    state: State<'_, GnsState>,
+) -> Result<u32> {
+    let storage = state.storage.read().await;
+    let written = state.breadcrumb_buffer.flush(&storage)?;
+
+    if written > 0 {
+        log::info!("Flushed {} breadcrumbs", written);
+    }
+
+    Ok(written as u32)
 }
 
 /// Get epoch history for the active identity.
@@ -321,24 +273,105 @@ pub async fn get_epochs(
     Ok(epochs)
 }
 
-// Helper functions
+/// Independently confirm a peer's claimed trajectory milestone: that
+/// `breadcrumb` was signed by `public_key` and published in its epoch
+/// `epoch_index`.
+///
+/// The relay supplies the epoch header and inclusion proof but is not
+/// trusted; the header must carry the peer's own signature.
+#[command]
+pub async fn verify_peer_epoch(
+    state: State<'_, GnsState>,
+    public_key: String,
+    epoch_index: u32,
+    breadcrumb: Breadcrumb,
+) -> Result<PeerEpochVerification> {
+    let proof = state
+        .network
+        .get_epoch_proof(&public_key, epoch_index, &breadcrumb.hash)
+        .await?;
 
-fn get_last_breadcrumb_hash(
-    storage: &crate::core::StorageManager,
-    identity_pk: &str,
+    let reason = peer_epoch_failure(&public_key, epoch_index, &breadcrumb, &proof)?;
+
+    if let Some(reason) = &reason {
+        log::warn!("Epoch {} of {} failed verification: {}", epoch_index, public_key.get(..16).unwrap_or(&public_key), reason);
+    }
+
+    Ok(PeerEpochVerification {
+        verified: reason.is_none(),
+        epoch: proof.epoch,
+        reason,
+    })
+}
+
+/// The first reason `breadcrumb` and `proof` fail to show that
+/// `public_key` published the breadcrumb in epoch `epoch_index`
+fn peer_epoch_failure(
+    public_key: &str,
+    epoch_index: u32,
+    breadcrumb: &Breadcrumb,
+    proof: &EpochProof,
 ) -> Result<Option<String>> {
-    // In a full implementation, query the most recent breadcrumb
-    // For now, return None (genesis)
-    Ok(None)
+    let reason = if !proof.epoch.identity.eq_ignore_ascii_case(public_key)
+        || proof.epoch.epoch_index != epoch_index
+    {
+        Some("Relay returned a different epoch".to_string())
+    } else if !verify_epoch_header(&proof.epoch)? {
+        Some("Epoch header is not signed by this identity".to_string())
+    } else if !breadcrumb.verify_hash() {
+        Some("Breadcrumb hash does not match its contents".to_string())
+    } else if !crate::core::CryptoEngine::verify(public_key, breadcrumb.hash.as_bytes(), &breadcrumb.signature)
+        .unwrap_or(false)
+    {
+        Some("Breadcrumb is not signed by this identity".to_string())
+    } else if !verify_epoch_membership(breadcrumb, proof, &proof.epoch.merkle_root) {
+        Some("Breadcrumb is not included in this epoch".to_string())
+    } else {
+        None
+    };
+    Ok(reason)
+}
+
+/// Check that `proof` links `breadcrumb`'s hash to `root`
+pub fn verify_epoch_membership(breadcrumb: &Breadcrumb, proof: &EpochProof, root: &str) -> bool {
+    if proof.breadcrumb_hash != breadcrumb.hash {
+        return false;
+    }
+
+    let computed = proof.path.iter().fold(breadcrumb.hash.clone(), |node, step| {
+        let combined = match (&step.sibling, step.sibling_is_left) {
+            (Some(sibling), true) => format!("{}{}", sibling, node),
+            (Some(sibling), false) => format!("{}{}", node, sibling),
+            (None, _) => node,
+        };
+        crate::core::CryptoEngine::sha256(combined.as_bytes())
+    });
+
+    computed.eq_ignore_ascii_case(root)
+}
+
+/// Check that an epoch header's hash matches its contents and is signed
+/// by the identity it names
+pub fn verify_epoch_header(epoch: &EpochHeader) -> Result<bool> {
+    if epoch_hash(epoch)? != epoch.epoch_hash {
+        return Ok(false);
+    }
+    crate::core::CryptoEngine::verify(&epoch.identity, epoch.epoch_hash.as_bytes(), &epoch.signature)
 }
 
-fn get_last_epoch_hash(
+// Helper functions
+
+/// Hash the next breadcrumb chains onto: the newest queued one, or else
+/// the newest saved one
+fn get_last_breadcrumb_hash(
     storage: &crate::core::StorageManager,
+    buffer: &crate::core::BreadcrumbBuffer,
     identity_pk: &str,
 ) -> Result<Option<String>> {
-    // In a full implementation, query the most recent epoch
-    // For now, return None (genesis)
-    Ok(None)
+    if let Some(hash) = buffer.last_hash(identity_pk)? {
+        return Ok(Some(hash));
+    }
+    storage.get_last_breadcrumb_hash(identity_pk)
 }
 
 #[cfg(test)]
@@ -360,6 +393,107 @@ mod tests {
         assert!(index_str.starts_with("87")); // Resolution 7 cells start with 87
     }
     
+    /// A peer's signed epoch over `count` breadcrumbs, with the proof for
+    /// breadcrumb `index`
+    fn signed_epoch(count: usize, index: usize) -> (Vec<Breadcrumb>, EpochProof) {
+        let (secret_key, public_key) = crate::core::CryptoEngine::generate_keypair().unwrap();
+        let breadcrumbs: Vec<Breadcrumb> = (0..count)
+            .map(|i| {
+                let mut breadcrumb = Breadcrumb {
+                    id: i.to_string(),
+                    h3_index: format!("h3_{}", i),
+                    h3_resolution: 10,
+                    timestamp: "2025-01-01T00:00:00Z".to_string(),
+                    prev_hash: None,
+                    signature: String::new(),
+                    hash: String::new(),
+                    source: LocationSource::Gps,
+                    accuracy: None,
+                    published: true,
+                };
+                breadcrumb.hash = breadcrumb.calculate_hash();
+                breadcrumb.signature = crate::core::CryptoEngine::sign(&secret_key, breadcrumb.hash.as_bytes()).unwrap();
+                breadcrumb
+            })
+            .collect();
+
+        let mut epoch = EpochHeader {
+            identity: public_key,
+            epoch_index: 3,
+            start_time: "2025-01-01T00:00:00Z".to_string(),
+            end_time: "2025-01-08T00:00:00Z".to_string(),
+            merkle_root: BreadcrumbBlock::calculate_merkle_root(&breadcrumbs),
+            block_count: 1,
+            prev_epoch_hash: None,
+            signature: String::new(),
+            epoch_hash: String::new(),
+        };
+        epoch.epoch_hash = epoch_hash(&epoch).unwrap();
+        epoch.signature = crate::core::CryptoEngine::sign(&secret_key, epoch.epoch_hash.as_bytes()).unwrap();
+
+        let proof = EpochProof {
+            epoch,
+            breadcrumb_hash: breadcrumbs[index].hash.clone(),
+            path: BreadcrumbBlock::merkle_proof(&breadcrumbs, index).unwrap(),
+        };
+        (breadcrumbs, proof)
+    }
+
+    #[test]
+    fn test_valid_inclusion_proof() {
+        // Odd count, so some nodes are hashed without a sibling
+        for index in 0..5 {
+            let (breadcrumbs, proof) = signed_epoch(5, index);
+            assert!(verify_epoch_header(&proof.epoch).unwrap());
+            assert!(verify_epoch_membership(&breadcrumbs[index], &proof, &proof.epoch.merkle_root));
+            let public_key = proof.epoch.identity.clone();
+            assert_eq!(peer_epoch_failure(&public_key, 3, &breadcrumbs[index], &proof).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn test_breadcrumb_must_match_its_hash() {
+        let (breadcrumbs, proof) = signed_epoch(4, 2);
+        let public_key = proof.epoch.identity.clone();
+
+        // Same signed hash, different location
+        let mut moved = breadcrumbs[2].clone();
+        moved.h3_index = "h3_elsewhere".to_string();
+        assert!(verify_epoch_membership(&moved, &proof, &proof.epoch.merkle_root));
+        assert_eq!(
+            peer_epoch_failure(&public_key, 3, &moved, &proof).unwrap().as_deref(),
+            Some("Breadcrumb hash does not match its contents")
+        );
+    }
+
+    #[test]
+    fn test_forged_inclusion_proof() {
+        let (breadcrumbs, proof) = signed_epoch(4, 1);
+        let root = proof.epoch.merkle_root.clone();
+
+        // A breadcrumb that was never published
+        let mut outsider = breadcrumbs[1].clone();
+        outsider.hash = crate::core::CryptoEngine::sha256(b"never published");
+        let mut forged = proof.clone();
+        forged.breadcrumb_hash = outsider.hash.clone();
+        assert!(!verify_epoch_membership(&outsider, &forged, &root));
+
+        // Tampered path
+        let mut forged = proof.clone();
+        forged.path[0].sibling = Some(crate::core::CryptoEngine::sha256(b"tampered"));
+        assert!(!verify_epoch_membership(&breadcrumbs[1], &forged, &root));
+
+        // Sibling on the wrong side
+        let mut forged = proof.clone();
+        forged.path[0].sibling_is_left = !forged.path[0].sibling_is_left;
+        assert!(!verify_epoch_membership(&breadcrumbs[1], &forged, &root));
+
+        // A relay swapping in its own root must also re-sign the header
+        let mut forged = proof;
+        forged.epoch.merkle_root = crate::core::CryptoEngine::sha256(b"relay root");
+        assert!(!verify_epoch_header(&forged.epoch).unwrap());
+    }
+
     #[test]
     fn test_collection_state() {
         // Initially not collecting
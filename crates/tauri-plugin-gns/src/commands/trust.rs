@@ -4,11 +4,24 @@
 
 use tauri::{command, State};
 use crate::{
+    commands::resolver::resolve_cached,
+    config::SourceWeights,
+    core::{CryptoEngine, NetworkClient, StorageManager},
     error::{Error, Result},
-    models::trust::{TrustScore, TrustComponents, TrustTier, TrustVerification, TrustCheck, TrustRequirements},
+    models::trust::{
+        LocalTrustEstimate, LocalTrustSource, NetworkTrust, NetworkTrustReport,
+        SourceContribution, TrustScore, TrustComponents, TrustTier, TrustVerification, TrustCheck, TrustRequirements,
+    },
+    models::{LocationSource, ResolvedHandle, SignedRecord},
     GnsState,
 };
 use chrono::{Utc, Duration};
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+
+/// How far the relay's trust score may drift from the one in the signed
+/// record before it's flagged
+const RECORD_TRUST_SCORE_TOLERANCE: f64 = 5.0;
 
 /// Get the trust score for the active identity.
 ///
@@ -29,59 +42,18 @@ pub async fn get_trust_score(state: State<'_, GnsState>) -> Result<TrustScore> {
         .or_else(|| identities.first())
         .ok_or(Error::IdentityNotFound("No identity found".into()))?;
     
-    // Calculate trust score from stored metrics
-    let breadcrumb_count = identity.breadcrumb_count as u32;
-    let trust_score = identity.trust_score;
-    
-    // Note: Account age calculation removed because Identity.created_at is String
-    // In production, parse the ISO 8601 string to DateTime and calculate
-    let account_age_days = 30u32; // Placeholder - would calculate from created_at string
-    
-    // For now, estimate other metrics from breadcrumb count
-    // In production, these would be calculated from actual breadcrumb data
-    let estimated_unique_locations = (breadcrumb_count as f32 * 0.3).min(1000.0) as u32;
-    let estimated_epoch_count = breadcrumb_count / 100; // ~100 breadcrumbs per epoch
-    
-    // Calculate component scores (0-100 each)
-    let trajectory_quality = calculate_trajectory_quality(breadcrumb_count, account_age_days);
-    let temporal_consistency = calculate_temporal_consistency(breadcrumb_count, account_age_days);
-    let chain_integrity = 100.0; // Assume valid chains (verified on collection)
-    let epoch_reliability = if estimated_epoch_count > 0 { 80.0 } else { 0.0 };
-    let geographic_diversity = calculate_geographic_diversity(estimated_unique_locations);
-    
-    let components = TrustComponents {
-        trajectory_quality: trajectory_quality as f64,
-        temporal_consistency: temporal_consistency as f64,
-        chain_integrity: chain_integrity as f64,
-        epoch_reliability: epoch_reliability as f64,
-        geographic_diversity: geographic_diversity as f64,
-    };
-    
-    // Weighted average of components (convert to f64)
-    let calculated_score = (
-        trajectory_quality as f64 * 0.25 +
-        temporal_consistency as f64 * 0.20 +
-        chain_integrity as f64 * 0.25 +
-        epoch_reliability as f64 * 0.15 +
-        geographic_diversity as f64 * 0.15
-    ).min(100.0);
-    
-    // Use stored score if available, otherwise use calculated
-    let final_score = if trust_score > 0.0 { trust_score } else { calculated_score };
-    
-    Ok(TrustScore {
-        score: final_score,
-        tier: TrustTier::from_score(final_score),
-        components,
-        calculated_at: Utc::now().to_rfc3339(),
-        breadcrumb_count,
-        account_age_days,
-        unique_locations: estimated_unique_locations,
-        epoch_count: estimated_epoch_count,
-    })
+    get_trust_score_for_identity(
+        &storage,
+        &identity.public_key,
+        identity.breadcrumb_count as u32,
+        &state.config.breadcrumb_source_weights,
+    )
 }
 
 /// Get detailed trust score breakdown with all components.
+///
+/// Includes how many breadcrumbs each location source contributed and how
+/// much of that counted after source weighting.
 #[command]
 pub async fn get_trust_details(state: State<'_, GnsState>) -> Result<TrustScore> {
     // Same as get_trust_score but guaranteed to include all component details
@@ -120,7 +92,12 @@ pub async fn verify_identity(
     let reqs = requirements.unwrap_or_else(TrustRequirements::for_handle_claim);
     
     // Get current trust score
-    let trust_score = get_trust_score_for_identity(&identity)?;
+    let trust_score = get_trust_score_for_identity(
+        &storage,
+        &identity.public_key,
+        identity.breadcrumb_count as u32,
+        &state.config.breadcrumb_source_weights,
+    )?;
     
     // Run verification checks
     let mut checks = Vec::new();
@@ -143,14 +120,14 @@ pub async fn verify_identity(
     // Check minimum breadcrumbs
     let min_breadcrumbs = reqs.min_breadcrumbs;
     if min_breadcrumbs > 0 {
-        let passed = trust_score.breadcrumb_count >= min_breadcrumbs;
+        let passed = trust_score.weighted_breadcrumb_count >= min_breadcrumbs;
         if !passed { all_passed = false; }
         checks.push(TrustCheck {
             name: "Minimum Breadcrumbs".to_string(),
             passed,
-            details: format!("Required: {}, Actual: {}", min_breadcrumbs, trust_score.breadcrumb_count),
+            details: format!("Required: {}, Actual: {}", min_breadcrumbs, trust_score.weighted_breadcrumb_count),
             required: Some(min_breadcrumbs.to_string()),
-            actual: Some(trust_score.breadcrumb_count.to_string()),
+            actual: Some(trust_score.weighted_breadcrumb_count.to_string()),
         });
     }
     
@@ -206,6 +183,163 @@ pub async fn verify_identity(
     })
 }
 
+/// Get the network-wide trust for a handle or public key.
+///
+/// Fetches the relay's aggregate score, breadcrumb and epoch counts and
+/// verification status, checks them against the identity's signed record,
+/// and returns them next to whatever this device can tell on its own, so
+/// profile views can show the network's figures and any disagreement.
+/// Results are cached for `cache_ttl_seconds` unless `force` is set.
+#[command]
+pub async fn get_network_trust(
+    state: State<'_, GnsState>,
+    handle_or_key: String,
+    force: Option<bool>,
+) -> Result<NetworkTrustReport> {
+    let target = handle_or_key.trim().trim_start_matches('@').to_lowercase();
+    let ttl = state.config.cache_ttl_seconds;
+
+    let (public_key, resolved) = if is_public_key(&target) {
+        (target, None)
+    } else {
+        let network = state.network.clone();
+        let resolved = resolve_cached(&state.storage, &target, ttl, false, |h| async move {
+            network.resolve_handle(&h).await
+        })
+        .await?;
+        (resolved.public_key.to_lowercase(), Some(resolved))
+    };
+
+    let mut report = network_trust_report(
+        &state.storage,
+        &state.network,
+        &public_key,
+        ttl,
+        force.unwrap_or(false),
+    )
+    .await?;
+
+    let storage = state.storage.read().await;
+    report.local = local_trust_estimate(
+        &storage,
+        &public_key,
+        resolved.as_ref(),
+        &state.config.breadcrumb_source_weights,
+    )?;
+
+    Ok(report)
+}
+
+fn is_public_key(s: &str) -> bool {
+    s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// The relay's trust for `public_key`, checked against its signed record,
+/// from cache if younger than `ttl_seconds`. Has no local estimate.
+pub(crate) async fn network_trust_report(
+    storage: &RwLock<StorageManager>,
+    network: &NetworkClient,
+    public_key: &str,
+    ttl_seconds: u64,
+    force: bool,
+) -> Result<NetworkTrustReport> {
+    if !force {
+        if let Some(cached) = storage.read().await.get_cached_network_trust(public_key, ttl_seconds)? {
+            return Ok(cached);
+        }
+    }
+
+    let (trust, record) = network.get_network_trust(public_key).await?;
+    let discrepancies = record_discrepancies(&trust, record.as_ref());
+    if !discrepancies.is_empty() {
+        log::warn!(
+            "Network trust for {} disagrees with its record: {}",
+            public_key.get(..16).unwrap_or(public_key),
+            discrepancies.join("; ")
+        );
+    }
+
+    let report = NetworkTrustReport {
+        network: trust,
+        consistent_with_record: discrepancies.is_empty(),
+        discrepancies,
+        local: None,
+        from_cache: false,
+    };
+    storage.read().await.cache_network_trust(&report)?;
+
+    Ok(report)
+}
+
+/// Where the relay's figures and the identity's own signed record
+/// disagree. The record is written by the identity and may lag behind,
+/// so the relay counting more than it is fine; counting fewer is not.
+fn record_discrepancies(trust: &NetworkTrust, record: Option<&SignedRecord>) -> Vec<String> {
+    let Some(record) = record else {
+        return vec!["Relay sent no signed record".to_string()];
+    };
+
+    let owner = &record.record_json.identity;
+    if !owner.eq_ignore_ascii_case(&trust.public_key) || !record.pk_root.eq_ignore_ascii_case(&trust.public_key) {
+        return vec![format!("Record belongs to {}", owner)];
+    }
+    let signed = serde_json::to_string(&record.record_json)
+        .ok()
+        .and_then(|json| CryptoEngine::verify(&record.pk_root, json.as_bytes(), &record.signature).ok())
+        .unwrap_or(false);
+    if !signed {
+        return vec!["Record signature is invalid".to_string()];
+    }
+
+    let mut discrepancies = Vec::new();
+    if trust.breadcrumb_count < record.record_json.breadcrumb_count {
+        discrepancies.push(format!(
+            "Relay counts {} breadcrumbs, record has {}",
+            trust.breadcrumb_count, record.record_json.breadcrumb_count
+        ));
+    }
+    let epoch_roots = record.record_json.epoch_roots.len() as u32;
+    if trust.epoch_count < epoch_roots {
+        discrepancies.push(format!("Relay counts {} epochs, record has {}", trust.epoch_count, epoch_roots));
+    }
+    if (trust.trust_score - record.record_json.trust_score).abs() > RECORD_TRUST_SCORE_TOLERANCE {
+        discrepancies.push(format!(
+            "Relay trust score {:.1} is far from the record's {:.1}",
+            trust.trust_score, record.record_json.trust_score
+        ));
+    }
+    discrepancies
+}
+
+/// Trust as far as this device knows it: computed for its own
+/// identities, otherwise what the handle resolved to last
+fn local_trust_estimate(
+    storage: &StorageManager,
+    public_key: &str,
+    resolved: Option<&ResolvedHandle>,
+    weights: &SourceWeights,
+) -> Result<Option<LocalTrustEstimate>> {
+    if let Some(identity) = storage.get_identity(public_key)? {
+        let score = get_trust_score_for_identity(
+            storage,
+            &identity.public_key,
+            identity.breadcrumb_count as u32,
+            weights,
+        )?;
+        return Ok(Some(LocalTrustEstimate {
+            trust_score: score.score,
+            breadcrumb_count: score.breadcrumb_count,
+            source: LocalTrustSource::Computed,
+        }));
+    }
+
+    Ok(resolved.map(|r| LocalTrustEstimate {
+        trust_score: r.trust_score,
+        breadcrumb_count: r.breadcrumb_count,
+        source: LocalTrustSource::HandleCache,
+    }))
+}
+
 // Helper functions for score calculation
 
 fn calculate_trajectory_quality(breadcrumb_count: u32, account_age_days: u32) -> f32 {
@@ -255,18 +389,89 @@ fn calculate_geographic_diversity(unique_locations: u32) -> f32 {
     }
 }
 
-fn get_trust_score_for_identity(identity: &crate::models::Identity) -> Result<TrustScore> {
-    let breadcrumb_count = identity.breadcrumb_count as u32;
+/// Breadcrumb totals after applying per-source trust weights
+struct WeightedTrajectory {
+    /// Sum of breadcrumbs times their source weight
+    breadcrumbs: f64,
+    /// Distinct cells, each counted at the best weight it was seen with
+    unique_locations: f64,
+    sources: Vec<SourceContribution>,
+}
+
+/// Apply source weights to per-(cell, source) breadcrumb counts
+fn weigh_breadcrumbs(cells: &[(String, LocationSource, u32)], weights: &SourceWeights) -> WeightedTrajectory {
+    let mut per_source: HashMap<LocationSource, (u32, u32)> = HashMap::new();
+    let mut best_per_cell: HashMap<&str, f64> = HashMap::new();
+
+    for (cell, source, count) in cells {
+        // Rows are already grouped by (cell, source), so each is a new cell for its source
+        let totals = per_source.entry(*source).or_default();
+        totals.0 += count;
+        totals.1 += 1;
+
+        let best = best_per_cell.entry(cell.as_str()).or_insert(0.0);
+        *best = best.max(weights.weight(*source));
+    }
+
+    let mut sources: Vec<SourceContribution> = per_source
+        .into_iter()
+        .map(|(source, (breadcrumb_count, unique_locations))| {
+            let weight = weights.weight(source);
+            SourceContribution {
+                source,
+                breadcrumb_count,
+                unique_locations,
+                weight,
+                weighted_breadcrumbs: breadcrumb_count as f64 * weight,
+            }
+        })
+        .collect();
+    sources.sort_by(|a, b| {
+        b.weighted_breadcrumbs
+            .total_cmp(&a.weighted_breadcrumbs)
+            .then_with(|| a.source.as_str().cmp(b.source.as_str()))
+    });
+
+    WeightedTrajectory {
+        breadcrumbs: sources.iter().map(|s| s.weighted_breadcrumbs).sum(),
+        unique_locations: best_per_cell.values().sum(),
+        sources,
+    }
+}
+
+/// Score an identity from its stored breadcrumbs, weighted by source
+pub(crate) fn get_trust_score_for_identity(
+    storage: &StorageManager,
+    public_key: &str,
+    breadcrumb_count: u32,
+    weights: &SourceWeights,
+) -> Result<TrustScore> {
+    let cells = storage.get_breadcrumb_cell_sources(public_key)?;
     // Account age calculation removed - created_at is String
     let account_age_days = 30u32; // Placeholder
-    let estimated_unique_locations = (breadcrumb_count as f32 * 0.3).min(1000.0) as u32;
-    let estimated_epoch_count = breadcrumb_count / 100;
+
+    Ok(calculate_trust_score(
+        breadcrumb_count,
+        account_age_days,
+        weigh_breadcrumbs(&cells, weights),
+    ))
+}
+
+fn calculate_trust_score(
+    breadcrumb_count: u32,
+    account_age_days: u32,
+    trajectory: WeightedTrajectory,
+) -> TrustScore {
+    // Weak sources only count for part of a breadcrumb (or nothing)
+    let weighted_count = trajectory.breadcrumbs.round() as u32;
+    let unique_locations = trajectory.unique_locations.round() as u32;
+    let epoch_count = breadcrumb_count / 100; // ~100 breadcrumbs per epoch
     
-    let trajectory_quality = calculate_trajectory_quality(breadcrumb_count, account_age_days);
-    let temporal_consistency = calculate_temporal_consistency(breadcrumb_count, account_age_days);
-    let chain_integrity = 100.0;
-    let epoch_reliability = if estimated_epoch_count > 0 { 80.0 } else { 0.0 };
-    let geographic_diversity = calculate_geographic_diversity(estimated_unique_locations);
+    let trajectory_quality = calculate_trajectory_quality(weighted_count, account_age_days);
+    let temporal_consistency = calculate_temporal_consistency(weighted_count, account_age_days);
+    let chain_integrity = 100.0; // Assume valid chains (verified on collection)
+    let epoch_reliability = if epoch_count > 0 { 80.0 } else { 0.0 };
+    let geographic_diversity = calculate_geographic_diversity(unique_locations);
     
     let components = TrustComponents {
         trajectory_quality: trajectory_quality as f64,
@@ -276,26 +481,25 @@ fn get_trust_score_for_identity(identity: &crate::models::Identity) -> Result<Tr
         geographic_diversity: geographic_diversity as f64,
     };
     
-    let score = if identity.trust_score > 0.0 {
-        identity.trust_score
-    } else {
-        (trajectory_quality as f64 * 0.25 +
-         temporal_consistency as f64 * 0.20 +
-         chain_integrity as f64 * 0.25 +
-         epoch_reliability as f64 * 0.15 +
-         geographic_diversity as f64 * 0.15).min(100.0)
-    };
+    // Always recomputed, so a stored score can't stand in for weak sources
+    let score = (trajectory_quality as f64 * 0.25 +
+        temporal_consistency as f64 * 0.20 +
+        chain_integrity as f64 * 0.25 +
+        epoch_reliability as f64 * 0.15 +
+        geographic_diversity as f64 * 0.15).min(100.0);
     
-    Ok(TrustScore {
+    TrustScore {
         score,
         tier: TrustTier::from_score(score),
         components,
         calculated_at: Utc::now().to_rfc3339(),
         breadcrumb_count,
+        weighted_breadcrumb_count: weighted_count,
         account_age_days,
-        unique_locations: estimated_unique_locations,
-        epoch_count: estimated_epoch_count,
-    })
+        unique_locations,
+        epoch_count,
+        sources: trajectory.sources,
+    }
 }
 
 #[cfg(test)]
@@ -344,4 +548,115 @@ mod tests {
         assert!(matches!(TrustTier::from_score(70.0), TrustTier::Trusted));
         assert!(matches!(TrustTier::from_score(90.0), TrustTier::Verified));
     }
+
+    fn cells(source: LocationSource, count: usize) -> Vec<(String, LocationSource, u32)> {
+        (0..count).map(|i| (format!("87283472{:07x}", i), source, 3)).collect()
+    }
+
+    #[test]
+    fn test_manual_breadcrumbs_score_lower_than_gps() {
+        let weights = SourceWeights::default();
+        let gps = calculate_trust_score(120, 30, weigh_breadcrumbs(&cells(LocationSource::Gps, 40), &weights));
+        let manual = calculate_trust_score(120, 30, weigh_breadcrumbs(&cells(LocationSource::Manual, 40), &weights));
+        let network = calculate_trust_score(120, 30, weigh_breadcrumbs(&cells(LocationSource::Network, 40), &weights));
+
+        assert!(manual.score < network.score);
+        assert!(network.score < gps.score);
+        assert_eq!(gps.unique_locations, 40);
+        assert_eq!(manual.unique_locations, 0);
+    }
+
+    #[test]
+    fn test_requirements_see_weighted_breadcrumbs() {
+        let weights = SourceWeights::default();
+        let gps = calculate_trust_score(120, 30, weigh_breadcrumbs(&cells(LocationSource::Gps, 40), &weights));
+        let manual = calculate_trust_score(120, 30, weigh_breadcrumbs(&cells(LocationSource::Manual, 40), &weights));
+
+        assert_eq!((gps.breadcrumb_count, gps.weighted_breadcrumb_count), (120, 120));
+        assert_eq!((manual.breadcrumb_count, manual.weighted_breadcrumb_count), (120, 0));
+    }
+
+    #[test]
+    fn test_source_contributions() {
+        let mut all = cells(LocationSource::Gps, 10);
+        all.extend(cells(LocationSource::Network, 4));
+        let trajectory = weigh_breadcrumbs(&all, &SourceWeights::default());
+
+        assert_eq!(trajectory.sources.len(), 2);
+        let gps = &trajectory.sources[0];
+        assert_eq!(gps.source, LocationSource::Gps);
+        assert_eq!((gps.breadcrumb_count, gps.unique_locations), (30, 10));
+        assert_eq!(gps.weighted_breadcrumbs, 30.0);
+        assert_eq!(trajectory.sources[1].weighted_breadcrumbs, 6.0);
+
+        // Cells seen by both sources count once, at the GPS weight
+        assert_eq!(trajectory.unique_locations, 10.0);
+    }
+
+    fn signed_record(secret_key: &str, public_key: &str, breadcrumb_count: u32, trust_score: f64) -> SignedRecord {
+        let record = crate::models::GnsRecord {
+            version: 1,
+            identity: public_key.to_string(),
+            handle: Some("alice".to_string()),
+            encryption_key: None,
+            modules: vec![],
+            endpoints: vec![],
+            epoch_roots: vec!["root-0".to_string(), "root-1".to_string()],
+            trust_score,
+            breadcrumb_count,
+            created_at: "2025-01-01T00:00:00Z".to_string(),
+            updated_at: "2025-01-01T00:00:00Z".to_string(),
+        };
+        let signature = CryptoEngine::sign(secret_key, serde_json::to_string(&record).unwrap().as_bytes()).unwrap();
+        SignedRecord { pk_root: public_key.to_string(), record_json: record, signature }
+    }
+
+    #[tokio::test]
+    async fn test_network_trust_is_checked_against_record_and_cached() {
+        use crate::core::{MockTransport, TransportResponse};
+        use reqwest::StatusCode;
+        use std::sync::Arc;
+
+        let (secret_key, public_key) = CryptoEngine::generate_keypair().unwrap();
+        let trust = |record: &SignedRecord, breadcrumb_count: u32| {
+            serde_json::json!({ "data": {
+                "identity": public_key, "trust_score": 60.0, "breadcrumb_count": breadcrumb_count,
+                "epoch_count": 2, "verified": true, "record": record,
+            }})
+        };
+
+        // Relay agrees with (or is ahead of) the record
+        let record = signed_record(&secret_key, &public_key, 500, 58.0);
+        let (network_trust, sent) = crate::core::network::parse_network_trust(&public_key, &trust(&record, 650)).unwrap();
+        assert!(record_discrepancies(&network_trust, sent.as_ref()).is_empty());
+
+        // Relay undercounts what the identity signed for
+        let dir = tempfile::tempdir().unwrap();
+        let storage = RwLock::new(StorageManager::new(&dir.path().join("test.db"), false).unwrap());
+        let transport = Arc::new(MockTransport::always(StatusCode::OK, trust(&record, 120)));
+        let network = NetworkClient::with_transport(&["https://relay.test".to_string()], transport.clone());
+
+        let report = network_trust_report(&storage, &network, &public_key, 300, false).await.unwrap();
+        assert!(!report.consistent_with_record);
+        assert_eq!(report.discrepancies, vec!["Relay counts 120 breadcrumbs, record has 500".to_string()]);
+        assert!(!report.from_cache);
+
+        // Served from cache within the TTL, refetched when forced
+        let cached = network_trust_report(&storage, &network, &public_key, 300, false).await.unwrap();
+        assert!(cached.from_cache);
+        assert_eq!(cached.discrepancies, report.discrepancies);
+        assert_eq!(transport.requests().len(), 1);
+        network_trust_report(&storage, &network, &public_key, 300, true).await.unwrap();
+        assert_eq!(transport.requests().len(), 2);
+
+        // A record someone else signed, or none at all
+        let (mallory_secret, _) = CryptoEngine::generate_keypair().unwrap();
+        let forged = signed_record(&mallory_secret, &public_key, 500, 58.0);
+        assert_eq!(record_discrepancies(&network_trust, Some(&forged)), vec!["Record signature is invalid".to_string()]);
+        assert_eq!(record_discrepancies(&network_trust, None).len(), 1);
+
+        // Trust score far from the record's
+        let inflated = NetworkTrust { trust_score: 95.0, ..network_trust };
+        assert_eq!(record_discrepancies(&inflated, Some(&record)).len(), 1);
+    }
 }
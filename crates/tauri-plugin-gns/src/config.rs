@@ -3,6 +3,7 @@
 //! Configuration options for the GNS plugin, loadable from
 //! `tauri.conf.json` or set programmatically via [`GnsBuilder`].
 
+use crate::models::LocationSource;
 use serde::{Deserialize, Serialize};
 
 /// GNS Plugin Configuration
@@ -18,7 +19,9 @@ use serde::{Deserialize, Serialize};
 ///       "relayUrls": ["https://relay.gns.earth"],
 ///       "encryptStorage": true,
 ///       "messageLimit": 50,
-///       "cacheTtlSeconds": 300
+///       "maxMessageBytes": 65536,
+///       "cacheTtlSeconds": 300,
+///       "breadcrumbSourceWeights": { "network": 0.25 }
 ///     }
 ///   }
 /// }
@@ -47,6 +50,39 @@ pub struct GnsConfig {
     #[serde(default = "default_message_limit")]
     pub message_limit: u32,
 
+    /// Largest message payload accepted, in bytes of plaintext JSON.
+    ///
+    /// Outgoing messages over the limit are rejected before encryption;
+    /// incoming envelopes over it are dropped unopened. Attachments should
+    /// travel as a blob reference in the payload, so only the reference
+    /// counts towards the limit, not the file itself.
+    ///
+    /// Default: `65536` (64 KiB)
+    #[serde(default = "default_max_message_bytes")]
+    pub max_message_bytes: usize,
+
+    /// Attach a proof-of-work stamp to outgoing messages.
+    ///
+    /// For relays that ask senders to spend some CPU on each message to
+    /// keep spam down. Costs about 2^difficulty SHA-256 hashes per message.
+    ///
+    /// Default: `false`
+    #[serde(default)]
+    pub require_pow: bool,
+
+    /// Leading zero bits a proof-of-work stamp starts out with.
+    ///
+    /// Default: `16`
+    #[serde(default = "default_pow_difficulty")]
+    pub pow_difficulty: u8,
+
+    /// Highest difficulty the client goes up to when a relay asks for more.
+    /// A relay asking for more than this gets an error instead.
+    ///
+    /// Default: `22`
+    #[serde(default = "default_max_pow_difficulty")]
+    pub max_pow_difficulty: u8,
+
     /// Cache time-to-live in seconds for handle resolutions.
     ///
     /// Default: `300` (5 minutes)
@@ -71,6 +107,14 @@ pub struct GnsConfig {
     #[serde(default = "default_min_breadcrumbs")]
     pub min_breadcrumbs_for_handle: u32,
 
+    /// How much a breadcrumb counts toward trust, by location source.
+    ///
+    /// A manually entered cell proves far less than a GPS fix, so weaker
+    /// sources count only partially (or not at all) toward the claim
+    /// threshold. See [`SourceWeights`] for the defaults.
+    #[serde(default)]
+    pub breadcrumb_source_weights: SourceWeights,
+
     /// H3 resolution for location quantization (privacy level).
     ///
     /// Higher values = more precise locations (less privacy).
@@ -109,6 +153,99 @@ pub struct GnsConfig {
     #[cfg_attr(docsrs, doc(cfg(feature = "trajectory")))]
     #[serde(default = "default_min_breadcrumbs_for_epoch")]
     pub min_breadcrumbs_for_epoch: usize,
+
+    /// Breadcrumbs written to storage per transaction.
+    ///
+    /// Default: `20`
+    #[cfg(feature = "trajectory")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "trajectory")))]
+    #[serde(default = "default_breadcrumb_batch_size")]
+    pub breadcrumb_batch_size: usize,
+
+    /// Longest a collected breadcrumb waits in memory before it is written,
+    /// in seconds. Bounds what a crash can lose.
+    ///
+    /// Default: `60`
+    #[cfg(feature = "trajectory")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "trajectory")))]
+    #[serde(default = "default_breadcrumb_flush_interval")]
+    pub breadcrumb_flush_interval: u64,
+
+    /// Breadcrumbs held in memory before collection is refused because
+    /// storage has fallen behind.
+    ///
+    /// Default: `1000`
+    #[cfg(feature = "trajectory")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "trajectory")))]
+    #[serde(default = "default_breadcrumb_max_pending")]
+    pub breadcrumb_max_pending: usize,
+
+    /// Publish an epoch automatically once this many breadcrumbs are
+    /// unpublished. Never publishes fewer than `min_breadcrumbs_for_epoch`.
+    ///
+    /// Default: `0` (off)
+    #[cfg(feature = "trajectory")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "trajectory")))]
+    #[serde(default)]
+    pub auto_publish_epoch_every: u32,
+
+    /// Also publish automatically once the oldest unpublished breadcrumb is
+    /// this many seconds old, if there are at least
+    /// `min_breadcrumbs_for_epoch` of them.
+    ///
+    /// Default: `0` (off)
+    #[cfg(feature = "trajectory")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "trajectory")))]
+    #[serde(default)]
+    pub auto_publish_epoch_window: u64,
+}
+
+/// Per-source trust weights, each between `0.0` (ignored) and `1.0` (full credit).
+///
+/// Sources left out of the JSON keep their default.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SourceWeights {
+    /// Default: `1.0`
+    pub gps: f64,
+    /// Default: `1.0`
+    pub fused: f64,
+    /// Default: `0.5`
+    pub wifi: f64,
+    /// Default: `0.5`
+    pub cell: f64,
+    /// Default: `0.5`
+    pub network: f64,
+    /// Default: `0.0`
+    pub manual: f64,
+}
+
+impl Default for SourceWeights {
+    fn default() -> Self {
+        Self {
+            gps: 1.0,
+            fused: 1.0,
+            wifi: 0.5,
+            cell: 0.5,
+            network: 0.5,
+            manual: 0.0,
+        }
+    }
+}
+
+impl SourceWeights {
+    /// Weight for `source`, clamped to `0.0..=1.0`
+    pub fn weight(&self, source: LocationSource) -> f64 {
+        let weight = match source {
+            LocationSource::Gps => self.gps,
+            LocationSource::Fused => self.fused,
+            LocationSource::Wifi => self.wifi,
+            LocationSource::Cell => self.cell,
+            LocationSource::Network => self.network,
+            LocationSource::Manual => self.manual,
+        };
+        weight.clamp(0.0, 1.0)
+    }
 }
 
 fn default_relay_urls() -> Vec<String> {
@@ -119,6 +256,18 @@ fn default_message_limit() -> u32 {
     50
 }
 
+fn default_max_message_bytes() -> usize {
+    64 * 1024
+}
+
+fn default_pow_difficulty() -> u8 {
+    16
+}
+
+fn default_max_pow_difficulty() -> u8 {
+    22
+}
+
 fn default_cache_ttl() -> u64 {
     300 // 5 minutes
 }
@@ -149,22 +298,52 @@ fn default_min_breadcrumbs_for_epoch() -> usize {
     100
 }
 
+#[cfg(feature = "trajectory")]
+fn default_breadcrumb_batch_size() -> usize {
+    20
+}
+
+#[cfg(feature = "trajectory")]
+fn default_breadcrumb_flush_interval() -> u64 {
+    60
+}
+
+#[cfg(feature = "trajectory")]
+fn default_breadcrumb_max_pending() -> usize {
+    1000
+}
+
 impl Default for GnsConfig {
     fn default() -> Self {
         Self {
             relay_urls: default_relay_urls(),
             encrypt_storage: false,
             message_limit: default_message_limit(),
+            max_message_bytes: default_max_message_bytes(),
+            require_pow: false,
+            pow_difficulty: default_pow_difficulty(),
+            max_pow_difficulty: default_max_pow_difficulty(),
             cache_ttl_seconds: default_cache_ttl(),
             network_timeout_seconds: default_network_timeout(),
             min_trust_score_for_handle: default_min_trust_score(),
             min_breadcrumbs_for_handle: default_min_breadcrumbs(),
+            breadcrumb_source_weights: SourceWeights::default(),
             h3_resolution: default_h3_resolution(),
             debug: false,
             #[cfg(feature = "trajectory")]
             breadcrumb_collection_interval: default_breadcrumb_interval(),
             #[cfg(feature = "trajectory")]
             min_breadcrumbs_for_epoch: default_min_breadcrumbs_for_epoch(),
+            #[cfg(feature = "trajectory")]
+            breadcrumb_batch_size: default_breadcrumb_batch_size(),
+            #[cfg(feature = "trajectory")]
+            breadcrumb_flush_interval: default_breadcrumb_flush_interval(),
+            #[cfg(feature = "trajectory")]
+            breadcrumb_max_pending: default_breadcrumb_max_pending(),
+            #[cfg(feature = "trajectory")]
+            auto_publish_epoch_every: 0,
+            #[cfg(feature = "trajectory")]
+            auto_publish_epoch_window: 0,
         }
     }
 }
@@ -208,6 +387,9 @@ mod tests {
         assert!(!config.relay_urls.is_empty());
         assert!(!config.encrypt_storage);
         assert_eq!(config.message_limit, 50);
+        assert_eq!(config.max_message_bytes, 65536);
+        assert!(!config.require_pow);
+        assert!(config.pow_difficulty <= config.max_pow_difficulty);
     }
 
     #[test]
@@ -237,4 +419,18 @@ mod tests {
         assert!(config.encrypt_storage);
         assert_eq!(config.message_limit, 100);
     }
+
+    #[test]
+    fn test_source_weights() {
+        let config: GnsConfig =
+            serde_json::from_str(r#"{"breadcrumbSourceWeights": {"network": 0.25, "manual": 2.0}}"#).unwrap();
+        let weights = &config.breadcrumb_source_weights;
+
+        assert_eq!(weights.weight(LocationSource::Network), 0.25);
+        assert_eq!(weights.weight(LocationSource::Gps), 1.0);
+        // Out-of-range weights are clamped
+        assert_eq!(weights.weight(LocationSource::Manual), 1.0);
+
+        assert_eq!(GnsConfig::default().breadcrumb_source_weights.weight(LocationSource::Manual), 0.0);
+    }
 }
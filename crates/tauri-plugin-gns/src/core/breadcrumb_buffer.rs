@@ -0,0 +1,237 @@
+//! Breadcrumb Write Buffer
+//!
+//! Saving breadcrumbs one at a time costs an INSERT plus an identity
+//! counter UPDATE per breadcrumb, which adds up on mobile storage. The
+//! buffer holds new breadcrumbs in memory and writes them in a single
+//! transaction once `batch_size` have accumulated or the oldest has waited
+//! `flush_interval`.
+//!
+//! # Durability
+//!
+//! Breadcrumbs are written in collection order and each flush is all or
+//! nothing, so the database always holds an unbroken prefix of the hash
+//! chain. A crash loses at most the unflushed tail; the next breadcrumb
+//! then chains onto the last one that was written.
+//!
+//! # Backpressure
+//!
+//! A failed flush keeps its breadcrumbs and is retried on the next push.
+//! Once `max_pending` are waiting, new breadcrumbs are refused with
+//! [`Error::RateLimited`] until the database catches up.
+
+use crate::core::StorageManager;
+use crate::error::{Error, Result};
+use crate::models::Breadcrumb;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// In-memory queue of breadcrumbs waiting to be written
+pub struct BreadcrumbBuffer {
+    pending: Mutex<Pending>,
+    batch_size: usize,
+    flush_interval: Duration,
+    max_pending: usize,
+}
+
+#[derive(Default)]
+struct Pending {
+    identity_pk: Option<String>,
+    breadcrumbs: Vec<Breadcrumb>,
+    oldest: Option<Instant>,
+}
+
+impl BreadcrumbBuffer {
+    /// Create a buffer that flushes every `batch_size` breadcrumbs or
+    /// `flush_interval`, whichever comes first
+    pub fn new(batch_size: usize, flush_interval: Duration, max_pending: usize) -> Self {
+        let batch_size = batch_size.max(1);
+        Self {
+            pending: Mutex::new(Pending::default()),
+            batch_size,
+            flush_interval,
+            max_pending: max_pending.max(batch_size),
+        }
+    }
+
+    /// How long a breadcrumb may wait before it is written
+    pub fn flush_interval(&self) -> Duration {
+        self.flush_interval
+    }
+
+    /// Queue a breadcrumb, flushing if the batch is full or old enough.
+    /// Returns how many breadcrumbs were written.
+    pub fn push(&self, storage: &StorageManager, identity_pk: &str, breadcrumb: Breadcrumb) -> Result<usize> {
+        let mut pending = self.lock()?;
+        let mut written = 0;
+
+        // One identity per batch
+        if pending.identity_pk.as_deref().is_some_and(|pk| pk != identity_pk) {
+            written += write(storage, &mut pending)?;
+        }
+
+        if pending.breadcrumbs.len() >= self.max_pending {
+            match write(storage, &mut pending) {
+                Ok(n) => written += n,
+                Err(e) => {
+                    return Err(Error::RateLimited(format!(
+                        "{} breadcrumbs waiting to be written: {}",
+                        pending.breadcrumbs.len(),
+                        e
+                    )))
+                }
+            }
+        }
+
+        pending.identity_pk = Some(identity_pk.to_string());
+        pending.oldest.get_or_insert_with(Instant::now);
+        pending.breadcrumbs.push(breadcrumb);
+
+        let due = pending.breadcrumbs.len() >= self.batch_size
+            || pending.oldest.is_some_and(|at| at.elapsed() >= self.flush_interval);
+        if due {
+            match write(storage, &mut pending) {
+                Ok(n) => written += n,
+                Err(e) => log::warn!(
+                    "Breadcrumb flush failed, keeping {} in memory: {}",
+                    pending.breadcrumbs.len(),
+                    e
+                ),
+            }
+        }
+
+        Ok(written)
+    }
+
+    /// Write everything queued. Returns how many breadcrumbs were written.
+    pub fn flush(&self, storage: &StorageManager) -> Result<usize> {
+        let mut pending = self.lock()?;
+        write(storage, &mut pending)
+    }
+
+    /// Hash of the newest queued breadcrumb for `identity_pk`, which the
+    /// next breadcrumb must chain onto
+    pub fn last_hash(&self, identity_pk: &str) -> Result<Option<String>> {
+        let pending = self.lock()?;
+        if pending.identity_pk.as_deref() != Some(identity_pk) {
+            return Ok(None);
+        }
+        Ok(pending.breadcrumbs.last().map(|b| b.hash.clone()))
+    }
+
+    /// Number of breadcrumbs not yet written
+    pub fn len(&self) -> usize {
+        self.pending.lock().map(|p| p.breadcrumbs.len()).unwrap_or(0)
+    }
+
+    /// Whether nothing is waiting to be written
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Pending>> {
+        self.pending.lock().map_err(|e| Error::Storage(e.to_string()))
+    }
+}
+
+/// Write the queued breadcrumbs in one transaction, clearing them only if
+/// it commits
+fn write(storage: &StorageManager, pending: &mut Pending) -> Result<usize> {
+    let Some(identity_pk) = pending.identity_pk.as_deref() else {
+        return Ok(0);
+    };
+    if pending.breadcrumbs.is_empty() {
+        return Ok(0);
+    }
+
+    storage.save_breadcrumbs(identity_pk, &pending.breadcrumbs)?;
+
+    let written = pending.breadcrumbs.len();
+    log::debug!("Flushed {} breadcrumbs", written);
+    pending.breadcrumbs.clear();
+    pending.oldest = None;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::CryptoEngine;
+    use crate::models::LocationSource;
+    use tempfile::tempdir;
+
+    const IDENTITY: &str = "abc123";
+
+    fn storage(dir: &std::path::Path, name: &str) -> StorageManager {
+        let storage = StorageManager::new(&dir.join(name), false).unwrap();
+        storage
+            .save_identity(IDENTITY, "secret", "enc_secret", "enc_public", "Test")
+            .unwrap();
+        storage
+    }
+
+    fn chain(count: usize) -> Vec<Breadcrumb> {
+        let mut prev_hash = "genesis".to_string();
+        (0..count)
+            .map(|i| {
+                let hash = CryptoEngine::sha256(format!("h3_{}|{}|{}", i, i, prev_hash).as_bytes());
+                Breadcrumb {
+                    id: format!("crumb-{}", i),
+                    h3_index: format!("h3_{}", i),
+                    h3_resolution: 7,
+                    timestamp: "2025-01-01T00:00:00Z".to_string(),
+                    prev_hash: Some(std::mem::replace(&mut prev_hash, hash.clone())),
+                    hash,
+                    signature: "sig".to_string(),
+                    source: LocationSource::Gps,
+                    accuracy: None,
+                    published: false,
+                }
+            })
+            .collect()
+    }
+
+    fn counter(storage: &StorageManager) -> u32 {
+        storage.get_identity(IDENTITY).unwrap().unwrap().breadcrumb_count
+    }
+
+    #[test]
+    fn test_batched_writes_match_per_item_writes() {
+        let dir = tempdir().unwrap();
+        let breadcrumbs = chain(45);
+
+        let per_item = storage(dir.path(), "per_item.db");
+        for breadcrumb in &breadcrumbs {
+            per_item.save_breadcrumb(IDENTITY, breadcrumb).unwrap();
+        }
+
+        let batched = storage(dir.path(), "batched.db");
+        let buffer = BreadcrumbBuffer::new(10, Duration::from_secs(3600), 100);
+        let written: usize = breadcrumbs
+            .iter()
+            .map(|b| buffer.push(&batched, IDENTITY, b.clone()).unwrap())
+            .sum();
+
+        // Four full batches written, the tail still in memory
+        assert_eq!(written, 40);
+        assert_eq!(buffer.len(), 5);
+        assert_eq!(batched.get_breadcrumb_count(IDENTITY).unwrap(), 40);
+        assert_eq!(
+            buffer.last_hash(IDENTITY).unwrap().as_deref(),
+            Some(breadcrumbs[44].hash.as_str())
+        );
+
+        assert_eq!(buffer.flush(&batched).unwrap(), 5);
+        assert!(buffer.is_empty());
+
+        assert_eq!(
+            batched.get_breadcrumb_count(IDENTITY).unwrap(),
+            per_item.get_breadcrumb_count(IDENTITY).unwrap()
+        );
+        assert_eq!(counter(&batched), counter(&per_item));
+        assert_eq!(counter(&batched), 45);
+        assert_eq!(
+            batched.get_last_breadcrumb_hash(IDENTITY).unwrap(),
+            per_item.get_last_breadcrumb_hash(IDENTITY).unwrap()
+        );
+    }
+}
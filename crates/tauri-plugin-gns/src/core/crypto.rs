@@ -18,6 +18,7 @@ use chacha20poly1305::{
 use ed25519_dalek::{
     Signature, Signer, SigningKey, Verifier, VerifyingKey,
 };
+use gns_crypto_core::SignatureDomain;
 use hkdf::Hkdf;
 use rand::rngs::OsRng;
 use sha2::Sha256;
@@ -36,6 +37,8 @@ pub const X25519_PUBLIC_KEY_SIZE: usize = 32;
 pub const NONCE_SIZE: usize = 12;
 /// Size of ChaCha20-Poly1305 key in bytes
 pub const SYMMETRIC_KEY_SIZE: usize = 32;
+/// Hardest proof-of-work accepted; each step doubles the expected work
+pub const MAX_POW_DIFFICULTY: u8 = 28;
 
 /// Secure wrapper for secret key bytes that zeroizes on drop
 #[derive(Zeroize, ZeroizeOnDrop)]
@@ -150,6 +153,21 @@ impl CryptoEngine {
         Ok(hex::encode(signature.to_bytes()))
     }
 
+    /// Sign a JSON payload on behalf of `app_domain` in the `Typed`
+    /// signature domain, byte for byte as the desktop app does.
+    /// Returns the hex signature and the exact bytes signed.
+    pub fn sign_typed_data(
+        secret_key_hex: &str,
+        app_domain: &str,
+        payload: &serde_json::Value,
+    ) -> Result<(String, Vec<u8>)> {
+        let message = gns_crypto_core::typed_data_message(app_domain, payload)
+            .map_err(|e| Error::InvalidInput(e.to_string()))?;
+        let signed_bytes = SignatureDomain::Typed.tag(&message);
+        let signature = Self::sign(secret_key_hex, &signed_bytes)?;
+        Ok((signature, signed_bytes))
+    }
+
     /// Verify an Ed25519 signature
     ///
     /// # Arguments
@@ -183,6 +201,41 @@ impl CryptoEngine {
         Ok(verifying_key.verify(message, &signature).is_ok())
     }
 
+    /// Verify many Ed25519 signatures at once, e.g. a burst of envelopes
+    /// from the relay
+    ///
+    /// Items are `(public_key_hex, message, signature_hex)`. The result
+    /// has one entry per item: a malformed key or signature is `false`
+    /// rather than an error, so one bad envelope can't sink the rest.
+    /// The whole batch is checked in one pass; only if that fails is each
+    /// item checked on its own to find which ones are bad.
+    pub fn verify_batch(items: &[(&str, &[u8], &str)]) -> Vec<bool> {
+        let mut results = vec![false; items.len()];
+        let mut indices = Vec::with_capacity(items.len());
+        let mut messages = Vec::with_capacity(items.len());
+        let mut signatures = Vec::with_capacity(items.len());
+        let mut keys = Vec::with_capacity(items.len());
+        for (i, (public_key_hex, message, signature_hex)) in items.iter().enumerate() {
+            if let Some((key, signature)) = parse_signed(public_key_hex, signature_hex) {
+                indices.push(i);
+                messages.push(*message);
+                signatures.push(signature);
+                keys.push(key);
+            }
+        }
+
+        if ed25519_dalek::verify_batch(&messages, &signatures, &keys).is_ok() {
+            for i in indices {
+                results[i] = true;
+            }
+        } else {
+            for (n, i) in indices.into_iter().enumerate() {
+                results[i] = keys[n].verify(messages[n], &signatures[n]).is_ok();
+            }
+        }
+        results
+    }
+
     /// Perform X25519 key exchange
     ///
     /// # Arguments
@@ -297,6 +350,43 @@ impl CryptoEngine {
             .map_err(|_| Error::DecryptionFailed("Decryption failed".to_string()))
     }
 
+    /// Encrypt an attachment under a fresh random key, as a single
+    /// ChaCha20-Poly1305 message. Uploads split the result into chunks
+    /// by byte range without touching the crypto, so the blob the relay
+    /// assembles decrypts in one go with the same key and nonce.
+    ///
+    /// # Returns
+    /// (key_hex, nonce_hex, ciphertext)
+    pub fn encrypt_blob(plaintext: &[u8]) -> Result<(String, String, Vec<u8>)> {
+        let mut key = [0u8; SYMMETRIC_KEY_SIZE];
+        rand::Rng::fill(&mut OsRng, &mut key);
+        let mut nonce_bytes = [0u8; NONCE_SIZE];
+        rand::Rng::fill(&mut OsRng, &mut nonce_bytes);
+
+        let cipher = ChaCha20Poly1305::new(&key.into());
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce_bytes), plaintext)
+            .map_err(|e| Error::Crypto(format!("Encryption failed: {}", e)))?;
+
+        let key_hex = hex::encode(key);
+        key.zeroize();
+        Ok((key_hex, hex::encode(nonce_bytes), ciphertext))
+    }
+
+    /// Decrypt a whole attachment blob from [`CryptoEngine::encrypt_blob`]
+    pub fn decrypt_blob(key_hex: &str, nonce_hex: &str, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let key = SecretKeyBytes::from_hex(key_hex)?;
+        let nonce_bytes = hex::decode(nonce_hex)?;
+        if nonce_bytes.len() != NONCE_SIZE {
+            return Err(Error::InvalidInput("Invalid nonce size".to_string()));
+        }
+
+        let cipher = ChaCha20Poly1305::new(&key.0.into());
+        cipher
+            .decrypt(Nonce::from_slice(&nonce_bytes), ciphertext)
+            .map_err(|_| Error::DecryptionFailed("Decryption failed".to_string()))
+    }
+
     /// Generate an ephemeral X25519 keypair for message encryption
     pub fn generate_ephemeral_keypair() -> (String, String) {
         let secret = X25519Secret::random_from_rng(OsRng);
@@ -317,6 +407,65 @@ impl CryptoEngine {
     pub fn random_id() -> String {
         uuid::Uuid::new_v4().to_string()
     }
+
+    /// Find a Hashcash-style nonce: SHA256(data || nonce as big-endian
+    /// u64) starts with at least `difficulty` zero bits.
+    ///
+    /// Takes about 2^difficulty hashes. The search gives up after 256 times
+    /// that, so even an unlucky run stays bounded.
+    pub fn compute_pow(data: &[u8], difficulty: u8) -> Result<u64> {
+        use sha2::{Digest, Sha256};
+
+        if difficulty > MAX_POW_DIFFICULTY {
+            return Err(Error::InvalidInput(format!(
+                "Proof-of-work difficulty {} is above the maximum of {}",
+                difficulty, MAX_POW_DIFFICULTY
+            )));
+        }
+
+        let prefix = Sha256::new_with_prefix(data);
+        let attempts = 1u64 << (difficulty + 8);
+        (0..attempts)
+            .find(|nonce| {
+                let hash = prefix.clone().chain_update(nonce.to_be_bytes()).finalize();
+                leading_zero_bits(&hash) >= u32::from(difficulty)
+            })
+            .ok_or_else(|| {
+                Error::Crypto(format!(
+                    "No proof-of-work found at difficulty {} in {} attempts",
+                    difficulty, attempts
+                ))
+            })
+    }
+
+    /// Check a nonce from `compute_pow` against at least `difficulty`
+    pub fn verify_pow(data: &[u8], nonce: u64, difficulty: u8) -> bool {
+        use sha2::{Digest, Sha256};
+
+        let hash = Sha256::new_with_prefix(data)
+            .chain_update(nonce.to_be_bytes())
+            .finalize();
+        leading_zero_bits(&hash) >= u32::from(difficulty)
+    }
+}
+
+/// Decode a hex key and signature, `None` if either is malformed
+fn parse_signed(public_key_hex: &str, signature_hex: &str) -> Option<(VerifyingKey, Signature)> {
+    let public_array: [u8; ED25519_PUBLIC_KEY_SIZE] = hex::decode(public_key_hex).ok()?.try_into().ok()?;
+    let sig_array: [u8; ED25519_SIGNATURE_SIZE] = hex::decode(signature_hex).ok()?.try_into().ok()?;
+    let verifying_key = VerifyingKey::from_bytes(&public_array).ok()?;
+    Some((verifying_key, Signature::from_bytes(&sig_array)))
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
 }
 
 #[cfg(test)]
@@ -346,6 +495,66 @@ mod tests {
         assert!(!invalid);
     }
 
+    #[test]
+    fn test_typed_data_signature_is_bound_to_its_domain() {
+        let (secret, public) = CryptoEngine::generate_keypair().unwrap();
+        let payload = serde_json::json!({ "nonce": 7, "action": "login" });
+
+        let (signature, signed_bytes) =
+            CryptoEngine::sign_typed_data(&secret, "app.example/login", &payload).unwrap();
+        assert!(CryptoEngine::verify(&public, &signed_bytes, &signature).unwrap());
+
+        let message = gns_crypto_core::typed_data_message("app.example/login", &payload).unwrap();
+        let verify = |message: &[u8]| {
+            gns_crypto_core::verify_in_domain_hex(
+                &public,
+                SignatureDomain::Typed,
+                message,
+                &signature,
+                gns_crypto_core::DomainPolicy::Strict,
+            )
+            .unwrap()
+        };
+        assert!(verify(&message));
+
+        // The same payload for another app is a different message
+        let other = gns_crypto_core::typed_data_message("other.example/login", &payload).unwrap();
+        assert!(!verify(&other));
+    }
+
+    #[test]
+    fn test_verify_batch_reports_each_signature() {
+        let keys: Vec<_> = (0..4).map(|_| CryptoEngine::generate_keypair().unwrap()).collect();
+        let messages: Vec<Vec<u8>> = (0..4).map(|i| format!("envelope {}", i).into_bytes()).collect();
+        let signatures: Vec<String> = keys
+            .iter()
+            .zip(&messages)
+            .map(|((secret, _), message)| CryptoEngine::sign(secret, message).unwrap())
+            .collect();
+
+        let all_valid: Vec<(&str, &[u8], &str)> = (0..4)
+            .map(|i| (keys[i].1.as_str(), messages[i].as_slice(), signatures[i].as_str()))
+            .collect();
+        assert_eq!(CryptoEngine::verify_batch(&all_valid), [true; 4]);
+
+        let mixed: Vec<(&str, &[u8], &str)> = vec![
+            all_valid[0],
+            // Signed by someone else
+            (keys[2].1.as_str(), messages[1].as_slice(), signatures[1].as_str()),
+            all_valid[2],
+            // Message altered after signing
+            (keys[3].1.as_str(), b"tampered".as_slice(), signatures[3].as_str()),
+            // Malformed signature
+            (keys[0].1.as_str(), messages[0].as_slice(), "not hex"),
+            all_valid[3],
+        ];
+        assert_eq!(
+            CryptoEngine::verify_batch(&mixed),
+            [true, false, true, false, false, true]
+        );
+        assert!(CryptoEngine::verify_batch(&[]).is_empty());
+    }
+
     #[test]
     fn test_encryption_roundtrip() {
         let (key, _) = CryptoEngine::generate_ephemeral_keypair();
@@ -395,6 +604,35 @@ mod tests {
         drop(secret_key);
     }
 
+    #[test]
+    fn test_pow_meets_difficulty() {
+        let data = b"message-id:recipient";
+        for difficulty in [0, 4, 12] {
+            let nonce = CryptoEngine::compute_pow(data, difficulty).unwrap();
+            assert!(CryptoEngine::verify_pow(data, nonce, difficulty));
+        }
+
+        let nonce = CryptoEngine::compute_pow(data, 12).unwrap();
+        // The stamp is tied to its data
+        assert!(!CryptoEngine::verify_pow(b"message-id:someone-else", nonce, 12));
+        // The first nonce found is the smallest one that works
+        assert!((0..nonce).all(|n| !CryptoEngine::verify_pow(data, n, 12)));
+    }
+
+    #[test]
+    fn test_pow_is_bounded() {
+        let started = std::time::Instant::now();
+        let nonce = CryptoEngine::compute_pow(b"bounded", 16).unwrap();
+        assert!(CryptoEngine::verify_pow(b"bounded", nonce, 16));
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+
+        // Out of range is refused before any hashing
+        let started = std::time::Instant::now();
+        assert!(CryptoEngine::compute_pow(b"bounded", MAX_POW_DIFFICULTY + 1).is_err());
+        assert!(started.elapsed() < std::time::Duration::from_millis(10));
+        assert!(!CryptoEngine::verify_pow(b"bounded", nonce, 255));
+    }
+
     #[test]
     fn test_secret_key_bytes_invalid_input() {
         // Too short
@@ -0,0 +1,312 @@
+//! Epoch Publisher
+//!
+//! Bundles an identity's unpublished breadcrumbs into a signed epoch and
+//! publishes it, either on request (`publish_epoch`) or automatically once
+//! enough have piled up. Publishes are serialized, and the breadcrumbs an
+//! epoch covers are marked published in the same transaction that records
+//! it, so a manual publish racing the background one can't put the same
+//! breadcrumb in two epochs.
+
+use crate::core::{BreadcrumbBuffer, CryptoEngine, NetworkClient, StorageManager};
+use crate::error::{Error, Result};
+use crate::models::{Breadcrumb, BreadcrumbBlock, EpochHeader, SignedEpoch};
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
+
+/// Breadcrumbs per block of an epoch
+const BREADCRUMBS_PER_BLOCK: usize = 10;
+
+/// When an epoch is published without being asked for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AutoPublishPolicy {
+    /// Publish once this many breadcrumbs are unpublished; `0` disables
+    pub every: u32,
+
+    /// Publish once the oldest unpublished breadcrumb is this old
+    pub window: Option<Duration>,
+}
+
+impl AutoPublishPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.every > 0 || self.window.is_some()
+    }
+
+    /// Whether `unpublished` breadcrumbs, the oldest `oldest_age` old, should
+    /// go out now. Never below `min_breadcrumbs`, whatever the policy.
+    pub fn is_due(&self, unpublished: u32, oldest_age: Option<Duration>, min_breadcrumbs: u32) -> bool {
+        if unpublished == 0 || unpublished < min_breadcrumbs {
+            return false;
+        }
+        let threshold = self.every > 0 && unpublished >= self.every;
+        let window = matches!((self.window, oldest_age), (Some(window), Some(age)) if age >= window);
+        threshold || window
+    }
+}
+
+/// Publishes epochs, one at a time
+pub struct EpochPublisher {
+    policy: AutoPublishPolicy,
+    min_breadcrumbs: u32,
+    publishing: Mutex<()>,
+}
+
+impl EpochPublisher {
+    pub fn new(policy: AutoPublishPolicy, min_breadcrumbs: usize) -> Self {
+        Self {
+            policy,
+            min_breadcrumbs: min_breadcrumbs as u32,
+            publishing: Mutex::new(()),
+        }
+    }
+
+    pub fn policy(&self) -> AutoPublishPolicy {
+        self.policy
+    }
+
+    /// Publish every unpublished breadcrumb of `identity_pk` as its next
+    /// epoch. Buffered breadcrumbs are flushed first so the epoch covers
+    /// everything collected so far.
+    pub async fn publish(
+        &self,
+        storage: &RwLock<StorageManager>,
+        buffer: &BreadcrumbBuffer,
+        network: &NetworkClient,
+        identity_pk: &str,
+    ) -> Result<EpochHeader> {
+        let _publishing = self.publishing.lock().await;
+
+        let (secret_key, breadcrumbs, previous) = {
+            let storage = storage.read().await;
+            buffer.flush(&storage)?;
+            let secret_key = storage
+                .get_secret_key(identity_pk)?
+                .ok_or(Error::IdentityNotFound("Secret key not found".into()))?;
+            (
+                secret_key,
+                storage.get_unpublished_breadcrumbs(identity_pk)?,
+                storage.get_last_epoch(identity_pk)?,
+            )
+        };
+
+        if breadcrumbs.is_empty() || breadcrumbs.len() < self.min_breadcrumbs as usize {
+            return Err(Error::InsufficientBreadcrumbs(format!(
+                "Need {} breadcrumbs, have {}",
+                self.min_breadcrumbs.max(1),
+                breadcrumbs.len()
+            )));
+        }
+
+        let epoch = build_epoch(identity_pk, &secret_key, &breadcrumbs, previous.as_ref())?;
+
+        network
+            .publish_epoch(&SignedEpoch {
+                pk_root: identity_pk.to_string(),
+                epoch: epoch.clone(),
+                signature: epoch.signature.clone(),
+            })
+            .await?;
+
+        let ids: Vec<&str> = breadcrumbs.iter().map(|b| b.id.as_str()).collect();
+        storage.read().await.save_published_epoch(identity_pk, &epoch, &ids)?;
+
+        log::info!(
+            "Published epoch {} with {} breadcrumbs in {} blocks",
+            epoch.epoch_index,
+            breadcrumbs.len(),
+            epoch.block_count
+        );
+
+        Ok(epoch)
+    }
+
+    /// Publish if the auto-publish policy says it's time. Returns `None`
+    /// when it isn't, or when the relay can't be reached; the breadcrumbs
+    /// stay unpublished and go out on the first check after it's back.
+    pub async fn publish_if_due(
+        &self,
+        storage: &RwLock<StorageManager>,
+        buffer: &BreadcrumbBuffer,
+        network: &NetworkClient,
+        identity_pk: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<EpochHeader>> {
+        if !self.policy.is_enabled() {
+            return Ok(None);
+        }
+
+        let (unpublished, oldest) = storage.read().await.get_unpublished_breadcrumb_stats(identity_pk)?;
+        let oldest_age = oldest
+            .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+            .and_then(|t| (now - t.with_timezone(&Utc)).to_std().ok());
+        if !self.policy.is_due(unpublished, oldest_age, self.min_breadcrumbs) {
+            return Ok(None);
+        }
+
+        if !network.health_check().await? {
+            log::info!("Relay unreachable, deferring epoch with {} breadcrumbs", unpublished);
+            return Ok(None);
+        }
+
+        match self.publish(storage, buffer, network, identity_pk).await {
+            Ok(epoch) => Ok(Some(epoch)),
+            // Another publish got there first
+            Err(Error::InsufficientBreadcrumbs(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Sign an epoch over `breadcrumbs`, chained onto `previous`
+fn build_epoch(
+    identity_pk: &str,
+    secret_key: &str,
+    breadcrumbs: &[Breadcrumb],
+    previous: Option<&EpochHeader>,
+) -> Result<EpochHeader> {
+    let (first, last) = match (breadcrumbs.first(), breadcrumbs.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Err(Error::InsufficientBreadcrumbs("No breadcrumbs to publish".into())),
+    };
+
+    let epoch = EpochHeader {
+        identity: identity_pk.to_string(),
+        epoch_index: previous.map_or(0, |p| p.epoch_index + 1),
+        start_time: first.timestamp.clone(),
+        end_time: last.timestamp.clone(),
+        merkle_root: BreadcrumbBlock::calculate_merkle_root(breadcrumbs),
+        block_count: breadcrumbs.len().div_ceil(BREADCRUMBS_PER_BLOCK) as u32,
+        prev_epoch_hash: previous.map(|p| p.epoch_hash.clone()),
+        signature: String::new(),
+        epoch_hash: String::new(),
+    };
+
+    let epoch_hash = epoch_hash(&epoch)?;
+    let signature = CryptoEngine::sign(secret_key, epoch_hash.as_bytes())?;
+    Ok(EpochHeader { epoch_hash, signature, ..epoch })
+}
+
+/// Hash an epoch header is signed under:
+/// H(identity | index | start | end | merkle_root | prev_epoch_hash)
+pub(crate) fn epoch_hash(epoch: &EpochHeader) -> Result<String> {
+    let start_dt = DateTime::parse_from_rfc3339(&epoch.start_time)
+        .map_err(|_| Error::InvalidInput("Invalid start time".into()))?;
+    let end_dt = DateTime::parse_from_rfc3339(&epoch.end_time)
+        .map_err(|_| Error::InvalidInput("Invalid end time".into()))?;
+
+    let epoch_data = format!(
+        "{}|{}|{}|{}|{}|{}",
+        epoch.identity,
+        epoch.epoch_index,
+        start_dt.timestamp(),
+        end_dt.timestamp(),
+        epoch.merkle_root,
+        epoch.prev_epoch_hash.as_deref().unwrap_or("genesis")
+    );
+    Ok(CryptoEngine::sha256(epoch_data.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{MockTransport, TransportResponse};
+    use crate::models::LocationSource;
+    use reqwest::StatusCode;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    fn breadcrumbs(count: usize) -> Vec<Breadcrumb> {
+        (0..count)
+            .map(|i| Breadcrumb {
+                id: format!("crumb-{}", i),
+                h3_index: format!("h3_{}", i),
+                h3_resolution: 7,
+                timestamp: format!("2025-01-01T00:{:02}:00Z", i),
+                prev_hash: None,
+                hash: CryptoEngine::sha256(format!("crumb-{}", i).as_bytes()),
+                signature: "sig".to_string(),
+                source: LocationSource::Gps,
+                accuracy: None,
+                published: false,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_threshold_publishes_one_epoch_per_batch() {
+        let dir = tempdir().unwrap();
+        let (secret_key, public_key) = CryptoEngine::generate_keypair().unwrap();
+        let storage = StorageManager::new(&dir.path().join("test.db"), false).unwrap();
+        storage.save_identity(&public_key, &secret_key, "enc_secret", "enc_public", "Test").unwrap();
+        let batch = breadcrumbs(25);
+        storage.save_breadcrumbs(&public_key, &batch).unwrap();
+        let storage = RwLock::new(storage);
+
+        let online = Arc::new(AtomicBool::new(false));
+        let relay_online = online.clone();
+        let transport = Arc::new(MockTransport::new(move |_| {
+            if !relay_online.load(Ordering::SeqCst) {
+                return Ok(TransportResponse::json(StatusCode::SERVICE_UNAVAILABLE, &serde_json::json!({})));
+            }
+            Ok(TransportResponse::json(StatusCode::OK, &serde_json::json!({ "success": true })))
+        }));
+        let network = NetworkClient::with_transport(&["https://relay.test".to_string()], transport.clone());
+        let epochs_posted = || transport.requests().iter().filter(|r| r.path() == "/api/epochs").count();
+
+        let buffer = BreadcrumbBuffer::new(10, Duration::from_secs(3600), 100);
+        let publisher = EpochPublisher::new(AutoPublishPolicy { every: 20, window: None }, 20);
+        let now = Utc::now();
+
+        // Offline: deferred, nothing marked
+        assert!(publisher.publish_if_due(&storage, &buffer, &network, &public_key, now).await.unwrap().is_none());
+        assert_eq!(epochs_posted(), 0);
+        assert_eq!(storage.read().await.get_unpublished_breadcrumb_stats(&public_key).unwrap().0, 25);
+
+        // Back online, and two ticks race for the same batch
+        online.store(true, Ordering::SeqCst);
+        let (a, b) = tokio::join!(
+            publisher.publish_if_due(&storage, &buffer, &network, &public_key, now),
+            publisher.publish_if_due(&storage, &buffer, &network, &public_key, now),
+        );
+        let published: Vec<EpochHeader> = [a.unwrap(), b.unwrap()].into_iter().flatten().collect();
+        assert_eq!(published.len(), 1);
+        assert_eq!(epochs_posted(), 1);
+
+        let epoch = &published[0];
+        assert_eq!(epoch.epoch_index, 0);
+        assert_eq!(epoch.block_count, 3);
+        assert_eq!(epoch.merkle_root, BreadcrumbBlock::calculate_merkle_root(&batch));
+        assert_eq!(epoch_hash(epoch).unwrap(), epoch.epoch_hash);
+        assert!(CryptoEngine::verify(&public_key, epoch.epoch_hash.as_bytes(), &epoch.signature).unwrap());
+        assert_eq!(storage.read().await.get_last_epoch(&public_key).unwrap().as_ref(), Some(epoch));
+
+        // Nothing left for a manual publish, or the next tick
+        assert_eq!(storage.read().await.get_unpublished_breadcrumb_stats(&public_key).unwrap().0, 0);
+        assert!(matches!(
+            publisher.publish(&storage, &buffer, &network, &public_key).await,
+            Err(Error::InsufficientBreadcrumbs(_))
+        ));
+        assert!(publisher.publish_if_due(&storage, &buffer, &network, &public_key, now).await.unwrap().is_none());
+        assert_eq!(epochs_posted(), 1);
+    }
+
+    #[test]
+    fn test_breadcrumbs_cannot_join_two_epochs() {
+        let dir = tempdir().unwrap();
+        let storage = StorageManager::new(&dir.path().join("test.db"), false).unwrap();
+        storage.save_identity("abc123", "secret", "enc_secret", "enc_public", "Test").unwrap();
+        storage.save_breadcrumbs("abc123", &breadcrumbs(3)).unwrap();
+
+        let (secret_key, _) = CryptoEngine::generate_keypair().unwrap();
+        let first = build_epoch("abc123", &secret_key, &breadcrumbs(2), None).unwrap();
+        storage.save_published_epoch("abc123", &first, &["crumb-0", "crumb-1"]).unwrap();
+
+        // Overlaps the first epoch: rejected as a whole
+        let second = build_epoch("abc123", &secret_key, &breadcrumbs(3), Some(&first)).unwrap();
+        assert_eq!(second.prev_epoch_hash.as_deref(), Some(first.epoch_hash.as_str()));
+        assert!(storage.save_published_epoch("abc123", &second, &["crumb-1", "crumb-2"]).is_err());
+        assert_eq!(storage.get_unpublished_breadcrumb_stats("abc123").unwrap().0, 1);
+        assert_eq!(storage.get_last_epoch("abc123").unwrap(), Some(first));
+    }
+}
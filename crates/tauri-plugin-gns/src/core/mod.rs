@@ -5,7 +5,13 @@
 pub mod crypto;
 pub mod storage;
 pub mod network;
+pub mod transport;
+pub mod breadcrumb_buffer;
+pub mod epoch_publisher;
 
 pub use crypto::CryptoEngine;
 pub use storage::StorageManager;
 pub use network::NetworkClient;
+pub use transport::{HttpTransport, MockTransport, Transport, TransportRequest, TransportResponse};
+pub use breadcrumb_buffer::BreadcrumbBuffer;
+pub use epoch_publisher::{AutoPublishPolicy, EpochPublisher};
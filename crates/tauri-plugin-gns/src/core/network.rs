@@ -1,32 +1,105 @@
 //! Network Client
 //!
-//! HTTP client for communicating with GNS relay servers.
+//! HTTP client for communicating with GNS relay servers. Requests go
+//! through a [`Transport`], so tests can stand in for the relay.
 
+use super::crypto::MAX_POW_DIFFICULTY;
+use super::transport::{HttpTransport, Transport, TransportRequest};
+use super::CryptoEngine;
 use crate::error::{Error, Result};
 use crate::models::*;
-use reqwest::Client;
-use std::time::Duration;
+use reqwest::StatusCode;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Bytes per attachment upload chunk
+pub const UPLOAD_CHUNK_BYTES: usize = 256 * 1024;
 
 /// Network client for GNS relay communication
 pub struct NetworkClient {
-    client: Client,
+    transport: Arc<dyn Transport>,
     relay_urls: Vec<String>,
+    /// Index into `relay_urls` of the relay requests go to
+    active_relay: AtomicUsize,
     timeout: Duration,
+    max_message_bytes: usize,
+    pow: Option<PowPolicy>,
+    upload_chunk_bytes: usize,
+    /// Unfinished uploads by SHA-256 of the blob, so a retry resumes
+    uploads: Mutex<HashMap<String, UploadSession>>,
+}
+
+/// How much of a blob a relay has acknowledged
+#[derive(Debug, Clone)]
+struct UploadSession {
+    relay: String,
+    upload_id: String,
+    acked: u64,
+}
+
+/// Proof-of-work attached to outgoing envelopes. Starts at the configured
+/// difficulty and goes up, never past `max_difficulty`, when a relay
+/// answers `428 Precondition Required` asking for more.
+struct PowPolicy {
+    difficulty: AtomicU8,
+    max_difficulty: u8,
+}
+
+impl PowPolicy {
+    /// Take on the difficulty a relay asked for. False if that is no
+    /// harder than what was sent or more than we are willing to do.
+    fn raise(&self, required: u8) -> bool {
+        if required > self.max_difficulty {
+            return false;
+        }
+        self.difficulty.fetch_max(required, Ordering::Relaxed) < required
+    }
 }
 
 impl NetworkClient {
     /// Create a new network client
     pub fn new(relay_urls: &[String]) -> Result<Self> {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build()
-            .map_err(|e| Error::Network(format!("Failed to create HTTP client: {}", e)))?;
+        let transport = HttpTransport::new(Duration::from_secs(30))?;
+        Ok(Self::with_transport(relay_urls, Arc::new(transport)))
+    }
 
-        Ok(Self {
-            client,
+    /// A client whose requests go through `transport` instead of HTTP
+    pub fn with_transport(relay_urls: &[String], transport: Arc<dyn Transport>) -> Self {
+        Self {
+            transport,
             relay_urls: relay_urls.to_vec(),
+            active_relay: AtomicUsize::new(0),
             timeout: Duration::from_secs(30),
-        })
+            max_message_bytes: usize::MAX,
+            pow: None,
+            upload_chunk_bytes: UPLOAD_CHUNK_BYTES,
+            uploads: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Stamp outgoing envelopes with proof-of-work of `difficulty` leading
+    /// zero bits, going up to `max_difficulty` if a relay asks for more
+    pub fn with_pow(mut self, difficulty: u8, max_difficulty: u8) -> Self {
+        let max_difficulty = max_difficulty.clamp(difficulty, MAX_POW_DIFFICULTY);
+        self.pow = Some(PowPolicy {
+            difficulty: AtomicU8::new(difficulty.min(max_difficulty)),
+            max_difficulty,
+        });
+        self
+    }
+
+    /// Drop fetched envelopes whose payload is larger than `bytes`
+    pub fn with_max_message_bytes(mut self, bytes: usize) -> Self {
+        self.max_message_bytes = bytes;
+        self
+    }
+
+    /// Upload attachments `bytes` at a time
+    pub fn with_upload_chunk_bytes(mut self, bytes: usize) -> Self {
+        self.upload_chunk_bytes = bytes.max(1);
+        self
     }
 
     /// Set request timeout
@@ -35,14 +108,33 @@ impl NetworkClient {
         self
     }
 
-    /// Get the primary relay URL
+    /// Get the relay requests go to: the first configured one unless
+    /// another was made active
     fn primary_relay(&self) -> Result<&str> {
         self.relay_urls
-            .first()
+            .get(self.active_relay.load(Ordering::Relaxed))
+            .or_else(|| self.relay_urls.first())
             .map(|s| s.as_str())
             .ok_or_else(|| Error::Config("No relay URLs configured".to_string()))
     }
 
+    /// The relay requests currently go to
+    pub fn active_relay(&self) -> Option<&str> {
+        self.primary_relay().ok()
+    }
+
+    /// Send requests to `url` for the rest of the session. It has to be
+    /// one of the configured relays.
+    pub fn set_active_relay(&self, url: &str) -> Result<()> {
+        let index = self
+            .relay_urls
+            .iter()
+            .position(|relay| relay == url)
+            .ok_or_else(|| Error::InvalidInput(format!("Not a configured relay: {}", url)))?;
+        self.active_relay.store(index, Ordering::Relaxed);
+        Ok(())
+    }
+
     // ==================== Identity Resolution ====================
 
     /// Resolve a handle to an identity
@@ -51,23 +143,38 @@ impl NetworkClient {
         let url = format!("{}/api/handles/{}", relay, handle.trim_start_matches('@'));
 
         let response = self
-            .client
-            .get(&url)
-            .timeout(self.timeout)
-            .send()
+            .transport
+            .send(TransportRequest::get(url).timeout(self.timeout))
             .await?;
 
-        if response.status().is_success() {
-            let data: serde_json::Value = response.json().await?;
+        // A failing relay must not look like an unregistered handle
+        let status = response.status();
+        if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(Error::Network(format!(
+                "Relay returned {} resolving @{}",
+                status, handle
+            )));
+        }
+
+        if status.is_success() {
+            let data: serde_json::Value = response.read_json()?;
             
             if let Some(identity) = data.get("data").and_then(|d| d.get("identity")) {
+                let public_key = identity
+                    .get("identity")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                let rotations: Vec<KeyRotation> = identity
+                    .get("key_rotations")
+                    .cloned()
+                    .and_then(|v| serde_json::from_value(v).ok())
+                    .unwrap_or_default();
+
                 return Ok(ResolvedHandle {
                     handle: handle.trim_start_matches('@').to_string(),
-                    public_key: identity
-                        .get("identity")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or_default()
-                        .to_string(),
+                    previous_keys: rotation_chain(&public_key, &rotations),
+                    public_key,
                     encryption_key: identity
                         .get("encryption_key")
                         .and_then(|v| v.as_str())
@@ -95,14 +202,12 @@ impl NetworkClient {
         let url = format!("{}/api/identities/{}", relay, public_key);
 
         let response = self
-            .client
-            .get(&url)
-            .timeout(self.timeout)
-            .send()
+            .transport
+            .send(TransportRequest::get(url).timeout(self.timeout))
             .await?;
 
         if response.status().is_success() {
-            let data: serde_json::Value = response.json().await?;
+            let data: serde_json::Value = response.read_json()?;
             
             if let Some(record) = data.get("data") {
                 let record: GnsRecord = serde_json::from_value(record.clone())?;
@@ -116,6 +221,31 @@ impl NetworkClient {
         )))
     }
 
+    /// Get the relay's aggregate trust for an identity, with the signed
+    /// record it should agree with
+    pub async fn get_network_trust(&self, public_key: &str) -> Result<(NetworkTrust, Option<SignedRecord>)> {
+        let relay = self.primary_relay()?;
+        let url = format!("{}/api/identities/{}/trust", relay, public_key);
+
+        let response = self
+            .transport
+            .send(TransportRequest::get(url).timeout(self.timeout))
+            .await?;
+
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND {
+            return Err(Error::IdentityNotFound(format!("Identity {} not found", public_key)));
+        }
+        if !status.is_success() {
+            return Err(Error::Network(format!(
+                "Relay returned {} fetching trust for {}",
+                status, public_key
+            )));
+        }
+
+        parse_network_trust(public_key, &response.read_json()?)
+    }
+
     /// Check if a handle is available
     pub async fn is_handle_available(&self, handle: &str) -> Result<bool> {
         match self.resolve_handle(handle).await {
@@ -133,17 +263,14 @@ impl NetworkClient {
         let url = format!("{}/api/handles/claim", relay);
 
         let response = self
-            .client
-            .post(&url)
-            .json(claim)
-            .timeout(self.timeout)
-            .send()
+            .transport
+            .send(TransportRequest::post(url, claim)?.timeout(self.timeout))
             .await?;
 
         if response.status().is_success() {
             Ok(())
         } else {
-            let error: serde_json::Value = response.json().await.unwrap_or_default();
+            let error: serde_json::Value = response.read_json().unwrap_or_default();
             Err(Error::Network(
                 error
                     .get("error")
@@ -159,15 +286,13 @@ impl NetworkClient {
         let relay = self.primary_relay()?;
         let url = format!("{}/api/handles/{}/release", relay, handle);
 
+        let body = serde_json::json!({
+            "identity": identity,
+            "signature": signature,
+        });
         let response = self
-            .client
-            .post(&url)
-            .json(&serde_json::json!({
-                "identity": identity,
-                "signature": signature,
-            }))
-            .timeout(self.timeout)
-            .send()
+            .transport
+            .send(TransportRequest::post(url, &body)?.timeout(self.timeout))
             .await?;
 
         if response.status().is_success() {
@@ -179,30 +304,57 @@ impl NetworkClient {
 
     // ==================== Messaging ====================
 
-    /// Send a message via relay
+    /// Send a message via relay, with a proof-of-work stamp if configured.
+    /// A relay answers 409 for an envelope whose `recipient_key` isn't the
+    /// recipient's current one; that comes back as [`Error::KeyMismatch`].
     pub async fn send_message(&self, envelope: &GnsEnvelope) -> Result<()> {
         let relay = self.primary_relay()?;
         let url = format!("{}/api/messages", relay);
+        let mut envelope = envelope.clone();
 
-        let response = self
-            .client
-            .post(&url)
-            .json(envelope)
-            .timeout(self.timeout)
-            .send()
-            .await?;
+        loop {
+            if let Some(pow) = &self.pow {
+                let difficulty = pow.difficulty.load(Ordering::Relaxed);
+                if !matches!(&envelope.pow, Some(stamp) if stamp.difficulty >= difficulty) {
+                    envelope.pow = Some(stamp_pow(&envelope, difficulty).await?);
+                }
+            }
 
-        if response.status().is_success() {
-            Ok(())
-        } else {
-            let error: serde_json::Value = response.json().await.unwrap_or_default();
-            Err(Error::Network(
+            let response = self
+                .transport
+                .send(TransportRequest::post(url.as_str(), &envelope)?.timeout(self.timeout))
+                .await?;
+
+            let status = response.status();
+            if status.is_success() {
+                return Ok(());
+            }
+            let error: serde_json::Value = response.read_json().unwrap_or_default();
+
+            if status == StatusCode::CONFLICT {
+                return Err(Error::KeyMismatch(
+                    error
+                        .get("error")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("Message sealed to an outdated encryption key")
+                        .to_string(),
+                ));
+            }
+
+            // Retry only while the difficulty goes up, so this ends
+            if let (Some(pow), Some(required)) = (&self.pow, required_pow(status, &error)) {
+                if pow.raise(required) {
+                    log::info!("Relay asked for proof-of-work difficulty {}, retrying", required);
+                    continue;
+                }
+            }
+            return Err(Error::Network(
                 error
                     .get("error")
                     .and_then(|v| v.as_str())
                     .unwrap_or("Failed to send message")
                     .to_string(),
-            ))
+            ));
         }
     }
 
@@ -215,29 +367,186 @@ impl NetworkClient {
             url.push_str(&format!("&since={}", since));
         }
 
-        let response = self
-            .client
-            .get(&url)
+        let request = TransportRequest::get(url)
             .header("X-GNS-PublicKey", identity)
-            .timeout(self.timeout)
-            .send()
-            .await?;
+            .timeout(self.timeout);
+        let response = self.transport.send(request).await?;
 
         if response.status().is_success() {
-            let data: serde_json::Value = response.json().await?;
+            let data: serde_json::Value = response.read_json()?;
             
             if let Some(messages) = data.get("data").and_then(|d| d.as_array()) {
                 let envelopes: Vec<GnsEnvelope> = messages
                     .iter()
                     .filter_map(|m| serde_json::from_value(m.clone()).ok())
                     .collect();
-                return Ok(envelopes);
+                return Ok(drop_unsigned(drop_oversize(envelopes, self.max_message_bytes)));
             }
         }
 
         Ok(vec![])
     }
 
+    /// How many envelopes the relay is holding for an identity.
+    ///
+    /// Relays without the status endpoint give `InboxStatus::Unknown`
+    /// rather than an error, so callers can simply skip the count.
+    pub async fn get_inbox_status(&self, public_key: &str) -> Result<InboxStatus> {
+        let relay = self.primary_relay()?;
+        let url = format!("{}/api/messages/status?to={}", relay, public_key);
+
+        let request = TransportRequest::get(url)
+            .header("X-GNS-PublicKey", public_key)
+            .timeout(self.timeout);
+        let response = self.transport.send(request).await?;
+
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND
+            || status == reqwest::StatusCode::METHOD_NOT_ALLOWED
+            || status == reqwest::StatusCode::NOT_IMPLEMENTED
+        {
+            return Ok(InboxStatus::Unknown);
+        }
+        if !status.is_success() {
+            return Err(Error::Network(format!(
+                "Relay returned {} for inbox status",
+                status
+            )));
+        }
+
+        let body: serde_json::Value = response.read_json().unwrap_or_default();
+        Ok(parse_inbox_status(&body))
+    }
+
+    // ==================== Attachments ====================
+
+    /// Upload an encrypted attachment in chunks and return the blob id the
+    /// relay assembled it under.
+    ///
+    /// Each chunk goes up as a `PUT` with a `Content-Range` and counts once
+    /// the relay acknowledges it; `on_progress` hears about every one. If
+    /// the upload fails partway, calling this again with the same blob
+    /// carries on from the last acknowledged offset instead of starting
+    /// over. A relay that has a different offset answers 416 with it, and
+    /// the upload continues from there.
+    pub async fn upload_blob(&self, blob: &[u8], on_progress: impl Fn(&UploadProgress)) -> Result<String> {
+        let relay = self.primary_relay()?.to_string();
+        let total = blob.len() as u64;
+        let digest = CryptoEngine::sha256(blob);
+
+        let resumed = self
+            .uploads
+            .lock()
+            .unwrap()
+            .get(&digest)
+            .filter(|session| session.relay == relay)
+            .cloned();
+        let mut session = match resumed {
+            Some(session) => {
+                log::info!("Resuming upload {} at {} of {} bytes", session.upload_id, session.acked, total);
+                session
+            }
+            None => UploadSession {
+                upload_id: self.start_upload(&relay, total, &digest).await?,
+                relay: relay.clone(),
+                acked: 0,
+            },
+        };
+        let url = format!("{}/api/blobs/uploads/{}", relay, session.upload_id);
+
+        use base64::{engine::general_purpose::STANDARD, Engine};
+        while session.acked < total {
+            let start = session.acked;
+            let end = total.min(start + self.upload_chunk_bytes as u64);
+            let chunk = &blob[start as usize..end as usize];
+            let request = TransportRequest::put(url.as_str(), &serde_json::json!({ "data": STANDARD.encode(chunk) }))?
+                .header("Content-Range", &format!("bytes {}-{}/{}", start, end - 1, total))
+                .timeout(self.timeout);
+            let response = self.transport.send(request).await?;
+
+            let status = response.status();
+            let body: serde_json::Value = response.read_json().unwrap_or_default();
+            let offset = body
+                .get("data")
+                .unwrap_or(&body)
+                .get("offset")
+                .and_then(|v| v.as_u64());
+            session.acked = match (status, offset) {
+                (status, _) if status.is_success() => offset.unwrap_or(end),
+                (StatusCode::RANGE_NOT_SATISFIABLE, Some(offset)) if offset != start => offset,
+                (StatusCode::NOT_FOUND, _) => {
+                    // The relay dropped the upload; the next attempt starts over
+                    self.uploads.lock().unwrap().remove(&digest);
+                    return Err(Error::Network(format!("Upload {} expired on the relay", session.upload_id)));
+                }
+                _ => {
+                    return Err(Error::Network(
+                        body.get("error")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or("Failed to upload attachment chunk")
+                            .to_string(),
+                    ))
+                }
+            };
+            if session.acked > total {
+                return Err(Error::Network(format!("Relay acknowledged {} of {} bytes", session.acked, total)));
+            }
+
+            self.uploads.lock().unwrap().insert(digest.clone(), session.clone());
+            on_progress(&UploadProgress {
+                upload_id: session.upload_id.clone(),
+                uploaded_bytes: session.acked,
+                total_bytes: total,
+            });
+        }
+
+        let response = self
+            .transport
+            .send(TransportRequest::post(format!("{}/complete", url), &serde_json::json!({ "sha256": digest }))?.timeout(self.timeout))
+            .await?;
+        let body: serde_json::Value = response.read_json().unwrap_or_default();
+        if !response.status().is_success() {
+            return Err(Error::Network(
+                body.get("error")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("Failed to complete attachment upload")
+                    .to_string(),
+            ));
+        }
+        self.uploads.lock().unwrap().remove(&digest);
+
+        body.get("data")
+            .and_then(|d| d.get("blobId"))
+            .and_then(|v| v.as_str())
+            .map(String::from)
+            .ok_or_else(|| Error::Network("Relay did not return a blob id".to_string()))
+    }
+
+    /// Open an upload of `size` bytes on `relay`, returning its id
+    async fn start_upload(&self, relay: &str, size: u64, sha256: &str) -> Result<String> {
+        let url = format!("{}/api/blobs/uploads", relay);
+        let body = serde_json::json!({ "size": size, "sha256": sha256, "chunkSize": self.upload_chunk_bytes });
+        let response = self
+            .transport
+            .send(TransportRequest::post(url, &body)?.timeout(self.timeout))
+            .await?;
+
+        let data: serde_json::Value = response.read_json().unwrap_or_default();
+        if !response.status().is_success() {
+            return Err(Error::Network(
+                data.get("error")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("Failed to start attachment upload")
+                    .to_string(),
+            ));
+        }
+        data.get("data")
+            .and_then(|d| d.get("uploadId"))
+            .and_then(|v| v.as_str())
+            .map(String::from)
+            .ok_or_else(|| Error::Network("Relay did not return an upload id".to_string()))
+    }
+
     // ==================== Record Operations ====================
 
     /// Update a GNS record
@@ -246,17 +555,14 @@ impl NetworkClient {
         let url = format!("{}/api/identities", relay);
 
         let response = self
-            .client
-            .post(&url)
-            .json(signed_record)
-            .timeout(self.timeout)
-            .send()
+            .transport
+            .send(TransportRequest::post(url, signed_record)?.timeout(self.timeout))
             .await?;
 
         if response.status().is_success() {
             Ok(())
         } else {
-            let error: serde_json::Value = response.json().await.unwrap_or_default();
+            let error: serde_json::Value = response.read_json().unwrap_or_default();
             Err(Error::Network(
                 error
                     .get("error")
@@ -275,17 +581,14 @@ impl NetworkClient {
         let url = format!("{}/api/epochs", relay);
 
         let response = self
-            .client
-            .post(&url)
-            .json(signed_epoch)
-            .timeout(self.timeout)
-            .send()
+            .transport
+            .send(TransportRequest::post(url, signed_epoch)?.timeout(self.timeout))
             .await?;
 
         if response.status().is_success() {
             Ok(())
         } else {
-            let error: serde_json::Value = response.json().await.unwrap_or_default();
+            let error: serde_json::Value = response.read_json().unwrap_or_default();
             Err(Error::Network(
                 error
                     .get("error")
@@ -302,14 +605,12 @@ impl NetworkClient {
         let url = format!("{}/api/epochs?identity={}", relay, identity);
 
         let response = self
-            .client
-            .get(&url)
-            .timeout(self.timeout)
-            .send()
+            .transport
+            .send(TransportRequest::get(url).timeout(self.timeout))
             .await?;
 
         if response.status().is_success() {
-            let data: serde_json::Value = response.json().await?;
+            let data: serde_json::Value = response.read_json()?;
             
             if let Some(epochs) = data.get("data").and_then(|d| d.as_array()) {
                 let headers: Vec<EpochHeader> = epochs
@@ -323,18 +624,252 @@ impl NetworkClient {
         Ok(vec![])
     }
 
+    /// Get a signed epoch header with a Merkle proof that the breadcrumb
+    /// hashed `breadcrumb_hash` was published in it. The relay is not
+    /// trusted: check the result with `verify_epoch_membership`.
+    pub async fn get_epoch_proof(
+        &self,
+        public_key: &str,
+        epoch_index: u32,
+        breadcrumb_hash: &str,
+    ) -> Result<EpochProof> {
+        let relay = self.primary_relay()?;
+        let url = format!("{}/api/epochs/{}/{}/proof", relay, public_key, epoch_index);
+
+        let request = TransportRequest::get(url)
+            .query("breadcrumb", breadcrumb_hash)
+            .timeout(self.timeout);
+        let response = self.transport.send(request).await?;
+
+        if response.status().is_success() {
+            let data: serde_json::Value = response.read_json()?;
+
+            if let Some(proof) = data.get("data") {
+                let proof: EpochProof = serde_json::from_value(proof.clone())?;
+                return Ok(proof);
+            }
+        }
+
+        Err(Error::Network(format!(
+            "Relay has no proof for breadcrumb {} in epoch {} of {}",
+            breadcrumb_hash, epoch_index, public_key
+        )))
+    }
+
     // ==================== Health Check ====================
 
     /// Check if the relay is healthy
     pub async fn health_check(&self) -> Result<bool> {
         let relay = self.primary_relay()?;
+        Ok(self.ping(relay).await.is_some())
+    }
+
+    /// Health-check every configured relay at once and time the round
+    /// trips. Reachable relays come first, fastest first; unreachable ones
+    /// follow in configured order. With `select`, the fastest reachable
+    /// relay becomes the active one.
+    pub async fn benchmark_relays(&self, select: bool) -> Vec<RelayBenchmark> {
+        let latencies =
+            futures::future::join_all(self.relay_urls.iter().map(|relay| self.ping(relay))).await;
+
+        let mut results: Vec<RelayBenchmark> = self
+            .relay_urls
+            .iter()
+            .zip(latencies)
+            .map(|(url, latency)| RelayBenchmark {
+                url: url.clone(),
+                reachable: latency.is_some(),
+                latency_ms: latency.map(|l| l.as_millis() as u64),
+                active: false,
+            })
+            .collect();
+        results.sort_by_key(|r| (!r.reachable, r.latency_ms));
+
+        if select {
+            if let Some(fastest) = results.iter().find(|r| r.reachable) {
+                if self.active_relay() != Some(fastest.url.as_str()) {
+                    log::info!("Switching to relay {} ({:?} ms)", fastest.url, fastest.latency_ms);
+                }
+                // Always one of ours, so this can't fail
+                let _ = self.set_active_relay(&fastest.url);
+            }
+        }
+
+        let active = self.active_relay();
+        for result in &mut results {
+            result.active = active == Some(result.url.as_str());
+        }
+        results
+    }
+
+    /// Round trip of a health check against `relay`, or `None` if it
+    /// didn't answer successfully
+    async fn ping(&self, relay: &str) -> Option<Duration> {
         let url = format!("{}/health", relay);
+        let request = TransportRequest::get(url).timeout(Duration::from_secs(5));
+
+        let started = Instant::now();
+        match self.transport.send(request).await {
+            Ok(response) if response.status().is_success() => Some(started.elapsed()),
+            _ => None,
+        }
+    }
+}
+
+/// Read a `/trust` response. The signed record is optional, and one that
+/// doesn't parse is treated as missing rather than failing the lookup.
+pub(crate) fn parse_network_trust(
+    public_key: &str,
+    body: &serde_json::Value,
+) -> Result<(NetworkTrust, Option<SignedRecord>)> {
+    let data = body
+        .get("data")
+        .ok_or_else(|| Error::Network(format!("Malformed trust response for {}", public_key)))?;
 
-        match self.client.get(&url).timeout(Duration::from_secs(5)).send().await {
-            Ok(response) => Ok(response.status().is_success()),
-            Err(_) => Ok(false),
+    if let Some(identity) = data.get("identity").and_then(|v| v.as_str()) {
+        if !identity.eq_ignore_ascii_case(public_key) {
+            return Err(Error::Network(format!(
+                "Relay answered for {} when asked about {}",
+                identity, public_key
+            )));
         }
     }
+
+    let count = |field: &str| data.get(field).and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+    let trust = NetworkTrust {
+        public_key: public_key.to_lowercase(),
+        trust_score: data.get("trust_score").and_then(|v| v.as_f64()).unwrap_or(0.0),
+        breadcrumb_count: count("breadcrumb_count"),
+        epoch_count: count("epoch_count"),
+        verified: data.get("verified").and_then(|v| v.as_bool()).unwrap_or(false),
+        rank: data.get("rank").and_then(|v| v.as_u64()).map(|r| r as u32),
+        fetched_at: chrono::Utc::now().to_rfc3339(),
+    };
+    let record = data
+        .get("record")
+        .cloned()
+        .and_then(|v| serde_json::from_value(v).ok());
+
+    Ok((trust, record))
+}
+
+/// Walk the rotation records back from `current_key`, returning each prior
+/// key, most recent first.
+///
+/// A link is followed only if its signature by the outgoing key verifies;
+/// the walk stops at the first missing or forged link, or if a key repeats.
+pub(crate) fn rotation_chain(current_key: &str, rotations: &[KeyRotation]) -> Vec<String> {
+    let mut chain: Vec<String> = Vec::new();
+    let mut key = current_key.to_lowercase();
+
+    while let Some(rotation) = rotations.iter().find(|r| {
+        r.new_key.eq_ignore_ascii_case(&key)
+            && CryptoEngine::verify(&r.previous_key, r.signing_payload().as_bytes(), &r.signature)
+                .unwrap_or(false)
+    }) {
+        let previous = rotation.previous_key.to_lowercase();
+        if previous == current_key.to_lowercase() || chain.contains(&previous) {
+            log::warn!("Key rotation chain for {} loops back on itself", current_key);
+            break;
+        }
+        chain.push(previous.clone());
+        key = previous;
+    }
+
+    chain
+}
+
+/// Compute a stamp for `envelope` off the async runtime
+async fn stamp_pow(envelope: &GnsEnvelope, difficulty: u8) -> Result<PowStamp> {
+    let input = envelope.pow_input();
+    let nonce = tokio::task::spawn_blocking(move || CryptoEngine::compute_pow(&input, difficulty))
+        .await
+        .map_err(|e| Error::Internal(format!("Proof-of-work task failed: {}", e)))??;
+    Ok(PowStamp { difficulty, nonce })
+}
+
+/// The difficulty a relay rejecting an envelope with
+/// `428 { "powDifficulty": n }` wants
+fn required_pow(status: StatusCode, body: &serde_json::Value) -> Option<u8> {
+    if status != StatusCode::PRECONDITION_REQUIRED {
+        return None;
+    }
+    body["powDifficulty"].as_u64().and_then(|d| u8::try_from(d).ok())
+}
+
+/// Check an envelope's stamp is over its own ID and recipient and meets
+/// `difficulty`, as a relay requiring proof-of-work does
+pub fn verify_envelope_pow(envelope: &GnsEnvelope, difficulty: u8) -> bool {
+    envelope.pow.as_ref().is_some_and(|stamp| {
+        stamp.difficulty >= difficulty
+            && CryptoEngine::verify_pow(&envelope.pow_input(), stamp.nonce, stamp.difficulty)
+    })
+}
+
+/// Read `{ "data": { "count": n, "oldest_timestamp": t } }`; anything
+/// else means the relay doesn't know
+fn parse_inbox_status(body: &serde_json::Value) -> InboxStatus {
+    let data = &body["data"];
+    match data["count"].as_u64() {
+        Some(count) => InboxStatus::Known {
+            queued: u32::try_from(count).unwrap_or(u32::MAX),
+            oldest_at: data["oldest_timestamp"].as_str().map(String::from),
+        },
+        None => InboxStatus::Unknown,
+    }
+}
+
+/// Filter out envelopes whose signature doesn't check out against their
+/// sender, logging each one dropped. A fetch can return a whole backlog,
+/// so the signatures are verified as one batch.
+fn drop_unsigned(envelopes: Vec<GnsEnvelope>) -> Vec<GnsEnvelope> {
+    let signed: Vec<String> = envelopes.iter().map(GnsEnvelope::signed_data).collect();
+    let items: Vec<(&str, &[u8], &str)> = envelopes
+        .iter()
+        .zip(&signed)
+        .map(|(envelope, data)| (envelope.from_pk.as_str(), data.as_bytes(), envelope.signature.as_str()))
+        .collect();
+    let valid = CryptoEngine::verify_batch(&items);
+
+    envelopes
+        .into_iter()
+        .zip(valid)
+        .filter_map(|(envelope, valid)| {
+            if !valid {
+                log::warn!(
+                    "Dropping message {} from {}: bad signature",
+                    envelope.message_id,
+                    envelope.from_pk
+                );
+                return None;
+            }
+            Some(envelope)
+        })
+        .collect()
+}
+
+/// Filter out envelopes over the size limit, logging each one dropped
+fn drop_oversize(envelopes: Vec<GnsEnvelope>, max_bytes: usize) -> Vec<GnsEnvelope> {
+    envelopes
+        .into_iter()
+        .filter(|envelope| {
+            // Unparseable payloads are measured by their encoded length
+            let size = envelope
+                .payload_len()
+                .unwrap_or(envelope.encrypted_payload.len());
+            if size > max_bytes {
+                log::warn!(
+                    "Dropping message {} from {}: payload is {} bytes, limit is {}",
+                    envelope.message_id,
+                    envelope.from_pk,
+                    size,
+                    max_bytes
+                );
+                return false;
+            }
+            true
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -358,4 +893,329 @@ mod tests {
         let client = NetworkClient::new(&[]).unwrap();
         assert!(client.primary_relay().is_err());
     }
+
+    fn rotation(previous_sk: &str, previous_pk: &str, new_pk: &str) -> KeyRotation {
+        let mut rotation = KeyRotation {
+            previous_key: previous_pk.to_string(),
+            new_key: new_pk.to_string(),
+            rotated_at: "2025-01-01T00:00:00Z".to_string(),
+            signature: String::new(),
+        };
+        rotation.signature =
+            CryptoEngine::sign(previous_sk, rotation.signing_payload().as_bytes()).unwrap();
+        rotation
+    }
+
+    #[test]
+    fn test_rotation_chain_stops_at_forged_link() {
+        let keys: Vec<_> = (0..4).map(|_| CryptoEngine::generate_keypair().unwrap()).collect();
+        let (sk0, pk0) = &keys[0];
+        let (sk1, pk1) = &keys[1];
+        let (_, pk2) = &keys[2];
+        let (sk3, pk3) = &keys[3];
+
+        // pk0 -> pk1 -> pk2, listed out of order
+        let rotations = vec![rotation(sk1, pk1, pk2), rotation(sk0, pk0, pk1)];
+        assert_eq!(rotation_chain(pk2, &rotations), vec![pk1.clone(), pk0.clone()]);
+        assert_eq!(rotation_chain(pk1, &rotations), vec![pk0.clone()]);
+        assert!(rotation_chain(pk3, &rotations).is_empty());
+
+        // A "rotation" to pk2 signed by someone other than the outgoing key
+        let mut forged = rotation(sk3, pk3, pk2);
+        forged.previous_key = pk0.clone();
+        assert!(rotation_chain(pk2, &[forged]).is_empty());
+    }
+
+    #[test]
+    fn test_parse_inbox_status() {
+        let body = serde_json::json!({
+            "success": true,
+            "data": { "count": 12, "oldest_timestamp": "2025-01-01T00:00:00Z" }
+        });
+        assert_eq!(
+            parse_inbox_status(&body),
+            InboxStatus::Known { queued: 12, oldest_at: Some("2025-01-01T00:00:00Z".to_string()) }
+        );
+
+        let empty = serde_json::json!({ "success": true, "data": { "count": 0 } });
+        assert_eq!(parse_inbox_status(&empty), InboxStatus::Known { queued: 0, oldest_at: None });
+
+        // Some other endpoint answering on the same path
+        assert_eq!(parse_inbox_status(&serde_json::json!({ "ok": true })), InboxStatus::Unknown);
+        assert_eq!(parse_inbox_status(&serde_json::Value::Null), InboxStatus::Unknown);
+
+        let wire = serde_json::to_value(InboxStatus::Known { queued: 3, oldest_at: None }).unwrap();
+        assert_eq!(wire, serde_json::json!({ "status": "known", "queued": 3, "oldestAt": null }));
+        assert_eq!(
+            serde_json::to_value(InboxStatus::Unknown).unwrap(),
+            serde_json::json!({ "status": "unknown" })
+        );
+    }
+
+    fn envelope_with_payload(id: &str, plaintext_len: usize) -> GnsEnvelope {
+        let key = hex::encode([7u8; 32]);
+        let (nonce, ciphertext) =
+            crate::core::CryptoEngine::encrypt(&key, &vec![b'x'; plaintext_len]).unwrap();
+        GnsEnvelope {
+            version: 1,
+            from_pk: "aa".repeat(32),
+            to_pk: "bb".repeat(32),
+            encrypted_payload: format!("{}:{}", nonce, ciphertext),
+            ephemeral_key: String::new(),
+            recipient_key: None,
+            signature: String::new(),
+            message_id: id.to_string(),
+            timestamp: "2025-01-01T00:00:00Z".to_string(),
+            pow: None,
+        }
+    }
+
+    #[test]
+    fn test_drop_oversize_envelopes() {
+        // Lengths around base64 padding boundaries all measure exactly
+        for len in [1000, 1001, 1002] {
+            assert_eq!(envelope_with_payload("m", len).payload_len(), Some(len));
+        }
+
+        let kept = drop_oversize(
+            vec![envelope_with_payload("at", 1000), envelope_with_payload("over", 1001)],
+            1000,
+        );
+        let ids: Vec<_> = kept.iter().map(|e| e.message_id.as_str()).collect();
+        assert_eq!(ids, vec!["at"]);
+    }
+
+    #[test]
+    fn test_drop_unsigned_envelopes() {
+        let (secret, public) = CryptoEngine::generate_keypair().unwrap();
+        let signed = |id: &str| {
+            let mut envelope = envelope_with_payload(id, 10);
+            envelope.from_pk = public.clone();
+            envelope.signature = CryptoEngine::sign(&secret, envelope.signed_data().as_bytes()).unwrap();
+            envelope
+        };
+        let mut tampered = signed("tampered");
+        tampered.encrypted_payload.push('A');
+        let mut forged = signed("forged");
+        forged.from_pk = CryptoEngine::generate_keypair().unwrap().1;
+
+        let kept = drop_unsigned(vec![signed("a"), tampered, forged, envelope_with_payload("unsigned", 10), signed("b")]);
+        let ids: Vec<_> = kept.iter().map(|e| e.message_id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_pow_stamp_verifies_for_its_envelope() {
+        let mut envelope = envelope_with_payload("m1", 10);
+        assert!(!verify_envelope_pow(&envelope, 0));
+        // Off by default, and left out of the JSON when absent
+        assert!(NetworkClient::new(&[]).unwrap().pow.is_none());
+        assert!(serde_json::to_value(&envelope).unwrap().get("pow").is_none());
+
+        envelope.pow = Some(stamp_pow(&envelope, 10).await.unwrap());
+        assert!(verify_envelope_pow(&envelope, 10));
+        assert!(!verify_envelope_pow(&envelope, 11));
+        let wire = serde_json::to_value(&envelope).unwrap();
+        assert_eq!(wire["pow"]["difficulty"], 10);
+
+        // Moving the stamp to another recipient breaks it
+        let mut resent = envelope.clone();
+        resent.to_pk = "cc".repeat(32);
+        assert!(!verify_envelope_pow(&resent, 10));
+    }
+
+    #[test]
+    fn test_pow_difficulty_adapts_within_limit() {
+        let client = NetworkClient::new(&[]).unwrap().with_pow(12, 16);
+        let pow = client.pow.as_ref().unwrap();
+
+        let asked = |d: u64| required_pow(StatusCode::PRECONDITION_REQUIRED, &serde_json::json!({ "powDifficulty": d }));
+        assert_eq!(asked(14), Some(14));
+        assert_eq!(required_pow(StatusCode::BAD_REQUEST, &serde_json::json!({ "powDifficulty": 14 })), None);
+        assert_eq!(asked(300), None);
+
+        assert!(pow.raise(14));
+        assert_eq!(pow.difficulty.load(Ordering::Relaxed), 14);
+        // Asking for the same again, less, or past the limit doesn't retry
+        assert!(!pow.raise(14));
+        assert!(!pow.raise(13));
+        assert!(!pow.raise(17));
+        assert_eq!(pow.difficulty.load(Ordering::Relaxed), 14);
+
+        // Configured limits are kept sane
+        let capped = NetworkClient::new(&[]).unwrap().with_pow(20, 255);
+        assert_eq!(capped.pow.unwrap().max_difficulty, MAX_POW_DIFFICULTY);
+    }
+
+    #[tokio::test]
+    async fn test_send_retries_at_the_difficulty_the_relay_asks_for() {
+        use crate::core::transport::{MockTransport, TransportResponse};
+
+        let relay = vec!["https://relay.test".to_string()];
+        let transport = Arc::new(MockTransport::new(|request| {
+            let envelope: GnsEnvelope = request.read_json()?;
+            Ok(if verify_envelope_pow(&envelope, 6) {
+                TransportResponse::json(StatusCode::OK, &serde_json::json!({ "success": true }))
+            } else {
+                TransportResponse::json(StatusCode::PRECONDITION_REQUIRED, &serde_json::json!({ "powDifficulty": 6 }))
+            })
+        }));
+        let client = NetworkClient::with_transport(&relay, transport.clone()).with_pow(4, 8);
+        client.send_message(&envelope_with_payload("m1", 10)).await.unwrap();
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].path(), "/api/messages");
+        let first: GnsEnvelope = requests[0].read_json().unwrap();
+        assert_eq!(first.pow.map(|stamp| stamp.difficulty), Some(4));
+
+        // Past the limit there is no second attempt
+        let strict = Arc::new(MockTransport::always(
+            StatusCode::PRECONDITION_REQUIRED,
+            serde_json::json!({ "powDifficulty": 9, "error": "More work required" }),
+        ));
+        let client = NetworkClient::with_transport(&relay, strict.clone()).with_pow(4, 8);
+        let err = client.send_message(&envelope_with_payload("m2", 10)).await.unwrap_err();
+        assert_eq!(err.to_string(), "Network error: More work required");
+        assert_eq!(strict.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_parse_network_trust() {
+        use crate::core::transport::{MockTransport, TransportResponse};
+
+        let pk = "ab".repeat(32);
+        let body = serde_json::json!({
+            "success": true,
+            "data": {
+                "identity": pk.to_uppercase(),
+                "trust_score": 72.5,
+                "breadcrumb_count": 1200,
+                "epoch_count": 11,
+                "verified": true,
+                "rank": 340,
+                "record": { "pkRoot": pk, "recordJson": "not a record", "signature": "00" }
+            }
+        });
+        let relay = vec!["https://relay.test".to_string()];
+        let transport = Arc::new(MockTransport::always(StatusCode::OK, body));
+        let client = NetworkClient::with_transport(&relay, transport.clone());
+
+        let (trust, record) = client.get_network_trust(&pk).await.unwrap();
+        assert_eq!(transport.requests()[0].path(), format!("/api/identities/{}/trust", pk));
+        assert_eq!((trust.trust_score, trust.breadcrumb_count, trust.epoch_count), (72.5, 1200, 11));
+        assert!(trust.verified);
+        assert_eq!(trust.rank, Some(340));
+        // An unreadable record is dropped, not fatal
+        assert!(record.is_none());
+
+        // Sparse response: missing figures are zero, no rank
+        let (sparse, _) = parse_network_trust(&pk, &serde_json::json!({ "data": { "trust_score": 10.0 } })).unwrap();
+        assert_eq!((sparse.breadcrumb_count, sparse.verified, sparse.rank), (0, false, None));
+
+        // Answering for someone else is an error
+        let other = serde_json::json!({ "data": { "identity": "cd".repeat(32), "trust_score": 99.0 } });
+        assert!(parse_network_trust(&pk, &other).is_err());
+        assert!(parse_network_trust(&pk, &serde_json::json!({ "error": "nope" })).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_benchmark_selects_the_fastest_reachable_relay() {
+        use crate::core::transport::{MockTransport, TransportResponse};
+
+        let relays = vec![
+            "https://slow.test".to_string(),
+            "https://down.test".to_string(),
+            "https://fast.test".to_string(),
+        ];
+        let transport = Arc::new(MockTransport::new(|request| {
+            let (delay, status) = match request.url.as_str() {
+                "https://slow.test/health" => (60, StatusCode::OK),
+                "https://fast.test/health" => (5, StatusCode::OK),
+                _ => (0, StatusCode::SERVICE_UNAVAILABLE),
+            };
+            std::thread::sleep(Duration::from_millis(delay));
+            Ok(TransportResponse::json(status, &serde_json::json!({})))
+        }));
+        let client = NetworkClient::with_transport(&relays, transport);
+
+        // Measuring alone doesn't move the client
+        let measured = client.benchmark_relays(false).await;
+        assert_eq!(client.active_relay(), Some("https://slow.test"));
+        assert!(measured.iter().find(|r| r.url == "https://slow.test").unwrap().active);
+
+        let results = client.benchmark_relays(true).await;
+        let order: Vec<_> = results.iter().map(|r| r.url.as_str()).collect();
+        assert_eq!(order, ["https://fast.test", "https://slow.test", "https://down.test"]);
+        assert!(results[0].latency_ms < results[1].latency_ms);
+        assert_eq!((results[2].reachable, results[2].latency_ms), (false, None));
+
+        assert_eq!(client.active_relay(), Some("https://fast.test"));
+        assert!(results[0].active && !results[1].active);
+        assert!(client.set_active_relay("https://elsewhere.test").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_upload_resumes_from_last_acked_offset() {
+        use crate::core::transport::{MockTransport, TransportResponse};
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let plaintext: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let (key, nonce, blob) = CryptoEngine::encrypt_blob(&plaintext).unwrap();
+
+        // The relay assembles chunks and drops the connection on the third
+        let assembled = Arc::new(Mutex::new(Vec::new()));
+        let puts = AtomicUsize::new(0);
+        let server = assembled.clone();
+        let transport = Arc::new(MockTransport::new(move |request| {
+            let ok = |data| Ok(TransportResponse::json(StatusCode::OK, &serde_json::json!({ "data": data })));
+            match (request.method.as_str(), request.path().as_str()) {
+                ("POST", "/api/blobs/uploads") => ok(serde_json::json!({ "uploadId": "up1" })),
+                ("POST", "/api/blobs/uploads/up1/complete") => ok(serde_json::json!({ "blobId": "blob1" })),
+                ("PUT", "/api/blobs/uploads/up1") => {
+                    if puts.fetch_add(1, Ordering::SeqCst) == 2 {
+                        return Err(Error::Network("connection reset".to_string()));
+                    }
+                    let body: serde_json::Value = request.read_json()?;
+                    let chunk = STANDARD.decode(body["data"].as_str().unwrap()).unwrap();
+                    let mut server = server.lock().unwrap();
+                    server.extend_from_slice(&chunk);
+                    ok(serde_json::json!({ "offset": server.len() }))
+                }
+                _ => Ok(TransportResponse::json(StatusCode::NOT_FOUND, &serde_json::json!({}))),
+            }
+        }));
+        let relay = vec!["https://relay.test".to_string()];
+        let client = NetworkClient::with_transport(&relay, transport.clone()).with_upload_chunk_bytes(4096);
+
+        let progress = Mutex::new(Vec::new());
+        let record = |p: &UploadProgress| progress.lock().unwrap().push(p.uploaded_bytes);
+        assert!(client.upload_blob(&blob, record).await.is_err());
+        let blob_id = client.upload_blob(&blob, record).await.unwrap();
+        assert_eq!(blob_id, "blob1");
+
+        // One upload was opened, and the retry picked up at the third chunk
+        let total = blob.len();
+        let ranges: Vec<_> = transport
+            .requests()
+            .iter()
+            .filter(|r| r.method == reqwest::Method::PUT)
+            .map(|r| r.headers.iter().find(|(k, _)| k == "Content-Range").unwrap().1.clone())
+            .collect();
+        assert_eq!(
+            ranges,
+            [
+                format!("bytes 0-4095/{}", total),
+                format!("bytes 4096-8191/{}", total),
+                format!("bytes 8192-{}/{}", total - 1, total),
+                format!("bytes 8192-{}/{}", total - 1, total),
+            ]
+        );
+        assert_eq!(transport.requests().iter().filter(|r| r.path() == "/api/blobs/uploads").count(), 1);
+        assert_eq!(*progress.lock().unwrap(), [4096, 8192, total as u64]);
+
+        let assembled = assembled.lock().unwrap();
+        assert_eq!(*assembled, blob);
+        assert_eq!(CryptoEngine::decrypt_blob(&key, &nonce, &assembled).unwrap(), plaintext);
+    }
 }
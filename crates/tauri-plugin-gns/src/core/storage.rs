@@ -167,6 +167,14 @@ impl StorageManager {
                 encryption_key TEXT,
                 trust_score REAL,
                 breadcrumb_count INTEGER,
+                cached_at TEXT NOT NULL,
+                previous_keys TEXT
+            );
+
+            -- Network trust cache
+            CREATE TABLE IF NOT EXISTS network_trust_cache (
+                public_key TEXT PRIMARY KEY,
+                report TEXT NOT NULL,
                 cached_at TEXT NOT NULL
             );
 
@@ -182,8 +190,21 @@ impl StorageManager {
                 UNIQUE(owner_pk, contact_pk),
                 FOREIGN KEY (owner_pk) REFERENCES identities(public_key)
             );
+
+            -- Audit log
+            CREATE TABLE IF NOT EXISTS audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp TEXT NOT NULL,
+                action TEXT NOT NULL,
+                target TEXT,
+                result TEXT NOT NULL
+            );
             "#,
         )?;
+
+        // Caches created before rotation history was tracked; fails
+        // harmlessly once the column exists
+        let _ = conn.execute("ALTER TABLE handle_cache ADD COLUMN previous_keys TEXT", []);
         
         Ok(())
     }
@@ -398,50 +419,33 @@ impl StorageManager {
         
         // Build params vector based on what was added  
         let messages: Vec<Message> = if let Some(ref peer) = query.peer_pk {
-            stmt.query_map(params![identity_pk, peer, peer], |row| {
-                let decrypted_cache: Option<String> = row.get(9)?;
-                let decrypted = decrypted_cache
-                    .and_then(|s| serde_json::from_str(&s).ok());
-                
-                Ok(Message {
-                    id: row.get(0)?,
-                    from_pk: row.get(1)?,
-                    to_pk: row.get(2)?,
-                    payload: row.get(3)?,
-                    ephemeral_key: row.get(4)?,
-                    signature: row.get(5)?,
-                    created_at: row.get(6)?,
-                    received_at: row.get(7)?,
-                    is_read: row.get::<_, i32>(8)? == 1,
-                    decrypted,
-                })
-            })?
+            stmt.query_map(params![identity_pk, peer, peer], message_from_row)?
             .collect::<std::result::Result<Vec<_>, _>>()?
         } else {
-            stmt.query_map(params![identity_pk], |row| {
-                let decrypted_cache: Option<String> = row.get(9)?;
-                let decrypted = decrypted_cache
-                    .and_then(|s| serde_json::from_str(&s).ok());
-                
-                Ok(Message {
-                    id: row.get(0)?,
-                    from_pk: row.get(1)?,
-                    to_pk: row.get(2)?,
-                    payload: row.get(3)?,
-                    ephemeral_key: row.get(4)?,
-                    signature: row.get(5)?,
-                    created_at: row.get(6)?,
-                    received_at: row.get(7)?,
-                    is_read: row.get::<_, i32>(8)? == 1,
-                    decrypted,
-                })
-            })?
+            stmt.query_map(params![identity_pk], message_from_row)?
             .collect::<std::result::Result<Vec<_>, _>>()?
         };
         
         Ok(messages)
     }
 
+    /// Get a single message by ID
+    pub fn get_message(&self, message_id: &str) -> Result<Option<Message>> {
+        let conn = self.conn.lock().map_err(|e| Error::Storage(e.to_string()))?;
+
+        conn.query_row(
+            r#"
+            SELECT id, from_pk, to_pk, payload, ephemeral_key, signature, created_at, received_at, is_read, decrypted_cache
+            FROM messages
+            WHERE id = ?1
+            "#,
+            params![message_id],
+            message_from_row,
+        )
+        .optional()
+        .map_err(|e| Error::Storage(e.to_string()))
+    }
+
     /// Mark message as read
     pub fn mark_message_read(&self, message_id: &str) -> Result<()> {
         let conn = self.conn.lock().map_err(|e| Error::Storage(e.to_string()))?;
@@ -522,7 +526,7 @@ impl StorageManager {
                 breadcrumb.prev_hash,
                 breadcrumb.hash,
                 breadcrumb.signature,
-                format!("{:?}", breadcrumb.source).to_lowercase(),
+                breadcrumb.source.as_str(),
                 breadcrumb.accuracy,
                 if breadcrumb.published { 1 } else { 0 },
             ],
@@ -537,6 +541,80 @@ impl StorageManager {
         Ok(())
     }
 
+    /// Save a run of breadcrumbs in one transaction, bumping the identity's
+    /// breadcrumb count once. Either all of them are written or none are.
+    pub fn save_breadcrumbs(&self, identity_pk: &str, breadcrumbs: &[Breadcrumb]) -> Result<()> {
+        let mut conn = self.conn.lock().map_err(|e| Error::Storage(e.to_string()))?;
+        let tx = conn.transaction()?;
+
+        {
+            let mut insert = tx.prepare(
+                r#"
+                INSERT INTO breadcrumbs
+                (id, identity_pk, h3_index, h3_resolution, timestamp, prev_hash, hash, signature, source, accuracy, published)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+                "#,
+            )?;
+            for breadcrumb in breadcrumbs {
+                insert.execute(params![
+                    breadcrumb.id,
+                    identity_pk,
+                    breadcrumb.h3_index,
+                    breadcrumb.h3_resolution,
+                    breadcrumb.timestamp,
+                    breadcrumb.prev_hash,
+                    breadcrumb.hash,
+                    breadcrumb.signature,
+                    breadcrumb.source.as_str(),
+                    breadcrumb.accuracy,
+                    if breadcrumb.published { 1 } else { 0 },
+                ])?;
+            }
+        }
+
+        tx.execute(
+            "UPDATE identities SET breadcrumb_count = breadcrumb_count + ?1 WHERE public_key = ?2",
+            params![breadcrumbs.len() as i64, identity_pk],
+        )?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Hash of the most recently saved breadcrumb for an identity
+    pub fn get_last_breadcrumb_hash(&self, identity_pk: &str) -> Result<Option<String>> {
+        let conn = self.conn.lock().map_err(|e| Error::Storage(e.to_string()))?;
+
+        conn.query_row(
+            "SELECT hash FROM breadcrumbs WHERE identity_pk = ?1 ORDER BY rowid DESC LIMIT 1",
+            params![identity_pk],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| Error::Storage(e.to_string()))
+    }
+
+    /// Breadcrumb counts per (H3 cell, source) for an identity
+    pub fn get_breadcrumb_cell_sources(
+        &self,
+        identity_pk: &str,
+    ) -> Result<Vec<(String, LocationSource, u32)>> {
+        let conn = self.conn.lock().map_err(|e| Error::Storage(e.to_string()))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT h3_index, source, COUNT(*) FROM breadcrumbs
+             WHERE identity_pk = ?1 GROUP BY h3_index, source",
+        )?;
+        let cells = stmt
+            .query_map(params![identity_pk], |row| {
+                let source: String = row.get(1)?;
+                Ok((row.get(0)?, LocationSource::parse(&source), row.get(2)?))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(cells)
+    }
+
     /// Get breadcrumb count for an identity
     pub fn get_breadcrumb_count(&self, identity_pk: &str) -> Result<u32> {
         let conn = self.conn.lock().map_err(|e| Error::Storage(e.to_string()))?;
@@ -549,6 +627,122 @@ impl StorageManager {
         .map_err(|e| Error::Storage(e.to_string()))
     }
 
+    /// Breadcrumbs not yet in an epoch, oldest first
+    pub fn get_unpublished_breadcrumbs(&self, identity_pk: &str) -> Result<Vec<Breadcrumb>> {
+        let conn = self.conn.lock().map_err(|e| Error::Storage(e.to_string()))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, h3_index, h3_resolution, timestamp, prev_hash, hash, signature, source, accuracy
+             FROM breadcrumbs WHERE identity_pk = ?1 AND published = 0 ORDER BY rowid",
+        )?;
+        let breadcrumbs = stmt
+            .query_map(params![identity_pk], |row| {
+                let source: String = row.get(7)?;
+                Ok(Breadcrumb {
+                    id: row.get(0)?,
+                    h3_index: row.get(1)?,
+                    h3_resolution: row.get(2)?,
+                    timestamp: row.get(3)?,
+                    prev_hash: row.get(4)?,
+                    hash: row.get(5)?,
+                    signature: row.get(6)?,
+                    source: LocationSource::parse(&source),
+                    accuracy: row.get(8)?,
+                    published: false,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(breadcrumbs)
+    }
+
+    /// How many breadcrumbs aren't in an epoch yet, and the timestamp of
+    /// the oldest of them
+    pub fn get_unpublished_breadcrumb_stats(&self, identity_pk: &str) -> Result<(u32, Option<String>)> {
+        let conn = self.conn.lock().map_err(|e| Error::Storage(e.to_string()))?;
+
+        conn.query_row(
+            "SELECT COUNT(*), MIN(timestamp) FROM breadcrumbs WHERE identity_pk = ?1 AND published = 0",
+            params![identity_pk],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| Error::Storage(e.to_string()))
+    }
+
+    // ==================== Epoch Operations ====================
+
+    /// The most recent epoch this device published for an identity
+    pub fn get_last_epoch(&self, identity_pk: &str) -> Result<Option<EpochHeader>> {
+        let conn = self.conn.lock().map_err(|e| Error::Storage(e.to_string()))?;
+
+        conn.query_row(
+            "SELECT epoch_hash, epoch_index, start_time, end_time, merkle_root, block_count, prev_epoch_hash, signature
+             FROM epochs WHERE identity_pk = ?1 ORDER BY epoch_index DESC LIMIT 1",
+            params![identity_pk],
+            |row| {
+                Ok(EpochHeader {
+                    identity: identity_pk.to_string(),
+                    epoch_hash: row.get(0)?,
+                    epoch_index: row.get(1)?,
+                    start_time: row.get(2)?,
+                    end_time: row.get(3)?,
+                    merkle_root: row.get(4)?,
+                    block_count: row.get(5)?,
+                    prev_epoch_hash: row.get(6)?,
+                    signature: row.get(7)?,
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| Error::Storage(e.to_string()))
+    }
+
+    /// Record a published epoch and mark the breadcrumbs it covers as
+    /// published, in one transaction. Fails without changing anything if
+    /// any of them is already in another epoch.
+    pub fn save_published_epoch(
+        &self,
+        identity_pk: &str,
+        epoch: &EpochHeader,
+        breadcrumb_ids: &[&str],
+    ) -> Result<()> {
+        let mut conn = self.conn.lock().map_err(|e| Error::Storage(e.to_string()))?;
+        let tx = conn.transaction()?;
+
+        {
+            let mut mark = tx.prepare(
+                "UPDATE breadcrumbs SET published = 1 WHERE id = ?1 AND identity_pk = ?2 AND published = 0",
+            )?;
+            for id in breadcrumb_ids {
+                if mark.execute(params![id, identity_pk])? != 1 {
+                    return Err(Error::Storage(format!("Breadcrumb {} is already in an epoch", id)));
+                }
+            }
+        }
+
+        tx.execute(
+            r#"
+            INSERT INTO epochs
+            (epoch_hash, identity_pk, epoch_index, start_time, end_time, merkle_root, block_count, prev_epoch_hash, signature)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            "#,
+            params![
+                epoch.epoch_hash,
+                identity_pk,
+                epoch.epoch_index,
+                epoch.start_time,
+                epoch.end_time,
+                epoch.merkle_root,
+                epoch.block_count,
+                epoch.prev_epoch_hash,
+                epoch.signature,
+            ],
+        )?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
     // ==================== Handle Cache ====================
 
     /// Cache a handle resolution
@@ -558,8 +752,8 @@ impl StorageManager {
         conn.execute(
             r#"
             INSERT OR REPLACE INTO handle_cache 
-            (handle, public_key, encryption_key, trust_score, breadcrumb_count, cached_at)
-            VALUES (?1, ?2, ?3, ?4, ?5, datetime('now'))
+            (handle, public_key, encryption_key, trust_score, breadcrumb_count, cached_at, previous_keys)
+            VALUES (?1, ?2, ?3, ?4, ?5, datetime('now'), ?6)
             "#,
             params![
                 handle,
@@ -567,6 +761,7 @@ impl StorageManager {
                 resolved.encryption_key,
                 resolved.trust_score,
                 resolved.breadcrumb_count,
+                serde_json::to_string(&resolved.previous_keys)?,
             ],
         )?;
         
@@ -579,7 +774,7 @@ impl StorageManager {
         
         conn.query_row(
             r#"
-            SELECT handle, public_key, encryption_key, trust_score, breadcrumb_count, cached_at
+            SELECT handle, public_key, encryption_key, trust_score, breadcrumb_count, cached_at, previous_keys
             FROM handle_cache 
             WHERE handle = ?1 
               AND datetime(cached_at, '+' || ?2 || ' seconds') > datetime('now')
@@ -594,12 +789,129 @@ impl StorageManager {
                     breadcrumb_count: row.get(4)?,
                     from_cache: true,
                     resolved_at: row.get(5)?,
+                    previous_keys: row
+                        .get::<_, Option<String>>(6)?
+                        .and_then(|json| serde_json::from_str(&json).ok())
+                        .unwrap_or_default(),
                 })
             },
         )
         .optional()
         .map_err(|e| Error::Storage(e.to_string()))
     }
+
+    /// List every cached handle, regardless of age
+    pub fn list_cached_handles(&self) -> Result<Vec<String>> {
+        let conn = self.conn.lock().map_err(|e| Error::Storage(e.to_string()))?;
+
+        let mut stmt = conn.prepare("SELECT handle FROM handle_cache ORDER BY handle")?;
+        let handles = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<String>, _>>()?;
+
+        Ok(handles)
+    }
+
+    /// Remove a single cached handle. Returns whether an entry existed.
+    pub fn remove_cached_handle(&self, handle: &str) -> Result<bool> {
+        let conn = self.conn.lock().map_err(|e| Error::Storage(e.to_string()))?;
+
+        let removed = conn.execute("DELETE FROM handle_cache WHERE handle = ?1", params![handle])?;
+
+        Ok(removed > 0)
+    }
+
+    /// Cache a checked network trust report
+    pub fn cache_network_trust(&self, report: &NetworkTrustReport) -> Result<()> {
+        let conn = self.conn.lock().map_err(|e| Error::Storage(e.to_string()))?;
+
+        conn.execute(
+            "INSERT OR REPLACE INTO network_trust_cache (public_key, report, cached_at) VALUES (?1, ?2, datetime('now'))",
+            params![report.network.public_key.to_lowercase(), serde_json::to_string(report)?],
+        )?;
+
+        Ok(())
+    }
+
+    /// Get a cached network trust report younger than `max_age_seconds`
+    pub fn get_cached_network_trust(
+        &self,
+        public_key: &str,
+        max_age_seconds: u64,
+    ) -> Result<Option<NetworkTrustReport>> {
+        let conn = self.conn.lock().map_err(|e| Error::Storage(e.to_string()))?;
+
+        let report: Option<String> = conn
+            .query_row(
+                r#"
+                SELECT report FROM network_trust_cache
+                WHERE public_key = ?1
+                  AND datetime(cached_at, '+' || ?2 || ' seconds') > datetime('now')
+                "#,
+                params![public_key.to_lowercase(), max_age_seconds as i64],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(report
+            .and_then(|json| serde_json::from_str::<NetworkTrustReport>(&json).ok())
+            .map(|report| NetworkTrustReport { from_cache: true, ..report }))
+    }
+
+    // ==================== Audit Log ====================
+
+    /// Record a sensitive action and how it ended
+    pub fn record_audit(&self, action: &str, target: Option<&str>, result: &str) -> Result<()> {
+        let conn = self.conn.lock().map_err(|e| Error::Storage(e.to_string()))?;
+
+        conn.execute(
+            "INSERT INTO audit_log (timestamp, action, target, result) VALUES (datetime('now'), ?1, ?2, ?3)",
+            params![action, target, result],
+        )?;
+
+        Ok(())
+    }
+
+    /// The latest `limit` audit entries, newest first
+    pub fn get_audit_log(&self, limit: u32) -> Result<Vec<AuditEntry>> {
+        let conn = self.conn.lock().map_err(|e| Error::Storage(e.to_string()))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT timestamp, action, target, result FROM audit_log ORDER BY id DESC LIMIT ?1",
+        )?;
+        let entries = stmt
+            .query_map(params![limit], |row| {
+                Ok(AuditEntry {
+                    timestamp: row.get(0)?,
+                    action: row.get(1)?,
+                    target: row.get(2)?,
+                    result: row.get(3)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(entries)
+    }
+}
+
+/// Map a `messages` row (in the column order used by the SELECTs above).
+/// A cache entry that no longer parses is treated as missing.
+fn message_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Message> {
+    let decrypted_cache: Option<String> = row.get(9)?;
+    let decrypted = decrypted_cache.and_then(|s| serde_json::from_str(&s).ok());
+
+    Ok(Message {
+        id: row.get(0)?,
+        from_pk: row.get(1)?,
+        to_pk: row.get(2)?,
+        payload: row.get(3)?,
+        ephemeral_key: row.get(4)?,
+        signature: row.get(5)?,
+        created_at: row.get(6)?,
+        received_at: row.get(7)?,
+        is_read: row.get::<_, i32>(8)? == 1,
+        decrypted,
+    })
 }
 
 #[cfg(test)]
@@ -634,4 +946,51 @@ mod tests {
         let list = storage.list_identities().unwrap();
         assert_eq!(list.len(), 1);
     }
+
+    #[test]
+    fn test_handle_cache_listing_and_removal() {
+        let dir = tempdir().unwrap();
+        let storage = StorageManager::new(&dir.path().join("test.db"), false).unwrap();
+
+        for handle in ["bob", "alice"] {
+            let resolved = ResolvedHandle {
+                handle: handle.to_string(),
+                public_key: "ab".repeat(32),
+                encryption_key: None,
+                trust_score: 50.0,
+                breadcrumb_count: 120,
+                from_cache: false,
+                resolved_at: chrono::Utc::now().to_rfc3339(),
+                previous_keys: Vec::new(),
+            };
+            storage.cache_handle(handle, &resolved).unwrap();
+        }
+
+        assert_eq!(storage.list_cached_handles().unwrap(), vec!["alice", "bob"]);
+
+        assert!(storage.remove_cached_handle("alice").unwrap());
+        assert!(!storage.remove_cached_handle("alice").unwrap());
+        assert!(storage.get_cached_handle("alice", 3600).unwrap().is_none());
+        assert_eq!(storage.list_cached_handles().unwrap(), vec!["bob"]);
+    }
+
+    #[test]
+    fn test_audit_log_lists_newest_first() {
+        let dir = tempdir().unwrap();
+        let storage = StorageManager::new(&dir.path().join("test.db"), false).unwrap();
+
+        storage
+            .record_audit("sign_typed_data", Some("app.example: Log in"), "ok")
+            .unwrap();
+        storage
+            .record_audit("sign_typed_data", None, "Identity not found: abc")
+            .unwrap();
+
+        let log = storage.get_audit_log(10).unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].result, "Identity not found: abc");
+        assert_eq!(log[0].target, None);
+        assert_eq!(log[1].target.as_deref(), Some("app.example: Log in"));
+        assert_eq!(storage.get_audit_log(1).unwrap().len(), 1);
+    }
 }
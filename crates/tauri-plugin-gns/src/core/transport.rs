@@ -0,0 +1,218 @@
+//! Relay Transport
+//!
+//! The HTTP calls `NetworkClient` makes, behind a trait so they can be
+//! answered by something other than a relay. [`HttpTransport`] sends them
+//! with reqwest; [`MockTransport`] answers from a closure and records each
+//! request, so send/resolve/claim flows can be tested offline.
+
+use crate::error::{Error, Result};
+use async_trait::async_trait;
+use reqwest::{Client, Method, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A request to a relay
+#[derive(Debug, Clone)]
+pub struct TransportRequest {
+    pub method: Method,
+    pub url: String,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<serde_json::Value>,
+    /// Overrides the transport's default timeout
+    pub timeout: Option<Duration>,
+}
+
+impl TransportRequest {
+    pub fn new(method: Method, url: impl Into<String>) -> Self {
+        Self {
+            method,
+            url: url.into(),
+            query: Vec::new(),
+            headers: Vec::new(),
+            body: None,
+            timeout: None,
+        }
+    }
+
+    pub fn get(url: impl Into<String>) -> Self {
+        Self::new(Method::GET, url)
+    }
+
+    /// A POST with `body` as JSON
+    pub fn post(url: impl Into<String>, body: &impl Serialize) -> Result<Self> {
+        let mut request = Self::new(Method::POST, url);
+        request.body = Some(serde_json::to_value(body)?);
+        Ok(request)
+    }
+
+    /// A PUT with `body` as JSON
+    pub fn put(url: impl Into<String>, body: &impl Serialize) -> Result<Self> {
+        let mut request = Self::new(Method::PUT, url);
+        request.body = Some(serde_json::to_value(body)?);
+        Ok(request)
+    }
+
+    pub fn query(mut self, key: &str, value: &str) -> Self {
+        self.query.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// The URL's path, e.g. `/api/messages`
+    pub fn path(&self) -> String {
+        url::Url::parse(&self.url)
+            .map(|url| url.path().to_string())
+            .unwrap_or_default()
+    }
+
+    /// The value of query parameter `key`, whether passed with
+    /// [`TransportRequest::query`] or written into the URL
+    pub fn query_param(&self, key: &str) -> Option<String> {
+        let in_url = url::Url::parse(&self.url).ok().and_then(|url| {
+            url.query_pairs()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.into_owned())
+        });
+        in_url.or_else(|| {
+            self.query
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.clone())
+        })
+    }
+
+    /// The JSON body read as `T`
+    pub fn read_json<T: DeserializeOwned>(&self) -> Result<T> {
+        let body = self
+            .body
+            .clone()
+            .ok_or_else(|| Error::InvalidInput("Request has no body".to_string()))?;
+        Ok(serde_json::from_value(body)?)
+    }
+}
+
+/// A relay's answer
+#[derive(Debug, Clone)]
+pub struct TransportResponse {
+    pub status: StatusCode,
+    pub body: Vec<u8>,
+}
+
+impl TransportResponse {
+    pub fn new(status: StatusCode, body: impl Into<Vec<u8>>) -> Self {
+        Self { status, body: body.into() }
+    }
+
+    /// A response with `body` as JSON
+    pub fn json(status: StatusCode, body: &serde_json::Value) -> Self {
+        Self::new(status, body.to_string())
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// The body read as `T`
+    pub fn read_json<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
+/// Carries `NetworkClient`'s requests to a relay
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Send `request`. Any HTTP answer, error statuses included, is a
+    /// response; only failing to get one is an error.
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse>;
+}
+
+/// The real transport, over HTTP
+pub struct HttpTransport {
+    client: Client,
+}
+
+impl HttpTransport {
+    /// A transport whose requests give up after `timeout` unless they set
+    /// their own
+    pub fn new(timeout: Duration) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|e| Error::Network(format!("Failed to create HTTP client: {}", e)))?;
+        Ok(Self { client })
+    }
+}
+
+#[async_trait]
+impl Transport for HttpTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        let mut builder = self.client.request(request.method, &request.url);
+        if !request.query.is_empty() {
+            builder = builder.query(&request.query);
+        }
+        for (name, value) in &request.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        if let Some(body) = &request.body {
+            builder = builder.json(body);
+        }
+        if let Some(timeout) = request.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        let response = builder.send().await?;
+        let status = response.status();
+        let body = response.bytes().await?;
+        Ok(TransportResponse::new(status, body.to_vec()))
+    }
+}
+
+type Handler = dyn Fn(&TransportRequest) -> Result<TransportResponse> + Send + Sync;
+
+/// An in-memory relay for tests: every request is recorded and answered
+/// by the handler it was created with
+pub struct MockTransport {
+    handler: Box<Handler>,
+    requests: Mutex<Vec<TransportRequest>>,
+}
+
+impl MockTransport {
+    pub fn new(
+        handler: impl Fn(&TransportRequest) -> Result<TransportResponse> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            handler: Box::new(handler),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// A transport answering every request with `status` and `body`
+    pub fn always(status: StatusCode, body: serde_json::Value) -> Self {
+        Self::new(move |_| Ok(TransportResponse::json(status, &body)))
+    }
+
+    /// Requests sent so far, oldest first
+    pub fn requests(&self) -> Vec<TransportRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Transport for MockTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        self.requests.lock().unwrap().push(request.clone());
+        (self.handler)(&request)
+    }
+}
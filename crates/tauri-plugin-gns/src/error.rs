@@ -47,6 +47,10 @@ pub enum Error {
     #[error("Decryption failed: {0}")]
     DecryptionFailed(String),
 
+    /// The relay rejected a message sealed to a key the recipient has replaced
+    #[error("Recipient key mismatch: {0}")]
+    KeyMismatch(String),
+
     /// Signature verification failed
     #[error("Invalid signature: {0}")]
     InvalidSignature(String),
@@ -90,6 +94,10 @@ pub enum Error {
     /// Rate limit exceeded
     #[error("Rate limit exceeded: {0}")]
     RateLimited(String),
+
+    /// Message payload over the configured size limit
+    #[error("Message too large: {0}")]
+    MessageTooLarge(String),
 }
 
 impl Error {
@@ -127,6 +135,7 @@ impl Error {
             Error::HandleUnavailable(_) => "GNS_HANDLE_UNAVAILABLE",
             Error::InvalidHandle(_) => "GNS_INVALID_HANDLE",
             Error::DecryptionFailed(_) => "GNS_DECRYPTION_FAILED",
+            Error::KeyMismatch(_) => "GNS_KEY_MISMATCH",
             Error::InvalidSignature(_) => "GNS_INVALID_SIGNATURE",
             Error::InsufficientTrust(_) => "GNS_INSUFFICIENT_TRUST",
             Error::InsufficientBreadcrumbs(_) => "GNS_INSUFFICIENT_BREADCRUMBS",
@@ -138,6 +147,7 @@ impl Error {
             Error::Internal(_) => "GNS_INTERNAL",
             Error::NotAvailable(_) => "GNS_NOT_AVAILABLE",
             Error::RateLimited(_) => "GNS_RATE_LIMITED",
+            Error::MessageTooLarge(_) => "GNS_MESSAGE_TOO_LARGE",
         }
     }
 }
@@ -79,6 +79,7 @@
 #![doc(html_favicon_url = "https://gns.earth/favicon.ico")]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+use std::collections::HashMap;
 use std::path::Path;
 use std::sync::Arc;
 
@@ -99,34 +100,37 @@ pub mod models;
 #[cfg_attr(docsrs, doc(cfg(feature = "payments")))]
 pub mod payments;
 
-pub use config::GnsConfig;
+pub use config::{GnsConfig, SourceWeights};
 pub use error::{Error, Result};
 pub use models::*;
 
 use core::{CryptoEngine, NetworkClient, StorageManager};
+#[cfg(feature = "trajectory")]
+use core::{AutoPublishPolicy, BreadcrumbBuffer, EpochPublisher};
 
 // Re-export commonly used types
 pub use commands::identity::{
     create_identity, delete_identity, export_identity, get_identity, get_public_key,
     import_identity, list_identities, load_identity, set_default_identity, sign_message,
-    verify_signature,
+    sign_typed_data, verify_signature,
 };
 pub use commands::messaging::{
-    decrypt_message, delete_message, get_conversations, get_message, get_messages, mark_as_read,
-    send_message,
+    decrypt_message, delete_message, get_conversations, get_inbox_status, get_message,
+    get_messages, mark_as_read, send_message, upload_attachment,
 };
 pub use commands::resolver::{
-    claim_handle, get_record, is_handle_available, release_handle, resolve_handle,
-    resolve_identity, update_record,
+    claim_handle, get_record, invalidate_handle, is_handle_available, refresh_handle_cache,
+    release_handle, resolve_handle, resolve_identity, update_record, verify_signature_by_handle,
 };
-pub use commands::trust::{get_trust_details, get_trust_score, verify_identity};
+pub use commands::trust::{get_network_trust, get_trust_details, get_trust_score, verify_identity};
+pub use commands::relay::benchmark_relays;
 
 // Trajectory commands (feature-gated)
 #[cfg(feature = "trajectory")]
 #[cfg_attr(docsrs, doc(cfg(feature = "trajectory")))]
 pub use commands::trajectory::{
-    collect_breadcrumb, get_breadcrumbs, get_collection_status, get_epochs, publish_epoch,
-    start_collection, stop_collection,
+    collect_breadcrumb, flush_breadcrumbs, get_breadcrumbs, get_collection_status, get_epochs,
+    publish_epoch, start_collection, stop_collection, verify_peer_epoch,
 };
 
 /// GNS Plugin State
@@ -153,6 +157,18 @@ pub struct GnsState {
 
     /// Current active identity (public key hex)
     pub active_identity: Arc<RwLock<Option<String>>>,
+
+    /// Times each peer's encryption key was found to have changed, by
+    /// handle, so a send re-resolves a stale key at most once
+    pub peer_key_generations: Arc<RwLock<HashMap<String, u64>>>,
+
+    /// Collected breadcrumbs not yet written to storage
+    #[cfg(feature = "trajectory")]
+    pub breadcrumb_buffer: Arc<BreadcrumbBuffer>,
+
+    /// Builds and publishes epochs, on request or automatically
+    #[cfg(feature = "trajectory")]
+    pub epoch_publisher: Arc<EpochPublisher>,
 }
 
 impl GnsState {
@@ -182,7 +198,28 @@ impl GnsState {
         let storage = StorageManager::new(&db_path, config.encrypt_storage)?;
 
         // Initialize network client
-        let network = NetworkClient::new(&config.relay_urls)?;
+        let mut network = NetworkClient::new(&config.relay_urls)?
+            .with_max_message_bytes(config.max_message_bytes);
+        if config.require_pow {
+            network = network.with_pow(config.pow_difficulty, config.max_pow_difficulty);
+        }
+
+        #[cfg(feature = "trajectory")]
+        let breadcrumb_buffer = BreadcrumbBuffer::new(
+            config.breadcrumb_batch_size,
+            std::time::Duration::from_secs(config.breadcrumb_flush_interval),
+            config.breadcrumb_max_pending,
+        );
+
+        #[cfg(feature = "trajectory")]
+        let epoch_publisher = EpochPublisher::new(
+            AutoPublishPolicy {
+                every: config.auto_publish_epoch_every,
+                window: (config.auto_publish_epoch_window > 0)
+                    .then(|| std::time::Duration::from_secs(config.auto_publish_epoch_window)),
+            },
+            config.min_breadcrumbs_for_epoch,
+        );
 
         log::info!(
             "GNS state initialized: db={}, relays={}",
@@ -196,6 +233,11 @@ impl GnsState {
             network: Arc::new(network),
             config,
             active_identity: Arc::new(RwLock::new(None)),
+            peer_key_generations: Arc::new(RwLock::new(HashMap::new())),
+            #[cfg(feature = "trajectory")]
+            breadcrumb_buffer: Arc::new(breadcrumb_buffer),
+            #[cfg(feature = "trajectory")]
+            epoch_publisher: Arc::new(epoch_publisher),
         })
     }
 
@@ -208,6 +250,105 @@ impl GnsState {
     pub async fn set_active_identity(&self, public_key: Option<String>) {
         *self.active_identity.write().await = public_key;
     }
+
+    /// Pick the fastest reachable relay in the background, then keep an
+    /// eye on the active one and pick again whenever it comes back after
+    /// being unreachable.
+    fn spawn_relay_benchmark(&self) {
+        if self.config.relay_urls.len() < 2 {
+            return;
+        }
+        let network = self.network.clone();
+
+        tauri::async_runtime::spawn(async move {
+            network.benchmark_relays(true).await;
+
+            let mut ticker = tokio::time::interval(std::time::Duration::from_secs(30));
+            ticker.tick().await;
+            let mut was_online = true;
+            loop {
+                ticker.tick().await;
+                let online = network.health_check().await.unwrap_or(false);
+                if online && !was_online {
+                    log::info!("Relay connectivity restored, re-benchmarking relays");
+                    network.benchmark_relays(true).await;
+                }
+                was_online = online;
+            }
+        });
+    }
+
+    /// Periodically write buffered breadcrumbs, so a quiet collector
+    /// doesn't leave them in memory past the flush interval.
+    #[cfg(feature = "trajectory")]
+    fn spawn_breadcrumb_flusher(&self) {
+        let storage = self.storage.clone();
+        let buffer = self.breadcrumb_buffer.clone();
+
+        tauri::async_runtime::spawn(async move {
+            let period = buffer.flush_interval().max(std::time::Duration::from_secs(1));
+            let mut ticker = tokio::time::interval(period);
+            loop {
+                ticker.tick().await;
+                if buffer.is_empty() {
+                    continue;
+                }
+                if let Err(e) = buffer.flush(&*storage.read().await) {
+                    log::warn!("Periodic breadcrumb flush failed: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Check the auto-publish policy once a minute and publish an epoch
+    /// for the active identity when it's due, emitting `epoch_published`.
+    /// Does nothing unless auto-publishing is configured.
+    #[cfg(feature = "trajectory")]
+    fn spawn_epoch_publisher<R: Runtime>(&self, app: AppHandle<R>) {
+        use tauri::Emitter;
+
+        if !self.epoch_publisher.policy().is_enabled() {
+            return;
+        }
+        let storage = self.storage.clone();
+        let network = self.network.clone();
+        let buffer = self.breadcrumb_buffer.clone();
+        let publisher = self.epoch_publisher.clone();
+        let active_identity = self.active_identity.clone();
+
+        tauri::async_runtime::spawn(async move {
+            let mut ticker = tokio::time::interval(std::time::Duration::from_secs(60));
+            loop {
+                ticker.tick().await;
+                let identity = match active_identity.read().await.clone() {
+                    Some(identity) => Some(identity),
+                    None => storage.read().await.list_identities().ok().and_then(|identities| {
+                        identities
+                            .iter()
+                            .find(|i| i.is_default)
+                            .or_else(|| identities.first())
+                            .map(|i| i.public_key.clone())
+                    }),
+                };
+                let Some(identity) = identity else {
+                    continue;
+                };
+
+                match publisher
+                    .publish_if_due(&storage, &buffer, &network, &identity, chrono::Utc::now())
+                    .await
+                {
+                    Ok(Some(epoch)) => {
+                        if let Err(e) = app.emit("epoch_published", &epoch) {
+                            log::warn!("Failed to emit epoch_published: {}", e);
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => log::warn!("Automatic epoch publish failed: {}", e),
+                }
+            }
+        });
+    }
 }
 
 /// Initialize the GNS plugin with default configuration.
@@ -241,6 +382,7 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
             commands::identity::import_identity,
             commands::identity::get_public_key,
             commands::identity::sign_message,
+            commands::identity::sign_typed_data,
             commands::identity::verify_signature,
             commands::identity::set_default_identity,
             // Messaging commands
@@ -251,6 +393,8 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
             commands::messaging::mark_as_read,
             commands::messaging::delete_message,
             commands::messaging::get_conversations,
+            commands::messaging::get_inbox_status,
+            commands::messaging::upload_attachment,
             // Resolver commands
             commands::resolver::resolve_handle,
             commands::resolver::resolve_identity,
@@ -259,10 +403,16 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
             commands::resolver::get_record,
             commands::resolver::update_record,
             commands::resolver::is_handle_available,
+            commands::resolver::refresh_handle_cache,
+            commands::resolver::invalidate_handle,
+            commands::resolver::verify_signature_by_handle,
             // Trust commands
             commands::trust::get_trust_score,
             commands::trust::get_trust_details,
             commands::trust::verify_identity,
+            commands::trust::get_network_trust,
+            // Relay commands
+            commands::relay::benchmark_relays,
             // Trajectory commands (if feature enabled)
             #[cfg(feature = "trajectory")]
             commands::trajectory::start_collection,
@@ -278,6 +428,10 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
             commands::trajectory::publish_epoch,
             #[cfg(feature = "trajectory")]
             commands::trajectory::get_epochs,
+            #[cfg(feature = "trajectory")]
+            commands::trajectory::verify_peer_epoch,
+            #[cfg(feature = "trajectory")]
+            commands::trajectory::flush_breadcrumbs,
         ])
         .setup(|app, _api| {
             // Load configuration from tauri.conf.json or use defaults
@@ -304,6 +458,11 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
             let state = GnsState::new(&app_dir, config)?;
 
             // Register state with Tauri
+            state.spawn_relay_benchmark();
+            #[cfg(feature = "trajectory")]
+            state.spawn_breadcrumb_flusher();
+            #[cfg(feature = "trajectory")]
+            state.spawn_epoch_publisher(app.clone());
             app.manage(state);
 
             log::info!("🌍 GNS Plugin initialized successfully");
@@ -378,6 +537,12 @@ impl GnsBuilder {
         self
     }
 
+    /// Set the largest message payload, in bytes, that will be sent or accepted.
+    pub fn max_message_bytes(mut self, bytes: usize) -> Self {
+        self.config.max_message_bytes = bytes;
+        self
+    }
+
     /// Set the cache time-to-live in seconds.
     ///
     /// Cached handle resolutions expire after this duration.
@@ -392,6 +557,23 @@ impl GnsBuilder {
         self
     }
 
+    /// Set how much breadcrumbs from each location source count toward trust.
+    pub fn breadcrumb_source_weights(mut self, weights: SourceWeights) -> Self {
+        self.config.breadcrumb_source_weights = weights;
+        self
+    }
+
+    /// Publish epochs automatically every `every` unpublished breadcrumbs,
+    /// and/or once the oldest has waited `window_seconds`. `0` turns either
+    /// trigger off.
+    #[cfg(feature = "trajectory")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "trajectory")))]
+    pub fn auto_publish_epoch(mut self, every: u32, window_seconds: u64) -> Self {
+        self.config.auto_publish_epoch_every = every;
+        self.config.auto_publish_epoch_window = window_seconds;
+        self
+    }
+
     /// Build the plugin with the configured options.
     ///
     /// # Returns
@@ -412,6 +594,7 @@ impl GnsBuilder {
                 commands::identity::import_identity,
                 commands::identity::get_public_key,
                 commands::identity::sign_message,
+                commands::identity::sign_typed_data,
                 commands::identity::verify_signature,
                 commands::identity::set_default_identity,
                 // Messaging commands
@@ -422,6 +605,8 @@ impl GnsBuilder {
                 commands::messaging::mark_as_read,
                 commands::messaging::delete_message,
                 commands::messaging::get_conversations,
+                commands::messaging::get_inbox_status,
+                commands::messaging::upload_attachment,
                 // Resolver commands
                 commands::resolver::resolve_handle,
                 commands::resolver::resolve_identity,
@@ -430,10 +615,16 @@ impl GnsBuilder {
                 commands::resolver::get_record,
                 commands::resolver::update_record,
                 commands::resolver::is_handle_available,
+                commands::resolver::refresh_handle_cache,
+                commands::resolver::invalidate_handle,
+                commands::resolver::verify_signature_by_handle,
                 // Trust commands
                 commands::trust::get_trust_score,
                 commands::trust::get_trust_details,
                 commands::trust::verify_identity,
+                commands::trust::get_network_trust,
+                // Relay commands
+                commands::relay::benchmark_relays,
                 // Trajectory commands (feature-gated)
                 #[cfg(feature = "trajectory")]
                 commands::trajectory::start_collection,
@@ -449,6 +640,10 @@ impl GnsBuilder {
                 commands::trajectory::publish_epoch,
                 #[cfg(feature = "trajectory")]
                 commands::trajectory::get_epochs,
+                #[cfg(feature = "trajectory")]
+                commands::trajectory::verify_peer_epoch,
+                #[cfg(feature = "trajectory")]
+                commands::trajectory::flush_breadcrumbs,
             ])
             .setup(move |app, _api| {
                 let app_dir = app.path().app_data_dir().map_err(|e| {
@@ -460,6 +655,11 @@ impl GnsBuilder {
                 })?;
 
                 let state = GnsState::new(&app_dir, config.clone())?;
+                state.spawn_relay_benchmark();
+                #[cfg(feature = "trajectory")]
+                state.spawn_breadcrumb_flusher();
+                #[cfg(feature = "trajectory")]
+                state.spawn_epoch_publisher(app.clone());
                 app.manage(state);
 
                 log::info!("🌍 GNS Plugin initialized with custom config");
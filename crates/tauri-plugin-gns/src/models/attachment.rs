@@ -0,0 +1,46 @@
+//! Attachment Models
+//!
+//! Files travel outside the message envelope: they are encrypted, uploaded
+//! to the relay as a blob, and the message carries an [`AttachmentRef`]
+//! to it.
+
+use serde::{Deserialize, Serialize};
+
+/// What a message needs to fetch and decrypt an uploaded attachment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentRef {
+    /// Blob id the relay assigned
+    pub blob_id: String,
+
+    /// Attachment key (hex). Only ever sent inside an encrypted envelope.
+    pub key: String,
+
+    /// Nonce the blob was encrypted with (hex)
+    pub nonce: String,
+
+    /// Size of the encrypted blob in bytes
+    pub size: u64,
+
+    /// SHA-256 of the encrypted blob (hex)
+    pub sha256: String,
+
+    /// MIME type of the file, if known
+    #[serde(default)]
+    pub mime_type: Option<String>,
+}
+
+/// How far an attachment upload has got; the payload of the
+/// `attachment_upload_progress` event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadProgress {
+    /// Upload id the relay assigned
+    pub upload_id: String,
+
+    /// Bytes the relay has acknowledged
+    pub uploaded_bytes: u64,
+
+    /// Size of the encrypted blob
+    pub total_bytes: u64,
+}
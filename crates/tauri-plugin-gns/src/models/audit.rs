@@ -0,0 +1,22 @@
+//! Audit Models
+//!
+//! Record of sensitive actions taken with an identity's keys.
+
+use serde::{Deserialize, Serialize};
+
+/// An entry in the audit log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    /// When the action happened
+    pub timestamp: String,
+
+    /// What was done (e.g. "sign_typed_data")
+    pub action: String,
+
+    /// What the action applied to
+    pub target: Option<String>,
+
+    /// "ok", or the error that stopped the action
+    pub result: String,
+}
@@ -45,7 +45,7 @@ pub struct Breadcrumb {
 }
 
 /// Location source types
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum LocationSource {
     /// GPS/GNSS
@@ -62,6 +62,33 @@ pub enum LocationSource {
     Fused,
 }
 
+impl LocationSource {
+    /// Name used in storage and JSON
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LocationSource::Gps => "gps",
+            LocationSource::Wifi => "wifi",
+            LocationSource::Cell => "cell",
+            LocationSource::Network => "network",
+            LocationSource::Manual => "manual",
+            LocationSource::Fused => "fused",
+        }
+    }
+
+    /// Parse a stored source name. Unrecognised values are treated as
+    /// `Manual`, the least trusted source.
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "gps" => LocationSource::Gps,
+            "wifi" => LocationSource::Wifi,
+            "cell" => LocationSource::Cell,
+            "network" => LocationSource::Network,
+            "fused" => LocationSource::Fused,
+            _ => LocationSource::Manual,
+        }
+    }
+}
+
 /// A breadcrumb block (batch of breadcrumbs)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -90,7 +117,7 @@ pub struct BreadcrumbBlock {
 /// Epochs are periodic publications of trajectory proofs.
 /// They contain merkle roots of breadcrumb blocks, allowing
 /// verification without revealing raw locations.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct EpochHeader {
     /// The identity that published this epoch
@@ -135,6 +162,46 @@ pub struct SignedEpoch {
     pub signature: String,
 }
 
+/// One step of a Merkle inclusion proof, read from the leaf upwards
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MerkleProofStep {
+    /// Hash of the sibling node, or `None` if the node was the odd one
+    /// out on its level and was hashed on its own
+    pub sibling: Option<String>,
+
+    /// Whether the sibling sits to the left of the node
+    pub sibling_is_left: bool,
+}
+
+/// A relay's proof that a breadcrumb was published in an epoch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EpochProof {
+    /// The epoch header, as signed by its identity
+    pub epoch: EpochHeader,
+
+    /// Hash of the breadcrumb the proof is for
+    pub breadcrumb_hash: String,
+
+    /// Path from the breadcrumb hash up to the epoch's merkle root
+    pub path: Vec<MerkleProofStep>,
+}
+
+/// Outcome of checking a peer's claimed trajectory milestone
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerEpochVerification {
+    /// Whether every check passed
+    pub verified: bool,
+
+    /// The epoch header the relay returned
+    pub epoch: EpochHeader,
+
+    /// The first check that failed
+    pub reason: Option<String>,
+}
+
 /// Breadcrumb collection status
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -239,6 +306,45 @@ impl BreadcrumbBlock {
 
         hashes.pop().unwrap_or_else(|| "0".repeat(64))
     }
+
+    /// Inclusion proof for the breadcrumb at `index`, matching the tree
+    /// built by [`Self::calculate_merkle_root`]
+    pub fn merkle_proof(breadcrumbs: &[Breadcrumb], index: usize) -> Option<Vec<MerkleProofStep>> {
+        use sha2::{Sha256, Digest};
+
+        if index >= breadcrumbs.len() {
+            return None;
+        }
+
+        let mut hashes: Vec<String> = breadcrumbs.iter().map(|b| b.hash.clone()).collect();
+        let mut position = index;
+        let mut path = Vec::new();
+
+        while hashes.len() > 1 {
+            let sibling_is_left = position % 2 == 1;
+            let sibling = if sibling_is_left {
+                Some(hashes[position - 1].clone())
+            } else {
+                hashes.get(position + 1).cloned()
+            };
+            path.push(MerkleProofStep { sibling, sibling_is_left });
+
+            hashes = hashes
+                .chunks(2)
+                .map(|chunk| {
+                    let mut hasher = Sha256::new();
+                    hasher.update(&chunk[0]);
+                    if chunk.len() > 1 {
+                        hasher.update(&chunk[1]);
+                    }
+                    hex::encode(hasher.finalize())
+                })
+                .collect();
+            position /= 2;
+        }
+
+        Some(path)
+    }
 }
 
 #[cfg(test)]
@@ -137,6 +137,21 @@ pub struct SignatureResult {
     pub message: String,
 }
 
+/// A signature over structured data in the `Typed` domain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypedDataSignature {
+    /// The public key that signed
+    pub public_key: String,
+
+    /// The signature (128 hex characters, Ed25519)
+    pub signature: String,
+
+    /// The exact bytes signed, domain tag included (hex encoded), so a
+    /// verifier can check them without re-canonicalizing the payload
+    pub signed_bytes: String,
+}
+
 /// Verification result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -148,6 +163,35 @@ pub struct VerifyResult {
     pub public_key: String,
 }
 
+/// Result of verifying a signature against the key a handle resolves to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HandleVerifyResult {
+    /// Whether the signature is valid for the resolved key (and, if an
+    /// expected key was given, that key is the one the handle resolves to)
+    pub valid: bool,
+
+    /// The handle that was resolved
+    pub handle: String,
+
+    /// The public key that was verified against
+    pub public_key: String,
+
+    /// Whether the key came from the local handle cache
+    pub from_cache: bool,
+
+    /// The cached key did not verify and a fresh lookup returned a different one
+    pub key_rotated: bool,
+
+    /// The signature was made by one of the handle's earlier keys rather
+    /// than `public_key`
+    pub signed_by_previous_key: bool,
+
+    /// Whether the caller's expected key is the resolved key or one of its
+    /// predecessors, if one was given
+    pub matches_expected: Option<bool>,
+}
+
 impl Identity {
     /// Get a short display version of the public key
     pub fn short_key(&self) -> String {
@@ -181,6 +181,11 @@ pub struct GnsEnvelope {
     /// Ephemeral X25519 public key for this message
     pub ephemeral_key: String,
 
+    /// The recipient X25519 key the payload is sealed to, so a relay can
+    /// turn away envelopes sealed to a key the recipient has replaced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipient_key: Option<String>,
+
     /// Ed25519 signature over the entire envelope
     pub signature: String,
 
@@ -189,6 +194,79 @@ pub struct GnsEnvelope {
 
     /// Timestamp
     pub timestamp: String,
+
+    /// Proof-of-work for relays that ask for one. Not covered by the
+    /// signature; it is checked against `pow_input` instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pow: Option<PowStamp>,
+}
+
+/// Hashcash-style stamp over an envelope's ID and recipient
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PowStamp {
+    /// Leading zero bits the stamp was computed for
+    pub difficulty: u8,
+
+    /// Nonce found by `CryptoEngine::compute_pow`
+    pub nonce: u64,
+}
+
+/// Envelopes waiting on the relay for an identity
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum InboxStatus {
+    /// The relay reported its queue
+    #[serde(rename_all = "camelCase")]
+    Known {
+        /// Undelivered envelopes
+        queued: u32,
+
+        /// Timestamp of the oldest undelivered envelope
+        oldest_at: Option<String>,
+    },
+
+    /// The relay doesn't report its queue
+    Unknown,
+}
+
+/// Poly1305 tag appended to every ciphertext
+const AEAD_TAG_SIZE: usize = 16;
+
+impl GnsEnvelope {
+    /// Size in bytes of the plaintext sealed in `encrypted_payload`.
+    ///
+    /// Worked out from the encoded length, so an oversize envelope can be
+    /// turned away without decoding or decrypting it. `None` if the payload
+    /// is not `nonce:base64` as written by `send_message`.
+    pub fn payload_len(&self) -> Option<usize> {
+        let (_, ciphertext) = self.encrypted_payload.split_once(':')?;
+        if ciphertext.len() % 4 != 0 {
+            return None;
+        }
+        let padding = ciphertext.bytes().rev().take_while(|&b| b == b'=').count();
+        (ciphertext.len() / 4 * 3).checked_sub(padding)?.checked_sub(AEAD_TAG_SIZE)
+    }
+
+    /// Data a proof-of-work stamp is computed over. Binding the recipient
+    /// stops one stamp being replayed to many inboxes.
+    pub fn pow_input(&self) -> Vec<u8> {
+        format!("{}:{}", self.message_id, self.to_pk.to_lowercase()).into_bytes()
+    }
+
+    /// Data the sender's signature covers. The proof-of-work stamp and
+    /// `recipient_key` are left out.
+    pub fn signed_data(&self) -> String {
+        serde_json::json!({
+            "from_pk": self.from_pk,
+            "to_pk": self.to_pk,
+            "encrypted_payload": self.encrypted_payload,
+            "ephemeral_key": self.ephemeral_key,
+            "message_id": self.message_id,
+            "timestamp": self.timestamp,
+        })
+        .to_string()
+    }
 }
 
 impl Message {
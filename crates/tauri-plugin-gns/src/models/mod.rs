@@ -7,9 +7,15 @@ pub mod message;
 pub mod record;
 pub mod breadcrumb;
 pub mod trust;
+pub mod relay;
+pub mod attachment;
+pub mod audit;
 
 pub use identity::*;
 pub use message::*;
 pub use record::*;
 pub use breadcrumb::*;
 pub use trust::*;
+pub use relay::*;
+pub use attachment::*;
+pub use audit::*;
@@ -196,6 +196,59 @@ pub struct ResolvedHandle {
 
     /// When the resolution was performed
     pub resolved_at: String,
+
+    /// Keys this identity held before `public_key`, most recent first.
+    /// Only keys reached through validly signed rotations are listed.
+    #[serde(default)]
+    pub previous_keys: Vec<String>,
+}
+
+/// A key rotation record as published by the relay.
+///
+/// Signed by the outgoing key, so only the holder of `previous_key` can
+/// hand the identity over to `new_key`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyRotation {
+    /// The key being retired
+    pub previous_key: String,
+
+    /// The key replacing it
+    pub new_key: String,
+
+    /// When the rotation happened
+    pub rotated_at: String,
+
+    /// Signature by `previous_key` over [`KeyRotation::signing_payload`]
+    pub signature: String,
+}
+
+impl KeyRotation {
+    /// The bytes the outgoing key signs
+    pub fn signing_payload(&self) -> String {
+        format!(
+            "gns-key-rotation:{}:{}:{}",
+            self.previous_key.to_lowercase(),
+            self.new_key.to_lowercase(),
+            self.rotated_at
+        )
+    }
+}
+
+/// Outcome of re-resolving every cached handle
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HandleCacheRefresh {
+    /// Entries that were in the cache
+    pub total: u32,
+
+    /// Entries re-resolved and rewritten
+    pub updated: u32,
+
+    /// Entries dropped because the handle no longer resolves
+    pub removed: u32,
+
+    /// Entries left untouched because the lookup failed (e.g. offline)
+    pub failed: u32,
 }
 
 /// Reserved handles that cannot be claimed
@@ -266,3 +319,19 @@ mod tests {
         assert!(record.validate().is_err());
     }
 }
+
+/// Payload of `peer_key_changed`: a handle re-resolved because the relay
+/// rejected a message sealed to its cached encryption key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerKeyChanged {
+    pub handle: String,
+    pub public_key: String,
+
+    /// The key messages were being sealed to
+    pub previous_encryption_key: Option<String>,
+    pub encryption_key: Option<String>,
+
+    /// How many times this peer's key has changed since the app started
+    pub generation: u64,
+}
@@ -0,0 +1,22 @@
+//! Relay Models
+//!
+//! How the configured relays answered when they were last measured.
+
+use serde::{Deserialize, Serialize};
+
+/// One relay's result from a benchmark round
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayBenchmark {
+    /// Relay URL, as configured
+    pub url: String,
+
+    /// Whether the relay answered its health check successfully
+    pub reachable: bool,
+
+    /// Round trip of the health check in milliseconds, if it answered
+    pub latency_ms: Option<u64>,
+
+    /// Whether this is the relay the client is using
+    pub active: bool,
+}
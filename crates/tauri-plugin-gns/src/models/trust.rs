@@ -3,6 +3,7 @@
 //! Trust scoring based on Proof-of-Trajectory.
 //! Trust is earned through physical presence, not purchased.
 
+use super::LocationSource;
 use serde::{Deserialize, Serialize};
 
 /// Trust score details
@@ -27,6 +28,10 @@ pub struct TrustScore {
     /// Total breadcrumb count
     pub breadcrumb_count: u32,
 
+    /// Breadcrumbs after source weighting; what trust requirements count
+    #[serde(default)]
+    pub weighted_breadcrumb_count: u32,
+
     /// Account age in days
     pub account_age_days: u32,
 
@@ -35,6 +40,30 @@ pub struct TrustScore {
 
     /// Number of published epochs
     pub epoch_count: u32,
+
+    /// How much each location source contributed
+    #[serde(default)]
+    pub sources: Vec<SourceContribution>,
+}
+
+/// Breadcrumbs from one location source and the credit they earned
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceContribution {
+    /// Location source
+    pub source: LocationSource,
+
+    /// Breadcrumbs collected from this source
+    pub breadcrumb_count: u32,
+
+    /// Distinct H3 cells seen from this source
+    pub unique_locations: u32,
+
+    /// Configured weight (0.0-1.0)
+    pub weight: f64,
+
+    /// Breadcrumbs counted toward trust after weighting
+    pub weighted_breadcrumbs: f64,
 }
 
 /// Trust score component breakdown
@@ -237,6 +266,72 @@ impl TrustRequirements {
     }
 }
 
+/// The relay's network-wide trust figures for an identity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkTrust {
+    /// The identity these figures are for
+    pub public_key: String,
+
+    /// Trust score (0-100) as the relay computes it
+    pub trust_score: f64,
+
+    /// Breadcrumbs the relay has seen published
+    pub breadcrumb_count: u32,
+
+    /// Epochs the relay has accepted
+    pub epoch_count: u32,
+
+    /// Whether the relay considers the identity verified
+    pub verified: bool,
+
+    /// Network-wide rank, if the relay ranks identities
+    pub rank: Option<u32>,
+
+    /// When the relay was asked
+    pub fetched_at: String,
+}
+
+/// Where a [`LocalTrustEstimate`] came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LocalTrustSource {
+    /// Computed from this device's own breadcrumbs
+    Computed,
+    /// Last seen when the handle was resolved
+    HandleCache,
+}
+
+/// What this device can tell about an identity's trust on its own
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalTrustEstimate {
+    pub trust_score: f64,
+    pub breadcrumb_count: u32,
+    pub source: LocalTrustSource,
+}
+
+/// Network trust checked against the identity's signed record, next to
+/// the local estimate
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkTrustReport {
+    pub network: NetworkTrust,
+
+    /// Whether the relay's figures agree with the signed record
+    pub consistent_with_record: bool,
+
+    /// What didn't agree, if anything
+    pub discrepancies: Vec<String>,
+
+    /// The local view, if there is one
+    pub local: Option<LocalTrustEstimate>,
+
+    /// Whether the network figures came from cache
+    #[serde(default)]
+    pub from_cache: bool,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -301,3 +301,107 @@ fn test_message_ordering() {
     
     println!("✅ Message ordering preserved across {} messages", messages.len());
 }
+
+/// Test: Alice → relay → Bob through `NetworkClient`, with an in-memory
+/// relay standing in for the real one
+#[tokio::test]
+async fn test_send_through_relay_and_receive() {
+    use std::sync::{Arc, Mutex};
+    use tauri_plugin_gns::core::{MockTransport, NetworkClient, TransportResponse};
+    use tauri_plugin_gns::{DecryptedPayload, GnsEnvelope, MessageType};
+
+    let (alice_secret, alice_public) = CryptoEngine::generate_keypair().unwrap();
+    let (bob_secret, bob_public) = CryptoEngine::generate_keypair().unwrap();
+    let (bob_enc_secret, bob_enc_public) = CryptoEngine::derive_encryption_key(&bob_secret).unwrap();
+
+    // The relay knows @bob and queues envelopes by recipient
+    let queued: Arc<Mutex<Vec<serde_json::Value>>> = Arc::default();
+    let relay = {
+        let queued = queued.clone();
+        let (bob_public, bob_enc_public) = (bob_public.clone(), bob_enc_public.clone());
+        Arc::new(MockTransport::new(move |request| {
+            let ok = |data: serde_json::Value| {
+                TransportResponse::json(reqwest::StatusCode::OK, &serde_json::json!({ "success": true, "data": data }))
+            };
+            Ok(match (request.method.as_str(), request.path().as_str()) {
+                ("GET", "/api/handles/bob") => ok(serde_json::json!({
+                    "identity": { "identity": bob_public, "encryption_key": bob_enc_public }
+                })),
+                ("POST", "/api/messages") => {
+                    queued.lock().unwrap().push(request.read_json()?);
+                    ok(serde_json::Value::Null)
+                }
+                ("GET", "/api/messages") => {
+                    let to = request.query_param("to").unwrap_or_default();
+                    let inbox: Vec<_> = queued.lock().unwrap().iter().filter(|e| e["toPk"] == to).cloned().collect();
+                    ok(serde_json::Value::Array(inbox))
+                }
+                _ => TransportResponse::json(reqwest::StatusCode::NOT_FOUND, &serde_json::json!({ "success": false })),
+            })
+        }))
+    };
+    let relays = vec!["https://relay.test".to_string()];
+    let alice = NetworkClient::with_transport(&relays, relay.clone());
+    let bob = NetworkClient::with_transport(&relays, relay.clone());
+
+    // ========================================
+    // Alice resolves @bob and sends
+    // ========================================
+
+    let recipient = alice.resolve_handle("@bob").await.expect("Resolving @bob failed");
+    assert_eq!(recipient.public_key, bob_public);
+    let their_enc_public = recipient.encryption_key.expect("Bob has no encryption key");
+
+    let payload = DecryptedPayload {
+        message_type: MessageType::Text,
+        content: "Meet at the relay 📡".to_string(),
+        metadata: None,
+        reply_to: None,
+    };
+    let (ephemeral_secret, ephemeral_public) = CryptoEngine::generate_ephemeral_keypair();
+    let shared = CryptoEngine::key_exchange(&ephemeral_secret, &their_enc_public).unwrap();
+    let key = CryptoEngine::derive_message_key(&shared, b"gns-message").unwrap();
+    let (nonce, ciphertext) = CryptoEngine::encrypt(&key, &serde_json::to_vec(&payload).unwrap()).unwrap();
+
+    let mut envelope = GnsEnvelope {
+        version: 1,
+        from_pk: alice_public.clone(),
+        to_pk: recipient.public_key.clone(),
+        encrypted_payload: format!("{}:{}", nonce, ciphertext),
+        ephemeral_key: ephemeral_public,
+        recipient_key: Some(their_enc_public),
+        signature: String::new(),
+        message_id: CryptoEngine::random_id(),
+        timestamp: Utc::now().to_rfc3339(),
+        pow: None,
+    };
+    envelope.signature = CryptoEngine::sign(&alice_secret, envelope.signed_data().as_bytes()).unwrap();
+    alice.send_message(&envelope).await.expect("Sending failed");
+
+    // ========================================
+    // Bob fetches, verifies and decrypts
+    // ========================================
+
+    let inbox = bob.fetch_messages(&bob_public, None).await.unwrap();
+    assert_eq!(inbox.len(), 1);
+    let received = &inbox[0];
+    assert_eq!(received.message_id, envelope.message_id);
+    assert!(CryptoEngine::verify(&received.from_pk, received.signed_data().as_bytes(), &received.signature).unwrap());
+
+    let (nonce, ciphertext) = received.encrypted_payload.split_once(':').unwrap();
+    let shared = CryptoEngine::key_exchange(&bob_enc_secret, &received.ephemeral_key).unwrap();
+    let key = CryptoEngine::derive_message_key(&shared, b"gns-message").unwrap();
+    let opened: DecryptedPayload = serde_json::from_slice(&CryptoEngine::decrypt(&key, nonce, ciphertext).unwrap()).unwrap();
+    assert_eq!(opened.content, payload.content);
+
+    // Nothing was queued for Alice, and the relay saw only what it should
+    assert!(alice.fetch_messages(&alice_public, None).await.unwrap().is_empty());
+    let requests = relay.requests();
+    let paths: Vec<_> = requests.iter().map(|r| format!("{} {}", r.method, r.path())).collect();
+    assert_eq!(
+        paths,
+        vec!["GET /api/handles/bob", "POST /api/messages", "GET /api/messages", "GET /api/messages"]
+    );
+    assert!(requests[2].headers.contains(&("X-GNS-PublicKey".to_string(), bob_public.clone())));
+    assert!(!serde_json::to_string(&requests[1].body).unwrap().contains(&payload.content));
+}
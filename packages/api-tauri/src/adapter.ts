@@ -13,14 +13,14 @@ export const tauriAdapter: GnsApi = {
             // Map generic events to Tauri platform specific events
             const tauriEvent = event === 'email:new' ? 'new_message' : event;
 
-            let unlisten: (() => void) | undefined;
-            const promise = listen(tauriEvent, (e) => callback(e.payload));
-
-            promise.then((fn) => { unlisten = fn; });
+            const promises = [listen(tauriEvent, (e) => callback(e.payload))];
+            if (tauriEvent === 'new_message') {
+                // Messages arriving together come as one batch; hand them on one by one
+                promises.push(listen<unknown[]>('messages_batch', (e) => e.payload.forEach(payload => callback(payload))));
+            }
 
             return () => {
-                if (unlisten) unlisten();
-                else promise.then(fn => fn());
+                promises.forEach(promise => promise.then(fn => fn()));
             };
         },
         once: (event, callback) => {
@@ -24,6 +24,32 @@ export function isTauriApp(): boolean {
 
 // ==================== Types ====================
 
+/**
+ * Rejection of a network-bound command (`resolveHandle`,
+ * `checkHandleAvailable`, `getStellarBalances`, `sendGns`,
+ * `claimGnsTokens`, `claimAllBalances`, `createGnsTrustline`,
+ * `removeGnsTrustline`), which gives up with kind `timeout` once past
+ * its configured time limit
+ */
+export interface CommandError {
+    kind: 'timeout' | 'failed';
+    message: string;
+}
+
+/**
+ * Readable message of a rejected command, whether it rejected with a
+ * `CommandError` (or another `{ kind, message }` error) or a plain string
+ */
+export function commandErrorMessage(error: unknown): string {
+    if (typeof error === 'string') {
+        return error;
+    }
+    if (error && typeof error === 'object' && 'message' in error) {
+        return String((error as { message: unknown }).message);
+    }
+    return String(error);
+}
+
 export interface IdentityInfo {
     public_key: string;
     encryption_key: string;
@@ -34,6 +60,10 @@ export interface IdentityBackup {
     private_key: string;
     public_key: string;
     encryption_key: string;
+    /** Explicit X25519 secrets, once the encryption key was rotated */
+    encryption_keys?: { current: string; retired: string[] } | null;
+    /** Seed of the Stellar wallet, once the identity key was rotated away from it */
+    wallet_key?: string | null;
     breadcrumb_count: number;
     created_at: number;
 }
@@ -82,6 +112,7 @@ export interface ThreadPreview {
     is_pinned: boolean;
     is_muted: boolean;
     subject?: string;
+    last_message_id?: string;
 }
 
 export interface Reaction {
@@ -102,6 +133,7 @@ export interface Message {
     reply_to_id?: string;
     is_starred?: boolean;
     forwarded_from_id?: string;
+    is_read?: boolean;
     reply_to?: Message;
     reactions: Reaction[];
 }
@@ -111,13 +143,78 @@ export interface SendResult {
     thread_id?: string;
 }
 
+export interface ScheduleMessageRequest {
+    recipient_handle?: string;
+    recipient_public_key?: string;
+    payload_type: string;
+    payload: unknown;
+    /** Unix millis */
+    send_at: number;
+    thread_id?: string;
+    reply_to_id?: string;
+}
+
+export interface ScheduledMessage {
+    id: string;
+    recipient_handle: string | null;
+    recipient_public_key: string | null;
+    payload_type: string;
+    payload: unknown;
+    thread_id: string | null;
+    reply_to_id: string | null;
+    send_at: number;
+    created_at: number;
+    /** Why the last send attempt failed */
+    last_error: string | null;
+    /** Failed send attempts; at 5 it is no longer retried until rescheduled */
+    attempts: number;
+}
+
+/** Payload of the `scheduled_message_sent` event */
+export interface ScheduledMessageSent {
+    scheduled_id: string;
+    message_id: string;
+    thread_id: string | null;
+}
+
 export interface ConnectionStatus {
     relay_connected: boolean;
     relay_url: string;
     last_message_at?: number;
     reconnect_attempts: number;
+    /** The relay has confirmed the subscription */
+    relay_subscribed: boolean;
+}
+
+/** Payload of the `relay_ready` event */
+export interface RelayReady {
+    public_key: string;
+}
+
+/** A thread read up to `read_up_to` on another of our devices */
+export interface ReadMarker {
+    thread_id: string;
+    read_up_to: number;
+    updated_at: number;
+}
+
+/** Payload of the `read_state_synced` event */
+export type ReadStateSynced = ReadMarker[];
+
+/** Decrypted name, avatar and members of a group thread; also the payload of the `thread_meta_updated` event */
+export interface ThreadMeta {
+    thread_id: string;
+    name: string | null;
+    avatar_ref: string | null;
+    /** Member public keys */
+    members: string[];
+    /** Who made the latest change */
+    author_public_key: string;
+    updated_at: number;
 }
 
+export type ServicesStatus = 'no_identity' | 'started' | 'already_running';
+
 export interface AppVersion {
     version: string;
     build_date: string;
@@ -133,6 +230,18 @@ export interface OfflineStatus {
     last_sync?: string;
 }
 
+export type ConnectivityReason =
+    | 'relay_connected'
+    | 'relay_disconnected'
+    | 'api_reachable'
+    | 'api_unreachable';
+
+/** Payload of the `connectivity_changed` event */
+export interface ConnectivityChanged {
+    online: boolean;
+    reason: ConnectivityReason;
+}
+
 export interface Breadcrumb {
     h3_index: string;
     timestamp: number;
@@ -178,6 +287,69 @@ export async function getEncryptionKey(): Promise<string | null> {
     }
 }
 
+export interface EncryptionKeyRotation {
+    encryption_key: string;
+    /** Older keys kept so messages already sent to them still decrypt */
+    retired_keys: number;
+    /** False if the new key isn't in the published record yet */
+    published: boolean;
+    error: string | null;
+}
+
+/** Counts of a decrypted cache rebuild; also the `decrypted_cache_progress` event payload */
+export interface CacheRebuildReport {
+    /** Received messages with a stored envelope */
+    total: number;
+    processed: number;
+    /** Cached message bodies that were wrong and have been replaced */
+    rebuilt: number;
+    /** Envelopes that no longer decrypt; those messages are marked */
+    failed: number;
+    /** Envelopes addressed to another identity */
+    skipped: number;
+}
+
+/** Replace the encryption key (not the identity) and publish the new one */
+export async function rotateEncryptionKey(): Promise<EncryptionKeyRotation> {
+    if (!isTauriApp()) {
+        throw new Error('Key rotation is only available in the desktop app.');
+    }
+    return invoke<EncryptionKeyRotation>('rotate_encryption_key');
+}
+
+export interface SigningKeyRotation {
+    previous_key: string;
+    public_key: string;
+    encryption_key: string;
+    /** False if the record under the new key isn't published yet */
+    published: boolean;
+    error: string | null;
+}
+
+/** Replace the identity key itself, keeping the handle and the Stellar wallet; the old key signs over to the new one */
+export async function rotateSigningKey(): Promise<SigningKeyRotation> {
+    if (!isTauriApp()) {
+        throw new Error('Key rotation is only available in the desktop app.');
+    }
+    return invoke<SigningKeyRotation>('rotate_signing_key');
+}
+
+/** Every signing key this identity has used, oldest first, ending with the current one */
+export async function getKeyHistory(): Promise<string[]> {
+    if (!isTauriApp()) {
+        throw new Error('Key history is only available in the desktop app.');
+    }
+    return invoke<string[]>('get_key_history');
+}
+
+/** Forget cached per-peer message keys; call when the app locks */
+export async function lockMessageKeys(): Promise<void> {
+    if (!isTauriApp()) {
+        return; // Nothing cached in web
+    }
+    return invoke('lock_message_keys');
+}
+
 export async function getIdentity(): Promise<{ handle?: string; publicKey?: string } | null> {
     try {
         if (!isTauriApp()) {
@@ -229,11 +401,22 @@ export async function generateIdentity(): Promise<IdentityInfo> {
     return invoke<IdentityInfo>('generate_identity');
 }
 
-export async function importIdentity(privateKeyHex: string): Promise<IdentityInfo> {
+export async function importIdentity(privateKeyHex: string, expectedPublicKey?: string): Promise<IdentityInfo> {
+    if (!isTauriApp()) {
+        throw new Error('Cannot import identity in web browser. Use mobile app.');
+    }
+    return invoke<IdentityInfo>('import_identity', { privateKeyHex, expectedPublicKey: expectedPublicKey ?? null });
+}
+
+export interface StellarIdentityInfo extends IdentityInfo {
+    stellar_address: string;
+}
+
+export async function importStellarSecret(secretKey: string, expectedAddress?: string): Promise<StellarIdentityInfo> {
     if (!isTauriApp()) {
         throw new Error('Cannot import identity in web browser. Use mobile app.');
     }
-    return invoke<IdentityInfo>('import_identity', { privateKeyHex });
+    return invoke<StellarIdentityInfo>('import_stellar_secret', { secretKey, expectedAddress: expectedAddress ?? null });
 }
 
 export async function exportIdentityBackup(): Promise<IdentityBackup> {
@@ -243,6 +426,14 @@ export async function exportIdentityBackup(): Promise<IdentityBackup> {
     return invoke<IdentityBackup>('export_identity_backup');
 }
 
+/** Restore an identity, with its rotated encryption and wallet keys, from a backup */
+export async function importIdentityBackup(backup: IdentityBackup): Promise<IdentityInfo> {
+    if (!isTauriApp()) {
+        throw new Error('Cannot import identity in web browser. Use mobile app.');
+    }
+    return invoke<IdentityInfo>('import_identity_backup', { backup });
+}
+
 export async function deleteIdentity(): Promise<void> {
     if (!isTauriApp()) {
         // Web: clear localStorage
@@ -262,6 +453,175 @@ export async function signString(message: string): Promise<string | null> {
     return invoke<string | null>('sign_string', { message });
 }
 
+export interface TypedDataSignature {
+    public_key: string;
+    signature: string;
+    /** Hex of the exact bytes signed, `gns-typed-v1:` tag included */
+    signed_bytes: string;
+}
+
+/**
+ * X25519 encryption key an Ed25519 public key converts to. A fallback for
+ * when the peer's record is unavailable; wrong for peers who have rotated
+ * their encryption key.
+ */
+export async function ed25519PublicToX25519Public(publicKey: string): Promise<string> {
+    if (!isTauriApp()) {
+        throw new Error('Key conversion is only available in the desktop app.');
+    }
+    return invoke<string>('ed25519_public_to_x25519_public', { publicKey });
+}
+
+/**
+ * Sign structured data for `domain`. The payload is canonicalized before
+ * signing; `intentDescription` is what the user agreed to and goes in the
+ * audit log.
+ */
+export async function signTypedData(
+    domain: string,
+    intentDescription: string,
+    payload: unknown
+): Promise<TypedDataSignature> {
+    if (!isTauriApp()) {
+        throw new Error('Cannot sign in web browser. Use mobile app to approve.');
+    }
+    return invoke<TypedDataSignature>('sign_typed_data', {
+        domain,
+        intentDescription,
+        payloadJson: JSON.stringify(payload),
+    });
+}
+
+// ==================== Identity Card Commands ====================
+
+/** A signed, public-only identity card */
+export interface IdentityCard {
+    version: number;
+    publicKey: string;
+    encryptionKey: string;
+    handle: string | null;
+    displayName: string | null;
+    stellarAddress: string;
+    signature: string;
+}
+
+export interface IdentityCardInfo {
+    card: IdentityCard;
+    /** `gns-card:` deep link to render as a QR code */
+    link: string;
+}
+
+export interface Contact {
+    public_key: string;
+    handle: string | null;
+    display_name: string | null;
+    encryption_key: string;
+    stellar_address: string;
+    added_at: number;
+}
+
+export async function exportIdentityCard(): Promise<IdentityCardInfo> {
+    if (!isTauriApp()) {
+        throw new Error('Identity cards are only available in the desktop app.');
+    }
+    return invoke<IdentityCardInfo>('export_identity_card');
+}
+
+/** Verify a scanned `gns-card:` link and add its owner as a contact; its handle is kept only if it resolves to the card's key */
+export async function importIdentityCard(card: string): Promise<Contact> {
+    if (!isTauriApp()) {
+        throw new Error('Identity cards are only available in the desktop app.');
+    }
+    return invoke<Contact>('import_identity_card', { card });
+}
+
+// ==================== Audit Log Commands ====================
+
+export interface AuditEntry {
+    seq: number;
+    /** Unix seconds */
+    timestamp: number;
+    action: string;
+    target: string | null;
+    /** "ok", or the error that stopped the action */
+    result: string;
+    prev_hash: string;
+    hash: string;
+}
+
+export interface SignedAuditLog {
+    version: number;
+    public_key: string;
+    exported_at: number;
+    entries: AuditEntry[];
+    head_hash: string;
+    signature: string;
+}
+
+export interface AuditLogVerification {
+    valid: boolean;
+    public_key: string;
+    entries: number;
+    error: string | null;
+}
+
+export async function getAuditLog(limit?: number, since?: number): Promise<AuditEntry[]> {
+    if (!isTauriApp()) {
+        return [];
+    }
+    return invoke<AuditEntry[]>('get_audit_log', { limit, since });
+}
+
+export async function exportAuditLog(): Promise<SignedAuditLog> {
+    if (!isTauriApp()) {
+        throw new Error('Audit log is only available in the desktop app.');
+    }
+    return invoke<SignedAuditLog>('export_audit_log');
+}
+
+export async function verifyAuditLog(log: SignedAuditLog): Promise<AuditLogVerification> {
+    if (!isTauriApp()) {
+        throw new Error('Audit log is only available in the desktop app.');
+    }
+    return invoke<AuditLogVerification>('verify_audit_log', { log });
+}
+
+// ==================== Device Commands ====================
+
+export interface DeviceStatus {
+    device_id: string;
+    name: string;
+    /** Milliseconds since the epoch */
+    created_at: number;
+    /** Milliseconds since the epoch */
+    last_seen: number;
+    active: boolean;
+    revoked_at: number | null;
+    /** Whether this is the device making the call */
+    is_current: boolean;
+}
+
+export async function listDevices(): Promise<DeviceStatus[]> {
+    if (!isTauriApp()) {
+        return [];
+    }
+    return invoke<DeviceStatus[]>('list_devices');
+}
+
+export async function renameDevice(deviceId: string, name: string): Promise<DeviceStatus[]> {
+    if (!isTauriApp()) {
+        throw new Error('Devices can only be managed in the desktop app.');
+    }
+    return invoke<DeviceStatus[]>('rename_device', { deviceId, name });
+}
+
+export async function revokeDevice(deviceId: string): Promise<DeviceStatus[]> {
+    if (!isTauriApp()) {
+        throw new Error('Devices can only be managed in the desktop app.');
+    }
+    return invoke<DeviceStatus[]>('revoke_device', { deviceId });
+}
+
 // ==================== Handle Commands ====================
 
 export async function resolveHandle(handle: string): Promise<HandleInfo | null> {
@@ -294,6 +654,32 @@ export async function checkHandleAvailable(handle: string): Promise<HandleAvaila
     return invoke<HandleAvailability>('check_handle_available', { handle });
 }
 
+/** The first format rule a handle breaks */
+export type HandleFormatError =
+    | { kind: 'empty' }
+    | { kind: 'too_short'; detail: { min: number; got: number } }
+    | { kind: 'too_long'; detail: { max: number; got: number } }
+    | { kind: 'invalid_character'; detail: { character: string } }
+    | { kind: 'starts_with_number' }
+    | { kind: 'reserved' };
+
+export interface HandleValidation {
+    valid: boolean;
+    /** Lowercased, without `@` */
+    normalized: string;
+    reason: HandleFormatError | null;
+    message: string | null;
+}
+
+/** Check a handle's format locally, e.g. on every keystroke */
+export async function validateHandleFormat(handle: string): Promise<HandleValidation> {
+    if (!isTauriApp()) {
+        const normalized = handle.trim().toLowerCase().replace(/@/g, '');
+        return { valid: false, normalized, reason: null, message: 'Not available in web browser' };
+    }
+    return invoke<HandleValidation>('validate_handle_format', { handle });
+}
+
 export async function claimHandle(handle: string): Promise<ClaimResult> {
     if (!isTauriApp()) {
         throw new Error('Cannot claim handle from web browser. Use mobile app.');
@@ -301,6 +687,26 @@ export async function claimHandle(handle: string): Promise<ClaimResult> {
     return invoke<ClaimResult>('claim_handle', { handle });
 }
 
+export interface HandleReleaseResult {
+    success: boolean;
+    handle: string;
+    error: string | null;
+}
+
+/**
+ * Give up the reserved handle so another can be reserved. A handle that
+ * has already been claimed is only released with `confirmClaimed`.
+ */
+export async function releaseReservedHandle(
+    handle: string,
+    confirmClaimed = false
+): Promise<CommandResult<HandleReleaseResult>> {
+    if (!isTauriApp()) {
+        throw new Error('Cannot release handle from web browser. Use mobile app.');
+    }
+    return invoke<CommandResult<HandleReleaseResult>>('release_reserved_handle', { handle, confirmClaimed });
+}
+
 export async function publishIdentity(): Promise<CommandResult<boolean>> {
     if (!isTauriApp()) {
         throw new Error('Cannot publish identity from web browser. Use mobile app.');
@@ -308,6 +714,30 @@ export async function publishIdentity(): Promise<CommandResult<boolean>> {
     return invoke<CommandResult<boolean>>('publish_identity');
 }
 
+export type IdentityRecordError =
+    | { kind: 'not_an_object' }
+    | { kind: 'missing_field'; field: string }
+    | { kind: 'wrong_type'; field: string; expected: string }
+    | { kind: 'invalid_timestamp'; field: string; value: string }
+    | { kind: 'invalid_handle'; handle: string; reason: string }
+    | { kind: 'identity_mismatch'; identity: string; signer: string };
+
+export interface IdentityRecordPreview {
+    record_json: Record<string, unknown>;
+    canonical_json: string;
+    /** The exact message signed: canonical_json, after the record domain tag if tagged signatures are on */
+    signed_message: string;
+    /** Why the record would be rejected; empty if it is valid */
+    errors: IdentityRecordError[];
+}
+
+export async function previewIdentityRecord(): Promise<IdentityRecordPreview> {
+    if (!isTauriApp()) {
+        throw new Error('Cannot preview identity record from web browser. Use mobile app.');
+    }
+    return invoke<IdentityRecordPreview>('preview_identity_record');
+}
+
 // ==================== Messaging Commands ====================
 
 export async function requestMessageDecryption(
@@ -338,6 +768,44 @@ export async function sendMessage(params: {
     return invoke<SendResult>('send_message', params);
 }
 
+/** Send a message later; a `send_at` in the past sends it right away */
+export async function scheduleMessage(request: ScheduleMessageRequest): Promise<ScheduledMessage> {
+    if (!isTauriApp()) {
+        throw new Error('Scheduled messages are only available in the desktop app.');
+    }
+    return invoke<ScheduledMessage>('schedule_message', { request });
+}
+
+export async function listScheduled(): Promise<ScheduledMessage[]> {
+    if (!isTauriApp()) {
+        return [];
+    }
+    return invoke<ScheduledMessage[]>('list_scheduled');
+}
+
+/** Returns false if the message was already sent */
+export async function cancelScheduled(id: string): Promise<boolean> {
+    if (!isTauriApp()) {
+        return false;
+    }
+    return invoke<boolean>('cancel_scheduled', { id });
+}
+
+export async function reschedule(id: string, sendAt: number): Promise<ScheduledMessage> {
+    if (!isTauriApp()) {
+        throw new Error('Scheduled messages are only available in the desktop app.');
+    }
+    return invoke<ScheduledMessage>('reschedule', { id, sendAt });
+}
+
+/** Decrypt stored messages again and repair cached bodies that don't match */
+export async function rebuildDecryptedCache(identityPk: string): Promise<CacheRebuildReport> {
+    if (!isTauriApp()) {
+        throw new Error('Local message storage is only available in the desktop app.');
+    }
+    return invoke<CacheRebuildReport>('rebuild_decrypted_cache', { identityPk });
+}
+
 export async function addReaction(params: {
     messageId: string;
     emoji: string;
@@ -376,6 +844,7 @@ export async function saveSentEmailMessage(params: {
 export async function getThreads(params?: {
     includeArchived?: boolean;
     limit?: number;
+    offset?: number;
 }): Promise<ThreadPreview[]> {
     if (!isTauriApp()) {
         // Web: return empty array (no local message storage)
@@ -391,10 +860,69 @@ export async function getThread(threadId: string): Promise<ThreadPreview | null>
     return invoke<ThreadPreview | null>('get_thread', { threadId });
 }
 
+/** Canonical id of the one-to-one thread with a public key or @handle */
+export async function getThreadId(peer: string): Promise<string> {
+    if (!isTauriApp()) {
+        throw new Error('Thread ids are computed by the desktop app');
+    }
+    return invoke<string>('get_thread_id', { peer });
+}
+
+export interface TranscriptMessage {
+    id: string;
+    from_public_key: string;
+    from_handle: string | null;
+    payload_type: string;
+    /** Decrypted payload */
+    content: unknown;
+    timestamp: number;
+    /** The signed envelope, if the app kept it */
+    envelope: Record<string, unknown> | null;
+}
+
+export interface ThreadTranscript {
+    version: number;
+    thread_id: string;
+    public_key: string;
+    exported_at: number;
+    messages: TranscriptMessage[];
+    signature: string;
+}
+
+export interface TranscriptVerification {
+    valid: boolean;
+    public_key: string;
+    messages: number;
+    /** Messages whose sender signature was checked */
+    sender_verified: number;
+    error: string | null;
+}
+
+/** Export a thread as a signed transcript others can verify */
+export async function exportThreadTranscript(threadId: string): Promise<ThreadTranscript> {
+    if (!isTauriApp()) {
+        throw new Error('Transcripts are only available in the desktop app.');
+    }
+    return invoke<ThreadTranscript>('export_thread_transcript', { threadId });
+}
+
+export async function verifyTranscript(transcript: ThreadTranscript): Promise<TranscriptVerification> {
+    if (!isTauriApp()) {
+        throw new Error('Transcripts are only available in the desktop app.');
+    }
+    return invoke<TranscriptVerification>('verify_transcript', { transcript });
+}
+
+/**
+ * Messages of a thread, newest first. Pass `payloadTypes` (e.g.
+ * `['text', 'file']`) to get only those; without it control messages
+ * (reactions, receipts, typing) are left out.
+ */
 export async function getMessages(params: {
     threadId: string;
     limit?: number;
     beforeId?: string;
+    payloadTypes?: string[];
 }): Promise<Message[]> {
     if (!isTauriApp()) {
         return [];
@@ -409,6 +937,24 @@ export async function markThreadRead(threadId: string): Promise<void> {
     return invoke('mark_thread_read', { threadId });
 }
 
+/**
+ * Name a group thread; the name is encrypted so only members can read it.
+ * `members` is only needed the first time, to create the thread's metadata.
+ */
+export async function setThreadName(threadId: string, name: string, members?: string[]): Promise<ThreadMeta> {
+    if (!isTauriApp()) {
+        throw new Error('Group threads are only available in the desktop app.');
+    }
+    return invoke<ThreadMeta>('set_thread_name', { threadId, name, members: members ?? null });
+}
+
+export async function getThreadMeta(threadId: string): Promise<ThreadMeta | null> {
+    if (!isTauriApp()) {
+        return null;
+    }
+    return invoke<ThreadMeta | null>('get_thread_meta', { threadId });
+}
+
 export async function deleteThread(threadId: string): Promise<void> {
     if (!isTauriApp()) {
         return;
@@ -487,6 +1033,7 @@ export async function getConnectionStatus(): Promise<ConnectionStatus> {
         relay_url: 'https://gns-browser-production.up.railway.app',
         last_message_at: Date.now(),
         reconnect_attempts: 0,
+        relay_subscribed: true,
     });
 
     if (!isTauriApp()) {
@@ -500,6 +1047,22 @@ export async function getConnectionStatus(): Promise<ConnectionStatus> {
     }
 }
 
+/** Start the relay and other network services; call after onboarding */
+export async function initializeServices(): Promise<ServicesStatus> {
+    if (!isTauriApp()) {
+        return 'already_running'; // Nothing to start in web
+    }
+    return invoke<ServicesStatus>('initialize_services');
+}
+
+/** Connect to the relay; resolves once it has confirmed the subscription */
+export async function connectRelay(): Promise<ConnectionStatus> {
+    if (!isTauriApp()) {
+        return getConnectionStatus();
+    }
+    return invoke<ConnectionStatus>('connect_relay');
+}
+
 export async function reconnect(): Promise<void> {
     if (!isTauriApp()) {
         return; // No-op in web
@@ -541,6 +1104,146 @@ export async function getOfflineStatus(): Promise<OfflineStatus> {
     return invoke<OfflineStatus>('get_offline_status');
 }
 
+export interface DiagnosticCheck {
+    name: string;
+    passed: boolean;
+    duration_ms: number;
+    detail: string;
+}
+
+export interface DiagnosticsReport {
+    generated_at: number;
+    app_version: string;
+    platform: string;
+    passed: boolean;
+    checks: DiagnosticCheck[];
+}
+
+export async function runDiagnostics(): Promise<DiagnosticsReport> {
+    if (!isTauriApp()) {
+        throw new Error('Diagnostics are only available in the desktop app.');
+    }
+    return invoke<DiagnosticsReport>('run_diagnostics');
+}
+
+export interface CompactionReport {
+    bytes_before: number;
+    bytes_after: number;
+    bytes_reclaimed: number;
+}
+
+/** Reclaim disk space left by deleted messages */
+export async function compactStorage(): Promise<CompactionReport> {
+    if (!isTauriApp()) {
+        throw new Error('Storage compaction is only available in the desktop app.');
+    }
+    return invoke<CompactionReport>('compact_storage');
+}
+
+export interface IntegrityIssue {
+    kind: 'corruption' | 'orphaned' | 'unexpected_sender' | 'broken_breadcrumb_chain';
+    table: string;
+    rowid: number | null;
+    detail: string;
+    /** Whether repairDatabase quarantines the row */
+    repairable: boolean;
+}
+
+export interface IntegrityReport {
+    checked_at: number;
+    ok: boolean;
+    issues: IntegrityIssue[];
+}
+
+export interface RepairReport {
+    quarantined: number;
+    /** What is still wrong after repairing */
+    after: IntegrityReport;
+}
+
+/** Check the local database for corruption and inconsistent rows */
+export async function verifyDatabaseIntegrity(): Promise<IntegrityReport> {
+    if (!isTauriApp()) {
+        throw new Error('Database checks are only available in the desktop app.');
+    }
+    return invoke<IntegrityReport>('verify_database_integrity');
+}
+
+/** Quarantine inconsistent rows and rebuild the indexes */
+export async function repairDatabase(): Promise<RepairReport> {
+    if (!isTauriApp()) {
+        throw new Error('Database repair is only available in the desktop app.');
+    }
+    return invoke<RepairReport>('repair_database');
+}
+
+export interface ThreadIdMigration {
+    messages_moved: number;
+    /** Threads emptied into another one and removed */
+    threads_merged: number;
+}
+
+/** File direct messages under canonical thread ids, merging split threads */
+export async function migrateThreadIds(): Promise<ThreadIdMigration> {
+    if (!isTauriApp()) {
+        throw new Error('Thread id migration is only available in the desktop app.');
+    }
+    return invoke<ThreadIdMigration>('migrate_thread_ids');
+}
+
+/** Message counts of one thread in a debug snapshot */
+export interface ThreadCounts {
+    messages: number;
+    outgoing: number;
+    unread: number;
+}
+
+/** App state for a support ticket; public keys and counts only */
+export interface DebugSnapshot {
+    snapshot_version: number;
+    generated_at: number;
+    app_version: string;
+    platform: string;
+    schema_version: number;
+    config: Record<string, unknown>;
+    identity: { public_key: string; encryption_key: string; handle: string | null } | null;
+    threads: ThreadCounts[];
+    message_count: number;
+    pending_messages: number;
+    breadcrumb_count: number;
+    connectivity: {
+        online: boolean;
+        relay_url: string;
+        relay_connected: boolean;
+        relay_ready: boolean;
+        reconnect_attempts: number;
+        last_relay_message_at: number | null;
+    };
+    audit: { seq: number; timestamp: number; action: string; target: string | null; result: string }[];
+    recent_errors: { timestamp: number; level: string; target: string; message: string }[];
+}
+
+export interface SnapshotLoadReport {
+    threads: number;
+    messages: number;
+}
+
+/** Capture a redacted snapshot of the app's state to attach to a bug report */
+export async function captureDebugSnapshot(): Promise<DebugSnapshot> {
+    if (!isTauriApp()) {
+        throw new Error('Debug snapshots are only available in the desktop app.');
+    }
+    return invoke<DebugSnapshot>('capture_debug_snapshot');
+}
+
+/** Recreate a snapshot's threads with placeholder messages (dev builds, empty profile only) */
+export async function loadDebugSnapshot(snapshot: DebugSnapshot): Promise<SnapshotLoadReport> {
+    if (!isTauriApp()) {
+        throw new Error('Debug snapshots are only available in the desktop app.');
+    }
+    return invoke<SnapshotLoadReport>('load_debug_snapshot', { snapshot });
+}
+
 // ==================== Stellar/GNS Token Types ====================
 
 export interface ClaimableBalance {
@@ -560,11 +1263,28 @@ export interface StellarBalances {
     use_testnet: boolean;
 }
 
+/**
+ * Something odd about a send's destination. `issuer_destination` blocks
+ * the send until it is repeated with `confirmed`; `self_send` doesn't.
+ */
+export type SendWarning =
+    | { kind: 'self_send' }
+    | { kind: 'issuer_destination'; asset_code: string };
+
+/** A failed send the user can act on, from the operation's result code */
+export type SendFailure = 'no_trust' | 'underfunded' | 'no_destination' | 'line_full';
+
+/** The action to offer for a `SendFailure` */
+export type Remediation = 'create_trustline' | 'add_funds' | 'create_account_first' | 'recipient_line_full';
+
 export interface TransactionResponse {
     success: boolean;
     hash: string | null;
     error: string | null;
     message: string | null;
+    warning?: SendWarning | null;
+    failure?: SendFailure | null;
+    remediation?: Remediation | null;
 }
 
 export interface SendGnsRequest {
@@ -572,6 +1292,125 @@ export interface SendGnsRequest {
     recipient_public_key?: string;
     amount: number;
     memo?: string;
+    /** Send even if the destination looks wrong */
+    confirmed?: boolean;
+}
+
+export interface BalanceClaimResult {
+    balance_id: string;
+    amount: string;
+    success: boolean;
+    hash: string | null;
+    /** Why the balance was skipped (e.g. "Expired") or its claim failed */
+    error: string | null;
+    /** Horizon's result code for the claim, e.g. `op_cannot_claim` */
+    code: string | null;
+    /**
+     * The claim was fine but another in the same transaction failed, and
+     * transactions apply all or nothing
+     */
+    rolled_back: boolean;
+}
+
+export type GnsDelivery = 'payment' | 'claimable_balance';
+
+export interface SendToHandleResponse {
+    handle: string;
+    recipient_public_key: string;
+    recipient_stellar_address: string;
+    /** As Horizon shows the transaction; null if it couldn't confirm it */
+    delivery: GnsDelivery | null;
+    hash: string | null;
+    message: string;
+    warning: SendWarning | null;
+}
+
+/** How GNS sent to a recipient would arrive, from `canReceiveGns` */
+export interface RecipientStatus {
+    stellar_address: string;
+    account_exists: boolean;
+    has_trustline: boolean;
+    will_use_claimable: boolean;
+}
+
+/** Network fee of a transaction, from `estimateSendFee` */
+export interface FeeEstimate {
+    operation_count: number;
+    base_fee_stroops: number;
+    /** What the transaction will be charged */
+    fee_stroops: number;
+    fee_xlm: string;
+    /** Median fee recently charged for as many operations */
+    recommended_fee_stroops: number;
+    recommended_fee_xlm: string;
+    /** 90th percentile fee, what busy periods cost */
+    congested_fee_stroops: number;
+    congested_fee_xlm: string;
+    /** False when the network's fee stats were unavailable */
+    from_network: boolean;
+}
+
+/** Whether an account can afford new ledger entries, from `checkReserveHeadroom` */
+export interface ReserveHeadroom {
+    stellar_address: string;
+    added_subentries: number;
+    xlm_balance: number;
+    minimum_balance: number;
+    new_minimum_balance: number;
+    available_xlm: number;
+    required_xlm: number;
+    affordable: boolean;
+    shortfall_xlm: number;
+    /** Shortfall rounded up to a tenth of an XLM */
+    xlm_to_add: number;
+}
+
+/** Rejection value of `sendGnsToHandle` and `canReceiveGns` */
+export interface SendToHandleError {
+    kind: 'no_identity' | 'handle_not_resolved' | 'lookup_failed' | 'send_failed' | 'stellar_lookup_failed' | 'needs_confirmation';
+    message: string;
+    failure?: SendFailure | null;
+    remediation?: Remediation | null;
+}
+
+export type OnboardingStep = 'trustline' | 'confirmation' | 'claim';
+
+/** Payload of the `wallet_onboarding_progress` event */
+export interface OnboardingProgress {
+    step: OnboardingStep;
+    status: 'started' | 'skipped' | 'done';
+    message: string | null;
+}
+
+export interface OnboardingResult {
+    trustline_created: boolean;
+    claims: BalanceClaimResult[];
+    gns_balance: number;
+}
+
+/** Rejection value of `onboardWallet` */
+export interface OnboardingError {
+    kind: 'no_identity' | 'account_not_funded' | 'insufficient_xlm' | 'trustline_failed' | 'not_confirmed' | 'timeout' | 'cancelled' | 'stellar';
+    message: string;
+}
+
+/** A Stellar credit asset the wallet works with */
+export interface StellarAsset {
+    code: string;
+    issuer: string;
+}
+
+/** The GNS asset checked against its issuer's stellar.toml */
+export interface AssetVerification {
+    code: string;
+    issuer: string;
+    home_domain: string | null;
+    verified: boolean;
+    /** Why the asset isn't verified */
+    reason: string | null;
+    name: string | null;
+    image: string | null;
+    description: string | null;
 }
 
 export interface PaymentHistoryItem {
@@ -586,6 +1425,39 @@ export interface PaymentHistoryItem {
     memo: string | null;
 }
 
+export type ClaimStatus = 'pending' | 'claimed' | 'expired';
+
+export interface ClaimableBalanceRecord {
+    balance_id: string;
+    asset_code: string;
+    asset_issuer: string | null;
+    amount: string;
+    status: ClaimStatus;
+    created_at: string;
+    claimed_at: string | null;
+}
+
+export interface ClaimableBalanceHistory {
+    records: ClaimableBalanceRecord[];
+    next_cursor: string | null;
+}
+
+export type OutgoingBalanceStatus = 'pending' | 'claimable_by_sponsor';
+
+/** A claimable balance this wallet created for someone else */
+export interface OutgoingClaimableBalance {
+    balance_id: string;
+    asset_code: string;
+    asset_issuer: string | null;
+    amount: string;
+    claimant: string | null;
+    /** The claimant's predicate, i.e. when its claim expires */
+    predicate: unknown | null;
+    /** When the sponsor may reclaim it; null if it never can */
+    sponsor_predicate: unknown | null;
+    status: OutgoingBalanceStatus;
+}
+
 // ==================== Stellar Commands ====================
 
 export async function getStellarAddress(): Promise<string> {
@@ -611,25 +1483,134 @@ export async function getStellarBalances(): Promise<StellarBalances> {
     return invoke<StellarBalances>('get_stellar_balances');
 }
 
-export async function claimGnsTokens(): Promise<TransactionResponse> {
+/** Pass an `operationId` to be able to `cancelOperation` the claim */
+export async function claimGnsTokens(operationId?: string): Promise<TransactionResponse> {
+    if (!isTauriApp()) {
+        return { success: false, hash: null, error: 'Not available in web browser', message: null };
+    }
+    return invoke<TransactionResponse>('claim_gns_tokens', { operationId });
+}
+
+/** Pass an `operationId` to be able to `cancelOperation` the claims */
+export async function claimAllBalances(operationId?: string): Promise<BalanceClaimResult[]> {
+    if (!isTauriApp()) {
+        return [];
+    }
+    return invoke<BalanceClaimResult[]>('claim_all_balances', { operationId });
+}
+
+/**
+ * Add the GNS trustline if missing and claim pending GNS. Safe to retry;
+ * listen to `wallet_onboarding_progress` for per-step progress.
+ */
+export async function onboardWallet(operationId?: string): Promise<OnboardingResult> {
+    if (!isTauriApp()) {
+        throw { kind: 'stellar', message: 'Not available in web browser' } as OnboardingError;
+    }
+    return invoke<OnboardingResult>('onboard_wallet', { operationId });
+}
+
+/** Pass an `operationId` to be able to `cancelOperation` the change */
+export async function createGnsTrustline(operationId?: string): Promise<TransactionResponse> {
     if (!isTauriApp()) {
         return { success: false, hash: null, error: 'Not available in web browser', message: null };
     }
-    return invoke<TransactionResponse>('claim_gns_tokens');
+    return invoke<TransactionResponse>('create_gns_trustline', { operationId });
 }
 
-export async function createGnsTrustline(): Promise<TransactionResponse> {
+/** Pass an `operationId` to be able to `cancelOperation` the removal */
+export async function removeGnsTrustline(operationId?: string): Promise<TransactionResponse> {
     if (!isTauriApp()) {
         return { success: false, hash: null, error: 'Not available in web browser', message: null };
     }
-    return invoke<TransactionResponse>('create_gns_trustline');
+    return invoke<TransactionResponse>('remove_gns_trustline', { operationId });
 }
 
-export async function sendGns(request: SendGnsRequest): Promise<TransactionResponse> {
+/** Pass an `operationId` to be able to `cancelOperation` the send */
+export async function sendGns(request: SendGnsRequest, operationId?: string): Promise<TransactionResponse> {
     if (!isTauriApp()) {
         return { success: false, hash: null, error: 'Not available in web browser', message: null };
     }
-    return invoke<TransactionResponse>('send_gns', { request });
+    // A send past its time limit is only stopped before submission; one
+    // already submitted resolves with its real outcome
+    return invoke<TransactionResponse>('send_gns', { request, operationId });
+}
+
+/**
+ * Cancel a send, claim, trustline change or onboarding started with
+ * `operationId`. It only stops before submitting; resolves false if
+ * nothing was cancelled.
+ */
+export async function cancelOperation(operationId: string): Promise<boolean> {
+    if (!isTauriApp()) {
+        return false;
+    }
+    return invoke<boolean>('cancel_operation', { operationId });
+}
+
+export async function sendGnsToHandle(handle: string, amount: number, confirmed = false): Promise<SendToHandleResponse> {
+    if (!isTauriApp()) {
+        throw { kind: 'send_failed', message: 'Not available in web browser' } as SendToHandleError;
+    }
+    return invoke<SendToHandleResponse>('send_gns_to_handle', { handle, amount, confirmed });
+}
+
+/** Check an @handle or public key before sending it GNS */
+export async function canReceiveGns(recipient: string): Promise<RecipientStatus> {
+    if (!isTauriApp()) {
+        throw { kind: 'stellar_lookup_failed', message: 'Not available in web browser' } as SendToHandleError;
+    }
+    return invoke<RecipientStatus>('can_receive_gns', { recipient });
+}
+
+/**
+ * Check the account (default: ours) can cover the reserve for
+ * `addedSubentries` new entries, e.g. 1 for a trustline
+ */
+export async function checkReserveHeadroom(addedSubentries = 1, gnsKey?: string): Promise<ReserveHeadroom> {
+    if (!isTauriApp()) {
+        throw new Error('Not available in web browser');
+    }
+    return invoke<ReserveHeadroom>('check_reserve_headroom', { gnsKey, addedSubentries });
+}
+
+export async function estimateSendFee(operationCount = 1): Promise<FeeEstimate> {
+    if (!isTauriApp()) {
+        throw new Error('Not available in web browser');
+    }
+    return invoke<FeeEstimate>('estimate_send_fee', { operationCount });
+}
+
+export async function sendAsset(
+    asset: StellarAsset,
+    recipientAddress: string,
+    amount: number,
+    confirmed = false
+): Promise<TransactionResponse> {
+    if (!isTauriApp()) {
+        return { success: false, hash: null, error: 'Not available in web browser', message: null };
+    }
+    return invoke<TransactionResponse>('send_asset', {
+        assetCode: asset.code,
+        assetIssuer: asset.issuer,
+        recipientAddress,
+        amount,
+        confirmed,
+    });
+}
+
+export async function verifyGnsAssetToml(): Promise<AssetVerification> {
+    if (!isTauriApp()) {
+        throw new Error('Not available in web browser');
+    }
+    return invoke<AssetVerification>('verify_gns_asset_toml');
+}
+
+export async function listKnownAssets(): Promise<StellarAsset[]> {
+    if (!isTauriApp()) {
+        return [];
+    }
+    return invoke<StellarAsset[]>('list_known_assets');
 }
 
 export async function fundTestnetAccount(): Promise<TransactionResponse> {
@@ -646,6 +1627,29 @@ export async function getPaymentHistory(limit?: number): Promise<PaymentHistoryI
     return invoke<PaymentHistoryItem[]>('get_payment_history', { limit });
 }
 
+export async function getClaimableBalanceHistory(cursor?: string, limit?: number): Promise<ClaimableBalanceHistory> {
+    if (!isTauriApp()) {
+        return { records: [], next_cursor: null };
+    }
+    return invoke<ClaimableBalanceHistory>('get_claimable_balance_history', { cursor: cursor ?? null, limit });
+}
+
+/** Unclaimed balances created by `gnsKey` (default: this wallet) for others */
+export async function getOutgoingClaimableBalances(gnsKey?: string): Promise<OutgoingClaimableBalance[]> {
+    if (!isTauriApp()) {
+        return [];
+    }
+    return invoke<OutgoingClaimableBalance[]>('get_outgoing_claimable_balances', { gnsKey: gnsKey ?? null });
+}
+
+/** Take back a sponsored balance whose status is `claimable_by_sponsor` */
+export async function reclaimExpiredBalance(balanceId: string): Promise<TransactionResponse> {
+    if (!isTauriApp()) {
+        return { success: false, hash: null, error: 'Not available in web browser', message: null };
+    }
+    return invoke<TransactionResponse>('reclaim_expired_balance', { balanceId });
+}
+
 // ==================== React Hooks ====================
 
 /**
@@ -744,6 +1748,42 @@ export function useConnectionStatus() {
     return status;
 }
 
+/**
+ * Hook for online/offline state, updated from `connectivity_changed` events
+ */
+export function useOnlineStatus() {
+    const [online, setOnline] = useState<boolean | null>(null);
+
+    useEffect(() => {
+        getOfflineStatus()
+            .then((s) => setOnline(s.is_online))
+            .catch((e) => console.error('Failed to get offline status:', e));
+
+        if (!isTauriApp()) {
+            const update = () => setOnline(navigator.onLine);
+            window.addEventListener('online', update);
+            window.addEventListener('offline', update);
+            return () => {
+                window.removeEventListener('online', update);
+                window.removeEventListener('offline', update);
+            };
+        }
+
+        let unlisten: UnlistenFn | undefined;
+        listen<ConnectivityChanged>('connectivity_changed', (event) => {
+            setOnline(event.payload.online);
+        }).then((fn) => {
+            unlisten = fn;
+        });
+
+        return () => {
+            unlisten?.();
+        };
+    }, []);
+
+    return online;
+}
+
 /**
  * Hook for listening to Tauri events
  */